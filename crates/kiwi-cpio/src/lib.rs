@@ -0,0 +1,172 @@
+//! A minimal reader for cpio archives in the "new ASCII" format (the format
+//! produced by `cpio -H newc` and used by Linux for its initramfs). This is
+//! the format of the initial ramdisk given to the kernel by the bootloader,
+//! and is shared between the kernel, which loads the init program from the
+//! archive, and user space, which spawns the other programs from it.
+//!
+//! Each entry of the archive is a 110-byte header made of a magic number and
+//! thirteen 8-digit hexadecimal fields, followed by the NUL-terminated name
+//! of the entry and then its data. Both the name and the data are padded to a
+//! multiple of 4 bytes. The archive ends with an entry named `TRAILER!!!`.
+#![no_std]
+
+/// The size of the header of an entry, in bytes.
+const HEADER_SIZE: usize = 110;
+
+/// The magic number of an entry without checksum.
+const MAGIC: &[u8; 6] = b"070701";
+
+/// The magic number of an entry with a checksum of its data. The checksum is
+/// ignored by this reader.
+const MAGIC_CRC: &[u8; 6] = b"070702";
+
+/// The name of the last entry of an archive.
+const TRAILER: &str = "TRAILER!!!";
+
+/// The mask of the file type in the mode of an entry.
+const MODE_TYPE_MASK: u32 = 0o170_000;
+
+/// The file type of regular files.
+const MODE_REGULAR: u32 = 0o100_000;
+
+/// The execute permission bits of the mode of an entry.
+const MODE_EXECUTABLE: u32 = 0o111;
+
+/// A validated cpio archive.
+#[derive(Debug, Clone, Copy)]
+pub struct Archive<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Archive<'a> {
+    /// Parses and validates the given archive. Returns `None` if an entry is
+    /// malformed or if the archive does not end with a trailer entry.
+    #[must_use]
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        let mut offset = 0;
+        loop {
+            let (entry, next) = Entry::parse(data, offset)?;
+            if entry.name == TRAILER {
+                return Some(Self { data });
+            }
+            offset = next;
+        }
+    }
+
+    /// Returns the raw bytes of the archive.
+    #[must_use]
+    pub const fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Returns an iterator over the entries of the archive, excluding the
+    /// trailer.
+    #[must_use]
+    pub const fn entries(&self) -> Entries<'a> {
+        Entries {
+            data: self.data,
+            offset: 0,
+        }
+    }
+
+    /// Returns the entry with the given name, or `None` if there is no such
+    /// entry. A leading `/` or `./` in the name is ignored.
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<Entry<'a>> {
+        let name = normalize(name);
+        self.entries().find(|entry| entry.name == name)
+    }
+}
+
+/// An iterator over the entries of an [`Archive`].
+#[derive(Debug, Clone)]
+pub struct Entries<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Entry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (entry, next) = Entry::parse(self.data, self.offset)?;
+        if entry.name == TRAILER {
+            return None;
+        }
+        self.offset = next;
+        Some(entry)
+    }
+}
+
+/// An entry of an [`Archive`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry<'a> {
+    /// The path of the entry in the archive, without any leading `/` or `./`.
+    pub name: &'a str,
+
+    /// The mode of the entry, containing its type and its permissions.
+    pub mode: u32,
+
+    /// The content of the entry. It is empty for directories.
+    pub data: &'a [u8],
+}
+
+impl<'a> Entry<'a> {
+    /// Returns whether the entry is a regular file.
+    #[must_use]
+    pub const fn is_file(&self) -> bool {
+        self.mode & MODE_TYPE_MASK == MODE_REGULAR
+    }
+
+    /// Returns whether the entry is a regular file that can be executed.
+    #[must_use]
+    pub const fn is_executable(&self) -> bool {
+        self.is_file() && self.mode & MODE_EXECUTABLE != 0
+    }
+
+    /// Parses the entry starting at the given offset of the archive, and
+    /// returns it along with the offset of the next entry.
+    fn parse(data: &'a [u8], offset: usize) -> Option<(Self, usize)> {
+        let header = data.get(offset..offset.checked_add(HEADER_SIZE)?)?;
+        if &header[..6] != MAGIC && &header[..6] != MAGIC_CRC {
+            return None;
+        }
+
+        let mode = field(header, 1)?;
+        let size = usize::try_from(field(header, 6)?).ok()?;
+        let name_size = usize::try_from(field(header, 11)?).ok()?;
+
+        // The name is NUL-terminated, and the terminator is included in its
+        // size.
+        let name_start = offset + HEADER_SIZE;
+        let name = data.get(name_start..name_start.checked_add(name_size)?)?;
+        let (&0, name) = name.split_last()? else {
+            return None;
+        };
+        let name = core::str::from_utf8(name).ok()?;
+
+        let data_start = (name_start + name_size).next_multiple_of(4);
+        let data_end = data_start.checked_add(size)?;
+        let content = data.get(data_start..data_end)?;
+        let entry = Self {
+            name: normalize(name),
+            mode,
+            data: content,
+        };
+        Some((entry, data_end.next_multiple_of(4)))
+    }
+}
+
+/// Parses the field with the given index of a header, encoded as 8
+/// hexadecimal digits after the magic number.
+fn field(header: &[u8], index: usize) -> Option<u32> {
+    let start = 6 + index * 8;
+    let digits = core::str::from_utf8(header.get(start..start + 8)?).ok()?;
+    u32::from_str_radix(digits, 16).ok()
+}
+
+/// Removes the leading `/` or `./` of a path.
+fn normalize(name: &str) -> &str {
+    let name = name.strip_prefix("./").unwrap_or(name);
+    name.strip_prefix('/').unwrap_or(name)
+}
@@ -0,0 +1,269 @@
+//! Feeds random register states to the decoding of syscalls and to the
+//! validation of the user pointers they carry, as the kernel does when a
+//! task traps into it, to catch panics and validation holes on the host.
+//!
+//! Run it with `cargo fuzz run syscall` from the directory of this crate.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use kcore::user::{Access, AddressSpace, Validator};
+use libfuzzer_sys::fuzz_target;
+use syscall::{
+    SyscallOp,
+    args::{self, Registers, SyscallArgs},
+    capability::{self, Capabilities},
+    table::{self, ArgKind},
+};
+
+/// The size of a page, in bytes.
+const PAGE_SIZE: usize = 4096;
+
+/// The first address after the end of the userland address space, as on
+/// riscv64 with the Sv39 paging mode.
+const USER_LIMIT: usize = 0x0000_0040_0000_0000;
+
+/// The number of pages of the window where the fake address space is mapped.
+const WINDOW_PAGES: usize = 64;
+
+/// The state of a task trapping into the kernel.
+#[derive(Debug, Arbitrary)]
+struct Input {
+    /// The syscall number.
+    id: usize,
+
+    /// The argument registers.
+    registers: Registers,
+
+    /// The capabilities of the task.
+    capabilities: u32,
+
+    /// Whether the pointer arguments are offsets from the start of the
+    /// window, so that they often point to mapped memory.
+    relative: bool,
+
+    /// The index of the first page of the window.
+    window: usize,
+
+    /// The pages of the window that are readable, one bit per page.
+    readable: u64,
+
+    /// The pages of the window that are writable, one bit per page. Writable
+    /// pages are readable too.
+    writable: u64,
+
+    /// The size of the objects pointed to by pointer arguments that are not
+    /// followed by a length.
+    size: usize,
+}
+
+/// An address space where only the pages of a window are mapped. The window
+/// may cross the end of the userland address space, so that the validation
+/// must reject the pages after it even though they are "mapped".
+struct FakeSpace {
+    start: usize,
+    readable: u64,
+    writable: u64,
+}
+
+impl AddressSpace for FakeSpace {
+    fn allows(&self, page: usize, access: Access) -> bool {
+        let Some(index) = page
+            .checked_sub(self.start)
+            .map(|offset| offset / PAGE_SIZE)
+        else {
+            return false;
+        };
+        if index >= WINDOW_PAGES {
+            return false;
+        }
+        let writable = self.writable & (1 << index) != 0;
+        match access {
+            Access::Read => writable || self.readable & (1 << index) != 0,
+            Access::Write => writable,
+        }
+    }
+}
+
+/// A straightforward implementation of the validation of user pointers, to
+/// compare the validator against. The arithmetic is done on 128 bits so that
+/// it cannot overflow.
+fn reference(space: &FakeSpace, start: usize, size: usize, access: Access) -> bool {
+    let end = start as u128 + size as u128;
+    if start >= USER_LIMIT || end > USER_LIMIT as u128 {
+        return false;
+    }
+
+    // This terminates quickly, since no page is mapped after the window.
+    let mut page = start & !(PAGE_SIZE - 1);
+    while (page as u128) < end {
+        if !space.allows(page, access) {
+            return false;
+        }
+        page += PAGE_SIZE;
+    }
+    true
+}
+
+/// Decodes the arguments of a syscall from the registers, and verifies that
+/// the decoding is stable: encoding the decoded arguments and decoding them
+/// again must give the same arguments. Returns the registers holding the
+/// decoded arguments.
+fn decode<A: SyscallArgs + PartialEq + core::fmt::Debug>(registers: &Registers) -> Registers {
+    let args = A::decode(registers);
+    let encoded = args.encode();
+    assert_eq!(
+        A::decode(&encoded),
+        args,
+        "Unstable decoding of {:?}",
+        A::OP
+    );
+    assert_eq!(encoded, args.encode());
+
+    // The syscall table must describe the syscall with the same arguments.
+    let descriptor = table::TABLE
+        .iter()
+        .find(|descriptor| descriptor.op == A::OP as usize)
+        .expect("Syscall missing from the table");
+    assert!(descriptor.args().eq(A::DESCRIPTOR.args()));
+    encoded
+}
+
+/// Decodes the arguments of the syscall with the given operation, by calling
+/// [`decode`] with the argument structure of the syscall. Returns `None` for
+/// an unknown syscall.
+macro_rules! decode {
+    ($op:expr, $registers:expr, $($name:ident),* $(,)?) => {
+        match $op {
+            $(SyscallOp::$name => Some(decode::<args::$name>($registers)),)*
+            SyscallOp::Unknown => None,
+        }
+    };
+}
+
+fuzz_target!(|input: Input| {
+    let op = SyscallOp::from(input.id);
+    _ = capability::allowed(op, Capabilities::from_bits(input.capabilities));
+
+    let space = FakeSpace {
+        start: (input.window % (USER_LIMIT / PAGE_SIZE + WINDOW_PAGES)) * PAGE_SIZE,
+        readable: input.readable,
+        writable: input.writable,
+    };
+
+    let mut registers = input.registers;
+    if input.relative {
+        for register in &mut registers {
+            *register = space
+                .start
+                .wrapping_add(*register % (WINDOW_PAGES * PAGE_SIZE * 2));
+        }
+    }
+
+    let Some(registers) = decode!(
+        op,
+        &registers,
+        Nop,
+        TaskExit,
+        TaskYield,
+        ServiceRegister,
+        ServiceUnregister,
+        ServiceConnect,
+        IpcSend,
+        IpcReceive,
+        IpcReply,
+        ServiceStatistics,
+        ServiceList,
+        TaskWait,
+        TaskUsage,
+        SystemStatistics,
+        SyscallTable,
+        MemProtect,
+        MemMap,
+        TaskSpawn,
+        ConsoleWrite,
+        ConsoleRead,
+        IrqRegister,
+        IrqWait,
+        DeviceQuery,
+        MemMapDevice,
+        MemAllocDma,
+        TaskSetPriority,
+        SyncCreate,
+        SyncSignal,
+        SyncWait,
+        SyncClear,
+        SyncDestroy,
+        FutexWait,
+        FutexWake,
+        ThreadCreate,
+        ThreadExit,
+        ThreadJoin,
+        TaskDropCaps,
+        ServiceAccept,
+        TraceRead,
+        KernelStatistics,
+        IpcTryReceive,
+        IpcForward,
+        TaskKill,
+        TaskSetLimit,
+        LogControl,
+        LogRead,
+        GetRandom,
+        TimerCreate,
+        TimerDestroy,
+        SystemPower,
+        ServiceConnectWait,
+        IpcSendOneway,
+        MemPressureSubscribe,
+        MemPressureUnsubscribe,
+        TaskSetEssential,
+        DeviceTreeQuery,
+        IrqMask,
+        IrqUnmask,
+        IrqSetAffinity,
+        ConsoleReadWait,
+        TaskTrace,
+        TaskSuspend,
+        TaskResume,
+        TaskGetRegs,
+        TaskSetRegs,
+        ServiceDisconnect,
+        DebugWrite,
+    ) else {
+        return;
+    };
+
+    // Validate each pointer argument, with the following argument as its
+    // length when it is an unsigned integer, as the syscalls taking a buffer
+    // do. The validator must agree with the reference implementation.
+    let descriptor = table::TABLE
+        .iter()
+        .find(|descriptor| descriptor.op == op as usize)
+        .unwrap();
+    let kinds: Vec<ArgKind> = descriptor.args().collect();
+    let validator = Validator::new(USER_LIMIT, PAGE_SIZE);
+    for (index, kind) in kinds.iter().enumerate() {
+        let access = match kind {
+            ArgKind::ConstPointer => Access::Read,
+            ArgKind::MutPointer => Access::Write,
+            _ => continue,
+        };
+        let size = match kinds.get(index + 1) {
+            Some(ArgKind::Unsigned) => registers[index + 1],
+            _ => input.size,
+        };
+
+        let start = registers[index];
+        let range = validator.check(&space, start, size, access);
+        assert_eq!(
+            range.is_some(),
+            reference(&space, start, size, access),
+            "{op:?}: {start:#x} ({size} bytes, {access:?})"
+        );
+        if let Some(range) = range {
+            assert_eq!(range.start, start);
+            assert_eq!(range.len(), size);
+            assert!(range.end <= USER_LIMIT);
+        }
+    }
+});
@@ -0,0 +1,420 @@
+//! The validation of the ELF executables loaded by the kernel. Everything that
+//! is read from the file is checked here before the kernel allocates anything
+//! for the new task: the headers, the loadable segments, the thread-local
+//! storage segment and the relocation table of position-independent
+//! executables. A malformed file is reported as a [`LoadError`], and never
+//! makes the kernel panic or wrap around the address space.
+use core::ops::Range;
+use elf::{
+    ElfBytes, abi,
+    endian::LittleEndian,
+    file::Class,
+    segment::{ProgramHeader, SegmentTable},
+};
+use usize_cast::{IntoIsize, IntoUsize};
+
+/// The size of an entry of the relocation table, in bytes.
+const RELA_ENTRY_SIZE: u64 = 24;
+
+/// The maximum number of loadable segments of an executable.
+pub const MAX_SEGMENTS: usize = 16;
+
+/// An error that can occur while loading an ELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    /// The ELF header or the program header table could not be parsed.
+    InvalidHeader,
+
+    /// The file is not a 64-bit executable for the architecture of the kernel,
+    /// either static or position-independent.
+    UnsupportedFile,
+
+    /// A loadable segment is malformed: it is outside of the user address
+    /// space or of the file, its file size is larger than its memory size, or
+    /// it overlaps another segment.
+    InvalidSegment,
+
+    /// The entry point is not inside a loadable segment.
+    InvalidEntry,
+
+    /// The thread-local storage segment is malformed: there is more than one,
+    /// its initialization image is not part of the data of a loadable
+    /// segment, or its alignment is not a power of two no larger than a page.
+    InvalidTls,
+
+    /// The file asks for an executable stack, which is not supported.
+    ExecutableStack,
+
+    /// The dynamic section or the relocation table is malformed, or contains
+    /// a relocation other than the relative relocation of the architecture.
+    InvalidRelocation,
+
+    /// The kernel ran out of memory while mapping a segment.
+    OutOfMemory,
+}
+
+impl From<elf::ParseError> for LoadError {
+    fn from(_: elf::ParseError) -> Self {
+        Self::InvalidHeader
+    }
+}
+
+/// The description of the executables that can run on an architecture.
+#[derive(Debug, Clone, Copy)]
+pub struct Machine {
+    /// The value of the `e_machine` field of the executables.
+    pub id: u16,
+
+    /// The type of the empty relocation of the architecture.
+    pub none: u32,
+
+    /// The type of the relative relocation of the architecture.
+    pub relative: u32,
+}
+
+/// The validator of the ELF executables for a given architecture and layout
+/// of the user address space.
+#[derive(Debug, Clone, Copy)]
+pub struct Loader {
+    /// The architecture of the executables.
+    machine: Machine,
+
+    /// The first address that the segments cannot reach, leaving room for
+    /// the stack at the top of the user address space.
+    limit: usize,
+
+    /// The size of a page, in bytes.
+    page_size: usize,
+
+    /// The address where position-independent executables are loaded.
+    pie_bias: usize,
+}
+
+/// A loadable segment, validated and relocated by the load bias.
+#[derive(Debug, Clone, Copy)]
+pub struct Segment<'a> {
+    /// The address of the first byte of the segment.
+    pub start: usize,
+
+    /// The address just after the last byte of the segment.
+    pub end: usize,
+
+    /// The data of the segment in the file. The rest of the segment, up to
+    /// its memory size, is zeroed.
+    pub data: &'a [u8],
+}
+
+/// The template of the thread-local storage of an executable, relocated by
+/// the load bias.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tls {
+    /// The address of the initialization image.
+    pub image: usize,
+
+    /// The size of the initialization image, in bytes.
+    pub file_size: usize,
+
+    /// The size of the thread-local storage of each thread, in bytes.
+    pub mem_size: usize,
+
+    /// The alignment of the thread-local storage, which is a power of two no
+    /// larger than a page.
+    pub align: usize,
+}
+
+/// A validated executable, ready to be loaded.
+#[derive(Debug)]
+pub struct Image<'a> {
+    /// The offset between the addresses in the file and the addresses where
+    /// the executable is loaded, which is zero for static executables.
+    pub bias: usize,
+
+    /// The address of the entry point, inside a loadable segment.
+    pub entry: usize,
+
+    /// The loadable segments, sorted by address and not overlapping.
+    pub segments: heapless::Vec<Segment<'a>, MAX_SEGMENTS>,
+
+    /// The template of the thread-local storage, if any.
+    pub tls: Option<Tls>,
+
+    /// The relocation table of a position-independent executable, whose
+    /// entries have the expected size. It is empty for static executables.
+    relocations: &'a [u8],
+
+    /// The architecture of the executable.
+    machine: Machine,
+}
+
+impl Segment<'_> {
+    /// Validates the given program header of the file and returns the
+    /// segment it describes, relocated by the given bias.
+    fn parse<'a>(
+        file: &'a [u8],
+        phdr: &ProgramHeader,
+        bias: usize,
+        limit: usize,
+    ) -> Result<Segment<'a>, LoadError> {
+        if phdr.p_filesz > phdr.p_memsz {
+            return Err(LoadError::InvalidSegment);
+        }
+
+        let offset = phdr.p_offset.into_usize();
+        let data = offset
+            .checked_add(phdr.p_filesz.into_usize())
+            .and_then(|end| file.get(offset..end))
+            .ok_or(LoadError::InvalidSegment)?;
+        let start = phdr
+            .p_vaddr
+            .into_usize()
+            .checked_add(bias)
+            .ok_or(LoadError::InvalidSegment)?;
+        let end = start
+            .checked_add(phdr.p_memsz.into_usize())
+            .ok_or(LoadError::InvalidSegment)?;
+
+        // The end of the segment is exclusive, and must leave room for the
+        // stack and its guard page at the top of the user address space.
+        if end > limit {
+            return Err(LoadError::InvalidSegment);
+        }
+        Ok(Segment { start, end, data })
+    }
+
+    /// Returns whether the given range of addresses is fully contained in
+    /// the data of the segment that comes from the file.
+    #[must_use]
+    pub fn contains_data(&self, start: usize, len: usize) -> bool {
+        start >= self.start
+            && start
+                .checked_add(len)
+                .is_some_and(|end| end <= self.start + self.data.len())
+    }
+
+    /// Returns the range of the segment beyond its data that must be zeroed
+    /// explicitly, when the pages of the segment are freshly mapped (and
+    /// therefore already zeroed) starting at the given address. The pages
+    /// below it are shared with the previous segment, and may hold anything.
+    #[must_use]
+    pub fn zeroed(&self, fresh: usize) -> Range<usize> {
+        let start = self.start + self.data.len();
+        start..self.end.min(fresh).max(start)
+    }
+}
+
+impl Loader {
+    /// Creates a validator for the executables of the given architecture.
+    /// Segments must end below `limit`, and position-independent executables
+    /// are loaded at `pie_bias`.
+    ///
+    /// # Panics
+    /// Panics if the page size is not a power of two.
+    #[must_use]
+    pub const fn new(machine: Machine, limit: usize, page_size: usize, pie_bias: usize) -> Self {
+        assert!(page_size.is_power_of_two());
+        Self {
+            machine,
+            limit,
+            page_size,
+            pie_bias,
+        }
+    }
+
+    /// Validates the given ELF file and returns the executable it describes.
+    ///
+    /// # Errors
+    /// Returns a [`LoadError`] describing the problem if the file is malformed
+    /// or not supported.
+    pub fn parse<'a>(&self, file: &'a [u8]) -> Result<Image<'a>, LoadError> {
+        let header = ElfBytes::<LittleEndian>::minimal_parse(file)?;
+        if header.ehdr.class != Class::ELF64 || header.ehdr.e_machine != self.machine.id {
+            return Err(LoadError::UnsupportedFile);
+        }
+
+        let bias = match header.ehdr.e_type {
+            abi::ET_EXEC => 0,
+            abi::ET_DYN => self.pie_bias,
+            _ => return Err(LoadError::UnsupportedFile),
+        };
+
+        // Loadable segments must be sorted by address and must not overlap,
+        // as required by the ELF specification.
+        let phdrs = header.segments().ok_or(LoadError::InvalidHeader)?;
+        let mut segments = heapless::Vec::<Segment, MAX_SEGMENTS>::new();
+        let mut tls = None;
+        for phdr in phdrs.iter() {
+            match phdr.p_type {
+                abi::PT_GNU_STACK if phdr.p_flags & abi::PF_X != 0 => {
+                    return Err(LoadError::ExecutableStack);
+                }
+                abi::PT_LOAD if phdr.p_memsz > 0 => {
+                    let segment = Segment::parse(file, &phdr, bias, self.limit)?;
+                    if segments.last().is_some_and(|last| last.end > segment.start) {
+                        return Err(LoadError::InvalidSegment);
+                    }
+                    segments
+                        .push(segment)
+                        .map_err(|_| LoadError::UnsupportedFile)?;
+                }
+                abi::PT_TLS if tls.is_some() => return Err(LoadError::InvalidTls),
+                abi::PT_TLS => tls = Some(phdr),
+                _ => {}
+            }
+        }
+
+        let entry = header
+            .ehdr
+            .e_entry
+            .into_usize()
+            .checked_add(bias)
+            .filter(|entry| segments.iter().any(|s| (s.start..s.end).contains(entry)))
+            .ok_or(LoadError::InvalidEntry)?;
+        let tls = tls
+            .map(|phdr| self.parse_tls(&phdr, &segments, bias))
+            .transpose()?;
+        let relocations = if header.ehdr.e_type == abi::ET_DYN {
+            relocation_table(file, phdrs, &segments, bias)?
+        } else {
+            &[]
+        };
+
+        Ok(Image {
+            bias,
+            entry,
+            segments,
+            tls,
+            relocations,
+            machine: self.machine,
+        })
+    }
+
+    /// Validates the thread-local storage segment of the file and returns the
+    /// template it describes, relocated by the given bias. The initialization
+    /// image must be part of the data of a loadable segment, since the runtime
+    /// copies it from there into the TLS block of each thread.
+    fn parse_tls(
+        &self,
+        phdr: &ProgramHeader,
+        segments: &[Segment],
+        bias: usize,
+    ) -> Result<Tls, LoadError> {
+        let align = phdr.p_align.max(1).into_usize();
+        if phdr.p_filesz > phdr.p_memsz || !align.is_power_of_two() || align > self.page_size {
+            return Err(LoadError::InvalidTls);
+        }
+
+        let image = phdr
+            .p_vaddr
+            .into_usize()
+            .checked_add(bias)
+            .ok_or(LoadError::InvalidTls)?;
+        let file_size = phdr.p_filesz.into_usize();
+        if file_size > 0 && !segments.iter().any(|s| s.contains_data(image, file_size)) {
+            return Err(LoadError::InvalidTls);
+        }
+
+        Ok(Tls {
+            image,
+            file_size,
+            mem_size: phdr.p_memsz.into_usize(),
+            align,
+        })
+    }
+}
+
+impl Image<'_> {
+    /// Returns the relocations to apply to a position-independent executable
+    /// once its segments are loaded, as the address of a 64-bit word along
+    /// with the value to write there. Each word is inside a loadable segment.
+    ///
+    /// # Errors
+    /// The iterator yields [`LoadError::InvalidRelocation`] for a relocation
+    /// that is not supported or whose target is outside of the segments. The
+    /// relocations before it may already have been applied.
+    pub fn relocations(&self) -> impl Iterator<Item = Result<(usize, usize), LoadError>> + '_ {
+        elf::relocation::RelaIterator::new(LittleEndian, Class::ELF64, self.relocations)
+            .filter(|relocation| relocation.r_type != self.machine.none)
+            .map(|relocation| {
+                if relocation.r_type != self.machine.relative {
+                    return Err(LoadError::InvalidRelocation);
+                }
+                let target = relocation
+                    .r_offset
+                    .into_usize()
+                    .checked_add(self.bias)
+                    .filter(|&target| {
+                        self.segments.iter().any(|segment| {
+                            target >= segment.start
+                                && target
+                                    .checked_add(size_of::<u64>())
+                                    .is_some_and(|end| end <= segment.end)
+                        })
+                    })
+                    .ok_or(LoadError::InvalidRelocation)?;
+                let value = self
+                    .bias
+                    .wrapping_add_signed(relocation.r_addend.into_isize());
+                Ok((target, value))
+            })
+    }
+}
+
+/// Finds the relocation table of a position-independent executable loaded
+/// with the given bias, and returns its data. Only the relative relocations
+/// are supported, which are the only ones emitted for static
+/// position-independent executables, so a file using a `DT_REL` table is
+/// rejected.
+fn relocation_table<'a>(
+    file: &'a [u8],
+    phdrs: SegmentTable<'_, LittleEndian>,
+    segments: &[Segment<'a>],
+    bias: usize,
+) -> Result<&'a [u8], LoadError> {
+    let Some(dynamic) = phdrs.iter().find(|phdr| phdr.p_type == abi::PT_DYNAMIC) else {
+        return Ok(&[]);
+    };
+
+    let start = dynamic.p_offset.into_usize();
+    let data = start
+        .checked_add(dynamic.p_filesz.into_usize())
+        .and_then(|end| file.get(start..end))
+        .ok_or(LoadError::InvalidRelocation)?;
+
+    let mut rela = None;
+    let mut rela_size = 0;
+    let mut rela_entry = RELA_ENTRY_SIZE;
+    for entry in elf::dynamic::DynamicTable::new(LittleEndian, Class::ELF64, data) {
+        match entry.d_tag {
+            abi::DT_NULL => break,
+            abi::DT_RELA => rela = Some(entry.d_ptr()),
+            abi::DT_RELASZ => rela_size = entry.d_val(),
+            abi::DT_RELAENT => rela_entry = entry.d_val(),
+            abi::DT_REL => return Err(LoadError::InvalidRelocation),
+            _ => {}
+        }
+    }
+
+    let Some(rela) = rela else {
+        return Ok(&[]);
+    };
+    if rela_entry != RELA_ENTRY_SIZE {
+        return Err(LoadError::InvalidRelocation);
+    }
+
+    // The relocation table is given by its address in memory. Find the data
+    // of the segment that contains it in the file, since the table itself may
+    // be in a read-only segment of the image.
+    let table_start = rela
+        .into_usize()
+        .checked_add(bias)
+        .ok_or(LoadError::InvalidRelocation)?;
+    let table_size = rela_size.into_usize();
+    segments
+        .iter()
+        .find(|segment| segment.contains_data(table_start, table_size))
+        .map(|segment| {
+            let offset = table_start - segment.start;
+            &segment.data[offset..offset + table_size]
+        })
+        .ok_or(LoadError::InvalidRelocation)
+}
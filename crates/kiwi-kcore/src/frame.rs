@@ -0,0 +1,273 @@
+//! The bookkeeping of the physical memory allocator of the kernel. Each
+//! physical frame of the RAM is described by a [`FrameInfo`], and the
+//! [`Bitmap`] allocates frames by searching the table for free frames.
+//!
+//! Frames are only identified by their index in the table: converting an
+//! index to a physical address, zeroing the allocated frames and caching
+//! frames per core are left to the kernel.
+//!
+//! An allocated frame may be shared by several owners, for example when it is
+//! mapped in several address spaces. Each frame therefore counts the
+//! references held on it, and is only freed when the last one is dropped.
+use bitflags::bitflags;
+
+bitflags! {
+    /// Some frame flags to indicate some specificities about the frame.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct FrameFlags: u8 {
+        /// If set, the frame is free to be allocated
+        const FREE = 1 << 0;
+
+        /// If set, the frame is used by the kernel. It cannot be set if the
+        /// `FREE` or `FIRMWARE` flags are set.
+        const KERNEL = 1 << 1;
+
+        /// If set, the frame is used by the firmware. It cannot be set if
+        /// the `FREE` or `KERNEL` flags are set.
+        const FIRMWARE = 1 << 2;
+    }
+}
+
+/// Informations about a frame.
+#[derive(Debug)]
+pub struct FrameInfo {
+    flags: FrameFlags,
+
+    /// The number of references held on the frame. It is zero for free
+    /// frames and for frames that were never allocated, such as the frames
+    /// reserved for the firmware.
+    references: u32,
+}
+
+impl FrameInfo {
+    /// The information of a frame reserved for the kernel. This is the state
+    /// of all frames before the free memory regions are known.
+    pub const RESERVED: Self = Self {
+        flags: FrameFlags::KERNEL,
+        references: 0,
+    };
+
+    /// Returns the flags of the frame.
+    #[must_use]
+    pub const fn flags(&self) -> FrameFlags {
+        self.flags
+    }
+
+    /// Returns the number of references held on the frame.
+    #[must_use]
+    pub const fn references(&self) -> u32 {
+        self.references
+    }
+}
+
+/// A bitmap allocator over a table of frames. This allocator is very slow,
+/// since it searches the whole table for free frames, but does not consume a
+/// lot of memory and is "good enought" for now.
+#[derive(Debug)]
+pub struct Bitmap<'a> {
+    frames: &'a mut [FrameInfo],
+}
+
+impl<'a> Bitmap<'a> {
+    /// Creates a bitmap allocator managing the given frames.
+    #[must_use]
+    pub const fn new(frames: &'a mut [FrameInfo]) -> Self {
+        Self { frames }
+    }
+
+    /// Returns the number of frames managed by the allocator.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns whether the allocator does not manage any frame.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Returns the flags of the frame at the given index.
+    ///
+    /// # Panics
+    /// Panics if the index is outside of the bitmap.
+    #[must_use]
+    pub fn flags(&self, index: usize) -> FrameFlags {
+        self.frames[index].flags
+    }
+
+    /// Returns the number of references held on the frame at the given index.
+    ///
+    /// # Panics
+    /// Panics if the index is outside of the bitmap.
+    #[must_use]
+    pub fn references(&self, index: usize) -> u32 {
+        self.frames[index].references
+    }
+
+    /// Marks the given range of frames as free memory. This is used while
+    /// setting up the allocator, with the free regions of the memory map.
+    ///
+    /// # Panics
+    /// Panics if the range is outside of the bitmap.
+    pub fn add_free(&mut self, range: core::ops::Range<usize>) {
+        for info in &mut self.frames[range] {
+            info.flags &= !FrameFlags::KERNEL;
+            info.flags |= FrameFlags::FREE;
+        }
+    }
+
+    /// Marks the given range of frames as used by the firmware, so that they
+    /// are never allocated.
+    ///
+    /// # Panics
+    /// Panics if the range is outside of the bitmap.
+    pub fn reserve_firmware(&mut self, range: core::ops::Range<usize>) {
+        for info in &mut self.frames[range] {
+            info.flags &= !(FrameFlags::KERNEL | FrameFlags::FREE);
+            info.flags |= FrameFlags::FIRMWARE;
+        }
+    }
+
+    /// Allocates `count` contiguous frames and returns the index of the first
+    /// one, or `None` if no such range is free. Only the ranges starting at
+    /// `first`, `first + step`, `first + 2 * step`... are considered, which
+    /// allows the caller to allocate ranges whose physical address is aligned.
+    /// The given flags are added to the allocated frames, and the caller holds
+    /// a single reference on each of them.
+    ///
+    /// If the count parameter is 0, this function returns `None`.
+    ///
+    /// # Panics
+    /// Panics if `step` is 0.
+    pub fn allocate(
+        &mut self,
+        count: usize,
+        first: usize,
+        step: usize,
+        flags: FrameFlags,
+    ) -> Option<usize> {
+        if count == 0 {
+            return None;
+        }
+
+        let last = self.frames.len().checked_sub(count)?;
+        let start = (first..=last).step_by(step).find(|&start| {
+            self.frames[start..start + count]
+                .iter()
+                .all(|info| info.flags.contains(FrameFlags::FREE))
+        })?;
+
+        for info in &mut self.frames[start..start + count] {
+            info.flags.remove(FrameFlags::FREE);
+            info.flags |= flags;
+            info.references = 1;
+        }
+        Some(start)
+    }
+
+    /// Takes up to `max` free frames from the bitmap in a single pass, and
+    /// returns their indexes. The frames are marked as used as the iterator is
+    /// consumed, but are not owned by anyone until the caller hands them out.
+    /// Each of them already holds the single reference that is handed out
+    /// along with it, so that handing it out does not touch the bitmap.
+    pub fn take_free(&mut self, max: usize) -> impl Iterator<Item = usize> + '_ {
+        self.frames
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, info)| info.flags.contains(FrameFlags::FREE))
+            .take(max)
+            .map(|(index, info)| {
+                info.flags.remove(FrameFlags::FREE);
+                info.references = 1;
+                index
+            })
+    }
+
+    /// Marks the frame at the given index, which was taken from the bitmap
+    /// without being owned by anyone, as free again.
+    ///
+    /// # Panics
+    /// Panics if the index is outside of the bitmap.
+    pub fn put_free(&mut self, index: usize) {
+        self.frames[index].flags.insert(FrameFlags::FREE);
+        self.frames[index].references = 0;
+    }
+
+    /// Takes an additional reference on the allocated frame at the given
+    /// index, so that it is not freed until this reference is dropped with
+    /// [`Self::put`].
+    ///
+    /// # Panics
+    /// Panics if the index is outside of the bitmap, if the frame is not
+    /// allocated, or if the number of references overflows.
+    pub fn get(&mut self, index: usize) {
+        let info = &mut self.frames[index];
+        assert!(info.references > 0 && !info.flags.contains(FrameFlags::FREE));
+        info.references = info
+            .references
+            .checked_add(1)
+            .expect("Too many references on a frame");
+    }
+
+    /// Drops a reference on the allocated frame at the given index. Returns
+    /// whether this was the last reference, in which case the reference is
+    /// kept and the caller must free the frame with [`Self::free`] or
+    /// [`Self::disown`].
+    ///
+    /// # Panics
+    /// Panics if the index is outside of the bitmap, or if no reference is
+    /// held on the frame (double free ?).
+    #[must_use]
+    pub fn put(&mut self, index: usize) -> bool {
+        let info = &mut self.frames[index];
+        assert!(info.references > 0 && !info.flags.contains(FrameFlags::FREE));
+        if info.references == 1 {
+            return true;
+        }
+        info.references -= 1;
+        false
+    }
+
+    /// Releases the ownership of the allocated frame at the given index
+    /// without making it free, so that it can be cached by the caller and
+    /// handed out again without going through the bitmap. The frame keeps a
+    /// single reference, which is handed out along with it.
+    ///
+    /// # Panics
+    /// Panics if the index is outside of the bitmap, if the frame is already
+    /// free (double free ?), or if it is still shared.
+    pub fn disown(&mut self, index: usize) {
+        let info = &mut self.frames[index];
+        assert!(!info.flags.contains(FrameFlags::FREE));
+        assert!(info.references <= 1, "Disowning a shared frame");
+        info.flags.remove(FrameFlags::KERNEL);
+        info.references = 1;
+    }
+
+    /// Frees the given range of frames, either allocated by [`Self::allocate`]
+    /// or reserved for the kernel while setting up the allocator. The frames
+    /// must not be shared: the references of the caller are dropped with them.
+    ///
+    /// # Panics
+    /// Panics if the range is outside of the bitmap, or if one of its frames
+    /// is already free or still shared.
+    pub fn free(&mut self, range: core::ops::Range<usize>) {
+        for info in &mut self.frames[range] {
+            assert!(!info.flags.contains(FrameFlags::FREE));
+            assert!(info.references <= 1, "Freeing a shared frame");
+            info.flags.remove(FrameFlags::KERNEL);
+            info.flags.insert(FrameFlags::FREE);
+            info.references = 0;
+        }
+    }
+
+    /// Returns the number of frames that have all the given flags.
+    #[must_use]
+    pub fn count(&self, flags: FrameFlags) -> usize {
+        self.frames
+            .iter()
+            .filter(|info| info.flags.contains(flags))
+            .count()
+    }
+}
@@ -0,0 +1,192 @@
+//! The IPC endpoint of a task: its mailbox of incoming messages, the slot where
+//! the reply to its last message is delivered, what it is waiting for, and the
+//! senders it must still reply to.
+//!
+//! The [`Endpoint`] only tracks the state of the exchanges. The kernel keeps it
+//! behind a lock in the local data of each task, and is responsible for
+//! allocating the messages, for waking up the tasks waiting on the endpoint
+//! and for checking that the tasks involved still exist.
+use alloc::{collections::VecDeque, vec::Vec};
+
+/// A message exchanged between tasks, as seen by the endpoint.
+pub trait Envelope {
+    /// The identifier of a task.
+    type Id: Copy + Eq;
+
+    /// Returns the task that sent the message.
+    fn sender(&self) -> Self::Id;
+
+    /// Returns whether the sender does not wait for a reply to the message.
+    fn oneway(&self) -> bool;
+}
+
+/// What the task owning an endpoint is waiting for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State<Id> {
+    /// The task does not wait for anything.
+    Idle,
+
+    /// The task is waiting for room in the mailbox of another task to send
+    /// it a message.
+    WaitingForSend,
+
+    /// The task is waiting to receive a message.
+    WaitingForMessage,
+
+    /// The task is waiting for a reply from the given task.
+    WaitingForReply(Id),
+}
+
+/// The reasons why a reply can be refused by an endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyError {
+    /// The task is not waiting for a reply, or its reply was already
+    /// delivered.
+    NotWaitingForReply,
+
+    /// The task is waiting for a reply from another task.
+    UnexpectedSender,
+}
+
+/// The IPC state of a task.
+#[derive(Debug)]
+pub struct Endpoint<M: Envelope> {
+    /// The incoming messages, from the oldest to the most recent.
+    mailbox: VecDeque<M>,
+
+    /// The reply to the last message sent, until the task takes it.
+    reply: Option<M>,
+
+    /// What the task is waiting for.
+    state: State<M::Id>,
+
+    /// The senders of the messages received and not yet replied to.
+    in_flight: Vec<M::Id>,
+}
+
+impl<M: Envelope> Endpoint<M> {
+    /// Creates an idle endpoint with an empty mailbox.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            mailbox: VecDeque::new(),
+            reply: None,
+            state: State::Idle,
+            in_flight: Vec::new(),
+        }
+    }
+
+    /// Returns what the task is waiting for.
+    #[must_use]
+    pub const fn state(&self) -> State<M::Id> {
+        self.state
+    }
+
+    /// Records that the task waits for room in the mailbox of another task.
+    pub fn wait_for_send(&mut self) {
+        self.state = State::WaitingForSend;
+    }
+
+    /// Records that the task waits for a message to arrive in its mailbox.
+    pub fn wait_for_message(&mut self) {
+        self.state = State::WaitingForMessage;
+    }
+
+    /// Records that the task waits for a reply from the given task. This is
+    /// also used when the message is forwarded, to wait for the reply of the
+    /// task it was forwarded to instead.
+    pub fn wait_for_reply(&mut self, from: M::Id) {
+        self.state = State::WaitingForReply(from);
+    }
+
+    /// Returns the task from which a reply is expected, if any.
+    #[must_use]
+    pub fn replier(&self) -> Option<M::Id> {
+        match self.state {
+            State::WaitingForReply(from) => Some(from),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the mailbox holds at least the given number of
+    /// messages, in which case no message can be delivered.
+    #[must_use]
+    pub fn is_full(&self, capacity: usize) -> bool {
+        self.mailbox.len() >= capacity
+    }
+
+    /// Puts the message at the end of the mailbox, unless the mailbox already
+    /// holds `capacity` messages.
+    ///
+    /// # Errors
+    /// Returns the message back if the mailbox is full.
+    pub fn deliver(&mut self, message: M, capacity: usize) -> Result<(), M> {
+        if self.is_full(capacity) {
+            return Err(message);
+        }
+        self.mailbox.push_back(message);
+        Ok(())
+    }
+
+    /// Takes the oldest message from the mailbox. Unless the sender does not
+    /// wait for a reply, the task must now reply to it.
+    pub fn receive(&mut self) -> Option<M> {
+        let message = self.mailbox.pop_front()?;
+        if !message.oneway() {
+            self.in_flight.push(message.sender());
+        }
+        if self.state == State::WaitingForMessage {
+            self.state = State::Idle;
+        }
+        Some(message)
+    }
+
+    /// Delivers the reply to the message sent by the task. Once accepted, the
+    /// task no longer waits for a reply, so a second reply is refused.
+    ///
+    /// # Errors
+    /// Returns a [`ReplyError`] if the task is not waiting for a reply from
+    /// the sender of the reply. The reply is dropped.
+    pub fn accept_reply(&mut self, reply: M) -> Result<(), ReplyError> {
+        match self.state {
+            State::WaitingForReply(from) if from == reply.sender() => {
+                self.reply = Some(reply);
+                self.state = State::Idle;
+                Ok(())
+            }
+            State::WaitingForReply(_) => Err(ReplyError::UnexpectedSender),
+            _ => Err(ReplyError::NotWaitingForReply),
+        }
+    }
+
+    /// Takes the reply delivered to the task, if any.
+    pub fn take_reply(&mut self) -> Option<M> {
+        self.reply.take()
+    }
+
+    /// Returns whether the task received a message from the given sender and
+    /// has not replied to it yet.
+    #[must_use]
+    pub fn is_pending(&self, sender: M::Id) -> bool {
+        self.in_flight.contains(&sender)
+    }
+
+    /// Records that the task replied to, or forwarded, a message of the given
+    /// sender. Returns whether a message from that sender was pending.
+    pub fn complete(&mut self, sender: M::Id) -> bool {
+        let position = self.in_flight.iter().position(|&id| id == sender);
+        position.map(|i| self.in_flight.swap_remove(i)).is_some()
+    }
+
+    /// Returns the number of messages received and not yet replied to.
+    #[must_use]
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+}
+
+impl<M: Envelope> Default for Endpoint<M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,19 @@
+//! The architecture-independent cores of some kernel subsystems. They do not
+//! depend on the hardware, on the memory layout of the kernel nor on its
+//! global state, so they can be built and tested on the host with `cargo
+//! test`, while the kernel wraps them with the glue that ties them to the
+//! machine (locks, per-core state, address translation, timers...).
+//!
+//! The clock and the memory managed by these cores are provided by the
+//! caller, which allows the tests to drive them with fake timers and with
+//! frame tables allocated on the host.
+#![no_std]
+
+extern crate alloc;
+
+pub mod elf;
+pub mod frame;
+pub mod ipc;
+pub mod paging;
+pub mod user;
+pub mod watchdog;
@@ -0,0 +1,347 @@
+//! The walk of the page tables of the kernel. Both architectures use three
+//! levels of tables of 512 entries, mapping 4 KiB pages and 2 MiB or 1 GiB
+//! huge pages: only the format of the entries differs, and is described by
+//! the [`Entry`] trait.
+//!
+//! The tables are allocated from, freed to and accessed through a
+//! [`Frames`] allocator. The kernel reaches them through its physical memory
+//! map, while the tests allocate them on the host.
+//!
+//! Levels are numbered from the root table: a leaf entry of level 0 maps
+//! 1 GiB, of level 1 maps 2 MiB and of level 2 maps 4 KiB.
+
+/// The number of entries of a page table.
+pub const ENTRIES: usize = 512;
+
+/// The number of levels of page tables.
+pub const LEVELS: usize = 3;
+
+/// The size of a page, in bytes.
+pub const PAGE_SIZE: usize = 4096;
+
+/// A page table entry.
+pub trait Entry: Copy {
+    /// Whether the architecture requires a break-before-make sequence when
+    /// replacing a leaf entry by a table: the entry must be cleared and the
+    /// cached translations flushed before the table is installed, since the
+    /// TLB could otherwise hold conflicting translations for the same address.
+    const BREAK_BEFORE_MAKE: bool;
+
+    /// Returns whether the entry maps a page or points to a table.
+    fn present(&self) -> bool;
+
+    /// Returns whether the entry maps a page, and not a table. Only
+    /// meaningful for present entries.
+    fn is_leaf(&self) -> bool;
+
+    /// Returns whether the entry maps the registers of a device, which are
+    /// not owned by the address space and must not be freed when unmapped.
+    fn device(&self) -> bool;
+
+    /// Returns the physical address of the page or of the table that the
+    /// entry points to.
+    fn address(&self) -> usize;
+
+    /// Clears the entry.
+    fn clear(&mut self);
+
+    /// Makes the entry point to the table at the given physical address.
+    fn set_table(&mut self, table: usize);
+
+    /// Returns a copy of this leaf entry mapping the page at the given
+    /// physical address as a leaf of the given level, with the same rights
+    /// and flags.
+    #[must_use]
+    fn split(&self, address: usize, level: usize) -> Self;
+}
+
+/// The allocator of the frames holding the page tables, and of the frames
+/// mapped by the tables when an address space is released.
+pub trait Frames {
+    /// The entries of the tables.
+    type Entry: Entry;
+
+    /// Allocates a zeroed frame for a table and returns its physical address,
+    /// or `None` if there is no memory left.
+    fn allocate(&mut self) -> Option<usize>;
+
+    /// Frees the given number of contiguous frames starting at the given
+    /// physical address.
+    fn deallocate(&mut self, frame: usize, count: usize);
+
+    /// Returns the table at the given physical address.
+    ///
+    /// # Safety
+    /// The address must be the address of a table, and the table must not be
+    /// accessed through another reference while the returned one is alive.
+    unsafe fn table<'a>(&self, address: usize) -> &'a mut [Self::Entry; ENTRIES];
+
+    /// Invalidates all the translations cached by the processors, after a
+    /// table was removed or before a huge page is replaced by a table.
+    fn flush(&mut self);
+}
+
+/// An error that can happen when walking the tables to map a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapError {
+    /// The address is already mapped, by a page of the requested size, by a
+    /// larger page or by smaller pages.
+    AlreadyMapped,
+
+    /// An intermediate table could not be allocated.
+    OutOfMemory,
+}
+
+/// An error that can happen when walking the tables to change or remove the
+/// mapping of a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmapError {
+    /// The address is not mapped.
+    NotMapped,
+
+    /// The address is mapped by a huge page that had to be split, but the
+    /// table needed to do so could not be allocated. The mapping is left
+    /// unchanged.
+    OutOfMemory,
+}
+
+/// Returns the index of the entry of each level of table translating the
+/// given virtual address, starting with the root table.
+#[must_use]
+pub const fn indexes(address: usize) -> [usize; LEVELS] {
+    [
+        (address >> 30) % ENTRIES,
+        (address >> 21) % ENTRIES,
+        (address >> 12) % ENTRIES,
+    ]
+}
+
+/// Returns the size of the memory mapped by a leaf entry at the given level
+/// of the page table: 1 GiB for the root table, 2 MiB for the second level and
+/// 4 KiB for the last level.
+#[must_use]
+pub const fn leaf_size(level: usize) -> usize {
+    PAGE_SIZE << (9 * (LEVELS - 1 - level))
+}
+
+/// Walks the tables from the given root table down to the given level,
+/// allocating the missing intermediate tables, and returns the empty entry
+/// where the leaf mapping the address must be written.
+///
+/// # Errors
+/// Returns [`MapError::AlreadyMapped`] if a leaf is found before the given
+/// level or if the entry at that level is present. For a huge page, this also
+/// covers the case where a table is present because smaller pages are mapped
+/// in its range. Returns [`MapError::OutOfMemory`] if a table could not be
+/// allocated: the tables allocated before are left in place, empty.
+pub fn map<'a, F: Frames>(
+    frames: &mut F,
+    root: &'a mut [F::Entry; ENTRIES],
+    indexes: [usize; LEVELS],
+    level: usize,
+) -> Result<&'a mut F::Entry, MapError> {
+    let mut entry = &mut root[indexes[0]];
+    for &index in &indexes[1..=level] {
+        if entry.is_leaf() && entry.present() {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        if !entry.present() {
+            let table = frames.allocate().ok_or(MapError::OutOfMemory)?;
+            entry.set_table(table);
+        }
+
+        // SAFETY: The entry is present and is not a leaf, so it points to a
+        // table, which is only reached through this walk.
+        entry = &mut unsafe { frames.table(entry.address()) }[index];
+    }
+
+    if entry.present() {
+        return Err(MapError::AlreadyMapped);
+    }
+    Ok(entry)
+}
+
+/// Walks the tables from the given root table and returns the leaf entry
+/// mapping the address, along with its level. Returns `None` if the address is
+/// not mapped. Contrary to [`leaf_mut`], huge pages are not split.
+#[must_use]
+pub fn leaf<'a, F: Frames>(
+    frames: &F,
+    root: &'a [F::Entry; ENTRIES],
+    indexes: [usize; LEVELS],
+) -> Option<(&'a F::Entry, usize)> {
+    let mut entry = &root[indexes[0]];
+    let mut level = 0;
+    for &index in &indexes[1..] {
+        if !entry.present() {
+            return None;
+        }
+        if entry.is_leaf() {
+            break;
+        }
+
+        // SAFETY: The entry is present and is not a leaf, so it points to a
+        // table. It is only read while the root table is borrowed.
+        entry = &unsafe { frames.table(entry.address()) }[index];
+        level += 1;
+    }
+
+    (entry.present() && entry.is_leaf()).then_some((entry, level))
+}
+
+/// Walks the tables from the given root table and returns the 4 KiB leaf
+/// entry mapping the address. If the address is mapped by a huge page, the
+/// huge page is split (see [`split`]) so that the returned entry only maps the
+/// page containing the address.
+///
+/// # Errors
+/// Returns [`UnmapError::NotMapped`] if the address is not mapped, or
+/// [`UnmapError::OutOfMemory`] if a huge page could not be split.
+///
+/// # Panics
+/// Panics if the last level holds a table, which means that the tables are
+/// corrupted.
+pub fn leaf_mut<'a, F: Frames>(
+    frames: &mut F,
+    root: &'a mut [F::Entry; ENTRIES],
+    indexes: [usize; LEVELS],
+) -> Result<&'a mut F::Entry, UnmapError> {
+    let mut entry = &mut root[indexes[0]];
+    for (level, &index) in indexes[1..].iter().enumerate() {
+        if !entry.present() {
+            return Err(UnmapError::NotMapped);
+        }
+        if entry.is_leaf() {
+            split(frames, entry, level)?;
+        }
+
+        // SAFETY: The entry is present and is not a leaf, so it points to a
+        // table, which is only reached through this walk.
+        entry = &mut unsafe { frames.table(entry.address()) }[index];
+    }
+
+    if !entry.present() {
+        return Err(UnmapError::NotMapped);
+    }
+    assert!(entry.is_leaf(), "Page table deeper than {LEVELS} levels");
+    Ok(entry)
+}
+
+/// Splits the huge page mapped by the given leaf entry, located at the given
+/// level, into 512 smaller pages with the same rights and flags. The entry is
+/// replaced by a table containing the smaller pages, so that the translation
+/// of any address in the huge page is left unchanged.
+///
+/// Unless the architecture requires a break-before-make sequence, there is no
+/// need to flush the TLB after splitting: the stale translation of the huge
+/// page is identical to the new ones, and will be invalidated when a page of
+/// the split range is unmapped.
+///
+/// # Errors
+/// Returns [`UnmapError::OutOfMemory`] if the table could not be allocated.
+/// In this case, the entry is left unchanged.
+pub fn split<F: Frames>(
+    frames: &mut F,
+    entry: &mut F::Entry,
+    level: usize,
+) -> Result<(), UnmapError> {
+    let address = frames.allocate().ok_or(UnmapError::OutOfMemory)?;
+
+    // SAFETY: The table was just allocated, and is not reachable yet.
+    let table = unsafe { frames.table(address) };
+    let base = entry.address();
+    let size = leaf_size(level + 1);
+    for (i, page) in table.iter_mut().enumerate() {
+        *page = entry.split(base + i * size, level + 1);
+    }
+
+    entry.clear();
+    if F::Entry::BREAK_BEFORE_MAKE {
+        frames.flush();
+    }
+    entry.set_table(address);
+    Ok(())
+}
+
+/// Frees the intermediate tables on the path to the page with the given
+/// indexes if they no longer contain any entry, after the page was unmapped.
+/// Emptiness is checked by scanning the table: page tables do not have any
+/// spare bits to keep a count of their entries, and unmapping is rare enough
+/// for the scan to be cheap compared to the TLB shootdown that precedes it.
+///
+/// The translations cached for the removed tables are invalidated before the
+/// tables are freed, since a flush for a single address only covers leaf
+/// entries. Returns the number of tables freed.
+pub fn prune<F: Frames>(
+    frames: &mut F,
+    root: &mut [F::Entry; ENTRIES],
+    indexes: [usize; LEVELS],
+) -> usize {
+    let top = &mut root[indexes[0]];
+    if !top.present() || top.is_leaf() {
+        return 0;
+    }
+
+    // SAFETY: The entry is present and is not a leaf, so it points to a
+    // table, which is only reached through this walk.
+    let middle = unsafe { frames.table(top.address()) };
+    let mut freed = [None; LEVELS - 1];
+    let entry = &mut middle[indexes[1]];
+    if entry.present() && !entry.is_leaf() {
+        // SAFETY: Same as above, for the last level.
+        let last = unsafe { frames.table(entry.address()) };
+        if is_empty(last) {
+            freed[0] = Some(entry.address());
+            entry.clear();
+        }
+    }
+    if is_empty(middle) {
+        freed[1] = Some(top.address());
+        top.clear();
+    }
+
+    let count = freed.iter().flatten().count();
+    if count > 0 {
+        frames.flush();
+        freed
+            .into_iter()
+            .flatten()
+            .for_each(|table| frames.deallocate(table, 1));
+    }
+    count
+}
+
+/// Clears the given entries of a table of the given level, and frees all the
+/// tables and pages they map, except the registers of devices. This is used
+/// to release an address space. Returns the number of frames freed, including
+/// the frames of the tables.
+pub fn release<F: Frames>(frames: &mut F, entries: &mut [F::Entry], level: usize) -> usize {
+    let mut released = 0;
+    for entry in entries.iter_mut().filter(|entry| entry.present()) {
+        let address = entry.address();
+        if entry.is_leaf() {
+            let count = leaf_size(level) / PAGE_SIZE;
+            let device = entry.device();
+            entry.clear();
+            if !device {
+                frames.deallocate(address, count);
+                released += count;
+            }
+        } else {
+            // SAFETY: The entry is present and is not a leaf, so it points to
+            // a table, which is only reached through this walk.
+            let table = unsafe { frames.table(address) };
+            released += release(frames, table, level + 1);
+            entry.clear();
+            frames.deallocate(address, 1);
+            released += 1;
+        }
+    }
+    released
+}
+
+/// Returns whether no entry of the given table is present.
+fn is_empty<E: Entry>(table: &[E]) -> bool {
+    table.iter().all(|entry| !entry.present())
+}
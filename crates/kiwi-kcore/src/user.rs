@@ -0,0 +1,80 @@
+//! The validation of the pointers given by user space to the kernel. Before
+//! accessing user memory on behalf of a syscall, the kernel checks that the
+//! whole object is in the userland address space and that each of its pages
+//! is mapped with the rights required by the access, so that an invalid
+//! buffer is reported to the caller instead of faulting during the copy.
+use core::ops::Range;
+
+/// The kind of access that the kernel intends to perform on user memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// The kernel will only read the memory.
+    Read,
+
+    /// The kernel will write to the memory.
+    Write,
+}
+
+/// The address space of a task, as seen by the validation of user pointers.
+pub trait AddressSpace {
+    /// Returns whether the page at the given address is mapped and accessible
+    /// from user space with the given access.
+    fn allows(&self, page: usize, access: Access) -> bool;
+}
+
+/// The validator of user pointers for a given layout of the address space.
+#[derive(Debug, Clone, Copy)]
+pub struct Validator {
+    /// The first address after the end of the userland address space.
+    limit: usize,
+
+    /// The size of a page, in bytes.
+    page_size: usize,
+}
+
+impl Validator {
+    /// Creates a validator for an address space whose userland part ends at
+    /// the given address (exclusive), with pages of the given size.
+    ///
+    /// # Panics
+    /// Panics if the page size is not a power of two.
+    #[must_use]
+    pub const fn new(limit: usize, page_size: usize) -> Self {
+        assert!(page_size.is_power_of_two());
+        Self { limit, page_size }
+    }
+
+    /// Returns the range of addresses covered by an object of `size` bytes
+    /// that starts at the given address, or `None` if the object is not fully
+    /// in the userland address space. Only integers are used here, so that a
+    /// range wrapping around the address space or crossing into the kernel
+    /// address space is rejected instead of being silently truncated. The end
+    /// of the range is exclusive, so an object can end exactly at the end of
+    /// the userland address space.
+    #[must_use]
+    pub fn range(&self, start: usize, size: usize) -> Option<Range<usize>> {
+        let end = start.checked_add(size)?;
+        (start < self.limit && end <= self.limit).then_some(start..end)
+    }
+
+    /// Returns the range of addresses covered by an object of `size` bytes
+    /// that starts at the given address, or `None` if the object is not fully
+    /// in the userland address space or if any of its pages cannot be accessed
+    /// as requested in the given address space. The range may span multiple
+    /// pages, and each of them is checked.
+    #[must_use]
+    pub fn check(
+        &self,
+        space: &impl AddressSpace,
+        start: usize,
+        size: usize,
+        access: Access,
+    ) -> Option<Range<usize>> {
+        let range = self.range(start, size)?;
+        let first = range.start & !(self.page_size - 1);
+        (first..range.end)
+            .step_by(self.page_size)
+            .all(|page| space.allows(page, access))
+            .then_some(range)
+    }
+}
@@ -0,0 +1,130 @@
+//! The lockup detection of the watchdog of the kernel. A [`Detector`] tracks
+//! the progress of a core, and is checked periodically to find out whether
+//! the core stopped making progress for too long.
+//!
+//! The detector is only updated with atomic operations, since the kernel
+//! checks it from the timer interrupt, which may interrupt the kernel while
+//! it holds any lock.
+use core::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// The value of [`Detector::runaway`] when no task was marked as runaway.
+const NO_RUNAWAY: usize = usize::MAX;
+
+/// A monotonic clock used by the detector to measure how long a core has
+/// not made progress.
+pub trait Clock {
+    /// Returns the number of nanoseconds elapsed since an arbitrary point in
+    /// the past, usually the boot of the system.
+    fn now(&self) -> u64;
+}
+
+/// What the detector does when it finds a core that has not made progress
+/// for the timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    /// The core is considered locked up as soon as the timeout expires.
+    Panic,
+
+    /// The running task is first marked as runaway so that it is terminated
+    /// as soon as its poll returns, and the core is given another timeout to
+    /// recover before being considered locked up.
+    TerminateTask,
+}
+
+/// The watchdog state of a core.
+#[derive(Debug)]
+pub struct Detector {
+    /// The progress counter of the core, incremented each time the executor
+    /// finishes polling a task or waits for a task to become ready.
+    progress: AtomicU64,
+
+    /// The value of the progress counter at the last check of the watchdog.
+    seen: AtomicU64,
+
+    /// The number of nanoseconds given by the clock when the progress counter
+    /// last changed, as observed by the watchdog.
+    since: AtomicU64,
+
+    /// The identifier of the task that was running when the core stopped
+    /// making progress, and that must be terminated once its poll returns, or
+    /// [`NO_RUNAWAY`] if there is none.
+    runaway: AtomicUsize,
+}
+
+impl Detector {
+    /// Creates the watchdog state of a core that has not made any progress.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            progress: AtomicU64::new(0),
+            seen: AtomicU64::new(0),
+            since: AtomicU64::new(0),
+            runaway: AtomicUsize::new(NO_RUNAWAY),
+        }
+    }
+
+    /// Starts measuring the time without progress from now.
+    pub fn start(&self, clock: &impl Clock) {
+        self.since.store(clock.now(), Ordering::Relaxed);
+    }
+
+    /// Signals that the core made progress.
+    pub fn pet(&self) {
+        self.progress.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Checks whether the core made progress since the last check. When the
+    /// core has not made progress for `timeout`, it is handled according to
+    /// the given policy, `running` being called to get the identifier of the
+    /// task running on the core, if any. Returns the duration without
+    /// progress if the core is locked up.
+    #[must_use]
+    pub fn check(
+        &self,
+        clock: &impl Clock,
+        timeout: Duration,
+        policy: Policy,
+        running: impl FnOnce() -> Option<usize>,
+    ) -> Option<Duration> {
+        let now = clock.now();
+        let progress = self.progress.load(Ordering::Relaxed);
+        if self.seen.swap(progress, Ordering::Relaxed) != progress {
+            self.since.store(now, Ordering::Relaxed);
+            return None;
+        }
+
+        let stalled = Duration::from_nanos(now.saturating_sub(self.since.load(Ordering::Relaxed)));
+        if stalled < timeout {
+            return None;
+        }
+
+        if policy == Policy::TerminateTask
+            && self.runaway.load(Ordering::Relaxed) == NO_RUNAWAY
+            && let Some(id) = running()
+        {
+            self.runaway.store(id, Ordering::Relaxed);
+            self.since.store(now, Ordering::Relaxed);
+            return None;
+        }
+
+        Some(stalled)
+    }
+
+    /// Returns whether the task with the given identifier was marked as
+    /// runaway, and clears the mark.
+    #[must_use]
+    pub fn take_runaway(&self, id: usize) -> bool {
+        self.runaway
+            .compare_exchange(id, NO_RUNAWAY, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,324 @@
+use elf::abi;
+use kiwi_kcore::elf::{LoadError, Loader, Machine};
+
+const PAGE_SIZE: usize = 0x1000;
+const LIMIT: usize = 0x100_0000;
+const PIE_BIAS: usize = 0x40_0000;
+const LOADER: Loader = Loader::new(
+    Machine {
+        id: abi::EM_RISCV,
+        none: abi::R_RISCV_NONE,
+        relative: abi::R_RISCV_RELATIVE,
+    },
+    LIMIT,
+    PAGE_SIZE,
+    PIE_BIAS,
+);
+
+/// The offset in the file of the data of the segments, after the ELF header
+/// and room for four program headers.
+const PAYLOAD: u64 = 0x100;
+
+/// A program header, whose offset is relative to the payload of the file.
+#[derive(Clone, Copy)]
+struct Phdr {
+    kind: u32,
+    flags: u32,
+    offset: u64,
+    vaddr: u64,
+    filesz: u64,
+    memsz: u64,
+}
+
+impl Phdr {
+    const fn load(offset: u64, vaddr: u64, filesz: u64, memsz: u64) -> Self {
+        Self {
+            kind: abi::PT_LOAD,
+            flags: abi::PF_R | abi::PF_X,
+            offset,
+            vaddr,
+            filesz,
+            memsz,
+        }
+    }
+
+    const fn stack(flags: u32) -> Self {
+        Self {
+            kind: abi::PT_GNU_STACK,
+            flags,
+            offset: 0,
+            vaddr: 0,
+            filesz: 0,
+            memsz: 0,
+        }
+    }
+}
+
+/// Builds a little-endian 64-bit RISC-V ELF file with the given type, entry
+/// point and program headers, followed by the given payload.
+fn build(kind: u16, entry: u64, phdrs: &[Phdr], payload: &[u8]) -> Vec<u8> {
+    assert!(phdrs.len() <= 4);
+    let mut file = Vec::new();
+    file.extend_from_slice(&[0x7F, b'E', b'L', b'F', 2, 1, 1, 0]);
+    file.extend_from_slice(&[0; 8]);
+    file.extend_from_slice(&kind.to_le_bytes());
+    file.extend_from_slice(&abi::EM_RISCV.to_le_bytes());
+    file.extend_from_slice(&1u32.to_le_bytes());
+    file.extend_from_slice(&entry.to_le_bytes());
+    file.extend_from_slice(&64u64.to_le_bytes());
+    file.extend_from_slice(&0u64.to_le_bytes());
+    file.extend_from_slice(&0u32.to_le_bytes());
+    file.extend_from_slice(&64u16.to_le_bytes());
+    file.extend_from_slice(&56u16.to_le_bytes());
+    file.extend_from_slice(&(phdrs.len() as u16).to_le_bytes());
+    file.extend_from_slice(&64u16.to_le_bytes());
+    file.extend_from_slice(&0u16.to_le_bytes());
+    file.extend_from_slice(&0u16.to_le_bytes());
+
+    for phdr in phdrs {
+        file.extend_from_slice(&phdr.kind.to_le_bytes());
+        file.extend_from_slice(&phdr.flags.to_le_bytes());
+        file.extend_from_slice(&PAYLOAD.wrapping_add(phdr.offset).to_le_bytes());
+        file.extend_from_slice(&phdr.vaddr.to_le_bytes());
+        file.extend_from_slice(&phdr.vaddr.to_le_bytes());
+        file.extend_from_slice(&phdr.filesz.to_le_bytes());
+        file.extend_from_slice(&phdr.memsz.to_le_bytes());
+        file.extend_from_slice(&(PAGE_SIZE as u64).to_le_bytes());
+    }
+
+    file.resize(PAYLOAD as usize, 0);
+    file.extend_from_slice(payload);
+    file
+}
+
+/// Builds a position-independent executable with a single segment at address
+/// zero holding the given relocation table at offset 0x20, followed by a
+/// dynamic section with the given tags.
+fn pie(relocations: &[(u64, u32, i64)], dynamic: &[(i64, u64)]) -> Vec<u8> {
+    let mut payload = vec![0; 0x20];
+    for &(offset, kind, addend) in relocations {
+        payload.extend_from_slice(&offset.to_le_bytes());
+        payload.extend_from_slice(&u64::from(kind).to_le_bytes());
+        payload.extend_from_slice(&addend.to_le_bytes());
+    }
+
+    let dynamic_offset = payload.len() as u64;
+    for &(tag, value) in dynamic.iter().chain(&[(abi::DT_NULL, 0)]) {
+        payload.extend_from_slice(&tag.to_le_bytes());
+        payload.extend_from_slice(&value.to_le_bytes());
+    }
+
+    let size = payload.len() as u64;
+    let phdrs = [
+        Phdr::load(0, 0, size, size),
+        Phdr {
+            kind: abi::PT_DYNAMIC,
+            flags: abi::PF_R,
+            offset: dynamic_offset,
+            vaddr: dynamic_offset,
+            filesz: size - dynamic_offset,
+            memsz: size - dynamic_offset,
+        },
+    ];
+    build(abi::ET_DYN, 0, &phdrs, &payload)
+}
+
+/// The dynamic tags describing a table of the given number of relocations at
+/// offset 0x20.
+fn rela(count: u64) -> [(i64, u64); 3] {
+    [
+        (abi::DT_RELA, 0x20),
+        (abi::DT_RELASZ, count * 24),
+        (abi::DT_RELAENT, 24),
+    ]
+}
+
+#[test]
+fn static_executables_are_not_relocated() {
+    let file = build(
+        abi::ET_EXEC,
+        0x1008,
+        &[Phdr::load(0, 0x1000, 0x10, 0x10)],
+        &[0xAA; 0x10],
+    );
+    let image = LOADER.parse(&file).unwrap();
+    assert_eq!(image.bias, 0);
+    assert_eq!(image.entry, 0x1008);
+    assert_eq!(image.segments.len(), 1);
+    assert_eq!(image.segments[0].start, 0x1000);
+    assert_eq!(image.segments[0].end, 0x1010);
+    assert_eq!(image.segments[0].data, &[0xAA; 0x10]);
+    assert_eq!(image.relocations().count(), 0);
+}
+
+#[test]
+fn position_independent_executables_are_loaded_at_the_bias() {
+    let relocations = [
+        (0x8, abi::R_RISCV_RELATIVE, 0x40),
+        (0x0, abi::R_RISCV_NONE, 0),
+        (0x10, abi::R_RISCV_RELATIVE, -0x10),
+    ];
+    let file = pie(&relocations, &rela(3));
+    let image = LOADER.parse(&file).unwrap();
+    assert_eq!(image.bias, PIE_BIAS);
+    assert_eq!(image.entry, PIE_BIAS);
+    assert_eq!(image.segments[0].start, PIE_BIAS);
+
+    let applied: Vec<_> = image.relocations().collect();
+    assert_eq!(
+        applied,
+        [
+            Ok((PIE_BIAS + 0x8, PIE_BIAS + 0x40)),
+            Ok((PIE_BIAS + 0x10, PIE_BIAS - 0x10)),
+        ]
+    );
+}
+
+#[test]
+fn relocations_must_be_relative_and_inside_a_segment() {
+    let file = pie(&[(0x8, abi::R_RISCV_64, 0)], &rela(1));
+    let image = LOADER.parse(&file).unwrap();
+    assert_eq!(
+        image.relocations().collect::<Vec<_>>(),
+        [Err(LoadError::InvalidRelocation)]
+    );
+
+    let file = pie(&[(0x1_0000, abi::R_RISCV_RELATIVE, 0)], &rela(1));
+    let image = LOADER.parse(&file).unwrap();
+    assert_eq!(
+        image.relocations().collect::<Vec<_>>(),
+        [Err(LoadError::InvalidRelocation)]
+    );
+
+    let file = pie(&[(u64::MAX - 4, abi::R_RISCV_RELATIVE, 0)], &rela(1));
+    let image = LOADER.parse(&file).unwrap();
+    assert_eq!(
+        image.relocations().collect::<Vec<_>>(),
+        [Err(LoadError::InvalidRelocation)]
+    );
+}
+
+#[test]
+fn bad_relocation_tables_are_rejected() {
+    let relocations = [(0x8, abi::R_RISCV_RELATIVE, 0)];
+    let bad_entry = [
+        (abi::DT_RELA, 0x20),
+        (abi::DT_RELASZ, 24),
+        (abi::DT_RELAENT, 16),
+    ];
+    let overflowing = [(abi::DT_RELA, u64::MAX - 8), (abi::DT_RELASZ, 24)];
+    let outside = [(abi::DT_RELA, 0x20), (abi::DT_RELASZ, 0x1000)];
+    let rel = [(abi::DT_REL, 0x20)];
+
+    for dynamic in [&bad_entry[..], &overflowing, &outside, &rel] {
+        let file = pie(&relocations, dynamic);
+        assert_eq!(
+            LOADER.parse(&file).unwrap_err(),
+            LoadError::InvalidRelocation
+        );
+    }
+}
+
+#[test]
+fn memory_beyond_the_file_data_is_zeroed() {
+    let file = build(
+        abi::ET_EXEC,
+        0x1800,
+        &[
+            Phdr::load(0, 0x1000, 0x10, 0x10),
+            Phdr::load(0x10, 0x1800, 0x100, 0x2000),
+        ],
+        &[0xAA; 0x110],
+    );
+    let image = LOADER.parse(&file).unwrap();
+    let segment = &image.segments[1];
+    assert_eq!(segment.data.len(), 0x100);
+
+    // The first page is shared with the previous segment: only the end of
+    // the segment in that page must be zeroed, the rest is freshly mapped.
+    assert_eq!(segment.zeroed(0x2000), 0x1900..0x2000);
+
+    // The whole segment is freshly mapped and already zeroed.
+    assert!(segment.zeroed(0x1000).is_empty());
+
+    // A segment ending in the shared page is zeroed up to its end.
+    let file = build(
+        abi::ET_EXEC,
+        0x1000,
+        &[Phdr::load(0, 0x1000, 0x10, 0x80)],
+        &[0xAA; 0x10],
+    );
+    let image = LOADER.parse(&file).unwrap();
+    assert_eq!(image.segments[0].zeroed(0x2000), 0x1010..0x1080);
+}
+
+#[test]
+fn executable_stacks_are_rejected() {
+    let load = Phdr::load(0, 0x1000, 0x10, 0x10);
+    let file = build(
+        abi::ET_EXEC,
+        0x1000,
+        &[load, Phdr::stack(abi::PF_R | abi::PF_W | abi::PF_X)],
+        &[0; 0x10],
+    );
+    assert_eq!(LOADER.parse(&file).unwrap_err(), LoadError::ExecutableStack);
+
+    let file = build(
+        abi::ET_EXEC,
+        0x1000,
+        &[load, Phdr::stack(abi::PF_R | abi::PF_W)],
+        &[0; 0x10],
+    );
+    assert!(LOADER.parse(&file).is_ok());
+}
+
+#[test]
+fn overlapping_segments_are_rejected() {
+    let overlapping = [
+        Phdr::load(0, 0x1000, 0x10, 0x1000),
+        Phdr::load(0, 0x1800, 0x10, 0x10),
+    ];
+    let unsorted = [
+        Phdr::load(0, 0x3000, 0x10, 0x10),
+        Phdr::load(0, 0x1000, 0x10, 0x10),
+    ];
+    for phdrs in [overlapping, unsorted] {
+        let file = build(abi::ET_EXEC, 0x1000, &phdrs, &[0; 0x10]);
+        assert_eq!(LOADER.parse(&file).unwrap_err(), LoadError::InvalidSegment);
+    }
+}
+
+#[test]
+fn overflowing_segments_are_rejected() {
+    let segments = [
+        // The end of the segment wraps around the address space.
+        (abi::ET_EXEC, Phdr::load(0, u64::MAX - 0x10, 0x10, 0x100)),
+        // The load bias makes the start wrap around the address space.
+        (abi::ET_DYN, Phdr::load(0, u64::MAX - 0x10, 0x10, 0x10)),
+        // The segment reaches the stack at the top of the user space.
+        (abi::ET_EXEC, Phdr::load(0, LIMIT as u64 - 0x8, 0x10, 0x10)),
+        // The data of the segment is beyond the end of the file.
+        (abi::ET_EXEC, Phdr::load(0x8, 0x1000, 0x10, 0x10)),
+        (
+            abi::ET_EXEC,
+            Phdr::load(u64::MAX - PAYLOAD - 0x8, 0x1000, 0x10, 0x10),
+        ),
+        // The file size is larger than the memory size.
+        (abi::ET_EXEC, Phdr::load(0, 0x1000, 0x10, 0x8)),
+    ];
+    for (kind, phdr) in segments {
+        let file = build(kind, 0x1000, &[phdr], &[0; 0x10]);
+        assert_eq!(LOADER.parse(&file).unwrap_err(), LoadError::InvalidSegment);
+    }
+}
+
+#[test]
+fn entry_must_be_inside_a_segment() {
+    let file = build(
+        abi::ET_EXEC,
+        0x1010,
+        &[Phdr::load(0, 0x1000, 0x10, 0x10)],
+        &[0; 0x10],
+    );
+    assert_eq!(LOADER.parse(&file).unwrap_err(), LoadError::InvalidEntry);
+}
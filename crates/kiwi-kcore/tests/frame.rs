@@ -0,0 +1,194 @@
+use kiwi_kcore::frame::{Bitmap, FrameFlags, FrameInfo};
+
+/// Creates a table of `count` frames reserved for the kernel, as the kernel
+/// does before knowing the free memory regions.
+fn frames(count: usize) -> Vec<FrameInfo> {
+    (0..count).map(|_| FrameInfo::RESERVED).collect()
+}
+
+#[test]
+fn frames_are_reserved_until_added() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    assert_eq!(bitmap.count(FrameFlags::KERNEL), 8);
+    assert_eq!(bitmap.allocate(1, 0, 1, FrameFlags::empty()), None);
+
+    bitmap.add_free(2..6);
+    assert_eq!(bitmap.count(FrameFlags::KERNEL), 4);
+    assert_eq!(bitmap.count(FrameFlags::FREE), 4);
+}
+
+#[test]
+fn firmware_frames_are_never_allocated() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..8);
+    bitmap.reserve_firmware(0..2);
+
+    assert_eq!(bitmap.flags(0), FrameFlags::FIRMWARE);
+    assert_eq!(bitmap.allocate(1, 0, 1, FrameFlags::empty()), Some(2));
+    assert_eq!(bitmap.count(FrameFlags::FIRMWARE), 2);
+}
+
+#[test]
+fn allocate_finds_contiguous_ranges() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..8);
+
+    assert_eq!(bitmap.allocate(0, 0, 1, FrameFlags::empty()), None);
+    assert_eq!(bitmap.allocate(3, 0, 1, FrameFlags::empty()), Some(0));
+    assert_eq!(bitmap.allocate(1, 0, 1, FrameFlags::empty()), Some(3));
+    assert_eq!(bitmap.allocate(4, 0, 1, FrameFlags::empty()), Some(4));
+    assert_eq!(bitmap.allocate(1, 0, 1, FrameFlags::empty()), None);
+    assert_eq!(bitmap.count(FrameFlags::FREE), 0);
+}
+
+#[test]
+fn allocate_skips_fragmented_ranges() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..3);
+    bitmap.add_free(4..8);
+
+    assert_eq!(bitmap.allocate(4, 0, 1, FrameFlags::empty()), Some(4));
+    assert_eq!(bitmap.allocate(4, 0, 1, FrameFlags::empty()), None);
+    assert_eq!(bitmap.count(FrameFlags::FREE), 3);
+}
+
+#[test]
+fn allocate_respects_alignment() {
+    let mut frames = frames(16);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..16);
+    bitmap.allocate(1, 0, 1, FrameFlags::empty());
+
+    // The first frame of an aligned range is only looked for every 4 frames,
+    // starting at the third one.
+    assert_eq!(bitmap.allocate(2, 2, 4, FrameFlags::empty()), Some(2));
+    assert_eq!(bitmap.allocate(2, 2, 4, FrameFlags::empty()), Some(6));
+    assert_eq!(bitmap.allocate(4, 2, 4, FrameFlags::empty()), Some(10));
+    assert_eq!(bitmap.allocate(4, 2, 4, FrameFlags::empty()), None);
+}
+
+#[test]
+fn kernel_frames_are_counted() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..8);
+
+    let start = bitmap.allocate(2, 0, 1, FrameFlags::KERNEL).unwrap();
+    bitmap.allocate(2, 0, 1, FrameFlags::empty()).unwrap();
+    assert_eq!(bitmap.count(FrameFlags::KERNEL), 2);
+
+    bitmap.free(start..start + 2);
+    assert_eq!(bitmap.count(FrameFlags::KERNEL), 0);
+    assert_eq!(bitmap.count(FrameFlags::FREE), 6);
+}
+
+#[test]
+fn reserved_frames_can_be_released() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.free(4..8);
+    assert_eq!(bitmap.allocate(4, 0, 1, FrameFlags::empty()), Some(4));
+}
+
+#[test]
+#[should_panic(expected = "assertion failed")]
+fn double_free_panics() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..8);
+    let start = bitmap.allocate(1, 0, 1, FrameFlags::empty()).unwrap();
+    bitmap.free(start..start + 1);
+    bitmap.free(start..start + 1);
+}
+
+#[test]
+#[should_panic(expected = "assertion failed")]
+fn disowning_a_free_frame_panics() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..8);
+    bitmap.disown(0);
+}
+
+#[test]
+fn take_free_marks_frames_as_used() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..2);
+    bitmap.add_free(5..8);
+
+    let taken: Vec<usize> = bitmap.take_free(4).collect();
+    assert_eq!(taken, [0, 1, 5, 6]);
+    assert_eq!(bitmap.count(FrameFlags::FREE), 1);
+
+    // A frame taken by the cache can be disowned and handed back.
+    bitmap.disown(5);
+    bitmap.put_free(5);
+    assert_eq!(bitmap.count(FrameFlags::FREE), 2);
+    assert_eq!(bitmap.take_free(8).collect::<Vec<_>>(), [5, 7]);
+}
+
+#[test]
+fn shared_frames_are_freed_on_last_reference() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..8);
+
+    let index = bitmap.allocate(1, 0, 1, FrameFlags::empty()).unwrap();
+    assert_eq!(bitmap.references(index), 1);
+    bitmap.get(index);
+    bitmap.get(index);
+    assert_eq!(bitmap.references(index), 3);
+
+    assert!(!bitmap.put(index));
+    assert!(!bitmap.put(index));
+    assert!(bitmap.put(index));
+    assert_eq!(bitmap.count(FrameFlags::FREE), 7);
+
+    bitmap.free(index..index + 1);
+    assert_eq!(bitmap.references(index), 0);
+    assert_eq!(bitmap.count(FrameFlags::FREE), 8);
+}
+
+#[test]
+fn cached_frames_hold_a_single_reference() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..8);
+
+    let taken: Vec<usize> = bitmap.take_free(2).collect();
+    assert!(taken.iter().all(|&index| bitmap.references(index) == 1));
+
+    bitmap.get(taken[0]);
+    assert!(!bitmap.put(taken[0]));
+    assert!(bitmap.put(taken[0]));
+    bitmap.disown(taken[0]);
+    assert_eq!(bitmap.references(taken[0]), 1);
+
+    bitmap.put_free(taken[0]);
+    assert_eq!(bitmap.references(taken[0]), 0);
+}
+
+#[test]
+#[should_panic(expected = "Freeing a shared frame")]
+fn freeing_a_shared_frame_panics() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..8);
+    let index = bitmap.allocate(1, 0, 1, FrameFlags::empty()).unwrap();
+    bitmap.get(index);
+    bitmap.free(index..index + 1);
+}
+
+#[test]
+#[should_panic(expected = "assertion failed")]
+fn referencing_a_free_frame_panics() {
+    let mut frames = frames(8);
+    let mut bitmap = Bitmap::new(&mut frames);
+    bitmap.add_free(0..8);
+    bitmap.get(0);
+}
@@ -0,0 +1,163 @@
+use kiwi_kcore::ipc::{Endpoint, Envelope, ReplyError, State};
+
+const CAPACITY: usize = 2;
+
+/// A message that only carries what the endpoint needs, along with a tag to
+/// tell messages apart.
+#[derive(Debug, PartialEq, Eq)]
+struct FakeMessage {
+    sender: u32,
+    oneway: bool,
+    tag: u32,
+}
+
+impl FakeMessage {
+    fn request(sender: u32, tag: u32) -> Self {
+        Self {
+            sender,
+            oneway: false,
+            tag,
+        }
+    }
+
+    fn oneway(sender: u32, tag: u32) -> Self {
+        Self {
+            sender,
+            oneway: true,
+            tag,
+        }
+    }
+}
+
+impl Envelope for FakeMessage {
+    type Id = u32;
+
+    fn sender(&self) -> u32 {
+        self.sender
+    }
+
+    fn oneway(&self) -> bool {
+        self.oneway
+    }
+}
+
+/// Delivers the message, which must fit in the mailbox.
+fn deliver(endpoint: &mut Endpoint<FakeMessage>, message: FakeMessage) {
+    endpoint.deliver(message, CAPACITY).unwrap();
+}
+
+#[test]
+fn messages_are_received_in_order() {
+    let mut endpoint = Endpoint::new();
+    deliver(&mut endpoint, FakeMessage::request(1, 10));
+    deliver(&mut endpoint, FakeMessage::request(2, 20));
+
+    assert_eq!(endpoint.receive().map(|m| m.tag), Some(10));
+    assert_eq!(endpoint.receive().map(|m| m.tag), Some(20));
+    assert_eq!(endpoint.receive(), None);
+}
+
+#[test]
+fn full_mailbox_returns_the_message() {
+    let mut endpoint = Endpoint::new();
+    deliver(&mut endpoint, FakeMessage::request(1, 10));
+    deliver(&mut endpoint, FakeMessage::request(1, 11));
+    assert!(endpoint.is_full(CAPACITY));
+
+    let rejected = endpoint.deliver(FakeMessage::request(2, 20), CAPACITY);
+    assert_eq!(rejected, Err(FakeMessage::request(2, 20)));
+
+    // Receiving a message frees a slot.
+    endpoint.receive().unwrap();
+    assert!(!endpoint.is_full(CAPACITY));
+    deliver(&mut endpoint, FakeMessage::request(2, 20));
+}
+
+#[test]
+fn receiving_ends_the_wait_for_a_message() {
+    let mut endpoint = Endpoint::new();
+    endpoint.wait_for_message();
+    assert_eq!(endpoint.state(), State::WaitingForMessage);
+
+    deliver(&mut endpoint, FakeMessage::request(1, 10));
+    endpoint.receive().unwrap();
+    assert_eq!(endpoint.state(), State::Idle);
+}
+
+#[test]
+fn requests_are_in_flight_until_completed() {
+    let mut endpoint = Endpoint::new();
+    deliver(&mut endpoint, FakeMessage::request(1, 10));
+    deliver(&mut endpoint, FakeMessage::oneway(2, 20));
+    endpoint.receive().unwrap();
+    endpoint.receive().unwrap();
+
+    // Only the sender of the request waits for a reply.
+    assert_eq!(endpoint.in_flight(), 1);
+    assert!(endpoint.is_pending(1));
+    assert!(!endpoint.is_pending(2));
+
+    assert!(endpoint.complete(1));
+    assert!(!endpoint.complete(1));
+    assert_eq!(endpoint.in_flight(), 0);
+}
+
+#[test]
+fn reply_is_accepted_once() {
+    let mut endpoint = Endpoint::new();
+    endpoint.wait_for_reply(7);
+    assert_eq!(endpoint.replier(), Some(7));
+
+    endpoint.accept_reply(FakeMessage::request(7, 1)).unwrap();
+    assert_eq!(endpoint.state(), State::Idle);
+    assert_eq!(endpoint.replier(), None);
+
+    // A second reply must not be delivered to the next message sent.
+    assert_eq!(
+        endpoint.accept_reply(FakeMessage::request(7, 2)),
+        Err(ReplyError::NotWaitingForReply)
+    );
+    assert_eq!(endpoint.take_reply().map(|m| m.tag), Some(1));
+    assert_eq!(endpoint.take_reply(), None);
+}
+
+#[test]
+fn reply_from_another_task_is_refused() {
+    let mut endpoint = Endpoint::new();
+    assert_eq!(
+        endpoint.accept_reply(FakeMessage::request(7, 1)),
+        Err(ReplyError::NotWaitingForReply)
+    );
+
+    endpoint.wait_for_reply(7);
+    assert_eq!(
+        endpoint.accept_reply(FakeMessage::request(8, 1)),
+        Err(ReplyError::UnexpectedSender)
+    );
+    assert_eq!(endpoint.state(), State::WaitingForReply(7));
+    assert_eq!(endpoint.take_reply(), None);
+}
+
+#[test]
+fn forwarded_request_is_replied_by_the_new_task() {
+    // The client waits for a reply from the router, which forwards the
+    // message to a service.
+    let mut client = Endpoint::new();
+    let mut router = Endpoint::new();
+    client.wait_for_reply(2);
+    deliver(&mut router, FakeMessage::request(1, 10));
+    router.receive().unwrap();
+
+    assert!(router.is_pending(1));
+    assert_eq!(client.replier(), Some(2));
+    client.wait_for_reply(3);
+    assert!(router.complete(1));
+
+    // Only the service can now reply.
+    assert_eq!(
+        client.accept_reply(FakeMessage::request(2, 1)),
+        Err(ReplyError::UnexpectedSender)
+    );
+    client.accept_reply(FakeMessage::request(3, 1)).unwrap();
+    assert_eq!(client.take_reply().map(|m| m.sender), Some(3));
+}
@@ -0,0 +1,351 @@
+use kiwi_kcore::paging::{
+    self, ENTRIES, Entry, Frames, MapError, PAGE_SIZE, UnmapError, indexes, leaf_size,
+};
+use std::collections::BTreeMap;
+
+/// A page table entry with a made-up format: the low bits hold the flags, and
+/// the physical address is stored as is above them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct FakeEntry(u64);
+
+impl FakeEntry {
+    const PRESENT: u64 = 1 << 0;
+    const LEAF: u64 = 1 << 1;
+    const DEVICE: u64 = 1 << 2;
+    const WRITABLE: u64 = 1 << 3;
+
+    /// The level of the leaf, used to check that split pages are marked as
+    /// leaves of the level below.
+    const LEVEL_SHIFT: u64 = 4;
+
+    fn page(address: usize, level: usize, flags: u64) -> Self {
+        Self(
+            address as u64
+                | ((level as u64) << Self::LEVEL_SHIFT)
+                | flags
+                | Self::PRESENT
+                | Self::LEAF,
+        )
+    }
+
+    fn level(self) -> usize {
+        ((self.0 >> Self::LEVEL_SHIFT) & 0b11) as usize
+    }
+}
+
+impl Entry for FakeEntry {
+    const BREAK_BEFORE_MAKE: bool = true;
+
+    fn present(&self) -> bool {
+        self.0 & Self::PRESENT != 0
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.0 & Self::LEAF != 0
+    }
+
+    fn device(&self) -> bool {
+        self.0 & Self::DEVICE != 0
+    }
+
+    fn address(&self) -> usize {
+        (self.0 & !(PAGE_SIZE as u64 - 1)) as usize
+    }
+
+    fn clear(&mut self) {
+        self.0 = 0;
+    }
+
+    fn set_table(&mut self, table: usize) {
+        self.0 = table as u64 | Self::PRESENT;
+    }
+
+    fn split(&self, address: usize, level: usize) -> Self {
+        Self::page(address, level, self.0 & (Self::DEVICE | Self::WRITABLE))
+    }
+}
+
+type Table = [FakeEntry; ENTRIES];
+
+/// A frame allocator handing out tables allocated on the host, at made-up
+/// physical addresses. It records the frames mapped by the tables that are
+/// freed, and can be limited to a number of tables to simulate running out of
+/// memory.
+#[derive(Default)]
+struct FakeFrames {
+    tables: BTreeMap<usize, *mut Table>,
+    next: usize,
+    limit: Option<usize>,
+    freed: Vec<(usize, usize)>,
+    flushes: usize,
+}
+
+impl FakeFrames {
+    /// The physical address of the first table. Tables are far above the
+    /// pages mapped by the tests, so that they cannot be mistaken for them.
+    const TABLES: usize = 0x1_0000_0000_0000;
+
+    fn limited(limit: usize) -> Self {
+        let mut frames = Self::default();
+        frames.limit = Some(limit);
+        frames
+    }
+
+    fn tables(&self) -> usize {
+        self.tables.len()
+    }
+}
+
+impl Frames for FakeFrames {
+    type Entry = FakeEntry;
+
+    fn allocate(&mut self) -> Option<usize> {
+        if self.limit.is_some_and(|limit| self.tables.len() >= limit) {
+            return None;
+        }
+        let address = Self::TABLES + self.next * PAGE_SIZE;
+        self.next += 1;
+        let table = Box::new([FakeEntry::default(); ENTRIES]);
+        self.tables.insert(address, Box::into_raw(table));
+        Some(address)
+    }
+
+    fn deallocate(&mut self, frame: usize, count: usize) {
+        match self.tables.remove(&frame) {
+            Some(table) => {
+                assert_eq!(count, 1);
+                // SAFETY: The table was allocated by `allocate` and is no
+                // longer reachable from the tables.
+                drop(unsafe { Box::from_raw(table) });
+            }
+            None => self.freed.push((frame, count)),
+        }
+    }
+
+    unsafe fn table<'a>(&self, address: usize) -> &'a mut Table {
+        let table = self.tables[&address];
+        // SAFETY: The caller ensures that the table is not aliased.
+        unsafe { &mut *table }
+    }
+
+    fn flush(&mut self) {
+        self.flushes += 1;
+    }
+}
+
+impl Drop for FakeFrames {
+    fn drop(&mut self) {
+        for table in self.tables.values() {
+            // SAFETY: The tables were allocated by `allocate`.
+            drop(unsafe { Box::from_raw(*table) });
+        }
+    }
+}
+
+/// Maps the page at the given physical address at the given virtual address,
+/// with a leaf of the given level.
+fn map(
+    frames: &mut FakeFrames,
+    root: &mut Table,
+    virt: usize,
+    phys: usize,
+    level: usize,
+) -> Result<(), MapError> {
+    let entry = paging::map(frames, root, indexes(virt), level)?;
+    *entry = FakeEntry::page(phys, level, FakeEntry::WRITABLE);
+    Ok(())
+}
+
+/// Translates the given virtual address, returning the physical address it is
+/// mapped to.
+fn translate(frames: &FakeFrames, root: &Table, virt: usize) -> Option<usize> {
+    let (entry, level) = paging::leaf(frames, root, indexes(virt))?;
+    Some(entry.address() + virt % leaf_size(level))
+}
+
+#[test]
+fn indexes_are_split_by_level() {
+    assert_eq!(indexes(0), [0, 0, 0]);
+    assert_eq!(indexes(0x4000_0000 + 0x20_0000 + 0x1000), [1, 1, 1]);
+    assert_eq!(indexes(0x3F_FFFF_F000), [255, 511, 511]);
+    assert_eq!(indexes(0xFFFF_FFFF_C000_0000), [511, 0, 0]);
+    assert_eq!(leaf_size(0), 1 << 30);
+    assert_eq!(leaf_size(1), 1 << 21);
+    assert_eq!(leaf_size(2), 1 << 12);
+}
+
+#[test]
+fn mapping_allocates_the_intermediate_tables() {
+    let mut frames = FakeFrames::default();
+    let mut root = [FakeEntry::default(); ENTRIES];
+
+    map(&mut frames, &mut root, 0x1000, 0x8000_0000, 2).unwrap();
+    assert_eq!(frames.tables(), 2);
+    assert_eq!(translate(&frames, &root, 0x1234), Some(0x8000_0234));
+    assert_eq!(translate(&frames, &root, 0x2000), None);
+
+    // A neighbouring page reuses the same tables.
+    map(&mut frames, &mut root, 0x2000, 0x8000_1000, 2).unwrap();
+    assert_eq!(frames.tables(), 2);
+    assert_eq!(translate(&frames, &root, 0x2010), Some(0x8000_1010));
+
+    // A huge page only needs the tables above its level.
+    map(&mut frames, &mut root, 0x4000_0000, 0x4000_0000, 0).unwrap();
+    map(&mut frames, &mut root, 0x8020_0000, 0x20_0000, 1).unwrap();
+    assert_eq!(frames.tables(), 3);
+    assert_eq!(translate(&frames, &root, 0x4123_4567), Some(0x4123_4567));
+    assert_eq!(translate(&frames, &root, 0x8031_2345), Some(0x31_2345));
+}
+
+#[test]
+fn mapped_addresses_are_not_overwritten() {
+    let mut frames = FakeFrames::default();
+    let mut root = [FakeEntry::default(); ENTRIES];
+    map(&mut frames, &mut root, 0x1000, 0x8000_0000, 2).unwrap();
+    map(&mut frames, &mut root, 0x4000_0000, 0x4000_0000, 0).unwrap();
+
+    // The same page, a huge page over smaller pages and a page inside a huge
+    // page are all rejected.
+    let attempts = [
+        (0x1000, 2),
+        (0, 1),
+        (0, 0),
+        (0x4020_0000, 1),
+        (0x4000_1000, 2),
+    ];
+    for (virt, level) in attempts {
+        assert_eq!(
+            map(&mut frames, &mut root, virt, 0x9000_0000, level),
+            Err(MapError::AlreadyMapped)
+        );
+    }
+    assert_eq!(translate(&frames, &root, 0x1000), Some(0x8000_0000));
+    assert_eq!(frames.tables(), 2);
+}
+
+#[test]
+fn mapping_fails_when_out_of_memory() {
+    let mut frames = FakeFrames::limited(1);
+    let mut root = [FakeEntry::default(); ENTRIES];
+    assert_eq!(
+        map(&mut frames, &mut root, 0x1000, 0x8000_0000, 2),
+        Err(MapError::OutOfMemory)
+    );
+    assert_eq!(translate(&frames, &root, 0x1000), None);
+
+    // The table allocated before running out of memory is left empty in
+    // place, and is freed by the next prune.
+    assert_eq!(frames.tables(), 1);
+    assert_eq!(paging::prune(&mut frames, &mut root, indexes(0x1000)), 1);
+    assert_eq!(frames.tables(), 0);
+}
+
+#[test]
+fn unmapping_prunes_the_empty_tables() {
+    let mut frames = FakeFrames::default();
+    let mut root = [FakeEntry::default(); ENTRIES];
+    map(&mut frames, &mut root, 0x1000, 0x8000_0000, 2).unwrap();
+    map(&mut frames, &mut root, 0x20_0000, 0x8000_1000, 2).unwrap();
+    assert_eq!(frames.tables(), 3);
+
+    // The last table of the first page is empty once it is unmapped, but the
+    // middle table still holds the table of the second page.
+    let entry = paging::leaf_mut(&mut frames, &mut root, indexes(0x1000)).unwrap();
+    assert_eq!(entry.address(), 0x8000_0000);
+    entry.clear();
+    assert_eq!(paging::prune(&mut frames, &mut root, indexes(0x1000)), 1);
+    assert_eq!(frames.tables(), 2);
+    assert_eq!(frames.flushes, 1);
+
+    paging::leaf_mut(&mut frames, &mut root, indexes(0x20_0000))
+        .unwrap()
+        .clear();
+    assert_eq!(paging::prune(&mut frames, &mut root, indexes(0x20_0000)), 2);
+    assert_eq!(frames.tables(), 0);
+    assert!(root.iter().all(|entry| !entry.present()));
+
+    // Nothing is left to prune, and no flush is needed.
+    assert_eq!(paging::prune(&mut frames, &mut root, indexes(0x20_0000)), 0);
+    assert_eq!(frames.flushes, 2);
+    assert_eq!(
+        paging::leaf_mut(&mut frames, &mut root, indexes(0x1000)).unwrap_err(),
+        UnmapError::NotMapped
+    );
+}
+
+#[test]
+fn huge_pages_are_split_when_changed() {
+    let mut frames = FakeFrames::default();
+    let mut root = [FakeEntry::default(); ENTRIES];
+    map(&mut frames, &mut root, 0x4000_0000, 0x8000_0000, 0).unwrap();
+    assert_eq!(frames.tables(), 0);
+
+    // The 1 GiB page is split into 2 MiB pages, and the one containing the
+    // address into 4 KiB pages.
+    let entry = paging::leaf_mut(&mut frames, &mut root, indexes(0x4020_3000)).unwrap();
+    assert_eq!(entry.address(), 0x8020_3000);
+    assert_eq!(entry.level(), 2);
+    entry.clear();
+    assert_eq!(frames.tables(), 2);
+    assert_eq!(frames.flushes, 2);
+
+    // The rest of the huge page is still mapped at the same addresses, with
+    // the same flags.
+    assert_eq!(translate(&frames, &root, 0x4020_2FFF), Some(0x8020_2FFF));
+    assert_eq!(translate(&frames, &root, 0x4020_3000), None);
+    assert_eq!(translate(&frames, &root, 0x4020_4000), Some(0x8020_4000));
+    assert_eq!(translate(&frames, &root, 0x7FFF_FFFF), Some(0xBFFF_FFFF));
+    let (entry, level) = paging::leaf(&frames, &root, indexes(0x4060_0000)).unwrap();
+    assert_eq!(level, 1);
+    assert_eq!(entry.level(), 1);
+    assert_eq!(entry.0 & FakeEntry::WRITABLE, FakeEntry::WRITABLE);
+}
+
+#[test]
+fn huge_pages_are_left_unchanged_when_out_of_memory() {
+    let mut frames = FakeFrames::limited(1);
+    let mut root = [FakeEntry::default(); ENTRIES];
+    map(&mut frames, &mut root, 0x4000_0000, 0x8000_0000, 0).unwrap();
+
+    // The first split succeeds, but the second cannot allocate its table.
+    assert_eq!(
+        paging::leaf_mut(&mut frames, &mut root, indexes(0x4020_3000)).unwrap_err(),
+        UnmapError::OutOfMemory
+    );
+    assert_eq!(translate(&frames, &root, 0x4020_3000), Some(0x8020_3000));
+    assert_eq!(
+        paging::leaf(&frames, &root, indexes(0x4020_3000))
+            .unwrap()
+            .1,
+        1
+    );
+}
+
+#[test]
+fn releasing_frees_tables_and_pages() {
+    let mut frames = FakeFrames::default();
+    let mut root = [FakeEntry::default(); ENTRIES];
+    map(&mut frames, &mut root, 0x1000, 0x8000_0000, 2).unwrap();
+    map(&mut frames, &mut root, 0x20_0000, 0x8040_0000, 1).unwrap();
+    map(&mut frames, &mut root, 0x4000_0000, 0xC000_0000, 0).unwrap();
+
+    // The registers of a device are unmapped but not freed.
+    let device = paging::map(&mut frames, &mut root, indexes(0x2000), 2).unwrap();
+    *device = FakeEntry::page(0x1000_0000, 2, FakeEntry::DEVICE);
+
+    // The pages: 1 + 512 + 512 * 512 frames, plus the two tables.
+    let released = paging::release(&mut frames, &mut root[..256], 0);
+    assert_eq!(released, 1 + 512 + 512 * 512 + 2);
+    assert_eq!(frames.tables(), 0);
+    assert!(root.iter().all(|entry| !entry.present()));
+
+    frames.freed.sort_unstable();
+    assert_eq!(
+        frames.freed,
+        [
+            (0x8000_0000, 1),
+            (0x8040_0000, 512),
+            (0xC000_0000, 512 * 512)
+        ]
+    );
+}
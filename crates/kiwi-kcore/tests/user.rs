@@ -0,0 +1,86 @@
+use kiwi_kcore::user::{Access, AddressSpace, Validator};
+
+const PAGE_SIZE: usize = 0x1000;
+const LIMIT: usize = 0x10_0000;
+const VALIDATOR: Validator = Validator::new(LIMIT, PAGE_SIZE);
+
+/// An address space where only the given pages are mapped, either read-only
+/// or read-write.
+struct FakeSpace {
+    readable: &'static [usize],
+    writable: &'static [usize],
+}
+
+impl AddressSpace for FakeSpace {
+    fn allows(&self, page: usize, access: Access) -> bool {
+        match access {
+            Access::Read => self.readable.contains(&page) || self.writable.contains(&page),
+            Access::Write => self.writable.contains(&page),
+        }
+    }
+}
+
+const SPACE: FakeSpace = FakeSpace {
+    readable: &[0x1000, 0x2000],
+    writable: &[0x3000, 0xFF000],
+};
+
+#[test]
+fn objects_must_be_in_user_space() {
+    assert_eq!(VALIDATOR.range(0x1000, 16), Some(0x1000..0x1010));
+    assert_eq!(VALIDATOR.range(LIMIT - 16, 16), Some(LIMIT - 16..LIMIT));
+    assert_eq!(VALIDATOR.range(LIMIT - 16, 17), None);
+    assert_eq!(VALIDATOR.range(LIMIT, 0), None);
+    assert_eq!(VALIDATOR.range(usize::MAX, 2), None);
+    assert_eq!(VALIDATOR.range(0x1000, usize::MAX), None);
+}
+
+#[test]
+fn every_page_is_checked() {
+    let check = |start, size, access| VALIDATOR.check(&SPACE, start, size, access).is_some();
+    assert!(check(0x1800, 0x2800, Access::Read));
+    assert!(!check(0x1800, 0x2801, Access::Read));
+    assert!(!check(0x1800, 0x2800, Access::Write));
+    assert!(check(0x3800, 0x800, Access::Write));
+    assert!(!check(0x3800, 0x801, Access::Write));
+    assert!(!check(0x800, 0x1000, Access::Read));
+}
+
+#[test]
+fn empty_objects_need_their_page() {
+    assert_eq!(
+        VALIDATOR.check(&SPACE, 0x1234, 0, Access::Read),
+        Some(0x1234..0x1234)
+    );
+    assert_eq!(VALIDATOR.check(&SPACE, 0x4234, 0, Access::Read), None);
+}
+
+#[test]
+fn objects_can_end_at_the_limit() {
+    assert_eq!(
+        VALIDATOR.check(&SPACE, LIMIT - 8, 8, Access::Write),
+        Some(LIMIT - 8..LIMIT)
+    );
+    assert_eq!(VALIDATOR.check(&SPACE, LIMIT - 8, 9, Access::Read), None);
+}
+
+/// The last userland address on both riscv64 and aarch64, as used by the
+/// kernel to build its own validator.
+const USER_END: usize = 0x0000_003F_FFFF_FFFF;
+
+#[test]
+fn kernel_boundaries() {
+    let validator = Validator::new(USER_END + 1, PAGE_SIZE);
+    assert_eq!(
+        validator.range(USER_END - 7, 8),
+        Some(USER_END - 7..USER_END + 1)
+    );
+    assert_eq!(validator.range(USER_END - 7, 9), None);
+    assert_eq!(validator.range(USER_END + 1, 1), None);
+    assert_eq!(
+        validator.range(USER_END - 7, usize::MAX - USER_END + 9),
+        None
+    );
+    assert_eq!(validator.range(USER_END, 0), Some(USER_END..USER_END));
+    assert_eq!(validator.range(USER_END + 1, 0), None);
+}
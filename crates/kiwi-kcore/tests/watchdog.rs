@@ -0,0 +1,90 @@
+use core::{cell::Cell, time::Duration};
+use kiwi_kcore::watchdog::{Clock, Detector, Policy};
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A clock that only moves forward when told to.
+#[derive(Default)]
+struct FakeClock(Cell<u64>);
+
+impl FakeClock {
+    fn advance(&self, duration: Duration) {
+        self.0
+            .set(self.0.get() + u64::try_from(duration.as_nanos()).unwrap());
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> u64 {
+        self.0.get()
+    }
+}
+
+#[test]
+fn progress_resets_the_timeout() {
+    let clock = FakeClock::default();
+    let detector = Detector::new();
+    detector.start(&clock);
+
+    for _ in 0..10 {
+        clock.advance(TIMEOUT / 2);
+        detector.pet();
+        assert_eq!(
+            detector.check(&clock, TIMEOUT, Policy::Panic, || None),
+            None
+        );
+    }
+}
+
+#[test]
+fn stall_is_detected_after_the_timeout() {
+    let clock = FakeClock::default();
+    let detector = Detector::new();
+    detector.start(&clock);
+
+    clock.advance(TIMEOUT / 2);
+    assert_eq!(
+        detector.check(&clock, TIMEOUT, Policy::Panic, || None),
+        None
+    );
+    clock.advance(TIMEOUT);
+    assert_eq!(
+        detector.check(&clock, TIMEOUT, Policy::Panic, || None),
+        Some(TIMEOUT * 3 / 2)
+    );
+}
+
+#[test]
+fn running_task_is_marked_runaway_first() {
+    let clock = FakeClock::default();
+    let detector = Detector::new();
+    detector.start(&clock);
+
+    clock.advance(TIMEOUT);
+    let check = || detector.check(&clock, TIMEOUT, Policy::TerminateTask, || Some(7));
+    assert_eq!(check(), None);
+    assert!(!detector.take_runaway(3));
+
+    // The core is given another timeout to recover, and is considered locked
+    // up if the task is still running after it.
+    clock.advance(TIMEOUT / 2);
+    assert_eq!(check(), None);
+    clock.advance(TIMEOUT / 2);
+    assert_eq!(check(), Some(TIMEOUT));
+
+    assert!(detector.take_runaway(7));
+    assert!(!detector.take_runaway(7));
+}
+
+#[test]
+fn stall_without_running_task_is_a_lockup() {
+    let clock = FakeClock::default();
+    let detector = Detector::new();
+    detector.start(&clock);
+
+    clock.advance(TIMEOUT);
+    assert_eq!(
+        detector.check(&clock, TIMEOUT, Policy::TerminateTask, || None),
+        Some(TIMEOUT)
+    );
+}
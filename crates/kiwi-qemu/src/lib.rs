@@ -0,0 +1,165 @@
+//! A runner booting the kernel in QEMU to run its integration test suite.
+//! The kernel must have been built with the `integration` feature, along with
+//! the user programs (see the `test-qemu` target of the top-level makefile).
+//! It then runs the test cases by itself, logs the result of each of them on
+//! the serial console and shuts down the machine, making QEMU exit with a
+//! non-zero status if any case failed.
+//!
+//! The runner captures the output of the serial console and the exit status
+//! of QEMU, so that the tests of this crate can assert on both of them.
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The architecture of the kernel to boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    /// The `virt` machine of `qemu-system-riscv64`.
+    Riscv64,
+
+    /// The `virt` machine of `qemu-system-aarch64`, with a Cortex-A72.
+    Aarch64,
+}
+
+impl Arch {
+    /// The target triple the kernel is built for on this architecture.
+    #[must_use]
+    pub const fn target(self) -> &'static str {
+        match self {
+            Self::Riscv64 => "riscv64gc-unknown-none-elf",
+            Self::Aarch64 => "aarch64-unknown-none-softfloat",
+        }
+    }
+
+    /// The path of the kernel binary built for this architecture.
+    #[must_use]
+    pub fn kernel(self) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../kernel/target")
+            .join(self.target())
+            .join("release/kernel")
+    }
+
+    /// The QEMU command booting the kernel of this architecture. The machine
+    /// is the same as the one used by `cargo run` in the kernel directory,
+    /// except that semihosting is enabled on aarch64 so that the kernel can
+    /// give its exit status to QEMU.
+    #[must_use]
+    pub fn command(self) -> Command {
+        let mut command = match self {
+            Self::Riscv64 => {
+                let mut command = Command::new("qemu-system-riscv64");
+                command.args(["-machine", "virt"]);
+                command
+            }
+            Self::Aarch64 => {
+                let mut command = Command::new("qemu-system-aarch64");
+                command.args(["-machine", "virt,gic-version=3", "-cpu", "cortex-a72"]);
+                command.arg("-semihosting");
+                command
+            }
+        };
+        command
+            .args(["-serial", "mon:stdio", "-nographic", "-m", "32M"])
+            .arg("-kernel")
+            .arg(self.kernel());
+        command
+    }
+}
+
+/// The outcome of a run of the integration test suite.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Whether QEMU exited with a zero status, meaning that the kernel
+    /// reported that all test cases passed.
+    pub success: bool,
+
+    /// The name of each test case whose result was logged by the kernel, and
+    /// whether it passed, in the order in which they terminated.
+    pub results: Vec<(String, bool)>,
+
+    /// Everything written on the serial console.
+    pub output: String,
+}
+
+impl Report {
+    /// Returns whether the test case with the given name passed. Returns
+    /// `None` if its result was never logged.
+    #[must_use]
+    pub fn passed(&self, name: &str) -> Option<bool> {
+        self.results
+            .iter()
+            .find(|(test, _)| test == name)
+            .map(|&(_, passed)| passed)
+    }
+}
+
+/// Boots the kernel of the given architecture in QEMU and waits until the
+/// machine shuts down, then returns the results of the test suite.
+///
+/// # Errors
+/// Returns an error if QEMU cannot be started, or if the machine is still
+/// running after the given timeout, in which case QEMU is killed.
+pub fn run(arch: Arch, timeout: Duration) -> io::Result<Report> {
+    let mut child = arch
+        .command()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+
+    let mut stdout = child.stdout.take().expect("The output of QEMU is piped");
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        _ = stdout.read_to_end(&mut output);
+        String::from_utf8_lossy(&output).into_owned()
+    });
+
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if start.elapsed() > timeout {
+            _ = child.kill();
+            _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "the kernel did not shut down in time",
+            ));
+        }
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    let output = reader.join().unwrap_or_default();
+    Ok(Report {
+        success: status.success(),
+        results: parse(&output),
+        output,
+    })
+}
+
+/// Extracts the results of the test cases from the output of the kernel. Each
+/// result is logged on its own line as `test <name> ... ok`, or as
+/// `test <name> ... FAILED` followed by the reason of the failure.
+#[must_use]
+pub fn parse(output: &str) -> Vec<(String, bool)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (_, result) = line.split_once("test ")?;
+            let (name, status) = result.split_once(" ... ")?;
+            if status.starts_with("ok") {
+                Some((name.to_owned(), true))
+            } else if status.starts_with("FAILED") {
+                Some((name.to_owned(), false))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
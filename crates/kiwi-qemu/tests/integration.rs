@@ -0,0 +1,72 @@
+//! Boots the kernel in QEMU and checks the results of its integration test
+//! suite. These tests need QEMU and a kernel built with the `integration`
+//! feature, so they are ignored by default: run them with `make test-qemu`.
+use kiwi_qemu::{Arch, Report};
+use std::time::Duration;
+
+/// How long the kernel may run before the test suite is considered stuck.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The test cases run by the kernel, which must all be reported.
+const CASES: [&str; 9] = [
+    "ipc",
+    "memory",
+    "service-rename",
+    "reconnect",
+    "spawn-exit",
+    "panic",
+    "null-dereference",
+    "read-only-write",
+    "illegal-instruction",
+];
+
+/// Checks that all test cases passed, and that the kernel reported it both on
+/// the serial console and through the exit status of QEMU.
+fn check(report: &Report) {
+    for case in CASES {
+        assert_eq!(
+            report.passed(case),
+            Some(true),
+            "test case {case} did not pass:\n{}",
+            report.output
+        );
+    }
+    assert!(
+        report.output.contains("test result: ok."),
+        "{}",
+        report.output
+    );
+    assert!(
+        report.success,
+        "QEMU reported a failure:\n{}",
+        report.output
+    );
+}
+
+#[test]
+#[ignore = "boots the kernel in QEMU, run with `make test-qemu`"]
+fn riscv64() {
+    let report = kiwi_qemu::run(Arch::Riscv64, TIMEOUT).expect("Failed to run QEMU");
+    check(&report);
+}
+
+#[test]
+#[ignore = "boots the kernel in QEMU, run with `make test-qemu ARCH=aarch64`"]
+fn aarch64() {
+    let report = kiwi_qemu::run(Arch::Aarch64, TIMEOUT).expect("Failed to run QEMU");
+    check(&report);
+}
+
+#[test]
+fn parse_results() {
+    let output = "\
+[INFO] Boot completed !
+[INFO] test ipc ... ok
+[ERROR] test memory ... FAILED (expected Exit(0), got Terminate(1))
+[INFO] test result: FAILED. 1 passed; 1 failed
+";
+    assert_eq!(
+        kiwi_qemu::parse(output),
+        [("ipc".to_owned(), true), ("memory".to_owned(), false)]
+    );
+}
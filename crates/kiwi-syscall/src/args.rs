@@ -0,0 +1,1010 @@
+//! Typed encoding of syscall arguments and results. Each syscall has a
+//! structure describing its arguments, that knows how to encode itself into
+//! the argument registers and how to decode itself from them. Both the kernel
+//! and user space use these structures, so that the register layout of a
+//! syscall is defined in a single place and cannot drift apart.
+use crate::{
+    SyscallOp,
+    capability::Capabilities,
+    device, ipc, log,
+    memory::Protection,
+    service::{self, RegisterFlags},
+    stats,
+    table::{ArgKind, SyscallDescriptor},
+    task, trace,
+};
+
+/// The raw argument registers of a syscall. These are the `a0`-`a5` registers
+/// on riscv64, and the `x0`-`x5` registers on aarch64.
+pub type Registers = [usize; 6];
+
+/// A value that can be passed in a single syscall register.
+pub trait Register: Copy {
+    /// The kind of the argument, as reported in the syscall table.
+    const KIND: ArgKind;
+
+    /// Encodes the value into a register.
+    fn to_register(self) -> usize;
+
+    /// Decodes the value from a register.
+    fn from_register(register: usize) -> Self;
+}
+
+impl Register for usize {
+    const KIND: ArgKind = ArgKind::Unsigned;
+
+    fn to_register(self) -> usize {
+        self
+    }
+
+    fn from_register(register: usize) -> Self {
+        register
+    }
+}
+
+impl Register for u32 {
+    const KIND: ArgKind = ArgKind::Unsigned;
+
+    fn to_register(self) -> usize {
+        self as usize
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn from_register(register: usize) -> Self {
+        register as u32
+    }
+}
+
+impl Register for i32 {
+    const KIND: ArgKind = ArgKind::Signed;
+
+    #[allow(clippy::cast_sign_loss)]
+    fn to_register(self) -> usize {
+        self as usize
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn from_register(register: usize) -> Self {
+        register as i32
+    }
+}
+
+impl Register for RegisterFlags {
+    const KIND: ArgKind = ArgKind::Flags;
+
+    fn to_register(self) -> usize {
+        self.bits()
+    }
+
+    fn from_register(register: usize) -> Self {
+        RegisterFlags::from_bits(register)
+    }
+}
+
+impl Register for Protection {
+    const KIND: ArgKind = ArgKind::Flags;
+
+    fn to_register(self) -> usize {
+        self.bits()
+    }
+
+    fn from_register(register: usize) -> Self {
+        Protection::from_bits_retain(register)
+    }
+}
+
+impl Register for Capabilities {
+    const KIND: ArgKind = ArgKind::Flags;
+
+    fn to_register(self) -> usize {
+        self.bits() as usize
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn from_register(register: usize) -> Self {
+        Capabilities::from_bits(register as u32)
+    }
+}
+
+impl<T> Register for *const T {
+    const KIND: ArgKind = ArgKind::ConstPointer;
+
+    fn to_register(self) -> usize {
+        self.expose_provenance()
+    }
+
+    fn from_register(register: usize) -> Self {
+        core::ptr::with_exposed_provenance(register)
+    }
+}
+
+impl<T> Register for *mut T {
+    const KIND: ArgKind = ArgKind::MutPointer;
+
+    fn to_register(self) -> usize {
+        self.expose_provenance()
+    }
+
+    fn from_register(register: usize) -> Self {
+        core::ptr::with_exposed_provenance_mut(register)
+    }
+}
+
+/// The arguments of a syscall.
+pub trait SyscallArgs: Sized {
+    /// The syscall operation that takes these arguments.
+    const OP: SyscallOp;
+
+    /// The description of the syscall, as reported in the syscall table.
+    const DESCRIPTOR: SyscallDescriptor;
+
+    /// Encodes the arguments into the argument registers. Unused registers
+    /// are set to zero.
+    fn encode(&self) -> Registers;
+
+    /// Decodes the arguments from the argument registers. Unused registers
+    /// are ignored.
+    fn decode(registers: &Registers) -> Self;
+}
+
+/// Defines the argument structure of a syscall and implements [`SyscallArgs`]
+/// for it. Fields are assigned to registers in declaration order. The error
+/// type of the syscall, if any, is given in parentheses after the operation.
+macro_rules! syscall_args {
+    (
+        $(#[$meta:meta])*
+        $name:ident => $op:ident $(($module:ident::$error:ident))? {
+            $($(#[$field_meta:meta])* $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub struct $name {
+            $($(#[$field_meta])* pub $field: $ty,)*
+        }
+
+        // Ensure that the error type named in the descriptor actually exists.
+        $(const _: Option<crate::$module::$error> = None;)?
+
+        impl SyscallArgs for $name {
+            const OP: SyscallOp = SyscallOp::$op;
+            const DESCRIPTOR: SyscallDescriptor = SyscallDescriptor::new(
+                SyscallOp::$op,
+                stringify!($op),
+                &[$(<$ty as Register>::KIND),*],
+                concat!("" $(, stringify!($module), "::", stringify!($error))?),
+            );
+
+            fn encode(&self) -> Registers {
+                let values: &[usize] = &[$(Register::to_register(self.$field)),*];
+                let mut registers = [0; 6];
+                registers[..values.len()].copy_from_slice(values);
+                registers
+            }
+
+            #[allow(unused_variables)]
+            fn decode(registers: &Registers) -> Self {
+                let [$($field,)* ..] = *registers;
+                Self {
+                    $($field: <$ty as Register>::from_register($field),)*
+                }
+            }
+        }
+    };
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::Nop`] syscall.
+    Nop => Nop {}
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskExit`] syscall.
+    TaskExit => TaskExit {
+        /// The exit code of the task.
+        code: i32,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskYield`] syscall.
+    TaskYield => TaskYield {}
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskSpawn`] syscall.
+    TaskSpawn => TaskSpawn (task::SpawnError) {
+        /// A pointer to the ELF image of the task.
+        image: *const u8,
+
+        /// The size of the ELF image, in bytes.
+        image_len: usize,
+
+        /// A pointer to the block of arguments and environment variables
+        /// given to the task (see [`crate::env`]).
+        env: *const u8,
+
+        /// The size of the block, in bytes.
+        env_len: usize,
+
+        /// The capabilities that the task may inherit. The task receives the
+        /// capabilities of the current task that are in this set.
+        capabilities: Capabilities,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskWait`] syscall.
+    TaskWait => TaskWait (task::WaitError) {
+        /// The identifier of the task to wait for.
+        task: usize,
+
+        /// Where the termination information should be written.
+        info: *mut task::TerminationInfo,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskUsage`] syscall.
+    TaskUsage => TaskUsage (task::UsageError) {
+        /// The identifier of the task.
+        task: usize,
+
+        /// Where the usage should be written.
+        usage: *mut task::Usage,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ServiceRegister`] syscall.
+    ServiceRegister => ServiceRegister (service::RegisterError) {
+        /// A pointer to the UTF-8 name of the service.
+        name: *const u8,
+
+        /// The length of the name, in bytes.
+        name_len: usize,
+
+        /// The registration flags.
+        flags: RegisterFlags,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ServiceUnregister`] syscall.
+    ServiceUnregister => ServiceUnregister (service::UnregisterError) {}
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ServiceConnect`] syscall.
+    ServiceConnect => ServiceConnect (service::ConnectionError) {
+        /// A pointer to the UTF-8 name of the service.
+        name: *const u8,
+
+        /// The length of the name, in bytes.
+        name_len: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ServiceConnectWait`] syscall.
+    ServiceConnectWait => ServiceConnectWait (service::ConnectionError) {
+        /// A pointer to the UTF-8 name of the service.
+        name: *const u8,
+
+        /// The length of the name, in bytes.
+        name_len: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ServiceDisconnect`] syscall.
+    ServiceDisconnect => ServiceDisconnect (service::DisconnectError) {
+        /// The handle of the connection to close.
+        handle: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ServiceStatistics`] syscall.
+    ServiceStatistics => ServiceStatistics (service::StatisticsError) {
+        /// A pointer to the UTF-8 name of the service.
+        name: *const u8,
+
+        /// The length of the name, in bytes.
+        name_len: usize,
+
+        /// Where the statistics should be written.
+        buffer: *mut service::KindStatistics,
+
+        /// The number of entries that fit in the buffer.
+        capacity: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ServiceList`] syscall.
+    ServiceList => ServiceList (service::ListError) {
+        /// Where the service information should be written.
+        buffer: *mut service::ServiceInfo,
+
+        /// The number of entries that fit in the buffer.
+        capacity: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IpcSend`] syscall.
+    IpcSend => IpcSend (ipc::SendError) {
+        /// The message to send.
+        message: *const ipc::Message,
+
+        /// Where the reply should be written.
+        reply: *mut ipc::Reply,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IpcSendOneway`] syscall.
+    IpcSendOneway => IpcSendOneway (ipc::SendError) {
+        /// The message to send.
+        message: *const ipc::Message,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IpcReceive`] syscall.
+    IpcReceive => IpcReceive (ipc::ReceiveError) {
+        /// Where the received message should be written.
+        message: *mut ipc::Message,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IpcTryReceive`] syscall.
+    IpcTryReceive => IpcTryReceive (ipc::ReceiveError) {
+        /// Where the received message should be written.
+        message: *mut ipc::Message,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IpcReply`] syscall.
+    IpcReply => IpcReply (ipc::ReplyError) {
+        /// The identifier of the task to reply to.
+        to: usize,
+
+        /// The reply to send.
+        reply: *const ipc::Reply,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IpcForward`] syscall.
+    IpcForward => IpcForward (ipc::ForwardError) {
+        /// The identifier of the client whose message is forwarded, and which
+        /// will receive the reply of the service instead of the current task.
+        to: usize,
+
+        /// The message to forward. Its receiver is the handle of a connection
+        /// to the service the message is forwarded to.
+        message: *const ipc::Message,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::SystemStatistics`] syscall.
+    SystemStatistics => SystemStatistics (stats::StatisticsError) {
+        /// Where the statistics should be written.
+        statistics: *mut stats::SystemStatistics,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::SyscallTable`] syscall.
+    SyscallTable => SyscallTable (table::TableError) {
+        /// The buffer where the syscall descriptors are written.
+        buffer: *mut SyscallDescriptor,
+
+        /// The maximum number of descriptors that can be written into the
+        /// buffer.
+        capacity: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::MemProtect`] syscall.
+    MemProtect => MemProtect (memory::ProtectError) {
+        /// The page-aligned start address of the range.
+        address: usize,
+
+        /// The size of the range, in bytes. It is rounded up to a whole
+        /// number of pages.
+        size: usize,
+
+        /// The new protection of the range.
+        protection: Protection,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::MemMap`] syscall.
+    MemMap => MemMap (memory::MapError) {
+        /// The page-aligned start address of the range.
+        address: usize,
+
+        /// The size of the range, in bytes. It is rounded up to a whole
+        /// number of pages.
+        size: usize,
+
+        /// The protection of the mapped memory.
+        protection: Protection,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ConsoleWrite`] syscall.
+    ConsoleWrite => ConsoleWrite (console::WriteError) {
+        /// A pointer to the bytes to write.
+        buffer: *const u8,
+
+        /// The number of bytes to write, at most
+        /// [`crate::console::MAX_WRITE_SIZE`].
+        len: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ConsoleRead`] syscall.
+    ConsoleRead => ConsoleRead (console::ReadError) {
+        /// A pointer to the buffer receiving the bytes read.
+        buffer: *mut u8,
+
+        /// The size of the buffer, in bytes.
+        capacity: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IrqRegister`] syscall.
+    IrqRegister => IrqRegister (irq::RegisterError) {
+        /// The interrupt source to bind, as numbered by the interrupt
+        /// controller of the platform.
+        irq: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IrqWait`] syscall.
+    IrqWait => IrqWait (irq::WaitError) {
+        /// The interrupt source to wait for, previously bound with
+        /// [`SyscallOp::IrqRegister`].
+        irq: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IrqMask`] syscall.
+    IrqMask => IrqMask (irq::ControlError) {
+        /// The interrupt source to mask, previously bound with
+        /// [`SyscallOp::IrqRegister`].
+        irq: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IrqUnmask`] syscall.
+    IrqUnmask => IrqUnmask (irq::ControlError) {
+        /// The interrupt source to unmask, previously bound with
+        /// [`SyscallOp::IrqRegister`].
+        irq: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::IrqSetAffinity`] syscall.
+    IrqSetAffinity => IrqSetAffinity (irq::AffinityError) {
+        /// The interrupt source to route, previously bound with
+        /// [`SyscallOp::IrqRegister`].
+        irq: usize,
+
+        /// The index of the CPU that should receive the interrupts of the
+        /// source, starting from 0 for the boot CPU.
+        cpu: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ConsoleReadWait`] syscall.
+    ConsoleReadWait => ConsoleReadWait (console::ReadError) {
+        /// A pointer to the buffer receiving the bytes read.
+        buffer: *mut u8,
+
+        /// The size of the buffer, in bytes.
+        capacity: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::DeviceQuery`] syscall.
+    DeviceQuery => DeviceQuery (device::QueryError) {
+        /// The index of the device, starting from 0.
+        device: usize,
+
+        /// A pointer to the buffer receiving the description of the device.
+        info: *mut device::Device,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::DeviceTreeQuery`] syscall.
+    DeviceTreeQuery => DeviceTreeQuery (device::TreeQueryError) {
+        /// A pointer to the UTF-8 path of the node, such as
+        /// `/soc/serial@10000000`.
+        path: *const u8,
+
+        /// The length of the path, in bytes.
+        path_len: usize,
+
+        /// A pointer to the UTF-8 name of the property, such as `reg`.
+        property: *const u8,
+
+        /// The length of the name of the property, in bytes.
+        property_len: usize,
+
+        /// A pointer to the buffer receiving the value of the property.
+        buffer: *mut u8,
+
+        /// The size of the buffer, in bytes.
+        capacity: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::MemMapDevice`] syscall.
+    MemMapDevice => MemMapDevice (device::MapError) {
+        /// The index of the device, starting from 0.
+        device: usize,
+
+        /// The page-aligned address where the registers of the device are
+        /// mapped.
+        address: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::DebugWrite`] syscall.
+    DebugWrite => DebugWrite (debug::WriteError) {
+        /// A pointer to the UTF-8 string to write.
+        string: *const u8,
+
+        /// The length of the string, in bytes.
+        len: usize,
+    }
+}
+
+/// The value returned by a syscall in the return register. Successful
+/// syscalls return a non-negative value, while failed syscalls return the
+/// negated error code, between -1 and -255 (inclusive).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct SyscallResult(usize);
+
+impl SyscallResult {
+    /// The highest error code that a syscall can return.
+    pub const MAX_ERROR_CODE: isize = 255;
+
+    /// Creates a successful result with the given value.
+    #[must_use]
+    pub const fn ok(value: usize) -> Self {
+        Self(value)
+    }
+
+    /// Creates a failed result with the given (positive) error code.
+    #[must_use]
+    pub const fn err(code: isize) -> Self {
+        Self((-code).cast_unsigned())
+    }
+
+    /// Creates a result from the raw value of the return register.
+    #[must_use]
+    pub const fn from_raw(raw: usize) -> Self {
+        Self(raw)
+    }
+
+    /// Returns the raw value to put in the return register.
+    #[must_use]
+    pub const fn raw(&self) -> usize {
+        self.0
+    }
+
+    /// Returns whether the syscall failed.
+    #[must_use]
+    pub const fn failed(&self) -> bool {
+        let value = self.0.cast_signed();
+        value < 0 && value >= -Self::MAX_ERROR_CODE
+    }
+
+    /// Decodes the result, returning the (positive) error code if the
+    /// syscall failed.
+    ///
+    /// # Errors
+    /// Returns the error code of the syscall if it failed.
+    pub const fn decode(&self) -> Result<usize, isize> {
+        if self.failed() {
+            Err(-self.0.cast_signed())
+        } else {
+            Ok(self.0)
+        }
+    }
+}
+
+impl<E: Into<isize>> From<Result<usize, E>> for SyscallResult {
+    fn from(result: Result<usize, E>) -> Self {
+        match result {
+            Ok(value) => Self::ok(value),
+            Err(error) => Self::err(error.into()),
+        }
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::MemAllocDma`] syscall.
+    MemAllocDma => MemAllocDma (memory::AllocDmaError) {
+        /// The page-aligned address where the memory is mapped.
+        address: usize,
+
+        /// The size of the memory, in bytes. It is rounded up to a whole
+        /// number of pages, and must not exceed
+        /// [`crate::memory::MAX_DMA_SIZE`].
+        size: usize,
+
+        /// A pointer to the buffer receiving the physical address of the
+        /// memory, to be given to the devices.
+        physical: *mut usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::MemPressureSubscribe`] syscall.
+    MemPressureSubscribe => MemPressureSubscribe (memory::SubscribeError) {
+        /// The operation of the messages notifying the changes of the
+        /// memory pressure.
+        operation: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::MemPressureUnsubscribe`] syscall.
+    MemPressureUnsubscribe => MemPressureUnsubscribe (memory::UnsubscribeError) {}
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskKill`] syscall.
+    TaskKill => TaskKill (task::KillError) {
+        /// The identifier of the task to terminate.
+        task: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskSetLimit`] syscall.
+    TaskSetLimit => TaskSetLimit (task::SetLimitError) {
+        /// The identifier of the task, or [`task::CURRENT_TASK`] for the
+        /// calling task.
+        task: usize,
+
+        /// The raw [`task::Limit`] to change.
+        limit: usize,
+
+        /// The new value of the limit, or [`task::UNLIMITED`].
+        value: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskSetPriority`] syscall.
+    TaskSetPriority => TaskSetPriority (task::SetPriorityError) {
+        /// The identifier of the task, or [`task::CURRENT_TASK`] for the
+        /// calling task.
+        task: usize,
+
+        /// The raw [`task::Priority`] of the task.
+        priority: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskSetEssential`] syscall.
+    TaskSetEssential => TaskSetEssential (task::SetEssentialError) {
+        /// The identifier of the task, or [`task::CURRENT_TASK`] for the
+        /// calling task.
+        task: usize,
+
+        /// Whether the task is essential: any value other than zero marks it
+        /// as essential, and zero makes it an ordinary task again.
+        essential: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskTrace`] syscall.
+    TaskTrace => TaskTrace (task::TraceError) {
+        /// The identifier of the task, or [`task::CURRENT_TASK`] for the
+        /// calling task.
+        task: usize,
+
+        /// Whether the syscalls of the task are traced: any value other than
+        /// zero enables tracing, and zero disables it.
+        enabled: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskSuspend`] syscall.
+    TaskSuspend => TaskSuspend (task::DebugError) {
+        /// The identifier of the task.
+        task: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskResume`] syscall.
+    TaskResume => TaskResume (task::DebugError) {
+        /// The identifier of the task, or of the thread to single-step.
+        task: usize,
+
+        /// Whether to single-step the thread: any value other than zero makes
+        /// the thread execute a single instruction and stop again, while the
+        /// other threads of its task stay stopped. Zero resumes all the
+        /// threads of the task.
+        step: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskGetRegs`] syscall.
+    TaskGetRegs => TaskGetRegs (task::DebugError) {
+        /// The identifier of the thread, which is also the identifier of the
+        /// task for its first thread.
+        thread: usize,
+
+        /// Where the registers should be written.
+        registers: *mut task::Registers,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskSetRegs`] syscall.
+    TaskSetRegs => TaskSetRegs (task::DebugError) {
+        /// The identifier of the thread, which is also the identifier of the
+        /// task for its first thread.
+        thread: usize,
+
+        /// A pointer to the new registers of the thread.
+        registers: *const task::Registers,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::SyncCreate`] syscall.
+    SyncCreate => SyncCreate (sync::CreateError) {
+        /// The raw [`sync::Kind`] of the object.
+        kind: usize,
+
+        /// The initial value of the object.
+        value: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::SyncSignal`] syscall.
+    SyncSignal => SyncSignal (sync::SignalError) {
+        /// The identifier of the object.
+        object: usize,
+
+        /// The count added to a semaphore, or the bits set in a notification.
+        /// It is ignored for an event.
+        value: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::SyncWait`] syscall.
+    SyncWait => SyncWait (sync::WaitError) {
+        /// The identifier of the object.
+        object: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::SyncClear`] syscall.
+    SyncClear => SyncClear (sync::ClearError) {
+        /// The identifier of the object.
+        object: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::SyncDestroy`] syscall.
+    SyncDestroy => SyncDestroy (sync::DestroyError) {
+        /// The identifier of the object.
+        object: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::FutexWait`] syscall.
+    FutexWait => FutexWait (futex::WaitError) {
+        /// The address of the futex word, aligned on
+        /// [`crate::futex::ALIGNMENT`].
+        address: *const u32,
+
+        /// The value that the word must hold for the task to sleep.
+        expected: u32,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::FutexWake`] syscall.
+    FutexWake => FutexWake (futex::WakeError) {
+        /// The address of the futex word, aligned on
+        /// [`crate::futex::ALIGNMENT`].
+        address: *const u32,
+
+        /// The maximum number of tasks to wake up.
+        count: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ThreadCreate`] syscall.
+    ThreadCreate => ThreadCreate (thread::CreateError) {
+        /// The address where the thread starts its execution.
+        entry: usize,
+
+        /// The initial stack pointer of the thread.
+        stack: usize,
+
+        /// The value given to the thread in its first argument register.
+        argument: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ThreadExit`] syscall.
+    ThreadExit => ThreadExit {
+        /// The exit code of the thread.
+        code: i32,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ThreadJoin`] syscall.
+    ThreadJoin => ThreadJoin (thread::JoinError) {
+        /// The identifier of the thread to wait for.
+        thread: usize,
+
+        /// A pointer to where the exit code of the thread is written.
+        code: *mut i32,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TaskDropCaps`] syscall.
+    TaskDropCaps => TaskDropCaps {
+        /// The capabilities to give up.
+        capabilities: Capabilities,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::ServiceAccept`] syscall.
+    ServiceAccept => ServiceAccept (service::AcceptError) {
+        /// The identifier of the task allowed to connect to the service.
+        task: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::LogControl`] syscall.
+    LogControl => LogControl (log::ControlError) {
+        /// A pointer to the UTF-8 name of the module, which is ignored if its
+        /// length is zero to change the global level instead.
+        module: *const u8,
+
+        /// The length of the name of the module, in bytes.
+        module_len: usize,
+
+        /// The raw new [`log::Level`].
+        level: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::LogRead`] syscall.
+    LogRead => LogRead (log::ReadError) {
+        /// The number of the first message to read. If this message was
+        /// already overwritten, reading starts at the oldest message kept.
+        sequence: usize,
+
+        /// Where the messages should be written.
+        buffer: *mut log::Record,
+
+        /// The number of messages that fit in the buffer.
+        capacity: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::GetRandom`] syscall.
+    GetRandom => GetRandom (random::FillError) {
+        /// Where the random bytes should be written.
+        buffer: *mut u8,
+
+        /// The number of random bytes to write, at most
+        /// [`crate::random::MAX_FILL_SIZE`].
+        len: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TimerCreate`] syscall.
+    TimerCreate => TimerCreate (timer::CreateError) {
+        /// The interval between two expirations of the timer, in nanoseconds.
+        /// The first expiration happens one interval after the creation.
+        interval: usize,
+
+        /// The raw [`crate::timer::Delivery`] of the expirations.
+        delivery: usize,
+
+        /// The operation of the messages, or the identifier of the object to
+        /// signal, depending on the delivery.
+        target: usize,
+
+        /// The value the object is signaled with. It is ignored when the
+        /// expirations are delivered as messages.
+        value: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TimerDestroy`] syscall.
+    TimerDestroy => TimerDestroy (timer::DestroyError) {
+        /// The identifier of the timer.
+        timer: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::SystemPower`] syscall.
+    SystemPower => SystemPower (power::PowerError) {
+        /// The raw [`crate::power::Action`] to perform.
+        action: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::TraceRead`] syscall.
+    TraceRead => TraceRead (trace::ReadError) {
+        /// Where the events should be written.
+        buffer: *mut trace::Event,
+
+        /// The number of events that fit in the buffer.
+        capacity: usize,
+    }
+}
+
+syscall_args! {
+    /// The arguments of the [`SyscallOp::KernelStatistics`] syscall.
+    KernelStatistics => KernelStatistics (stats::StatisticsError) {
+        /// Where the statistics should be written.
+        statistics: *mut stats::KernelStatistics,
+
+        /// Where the number of invocations of each syscall should be written,
+        /// in the order of [`crate::table::TABLE`].
+        syscalls: *mut stats::SyscallCount,
+
+        /// The number of entries that fit in the syscall buffer.
+        capacity: usize,
+    }
+}
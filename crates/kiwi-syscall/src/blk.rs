@@ -0,0 +1,174 @@
+//! The protocol spoken between block device drivers and their clients. Like
+//! the file protocol (see [`crate::fs`]), the operation is given in the `kind`
+//! field of the message and its request structure in the payload, and the
+//! status of the reply is encoded like the result of a syscall.
+//!
+//! Clients address the device by byte offsets rather than by sectors, since a
+//! sector does not fit in the payload of a message: the driver reads and
+//! writes whole sectors on the device, and only transfers the requested part.
+use crate::{args::SyscallResult, ipc::MAX_PAYLOAD_SIZE};
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+pub use crate::ipc::Payload;
+
+/// The name under which the driver of the first block device registers
+/// itself.
+pub const SERVICE_NAME: &str = "blk";
+
+/// The maximum number of bytes that can be read with a single request. The
+/// data is returned as the payload of the reply.
+pub const MAX_READ_SIZE: usize = MAX_PAYLOAD_SIZE;
+
+/// The maximum number of bytes that can be written with a single request.
+pub const MAX_WRITE_SIZE: usize = MAX_PAYLOAD_SIZE - 16;
+
+impl Payload for ReadRequest {}
+impl Payload for WriteRequest {}
+impl Payload for Info {}
+
+/// An operation that can be requested to a block device driver, given in the
+/// `kind` field of the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum Operation {
+    /// An unknown operation, which drivers must reject with
+    /// [`BlockError::Unsupported`].
+    Unknown = 0,
+
+    /// Returns the geometry of the device. The request has no payload, and
+    /// the payload of the reply holds an [`Info`] structure.
+    Info = 1,
+
+    /// Reads from the device ([`ReadRequest`]). The status of the reply is the
+    /// number of bytes read, and its payload holds the data. Fewer bytes than
+    /// requested are only read at the end of the device.
+    Read = 2,
+
+    /// Writes to the device ([`WriteRequest`]). The status of the reply is the
+    /// number of bytes written.
+    Write = 3,
+}
+
+impl From<usize> for Operation {
+    fn from(kind: usize) -> Self {
+        match kind {
+            1 => Operation::Info,
+            2 => Operation::Read,
+            3 => Operation::Write,
+            _ => Operation::Unknown,
+        }
+    }
+}
+
+/// The request of the [`Operation::Read`] operation.
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct ReadRequest {
+    /// The offset on the device of the first byte to read.
+    pub offset: u64,
+
+    /// The number of bytes to read, at most [`MAX_READ_SIZE`].
+    pub len: u64,
+}
+
+/// The request of the [`Operation::Write`] operation.
+#[derive(Debug, Clone, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct WriteRequest {
+    /// The offset on the device where the data is written.
+    pub offset: u64,
+
+    /// The number of bytes to write.
+    pub len: u64,
+
+    /// The data to write. Only the first `len` bytes are valid.
+    pub data: [u8; MAX_WRITE_SIZE],
+}
+
+impl WriteRequest {
+    /// Creates a request to write the given data at the given offset of the
+    /// device. Only the first [`MAX_WRITE_SIZE`] bytes of the data are written
+    /// if it is longer.
+    #[must_use]
+    pub fn new(offset: u64, data: &[u8]) -> Self {
+        let len = data.len().min(MAX_WRITE_SIZE);
+        let mut request = Self {
+            offset,
+            len: len as u64,
+            data: [0; MAX_WRITE_SIZE],
+        };
+        request.data[..len].copy_from_slice(&data[..len]);
+        request
+    }
+
+    /// Returns the data to write, or `None` if the length of the data is
+    /// invalid.
+    #[must_use]
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.get(..usize::try_from(self.len).ok()?)
+    }
+}
+
+/// The reply of the [`Operation::Info`] operation.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct Info {
+    /// The size of the device, in bytes.
+    pub size: u64,
+
+    /// The size of the sectors of the device, in bytes. Requests that are not
+    /// aligned on sectors are slower, since the driver must read the whole
+    /// sectors before writing them.
+    pub sector_size: u64,
+
+    /// Whether the device is read-only (1) or writable (0).
+    pub read_only: u64,
+}
+
+impl Info {
+    /// Returns whether the device is read-only.
+    #[must_use]
+    pub const fn read_only(&self) -> bool {
+        self.read_only != 0
+    }
+}
+
+syscall_error! {
+    /// Errors that can be returned by a block device driver.
+    pub enum BlockError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The operation is not supported by the driver.
+        Unsupported = 1,
+
+        /// The request is malformed: its payload is too short, or one of its
+        /// fields is invalid.
+        BadRequest = 2,
+
+        /// The request starts past the end of the device.
+        OutOfRange = 3,
+
+        /// The device is read-only.
+        ReadOnly = 4,
+
+        /// The device reported an error while transferring the data.
+        Io = 5,
+    }
+}
+
+/// Encodes the result of an operation into the status of a reply.
+#[must_use]
+pub fn encode_status(result: Result<usize, BlockError>) -> usize {
+    SyscallResult::from(result).raw()
+}
+
+/// Decodes the status of a reply into the result of the operation.
+///
+/// # Errors
+/// Returns the [`BlockError`] reported by the driver if the operation failed.
+pub fn decode_status(status: usize) -> Result<usize, BlockError> {
+    SyscallResult::from_raw(status)
+        .decode()
+        .map_err(BlockError::from)
+}
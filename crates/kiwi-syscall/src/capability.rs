@@ -0,0 +1,272 @@
+use crate::SyscallOp;
+
+/// The error code returned by the kernel when a task invokes a syscall without
+/// holding all the capabilities required by the syscall. It is shared by all
+/// syscalls and chosen at the end of the error code range to never collide
+/// with syscall-specific error codes.
+pub const PERMISSION_DENIED: isize = 255;
+
+/// A set of capabilities held by a task. Each capability grants the right to
+/// invoke a family of syscalls, as described by [`PERMISSION_MATRIX`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(transparent)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// No capabilities.
+    pub const NONE: Self = Self(0);
+
+    /// Allows the task to register itself as a service provider.
+    pub const SERVICE_PROVIDE: Self = Self(1 << 0);
+
+    /// Allows the task to connect to services.
+    pub const SERVICE_CONNECT: Self = Self(1 << 1);
+
+    /// Allows the task to enumerate services and inspect their statistics.
+    pub const SERVICE_INSPECT: Self = Self(1 << 2);
+
+    /// Allows the task to send, receive and reply to IPC messages.
+    pub const IPC: Self = Self(1 << 3);
+
+    /// Allows the task to write on the kernel debug output.
+    pub const DEBUG: Self = Self(1 << 4);
+
+    /// Allows the task to inspect the resource usage of tasks, the
+    /// system-wide statistics, the activity counters of the kernel and the
+    /// kernel trace buffers, and to trace the syscalls of tasks.
+    pub const INSPECT: Self = Self(1 << 5);
+
+    /// Allows the task to spawn new tasks. Spawned tasks inherit the
+    /// capabilities of their parent that are allowed by the spawner, except
+    /// [`Capabilities::POWER`].
+    pub const TASK_SPAWN: Self = Self(1 << 6);
+
+    /// Allows the task to read from and write to the console.
+    pub const CONSOLE: Self = Self(1 << 7);
+
+    /// Allows the task to bind interrupts of devices, wait for them, mask
+    /// them and route them to a CPU, in order to drive the devices from user
+    /// space.
+    pub const IRQ: Self = Self(1 << 8);
+
+    /// Allows the task to inspect the devices of the system and the device
+    /// tree describing them, to map their registers in its address space and
+    /// to allocate memory they can access.
+    pub const DEVICE: Self = Self(1 << 9);
+
+    /// Allows the task to change the scheduling class of any task, including
+    /// itself.
+    pub const SCHEDULE: Self = Self(1 << 10);
+
+    /// Allows the task to create synchronization objects, to signal and wait
+    /// on them, and to sleep on futexes.
+    pub const SYNC: Self = Self(1 << 11);
+
+    /// Allows the task to create new threads. Exiting and joining threads
+    /// does not require any capability.
+    pub const THREAD: Self = Self(1 << 12);
+
+    /// Allows the task to terminate other tasks, and to protect them from the
+    /// out-of-memory handler, as long as they do not hold any capability that
+    /// the task does not hold itself.
+    pub const TASK_KILL: Self = Self(1 << 13);
+
+    /// Allows the task to change its own resource limits, and those of the
+    /// tasks that do not hold any capability that it does not hold itself.
+    pub const LIMIT: Self = Self(1 << 14);
+
+    /// Allows the task to change the levels of the kernel log.
+    pub const LOG: Self = Self(1 << 15);
+
+    /// Allows the task to power off or reboot the system. It is never
+    /// inherited by spawned tasks, so only `init` holds it.
+    pub const POWER: Self = Self(1 << 16);
+
+    /// Allows the task to suspend and resume other tasks, to inspect and
+    /// modify the registers of their threads and to single-step them, as long
+    /// as they do not hold any capability that the task does not hold itself.
+    pub const TASK_DEBUG: Self = Self(1 << 17);
+
+    /// All the capabilities.
+    pub const ALL: Self = Self(
+        Self::SERVICE_PROVIDE.0
+            | Self::SERVICE_CONNECT.0
+            | Self::SERVICE_INSPECT.0
+            | Self::IPC.0
+            | Self::DEBUG.0
+            | Self::INSPECT.0
+            | Self::TASK_SPAWN.0
+            | Self::CONSOLE.0
+            | Self::IRQ.0
+            | Self::DEVICE.0
+            | Self::SCHEDULE.0
+            | Self::SYNC.0
+            | Self::THREAD.0
+            | Self::TASK_KILL.0
+            | Self::LIMIT.0
+            | Self::LOG.0
+            | Self::POWER.0
+            | Self::TASK_DEBUG.0,
+    );
+
+    /// Creates a set of capabilities from its raw representation. Unknown
+    /// bits are silently ignored.
+    #[must_use]
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+
+    /// Returns the raw representation of the set of capabilities.
+    #[must_use]
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Returns whether all the given capabilities are in this set.
+    #[must_use]
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the union of the two sets of capabilities.
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Returns the capabilities that are in both sets.
+    #[must_use]
+    pub const fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// Returns the capabilities of this set that are not in the other set.
+    #[must_use]
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl core::ops::BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl core::ops::BitAnd for Capabilities {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        self.intersection(rhs)
+    }
+}
+
+/// Defines [`PERMISSION_MATRIX`] from a list of syscalls and the capability
+/// each of them requires, and appends to its documentation a table generated
+/// from the same list, so that the documented permissions can never go out of
+/// sync with the ones checked by the kernel.
+macro_rules! permission_matrix {
+    (
+        $(#[$meta:meta])*
+        $($op:ident => $capability:ident),* $(,)?
+    ) => {
+        $(#[$meta])*
+        ///
+        /// | Syscall | Required capabilities |
+        /// |---------|-----------------------|
+        $(
+            #[doc = concat!(
+                "| [`", stringify!($op), "`](SyscallOp::", stringify!($op), ") ",
+                "| [`", stringify!($capability), "`](Capabilities::", stringify!($capability), ") |"
+            )]
+        )*
+        pub const PERMISSION_MATRIX: [(SyscallOp, Capabilities); [$(SyscallOp::$op),*].len()] = [
+            $((SyscallOp::$op, Capabilities::$capability),)*
+        ];
+    };
+}
+
+permission_matrix! {
+    /// The capabilities required to invoke each syscall. This is the single
+    /// source of truth for syscall permissions: the kernel checks every syscall
+    /// against this table, and user space can use it to know in advance whether
+    /// it is allowed to invoke a syscall. Syscalls that do not appear in this
+    /// table can be invoked without any capability. This is notably the case of
+    /// `TaskDropCaps`, since giving up capabilities is always allowed.
+    ServiceRegister => SERVICE_PROVIDE,
+    ServiceUnregister => SERVICE_PROVIDE,
+    ServiceAccept => SERVICE_PROVIDE,
+    ServiceConnect => SERVICE_CONNECT,
+    ServiceConnectWait => SERVICE_CONNECT,
+    ServiceDisconnect => SERVICE_CONNECT,
+    ServiceStatistics => SERVICE_INSPECT,
+    ServiceList => SERVICE_INSPECT,
+    IpcSend => IPC,
+    IpcSendOneway => IPC,
+    IpcReceive => IPC,
+    IpcTryReceive => IPC,
+    IpcReply => IPC,
+    IpcForward => IPC,
+    MemPressureSubscribe => IPC,
+    DebugWrite => DEBUG,
+    TaskUsage => INSPECT,
+    SystemStatistics => INSPECT,
+    TraceRead => INSPECT,
+    LogRead => INSPECT,
+    KernelStatistics => INSPECT,
+    TaskTrace => INSPECT,
+    TaskSpawn => TASK_SPAWN,
+    TaskKill => TASK_KILL,
+    TaskSetEssential => TASK_KILL,
+    TaskSetLimit => LIMIT,
+    TaskSuspend => TASK_DEBUG,
+    TaskResume => TASK_DEBUG,
+    TaskGetRegs => TASK_DEBUG,
+    TaskSetRegs => TASK_DEBUG,
+    ConsoleWrite => CONSOLE,
+    ConsoleRead => CONSOLE,
+    ConsoleReadWait => CONSOLE,
+    IrqRegister => IRQ,
+    IrqWait => IRQ,
+    IrqMask => IRQ,
+    IrqUnmask => IRQ,
+    IrqSetAffinity => IRQ,
+    DeviceQuery => DEVICE,
+    MemMapDevice => DEVICE,
+    MemAllocDma => DEVICE,
+    DeviceTreeQuery => DEVICE,
+    TaskSetPriority => SCHEDULE,
+    SyncCreate => SYNC,
+    SyncSignal => SYNC,
+    SyncWait => SYNC,
+    SyncClear => SYNC,
+    SyncDestroy => SYNC,
+    FutexWait => SYNC,
+    FutexWake => SYNC,
+    ThreadCreate => THREAD,
+    LogControl => LOG,
+    SystemPower => POWER,
+}
+
+/// Returns the capabilities required to invoke the given syscall, as defined
+/// by [`PERMISSION_MATRIX`].
+#[must_use]
+pub const fn required(op: SyscallOp) -> Capabilities {
+    let mut i = 0;
+    while i < PERMISSION_MATRIX.len() {
+        if PERMISSION_MATRIX[i].0 as u32 == op as u32 {
+            return PERMISSION_MATRIX[i].1;
+        }
+        i += 1;
+    }
+    Capabilities::NONE
+}
+
+/// Returns whether a task holding the given capabilities is allowed to invoke
+/// the given syscall.
+#[must_use]
+pub const fn allowed(op: SyscallOp, held: Capabilities) -> bool {
+    held.contains(required(op))
+}
@@ -0,0 +1,27 @@
+/// The maximum number of bytes that can be written to the console with a
+/// single [`crate::SyscallOp::ConsoleWrite`] syscall. Larger writes must be
+/// split by the caller.
+pub const MAX_WRITE_SIZE: usize = 4096;
+
+syscall_error! {
+    /// Errors that can occur when writing to the console.
+    pub enum WriteError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer is not valid for reads, or is larger than
+        /// [`MAX_WRITE_SIZE`].
+        BadBuffer = 1,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when reading from the console.
+    pub enum ReadError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer is not valid for writes.
+        BadBuffer = 1,
+    }
+}
@@ -0,0 +1,133 @@
+//! Devices discovered by the kernel and driven from user space. The kernel
+//! only describes the devices and maps their registers in the address space of
+//! their driver: everything else is done by the drivers themselves. The
+//! device tree given by the bootloader can also be queried, to find the
+//! devices that the kernel does not describe itself.
+use zerocopy::{FromBytes, IntoBytes};
+
+/// The kind of a device, which tells which driver can drive it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum DeviceKind {
+    /// An unknown kind of device, that may be defined by a more recent
+    /// version of the kernel.
+    Unknown = 0,
+
+    /// A virtio device using the MMIO transport. The kind of virtio device is
+    /// given by the `DeviceID` register of the device.
+    VirtioMmio = 1,
+}
+
+impl From<u64> for DeviceKind {
+    fn from(kind: u64) -> Self {
+        match kind {
+            1 => DeviceKind::VirtioMmio,
+            _ => DeviceKind::Unknown,
+        }
+    }
+}
+
+/// The virtio device ID of block devices.
+pub const VIRTIO_BLOCK: u64 = 2;
+
+/// The description of a device, as returned by the
+/// [`crate::SyscallOp::DeviceQuery`] syscall. We use the C representation to
+/// ensure a predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct Device {
+    /// The raw [`DeviceKind`] of the device.
+    pub kind: u64,
+
+    /// The model of the device, whose meaning depends on its kind. For virtio
+    /// devices, this is the virtio device ID, such as [`VIRTIO_BLOCK`].
+    pub model: u64,
+
+    /// The physical address of the registers of the device.
+    pub base: u64,
+
+    /// The size of the registers of the device, in bytes.
+    pub size: u64,
+
+    /// The interrupt source of the device, or 0 if it has none. It can be
+    /// bound with [`crate::SyscallOp::IrqRegister`].
+    pub irq: u64,
+}
+
+impl Device {
+    /// Returns the kind of the device.
+    #[must_use]
+    pub fn kind(&self) -> DeviceKind {
+        DeviceKind::from(self.kind)
+    }
+
+    /// Returns the interrupt source of the device, if any.
+    #[must_use]
+    pub fn irq(&self) -> Option<usize> {
+        usize::try_from(self.irq).ok().filter(|&irq| irq != 0)
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when querying the description of a device.
+    pub enum QueryError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// There is no device with the given index.
+        NotFound = 1,
+
+        /// The buffer where the description should be written is invalid.
+        BadBuffer = 2,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when reading a property of the device tree.
+    pub enum TreeQueryError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The kernel did not keep a device tree, because the bootloader did
+        /// not provide one or it could not be kept in memory.
+        Unavailable = 1,
+
+        /// The path or the name of the property is invalid, too long or not
+        /// valid UTF-8.
+        BadName = 2,
+
+        /// There is no node with the given path.
+        NodeNotFound = 3,
+
+        /// The node has no property with the given name.
+        PropertyNotFound = 4,
+
+        /// The buffer where the value should be written is invalid.
+        BadBuffer = 5,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when mapping the registers of a device.
+    pub enum MapError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// There is no device with the given index.
+        NotFound = 1,
+
+        /// The start address is null or not page-aligned, or the registers
+        /// would not fit in the part of the user address space available to
+        /// the task.
+        BadRange = 2,
+
+        /// A page of the range is already mapped. Nothing is mapped.
+        AlreadyMapped = 3,
+
+        /// The device is already driven by another task.
+        AlreadyClaimed = 4,
+
+        /// The kernel ran out of memory while allocating page tables.
+        OutOfMemory = 5,
+    }
+}
@@ -0,0 +1,137 @@
+//! The block of arguments and environment variables given to a task when it
+//! starts. The kernel copies the block at the top of the user stack of the
+//! first thread of the task, just above its initial stack pointer, and passes
+//! its address in the first argument register (`a0` on riscv64, `x0` on
+//! aarch64).
+//!
+//! The block starts with a [`Header`], followed by one [`Entry`] per argument
+//! and then one per environment variable, followed by the strings themselves.
+//! Strings are referenced by their offset from the start of the block, so the
+//! block can be copied anywhere without being modified. All strings are UTF-8,
+//! and environment variables have the `KEY=VALUE` form.
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+/// The maximum size of a block, in bytes. The block is stored on the user
+/// stack, so it must remain small compared to the size of the stack.
+pub const MAX_BLOCK_SIZE: usize = 8192;
+
+/// The header of a block. We use the C representation to ensure a
+/// predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct Header {
+    /// The total size of the block, in bytes, including this header.
+    pub size: u64,
+
+    /// The number of arguments.
+    pub argc: u64,
+
+    /// The number of environment variables.
+    pub envc: u64,
+}
+
+/// The location of a string in a block.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct Entry {
+    /// The offset of the string from the start of the block, in bytes.
+    pub offset: u64,
+
+    /// The length of the string, in bytes.
+    pub len: u64,
+}
+
+/// A validated block of arguments and environment variables.
+#[derive(Debug, Clone, Copy)]
+pub struct Block<'a> {
+    bytes: &'a [u8],
+    argc: usize,
+    envc: usize,
+}
+
+impl<'a> Block<'a> {
+    /// Parses and validates the given block. Returns `None` if the size in
+    /// the header does not match the size of the slice, if the block is
+    /// larger than [`MAX_BLOCK_SIZE`], or if a string is outside of the block
+    /// or is not valid UTF-8.
+    #[must_use]
+    pub fn new(bytes: &'a [u8]) -> Option<Self> {
+        let (header, _) = Header::read_from_prefix(bytes).ok()?;
+        if usize::try_from(header.size).ok()? != bytes.len() || bytes.len() > MAX_BLOCK_SIZE {
+            return None;
+        }
+
+        let block = Self {
+            bytes,
+            argc: usize::try_from(header.argc).ok()?,
+            envc: usize::try_from(header.envc).ok()?,
+        };
+        let count = block.argc.checked_add(block.envc)?;
+        (0..count)
+            .all(|index| block.string(index).is_some())
+            .then_some(block)
+    }
+
+    /// Returns the raw bytes of the block.
+    #[must_use]
+    pub const fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns an iterator over the arguments.
+    pub fn args(self) -> impl Iterator<Item = &'a str> {
+        (0..self.argc).filter_map(move |index| self.string(index))
+    }
+
+    /// Returns an iterator over the environment variables, in the
+    /// `KEY=VALUE` form.
+    pub fn vars(self) -> impl Iterator<Item = &'a str> {
+        (self.argc..self.argc + self.envc).filter_map(move |index| self.string(index))
+    }
+
+    /// Returns the string of the given entry, or `None` if the entry or the
+    /// string is outside of the block or if the string is not valid UTF-8.
+    fn string(&self, index: usize) -> Option<&'a str> {
+        let offset = size_of::<Header>().checked_add(index.checked_mul(size_of::<Entry>())?)?;
+        let (entry, _) = Entry::read_from_prefix(self.bytes.get(offset..)?).ok()?;
+        let start = usize::try_from(entry.offset).ok()?;
+        let end = start.checked_add(usize::try_from(entry.len).ok()?)?;
+        core::str::from_utf8(self.bytes.get(start..end)?).ok()
+    }
+}
+
+/// Returns the size of the block holding the given arguments and environment
+/// variables, in bytes.
+#[must_use]
+pub fn encoded_size(args: &[&str], vars: &[&str]) -> usize {
+    let strings: usize = args.iter().chain(vars).map(|string| string.len()).sum();
+    size_of::<Header>() + (args.len() + vars.len()) * size_of::<Entry>() + strings
+}
+
+/// Encodes the given arguments and environment variables into the buffer,
+/// and returns the size of the block. Returns `None` if the buffer is too
+/// small, in which case its content is unspecified.
+pub fn encode(buffer: &mut [u8], args: &[&str], vars: &[&str]) -> Option<usize> {
+    let size = encoded_size(args, vars);
+    let buffer = buffer.get_mut(..size)?;
+    let header = Header {
+        size: size as u64,
+        argc: args.len() as u64,
+        envc: vars.len() as u64,
+    };
+    header.write_to_prefix(buffer).ok()?;
+
+    let mut entry = size_of::<Header>();
+    let mut offset = entry + (args.len() + vars.len()) * size_of::<Entry>();
+    for string in args.iter().chain(vars) {
+        let location = Entry {
+            offset: offset as u64,
+            len: string.len() as u64,
+        };
+        location.write_to_prefix(&mut buffer[entry..]).ok()?;
+        buffer[offset..offset + string.len()].copy_from_slice(string.as_bytes());
+        entry += size_of::<Entry>();
+        offset += string.len();
+    }
+    Some(size)
+}
@@ -0,0 +1,350 @@
+//! The protocol spoken between file servers and their clients. Files are not
+//! managed by the kernel: they are provided by user space services, which
+//! receive the requests of their clients as IPC messages. This module defines
+//! the operations, the layout of the requests and replies and the errors, so
+//! that all file servers and clients agree on them.
+//!
+//! The operation is given in the `kind` field of the message, and the request
+//! structure of the operation in its payload. The status of the reply is
+//! encoded like the result of a syscall (see [`SyscallResult`]): it is either
+//! a [`FileError`] or a value specific to the operation, and the payload of
+//! the reply holds the reply structure of the operation, if any.
+use crate::{args::SyscallResult, ipc::MAX_PAYLOAD_SIZE};
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+pub use crate::ipc::Payload;
+
+/// The name under which the root file server registers itself.
+pub const SERVICE_NAME: &str = "fs";
+
+/// The maximum length of a path, in bytes.
+pub const MAX_PATH_LEN: usize = MAX_PAYLOAD_SIZE - 16;
+
+/// The maximum number of bytes that can be read with a single request. The
+/// data is returned as the payload of the reply.
+pub const MAX_READ_SIZE: usize = MAX_PAYLOAD_SIZE;
+
+/// The maximum number of bytes that can be written with a single request.
+pub const MAX_WRITE_SIZE: usize = MAX_PAYLOAD_SIZE - 24;
+
+impl Payload for OpenRequest {}
+impl Payload for ReadRequest {}
+impl Payload for WriteRequest {}
+impl Payload for HandleRequest {}
+impl Payload for Metadata {}
+
+/// An operation that can be requested to a file server, given in the `kind`
+/// field of the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum Operation {
+    /// An unknown operation, which file servers must reject with
+    /// [`FileError::Unsupported`].
+    Unknown = 0,
+
+    /// Opens the file at the given path ([`OpenRequest`]). The status of the
+    /// reply is the handle of the opened file.
+    Open = 1,
+
+    /// Reads from an opened file ([`ReadRequest`]). The status of the reply is
+    /// the number of bytes read, and its payload holds the data. Fewer bytes
+    /// than requested are only read at the end of the file.
+    Read = 2,
+
+    /// Writes to an opened file ([`WriteRequest`]). The status of the reply is
+    /// the number of bytes written.
+    Write = 3,
+
+    /// Closes an opened file ([`HandleRequest`]). The handle cannot be used
+    /// anymore once the reply is received.
+    Close = 4,
+
+    /// Returns the metadata of an opened file ([`HandleRequest`]). The payload
+    /// of the reply holds a [`Metadata`] structure.
+    Stat = 5,
+}
+
+impl From<usize> for Operation {
+    fn from(kind: usize) -> Self {
+        match kind {
+            1 => Operation::Open,
+            2 => Operation::Read,
+            3 => Operation::Write,
+            4 => Operation::Close,
+            5 => Operation::Stat,
+            _ => Operation::Unknown,
+        }
+    }
+}
+
+/// Flags that can be given when opening a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(transparent)]
+pub struct OpenFlags(u64);
+
+impl OpenFlags {
+    /// No flags: the file must exist, and can only be inspected.
+    pub const NONE: Self = Self(0);
+
+    /// The file can be read.
+    pub const READ: Self = Self(1 << 0);
+
+    /// The file can be written.
+    pub const WRITE: Self = Self(1 << 1);
+
+    /// The file is created if it does not exist.
+    pub const CREATE: Self = Self(1 << 2);
+
+    /// The file is truncated to an empty file when opened. This requires
+    /// [`Self::WRITE`].
+    pub const TRUNCATE: Self = Self(1 << 3);
+
+    /// All the valid bits.
+    const ALL: u64 = Self::READ.0 | Self::WRITE.0 | Self::CREATE.0 | Self::TRUNCATE.0;
+
+    /// Creates flags from their raw representation. Returns `None` if an
+    /// unknown bit is set, so that a client compiled against a more recent
+    /// protocol does not silently get a different behavior than it asked for.
+    #[must_use]
+    pub const fn from_bits(bits: u64) -> Option<Self> {
+        if bits & !Self::ALL == 0 {
+            Some(Self(bits))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the raw representation of the flags.
+    #[must_use]
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns whether all the given flags are set.
+    #[must_use]
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for OpenFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The request of the [`Operation::Open`] operation.
+#[derive(Debug, Clone, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct OpenRequest {
+    /// The raw [`OpenFlags`].
+    pub flags: u64,
+
+    /// The length of the path, in bytes.
+    pub path_len: u64,
+
+    /// The absolute path of the file. Only the first `path_len` bytes are
+    /// valid.
+    pub path: [u8; MAX_PATH_LEN],
+}
+
+impl OpenRequest {
+    /// Creates a request to open the file at the given path. Returns `None`
+    /// if the path is longer than [`MAX_PATH_LEN`].
+    #[must_use]
+    pub fn new(path: &str, flags: OpenFlags) -> Option<Self> {
+        let mut request = Self {
+            flags: flags.bits(),
+            path_len: path.len() as u64,
+            path: [0; MAX_PATH_LEN],
+        };
+        request
+            .path
+            .get_mut(..path.len())?
+            .copy_from_slice(path.as_bytes());
+        Some(request)
+    }
+
+    /// Returns the path of the file to open, or `None` if it is not valid
+    /// UTF-8 or if its length is invalid.
+    #[must_use]
+    pub fn path(&self) -> Option<&str> {
+        let len = usize::try_from(self.path_len).ok()?;
+        core::str::from_utf8(self.path.get(..len)?).ok()
+    }
+
+    /// Returns the flags of the request, or `None` if an unknown flag is set.
+    #[must_use]
+    pub const fn flags(&self) -> Option<OpenFlags> {
+        OpenFlags::from_bits(self.flags)
+    }
+}
+
+/// The request of the [`Operation::Read`] operation.
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct ReadRequest {
+    /// The handle of the file, as returned by [`Operation::Open`].
+    pub handle: u64,
+
+    /// The offset in the file of the first byte to read.
+    pub offset: u64,
+
+    /// The number of bytes to read, at most [`MAX_READ_SIZE`].
+    pub len: u64,
+}
+
+/// The request of the [`Operation::Write`] operation.
+#[derive(Debug, Clone, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct WriteRequest {
+    /// The handle of the file, as returned by [`Operation::Open`].
+    pub handle: u64,
+
+    /// The offset in the file where the data is written. The file is extended
+    /// if the data is written past its end, and the gap is filled with zeroes.
+    pub offset: u64,
+
+    /// The number of bytes to write.
+    pub len: u64,
+
+    /// The data to write. Only the first `len` bytes are valid.
+    pub data: [u8; MAX_WRITE_SIZE],
+}
+
+impl WriteRequest {
+    /// Creates a request to write the given data at the given offset of a
+    /// file. Only the first [`MAX_WRITE_SIZE`] bytes of the data are written
+    /// if it is longer.
+    #[must_use]
+    pub fn new(handle: u64, offset: u64, data: &[u8]) -> Self {
+        let len = data.len().min(MAX_WRITE_SIZE);
+        let mut request = Self {
+            handle,
+            offset,
+            len: len as u64,
+            data: [0; MAX_WRITE_SIZE],
+        };
+        request.data[..len].copy_from_slice(&data[..len]);
+        request
+    }
+
+    /// Returns the data to write, or `None` if the length of the data is
+    /// invalid.
+    #[must_use]
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.get(..usize::try_from(self.len).ok()?)
+    }
+}
+
+/// The request of the operations that only need the handle of a file:
+/// [`Operation::Close`] and [`Operation::Stat`].
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct HandleRequest {
+    /// The handle of the file, as returned by [`Operation::Open`].
+    pub handle: u64,
+}
+
+/// The kind of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub enum FileKind {
+    /// An unknown kind of file, that may be defined by a more recent version
+    /// of the protocol.
+    Unknown = 0,
+
+    /// A regular file.
+    Regular = 1,
+
+    /// A directory.
+    Directory = 2,
+}
+
+impl From<u64> for FileKind {
+    fn from(kind: u64) -> Self {
+        match kind {
+            1 => FileKind::Regular,
+            2 => FileKind::Directory,
+            _ => FileKind::Unknown,
+        }
+    }
+}
+
+/// The reply of the [`Operation::Stat`] operation.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct Metadata {
+    /// The size of the file, in bytes.
+    pub size: u64,
+
+    /// The raw [`FileKind`] of the file.
+    pub kind: u64,
+
+    /// The permissions of the file, in the Unix format.
+    pub mode: u64,
+}
+
+impl Metadata {
+    /// Returns the kind of the file.
+    #[must_use]
+    pub fn kind(&self) -> FileKind {
+        FileKind::from(self.kind)
+    }
+}
+
+syscall_error! {
+    /// Errors that can be returned by a file server.
+    pub enum FileError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The operation is not supported by the file server.
+        Unsupported = 1,
+
+        /// The request is malformed: its payload is too short, or one of its
+        /// fields is invalid.
+        BadRequest = 2,
+
+        /// The file does not exist.
+        NotFound = 3,
+
+        /// The file already exists.
+        AlreadyExists = 4,
+
+        /// The handle does not refer to a file opened by the client.
+        InvalidHandle = 5,
+
+        /// The file was not opened with the rights needed by the operation, or
+        /// the file server does not allow them.
+        PermissionDenied = 6,
+
+        /// The operation is not allowed on a directory.
+        IsDirectory = 7,
+
+        /// The client has too many opened files.
+        TooManyOpenFiles = 8,
+
+        /// The file server ran out of memory.
+        OutOfMemory = 9,
+    }
+}
+
+/// Encodes the result of an operation into the status of a reply.
+#[must_use]
+pub fn encode_status(result: Result<usize, FileError>) -> usize {
+    SyscallResult::from(result).raw()
+}
+
+/// Decodes the status of a reply into the result of the operation.
+///
+/// # Errors
+/// Returns the [`FileError`] reported by the file server if the operation
+/// failed.
+pub fn decode_status(status: usize) -> Result<usize, FileError> {
+    SyscallResult::from_raw(status)
+        .decode()
+        .map_err(FileError::from)
+}
@@ -0,0 +1,43 @@
+//! Futexes: sleeping on a word of user memory. The fast path of user space
+//! locks only uses atomic operations on a 32-bit word, and the kernel is only
+//! involved when a task must sleep until the word changes
+//! ([`crate::SyscallOp::FutexWait`]) or must wake up the tasks sleeping on it
+//! ([`crate::SyscallOp::FutexWake`]).
+//!
+//! Futexes are identified by the physical address of the word, so that tasks
+//! mapping the same memory at different addresses share the same futex. The
+//! kernel does not keep any state for a futex when no task is sleeping on it.
+//!
+//! A wait may return without a wake having been requested, and a woken task
+//! must check the word again before deciding to sleep again.
+
+/// The alignment required for the address of a futex word.
+pub const ALIGNMENT: usize = align_of::<u32>();
+
+syscall_error! {
+    /// Errors that can occur when waiting on a futex.
+    pub enum WaitError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The address is not aligned on [`ALIGNMENT`], or is not mapped
+        /// readable in the address space of the task.
+        BadAddress = 1,
+
+        /// The word did not hold the expected value, so the task did not
+        /// sleep.
+        ValueMismatch = 2,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when waking up the tasks sleeping on a futex.
+    pub enum WakeError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The address is not aligned on [`ALIGNMENT`], or is not mapped
+        /// readable in the address space of the task.
+        BadAddress = 1,
+    }
+}
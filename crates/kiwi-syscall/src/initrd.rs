@@ -0,0 +1,14 @@
+/// The user virtual address where the kernel maps the initial ramdisk in the
+/// address space of the `init` task. The ramdisk is a cpio archive in the
+/// "new ASCII" format, mapped read-only, and is only given to `init` when the
+/// bootloader provided one.
+pub const INITRD_ADDRESS: usize = 0x0000_0020_0000_0000;
+
+/// The maximum size of the initial ramdisk, in bytes. Larger ramdisks are
+/// ignored by the kernel.
+pub const INITRD_MAX_SIZE: usize = 0x0000_0010_0000_0000;
+
+/// The environment variable giving the size in bytes of the initial ramdisk
+/// mapped at [`INITRD_ADDRESS`], written in decimal. It is only set in the
+/// environment of `init`, and only when a ramdisk was mapped.
+pub const INITRD_SIZE_VAR: &str = "INITRD_SIZE";
@@ -1,20 +1,45 @@
-use zerocopy::{FromBytes, IntoBytes};
+use zerocopy::{FromBytes, Immutable, IntoBytes};
 
 /// Maximum payload size for IPC messages.
 pub const MAX_PAYLOAD_SIZE: usize = 256;
 
+/// The sender of the messages sent by the kernel itself, such as the
+/// expirations of timers (see [`crate::timer`]). No task ever has this
+/// identifier, and such messages must not be replied to: they are always
+/// marked with [`SenderInfo::ONEWAY`].
+pub const KERNEL_SENDER: usize = crate::task::CURRENT_TASK;
+
+/// A request or reply structure carried in the payload of a message, as
+/// defined by the protocols of the services (see [`crate::fs`] and
+/// [`crate::blk`]).
+pub trait Payload: FromBytes + IntoBytes + Immutable + Sized {
+    /// Reads the structure from the start of the given payload. Returns
+    /// `None` if the payload is too short.
+    #[must_use]
+    fn from_payload(payload: &[u8]) -> Option<Self> {
+        Self::read_from_prefix(payload).ok().map(|(value, _)| value)
+    }
+
+    /// Returns the bytes of the structure, to be sent as a payload.
+    #[must_use]
+    fn as_payload(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
 /// Represents an IPC message used by syscalls to reduce the number of
 /// parameters passed. We use the C representation to ensure a predictable
 /// layout compatible with the kernel.
-#[derive(FromBytes, IntoBytes)]
+#[derive(FromBytes, IntoBytes, Immutable)]
 #[repr(C)]
 pub struct Message {
     /// The sender task ID. If the message is sent from user space, this
     /// field is ignored and will be filled in by the kernel.
     pub sender: usize,
 
-    /// The receiver task ID. If the message is sent to user space, this
-    /// field is ignored and will be filled in by the kernel.
+    /// The handle of the connection to the receiver service. If the message
+    /// is sent to user space, this field is filled in by the kernel with the
+    /// receiver task ID.
     pub receiver: usize,
 
     /// The message kind.
@@ -23,14 +48,59 @@ pub struct Message {
     /// The length of the payload.
     pub payload_len: usize,
 
+    /// Information about the sender, verified by the kernel. If the message
+    /// is sent from user space, this field is ignored and will be filled in
+    /// by the kernel.
+    pub sender_info: SenderInfo,
+
     /// The payload data.
     pub payload: [u8; MAX_PAYLOAD_SIZE],
 }
 
+/// Information about the sender of a message, filled in by the kernel. Unlike
+/// the payload, it cannot be forged by the sender. Each field is zero when the
+/// information is not available, since none of them can be zero otherwise.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct SenderInfo {
+    /// The badge of the connection the message was sent through. Each
+    /// connection made by a task gets a different badge, so a service can
+    /// tell apart the connections of a client.
+    pub badge: u64,
+
+    /// The generation of the sender. It is unique to each thread ever created,
+    /// so it can be used to make sure that a task identifier still refers to
+    /// the same thread.
+    pub generation: u64,
+
+    /// The serial number of the registration of the service provided by the
+    /// sender, or zero if the sender does not provide any service. This is the
+    /// same value as [`crate::service::ServiceInfo::registration`].
+    pub service: u64,
+
+    /// A combination of flags describing how the message was sent, such as
+    /// [`SenderInfo::ONEWAY`].
+    pub flags: u64,
+}
+
+impl SenderInfo {
+    /// The message was sent with [`crate::SyscallOp::IpcSendOneway`] or by
+    /// the kernel: the sender does not wait for a reply, and the message
+    /// must not be replied to.
+    pub const ONEWAY: u64 = 1 << 0;
+
+    /// Returns whether the message must not be replied to (see
+    /// [`SenderInfo::ONEWAY`]).
+    #[must_use]
+    pub const fn is_oneway(&self) -> bool {
+        self.flags & Self::ONEWAY != 0
+    }
+}
+
 /// Represents an IPC reply used by syscalls to reduce the number of
 /// parameters passed. We use the C representation to ensure a predictable
 /// layout compatible with the kernel.
-#[derive(FromBytes, IntoBytes)]
+#[derive(FromBytes, IntoBytes, Immutable)]
 #[repr(C)]
 pub struct Reply {
     /// The status of the reply.
@@ -43,99 +113,117 @@ pub struct Reply {
     pub payload: [u8; MAX_PAYLOAD_SIZE],
 }
 
-/// Errors that can occur when sending an IPC message.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum SendError {
-    /// An unknown error occurred.
-    Unknown = 0,
+syscall_error! {
+    /// Errors that can occur when sending an IPC message.
+    pub enum SendError {
+        /// An unknown error occurred.
+        Unknown = 0,
 
-    /// The destination is invalid.
-    InvalidDestination = 1,
+        /// The destination is invalid. This happens when the handle does not
+        /// refer to a connection held by the sender.
+        InvalidDestination = 1,
 
-    /// The message is invalid.
-    BadMessage = 2,
+        /// The message is invalid.
+        BadMessage = 2,
 
-    /// The payload size exceeds the maximum allowed size.
-    PayloadTooLarge = 3,
+        /// The payload size exceeds the maximum allowed size.
+        PayloadTooLarge = 3,
 
-    /// The target task does not exist.
-    TaskDoesNotExist = 4,
+        /// The target task does not exist.
+        TaskDoesNotExist = 4,
 
-    /// The target task has been destroyed before the message could be sent.
-    TaskDestroyed = 5,
-}
+        /// The target task has been destroyed before the message could be sent.
+        TaskDestroyed = 5,
+
+        /// The target service has been unregistered before the message could be
+        /// sent.
+        ServiceUnregistered = 6,
+
+        /// The target service terminated after the message was delivered but
+        /// before replying to it.
+        ServiceDied = 7,
 
-impl From<SendError> for isize {
-    fn from(error: SendError) -> Self {
-        match error {
-            SendError::Unknown => 0,
-            SendError::InvalidDestination => 1,
-            SendError::BadMessage => 2,
-            SendError::PayloadTooLarge => 3,
-            SendError::TaskDoesNotExist => 4,
-            SendError::TaskDestroyed => 5,
-        }
+        /// The mailbox of the target service is full. This is only returned by
+        /// [`crate::SyscallOp::IpcSendOneway`], which never waits for room in
+        /// the mailbox: the message is dropped, and the sender may try again
+        /// later.
+        MailboxFull = 8,
     }
 }
 
-/// Errors that can occur when receiving an IPC message.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ReceiveError {
-    /// An unknown error occurred.
-    Unknown = 0,
+syscall_error! {
+    /// Errors that can occur when receiving an IPC message.
+    pub enum ReceiveError {
+        /// An unknown error occurred.
+        Unknown = 0,
 
-    /// The buffer pointer is invalid.
-    BadBuffer = 1,
-}
+        /// The buffer pointer is invalid.
+        BadBuffer = 1,
 
-impl From<ReceiveError> for isize {
-    fn from(error: ReceiveError) -> Self {
-        match error {
-            ReceiveError::Unknown => 0,
-            ReceiveError::BadBuffer => 1,
-        }
+        /// The mailbox of the task is empty. This is only returned by
+        /// [`crate::SyscallOp::IpcTryReceive`].
+        NoMessage = 2,
     }
 }
 
-/// Errors that can occur when replying to an IPC message.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ReplyError {
-    /// An unknown error occurred.
-    Unknown = 0,
+syscall_error! {
+    /// Errors that can occur when replying to an IPC message.
+    pub enum ReplyError {
+        /// An unknown error occurred.
+        Unknown = 0,
 
-    /// The destination is invalid.
-    InvalidDestination = 1,
+        /// The destination is invalid.
+        InvalidDestination = 1,
 
-    /// The message is invalid.
-    BadMessage = 2,
+        /// The message is invalid.
+        BadMessage = 2,
 
-    /// The payload size exceeds the maximum allowed size.
-    PayloadTooLarge = 3,
+        /// The payload size exceeds the maximum allowed size.
+        PayloadTooLarge = 3,
 
-    /// The task is not waiting for a reply from the sender.
-    NotWaitingForReply = 4,
+        /// The task is not waiting for a reply from the sender.
+        NotWaitingForReply = 4,
 
-    /// The receiver expected a reply from a different sender.
-    UnexpectedSender = 5,
+        /// The receiver expected a reply from a different sender.
+        UnexpectedSender = 5,
 
-    /// The target task does not exist.
-    TaskDoesNotExist = 6,
+        /// The target task does not exist.
+        TaskDoesNotExist = 6,
 
-    /// The target task has been destroyed before the reply could be sent.
-    TaskDestroyed = 7,
+        /// The target task has been destroyed before the reply could be sent.
+        TaskDestroyed = 7,
+    }
 }
 
-impl From<ReplyError> for isize {
-    fn from(error: ReplyError) -> Self {
-        match error {
-            ReplyError::Unknown => 0,
-            ReplyError::InvalidDestination => 1,
-            ReplyError::BadMessage => 2,
-            ReplyError::PayloadTooLarge => 3,
-            ReplyError::NotWaitingForReply => 4,
-            ReplyError::UnexpectedSender => 5,
-            ReplyError::TaskDoesNotExist => 6,
-            ReplyError::TaskDestroyed => 7,
-        }
+syscall_error! {
+    /// Errors that can occur when forwarding an IPC message.
+    pub enum ForwardError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The destination is invalid. This happens when the handle does not
+        /// refer to a connection held by the task forwarding the message.
+        InvalidDestination = 1,
+
+        /// The message is invalid.
+        BadMessage = 2,
+
+        /// The payload size exceeds the maximum allowed size.
+        PayloadTooLarge = 3,
+
+        /// The client is not waiting for a reply from the task forwarding the
+        /// message.
+        NotWaitingForReply = 4,
+
+        /// The target task does not exist.
+        TaskDoesNotExist = 5,
+
+        /// The target task has been destroyed before the message could be
+        /// forwarded.
+        TaskDestroyed = 6,
+
+        /// The target service has been unregistered before the message could
+        /// be forwarded.
+        ServiceUnregistered = 7,
     }
 }
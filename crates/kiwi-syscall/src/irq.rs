@@ -0,0 +1,71 @@
+//! Interrupts of devices delivered to user space drivers. A driver binds an
+//! interrupt source of the platform interrupt controller to itself with
+//! [`crate::SyscallOp::IrqRegister`], then waits for the source to raise an
+//! interrupt with [`crate::SyscallOp::IrqWait`].
+//!
+//! When a bound source raises an interrupt, the kernel masks it and wakes up
+//! the driver. The source stays masked until the driver waits again, which
+//! acknowledges the interrupt: the driver must have serviced the device by
+//! then, otherwise the interrupt is raised again immediately.
+//!
+//! A driver can also mask its source with [`crate::SyscallOp::IrqMask`], for
+//! example to poll its device while it is busy and coalesce the interrupts.
+//! The source then stays masked, even across waits, until the driver unmasks
+//! it with [`crate::SyscallOp::IrqUnmask`]. On systems with several CPUs, the
+//! source can be routed to the CPU running the driver with
+//! [`crate::SyscallOp::IrqSetAffinity`].
+
+syscall_error! {
+    /// Errors that can occur when binding an interrupt to a task.
+    pub enum RegisterError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The platform has no interrupt controller that the kernel can
+        /// drive.
+        NoController = 1,
+
+        /// The interrupt source does not exist, or is reserved by the kernel.
+        InvalidIrq = 2,
+
+        /// The interrupt source is already bound to a task.
+        AlreadyBound = 3,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when waiting for an interrupt.
+    pub enum WaitError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The interrupt source is not bound to the current task.
+        NotBound = 1,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when masking or unmasking an interrupt.
+    pub enum ControlError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The interrupt source is not bound to the current task.
+        NotBound = 1,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when routing an interrupt to a CPU.
+    pub enum AffinityError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The interrupt source is not bound to the current task.
+        NotBound = 1,
+
+        /// The CPU does not exist, or the interrupt controller cannot route
+        /// the source to it.
+        InvalidCpu = 2,
+    }
+}
@@ -6,9 +6,73 @@
 //! if they get out of sync.
 #![no_std]
 
+/// Defines the error type of a syscall, and implements the conversions from
+/// and to the error code returned by the kernel. Both conversions are derived
+/// from the discriminants of the enumeration, so that the kernel and user
+/// space can never disagree on the meaning of an error code.
+///
+/// Error codes that do not match any variant are converted to the `Unknown`
+/// variant, which every error type must define. This can happen if the kernel
+/// is more recent than the user space program that invoked the syscall.
+macro_rules! syscall_error {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident = $code:literal),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant = $code,)*
+        }
+
+        impl From<$name> for isize {
+            fn from(error: $name) -> Self {
+                match error {
+                    $($name::$variant => $code,)*
+                }
+            }
+        }
+
+        impl From<isize> for $name {
+            fn from(code: isize) -> Self {
+                match code {
+                    $($code => $name::$variant,)*
+                    _ => $name::Unknown,
+                }
+            }
+        }
+    };
+}
+
+pub mod args;
+pub mod blk;
+pub mod capability;
+pub mod console;
 pub mod debug;
+pub mod device;
+pub mod env;
+pub mod fs;
+pub mod futex;
+pub mod initrd;
 pub mod ipc;
+pub mod irq;
+pub mod log;
+pub mod memory;
+pub mod power;
+pub mod random;
+#[cfg(feature = "userspace")]
+pub mod raw;
 pub mod service;
+pub mod stats;
+pub mod sync;
+pub mod table;
+pub mod task;
+pub mod thread;
+pub mod timer;
+pub mod trace;
+pub mod vdso;
 
 /// Enumeration of supported syscall operations by the kernel.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -41,6 +105,182 @@ pub enum SyscallOp {
     /// Reply to an IPC message
     IpcReply = 8,
 
+    /// Retrieve the statistics of a service
+    ServiceStatistics = 9,
+
+    /// Enumerate the registered services
+    ServiceList = 10,
+
+    /// Wait for the termination of a task
+    TaskWait = 11,
+
+    /// Retrieve the resource usage of a task
+    TaskUsage = 12,
+
+    /// Retrieve the system-wide statistics
+    SystemStatistics = 13,
+
+    /// Retrieve the machine-readable description of all syscalls
+    SyscallTable = 14,
+
+    /// Change the protection of a range of user memory
+    MemProtect = 15,
+
+    /// Map anonymous memory in the address space of the current task
+    MemMap = 16,
+
+    /// Spawn a new task from an ELF image
+    TaskSpawn = 17,
+
+    /// Write raw bytes to the console
+    ConsoleWrite = 18,
+
+    /// Read the bytes available from the console, without blocking
+    ConsoleRead = 19,
+
+    /// Bind an interrupt source to the current task
+    IrqRegister = 20,
+
+    /// Wait for an interrupt bound to the current task
+    IrqWait = 21,
+
+    /// Retrieve the description of a device
+    DeviceQuery = 22,
+
+    /// Map the registers of a device in the address space of the current task
+    MemMapDevice = 23,
+
+    /// Allocate physically contiguous memory that devices can access, and map
+    /// it in the address space of the current task
+    MemAllocDma = 24,
+
+    /// Change the scheduling class of a task
+    TaskSetPriority = 25,
+
+    /// Create a synchronization object
+    SyncCreate = 26,
+
+    /// Signal a synchronization object, waking up the tasks waiting on it
+    SyncSignal = 27,
+
+    /// Wait until a synchronization object is signaled
+    SyncWait = 28,
+
+    /// Reset the value of a synchronization object
+    SyncClear = 29,
+
+    /// Destroy a synchronization object created by the current task
+    SyncDestroy = 30,
+
+    /// Sleep until woken up, if a word of user memory holds the expected value
+    FutexWait = 31,
+
+    /// Wake up the tasks sleeping on a word of user memory
+    FutexWake = 32,
+
+    /// Create a new thread in the current task
+    ThreadCreate = 33,
+
+    /// Terminate the current thread
+    ThreadExit = 34,
+
+    /// Wait for the termination of a thread of the current task
+    ThreadJoin = 35,
+
+    /// Give up capabilities of the current task
+    TaskDropCaps = 36,
+
+    /// Allow a task to connect to the restricted service of the current task
+    ServiceAccept = 37,
+
+    /// Drain the kernel trace buffers
+    TraceRead = 38,
+
+    /// Retrieve the activity counters of the kernel
+    KernelStatistics = 39,
+
+    /// Receive an IPC message without waiting
+    IpcTryReceive = 40,
+
+    /// Forward an IPC message to another service, which replies in place of
+    /// the current task
+    IpcForward = 41,
+
+    /// Terminate another task
+    TaskKill = 42,
+
+    /// Change a resource limit of a task
+    TaskSetLimit = 43,
+
+    /// Change the level of the kernel log, globally or for a module
+    LogControl = 44,
+
+    /// Read the messages of the kernel log
+    LogRead = 45,
+
+    /// Fill a buffer with random bytes
+    GetRandom = 46,
+
+    /// Create a periodic timer
+    TimerCreate = 47,
+
+    /// Destroy a timer
+    TimerDestroy = 48,
+
+    /// Power off or reboot the system
+    SystemPower = 49,
+
+    /// Connect to a service, waiting until it is registered
+    ServiceConnectWait = 50,
+
+    /// Send an IPC message without waiting for a reply
+    IpcSendOneway = 51,
+
+    /// Subscribe to the memory pressure notifications
+    MemPressureSubscribe = 52,
+
+    /// Unsubscribe from the memory pressure notifications
+    MemPressureUnsubscribe = 53,
+
+    /// Mark a task as essential, so that it is never killed when the kernel
+    /// runs out of memory
+    TaskSetEssential = 54,
+
+    /// Read a property of a node of the device tree given by the bootloader
+    DeviceTreeQuery = 55,
+
+    /// Mask an interrupt bound to the current task
+    IrqMask = 56,
+
+    /// Unmask an interrupt bound to the current task
+    IrqUnmask = 57,
+
+    /// Route an interrupt bound to the current task to a given CPU
+    IrqSetAffinity = 58,
+
+    /// Read the bytes available from the console, waiting until at least one
+    /// byte is available
+    ConsoleReadWait = 59,
+
+    /// Enable or disable the tracing of the syscalls invoked by a task
+    TaskTrace = 60,
+
+    /// Stop all the threads of a task, so that a debugger can inspect them
+    TaskSuspend = 61,
+
+    /// Resume a suspended task, or execute a single instruction of one of its
+    /// threads
+    TaskResume = 62,
+
+    /// Read the registers of a stopped thread
+    TaskGetRegs = 63,
+
+    /// Modify the registers of a stopped thread
+    TaskSetRegs = 64,
+
+    /// Close a connection to a service
+    ServiceDisconnect = 65,
+
     /// Write on the kernel debug output. This should only be used for
     /// debugging purposes, and this is not guaranteed to be available in
     /// production builds.
@@ -63,6 +303,63 @@ impl From<usize> for SyscallOp {
             6 => SyscallOp::IpcSend,
             7 => SyscallOp::IpcReceive,
             8 => SyscallOp::IpcReply,
+            9 => SyscallOp::ServiceStatistics,
+            10 => SyscallOp::ServiceList,
+            11 => SyscallOp::TaskWait,
+            12 => SyscallOp::TaskUsage,
+            13 => SyscallOp::SystemStatistics,
+            14 => SyscallOp::SyscallTable,
+            15 => SyscallOp::MemProtect,
+            16 => SyscallOp::MemMap,
+            17 => SyscallOp::TaskSpawn,
+            18 => SyscallOp::ConsoleWrite,
+            19 => SyscallOp::ConsoleRead,
+            20 => SyscallOp::IrqRegister,
+            21 => SyscallOp::IrqWait,
+            22 => SyscallOp::DeviceQuery,
+            23 => SyscallOp::MemMapDevice,
+            24 => SyscallOp::MemAllocDma,
+            25 => SyscallOp::TaskSetPriority,
+            26 => SyscallOp::SyncCreate,
+            27 => SyscallOp::SyncSignal,
+            28 => SyscallOp::SyncWait,
+            29 => SyscallOp::SyncClear,
+            30 => SyscallOp::SyncDestroy,
+            31 => SyscallOp::FutexWait,
+            32 => SyscallOp::FutexWake,
+            33 => SyscallOp::ThreadCreate,
+            34 => SyscallOp::ThreadExit,
+            35 => SyscallOp::ThreadJoin,
+            36 => SyscallOp::TaskDropCaps,
+            37 => SyscallOp::ServiceAccept,
+            38 => SyscallOp::TraceRead,
+            39 => SyscallOp::KernelStatistics,
+            40 => SyscallOp::IpcTryReceive,
+            41 => SyscallOp::IpcForward,
+            42 => SyscallOp::TaskKill,
+            43 => SyscallOp::TaskSetLimit,
+            44 => SyscallOp::LogControl,
+            45 => SyscallOp::LogRead,
+            46 => SyscallOp::GetRandom,
+            47 => SyscallOp::TimerCreate,
+            48 => SyscallOp::TimerDestroy,
+            49 => SyscallOp::SystemPower,
+            50 => SyscallOp::ServiceConnectWait,
+            51 => SyscallOp::IpcSendOneway,
+            52 => SyscallOp::MemPressureSubscribe,
+            53 => SyscallOp::MemPressureUnsubscribe,
+            54 => SyscallOp::TaskSetEssential,
+            55 => SyscallOp::DeviceTreeQuery,
+            56 => SyscallOp::IrqMask,
+            57 => SyscallOp::IrqUnmask,
+            58 => SyscallOp::IrqSetAffinity,
+            59 => SyscallOp::ConsoleReadWait,
+            60 => SyscallOp::TaskTrace,
+            61 => SyscallOp::TaskSuspend,
+            62 => SyscallOp::TaskResume,
+            63 => SyscallOp::TaskGetRegs,
+            64 => SyscallOp::TaskSetRegs,
+            65 => SyscallOp::ServiceDisconnect,
             999 => SyscallOp::DebugWrite,
             _ => SyscallOp::Unknown,
         }
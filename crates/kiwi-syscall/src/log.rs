@@ -0,0 +1,146 @@
+//! Control of the kernel log. The kernel only prints the messages that are at
+//! least as severe as a level, which is global but can be overridden for
+//! each module of the kernel. Both are set at boot from the kernel command
+//! line, and can be changed afterwards with the
+//! [`crate::SyscallOp::LogControl`] syscall.
+//!
+//! A module is named by its path in the kernel, such as `ipc` or
+//! `user::syscall`, and its level also applies to its submodules unless they
+//! have their own level.
+//!
+//! The kernel also keeps its most recent messages in a ring buffer, including
+//! those printed during the boot process, so that user space can display
+//! them again with the [`crate::SyscallOp::LogRead`] syscall. Each message is
+//! numbered, which lets readers resume where they stopped and notice the
+//! messages that were overwritten before they could read them.
+use zerocopy::{FromBytes, IntoBytes};
+
+/// The maximum length of the name of a module, in bytes.
+pub const MAX_MODULE_LEN: usize = 64;
+
+/// The maximum length of the text of a [`Record`], in bytes. Longer messages
+/// are truncated.
+pub const MAX_RECORD_LEN: usize = 192;
+
+/// The most verbose level of the messages printed by the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// An unknown level.
+    Unknown = 0,
+
+    /// No message is printed.
+    Off = 1,
+
+    /// Only errors are printed.
+    Error = 2,
+
+    /// Errors and warnings are printed.
+    Warn = 3,
+
+    /// Errors, warnings and informational messages are printed.
+    Info = 4,
+
+    /// Debugging messages are also printed.
+    Debug = 5,
+
+    /// All messages are printed, including the most verbose ones.
+    Trace = 6,
+
+    /// The module does not have its own level, and uses the level of its
+    /// parent module or the global level. This is not a valid global level.
+    Default = 7,
+}
+
+impl From<usize> for Level {
+    fn from(value: usize) -> Self {
+        match value {
+            1 => Level::Off,
+            2 => Level::Error,
+            3 => Level::Warn,
+            4 => Level::Info,
+            5 => Level::Debug,
+            6 => Level::Trace,
+            7 => Level::Default,
+            _ => Level::Unknown,
+        }
+    }
+}
+
+impl From<u32> for Level {
+    fn from(value: u32) -> Self {
+        Level::from(value as usize)
+    }
+}
+
+/// A message of the kernel log. We use the C representation to ensure a
+/// predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct Record {
+    /// The number of the message. Messages are numbered from zero in the
+    /// order in which they were logged.
+    pub sequence: u64,
+
+    /// The number of nanoseconds elapsed since boot when the message was
+    /// logged, or zero if it was logged before the timer was set up.
+    pub timestamp: u64,
+
+    /// The raw level of the message (see [`Record::level`]).
+    pub level: u32,
+
+    /// The length of the text of the message, in bytes.
+    pub len: u32,
+
+    /// The UTF-8 text of the message. Only the first `len` bytes are used.
+    pub text: [u8; MAX_RECORD_LEN],
+}
+
+impl Record {
+    /// Returns the level of the message, which is never [`Level::Off`] nor
+    /// [`Level::Default`].
+    #[must_use]
+    pub fn level(&self) -> Level {
+        Level::from(self.level)
+    }
+
+    /// Returns the text of the message. An empty string is returned if the
+    /// record is malformed.
+    #[must_use]
+    pub fn text(&self) -> &str {
+        self.text
+            .get(..self.len as usize)
+            .and_then(|text| core::str::from_utf8(text).ok())
+            .unwrap_or_default()
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when changing a level of the kernel log.
+    pub enum ControlError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The level is unknown, or [`Level::Default`] was given as the global
+        /// level.
+        InvalidLevel = 1,
+
+        /// An invalid module name was provided. It could be due to an invalid
+        /// pointer, the name being longer than [`MAX_MODULE_LEN`] or the name
+        /// not being valid UTF-8.
+        BadModule = 2,
+
+        /// The kernel cannot hold more module levels.
+        TooManyModules = 3,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when reading the kernel log.
+    pub enum ReadError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer where the messages should be written is invalid.
+        BadBuffer = 1,
+    }
+}
@@ -0,0 +1,232 @@
+use crate::ipc::Payload;
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+/// The maximum size of the memory allocated with a single
+/// [`crate::SyscallOp::MemAllocDma`] syscall, in bytes. The memory must be
+/// physically contiguous, so large allocations are likely to fail anyway once
+/// the physical memory is fragmented.
+pub const MAX_DMA_SIZE: usize = 4 * 1024 * 1024;
+
+/// How low the system is on free physical memory. The kernel notifies the
+/// tasks subscribed with [`crate::SyscallOp::MemPressureSubscribe`] each time
+/// the level changes, so that they can release their caches before memory runs
+/// out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(usize)]
+pub enum Pressure {
+    /// There is plenty of free memory.
+    Normal = 0,
+
+    /// The free memory went below the low watermark. Caches that are cheap to
+    /// rebuild should be released.
+    Low = 1,
+
+    /// The free memory went below the critical watermark, and allocations are
+    /// about to fail. Everything that can be released should be.
+    Critical = 2,
+}
+
+impl Pressure {
+    /// Creates a level from its raw representation. Returns `None` if the
+    /// level is unknown.
+    #[must_use]
+    pub const fn from_raw(raw: usize) -> Option<Self> {
+        match raw {
+            0 => Some(Pressure::Normal),
+            1 => Some(Pressure::Low),
+            2 => Some(Pressure::Critical),
+            _ => None,
+        }
+    }
+}
+
+/// The payload of the messages notifying a change of the [`Pressure`] level,
+/// sent by [`crate::ipc::KERNEL_SENDER`] to the subscribed tasks. We use the
+/// C representation to ensure a predictable layout compatible with the
+/// kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct PressureNotice {
+    /// The new level, as the raw representation of a [`Pressure`].
+    pub level: u64,
+
+    /// The number of free pages when the level changed.
+    pub free_pages: u64,
+
+    /// The total number of pages of the system.
+    pub total_pages: u64,
+}
+
+impl Payload for PressureNotice {}
+
+/// The access rights of user memory, as passed to the
+/// [`crate::SyscallOp::MemMap`] and [`crate::SyscallOp::MemProtect`] syscalls. This is a set of flags rather
+/// than an enumeration so that new rights can be added without breaking the
+/// ABI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(transparent)]
+pub struct Protection(usize);
+
+impl Protection {
+    /// No access at all. Any access to the memory will fault, which is useful
+    /// to create guard pages.
+    pub const NONE: Self = Self(0);
+
+    /// The memory can be read.
+    pub const READ: Self = Self(1 << 0);
+
+    /// The memory can be written. On both riscv64 and aarch64, writable
+    /// memory is always readable, so this implies [`Self::READ`].
+    pub const WRITE: Self = Self(1 << 1);
+
+    /// The memory can be executed.
+    pub const EXECUTE: Self = Self(1 << 2);
+
+    /// The memory can be read and written.
+    pub const READ_WRITE: Self = Self(Self::READ.0 | Self::WRITE.0);
+
+    /// The memory can be read and executed.
+    pub const READ_EXECUTE: Self = Self(Self::READ.0 | Self::EXECUTE.0);
+
+    /// All the valid bits.
+    const ALL: usize = Self::READ.0 | Self::WRITE.0 | Self::EXECUTE.0;
+
+    /// Creates flags from their raw representation. Returns `None` if an
+    /// unknown bit is set, so that a program compiled against a more recent
+    /// ABI does not silently get less protection than it asked for.
+    #[must_use]
+    pub const fn from_bits(bits: usize) -> Option<Self> {
+        if bits & !Self::ALL == 0 {
+            Some(Self(bits))
+        } else {
+            None
+        }
+    }
+
+    /// Creates flags from their raw representation without checking for
+    /// unknown bits.
+    #[must_use]
+    pub const fn from_bits_retain(bits: usize) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw representation of the flags.
+    #[must_use]
+    pub const fn bits(&self) -> usize {
+        self.0
+    }
+
+    /// Returns whether all the given flags are set.
+    #[must_use]
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl core::ops::BitOr for Protection {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when changing the protection of user memory.
+    pub enum ProtectError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The start address is not page-aligned, or the range is not fully
+        /// in the user address space.
+        BadRange = 1,
+
+        /// A page of the range is not mapped. The protection of the range is
+        /// left untouched.
+        NotMapped = 2,
+
+        /// The requested protection contains unknown flags.
+        InvalidProtection = 3,
+
+        /// The kernel ran out of memory while splitting a huge page partially
+        /// covered by the range. The protection of the pages before the huge
+        /// page may already have been changed.
+        OutOfMemory = 4,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when mapping anonymous memory.
+    pub enum MapError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The start address is null or not page-aligned, or the range is not
+        /// fully in the part of the user address space available to the
+        /// task.
+        BadRange = 1,
+
+        /// A page of the range is already mapped. Nothing is mapped.
+        AlreadyMapped = 2,
+
+        /// The requested protection contains unknown flags.
+        InvalidProtection = 3,
+
+        /// The kernel ran out of memory. The pages mapped by the syscall are
+        /// released.
+        OutOfMemory = 4,
+
+        /// Mapping the range would exceed the [`crate::task::Limit::Memory`]
+        /// limit of the task. Nothing is mapped.
+        LimitExceeded = 5,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when allocating memory accessible by devices.
+    pub enum AllocDmaError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The start address is null or not page-aligned, the size is zero or
+        /// larger than [`MAX_DMA_SIZE`], or the range is not fully in the part
+        /// of the user address space available to the task.
+        BadRange = 1,
+
+        /// A page of the range is already mapped. Nothing is mapped.
+        AlreadyMapped = 2,
+
+        /// The buffer receiving the physical address cannot be written.
+        /// Nothing is mapped.
+        BadBuffer = 3,
+
+        /// The kernel could not find enough contiguous free memory, or ran
+        /// out of memory while mapping it. Nothing is mapped.
+        OutOfMemory = 4,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when subscribing to the memory pressure
+    /// notifications.
+    pub enum SubscribeError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The operation of the messages is
+        /// [`crate::power::NOTICE_OPERATION`], which is reserved.
+        InvalidOperation = 1,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when unsubscribing from the memory pressure
+    /// notifications.
+    pub enum UnsubscribeError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The task is not subscribed.
+        NotSubscribed = 1,
+    }
+}
@@ -0,0 +1,65 @@
+//! Powering off and rebooting the system. A task holding the
+//! [`crate::capability::Capabilities::POWER`] capability, which only `init`
+//! holds, invokes the [`crate::SyscallOp::SystemPower`] syscall. The kernel
+//! then sends a [`Notice`] to every registered service, as an IPC message sent
+//! by [`crate::ipc::KERNEL_SENDER`] with the [`NOTICE_OPERATION`] operation,
+//! and gives the services [`GRACE_PERIOD_MS`] milliseconds to unregister or
+//! terminate before powering off or rebooting the machine.
+use crate::ipc::Payload;
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+/// The operation of the notices sent to the services. The messages of timers
+/// cannot use this operation, so that a notice is never confused with them.
+pub const NOTICE_OPERATION: usize = usize::MAX;
+
+/// How long the kernel waits for the notified services to unregister or
+/// terminate before powering off or rebooting the machine, in milliseconds.
+pub const GRACE_PERIOD_MS: u64 = 2000;
+
+/// What the system does when it goes down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum Action {
+    /// Power off the machine.
+    PowerOff = 0,
+
+    /// Reboot the machine.
+    Reboot = 1,
+}
+
+impl Action {
+    /// Creates an action from its raw representation. Returns `None` if the
+    /// action is unknown.
+    #[must_use]
+    pub const fn from_raw(raw: usize) -> Option<Self> {
+        match raw {
+            0 => Some(Action::PowerOff),
+            1 => Some(Action::Reboot),
+            _ => None,
+        }
+    }
+}
+
+/// The payload of the notices sent to the services before the system goes
+/// down. We use the C representation to ensure a predictable layout
+/// compatible with the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct Notice {
+    /// The raw [`Action`] about to be performed.
+    pub action: u64,
+}
+
+impl Payload for Notice {}
+
+syscall_error! {
+    /// Errors that can occur when powering off or rebooting the system. The
+    /// syscall does not return if it succeeds.
+    pub enum PowerError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The action is unknown.
+        InvalidAction = 1,
+    }
+}
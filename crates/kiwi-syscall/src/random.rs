@@ -0,0 +1,20 @@
+//! Random bytes generated by the kernel. The kernel keeps an entropy pool,
+//! seeded at boot with the seed given by the bootloader and fed with the
+//! timing of interrupts, from which user space draws random bytes with the
+//! [`crate::SyscallOp::GetRandom`] syscall.
+/// The maximum number of bytes that can be generated with a single
+/// [`crate::SyscallOp::GetRandom`] syscall. Larger buffers must be filled
+/// with several syscalls.
+pub const MAX_FILL_SIZE: usize = 4096;
+
+syscall_error! {
+    /// Errors that can occur when filling a buffer with random bytes.
+    pub enum FillError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer is not valid for writes, or is larger than
+        /// [`MAX_FILL_SIZE`].
+        BadBuffer = 1,
+    }
+}
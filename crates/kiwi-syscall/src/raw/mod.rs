@@ -0,0 +1,48 @@
+//! Raw syscall invocation. This module provides the architecture-specific
+//! stubs that trap into the kernel, so that every user space runtime invokes
+//! syscalls the same way instead of duplicating the inline assembly. It is
+//! only available with the `userspace` feature, since the kernel never
+//! invokes syscalls itself.
+//!
+//! The stubs are named after the number of arguments they pass to the kernel
+//! (`syscall0` to `syscall6`). Most code should use [`invoke`], which encodes
+//! the arguments of a syscall from their typed description in [`crate::args`].
+use crate::args::{SyscallArgs, SyscallResult};
+
+#[cfg(target_arch = "riscv64")]
+mod riscv64;
+#[cfg(target_arch = "riscv64")]
+pub use riscv64::*;
+
+#[cfg(target_arch = "aarch64")]
+mod aarch64;
+#[cfg(target_arch = "aarch64")]
+pub use aarch64::*;
+
+#[cfg(not(any(target_arch = "riscv64", target_arch = "aarch64")))]
+compile_error!("Raw syscalls are not implemented for this architecture");
+
+/// Invokes the syscall described by the given arguments, and returns the raw
+/// result of the syscall.
+///
+/// # Safety
+/// The caller must ensure that all pointers in the arguments are valid for
+/// the syscall, as the kernel may read from or write to them.
+pub unsafe fn invoke<A: SyscallArgs>(args: &A) -> SyscallResult {
+    let [a0, a1, a2, a3, a4, a5] = args.encode();
+    // SAFETY: The caller guarantees that the arguments are valid.
+    SyscallResult::from_raw(unsafe { syscall6(A::OP, a0, a1, a2, a3, a4, a5) })
+}
+
+/// Invokes the syscall described by the given arguments, which must never
+/// return, like the syscalls that terminate the current task or thread.
+///
+/// # Safety
+/// The caller must ensure that all pointers in the arguments are valid for
+/// the syscall, and that the syscall never returns.
+pub unsafe fn invoke_noreturn<A: SyscallArgs>(args: &A) -> ! {
+    let [a0, ..] = args.encode();
+    // SAFETY: The caller guarantees that the arguments are valid and that the
+    // syscall never returns.
+    unsafe { syscall1_noreturn(A::OP, a0) }
+}
@@ -0,0 +1,190 @@
+//! Syscall stubs for RISC-V. The syscall number is passed in `a7`, the
+//! arguments in `a0` to `a5`, and the kernel returns the raw result in `a0`.
+use crate::SyscallOp;
+
+/// Invokes the given syscall without arguments.
+///
+/// # Safety
+/// The caller must ensure that the syscall can be invoked without arguments.
+#[inline]
+pub unsafe fn syscall0(op: SyscallOp) -> usize {
+    let ret;
+    // SAFETY: The caller guarantees that the syscall is valid.
+    unsafe {
+        core::arch::asm!("ecall",
+            in("a7") op as usize,
+            lateout("a0") ret,
+            options(nostack, preserves_flags)
+        );
+    }
+    ret
+}
+
+/// Invokes the given syscall with one argument.
+///
+/// # Safety
+/// The caller must ensure that the argument is valid for the syscall, as the
+/// kernel may read from or write to the memory it points to.
+#[inline]
+pub unsafe fn syscall1(op: SyscallOp, a0: usize) -> usize {
+    let ret;
+    // SAFETY: The caller guarantees that the syscall and its argument are
+    // valid.
+    unsafe {
+        core::arch::asm!("ecall",
+            in("a7") op as usize,
+            inlateout("a0") a0 => ret,
+            options(nostack, preserves_flags)
+        );
+    }
+    ret
+}
+
+/// Invokes the given syscall with two arguments.
+///
+/// # Safety
+/// The caller must ensure that the arguments are valid for the syscall, as
+/// the kernel may read from or write to the memory they point to.
+#[inline]
+pub unsafe fn syscall2(op: SyscallOp, a0: usize, a1: usize) -> usize {
+    let ret;
+    // SAFETY: The caller guarantees that the syscall and its arguments are
+    // valid.
+    unsafe {
+        core::arch::asm!("ecall",
+            in("a7") op as usize,
+            inlateout("a0") a0 => ret,
+            in("a1") a1,
+            options(nostack, preserves_flags)
+        );
+    }
+    ret
+}
+
+/// Invokes the given syscall with three arguments.
+///
+/// # Safety
+/// The caller must ensure that the arguments are valid for the syscall, as
+/// the kernel may read from or write to the memory they point to.
+#[inline]
+pub unsafe fn syscall3(op: SyscallOp, a0: usize, a1: usize, a2: usize) -> usize {
+    let ret;
+    // SAFETY: The caller guarantees that the syscall and its arguments are
+    // valid.
+    unsafe {
+        core::arch::asm!("ecall",
+            in("a7") op as usize,
+            inlateout("a0") a0 => ret,
+            in("a1") a1,
+            in("a2") a2,
+            options(nostack, preserves_flags)
+        );
+    }
+    ret
+}
+
+/// Invokes the given syscall with four arguments.
+///
+/// # Safety
+/// The caller must ensure that the arguments are valid for the syscall, as
+/// the kernel may read from or write to the memory they point to.
+#[inline]
+pub unsafe fn syscall4(op: SyscallOp, a0: usize, a1: usize, a2: usize, a3: usize) -> usize {
+    let ret;
+    // SAFETY: The caller guarantees that the syscall and its arguments are
+    // valid.
+    unsafe {
+        core::arch::asm!("ecall",
+            in("a7") op as usize,
+            inlateout("a0") a0 => ret,
+            in("a1") a1,
+            in("a2") a2,
+            in("a3") a3,
+            options(nostack, preserves_flags)
+        );
+    }
+    ret
+}
+
+/// Invokes the given syscall with five arguments.
+///
+/// # Safety
+/// The caller must ensure that the arguments are valid for the syscall, as
+/// the kernel may read from or write to the memory they point to.
+#[inline]
+pub unsafe fn syscall5(
+    op: SyscallOp,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+) -> usize {
+    let ret;
+    // SAFETY: The caller guarantees that the syscall and its arguments are
+    // valid.
+    unsafe {
+        core::arch::asm!("ecall",
+            in("a7") op as usize,
+            inlateout("a0") a0 => ret,
+            in("a1") a1,
+            in("a2") a2,
+            in("a3") a3,
+            in("a4") a4,
+            options(nostack, preserves_flags)
+        );
+    }
+    ret
+}
+
+/// Invokes the given syscall with six arguments, which is the maximum number
+/// of arguments of a syscall.
+///
+/// # Safety
+/// The caller must ensure that the arguments are valid for the syscall, as
+/// the kernel may read from or write to the memory they point to.
+#[inline]
+pub unsafe fn syscall6(
+    op: SyscallOp,
+    a0: usize,
+    a1: usize,
+    a2: usize,
+    a3: usize,
+    a4: usize,
+    a5: usize,
+) -> usize {
+    let ret;
+    // SAFETY: The caller guarantees that the syscall and its arguments are
+    // valid.
+    unsafe {
+        core::arch::asm!("ecall",
+            in("a7") op as usize,
+            inlateout("a0") a0 => ret,
+            in("a1") a1,
+            in("a2") a2,
+            in("a3") a3,
+            in("a4") a4,
+            in("a5") a5,
+            options(nostack, preserves_flags)
+        );
+    }
+    ret
+}
+
+/// Invokes the given syscall with one argument, which never returns.
+///
+/// # Safety
+/// The caller must ensure that the argument is valid for the syscall, and
+/// that the syscall never returns.
+#[inline]
+pub unsafe fn syscall1_noreturn(op: SyscallOp, a0: usize) -> ! {
+    // SAFETY: The caller guarantees that the syscall and its argument are
+    // valid, and that the syscall never returns.
+    unsafe {
+        core::arch::asm!("ecall",
+            in("a7") op as usize,
+            in("a0") a0,
+            options(noreturn)
+        );
+    }
+}
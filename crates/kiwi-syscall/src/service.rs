@@ -1,71 +1,219 @@
-/// Errors that may occur during service registration.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum RegisterError {
-    /// An unknown error occurred.
-    Unknown = 0,
-
-    /// An invalid name was provided. It could be due to an invalid pointer,
-    /// length, or the name not being valid UTF-8.
-    BadName = 1,
-
-    /// The service name is already taken by another service.
-    NameNotAvailable = 2,
-
-    /// The task is already registered as a service provider and cannot
-    /// be registered again.
-    TaskAlreadyRegistered = 3,
+use zerocopy::{FromBytes, IntoBytes};
+
+/// The maximum length of a service name, in bytes.
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Flags that can be given when registering a service to customize how the
+/// kernel handles it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(transparent)]
+pub struct RegisterFlags(usize);
+
+impl RegisterFlags {
+    /// No flags.
+    pub const NONE: Self = Self(0);
+
+    /// Do not collect any statistics about the messages sent to the service.
+    /// This can be used by services that handle sensitive requests or that
+    /// cannot afford the small overhead of statistics collection.
+    pub const NO_STATISTICS: Self = Self(1 << 0);
+
+    /// Only accept connections from the tasks explicitly allowed by the
+    /// service with [`crate::SyscallOp::ServiceAccept`]. Other tasks fail to
+    /// connect with [`ConnectionError::AccessDenied`]. This is intended for
+    /// security-sensitive services that must not be callable by arbitrary
+    /// tasks.
+    pub const RESTRICTED: Self = Self(1 << 1);
+
+    /// Creates flags from their raw representation. Unknown bits are
+    /// silently ignored.
+    #[must_use]
+    pub const fn from_bits(bits: usize) -> Self {
+        Self(bits & (Self::NO_STATISTICS.0 | Self::RESTRICTED.0))
+    }
+
+    /// Returns the raw representation of the flags.
+    #[must_use]
+    pub const fn bits(&self) -> usize {
+        self.0
+    }
+
+    /// Returns whether all the given flags are set.
+    #[must_use]
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
 }
 
-impl From<RegisterError> for isize {
-    fn from(error: RegisterError) -> Self {
-        match error {
-            RegisterError::Unknown => 0,
-            RegisterError::BadName => 1,
-            RegisterError::NameNotAvailable => 2,
-            RegisterError::TaskAlreadyRegistered => 3,
-        }
+impl core::ops::BitOr for RegisterFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
     }
 }
 
-/// Errors that may occur during service unregistration.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum UnregisterError {
-    /// An unknown error occurred.
-    Unknown = 0,
+/// Information about a registered service, as returned when enumerating the
+/// services registered in the kernel. We use the C representation to ensure a
+/// predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct ServiceInfo {
+    /// The identifier of the task providing the service.
+    pub task: usize,
+
+    /// The serial number of the registration of the service. It is the value
+    /// of [`crate::ipc::SenderInfo::service`] in the messages sent by the
+    /// service, and changes if the service registers again.
+    pub registration: u64,
 
-    /// The service unregistration feature is not yet implemented.
-    NotImplemented = 1,
+    /// The length of the name of the service, in bytes.
+    pub name_len: usize,
+
+    /// The name of the service. Only the first `name_len` bytes are valid,
+    /// and are guaranteed to be valid UTF-8.
+    pub name: [u8; MAX_NAME_LEN],
 }
 
-impl From<UnregisterError> for isize {
-    fn from(error: UnregisterError) -> Self {
-        match error {
-            UnregisterError::Unknown => 0,
-            UnregisterError::NotImplemented => 1,
-        }
+impl ServiceInfo {
+    /// Returns the name of the service, or `None` if the name is not valid
+    /// UTF-8. This should never happen with information filled in by the
+    /// kernel.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        core::str::from_utf8(self.name.get(..self.name_len)?).ok()
     }
 }
 
-/// Errors that may occur during service connection.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ConnectionError {
-    /// An unknown error occurred.
-    Unknown = 0,
+/// Statistics about the messages of a given kind sent to a service. We use the
+/// C representation to ensure a predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct KindStatistics {
+    /// The kind of the messages.
+    pub kind: usize,
+
+    /// The number of messages of this kind that received a reply.
+    pub count: u64,
+
+    /// The sum of the latencies of all these messages, in nanoseconds. The
+    /// latency of a message is the time elapsed between the moment it was
+    /// sent and the moment the reply was received.
+    pub total_latency: u64,
+}
+
+syscall_error! {
+    /// Errors that may occur during service registration.
+    pub enum RegisterError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// An invalid name was provided. It could be due to an invalid pointer,
+        /// length, the name being longer than [`MAX_NAME_LEN`] or the name not
+        /// being valid UTF-8.
+        BadName = 1,
 
-    /// An invalid name was provided. It could be due to an invalid pointer,
-    /// length, or the name not being valid UTF-8.
-    BadName = 1,
+        /// The service name is already taken by another service.
+        NameNotAvailable = 2,
 
-    /// No service with the specified name exists.
-    ServiceNotFound = 2,
+        /// The task is already registered as a service provider and cannot
+        /// be registered again.
+        TaskAlreadyRegistered = 3,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur during service unregistration.
+    pub enum UnregisterError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The task is not registered as a service provider.
+        NotRegistered = 1,
+    }
 }
 
-impl From<ConnectionError> for isize {
-    fn from(error: ConnectionError) -> Self {
-        match error {
-            ConnectionError::Unknown => 0,
-            ConnectionError::BadName => 1,
-            ConnectionError::ServiceNotFound => 2,
-        }
+syscall_error! {
+    /// Errors that may occur during service connection.
+    pub enum ConnectionError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// An invalid name was provided. It could be due to an invalid pointer,
+        /// length, or the name not being valid UTF-8.
+        BadName = 1,
+
+        /// No service with the specified name exists.
+        ServiceNotFound = 2,
+
+        /// The service is restricted and did not accept connections from the
+        /// current task.
+        AccessDenied = 3,
+
+        /// The task already holds as many connections as it is allowed to.
+        /// Connections that are no longer used can be closed with
+        /// [`crate::SyscallOp::ServiceDisconnect`].
+        TooManyConnections = 4,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when closing a connection to a service.
+    pub enum DisconnectError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The handle does not refer to a connection of the current task.
+        InvalidHandle = 1,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when allowing a task to connect to a service.
+    pub enum AcceptError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The current task is not registered as a service provider.
+        NotRegistered = 1,
+
+        /// The service was not registered with [`RegisterFlags::RESTRICTED`],
+        /// so any task can already connect to it.
+        NotRestricted = 2,
+
+        /// The task to accept does not exist.
+        TaskNotFound = 3,
+
+        /// The service already accepts as many tasks as it is allowed to.
+        TooManyTasks = 4,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when retrieving the statistics of a service.
+    pub enum StatisticsError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// An invalid name was provided. It could be due to an invalid pointer,
+        /// length, or the name not being valid UTF-8.
+        BadName = 1,
+
+        /// No service with the specified name exists.
+        ServiceNotFound = 2,
+
+        /// The buffer where the statistics should be written is invalid.
+        BadBuffer = 3,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when enumerating the registered services.
+    pub enum ListError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer where the services should be written is invalid.
+        BadBuffer = 1,
     }
 }
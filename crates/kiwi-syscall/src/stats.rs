@@ -0,0 +1,93 @@
+use zerocopy::{FromBytes, IntoBytes};
+
+/// System-wide statistics maintained by the kernel. We use the C
+/// representation to ensure a predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct SystemStatistics {
+    /// The number of minor page faults resolved by the kernel since boot.
+    pub minor_faults: u64,
+
+    /// The number of major page faults resolved by the kernel since boot.
+    pub major_faults: u64,
+
+    /// The number of bytes currently mapped for the kernel heap.
+    pub heap_size: u64,
+
+    /// The largest number of bytes ever mapped for the kernel heap since
+    /// boot.
+    pub heap_peak: u64,
+}
+
+/// The maximum number of cores whose run queue is described in
+/// [`KernelStatistics`]. The kernel never supports more cores than this.
+pub const MAX_CPUS: usize = 8;
+
+/// Counters about the activity of the kernel since boot, along with the
+/// current state of its memory and its executor. The counters are collected
+/// by each core separately and summed when read. We use the C representation
+/// to ensure a predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct KernelStatistics {
+    /// The number of syscalls invoked since boot, including the unknown and
+    /// the denied ones.
+    pub syscalls: u64,
+
+    /// The number of times the executor switched to a task since boot.
+    pub context_switches: u64,
+
+    /// The number of IPC messages delivered since boot, excluding replies.
+    pub ipc_messages: u64,
+
+    /// The total number of physical frames of the system.
+    pub frames_total: u64,
+
+    /// The number of physical frames currently allocated.
+    pub frames_allocated: u64,
+
+    /// The number of physical frames currently free.
+    pub frames_free: u64,
+
+    /// The number of tasks currently known by the executor, each thread of a
+    /// task counting as a separate task. This includes the background tasks
+    /// of the kernel counted in `kernel_tasks`.
+    pub tasks: u64,
+
+    /// The number of woken tasks that were not yet taken by any core.
+    pub pending_wakeups: u64,
+
+    /// The number of cores whose run queue is described in `ready`.
+    pub cpus: u64,
+
+    /// The number of tasks ready to run on each core. Only the first `cpus`
+    /// entries are meaningful.
+    pub ready: [u64; MAX_CPUS],
+
+    /// The number of background tasks of the kernel, which run kernel code
+    /// only and belong to no user task.
+    pub kernel_tasks: u64,
+}
+
+/// The number of times a syscall was invoked since boot. We use the C
+/// representation to ensure a predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct SyscallCount {
+    /// The number of the syscall.
+    pub op: usize,
+
+    /// The number of times the syscall was invoked.
+    pub count: u64,
+}
+
+syscall_error! {
+    /// Errors that may occur when retrieving the system-wide statistics.
+    pub enum StatisticsError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer where the statistics should be written is invalid.
+        BadBuffer = 1,
+    }
+}
@@ -0,0 +1,124 @@
+//! Synchronization objects that tasks can block on, without exchanging IPC
+//! messages. An object is created with [`crate::SyscallOp::SyncCreate`], and
+//! is identified by a number that is unique in the whole system and never
+//! reused. This number can be given to other tasks (in a message, or in the
+//! arguments of a spawned task) so that they can use the object too: any task
+//! holding the [`crate::capability::Capabilities::SYNC`] capability can use
+//! an object once it knows its identifier.
+//!
+//! An object holds a single value, whose meaning depends on the [`Kind`] of
+//! the object. Signaling an object updates its value and wakes up the tasks
+//! waiting on it, and waiting on an object blocks until its value allows the
+//! wait to complete. An object is destroyed when the task that created it
+//! destroys it or terminates, and the tasks waiting on it are then woken up
+//! with an error.
+
+/// The largest value that an object can hold. Values are returned by the
+/// syscalls, so they must never be confused with an error code.
+pub const MAX_VALUE: usize = isize::MAX.cast_unsigned();
+
+/// The kind of a synchronization object, which defines how signaling and
+/// waiting on the object affect its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum Kind {
+    /// A manual-reset event. Signaling the event sets it and wakes up all the
+    /// waiting tasks. Waiting on a set event completes immediately, and the
+    /// event stays set until it is cleared. Its value is 1 when it is set,
+    /// and 0 otherwise.
+    Event = 0,
+
+    /// A counting semaphore. Signaling the semaphore adds the given count to
+    /// its value, and waiting on it blocks until its value is not zero, then
+    /// decrements it.
+    Semaphore = 1,
+
+    /// A notification word. Signaling the notification sets the given bits
+    /// in its value, and waiting on it blocks until a bit is set, then returns
+    /// all the bits set and clears them. This allows a single object to carry
+    /// several kinds of notifications at once.
+    Notification = 2,
+}
+
+impl Kind {
+    /// Creates a kind from its raw representation. Returns `None` if the kind
+    /// is unknown.
+    #[must_use]
+    pub const fn from_raw(raw: usize) -> Option<Self> {
+        match raw {
+            0 => Some(Kind::Event),
+            1 => Some(Kind::Semaphore),
+            2 => Some(Kind::Notification),
+            _ => None,
+        }
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when creating a synchronization object.
+    pub enum CreateError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The kind of the object is unknown.
+        InvalidKind = 1,
+
+        /// The initial value is larger than [`MAX_VALUE`], or is neither 0
+        /// nor 1 for an event.
+        InvalidValue = 2,
+
+        /// The task already created as many objects as allowed.
+        TooManyObjects = 3,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when signaling a synchronization object.
+    pub enum SignalError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The object does not exist, or was destroyed.
+        NotFound = 1,
+
+        /// The value of the object would exceed [`MAX_VALUE`]. The object is
+        /// left unchanged.
+        Overflow = 2,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when waiting on a synchronization object.
+    pub enum WaitError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The object does not exist, or was destroyed while waiting on it.
+        NotFound = 1,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when clearing a synchronization object.
+    pub enum ClearError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The object does not exist, or was destroyed.
+        NotFound = 1,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when destroying a synchronization object.
+    pub enum DestroyError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The object does not exist, or was already destroyed.
+        NotFound = 1,
+
+        /// The object was not created by the current task.
+        NotOwner = 2,
+    }
+}
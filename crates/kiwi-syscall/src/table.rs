@@ -0,0 +1,248 @@
+//! Machine-readable description of the syscall interface. The description of
+//! each syscall is generated from the same definitions that the kernel uses
+//! to decode the arguments of the syscall, so that tools (debuggers, tracers,
+//! simulators) can retrieve the syscall table from the kernel at runtime and
+//! never go out of sync with it.
+use crate::{SyscallOp, args::SyscallArgs};
+use zerocopy::{FromBytes, IntoBytes};
+
+/// The version of the syscall ABI implemented by this crate. It is increased
+/// each time a syscall is added or its arguments are changed, including the
+/// layout of the structures exchanged with the kernel.
+pub const ABI_VERSION: usize = 37;
+
+/// The maximum length of the names in a [`SyscallDescriptor`], in bytes.
+pub const MAX_NAME_LEN: usize = 32;
+
+/// The maximum number of arguments of a syscall.
+pub const MAX_ARGS: usize = 6;
+
+/// The kind of an argument of a syscall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// The argument is unused.
+    None = 0,
+
+    /// An unsigned integer.
+    Unsigned = 1,
+
+    /// A signed integer.
+    Signed = 2,
+
+    /// A set of flags.
+    Flags = 3,
+
+    /// A pointer to memory that is only read by the kernel.
+    ConstPointer = 4,
+
+    /// A pointer to memory that may be written by the kernel.
+    MutPointer = 5,
+}
+
+impl From<usize> for ArgKind {
+    fn from(value: usize) -> Self {
+        match value {
+            1 => ArgKind::Unsigned,
+            2 => ArgKind::Signed,
+            3 => ArgKind::Flags,
+            4 => ArgKind::ConstPointer,
+            5 => ArgKind::MutPointer,
+            _ => ArgKind::None,
+        }
+    }
+}
+
+/// The description of a syscall. We use the C representation to ensure a
+/// predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct SyscallDescriptor {
+    /// The number of the syscall.
+    pub op: usize,
+
+    /// The length of the name of the syscall, in bytes.
+    pub name_len: usize,
+
+    /// The name of the syscall. Only the first `name_len` bytes are valid.
+    pub name: [u8; MAX_NAME_LEN],
+
+    /// The number of arguments of the syscall.
+    pub arg_count: usize,
+
+    /// The kind of each argument, as an [`ArgKind`]. Only the first
+    /// `arg_count` entries are valid.
+    pub arg_kinds: [usize; MAX_ARGS],
+
+    /// The length of the name of the error type of the syscall, in bytes. It
+    /// is zero if the syscall cannot fail.
+    pub error_len: usize,
+
+    /// The name of the error type of the syscall, qualified with the module of
+    /// this crate where it is defined (e.g. `ipc::SendError`). Only the first
+    /// `error_len` bytes are valid.
+    pub error: [u8; MAX_NAME_LEN],
+
+    /// The minimum ABI version that supports the syscall.
+    pub min_abi: usize,
+}
+
+impl SyscallDescriptor {
+    /// Creates the description of a syscall.
+    ///
+    /// # Panics
+    /// Panics if a name is longer than [`MAX_NAME_LEN`] or if there are more
+    /// than [`MAX_ARGS`] arguments. Since descriptors are built at compile
+    /// time, this results in a compilation error.
+    #[must_use]
+    pub const fn new(op: SyscallOp, name: &str, args: &[ArgKind], error: &str) -> Self {
+        assert!(args.len() <= MAX_ARGS);
+        let mut arg_kinds = [0; MAX_ARGS];
+        let mut i = 0;
+        while i < args.len() {
+            arg_kinds[i] = args[i] as usize;
+            i += 1;
+        }
+
+        Self {
+            op: op as usize,
+            name_len: name.len(),
+            name: Self::pad(name),
+            arg_count: args.len(),
+            arg_kinds,
+            error_len: error.len(),
+            error: Self::pad(error),
+            // Syscalls introduced after the first version of the ABI are
+            // marked with `since` in the table.
+            min_abi: 1,
+        }
+    }
+
+    /// Returns the same description, for a syscall introduced in the given
+    /// ABI version.
+    #[must_use]
+    pub const fn since(mut self, abi: usize) -> Self {
+        self.min_abi = abi;
+        self
+    }
+
+    /// Returns the name of the syscall, or `None` if the name is not valid
+    /// UTF-8.
+    #[must_use]
+    pub fn name(&self) -> Option<&str> {
+        core::str::from_utf8(self.name.get(..self.name_len)?).ok()
+    }
+
+    /// Returns the name of the error type of the syscall, or `None` if the
+    /// syscall cannot fail or if the name is not valid UTF-8.
+    #[must_use]
+    pub fn error(&self) -> Option<&str> {
+        let error = self.error.get(..self.error_len)?;
+        (!error.is_empty())
+            .then(|| core::str::from_utf8(error).ok())
+            .flatten()
+    }
+
+    /// Returns an iterator over the kinds of the arguments of the syscall.
+    pub fn args(&self) -> impl Iterator<Item = ArgKind> + '_ {
+        self.arg_kinds
+            .iter()
+            .take(self.arg_count)
+            .map(|&kind| ArgKind::from(kind))
+    }
+
+    /// Copies the given string into a zero-padded array.
+    const fn pad(string: &str) -> [u8; MAX_NAME_LEN] {
+        assert!(string.len() <= MAX_NAME_LEN);
+        let mut array = [0; MAX_NAME_LEN];
+        let bytes = string.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            array[i] = bytes[i];
+            i += 1;
+        }
+        array
+    }
+}
+
+/// The description of all the syscalls supported by the kernel, sorted by
+/// syscall number.
+pub const TABLE: [SyscallDescriptor; 67] = [
+    crate::args::Nop::DESCRIPTOR,
+    crate::args::TaskExit::DESCRIPTOR,
+    crate::args::TaskYield::DESCRIPTOR,
+    crate::args::ServiceRegister::DESCRIPTOR,
+    crate::args::ServiceUnregister::DESCRIPTOR,
+    crate::args::ServiceConnect::DESCRIPTOR,
+    crate::args::IpcSend::DESCRIPTOR,
+    crate::args::IpcReceive::DESCRIPTOR,
+    crate::args::IpcReply::DESCRIPTOR,
+    crate::args::ServiceStatistics::DESCRIPTOR,
+    crate::args::ServiceList::DESCRIPTOR,
+    crate::args::TaskWait::DESCRIPTOR,
+    crate::args::TaskUsage::DESCRIPTOR,
+    crate::args::SystemStatistics::DESCRIPTOR,
+    crate::args::SyscallTable::DESCRIPTOR,
+    crate::args::MemProtect::DESCRIPTOR.since(2),
+    crate::args::MemMap::DESCRIPTOR.since(3),
+    crate::args::TaskSpawn::DESCRIPTOR.since(4),
+    crate::args::ConsoleWrite::DESCRIPTOR.since(5),
+    crate::args::ConsoleRead::DESCRIPTOR.since(5),
+    crate::args::IrqRegister::DESCRIPTOR.since(6),
+    crate::args::IrqWait::DESCRIPTOR.since(6),
+    crate::args::DeviceQuery::DESCRIPTOR.since(7),
+    crate::args::MemMapDevice::DESCRIPTOR.since(7),
+    crate::args::MemAllocDma::DESCRIPTOR.since(8),
+    crate::args::TaskSetPriority::DESCRIPTOR.since(9),
+    crate::args::SyncCreate::DESCRIPTOR.since(10),
+    crate::args::SyncSignal::DESCRIPTOR.since(10),
+    crate::args::SyncWait::DESCRIPTOR.since(10),
+    crate::args::SyncClear::DESCRIPTOR.since(10),
+    crate::args::SyncDestroy::DESCRIPTOR.since(10),
+    crate::args::FutexWait::DESCRIPTOR.since(11),
+    crate::args::FutexWake::DESCRIPTOR.since(11),
+    crate::args::ThreadCreate::DESCRIPTOR.since(12),
+    crate::args::ThreadExit::DESCRIPTOR.since(12),
+    crate::args::ThreadJoin::DESCRIPTOR.since(12),
+    crate::args::TaskDropCaps::DESCRIPTOR.since(13),
+    crate::args::ServiceAccept::DESCRIPTOR.since(14),
+    crate::args::TraceRead::DESCRIPTOR.since(15),
+    crate::args::KernelStatistics::DESCRIPTOR.since(16),
+    crate::args::IpcTryReceive::DESCRIPTOR.since(17),
+    crate::args::IpcForward::DESCRIPTOR.since(18),
+    crate::args::TaskKill::DESCRIPTOR.since(20),
+    crate::args::TaskSetLimit::DESCRIPTOR.since(21),
+    crate::args::LogControl::DESCRIPTOR.since(22),
+    crate::args::LogRead::DESCRIPTOR.since(23),
+    crate::args::GetRandom::DESCRIPTOR.since(24),
+    crate::args::TimerCreate::DESCRIPTOR.since(25),
+    crate::args::TimerDestroy::DESCRIPTOR.since(25),
+    crate::args::SystemPower::DESCRIPTOR.since(26),
+    crate::args::ServiceConnectWait::DESCRIPTOR.since(27),
+    crate::args::IpcSendOneway::DESCRIPTOR.since(28),
+    crate::args::MemPressureSubscribe::DESCRIPTOR.since(29),
+    crate::args::MemPressureUnsubscribe::DESCRIPTOR.since(29),
+    crate::args::TaskSetEssential::DESCRIPTOR.since(30),
+    crate::args::DeviceTreeQuery::DESCRIPTOR.since(31),
+    crate::args::IrqMask::DESCRIPTOR.since(32),
+    crate::args::IrqUnmask::DESCRIPTOR.since(32),
+    crate::args::IrqSetAffinity::DESCRIPTOR.since(32),
+    crate::args::ConsoleReadWait::DESCRIPTOR.since(33),
+    crate::args::TaskTrace::DESCRIPTOR.since(35),
+    crate::args::TaskSuspend::DESCRIPTOR.since(36),
+    crate::args::TaskResume::DESCRIPTOR.since(36),
+    crate::args::TaskGetRegs::DESCRIPTOR.since(36),
+    crate::args::TaskSetRegs::DESCRIPTOR.since(36),
+    crate::args::ServiceDisconnect::DESCRIPTOR.since(37),
+    crate::args::DebugWrite::DESCRIPTOR,
+];
+
+syscall_error! {
+    /// Errors that may occur when retrieving the syscall table.
+    pub enum TableError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer where the table should be written is invalid.
+        BadBuffer = 1,
+    }
+}
@@ -0,0 +1,550 @@
+use zerocopy::{FromBytes, IntoBytes};
+
+/// The kind of fault that caused a task to be terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    /// An unknown fault occurred.
+    Unknown = 0,
+
+    /// The task accessed a page that is not mapped, or that is mapped without
+    /// the required permissions.
+    PageFault = 1,
+
+    /// The task accessed a physical address that does not exist or that
+    /// cannot be accessed.
+    AccessFault = 2,
+
+    /// The task executed an invalid instruction.
+    IllegalInstruction = 3,
+
+    /// The task performed a misaligned memory access.
+    Misaligned = 4,
+}
+
+impl From<usize> for FaultKind {
+    fn from(value: usize) -> Self {
+        match value {
+            1 => FaultKind::PageFault,
+            2 => FaultKind::AccessFault,
+            3 => FaultKind::IllegalInstruction,
+            4 => FaultKind::Misaligned,
+            _ => FaultKind::Unknown,
+        }
+    }
+}
+
+/// A resource limit of a task, which can be changed with the
+/// [`crate::SyscallOp::TaskSetLimit`] syscall. Spawned tasks inherit the
+/// limits of their parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// An unknown limit.
+    Unknown = 0,
+
+    /// The maximum number of pages that the task can map with the
+    /// [`crate::SyscallOp::MemMap`] syscall.
+    Memory = 1,
+
+    /// The maximum number of children of the task that can be alive at the
+    /// same time.
+    Children = 2,
+
+    /// The maximum number of IPC messages that can be pending in the mailbox
+    /// of the task, between 1 and the capacity of the mailbox.
+    Messages = 3,
+}
+
+impl From<usize> for Limit {
+    fn from(value: usize) -> Self {
+        match value {
+            1 => Limit::Memory,
+            2 => Limit::Children,
+            3 => Limit::Messages,
+            _ => Limit::Unknown,
+        }
+    }
+}
+
+/// The value of a [`Limit`] that does not limit anything. Larger values are
+/// treated as this one. It is not `usize::MAX`, since the previous value of a
+/// limit returned by a syscall must not be confused with an error code.
+pub const UNLIMITED: usize = isize::MAX.cast_unsigned();
+
+/// The reason why a task terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    /// The task exited voluntarily with the given exit code.
+    Exited(i32),
+
+    /// The task was killed by the task with the given identifier.
+    Killed(usize),
+
+    /// The task was terminated because of a fault. The address is the faulting
+    /// address if the fault was caused by an invalid memory access.
+    Faulted {
+        kind: FaultKind,
+        address: Option<usize>,
+    },
+
+    /// The task was terminated because it exceeded one of its limits.
+    LimitExceeded(Limit),
+
+    /// The task was killed by the kernel to free memory, because the system
+    /// ran out of memory.
+    OutOfMemory,
+}
+
+/// The raw representation of a [`Termination`] used by the `TaskWait` syscall
+/// to reduce the number of parameters passed. We use the C representation to
+/// ensure a predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct TerminationInfo {
+    /// The reason of the termination: 0 for an exit, 1 for a kill, 2 for a
+    /// fault, 3 for an exceeded limit, and 4 for an out-of-memory kill.
+    pub reason: usize,
+
+    /// The value associated with the reason: the exit code, the identifier of
+    /// the killer, the kind of fault, or the exceeded limit.
+    pub value: usize,
+
+    /// Whether the `address` field is meaningful.
+    pub has_address: usize,
+
+    /// The faulting address, if any.
+    pub address: usize,
+
+    /// Whether the task terminated uncleanly, i.e. while it was still handling
+    /// IPC requests it had received but not yet replied to. The senders of
+    /// those requests are failed with [`crate::ipc::SendError::ServiceDied`].
+    pub unclean: usize,
+}
+
+impl TerminationInfo {
+    /// Decodes the raw termination information. Returns `None` if the reason
+    /// is unknown, which can happen if the kernel is more recent than this
+    /// library.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn decode(&self) -> Option<Termination> {
+        match self.reason {
+            0 => Some(Termination::Exited(self.value as i32)),
+            1 => Some(Termination::Killed(self.value)),
+            2 => Some(Termination::Faulted {
+                kind: FaultKind::from(self.value),
+                address: (self.has_address != 0).then_some(self.address),
+            }),
+            3 => Some(Termination::LimitExceeded(Limit::from(self.value))),
+            4 => Some(Termination::OutOfMemory),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the task terminated uncleanly, while it was still
+    /// handling IPC requests. A service that unregisters and then exits
+    /// normally always terminates cleanly.
+    #[must_use]
+    pub const fn unclean(&self) -> bool {
+        self.unclean != 0
+    }
+}
+
+impl From<Termination> for TerminationInfo {
+    #[allow(clippy::cast_sign_loss)]
+    fn from(termination: Termination) -> Self {
+        match termination {
+            Termination::Exited(code) => Self {
+                reason: 0,
+                value: code as usize,
+                ..Self::default()
+            },
+            Termination::Killed(by) => Self {
+                reason: 1,
+                value: by,
+                ..Self::default()
+            },
+            Termination::Faulted { kind, address } => Self {
+                reason: 2,
+                value: kind as usize,
+                has_address: usize::from(address.is_some()),
+                address: address.unwrap_or(0),
+                ..Self::default()
+            },
+            Termination::LimitExceeded(limit) => Self {
+                reason: 3,
+                value: limit as usize,
+                ..Self::default()
+            },
+            Termination::OutOfMemory => Self {
+                reason: 4,
+                ..Self::default()
+            },
+        }
+    }
+}
+
+/// The maximum number of recent fault addresses reported in [`Usage`].
+pub const RECENT_FAULTS: usize = 8;
+
+/// The resource usage of a task. We use the C representation to ensure a
+/// predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct Usage {
+    /// The number of minor page faults resolved by the kernel for the task.
+    pub minor_faults: u64,
+
+    /// The number of major page faults resolved by the kernel for the task.
+    pub major_faults: u64,
+
+    /// The number of valid entries in `recent_faults`.
+    pub recent_faults_len: usize,
+
+    /// The addresses of the most recent page faults of the task, from the
+    /// oldest to the most recent.
+    pub recent_faults: [usize; RECENT_FAULTS],
+}
+
+impl Usage {
+    /// Returns the addresses of the most recent page faults of the task, from
+    /// the oldest to the most recent.
+    #[must_use]
+    pub fn recent_faults(&self) -> &[usize] {
+        &self.recent_faults[..self.recent_faults_len.min(RECENT_FAULTS)]
+    }
+}
+
+/// The number of general purpose registers in [`Registers`].
+pub const GENERAL_REGISTERS: usize = 32;
+
+/// The registers of a stopped thread, as read and written by a debugger with
+/// [`crate::SyscallOp::TaskGetRegs`] and [`crate::SyscallOp::TaskSetRegs`].
+/// Their meaning depends on the architecture. We use the C representation to
+/// ensure a predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct Registers {
+    /// The general purpose registers, indexed by their number. On RISC-V,
+    /// these are `x0` to `x31`, where `x0` is always zero. On AArch64, these
+    /// are `x0` to `x30` followed by the stack pointer.
+    pub general: [u64; GENERAL_REGISTERS],
+
+    /// The address of the next instruction executed by the thread.
+    pub pc: u64,
+
+    /// The condition flags of the thread. On AArch64, these are the `NZCV`
+    /// flags of `PSTATE` at their architectural position (bits 28 to 31),
+    /// and the other bits are ignored when the registers are written. On
+    /// RISC-V, which has no condition flags, this is always zero.
+    pub flags: u64,
+
+    /// The thread pointer of the thread. On AArch64, this is the `TPIDR_EL0`
+    /// register. On RISC-V, where the thread pointer is the `x4` general
+    /// purpose register, this is always zero.
+    pub tls: u64,
+}
+
+/// The identifier designating the calling task in the
+/// [`crate::SyscallOp::TaskSetPriority`] syscall. No task ever has this
+/// identifier.
+pub const CURRENT_TASK: usize = 0;
+
+/// A task identifier, as passed to and returned by the syscalls. It packs the
+/// index of the slot of the task in its lower [`TaskId::INDEX_BITS`] bits, and
+/// the generation of the slot in the remaining bits. The kernel reuses the
+/// slots of terminated tasks, but increases their generation each time, so
+/// that an identifier kept after its task terminated never designates a later
+/// task.
+///
+/// The first generation of each slot is zero, so the identifier of a task
+/// whose slot was never reused is simply its index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskId(usize);
+
+impl TaskId {
+    /// The number of bits used by the index of a task identifier.
+    pub const INDEX_BITS: u32 = 32;
+
+    /// The largest index of a task identifier.
+    pub const MAX_INDEX: u32 = u32::MAX;
+
+    /// The largest generation of a task identifier. A slot that reaches this
+    /// generation is never reused again.
+    pub const MAX_GENERATION: u32 = u32::MAX;
+
+    /// Creates a task identifier from its index and its generation.
+    #[must_use]
+    pub const fn new(index: u32, generation: u32) -> Self {
+        Self(((generation as usize) << Self::INDEX_BITS) | index as usize)
+    }
+
+    /// Creates a task identifier from its raw representation. Returns `None`
+    /// if the identifier is not valid (see [`TaskId::is_valid`]).
+    #[must_use]
+    pub const fn from_raw(raw: usize) -> Option<Self> {
+        let id = Self(raw);
+        if id.is_valid() { Some(id) } else { None }
+    }
+
+    /// Returns the raw representation of the identifier.
+    #[must_use]
+    pub const fn raw(self) -> usize {
+        self.0
+    }
+
+    /// Returns the index of the slot of the task.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn index(self) -> u32 {
+        self.0 as u32
+    }
+
+    /// Returns the generation of the slot of the task.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn generation(self) -> u32 {
+        (self.0 >> Self::INDEX_BITS) as u32
+    }
+
+    /// Returns whether the identifier may designate a task. No task has the
+    /// index zero, since it is reserved for [`CURRENT_TASK`].
+    #[must_use]
+    pub const fn is_valid(self) -> bool {
+        self.index() != 0
+    }
+
+    /// Returns whether the identifier designates an earlier task than `other`
+    /// in the same slot, meaning that the task it designated has terminated.
+    #[must_use]
+    pub const fn is_stale_for(self, other: Self) -> bool {
+        self.index() == other.index() && self.generation() < other.generation()
+    }
+
+    /// Returns the identifier of the next task in the same slot, or `None` if
+    /// the slot reached [`TaskId::MAX_GENERATION`] and cannot be reused.
+    #[must_use]
+    pub const fn next_generation(self) -> Option<Self> {
+        if self.generation() == Self::MAX_GENERATION {
+            None
+        } else {
+            Some(Self::new(self.index(), self.generation() + 1))
+        }
+    }
+}
+
+impl From<TaskId> for usize {
+    fn from(id: TaskId) -> usize {
+        id.0
+    }
+}
+
+/// The scheduling class of a task. A task only runs when no task of a higher
+/// class is ready to run, and the processor time is shared fairly between the
+/// tasks of the same class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[repr(usize)]
+pub enum Priority {
+    /// Latency-sensitive tasks, such as device drivers or interactive
+    /// services, that must run as soon as they are ready. They can starve all
+    /// the other tasks, and must therefore block often.
+    Realtime = 0,
+
+    /// The class of all tasks when they are spawned.
+    #[default]
+    Normal = 1,
+
+    /// Background tasks that only run when no other task is ready to run.
+    Idle = 2,
+}
+
+impl Priority {
+    /// All the priorities, from the highest to the lowest.
+    pub const ALL: [Self; 3] = [Self::Realtime, Self::Normal, Self::Idle];
+
+    /// Decodes the raw representation of a priority. Returns `None` if it is
+    /// unknown.
+    #[must_use]
+    pub const fn from_raw(raw: usize) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Realtime),
+            1 => Some(Self::Normal),
+            2 => Some(Self::Idle),
+            _ => None,
+        }
+    }
+}
+
+/// The maximum size of the ELF image of a task spawned with the
+/// [`crate::SyscallOp::TaskSpawn`] syscall, in bytes.
+pub const MAX_IMAGE_SIZE: usize = 4 * 1024 * 1024;
+
+syscall_error! {
+    /// Errors that may occur when spawning a task.
+    pub enum SpawnError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer containing the ELF image is invalid, or is larger than
+        /// [`MAX_IMAGE_SIZE`].
+        BadImage = 1,
+
+        /// The ELF image is malformed or not supported by the kernel.
+        InvalidImage = 2,
+
+        /// The buffer containing the arguments and environment variables is
+        /// invalid, or does not contain a valid [`crate::env::Block`].
+        BadEnvironment = 3,
+
+        /// The kernel ran out of memory while loading the task.
+        OutOfMemory = 4,
+
+        /// The task already has as many children alive as allowed by its
+        /// [`Limit::Children`] limit.
+        LimitExceeded = 5,
+
+        /// The kernel cannot run more tasks, either because it already runs
+        /// as many tasks as its configuration allows, or because the system
+        /// is going down.
+        TooManyTasks = 6,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when retrieving the resource usage of a task.
+    pub enum UsageError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer where the usage should be written is invalid.
+        BadBuffer = 1,
+
+        /// The task does not exist.
+        TaskNotFound = 2,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when waiting for the termination of a task.
+    pub enum WaitError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer where the termination information should be written is
+        /// invalid.
+        BadBuffer = 1,
+
+        /// The task does not exist, or its termination information was already
+        /// collected or has expired.
+        TaskNotFound = 2,
+
+        /// A task cannot wait for its own termination.
+        SelfWait = 3,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when terminating another task.
+    pub enum KillError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The task does not exist or is already terminating.
+        TaskNotFound = 1,
+
+        /// The task tried to terminate itself. The `TaskExit` syscall must be
+        /// used instead.
+        SelfKill = 2,
+
+        /// The task is a well-known system task, which cannot be terminated.
+        Protected = 3,
+
+        /// The task holds capabilities that the calling task does not hold.
+        NotAllowed = 4,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when changing a resource limit of a task.
+    pub enum SetLimitError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The limit is unknown, or the value is not allowed for this limit.
+        InvalidLimit = 1,
+
+        /// The task does not exist.
+        TaskNotFound = 2,
+
+        /// The task holds capabilities that the calling task does not hold.
+        NotAllowed = 3,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when marking a task as essential.
+    pub enum SetEssentialError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The task does not exist.
+        TaskNotFound = 1,
+
+        /// The task holds capabilities that the calling task does not hold.
+        NotAllowed = 2,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when suspending or resuming a task, or when
+    /// accessing the registers of one of its threads.
+    pub enum DebugError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The task or the thread does not exist.
+        TaskNotFound = 1,
+
+        /// The task holds capabilities that the calling task does not hold,
+        /// or is the calling task itself.
+        NotAllowed = 2,
+
+        /// The thread is not stopped: its task was not suspended, or the
+        /// thread did not stop yet because it is waiting inside a syscall.
+        NotStopped = 3,
+
+        /// The buffer holding the registers is invalid.
+        BadBuffer = 4,
+
+        /// Single-stepping is not supported on this architecture.
+        StepUnsupported = 5,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when enabling or disabling the tracing of the
+    /// syscalls of a task.
+    pub enum TraceError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The task does not exist.
+        TaskNotFound = 1,
+
+        /// The task holds capabilities that the calling task does not hold.
+        NotAllowed = 2,
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when changing the scheduling class of a task.
+    pub enum SetPriorityError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The priority is unknown.
+        InvalidPriority = 1,
+
+        /// The task does not exist, or is running on another core.
+        TaskNotFound = 2,
+    }
+}
@@ -0,0 +1,57 @@
+//! Threads: additional flows of execution inside a task. A thread is created
+//! with [`crate::SyscallOp::ThreadCreate`] and shares the address space, the
+//! handles and the capabilities of the task, as well as the resources owned
+//! by the task (interrupts, devices, DMA buffers and synchronization objects).
+//! It runs on a stack allocated by the task, and starts at the given entry
+//! point with the given argument in its first argument register and the
+//! address of its own shared page (see [`crate::vdso::SHARED_PAGE_ADDRESS`])
+//! in its second argument register.
+//!
+//! Each thread has its own identifier, drawn from the same space as the task
+//! identifiers. The first thread of a task has the identifier of the task,
+//! and the task identifier remains valid as long as one of its threads is
+//! alive. IPC messages are delivered to the thread they are sent to, so a
+//! service should receive its requests from a single thread.
+//!
+//! A thread terminates with [`crate::SyscallOp::ThreadExit`], and another
+//! thread of the same task can wait for its termination and retrieve its exit
+//! code with [`crate::SyscallOp::ThreadJoin`]. When the last thread of a task
+//! terminates, the task terminates with the exit code of that thread. A call
+//! to [`crate::SyscallOp::TaskExit`] or a fault in any thread terminates all
+//! the threads of the task at once.
+
+syscall_error! {
+    /// Errors that can occur when creating a thread.
+    pub enum CreateError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The entry point or the stack pointer is not a valid user address.
+        BadAddress = 1,
+
+        /// The task already has as many threads as it is allowed to.
+        TooManyThreads = 2,
+
+        /// The memory needed by the runtime to start the thread could not be
+        /// allocated.
+        OutOfMemory = 3,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when waiting for the termination of a thread.
+    pub enum JoinError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The thread does not exist, does not belong to the current task, or
+        /// was already joined.
+        NotFound = 1,
+
+        /// A thread cannot wait for its own termination.
+        SelfJoin = 2,
+
+        /// The buffer where the exit code should be written is invalid.
+        BadBuffer = 3,
+    }
+}
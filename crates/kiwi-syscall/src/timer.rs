@@ -0,0 +1,104 @@
+//! Periodic timers managed by the kernel. A timer is created with the
+//! [`crate::SyscallOp::TimerCreate`] syscall and expires each time its
+//! interval elapses, until it is destroyed with
+//! [`crate::SyscallOp::TimerDestroy`] or its owner terminates. Each expiration
+//! is delivered to the owner in one of two ways, chosen when the timer is
+//! created (see [`Delivery`]):
+//! - as an IPC message in the mailbox of the owner, sent by
+//!   [`crate::ipc::KERNEL_SENDER`] and carrying an [`Expiration`] as payload.
+//!   Such messages must not be replied to.
+//! - by signaling a synchronization object (see [`crate::sync`]), on which
+//!   any task can wait.
+//!
+//! The kernel does not queue expirations: if the owner does not keep up with
+//! its timer, the expirations are coalesced and the [`Expiration::count`] of
+//! the next message tells how many periods elapsed since the previous one.
+use crate::ipc::Payload;
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+/// The shortest interval of a timer, in nanoseconds. Shorter intervals would
+/// let a task flood the kernel with expirations.
+pub const MIN_INTERVAL: usize = 1_000_000;
+
+/// How the expirations of a timer are delivered to its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum Delivery {
+    /// Each expiration is delivered as an IPC message whose operation is the
+    /// target given when creating the timer.
+    Message = 0,
+
+    /// Each expiration signals the synchronization object whose identifier is
+    /// the target given when creating the timer, with the given value.
+    Signal = 1,
+}
+
+impl Delivery {
+    /// Creates a delivery from its raw representation. Returns `None` if the
+    /// delivery is unknown.
+    #[must_use]
+    pub const fn from_raw(raw: usize) -> Option<Self> {
+        match raw {
+            0 => Some(Delivery::Message),
+            1 => Some(Delivery::Signal),
+            _ => None,
+        }
+    }
+}
+
+/// The payload of the messages delivering the expirations of a timer. We use
+/// the C representation to ensure a predictable layout compatible with the
+/// kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable)]
+#[repr(C)]
+pub struct Expiration {
+    /// The identifier of the timer that expired.
+    pub timer: u64,
+
+    /// The number of periods elapsed since the previous message of the timer,
+    /// which is more than one if some expirations could not be delivered in
+    /// time.
+    pub count: u64,
+}
+
+impl Payload for Expiration {}
+
+syscall_error! {
+    /// Errors that can occur when creating a timer.
+    pub enum CreateError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The interval is shorter than [`MIN_INTERVAL`].
+        InvalidInterval = 1,
+
+        /// The delivery is unknown, or the operation of the messages is
+        /// [`crate::power::NOTICE_OPERATION`], which is reserved.
+        InvalidDelivery = 2,
+
+        /// The task does not hold the capability needed by the delivery:
+        /// [`crate::capability::Capabilities::IPC`] for messages and
+        /// [`crate::capability::Capabilities::SYNC`] for signals.
+        NotAllowed = 3,
+
+        /// The synchronization object to signal does not exist.
+        ObjectNotFound = 4,
+
+        /// The task already owns as many timers as allowed.
+        TooManyTimers = 5,
+    }
+}
+
+syscall_error! {
+    /// Errors that can occur when destroying a timer.
+    pub enum DestroyError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The timer does not exist.
+        NotFound = 1,
+
+        /// The timer is owned by another task.
+        NotOwner = 2,
+    }
+}
@@ -0,0 +1,135 @@
+//! Kernel event tracing. When the kernel is built with its `trace` feature or
+//! booted with the `trace` option on its command line, it records typed
+//! binary events into a ring buffer per CPU core: syscall entry and exit,
+//! context switches, IPC operations and page faults. The buffers are drained
+//! with [`crate::SyscallOp::TraceRead`], which is much cheaper than logging
+//! strings over the serial port and does not disturb the timing of the traced
+//! tasks as much.
+//!
+//! When a buffer is full, its oldest events are overwritten. The number of
+//! overwritten events is reported by an [`EventKind::Lost`] event the next
+//! time the buffer is drained. Events are ordered within the buffer of a core,
+//! but the events of different cores are not: they must be sorted by their
+//! timestamp to rebuild the global order.
+//!
+//! The syscalls of a single task can also be traced in more detail with
+//! [`crate::SyscallOp::TaskTrace`], which records all the arguments of each
+//! syscall invoked by the task and how long the kernel took to handle it. This
+//! works even if kernel tracing is disabled, in which case only the syscalls
+//! of the traced tasks are recorded. Combined with the syscall table (see
+//! [`crate::table`]) to decode the arguments, this is enough to build an
+//! `strace`-like tool for user services.
+use zerocopy::{FromBytes, IntoBytes};
+
+/// The kind of a trace event, which defines the meaning of its arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// An unknown event.
+    Unknown = 0,
+
+    /// Events were overwritten because the buffer of the core was full. The
+    /// first argument is the number of lost events.
+    Lost = 1,
+
+    /// A task invoked a syscall. The first argument is the syscall number and
+    /// the second one the first argument of the syscall.
+    SyscallEnter = 2,
+
+    /// A syscall returned to the task. The first argument is the syscall
+    /// number and the second one the raw return value.
+    SyscallExit = 3,
+
+    /// The executor switched to the task of the event.
+    ContextSwitch = 4,
+
+    /// An IPC message was delivered to the mailbox of a task. The first
+    /// argument is the receiver and the second one the operation.
+    IpcSend = 5,
+
+    /// A task took an IPC message from its mailbox. The first argument is the
+    /// sender and the second one the operation.
+    IpcReceive = 6,
+
+    /// A task replied to an IPC message. The first argument is the task that
+    /// receives the reply and the second one the status.
+    IpcReply = 7,
+
+    /// A task triggered a page fault. The first argument is the faulting
+    /// address, and the second one is `1` if the kernel resolved the fault or
+    /// `0` if the task was terminated.
+    PageFault = 8,
+
+    /// The arguments of a syscall invoked by a traced task. Three events of
+    /// this kind follow the [`EventKind::SyscallEnter`] event of the syscall,
+    /// each holding the next two arguments of the syscall in order.
+    SyscallArgs = 9,
+
+    /// How long the kernel took to handle a syscall invoked by a traced task,
+    /// including the time the task waited inside the syscall. This event
+    /// follows the [`EventKind::SyscallExit`] event of the syscall. The first
+    /// argument is the syscall number and the second one the duration in
+    /// nanoseconds.
+    SyscallDuration = 10,
+}
+
+impl From<u32> for EventKind {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => EventKind::Lost,
+            2 => EventKind::SyscallEnter,
+            3 => EventKind::SyscallExit,
+            4 => EventKind::ContextSwitch,
+            5 => EventKind::IpcSend,
+            6 => EventKind::IpcReceive,
+            7 => EventKind::IpcReply,
+            8 => EventKind::PageFault,
+            9 => EventKind::SyscallArgs,
+            10 => EventKind::SyscallDuration,
+            _ => EventKind::Unknown,
+        }
+    }
+}
+
+/// An event recorded by the kernel. We use the C representation to ensure a
+/// predictable layout compatible with the kernel.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct Event {
+    /// The number of nanoseconds elapsed since boot when the event occurred.
+    pub timestamp: u64,
+
+    /// The identifier of the task running when the event occurred, or `0` if
+    /// no task was running.
+    pub task: u64,
+
+    /// The raw kind of the event (see [`Event::kind`]).
+    pub kind: u32,
+
+    /// The index of the core where the event occurred.
+    pub cpu: u32,
+
+    /// The arguments of the event, whose meaning depends on its kind.
+    pub args: [u64; 2],
+}
+
+impl Event {
+    /// Returns the kind of the event.
+    #[must_use]
+    pub fn kind(&self) -> EventKind {
+        EventKind::from(self.kind)
+    }
+}
+
+syscall_error! {
+    /// Errors that may occur when reading the trace buffers.
+    pub enum ReadError {
+        /// An unknown error occurred.
+        Unknown = 0,
+
+        /// The buffer where the events should be written is invalid.
+        BadBuffer = 1,
+
+        /// Tracing is disabled in the kernel, and no task was ever traced.
+        Disabled = 2,
+    }
+}
@@ -0,0 +1,113 @@
+use zerocopy::{FromBytes, IntoBytes};
+
+/// The user virtual address where the kernel maps the shared pages of a task.
+/// Each thread has its own page, mapped at this address plus its slot in the
+/// task multiplied by the page size: the first thread of a task always gets
+/// the page at this exact address, while the address of the page of each
+/// other thread is given to it in its second argument register when it starts.
+/// The page is updated by the kernel every time the thread is resumed, and
+/// some fields can be written by the thread to give hints to the kernel.
+///
+/// The fields describing the task rather than the thread (`tick_duration`,
+/// `tls` and `mmap_base`) are only guaranteed to be set in the page at this
+/// address, which stays mapped as long as the task is alive.
+pub const SHARED_PAGE_ADDRESS: usize = 0x0000_003F_F000_0000;
+
+/// The lowest address at which the kernel places the base of the anonymous
+/// mappings of a task (see [`SharedPage::mmap_base`]). It is far enough from
+/// the start of the address space to leave room for the program image, and
+/// far enough from the stack and the shared page at the top of the address
+/// space.
+pub const MMAP_BASE: usize = 0x0000_0010_0000_0000;
+
+/// The size of the range above [`MMAP_BASE`] in which the kernel randomly
+/// places the base of the anonymous mappings of each task, unless the kernel
+/// was built without address space layout randomization.
+pub const MMAP_RANDOM_RANGE: usize = 0x0000_0004_0000_0000;
+
+/// The layout of the page shared between the kernel and a user thread. This
+/// page allow user space to cheaply read some information maintained by the
+/// kernel without invoking a syscall. We use the C representation to ensure a
+/// predictable layout compatible with the kernel.
+///
+/// # Green threads
+/// User runtimes that implement their own green threads switch stacks without
+/// the kernel knowing it. To still get meaningful diagnostics when a green
+/// thread overflows its stack, the runtime should publish the bounds of the
+/// stack it is about to switch to in `stack_low` and `stack_high` *before*
+/// switching to it. Setting both fields to zero indicates that the thread runs
+/// on the stack provided by the kernel.
+///
+/// The kernel preserves all registers across traps, so a user-level context
+/// switch only needs to follow the regular calling convention: on riscv64,
+/// `ra`, `sp` and `s0`-`s11` must be saved and restored by the switch routine,
+/// while `gp` and `tp` are shared by all green threads of a thread and must be
+/// left untouched. Floating point is unsupported in user space on riscv64 since
+/// the kernel never enables `sstatus.FS`, so `fs0`-`fs11` do not need to be
+/// saved (and cannot be, since any floating point instruction traps). On
+/// aarch64, `x19`-`x30`, `sp` and `d8`-`d15` must be saved, while `tpidr_el0`
+/// is shared by all green threads of a thread.
+#[derive(Debug, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct SharedPage {
+    /// The time, in nanoseconds since boot, at which the current quantum of
+    /// the thread expires. Past this point, the kernel may preempt the thread
+    /// at any time.
+    pub quantum_deadline: u64,
+
+    /// The duration of a single timer tick, in nanoseconds. This can be used
+    /// by user space to convert the value of the `time` counter into
+    /// nanoseconds since boot.
+    pub tick_duration: u64,
+
+    /// The lowest address (inclusive) of the stack currently used by the
+    /// thread. This field is written by the thread and is only used by the
+    /// kernel as a hint to classify faults.
+    pub stack_low: u64,
+
+    /// The highest address (exclusive) of the stack currently used by the
+    /// thread. This field is written by the thread and is only used by the
+    /// kernel as a hint to classify faults.
+    pub stack_high: u64,
+
+    /// The thread-local storage template of the executable, written by the
+    /// kernel when the executable is loaded.
+    pub tls: TlsTemplate,
+
+    /// The address from which the runtime should place its heap and its
+    /// other anonymous mappings, chosen by the kernel when the address space
+    /// of the task is created. It is page aligned and located between
+    /// [`MMAP_BASE`] and [`MMAP_BASE`] + [`MMAP_RANDOM_RANGE`].
+    pub mmap_base: u64,
+}
+
+/// The thread-local storage (TLS) template of an executable, as described by
+/// its `PT_TLS` segment. Each thread needs its own TLS block of `mem_size`
+/// bytes aligned on `align`, starting with a copy of the `file_size` bytes of
+/// the initialization image and zeroed after it.
+///
+/// On riscv64, the `tp` register of a thread must point to the start of its
+/// block. On aarch64, the `tpidr_el0` register must point to a 16 bytes
+/// thread control block, followed by the block at the first offset aligned on
+/// `align`. The kernel never allocates TLS blocks nor changes the thread
+/// pointer of a thread, but preserves it across traps: the runtime must set
+/// it up in each thread before accessing any thread-local variable. A
+/// template with a `mem_size` of zero means that the executable has no
+/// thread-local variables.
+#[derive(Debug, Clone, Copy, Default, FromBytes, IntoBytes)]
+#[repr(C)]
+pub struct TlsTemplate {
+    /// The address of the initialization image in the address space of the
+    /// task.
+    pub image: u64,
+
+    /// The size of the initialization image, in bytes.
+    pub file_size: u64,
+
+    /// The size of the TLS block of each thread, in bytes.
+    pub mem_size: u64,
+
+    /// The alignment of the TLS block of each thread, in bytes. It is always
+    /// a power of two.
+    pub align: u64,
+}
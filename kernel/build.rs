@@ -1,12 +1,16 @@
 fn main() {
     let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
 
-    std::fs::write(
-        out_dir.join("link.ld"),
-        include_bytes!("src/arch/riscv64/config/link.ld"),
-    )
-    .unwrap();
+    // Each architecture has its own linker script, since the kernel is not
+    // loaded at the same physical address on all platforms.
+    let script: &[u8] = match std::env::var("CARGO_CFG_TARGET_ARCH").unwrap().as_str() {
+        "aarch64" => include_bytes!("src/arch/aarch64/config/link.ld"),
+        _ => include_bytes!("src/arch/riscv64/config/link.ld"),
+    };
+
+    std::fs::write(out_dir.join("link.ld"), script).unwrap();
     println!("cargo:rustc-link-search={}", out_dir.display());
     println!("cargo:rerun-if-changed=build.rs");
-    println!("cargo:rerun-if-changed=link.ld");
+    println!("cargo:rerun-if-changed=src/arch/riscv64/config/link.ld");
+    println!("cargo:rerun-if-changed=src/arch/aarch64/config/link.ld");
 }
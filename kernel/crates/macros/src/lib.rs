@@ -15,8 +15,9 @@ use syn::{ItemFn, ItemStatic, parse_macro_input};
 ///
 /// # Safety
 /// If an function with this attribute is called after the kernel has been
-/// initialized, the behavior is undefined and will probably cause a kernel
-/// panic.
+/// initialized, the behavior is undefined. Since the .init section is unmapped
+/// at the end of the boot process, this will most likely cause a kernel panic
+/// with an instruction page fault.
 #[proc_macro_attribute]
 pub fn init(_: TokenStream, item: TokenStream) -> TokenStream {
     let mut input_fn = parse_macro_input!(item as ItemFn);
@@ -0,0 +1,269 @@
+.equ KERNEL_START, 0xFFFFFFC000000000
+
+// The difference between the physical address and the link address of the
+// sections of the kernel image linked in the kernel virtual base
+.equ PHYSICAL_OFFSET, 0x40000000 - 0xFFFFFFFFC0000000
+
+// The address of the device tree when the bootloader does not give one. QEMU
+// puts the device tree at the start of the RAM when booting an ELF image
+.equ DEFAULT_DEVICE_TREE, 0x40000000
+
+// The size of the granularity of the kernel slide, and the number of 2 MiB
+// regions mapped for the kernel image by the boot page table
+.equ SLIDE_ALIGN_SHIFT, 21
+.equ IMAGE_REGIONS, {image_regions}
+
+// The index of the entry of the boot image table mapping the start of the
+// kernel image when the kernel is not slid
+.equ IMAGE_FIRST_REGION, 1
+
+// The type of the only relocations found in the kernel image. The linker may
+// also leave empty relocations, whose type is zero, which are skipped
+.equ R_AARCH64_RELATIVE, 1027
+
+// The attributes of the 2 MiB blocks mapping the kernel image: normal inner
+// shareable memory with the access flag set, never executable by user space
+.equ IMAGE_BLOCK, 0x0040000000000701
+
+// The value of `SCTLR_EL1` while the MMU is disabled, with only the bits that
+// are reserved to one set
+.equ SCTLR_MMU_OFF, 0x30D00800
+
+// The value of `SCTLR_EL1` once the MMU is enabled. On top of the bits that
+// are reserved to one, this enables the MMU, the caches and the alignment
+// checks of the stack pointer, and lets user space query the cache type and
+// maintain the caches, which is needed to run code it generates itself
+.equ SCTLR_MMU_ON, 0x34D4D81D
+
+// The memory attributes referenced by the page tables: normal write-back
+// cacheable memory, and device memory
+.equ MAIR, 0x00FF
+
+// The maximum physical address size supported with the 4 KiB granule, which
+// is 48 bits
+.equ MAX_PHYSICAL_RANGE, 5
+
+.section .early, "ax"
+.globl _start
+.balign 16
+_start:
+  // Disable interrupts, and keep the device tree pointer in x19. It is given
+  // in x0 when booting as a Linux image
+  msr daifset, #0xF
+  mov x19, x0
+  cbnz x19, .device_tree_found
+  mov x19, #DEFAULT_DEVICE_TREE
+.device_tree_found:
+
+  // Drop to EL1 if the kernel was started at EL2. EL1 runs in AArch64, can
+  // use the counter and the timer, and can use the system registers of the
+  // GIC if there is one
+  mrs x9, CurrentEL
+  lsr x9, x9, #2
+  cmp x9, #2
+  b.ne .el1
+  mov x9, #(1 << 31)
+  msr hcr_el2, x9
+  mov x9, #3
+  msr cnthctl_el2, x9
+  msr cntvoff_el2, xzr
+  mrs x9, id_aa64pfr0_el1
+  ubfx x9, x9, #24, #4
+  cbz x9, .gic_configured
+  mrs x9, icc_sre_el2
+  mov x10, #0x9
+  orr x9, x9, x10
+  msr icc_sre_el2, x9
+  isb
+.gic_configured:
+  mov x9, #0x3C5
+  msr spsr_el2, x9
+  adr x9, .el1
+  msr elr_el2, x9
+  eret
+.el1:
+  movz x9, #(SCTLR_MMU_OFF & 0xFFFF)
+  movk x9, #(SCTLR_MMU_OFF >> 16), lsl #16
+  msr sctlr_el1, x9
+  isb
+
+  // Choose the slide of the kernel image in x20, a multiple of 2 MiB derived
+  // from the counter. This is the only source of entropy available this
+  // early, before the device tree can be parsed
+  mov x20, xzr
+  mov x9, #{randomize}
+  cbz x9, .slide_chosen
+  mrs x9, cntpct_el0
+  movz x10, #0x7C15
+  movk x10, #0x7F4A, lsl #16
+  movk x10, #0x79B9, lsl #32
+  movk x10, #0x9E37, lsl #48
+  mul x9, x9, x10
+  lsr x9, x9, #(64 - {slide_bits})
+  lsl x20, x9, #SLIDE_ALIGN_SHIFT
+.slide_chosen:
+
+  // Apply the relocations of the kernel image. Paging is still disabled,
+  // so each relocated word is written through its physical address. The
+  // addresses of the symbols of the kernel image are computed from their
+  // offsets to `_start`, since they are too far away to be addressed
+  // relative to the program counter
+  adr x21, _start
+  mov x22, #PHYSICAL_OFFSET
+  add x22, x22, x21
+  ldr x9, .rela_start_offset
+  ldr x10, .rela_end_offset
+  add x9, x9, x22
+  add x10, x10, x22
+  mov x11, #PHYSICAL_OFFSET
+.relocate:
+  cmp x9, x10
+  b.hs .relocated
+  ldp x12, x13, [x9], #16
+  ldr x14, [x9], #8
+  cbz x13, .relocate
+  cmp x13, #R_AARCH64_RELATIVE
+  b.ne .halt
+  add x12, x12, x11
+  add x14, x14, x20
+  str x14, [x12]
+  b .relocate
+.relocated:
+
+  // Clear the BSS section with zeros, also through its physical address
+  ldr x9, .bss_start_offset
+  ldr x10, .bss_end_offset
+  add x9, x9, x22
+  add x10, x10, x22
+.bss_clear:
+  cmp x9, x10
+  b.hs .bss_cleared
+  str xzr, [x9], #8
+  b .bss_clear
+.bss_cleared:
+
+  // Map the kernel image at its slid address with 2 MiB blocks in the boot
+  // image table, and link the table in the last entry of the boot page table
+  adr x9, boot_image_table
+  add x9, x9, x20, lsr #(SLIDE_ALIGN_SHIFT - 3)
+  add x9, x9, #IMAGE_FIRST_REGION * 8
+  movz x10, #(IMAGE_BLOCK & 0xFFFF)
+  movk x10, #(IMAGE_BLOCK >> 48), lsl #48
+  orr x10, x10, x21
+  mov x11, #IMAGE_REGIONS
+.map_image:
+  str x10, [x9], #8
+  add x10, x10, #(1 << SLIDE_ALIGN_SHIFT)
+  subs x11, x11, #1
+  b.ne .map_image
+
+  adr x9, boot_image_table
+  orr x9, x9, #3
+  adr x10, boot_page_table
+  str x9, [x10, #511 * 8]
+
+  // Configure the translation: 38-bit virtual addresses with the 4 KiB
+  // granule in both halves, translated with inner shareable write-back
+  // cacheable table walks, and the physical address size of the processor.
+  // The boot page table translates the user half, which identity maps this
+  // code, and its last 256 entries translate the kernel half
+  mov x9, #MAIR
+  msr mair_el1, x9
+  movz x9, #0x351A
+  movk x9, #0xB51A, lsl #16
+  mrs x11, id_aa64mmfr0_el1
+  and x11, x11, #0xF
+  cmp x11, #MAX_PHYSICAL_RANGE
+  mov x12, #MAX_PHYSICAL_RANGE
+  csel x11, x11, x12, ls
+  orr x9, x9, x11, lsl #32
+  msr tcr_el1, x9
+  msr ttbr0_el1, x10
+  add x10, x10, #256 * 8
+  msr ttbr1_el1, x10
+  isb
+  tlbi vmalle1
+  dsb nsh
+  isb
+
+  // Let user space use the floating point and SIMD registers, and enable the
+  // MMU and the caches
+  mov x9, #(3 << 20)
+  msr cpacr_el1, x9
+  movz x9, #(SCTLR_MMU_ON & 0xFFFF)
+  movk x9, #(SCTLR_MMU_ON >> 16), lsl #16
+  msr sctlr_el1, x9
+  isb
+
+  // Give the identifier of the CPU in x0, the device tree pointer in x1
+  // through the physical memory map, since the kernel virtual base only maps
+  // the kernel image once the kernel table is set up, and the slide in x2
+  mrs x0, mpidr_el1
+  and x0, x0, #0xFFFFFF
+  mov x9, #KERNEL_START
+  add x1, x19, x9
+  mov x2, x20
+
+  // Setup the stack pointer and jump to the entry point, both at their slid
+  // addresses. The frame pointer and the link register are cleared so that
+  // backtraces stop at the entry point
+  ldr x9, .stack_top_offset
+  add x9, x9, x21
+  add x9, x9, x20
+  mov sp, x9
+  ldr x9, .entry_offset
+  add x9, x9, x21
+  add x9, x9, x20
+  mov x29, xzr
+  mov x30, xzr
+  br x9
+
+  // An unexpected relocation was found, and the kernel cannot be relocated
+.halt:
+  wfe
+  b .halt
+
+// The offsets of the symbols used above to `_start`, computed by the linker
+.balign 8
+.rela_start_offset:
+  .quad __rela_start - _start
+.rela_end_offset:
+  .quad __rela_end - _start
+.bss_start_offset:
+  .quad __bss_start - _start
+.bss_end_offset:
+  .quad __bss_end - _start
+.stack_top_offset:
+  .quad boot_stack_top - _start
+.entry_offset:
+  .quad entry - _start
+
+// The boot page table. It identity maps the first 3 GiB of physical memory,
+// maps the first 3 GiB of the physical memory map at `KERNEL_START`, and the
+// kernel image at the kernel virtual base through the boot image table. The
+// first 1 GiB holds the registers of the devices, and is mapped as device
+// memory
+.balign 4096
+boot_page_table:
+  .quad 0x0060000000000405
+  .quad 0x0040000040000701
+  .quad 0x0040000080000701
+  .fill 253, 8, 0
+  .quad 0x0060000000000405
+  .quad 0x0060000040000701
+  .quad 0x0060000080000701
+  .fill 253, 8, 0
+
+// The boot image table, mapping the last 1 GiB of virtual memory
+.balign 4096
+boot_image_table:
+  .fill 512, 8, 0
+
+// Reserve 64 KiB for the boot stack, in its own section so that the linker
+// script can put a guard page below it
+.section .stack, "aw", %nobits
+.globl boot_stack_bottom
+.globl boot_stack_top
+boot_stack_bottom:
+.space 64 * 1024
+boot_stack_top:
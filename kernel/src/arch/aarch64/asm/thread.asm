@@ -0,0 +1,179 @@
+// The offsets of the fields of the thread's context
+.equ CONTEXT_SP, 31 * 8
+.equ CONTEXT_ELR, 32 * 8
+.equ CONTEXT_SPSR, 33 * 8
+.equ CONTEXT_TPIDR, 34 * 8
+.equ CONTEXT_KIND, 35 * 8
+
+// Parameters:
+//   x0: pointer to the thread's context
+.section .text
+.globl thread_execute
+.balign 16
+thread_execute:
+  // Save the registers that must be preserved across function calls by the
+  // AArch64 ABI on the kernel stack, since those registers will be used by
+  // the user code. The kernel stack pointer stays in SP_EL1 while the thread
+  // runs, so the trap handler finds them there when the thread traps.
+  stp x19, x20, [sp, #-96]!
+  stp x21, x22, [sp, #16]
+  stp x23, x24, [sp, #32]
+  stp x25, x26, [sp, #48]
+  stp x27, x28, [sp, #64]
+  stp x29, x30, [sp, #80]
+
+  // Store the thread's context into the TPIDR_EL1 register, where the trap
+  // handler will find it
+  msr tpidr_el1, x0
+
+  // Restore the user state that is not held in general purpose registers
+  ldr x1, [x0, #CONTEXT_ELR]
+  msr elr_el1, x1
+  ldr x1, [x0, #CONTEXT_SPSR]
+  msr spsr_el1, x1
+  ldr x1, [x0, #CONTEXT_SP]
+  msr sp_el0, x1
+  ldr x1, [x0, #CONTEXT_TPIDR]
+  msr tpidr_el0, x1
+
+  // Restore the thread's registers, ending with x0 since it holds the
+  // pointer to the context
+  ldp x2, x3, [x0, #2 * 8]
+  ldp x4, x5, [x0, #4 * 8]
+  ldp x6, x7, [x0, #6 * 8]
+  ldp x8, x9, [x0, #8 * 8]
+  ldp x10, x11, [x0, #10 * 8]
+  ldp x12, x13, [x0, #12 * 8]
+  ldp x14, x15, [x0, #14 * 8]
+  ldp x16, x17, [x0, #16 * 8]
+  ldp x18, x19, [x0, #18 * 8]
+  ldp x20, x21, [x0, #20 * 8]
+  ldp x22, x23, [x0, #22 * 8]
+  ldp x24, x25, [x0, #24 * 8]
+  ldp x26, x27, [x0, #26 * 8]
+  ldp x28, x29, [x0, #28 * 8]
+  ldr x30, [x0, #30 * 8]
+  ldp x0, x1, [x0, #0 * 8]
+
+  // Return to the user code
+  eret
+
+// Thread that traps will jump here from the vector table, with the first two
+// registers of the thread pushed on the kernel stack and the kind of the
+// exception in x0. This function will restore the kernel state and then
+// return to code that called `thread_execute`.
+.globl thread_trap
+.balign 16
+thread_trap:
+  // Save the thread's context, starting with the kind of the exception so
+  // that x0 can be used to hold the pointer to the context
+  mrs x1, tpidr_el1
+  str x0, [x1, #CONTEXT_KIND]
+  mov x0, x1
+  stp x2, x3, [x0, #2 * 8]
+  stp x4, x5, [x0, #4 * 8]
+  stp x6, x7, [x0, #6 * 8]
+  stp x8, x9, [x0, #8 * 8]
+  stp x10, x11, [x0, #10 * 8]
+  stp x12, x13, [x0, #12 * 8]
+  stp x14, x15, [x0, #14 * 8]
+  stp x16, x17, [x0, #16 * 8]
+  stp x18, x19, [x0, #18 * 8]
+  stp x20, x21, [x0, #20 * 8]
+  stp x22, x23, [x0, #22 * 8]
+  stp x24, x25, [x0, #24 * 8]
+  stp x26, x27, [x0, #26 * 8]
+  stp x28, x29, [x0, #28 * 8]
+  str x30, [x0, #30 * 8]
+
+  // Save the first two registers of the thread pushed by the vector table
+  ldp x2, x3, [sp], #16
+  stp x2, x3, [x0, #0 * 8]
+
+  // Save the user state that is not held in general purpose registers
+  mrs x1, elr_el1
+  str x1, [x0, #CONTEXT_ELR]
+  mrs x1, spsr_el1
+  str x1, [x0, #CONTEXT_SPSR]
+  mrs x1, sp_el0
+  str x1, [x0, #CONTEXT_SP]
+  mrs x1, tpidr_el0
+  str x1, [x0, #CONTEXT_TPIDR]
+
+  // Restore the kernel state
+  ldp x21, x22, [sp, #16]
+  ldp x23, x24, [sp, #32]
+  ldp x25, x26, [sp, #48]
+  ldp x27, x28, [sp, #64]
+  ldp x29, x30, [sp, #80]
+  ldp x19, x20, [sp], #96
+
+  // Go back to the caller of `thread_execute`
+  ret
+
+// The offsets of the fields of the floating point state of a thread, after the
+// 32 SIMD registers
+.equ FPU_FPCR, 32 * 16
+.equ FPU_FPSR, 32 * 16 + 8
+
+// The kernel is built without floating point and SIMD support, so the
+// instructions below must be enabled explicitly
+.arch_extension fp
+.arch_extension simd
+
+// Parameters:
+//   x0: pointer to the thread's floating point state
+.globl thread_save_fpu
+.balign 16
+thread_save_fpu:
+  stp q0, q1, [x0, #0 * 16]
+  stp q2, q3, [x0, #2 * 16]
+  stp q4, q5, [x0, #4 * 16]
+  stp q6, q7, [x0, #6 * 16]
+  stp q8, q9, [x0, #8 * 16]
+  stp q10, q11, [x0, #10 * 16]
+  stp q12, q13, [x0, #12 * 16]
+  stp q14, q15, [x0, #14 * 16]
+  stp q16, q17, [x0, #16 * 16]
+  stp q18, q19, [x0, #18 * 16]
+  stp q20, q21, [x0, #20 * 16]
+  stp q22, q23, [x0, #22 * 16]
+  stp q24, q25, [x0, #24 * 16]
+  stp q26, q27, [x0, #26 * 16]
+  stp q28, q29, [x0, #28 * 16]
+  stp q30, q31, [x0, #30 * 16]
+  mrs x1, fpcr
+  str x1, [x0, #FPU_FPCR]
+  mrs x1, fpsr
+  str x1, [x0, #FPU_FPSR]
+  ret
+
+// Parameters:
+//   x0: pointer to the thread's floating point state
+.globl thread_restore_fpu
+.balign 16
+thread_restore_fpu:
+  ldp q0, q1, [x0, #0 * 16]
+  ldp q2, q3, [x0, #2 * 16]
+  ldp q4, q5, [x0, #4 * 16]
+  ldp q6, q7, [x0, #6 * 16]
+  ldp q8, q9, [x0, #8 * 16]
+  ldp q10, q11, [x0, #10 * 16]
+  ldp q12, q13, [x0, #12 * 16]
+  ldp q14, q15, [x0, #14 * 16]
+  ldp q16, q17, [x0, #16 * 16]
+  ldp q18, q19, [x0, #18 * 16]
+  ldp q20, q21, [x0, #20 * 16]
+  ldp q22, q23, [x0, #22 * 16]
+  ldp q24, q25, [x0, #24 * 16]
+  ldp q26, q27, [x0, #26 * 16]
+  ldp q28, q29, [x0, #28 * 16]
+  ldp q30, q31, [x0, #30 * 16]
+  ldr x1, [x0, #FPU_FPCR]
+  msr fpcr, x1
+  ldr x1, [x0, #FPU_FPSR]
+  msr fpsr, x1
+  ret
+
+.arch_extension nosimd
+.arch_extension nofp
@@ -0,0 +1,104 @@
+// The kinds of exceptions, given to the handlers to know which entry of the
+// vector table was taken
+.equ TRAP_SYNC, 0
+.equ TRAP_IRQ, 1
+.equ TRAP_FIQ, 2
+.equ TRAP_SERROR, 3
+
+// An entry of the vector table taken while the kernel was running. There is
+// only room for 32 instructions per entry, so the entry saves the first two
+// registers and jumps to the common kernel trap handler with the kind of the
+// exception in x1
+.macro kernel_entry kind
+.balign 0x80
+  sub sp, sp, #32 * 8
+  stp x0, x1, [sp, #0 * 8]
+  mov x1, #\kind
+  b kernel_trap
+.endm
+
+// An entry of the vector table taken while a user thread was running. The
+// stack pointer is the kernel stack pointer saved by `thread_execute`, so the
+// first two registers of the thread are pushed on it to make room for the
+// kind of the exception in x0
+.macro thread_entry kind
+.balign 0x80
+  stp x0, x1, [sp, #-16]!
+  mov x0, #\kind
+  b thread_trap
+.endm
+
+.section .text
+.extern thread_trap
+.globl kernel_vectors
+.balign 2048
+kernel_vectors:
+  // Exceptions taken from the kernel while using SP_EL0, which the kernel
+  // never does
+  kernel_entry TRAP_SYNC
+  kernel_entry TRAP_IRQ
+  kernel_entry TRAP_FIQ
+  kernel_entry TRAP_SERROR
+
+  // Exceptions taken from the kernel while using SP_EL1
+  kernel_entry TRAP_SYNC
+  kernel_entry TRAP_IRQ
+  kernel_entry TRAP_FIQ
+  kernel_entry TRAP_SERROR
+
+  // Exceptions taken from a user thread running in AArch64
+  thread_entry TRAP_SYNC
+  thread_entry TRAP_IRQ
+  thread_entry TRAP_FIQ
+  thread_entry TRAP_SERROR
+
+  // Exceptions taken from a user thread running in AArch32, which is not
+  // supported and cannot happen since the kernel never enters AArch32
+  kernel_entry TRAP_SYNC
+  kernel_entry TRAP_IRQ
+  kernel_entry TRAP_FIQ
+  kernel_entry TRAP_SERROR
+
+.balign 16
+kernel_trap:
+  // Save the remaining registers into the kernel stack. The first two
+  // registers were saved by the entry of the vector table
+  stp x2, x3, [sp, #2 * 8]
+  stp x4, x5, [sp, #4 * 8]
+  stp x6, x7, [sp, #6 * 8]
+  stp x8, x9, [sp, #8 * 8]
+  stp x10, x11, [sp, #10 * 8]
+  stp x12, x13, [sp, #12 * 8]
+  stp x14, x15, [sp, #14 * 8]
+  stp x16, x17, [sp, #16 * 8]
+  stp x18, x19, [sp, #18 * 8]
+  stp x20, x21, [sp, #20 * 8]
+  stp x22, x23, [sp, #22 * 8]
+  stp x24, x25, [sp, #24 * 8]
+  stp x26, x27, [sp, #26 * 8]
+  stp x28, x29, [sp, #28 * 8]
+  str x30, [sp, #30 * 8]
+
+  // Give the saved registers and the kind of the exception to the handler
+  mov x0, sp
+  bl kernel_trap_handler
+
+  // Restore all registers
+  ldp x2, x3, [sp, #2 * 8]
+  ldp x4, x5, [sp, #4 * 8]
+  ldp x6, x7, [sp, #6 * 8]
+  ldp x8, x9, [sp, #8 * 8]
+  ldp x10, x11, [sp, #10 * 8]
+  ldp x12, x13, [sp, #12 * 8]
+  ldp x14, x15, [sp, #14 * 8]
+  ldp x16, x17, [sp, #16 * 8]
+  ldp x18, x19, [sp, #18 * 8]
+  ldp x20, x21, [sp, #20 * 8]
+  ldp x22, x23, [sp, #22 * 8]
+  ldp x24, x25, [sp, #24 * 8]
+  ldp x26, x27, [sp, #26 * 8]
+  ldp x28, x29, [sp, #28 * 8]
+  ldr x30, [sp, #30 * 8]
+  ldp x0, x1, [sp, #0 * 8]
+  add sp, sp, #32 * 8
+  eret
@@ -0,0 +1,47 @@
+// Copy bytes between user and kernel memory. If the copy faults, the kernel
+// trap handler resumes the execution at `user_copy_fault` instead of panicking,
+// which reports the fault to the caller.
+//
+// Parameters:
+//   x0: destination address
+//   x1: source address
+//   x2: number of bytes to copy
+//
+// Returns:
+//   x0: 0 if the copy succeeded, 1 if a fault occurred during the copy
+.section .text
+.globl user_copy
+.globl user_copy_end
+.globl user_copy_fault
+.balign 16
+user_copy:
+  // Copy 8 bytes at a time if both addresses are aligned on 8 bytes
+  orr x3, x0, x1
+  tst x3, #7
+  b.ne 2f
+1:
+  cmp x2, #8
+  b.lo 2f
+  ldr x3, [x1], #8
+  str x3, [x0], #8
+  sub x2, x2, #8
+  b 1b
+
+  // Copy the remaining bytes one by one
+2:
+  cbz x2, 3f
+  ldrb w3, [x1], #1
+  strb w3, [x0], #1
+  sub x2, x2, #1
+  b 2b
+3:
+  mov x0, #0
+  ret
+user_copy_end:
+
+// The trap handler jumps here if a fault occurs between `user_copy` and
+// `user_copy_end`. Since `user_copy` is a leaf function, the link register
+// is still valid and we can directly return to the caller.
+user_copy_fault:
+  mov x0, #1
+  ret
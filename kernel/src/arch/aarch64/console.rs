@@ -0,0 +1,19 @@
+use super::uart;
+
+/// Write the given bytes to the UART. The bytes are dropped if there is no
+/// UART, since there is no firmware console to fall back to on AArch64.
+pub fn write(bytes: &[u8]) {
+    _ = uart::write(bytes);
+}
+
+/// Move the bytes received by the UART to the console input.
+pub fn poll() {
+    uart::poll();
+}
+
+/// Return whether the console input is delivered by an interrupt. This is
+/// never the case without a UART.
+#[must_use]
+pub fn interrupt_driven() -> bool {
+    uart::interrupt_driven()
+}
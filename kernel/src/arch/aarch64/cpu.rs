@@ -0,0 +1,56 @@
+/// Relaxes the CPU by waiting for an interrupt. This function use the `wfi`
+/// instruction to wait for an interrupt and give an hint to the CPU that it
+/// can enter a low power state. The architecture allows `wfi` to complete at
+/// any time, so the caller must not assume that an interrupt is pending when
+/// this function returns.
+///
+/// Interrupts are disabled while waiting and external interrupts are
+/// unmasked, so that an external interrupt masked while the kernel was running
+/// still wakes up the CPU: `wfi` completes when an interrupt is pending even
+/// if it is masked by the `PSTATE.I` bit. Pending interrupts are taken when
+/// interrupts are enabled again, if they were enabled before.
+#[inline]
+pub fn relax() {
+    let enabled = super::irq::enabled();
+    super::irq::disable();
+    super::irq::unmask_external();
+    // SAFETY: Waiting for an interrupt has no side effects.
+    unsafe {
+        core::arch::asm!("wfi", options(nomem, nostack));
+    }
+    if enabled {
+        // SAFETY: Interrupts were enabled before waiting.
+        unsafe {
+            super::irq::enable();
+        }
+    }
+}
+
+/// Puts the CPU in a low power state until an interrupt is pending, like
+/// [`relax`]. The kernel does not use the `CPU_SUSPEND` call of PSCI, whose
+/// power states are platform specific, so this simply waits with `wfi`.
+#[inline]
+pub fn idle() {
+    relax();
+}
+
+/// Returns the index of the current CPU. Kiwi currently only runs on the boot
+/// CPU, which is always given the index 0 regardless of its affinity in the
+/// `MPIDR_EL1` register. When SMP support is added, the index of each CPU
+/// will have to be derived from its affinity when it is started.
+#[inline]
+#[must_use]
+pub const fn id() -> usize {
+    0
+}
+
+/// Freezes the CPU by entering an infinite loop. This function is used to
+/// stop the CPU from executing instructions and is used to halt the CPU.
+/// This function should not return and should be used to stop the CPU from
+/// executing instructions.
+#[inline]
+pub fn freeze() -> ! {
+    loop {
+        relax();
+    }
+}
@@ -0,0 +1,86 @@
+use super::irq::gic;
+use crate::arch::{
+    self,
+    device::{Device, Kind},
+    memory::Region,
+    mmu::{Align, PAGE_SIZE},
+    target::addr::Physical,
+};
+
+/// The maximum number of devices that can be discovered. Devices found after
+/// this limit are ignored.
+const MAX_DEVICES: usize = 32;
+
+/// The value of the `MagicValue` register of a virtio MMIO transport, "virt"
+/// in little endian.
+const VIRTIO_MAGIC: u32 = 0x7472_6976;
+
+/// The offset of the `DeviceID` register of a virtio MMIO transport.
+const VIRTIO_DEVICE_ID: usize = 0x008;
+
+/// The devices found in the device tree.
+static DEVICES: spin::Once<heapless::Vec<Device, MAX_DEVICES>> = spin::Once::new();
+
+/// Find the devices that can be driven from user space in the device tree.
+/// Only virtio MMIO transports are supported for now. The transports with no
+/// device behind them, as created in bulk by QEMU, are ignored.
+pub fn setup(device_tree: &fdt::Fdt) {
+    let mut devices = heapless::Vec::new();
+    for node in device_tree.all_nodes() {
+        if !node
+            .compatible()
+            .is_some_and(|compatible| compatible.all().any(|c| c == "virtio,mmio"))
+        {
+            continue;
+        }
+        let Some(reg) = node.reg().and_then(|mut reg| reg.next()) else {
+            continue;
+        };
+
+        let start = reg.starting_address.addr();
+        let region = Region {
+            start,
+            length: reg.size.unwrap_or(PAGE_SIZE).page_align_up(),
+        };
+        if !start.is_multiple_of(PAGE_SIZE) {
+            continue;
+        }
+        let Some(model) = virtio_device_id(region) else {
+            continue;
+        };
+
+        let device = Device {
+            kind: Kind::VirtioMmio,
+            model,
+            region,
+            irq: gic::interrupts(&node).next(),
+        };
+        if devices.push(device).is_err() {
+            log::warn!("Too many devices, ignoring the others");
+            break;
+        }
+        log::info!(
+            "virtio-mmio device at {:#010x} (irq {:?})",
+            start,
+            device.irq
+        );
+    }
+    DEVICES.call_once(|| devices);
+}
+
+/// Return the device with the given index, or `None` if there are fewer
+/// devices.
+#[must_use]
+pub fn get(index: usize) -> Option<Device> {
+    DEVICES.get()?.get(index).copied()
+}
+
+/// Return the virtio device ID of the device behind the virtio MMIO transport
+/// whose registers are in the given region, or `None` if there is no device
+/// behind it. Only the identification registers are read, which has no side
+/// effect on the device.
+fn virtio_device_id(region: Region) -> Option<u32> {
+    let registers = arch::mmu::map_mmio::<u32>(Physical::new(region.start), region.length)?;
+    let id = registers.read(VIRTIO_DEVICE_ID);
+    (registers.read(0) == VIRTIO_MAGIC && id != 0).then_some(id)
+}
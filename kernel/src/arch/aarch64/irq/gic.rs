@@ -0,0 +1,450 @@
+//! Driver for the GICv3 interrupt controller. The distributor routes the
+//! shared peripheral interrupts (SPIs) of the devices, the redistributor of
+//! each CPU handles its private peripheral interrupts (PPIs) such as the
+//! timer, and the CPU interface is accessed through system registers.
+//!
+//! All the interrupts are in the non-secure group 1. The timer is given a
+//! higher priority than the devices, so that external interrupts can be
+//! masked with the priority mask while the kernel runs without also masking
+//! the timer used by the watchdog.
+use crate::arch::{self, mmio::MmioRegion, target::addr::Physical};
+
+/// The control register of the distributor.
+const GICD_CTLR: usize = 0x0000;
+
+/// The type register of the distributor, giving the number of interrupts.
+const GICD_TYPER: usize = 0x0004;
+
+/// The group registers of the distributor, one bit per interrupt.
+const GICD_IGROUPR: usize = 0x0080;
+
+/// The set-enable registers of the distributor, one bit per interrupt.
+const GICD_ISENABLER: usize = 0x0100;
+
+/// The clear-enable registers of the distributor, one bit per interrupt.
+const GICD_ICENABLER: usize = 0x0180;
+
+/// The priority registers of the distributor, one byte per interrupt.
+const GICD_IPRIORITYR: usize = 0x0400;
+
+/// The configuration registers of the distributor, two bits per interrupt.
+const GICD_ICFGR: usize = 0x0C00;
+
+/// The routing registers of the distributor, one 64-bit register per SPI.
+const GICD_IROUTER: usize = 0x6000;
+
+/// A register write is still in progress, in `GICD_CTLR` and `GICR_CTLR`.
+const CTLR_RWP: u32 = 1 << 31;
+
+/// Enable affinity routing and both groups of interrupts. When the GIC has
+/// two security states, the bits for the secure group are ignored.
+const GICD_CTLR_ENABLE: u32 = (1 << 4) | (1 << 1) | (1 << 0);
+
+/// Enable affinity routing without enabling any interrupt.
+const GICD_CTLR_ARE: u32 = 1 << 4;
+
+/// The type register of a redistributor, giving the affinity of its CPU.
+const GICR_TYPER: usize = 0x0008;
+
+/// The power management register of a redistributor.
+const GICR_WAKER: usize = 0x0014;
+
+/// The redistributor is the last one of its region, in `GICR_TYPER`.
+const GICR_TYPER_LAST: u64 = 1 << 4;
+
+/// The redistributor supports virtual LPIs and has two more frames, in
+/// `GICR_TYPER`.
+const GICR_TYPER_VLPIS: u64 = 1 << 1;
+
+/// The CPU of the redistributor is asleep, in `GICR_WAKER`.
+const GICR_WAKER_PROCESSOR_SLEEP: u32 = 1 << 1;
+
+/// The interface of the redistributor is quiescent, in `GICR_WAKER`.
+const GICR_WAKER_CHILDREN_ASLEEP: u32 = 1 << 2;
+
+/// The offset of the frame holding the SGI and PPI registers of a
+/// redistributor, which have the same layout as in the distributor.
+const GICR_SGI_BASE: usize = 0x1_0000;
+
+/// The size of the frames of a redistributor without virtual LPIs.
+const GICR_STRIDE: usize = 0x2_0000;
+
+/// The size of the registers of the distributor.
+const GICD_SIZE: usize = 0x1_0000;
+
+/// The index of the boot CPU, whose redistributor is the only one initialized.
+const BOOT_CPU: usize = 0;
+
+/// The first shared peripheral interrupt. Interrupts below are private to
+/// each CPU: 0 to 15 are software generated and 16 to 31 are peripheral.
+pub const FIRST_SPI: usize = 32;
+
+/// The first interrupt identifier reserved for special purposes, returned
+/// when acknowledging an interrupt while none is pending.
+const SPURIOUS: usize = 1020;
+
+/// The offset of the shared peripheral interrupts in the device tree.
+const DT_SPI_OFFSET: usize = 32;
+
+/// The offset of the private peripheral interrupts in the device tree.
+const DT_PPI_OFFSET: usize = 16;
+
+/// The type of a shared peripheral interrupt in the device tree.
+const DT_SPI: u32 = 0;
+
+/// The type of a private peripheral interrupt in the device tree.
+const DT_PPI: u32 = 1;
+
+/// The priority of the timer interrupt. Lower values are higher priorities.
+const TIMER_PRIORITY: u8 = 0x80;
+
+/// The priority of the device interrupts.
+const DEVICE_PRIORITY: u8 = 0xA0;
+
+/// The priority mask letting all the interrupts through.
+const PRIORITY_MASK_ALL: u64 = 0xF0;
+
+/// The priority mask only letting the timer interrupt through.
+const PRIORITY_MASK_TIMER: u64 = 0x90;
+
+/// The GIC of the system, if one was found in the device tree.
+static GIC: spin::Once<Gic> = spin::Once::new();
+
+/// A GICv3 interrupt controller. Only the redistributor of the boot CPU is
+/// used, since the kernel runs on a single CPU.
+#[derive(Debug)]
+pub struct Gic {
+    /// The registers of the distributor.
+    distributor: MmioRegion<u32>,
+
+    /// The SGI and PPI registers of the redistributor of the boot CPU.
+    redistributor: MmioRegion<u32>,
+
+    /// The number of interrupt identifiers supported by the distributor,
+    /// including the private ones.
+    lines: usize,
+
+    /// The affinity of the boot CPU, as found in the `MPIDR_EL1` register.
+    affinity: usize,
+}
+
+impl Gic {
+    /// Writes the priority of the given interrupt, in the priority registers
+    /// of the given registers. The priority registers are byte accessible.
+    fn write_priority(registers: &MmioRegion<u32>, irq: usize, priority: u8) {
+        registers
+            .subregion::<u8>(GICD_IPRIORITYR + irq, 1)
+            .write(0, priority);
+    }
+
+    /// Waits until the last write to the control register at the given
+    /// offset of the given registers has taken effect.
+    fn wait_for_write(registers: &MmioRegion<u32>, control: usize) {
+        while registers.read(control) & CTLR_RWP != 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Returns the registers controlling the given interrupt: the
+    /// redistributor for private interrupts, and the distributor for shared
+    /// ones.
+    const fn registers(&self, irq: usize) -> &MmioRegion<u32> {
+        if irq < FIRST_SPI {
+            &self.redistributor
+        } else {
+            &self.distributor
+        }
+    }
+
+    /// Returns whether the given interrupt exists.
+    #[must_use]
+    pub const fn contains(&self, irq: usize) -> bool {
+        irq < self.lines
+    }
+
+    /// Enables the given interrupt. Interrupts that do not exist are ignored.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn enable(&self, irq: usize) {
+        if self.contains(irq) {
+            let offset = GICD_ISENABLER + (irq / 32) * 4;
+            self.registers(irq).write(offset, 1 << (irq % 32));
+        }
+    }
+
+    /// Disables the given interrupt. An interrupt already acknowledged must
+    /// still be ended. Interrupts that do not exist are ignored.
+    pub fn disable(&self, irq: usize) {
+        if self.contains(irq) {
+            let offset = GICD_ICENABLER + (irq / 32) * 4;
+            self.registers(irq).write(offset, 1 << (irq % 32));
+        }
+    }
+
+    /// Routes the given shared interrupt to the CPU with the given index, by
+    /// writing the affinity of the CPU in the routing register of the
+    /// interrupt. Only the redistributor of the boot CPU is initialized, so
+    /// interrupts can only be routed to it. Returns whether the interrupt is
+    /// routed to the CPU.
+    #[must_use]
+    pub fn route(&self, irq: usize, cpu: usize) -> bool {
+        if irq < FIRST_SPI || !self.contains(irq) || cpu != BOOT_CPU {
+            return false;
+        }
+        self.distributor
+            .subregion::<u64>(GICD_IROUTER + (irq - FIRST_SPI) * 8, 8)
+            .write(0, affinity_routing(self.affinity));
+        true
+    }
+
+    /// Sets the priority of the given interrupt. Interrupts that do not exist
+    /// are ignored.
+    fn set_priority(&self, irq: usize, priority: u8) {
+        if self.contains(irq) {
+            Self::write_priority(self.registers(irq), irq, priority);
+        }
+    }
+}
+
+/// Find the GIC in the device tree, initialize the distributor and the
+/// redistributor of the given CPU with all the interrupts disabled, and
+/// enable the CPU interface. If no GICv3 is found, interrupts are not
+/// supported: devices can only be polled and threads are never preempted.
+pub fn setup(device_tree: &fdt::Fdt, cpu: usize) {
+    let Some(node) = device_tree.find_compatible(&["arm,gic-v3"]) else {
+        log::warn!("No GICv3 found, interrupts are disabled");
+        return;
+    };
+    let mut reg = node.reg().into_iter().flatten();
+    let (Some(distributor), Some(redistributors)) = (reg.next(), reg.next()) else {
+        log::warn!("GICv3 without registers, interrupts are disabled");
+        return;
+    };
+
+    let physical = distributor.starting_address.addr();
+    let map = |region: fdt::standard_nodes::MemoryRegion, size: usize| {
+        let base = Physical::new(region.starting_address.addr());
+        arch::mmu::map_mmio::<u32>(base, region.size.unwrap_or(size))
+    };
+    let (Some(distributor), Some(redistributors)) = (
+        map(distributor, GICD_SIZE),
+        map(redistributors, GICR_STRIDE),
+    ) else {
+        log::warn!("GICv3 registers cannot be mapped, interrupts are disabled");
+        return;
+    };
+
+    let stride = node
+        .property("redistributor-stride")
+        .and_then(fdt::node::NodeProperty::as_usize);
+    let Some(offset) = find_redistributor(&redistributors, stride, cpu) else {
+        log::warn!(
+            "No redistributor for cpu {:#x}, interrupts are disabled",
+            cpu
+        );
+        return;
+    };
+
+    let redistributor = redistributors.subregion::<u32>(offset, GICR_STRIDE);
+    let lines = 32 * ((distributor.read(GICD_TYPER) as usize & 0x1F) + 1);
+    let gic = Gic {
+        redistributor: redistributor.subregion(GICR_SGI_BASE, GICR_STRIDE - GICR_SGI_BASE),
+        distributor,
+        lines: lines.min(SPURIOUS),
+        affinity: cpu,
+    };
+    let distributor = &gic.distributor;
+
+    // Configure all the shared interrupts as disabled, level-sensitive and
+    // routed to the boot CPU. They are level-sensitive since the kernel
+    // disables an interrupt until its driver has serviced the device.
+    distributor.write(GICD_CTLR, 0);
+    Gic::wait_for_write(distributor, GICD_CTLR);
+    distributor.write(GICD_CTLR, GICD_CTLR_ARE);
+    Gic::wait_for_write(distributor, GICD_CTLR);
+    for irq in (FIRST_SPI..gic.lines).step_by(32) {
+        distributor.write(GICD_ICENABLER + (irq / 32) * 4, u32::MAX);
+        distributor.write(GICD_IGROUPR + (irq / 32) * 4, u32::MAX);
+    }
+    for irq in (FIRST_SPI..gic.lines).step_by(16) {
+        distributor.write(GICD_ICFGR + (irq / 16) * 4, 0);
+    }
+    let routers = distributor.subregion::<u64>(GICD_IROUTER, (gic.lines - FIRST_SPI) * 8);
+    for irq in FIRST_SPI..gic.lines {
+        Gic::write_priority(distributor, irq, DEVICE_PRIORITY);
+        routers.write((irq - FIRST_SPI) * 8, affinity_routing(cpu));
+    }
+    distributor.write(GICD_CTLR, GICD_CTLR_ENABLE);
+    Gic::wait_for_write(distributor, GICD_CTLR);
+
+    // Wake up the redistributor, and disable all the private interrupts.
+    let waker = redistributor.read(GICR_WAKER);
+    redistributor.write(GICR_WAKER, waker & !GICR_WAKER_PROCESSOR_SLEEP);
+    while redistributor.read(GICR_WAKER) & GICR_WAKER_CHILDREN_ASLEEP != 0 {
+        core::hint::spin_loop();
+    }
+    gic.redistributor.write(GICD_ICENABLER, u32::MAX);
+    gic.redistributor.write(GICD_IGROUPR, u32::MAX);
+    for irq in 0..FIRST_SPI {
+        Gic::write_priority(&gic.redistributor, irq, DEVICE_PRIORITY);
+    }
+
+    // SAFETY: Enabling the system register interface of the CPU interface
+    // and group 1 interrupts only allows interrupts to be signaled, which are
+    // still masked by the `PSTATE.I` bit.
+    unsafe {
+        core::arch::asm!(
+            "mrs {tmp}, icc_sre_el1",
+            "orr {tmp}, {tmp}, #1",
+            "msr icc_sre_el1, {tmp}",
+            "isb",
+            "msr icc_pmr_el1, {mask}",
+            "msr icc_bpr1_el1, xzr",
+            "msr icc_ctlr_el1, xzr",
+            "msr icc_igrpen1_el1, {enable}",
+            "isb",
+            tmp = out(reg) _,
+            mask = in(reg) PRIORITY_MASK_ALL,
+            enable = in(reg) 1u64,
+            options(nostack)
+        );
+    }
+
+    log::info!("GICv3 at {:#010x} with {} interrupts", physical, gic.lines);
+    GIC.call_once(|| gic);
+}
+
+/// Find the redistributor of the CPU with the given affinity, by walking the
+/// redistributors of the given region. Returns the offset of its first frame
+/// in the region, or `None` if it is not found before the end of the region.
+fn find_redistributor(
+    redistributors: &MmioRegion<u32>,
+    stride: Option<usize>,
+    cpu: usize,
+) -> Option<usize> {
+    let mut offset = 0;
+    while offset + GICR_STRIDE <= redistributors.size() {
+        let typer = redistributors
+            .subregion::<u64>(offset + GICR_TYPER, 8)
+            .read(0);
+        if (typer >> 32) as usize & 0xFF_FFFF == cpu & 0xFF_FFFF {
+            return Some(offset);
+        }
+        if typer & GICR_TYPER_LAST != 0 {
+            return None;
+        }
+        offset += stride.unwrap_or(if typer & GICR_TYPER_VLPIS != 0 {
+            2 * GICR_STRIDE
+        } else {
+            GICR_STRIDE
+        });
+    }
+    None
+}
+
+/// Returns the value of a routing register targeting the CPU with the given
+/// affinity, as found in the `MPIDR_EL1` register.
+const fn affinity_routing(cpu: usize) -> u64 {
+    (cpu & 0xFF_FFFF) as u64
+}
+
+/// Return the GIC of the system, or `None` if there is none.
+#[must_use]
+pub fn get() -> Option<&'static Gic> {
+    GIC.get()
+}
+
+/// Enable the given private interrupt of the timer with a priority higher
+/// than the devices, so that it is not masked by [`mask_devices`].
+pub fn enable_timer(irq: usize) {
+    if let Some(gic) = get() {
+        gic.set_priority(irq, TIMER_PRIORITY);
+        gic.enable(irq);
+    }
+}
+
+/// Acknowledge the pending interrupt with the highest priority, and return
+/// its identifier. Returns `None` if no interrupt is pending. The interrupt
+/// will not be signaled again until it is ended with [`end`].
+#[must_use]
+pub fn acknowledge() -> Option<usize> {
+    let irq: u64;
+    // SAFETY: Acknowledging an interrupt only changes the state of the GIC.
+    unsafe {
+        core::arch::asm!("mrs {}, icc_iar1_el1", out(reg) irq, options(nomem, nostack));
+    }
+    let irq = (irq & 0xFF_FFFF) as usize;
+    (irq < SPURIOUS).then_some(irq)
+}
+
+/// Return the pending interrupt with the highest priority without
+/// acknowledging it, or `None` if no interrupt is pending.
+#[must_use]
+pub fn pending() -> Option<usize> {
+    let irq: u64;
+    // SAFETY: Reading the highest priority pending interrupt has no side
+    // effect.
+    unsafe {
+        core::arch::asm!("mrs {}, icc_hppir1_el1", out(reg) irq, options(nomem, nostack));
+    }
+    let irq = (irq & 0xFF_FFFF) as usize;
+    (irq < SPURIOUS).then_some(irq)
+}
+
+/// End the handling of the given interrupt acknowledged with
+/// [`acknowledge`], allowing it to be signaled again.
+pub fn end(irq: usize) {
+    // SAFETY: Ending an interrupt only changes the state of the GIC.
+    unsafe {
+        core::arch::asm!("msr icc_eoir1_el1, {}", "isb", in(reg) irq as u64, options(nomem, nostack));
+    }
+}
+
+/// Mask the interrupts of the devices with the priority mask of the CPU
+/// interface, while still letting the timer interrupt through.
+pub fn mask_devices() {
+    set_priority_mask(PRIORITY_MASK_TIMER);
+}
+
+/// Unmask the interrupts of the devices masked by [`mask_devices`].
+pub fn unmask_devices() {
+    set_priority_mask(PRIORITY_MASK_ALL);
+}
+
+/// Set the priority mask of the CPU interface. Only the interrupts with a
+/// higher priority than the mask, meaning a lower value, are signaled.
+fn set_priority_mask(mask: u64) {
+    if get().is_some() {
+        // SAFETY: Changing the priority mask only delays the signaling of
+        // interrupts.
+        unsafe {
+            core::arch::asm!("msr icc_pmr_el1, {}", "isb", in(reg) mask, options(nomem, nostack));
+        }
+    }
+}
+
+/// Returns the interrupt identifiers given in the `interrupts` property of
+/// the given node, for interrupts routed to the GIC. Each interrupt is given
+/// by three cells: its type, its number among the interrupts of this type,
+/// and its trigger flags. Contrary to the `fdt` crate, which only supports
+/// single cell interrupts, this converts the interrupts to their identifier
+/// in the GIC.
+pub fn interrupts<'a>(node: &fdt::node::FdtNode<'_, 'a>) -> impl Iterator<Item = usize> + 'a {
+    node.property("interrupts")
+        .map_or(&[][..], |property| property.value)
+        .chunks_exact(12)
+        .filter_map(|cells| {
+            let cell = |index: usize| {
+                u32::from_be_bytes([
+                    cells[index * 4],
+                    cells[index * 4 + 1],
+                    cells[index * 4 + 2],
+                    cells[index * 4 + 3],
+                ])
+            };
+            match cell(0) {
+                DT_SPI => Some(cell(1) as usize + DT_SPI_OFFSET),
+                DT_PPI => Some(cell(1) as usize + DT_PPI_OFFSET),
+                _ => None,
+            }
+        })
+}
@@ -0,0 +1,164 @@
+use super::uart;
+
+pub mod gic;
+
+/// Enable interrupts.
+///
+/// # Safety
+/// This function is unsafe because it can break invariants of other code.
+/// Enabling interrupts could lead to memory unsafety, race conditions,
+/// deadlocks, and other undefined behavior.
+pub unsafe fn enable() {
+    // SAFETY: The caller guarantees that interrupts can be enabled.
+    unsafe {
+        core::arch::asm!("msr daifclr, #2", options(nomem, nostack));
+    }
+}
+
+/// Disable interrupts. No interrupt will be triggered until interrupts
+/// are enabled again. However, exceptions will still be triggered.
+pub fn disable() {
+    // SAFETY: Disabling interrupts should be safe and should
+    // not cause any side effect that could lead to undefined
+    // behavior.
+    unsafe {
+        core::arch::asm!("msr daifset, #2", options(nomem, nostack));
+    }
+}
+
+/// Check if interrupts are enabled.
+#[must_use]
+pub fn enabled() -> bool {
+    let daif: u64;
+    // SAFETY: Reading the interrupt mask bits has no side effects.
+    unsafe {
+        core::arch::asm!("mrs {}, daif", out(reg) daif, options(nomem, nostack));
+    }
+    daif & (1 << 7) == 0
+}
+
+/// Initialize the interrupt controller for the given CPU. The interrupt of
+/// the UART is routed to the kernel, and all the other interrupts stay
+/// disabled until they are bound to a user space driver, except the timer
+/// which is enabled when the timer is set up.
+pub fn setup(device_tree: &fdt::Fdt, cpu: usize) {
+    gic::setup(device_tree, cpu);
+    let Some(gic) = gic::get() else {
+        return;
+    };
+
+    if let Some(irq) = uart::irq() {
+        gic.enable(irq);
+    }
+}
+
+/// Enable the interrupt of the timer.
+pub fn enable_timer(irq: usize) {
+    gic::enable_timer(irq);
+}
+
+/// Return whether the given interrupt can be bound to a user space driver: it
+/// must be a shared interrupt supported by the GIC, and must not be used by
+/// the kernel itself.
+#[must_use]
+pub fn bindable(irq: usize) -> bool {
+    irq >= gic::FIRST_SPI
+        && gic::get().is_some_and(|gic| gic.contains(irq))
+        && uart::irq() != Some(irq)
+}
+
+/// Return whether the platform has an interrupt controller driven by the
+/// kernel.
+#[must_use]
+pub fn controller_available() -> bool {
+    gic::get().is_some()
+}
+
+/// Mask the given interrupt, preventing it from being signaled.
+pub fn mask(irq: usize) {
+    if let Some(gic) = gic::get() {
+        gic.disable(irq);
+    }
+}
+
+/// Unmask the given interrupt, allowing it to be signaled again.
+pub fn unmask(irq: usize) {
+    if let Some(gic) = gic::get() {
+        gic.enable(irq);
+    }
+}
+
+/// Route the given interrupt to the CPU with the given index. Returns `false`
+/// if the interrupt is not a shared interrupt supported by the GIC, or if the
+/// GIC cannot route it to this CPU.
+#[must_use]
+pub fn set_affinity(irq: usize, cpu: usize) -> bool {
+    gic::get().is_some_and(|gic| gic.route(irq, cpu))
+}
+
+/// Handle all the pending interrupts, and return whether there was any. The
+/// timer interrupt is handled by rearming the timer for the watchdog, and
+/// the interrupts of the UART are handled by the kernel. The others are
+/// masked and forwarded to the task they are bound to by the executor, which
+/// unmasks them once it has serviced its device. Interrupts that are not bound to any
+/// task stay masked.
+#[must_use]
+pub fn handle_external() -> bool {
+    let Some(gic) = gic::get() else {
+        return false;
+    };
+
+    let mut handled = false;
+    while let Some(irq) = gic::acknowledge() {
+        if super::timer::irq() == Some(irq) {
+            // The timer interrupt is level-sensitive, so the timer must be
+            // rearmed before the interrupt is ended. The expired timers are
+            // delivered by the executor.
+            crate::watchdog::arm();
+            crate::future::deferred::defer(|_| crate::time::wheel::poll(), 0);
+        } else if uart::irq() == Some(irq) {
+            uart::handle_interrupt();
+        } else {
+            gic.disable(irq);
+            crate::future::deferred::defer(notify, irq);
+        }
+        gic::end(irq);
+        handled = true;
+    }
+    handled
+}
+
+/// Notify the task bound to the given interrupt that it was raised. This is
+/// deferred to the executor by [`handle_external`].
+fn notify(irq: usize) {
+    if !crate::user::irq::notify(irq) {
+        log::warn!("Spurious interrupt {} not bound to any task", irq);
+    }
+}
+
+/// Handle the interrupts that are pending while the kernel was not running a
+/// user thread. The kernel does not handle external interrupts while it runs,
+/// so they would otherwise only be handled the next time a user thread is
+/// interrupted. The GIC only lets the kernel acknowledge the interrupts that
+/// are not masked by the priority mask, so they are unmasked first.
+pub fn handle_pending() {
+    crate::arch::irq::without(|| {
+        unmask_external();
+        _ = handle_external();
+    });
+}
+
+/// Mask external interrupts until [`unmask_external`] is called. When the
+/// watchdog lets the timer interrupt the kernel, an external interrupt can
+/// also be raised while the kernel runs. It cannot be handled there since the
+/// kernel may hold the locks needed to handle it, so external interrupts are
+/// masked with the priority mask of the GIC and left pending until the kernel
+/// is ready to handle them.
+pub fn mask_external() {
+    gic::mask_devices();
+}
+
+/// Unmask external interrupts masked by [`mask_external`].
+pub fn unmask_external() {
+    gic::unmask_devices();
+}
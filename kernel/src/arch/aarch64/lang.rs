@@ -0,0 +1,150 @@
+use super::trap;
+use crate::{config, future};
+use ::syscall::SyscallOp;
+use core::sync::atomic::{AtomicBool, Ordering};
+use macros::init;
+
+core::arch::global_asm!(
+    include_str!("asm/boot.asm"),
+    randomize = const config::RANDOMIZE_KERNEL_BASE as usize,
+    slide_bits = const super::mmu::KERNEL_SLIDE_BITS,
+    image_regions = const super::mmu::KERNEL_IMAGE_PAGE_TABLES,
+);
+
+/// The maximum number of frames printed in the backtrace of a kernel panic.
+/// This bounds the output if the chain of frame pointers is corrupted in a
+/// way that still looks valid.
+const MAX_BACKTRACE_DEPTH: usize = 32;
+
+/// Set when the kernel starts panicking, so that a panic raised while
+/// printing the report of another panic does not recurse forever.
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "C" {
+    static boot_stack_bottom: u8;
+    static boot_stack_top: u8;
+}
+
+/// Oops ! The kernel panicked and must be stopped. Since we are developing a
+/// microkernel, this should never happen. If it does, it means that there is a
+/// bug in the kernel. It will print the panic message, the registers of the
+/// kernel if the panic was caused by a trap, the task and syscall being
+/// handled and a backtrace, and then shut down or reboot the computer
+/// depending on [`config::REBOOT_ON_PANIC`].
+///
+/// Everything printed here must not block, since the panic may have happened
+/// while a lock was held.
+#[cold]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    if PANICKING.swap(true, Ordering::Relaxed) {
+        ::log::error!("Kernel panic while panicking: {}", info.message());
+        super::psci::system_off();
+    }
+
+    if let Some(location) = info.location() {
+        ::log::error!(
+            "Kernel panic at {}:{}: {}",
+            location.file(),
+            location.line(),
+            info.message()
+        );
+    } else {
+        ::log::error!("Kernel panic without location or message :(");
+    }
+
+    let frame_pointer = if let Some(trap) = trap::fatal_trap() {
+        ::log::error!(
+            "Caused by trap {:?} (esr: {:#x}, far: {:#x})",
+            trap.kind,
+            trap.esr,
+            trap.far
+        );
+        ::log::error!("Registers:\n{}", trap.context);
+        trap.frame_pointer()
+    } else {
+        let fp: usize;
+        // SAFETY: Reading the frame pointer has no side effects.
+        unsafe {
+            core::arch::asm!("mov {}, x29", out(reg) fp);
+        }
+        fp
+    };
+
+    let task = future::executor::peek_current_task_id();
+    let syscall = task.and_then(future::task::peek_syscall);
+    if let Some(id) = task {
+        ::log::error!("Current task: {:?}", id);
+    } else {
+        ::log::error!("No current task");
+    }
+    if let Some(syscall) = syscall {
+        ::log::error!("In syscall: {:?} ({})", SyscallOp::from(syscall), syscall);
+    }
+
+    backtrace(frame_pointer);
+
+    if config::REBOOT_ON_PANIC {
+        super::reboot();
+    }
+    super::shutdown();
+}
+
+/// Prints the return addresses of the kernel stack, starting from the frame
+/// with the given frame pointer. The kernel is built with frame pointers, so
+/// each frame stores the frame pointer of its caller at `fp` and the return
+/// address at `fp + 8`. The walk stops at the entry point, whose frame
+/// pointer is zero, or as soon as a frame pointer does not point into the
+/// boot stack or does not move up the stack, so that a corrupted stack does
+/// not cause another fault.
+fn backtrace(mut fp: usize) {
+    let bottom = (&raw const boot_stack_bottom).addr();
+    let top = (&raw const boot_stack_top).addr();
+
+    // Print the addresses where the code was linked rather than where it
+    // runs, so that they can be looked up in the kernel binary.
+    let slide = super::mmu::kernel_slide();
+    ::log::error!("Backtrace (kernel slide: {:#x}):", slide);
+    for depth in 0..MAX_BACKTRACE_DEPTH {
+        if fp < bottom || fp + 16 > top || !fp.is_multiple_of(8) {
+            return;
+        }
+
+        // SAFETY: The frame pointer was checked to point into the boot stack
+        // and to be aligned, so both words above it can be read.
+        let (ra, previous) = unsafe {
+            (
+                core::ptr::with_exposed_provenance::<usize>(fp + 8).read(),
+                core::ptr::with_exposed_provenance::<usize>(fp).read(),
+            )
+        };
+        if ra == 0 {
+            return;
+        }
+
+        ::log::error!("  #{:<2} {:#x}", depth, ra.wrapping_sub(slide));
+        if previous <= fp {
+            return;
+        }
+        fp = previous;
+    }
+    ::log::error!("  ... (truncated)");
+}
+
+/// The entry point of the kernel. It will call architecture-specific setup
+/// and then call the `kiwi` function which is the main function of the kernel
+/// that will properly start the kernel and never return. The boot code gives
+/// the slide applied to the virtual address of the kernel image as the last
+/// argument.
+#[init]
+#[unsafe(no_mangle)]
+unsafe extern "C" fn entry(cpu: usize, device_tree: usize, slide: usize) -> ! {
+    // SAFETY: This is the slide chosen by the boot code, which relocated and
+    // mapped the kernel image accordingly.
+    unsafe {
+        super::mmu::set_kernel_slide(slide);
+    }
+
+    // Setup the architecture-specific stuff and start the kernel
+    crate::kiwi(super::setup(cpu, device_tree));
+}
@@ -0,0 +1,5 @@
+/// Write a message to the console. Messages written before the UART is
+/// initialized, or on platforms without a UART, are dropped.
+pub fn write(message: &str) {
+    super::console::write(message.as_bytes());
+}
@@ -0,0 +1,144 @@
+use super::mmu;
+use crate::arch::{generic::memory::UsableMemory, memory::Region};
+use heapless::Vec;
+
+unsafe extern "C" {
+    static __reclaimable_start: [u8; 0];
+    static __reclaimable_end: [u8; 0];
+    static __start: [u8; 0];
+    static __end: [u8; 0];
+}
+
+impl UsableMemory {
+    /// Create a new `UsableMemory` structure from the device tree given
+    /// as argument.
+    ///
+    /// # Panics
+    /// This function will panic if there is no information about the memory
+    /// regions in the device tree, or if there are too many memory regions
+    /// in the device tree that we cannot handle.
+    #[inline]
+    #[must_use]
+    pub fn new(device_tree: &fdt::Fdt) -> Self {
+        // Compute the kernel start and end addresses in physical memory, so
+        // that we can skip the kernel memory region when adding the memory
+        // regions to the usable memory to avoid overwriting ourselves :(
+        let kernel_physical_start =
+            usize::from(mmu::translate_kernel_ptr(core::ptr::addr_of!(__start)));
+        let kernel_physical_end =
+            usize::from(mmu::translate_kernel_ptr(core::ptr::addr_of!(__end)));
+        let kernel_reclaimable_start = usize::from(mmu::translate_kernel_ptr(core::ptr::addr_of!(
+            __reclaimable_start
+        )));
+        let kernel_reclaimable_end = usize::from(mmu::translate_kernel_ptr(core::ptr::addr_of!(
+            __reclaimable_end
+        )));
+
+        let kernel_memory = kernel_physical_end - kernel_physical_start;
+        let firmware_memory = 0x0020_0000;
+        let total_memory = device_tree
+            .memory()
+            .regions()
+            .map(|r| r.size.unwrap_or(0))
+            .sum::<usize>();
+
+        let ram_start = device_tree
+            .memory()
+            .regions()
+            .map(|region| region.starting_address.addr())
+            .min()
+            .unwrap();
+
+        let ram_end = device_tree
+            .memory()
+            .regions()
+            .map(|region| {
+                let start = region.starting_address.addr();
+                start + region.size.unwrap_or(0)
+            })
+            .max()
+            .unwrap();
+
+        log::info!("Total memory: {} kiB", total_memory / 1024);
+        log::info!("Firmware memory: {} KiB", firmware_memory / 1024);
+        log::info!("Kernel memory: {} kiB", kernel_memory / 1024);
+        log::debug!(
+            "Reclaimable memory: {} KiB",
+            (kernel_reclaimable_end - kernel_reclaimable_start) / 1024
+        );
+        log::debug!("RAM start: 0x{:016x}", ram_start);
+        log::debug!("RAM end: 0x{:016x}", ram_end);
+
+        // Iterate over all the memory regions in the device tree and add
+        // them to the usable memory regions
+        let mut regions = Vec::<Region, 32>::new();
+        for region in device_tree.memory().regions() {
+            let mut start = region.starting_address.addr();
+            let mut length = region.size.unwrap_or(0);
+
+            // The region 0x40000000 to 0x40200000 holds the device tree given
+            // by QEMU and is reserved for the firmware. The region
+            // kernel_start (0x40200000) to kernel_end is reserved for the
+            // kernel static code and data
+            if start < kernel_physical_end {
+                length -= kernel_physical_end - mmu::KERNEL_PHYSICAL_BASE.as_usize();
+                start = kernel_physical_end;
+            }
+
+            regions
+                .push(Region { start, length })
+                .expect("Failed to push region");
+
+            ::log::debug!(
+                "Available memory region: {:#010x} - {:#010x}",
+                start,
+                start + length
+            );
+        }
+
+        let mut memory = Self {
+            regions,
+            firmware_memory,
+            kernel_memory,
+            total_memory,
+            ram_start,
+            ram_end,
+            initrd: None,
+        };
+
+        // Keep the initial ramdisk loaded by the bootloader out of the usable
+        // memory, so that it is not overwritten before being parsed.
+        if let Some(initrd) = initrd(device_tree) {
+            ::log::info!(
+                "Initial ramdisk: {:#010x} - {:#010x}",
+                initrd.start,
+                initrd.end()
+            );
+            memory.reserve(initrd);
+            memory.initrd = Some(initrd);
+        }
+        memory
+    }
+}
+
+/// Find the initial ramdisk loaded by the bootloader in the device tree. Its
+/// location is given by the `linux,initrd-start` and `linux,initrd-end`
+/// properties of the `/chosen` node, which are used by both QEMU and U-Boot.
+/// Returns `None` if there is no initial ramdisk or if it is empty.
+fn initrd(device_tree: &fdt::Fdt) -> Option<Region> {
+    let chosen = device_tree.find_node("/chosen")?;
+    let start = chosen.property("linux,initrd-start")?.as_usize()?;
+    let end = chosen.property("linux,initrd-end")?.as_usize()?;
+    (end > start).then(|| Region {
+        start,
+        length: end - start,
+    })
+}
+
+/// Return the range of virtual addresses of the kernel image that is only used
+/// during boot: the `.early` section holding the boot code and the boot page
+/// table, and the `.init` section. Both are page aligned by the linker script.
+#[must_use]
+pub fn reclaimable_range() -> core::ops::Range<usize> {
+    (&raw const __reclaimable_start).addr()..(&raw const __reclaimable_end).addr()
+}
@@ -0,0 +1,1402 @@
+//! AArch64 Memory Management Unit implementation. The page tables use the 4
+//! KiB granule with 38-bit virtual addresses in both halves of the address
+//! space, so that the translation starts at level 1 and uses 3 levels of page
+//! tables, like the SV39 layout of RISC-V.
+//!
+//! Contrary to RISC-V, the two halves of the address space are translated by
+//! two different tables: `TTBR0_EL1` holds the root table of the current user
+//! address space, and `TTBR1_EL1` holds the kernel table. To keep the same
+//! layout as other architectures, each root table has 512 entries where the
+//! first 256 entries map the user half and the last 256 entries map the kernel
+//! half. The MMU only walks the first 256 entries of the table given in
+//! `TTBR0_EL1`, and `TTBR1_EL1` points to the last 256 entries of the kernel
+//! table, which is never changed after boot.
+use super::{
+    addr::{self, Frame1Gib, Frame2Mib, Frame4Kib, Physical, Virtual, virt::Kernel},
+    tlb,
+};
+use crate::{
+    arch::{
+        memory::UsableMemory,
+        mmu::{Flags, MapError, Rights, UnmapError},
+    },
+    mm::{self, phys::AllocationFlags},
+    utils::lock,
+};
+use bitflags::bitflags;
+use core::{
+    ops::{Index, IndexMut},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use kcore::paging;
+use usize_cast::IntoUsize;
+
+/// The virtual address where the kernel base starts. The last 1 GiB of
+/// virtual memory is reserved for the kernel, and this address is where
+/// the kernel maps the first 1 GiB of physical memory. The rest of the
+/// physical memory is identity mapped in the kernel's address space to
+/// allow the kernel to access any physical address easily.
+pub const KERNEL_VIRTUAL_BASE: Virtual<Kernel> = Virtual::<Kernel>::new(0xFFFF_FFFF_C000_0000);
+
+/// The physical address where the RAM starts on the QEMU virt machine. This
+/// address will be mapped to the kernel's address space at the address
+/// defined by `KERNEL_VIRTUAL_BASE`.
+pub const KERNEL_PHYSICAL_BASE: Frame1Gib =
+    unsafe { Frame1Gib::new_unchecked(Physical::new(0x4000_0000)) };
+
+/// The start of ther kernel's address space. This corresponds to the first
+/// address translated with the `TTBR1_EL1` register and goes up to the last
+/// address of the virtual address space.
+pub const KERNEL_START: Virtual<Kernel> = Virtual::<Kernel>::new(0xFFFF_FFC0_0000_0000);
+
+/// The start of the virtual memory range reserved for the kernel heap. This
+/// range covers the 1 GiB of virtual memory just below the kernel, and is
+/// populated on demand with [`map_kernel`] as the heap grows.
+pub const KERNEL_HEAP_START: Virtual<Kernel> = Virtual::<Kernel>::new(0xFFFF_FFFF_8000_0000);
+
+/// The size of the virtual memory range reserved for the kernel heap.
+pub const KERNEL_HEAP_SIZE: usize = 1024 * 1024 * 1024;
+
+/// The index of the kernel space entry of the root table covering the kernel
+/// heap range.
+const KERNEL_HEAP_ENTRY: usize = 254;
+
+/// The size of a page in bytes.
+pub const PAGE_SIZE: usize = 4096;
+
+/// The shift required to convert a byte address to a page address.
+pub const PAGE_SHIFT: usize = 12;
+
+/// The mask of the output address bits of a descriptor.
+const ADDRESS_MASK: u64 = 0x0000_FFFF_FFFF_F000;
+
+/// The kernel's page table. This table is used by the kernel to identity
+/// map the physical memory of the system, allowing the kernel to easily
+/// access the physical memory of the system.
+static KERNEL_TABLE: spin::Once<lock::Mutex<RootTable>> = spin::Once::new();
+
+/// The table mapping the kernel heap range. It is statically allocated since
+/// the physical memory manager is not yet initialized when the MMU is set up.
+/// It is only accessed through the kernel table, while holding its lock.
+static mut KERNEL_HEAP_TABLE: Table = Table::empty();
+
+/// Whether all the intermediate tables of the kernel half have been allocated
+/// by [`preallocate_kernel_tables`]. Once set, mapping a page in the kernel
+/// half must never allocate a table.
+static KERNEL_TABLES_PREALLOCATED: AtomicBool = AtomicBool::new(false);
+
+/// The table mapping the kernel image, in the last 1 GiB of virtual memory.
+/// Like the kernel heap table, it is statically allocated and only accessed
+/// through the kernel table.
+static mut KERNEL_IMAGE_TABLE: Table = Table::empty();
+
+/// The maximum number of 2 MiB regions spanned by the kernel image.
+pub const KERNEL_IMAGE_PAGE_TABLES: usize = 4;
+
+/// The number of bits of entropy of the slide of the kernel image. The image
+/// is slid by a multiple of 2 MiB, so that the boot code can map it with 2 MiB
+/// pages, and this allows slides of up to 512 MiB.
+pub const KERNEL_SLIDE_BITS: usize = 8;
+
+/// The offset between the address where the kernel image is mapped and the
+/// address where it was linked. It is chosen and applied by the boot code when
+/// [`crate::config::RANDOMIZE_KERNEL_BASE`] is set, and is zero otherwise.
+static KERNEL_SLIDE: AtomicUsize = AtomicUsize::new(0);
+
+/// The tables mapping the kernel image with 4 KiB pages, one for each 2 MiB
+/// region spanned by the image. The kernel image is mapped with 4 KiB pages
+/// so that each section is mapped with its own rights, and so that the
+/// sections only used during boot can be unmapped afterwards.
+static mut KERNEL_IMAGE_PAGES: [Table; KERNEL_IMAGE_PAGE_TABLES] =
+    [const { Table::empty() }; KERNEL_IMAGE_PAGE_TABLES];
+
+/// Whether the processor implements Privileged Access Never, which prevents
+/// the kernel from accessing user pages unless it explicitly allows it.
+static PAN: AtomicBool = AtomicBool::new(false);
+
+unsafe extern "C" {
+    static __reclaimable_start: [u8; 0];
+    static __init_start: [u8; 0];
+    static __init_data_start: [u8; 0];
+    static __init_end: [u8; 0];
+    static __text_start: [u8; 0];
+    static __text_end: [u8; 0];
+    static __rodata_start: [u8; 0];
+    static __rodata_end: [u8; 0];
+    static __data_start: [u8; 0];
+    static __data_end: [u8; 0];
+    static boot_stack_bottom: [u8; 0];
+    static __end: [u8; 0];
+}
+
+/// Returns the physical address of the root table of the current user
+/// address space, held by the `TTBR0_EL1` register.
+fn current_root() -> Physical {
+    let ttbr0: usize;
+    // SAFETY: Reading the `TTBR0_EL1` register has no side effects.
+    unsafe {
+        core::arch::asm!("mrs {}, ttbr0_el1", out(reg) ttbr0);
+    }
+    Physical::new(ttbr0 & ADDRESS_MASK.into_usize())
+}
+
+/// The root page table type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootTable(Table);
+
+impl RootTable {
+    /// Create an empty root page table. The root page table is the top-level
+    /// page table that contains the entries for the entire virtual address
+    /// space.
+    /// Trying to use this table without properly initializing it will lead
+    /// to immediate page faults.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self(Table::empty())
+    }
+
+    /// Set all the user-accessible entries of the table to zero and copy the
+    /// kernel address space into the table. The MMU never walks the kernel
+    /// entries of a user table, but they keep the layout identical to other
+    /// architectures, so that a kernel address can be translated with any
+    /// table.
+    ///
+    /// If any of the user-accessible entries was pointer to a valid table or
+    /// a valid page, this will lead to the memory leak of the entire table
+    /// and its sub-tables or pages.
+    ///
+    /// # Panics
+    /// This function will panic if the kernel table is not initialized.
+    pub fn copy_kernel_space(&mut self) {
+        let table = KERNEL_TABLE.get().unwrap().lock();
+        self.user_space_mut().iter_mut().for_each(Entry::clear);
+        self.kernel_space_mut()
+            .iter_mut()
+            .zip(table.kernel_space())
+            .for_each(|(dst, src)| *dst = *src);
+    }
+
+    /// Set the current user page table to this table. If this table is
+    /// already the current page table, this function does nothing and avoids
+    /// the costly operation of switching the page table and flushing the TLB.
+    ///
+    /// # Safety
+    /// This function is unsafe because it can cause undefined behavior if
+    /// the table is not properly initialized. The caller must ensure that
+    /// the table given will not cause an instant page fault when set as
+    /// the current page table, and must ensure that the table will remain
+    /// in memory while it is set as the current page table.
+    pub unsafe fn set_current(&self) {
+        let root = translate_kernel_ptr(self);
+        if current_root() != root {
+            // Only the user translations need to be flushed since the kernel
+            // half is mapped with global translations shared by all address
+            // spaces.
+            unsafe {
+                core::arch::asm!(
+                    "msr ttbr0_el1, {}",
+                    "isb",
+                    in(reg) root.as_usize(),
+                    options(nostack)
+                );
+            }
+            tlb::flush_user();
+        }
+    }
+
+    /// Get a mutable reference to the last entry of the kernel space.
+    #[must_use]
+    pub fn last_kernel_entry_mut(&mut self) -> &mut Entry {
+        &mut self.kernel_space_mut()[255]
+    }
+
+    /// Get a reference to the last entry of the kernel space.
+    #[must_use]
+    pub fn last_kernel_entry(&self) -> &Entry {
+        &self.kernel_space()[255]
+    }
+
+    /// Get a mutable reference to the address space table.
+    pub fn address_space_mut(&mut self) -> &mut Table {
+        &mut self.0
+    }
+
+    /// Get a mutable reference to the kernel space entries of the table.
+    #[must_use]
+    pub fn kernel_space_mut(&mut self) -> &mut [Entry] {
+        &mut self.0.0[256..512]
+    }
+
+    /// Get a mutable reference to the user space entries of the table.
+    #[must_use]
+    pub fn user_space_mut(&mut self) -> &mut [Entry] {
+        &mut self.0.0[0..256]
+    }
+
+    /// Get a reference to the address space table.
+    #[must_use]
+    pub fn address_space(&self) -> &Table {
+        &self.0
+    }
+
+    /// Get a reference to the kernel space entries of the table.
+    #[must_use]
+    pub fn kernel_space(&self) -> &[Entry] {
+        &self.0.0[256..512]
+    }
+
+    /// Get a reference to the user space entries of the table.
+    #[must_use]
+    pub fn user_space(&self) -> &[Entry] {
+        &self.0.0[0..256]
+    }
+}
+
+impl AsRef<Table> for RootTable {
+    fn as_ref(&self) -> &Table {
+        &self.0
+    }
+}
+
+impl Drop for RootTable {
+    fn drop(&mut self) {
+        // SAFETY: Switching to the kernel page table should be safe because
+        // the kernel table should be initialized at this point, and we must
+        // ensure that the thread's page table is not active when it is being
+        // dropped. Also, unmapping all user space mappings should be safe to
+        // do in the kernel because the kernel is dropping the entire address
+        // space, and should not have directs references in user space.
+        unsafe {
+            use_kernel_table();
+            paging::release(&mut TableFrames::default(), self.user_space_mut(), 0);
+        }
+    }
+}
+
+/// Represents a page table. A page table is a data structure used by the
+/// processor to translate virtual addresses to physical addresses. With the
+/// 4 KiB granule, each table contains 512 entries that point to the table of
+/// the next level or map a block of physical memory.
+///
+/// An entry of the first or second level with the table bit set points to a
+/// table, and maps a block of 1 GiB or 2 MiB otherwise. Entries of the last
+/// level must always have this bit set to map a page. To tell leaf entries
+/// apart without knowing their level, the kernel sets the access flag of all
+/// the leaf entries, which is ignored by the processor in table entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(align(4096))]
+pub struct Table([Entry; 512], core::marker::PhantomPinned);
+
+impl Table {
+    /// Create a new empty page table. An empty page table is a table where
+    /// all entries are missing, meaning that they do not point to a physical
+    /// address and are not present in the page table.
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self([Entry::missing(); 512], core::marker::PhantomPinned)
+    }
+
+    /// Returns whether no entry of the table is present.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|entry| !entry.present())
+    }
+}
+
+impl Index<usize> for Table {
+    type Output = Entry;
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for Table {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+/// A page table entry, called a descriptor by the AArch64 specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Entry(u64);
+
+impl Entry {
+    /// Create a new entry that is missing, meaning that it does not point to a
+    /// physical address and is not present in the page table.
+    #[must_use]
+    pub const fn missing() -> Self {
+        Self(0)
+    }
+
+    /// Create a new entry that points to a physical address. However, this
+    /// entry will not have any flags set, meaning that it is not present in
+    /// the page table and trying to access it will raise an exception.
+    ///
+    /// Furthermore, the physical address must be properly aligned, depending
+    /// on the level of the page table that this entry is part of: 4 KiB for
+    /// the last level, 2 MiB for the second level and 1 GiB for the first
+    /// level.
+    #[must_use]
+    pub const fn new(frame: Frame4Kib) -> Self {
+        Self(frame.inner().as_u64() & ADDRESS_MASK)
+    }
+
+    /// Set the access rights of the entry. The access flag is also set, since
+    /// the kernel does not track accesses to pages and this marks the entry
+    /// as a leaf (see [`Table`]).
+    ///
+    /// The read right is implicit on AArch64, and pages accessible by user
+    /// space are never executable by the kernel.
+    pub fn set_rights(&mut self, rights: Rights) {
+        let user = rights.contains(Rights::USER);
+        let execute = rights.contains(Rights::EXECUTE);
+        self.set(EntryFlags::USER, user);
+        self.set(EntryFlags::READ_ONLY, !rights.contains(Rights::WRITE));
+        self.set(EntryFlags::PRIVILEGED_EXECUTE_NEVER, user || !execute);
+        self.set(EntryFlags::USER_EXECUTE_NEVER, !user || !execute);
+        self.set(EntryFlags::ACCESSED, true);
+    }
+
+    /// Set the flags of the entry.
+    pub fn set_flags(&mut self, flags: Flags) {
+        self.set_global(flags.contains(Flags::GLOBAL));
+        self.set_device(flags.contains(Flags::DEVICE));
+    }
+
+    /// Set or clear the valid bit of the entry. If this bit is set, the
+    /// page is mapped to a physical address. If this bit is not set, the page
+    /// is not mapped to a physical address and trying to access it will raise
+    /// an exception.
+    pub fn set_present(&mut self, present: bool) {
+        self.set(EntryFlags::VALID, present);
+    }
+
+    /// Set or clear the table bit of the entry. This bit must be set for
+    /// entries of the last level, which map pages, and cleared for leaf
+    /// entries of the other levels, which map blocks.
+    pub fn set_page(&mut self, page: bool) {
+        self.set(EntryFlags::TABLE, page);
+    }
+
+    /// Make the entry point to the table of the next level at the given
+    /// physical address.
+    pub fn set_next_table<T: Into<Frame4Kib>>(&mut self, frame: T) {
+        self.clear();
+        self.set_address(frame);
+        self.0 |= (EntryFlags::VALID | EntryFlags::TABLE).bits();
+    }
+
+    /// Set or clear the global bit of the entry. If this bit is set, the
+    /// entry will not be flushed from the TLB when changing the address
+    /// space. This bit should only be set if the page is shared between all
+    /// address spaces, otherwise it may lead to security issues or strange
+    /// bugs that will be very, very hard to debug.
+    pub fn set_global(&mut self, global: bool) {
+        self.set(EntryFlags::NOT_GLOBAL, !global);
+    }
+
+    /// Set or clear the device bit of the entry. The kernel uses one of the
+    /// bits reserved for the software to remember that the frame mapped by
+    /// the entry holds the registers of a device, and must not be freed when
+    /// unmapped. The memory attributes of the entry are also changed, since
+    /// the registers of a device must not be cached nor accessed
+    /// speculatively.
+    pub fn set_device(&mut self, device: bool) {
+        self.set(EntryFlags::DEVICE, device);
+        self.set(EntryFlags::DEVICE_MEMORY, device);
+        self.set(EntryFlags::INNER_SHAREABLE, !device);
+    }
+
+    /// Set the physical address that the entry points to. The physical address
+    /// must be properly aligned, depending on the level of the page table that
+    /// this entry is part of.
+    pub fn set_address<T: Into<Frame4Kib>>(&mut self, frame: T) {
+        self.0 &= !ADDRESS_MASK;
+        self.0 |= frame.into().inner().as_u64() & ADDRESS_MASK;
+    }
+
+    /// Check if the entry is present, meaning that the page is mapped to a
+    /// physical address. If this bit is not set, the page is not mapped to
+    /// a physical address and trying to access it will raise an exception.
+    #[must_use]
+    pub fn present(&self) -> bool {
+        self.contains(EntryFlags::VALID)
+    }
+
+    /// Check if the entry is writable, meaning that the page can be written to
+    /// by the processor. Trying to write to a page that is not writable will
+    /// raise an exception.
+    #[must_use]
+    pub fn writable(&self) -> bool {
+        !self.contains(EntryFlags::READ_ONLY)
+    }
+
+    /// Check if the entry is executable, meaning that the page can be executed
+    /// by the processor at the privilege level that can access it. Trying to
+    /// execute a page that is not executable will raise an exception.
+    #[must_use]
+    pub fn executable(&self) -> bool {
+        if self.user() {
+            !self.contains(EntryFlags::USER_EXECUTE_NEVER)
+        } else {
+            !self.contains(EntryFlags::PRIVILEGED_EXECUTE_NEVER)
+        }
+    }
+
+    /// Check if the entry is accessible by the user, meaning that it can be
+    /// accessed by the processor when running at EL0. If this bit is not set,
+    /// the entry can only be accessed by the kernel.
+    #[must_use]
+    pub fn user(&self) -> bool {
+        self.contains(EntryFlags::USER)
+    }
+
+    /// Check if the entry is global, meaning that it is not flushed from the
+    /// TLB when changing the address space.
+    #[must_use]
+    pub fn global(&self) -> bool {
+        !self.contains(EntryFlags::NOT_GLOBAL)
+    }
+
+    /// Check if the entry maps the registers of a device. See
+    /// [`Self::set_device`] for more details.
+    #[must_use]
+    pub fn device(&self) -> bool {
+        self.contains(EntryFlags::DEVICE)
+    }
+
+    /// Return the physical address that the entry points to. This method does
+    /// not check if the entry is present, and calling this method on an entry
+    /// that is not present will return incorrect results.
+    #[must_use]
+    pub fn address(&self) -> Physical {
+        // SAFETY: This is safe because the address is masked to 48 bits, so
+        // it cannot be greater than [`Physical::MAX`]
+        unsafe { Physical::new_unchecked((self.0 & ADDRESS_MASK).into_usize()) }
+    }
+
+    /// Check if the entry is a leaf entry, meaning that it points to a
+    /// physical address and not to another table. See [`Table`] for how leaf
+    /// entries are told apart from table entries.
+    #[must_use]
+    pub fn is_leaf(&self) -> bool {
+        self.contains(EntryFlags::ACCESSED)
+    }
+
+    /// Get the rights granted by the entry. The read right is implicit on
+    /// AArch64, so it is always granted.
+    #[must_use]
+    pub fn rights(&self) -> Rights {
+        let mut rights = Rights::READ;
+        rights.set(Rights::USER, self.user());
+        rights.set(Rights::WRITE, self.writable());
+        rights.set(Rights::EXECUTE, self.executable());
+        rights
+    }
+
+    /// Get the physical address that the entry points to and clear the entry. This is
+    /// equivalent to calling `address()` followed by `clear()`, but is more convenient.
+    #[must_use]
+    pub fn address_and_clear(&mut self) -> Physical {
+        let addr = self.address();
+        self.clear();
+        addr
+    }
+
+    /// Clear the entry, meaning that it does not point to a physical address
+    /// and does not have any flags set.
+    pub fn clear(&mut self) {
+        self.0 = 0;
+    }
+
+    /// Get the next table from the entry. If the entry is a leaf entry or is
+    /// not present, this method will return `None`.
+    ///
+    /// # Safety
+    /// This function assume that the entry points to a valid physical address
+    /// that will be translated to a valid virtual address that contains a
+    /// valid table.
+    ///
+    /// If the address is not valid or points to another object, the behavior
+    /// is undefined and may lead to memory corruption or data loss.
+    ///
+    /// # Panics
+    /// Panics if the physical address in the table cannot be translated to a
+    /// virtual address. This should not happen, as this would require a
+    /// machine with more than 255 GiB of RAM, which is not supported by Kiwi.
+    #[must_use]
+    pub unsafe fn next_table(&self) -> Option<&Table> {
+        if self.is_leaf() || !self.present() {
+            None
+        } else {
+            let table = translate_physical(self.address())
+                .expect("Failed to translate table physical address")
+                .as_ptr::<Table>();
+            Some(&*(table))
+        }
+    }
+
+    /// Get a mutable reference to the next table. See [`Entry::next_table`]
+    /// for more details.
+    ///
+    /// # Safety
+    /// See [`Entry::next_table`].
+    ///
+    /// # Panics
+    /// See [`Entry::next_table`].
+    #[must_use]
+    pub unsafe fn next_table_mut(&mut self) -> Option<&mut Table> {
+        if self.is_leaf() || !self.present() {
+            None
+        } else {
+            let table = translate_physical(self.address())
+                .expect("Failed to translate table physical address")
+                .as_mut_ptr::<Table>();
+            Some(&mut *(table))
+        }
+    }
+
+    /// Returns whether all the given bits are set in the entry.
+    const fn contains(self, flags: EntryFlags) -> bool {
+        self.0 & flags.bits() == flags.bits()
+    }
+
+    /// Set or clear the given bits of the entry.
+    fn set(&mut self, flags: EntryFlags, value: bool) {
+        if value {
+            self.0 |= flags.bits();
+        } else {
+            self.0 &= !flags.bits();
+        }
+    }
+}
+
+impl paging::Entry for Entry {
+    // The huge page is briefly unmapped while it is split, which is not a
+    // problem since the kernel never splits its own mappings.
+    const BREAK_BEFORE_MAKE: bool = true;
+
+    fn present(&self) -> bool {
+        Entry::present(self)
+    }
+
+    fn is_leaf(&self) -> bool {
+        Entry::is_leaf(self)
+    }
+
+    fn device(&self) -> bool {
+        Entry::device(self)
+    }
+
+    fn address(&self) -> usize {
+        usize::from(Entry::address(self))
+    }
+
+    fn clear(&mut self) {
+        Entry::clear(self);
+    }
+
+    fn set_table(&mut self, table: usize) {
+        self.set_next_table(Physical::new(table));
+    }
+
+    fn split(&self, address: usize, level: usize) -> Self {
+        let mut page = *self;
+        page.set_address(Physical::new(address));
+        page.set_page(level == 2);
+        page
+    }
+}
+
+/// The frames holding the page tables, allocated from the physical memory
+/// manager and accessed through the physical memory map of the kernel.
+#[derive(Debug, Default)]
+struct TableFrames {
+    /// Whether the walk is done in the kernel half of the address space,
+    /// where no table may be allocated once [`preallocate_kernel_tables`] was
+    /// called.
+    kernel: bool,
+}
+
+impl TableFrames {
+    /// Returns the frames used to walk the tables translating the address
+    /// with the given table indexes.
+    fn new(indexes: [usize; 3]) -> Self {
+        Self {
+            kernel: indexes[0] >= 256,
+        }
+    }
+}
+
+impl paging::Frames for TableFrames {
+    type Entry = Entry;
+
+    fn allocate(&mut self) -> Option<usize> {
+        // Once preallocated, the kernel half is entirely populated, so a
+        // table should only be allocated in the user half of the address
+        // space.
+        assert!(
+            !self.kernel || !KERNEL_TABLES_PREALLOCATED.load(Ordering::Acquire),
+            "Intermediate kernel page table allocated after boot"
+        );
+        let flags = AllocationFlags::KERNEL | AllocationFlags::ZEROED;
+        mm::phys::allocate_frame(flags).map(|frame| usize::from(Physical::from(frame)))
+    }
+
+    fn deallocate(&mut self, frame: usize, count: usize) {
+        let frame = Physical::new(frame);
+        if count == 1 {
+            mm::phys::deallocate_frame(frame);
+        } else {
+            mm::phys::deallocate_range(frame, count);
+        }
+    }
+
+    unsafe fn table<'a>(&self, address: usize) -> &'a mut [Entry; 512] {
+        let table = translate_physical(Physical::new(address))
+            .expect("Failed to translate table physical address")
+            .as_mut_ptr::<[Entry; 512]>();
+
+        // SAFETY: The caller guarantees that the address is the address of a
+        // table that is not otherwise referenced.
+        unsafe { &mut *table }
+    }
+
+    fn flush(&mut self) {
+        tlb::shootdown_all();
+    }
+}
+
+bitflags! {
+    /// The bits of a descriptor used by the kernel, for a 4 KiB granule with
+    /// the memory attributes configured by the boot code in `MAIR_EL1`.
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct EntryFlags: u64 {
+        /// The entry is valid.
+        const VALID = 1 << 0;
+
+        /// The entry points to a table, or maps a page at the last level.
+        const TABLE = 1 << 1;
+
+        /// The entry uses the second memory attribute of `MAIR_EL1`, which
+        /// is the non-cacheable device memory. The first one is the normal
+        /// write-back cacheable memory.
+        const DEVICE_MEMORY = 1 << 2;
+
+        /// The entry is accessible by user space (`AP[1]`).
+        const USER = 1 << 6;
+
+        /// The entry is read-only (`AP[2]`).
+        const READ_ONLY = 1 << 7;
+
+        /// The memory mapped by the entry is shared by all the CPUs. This is
+        /// ignored for device memory.
+        const INNER_SHAREABLE = 3 << 8;
+
+        /// The access flag. Accessing a page whose access flag is not set
+        /// raises an exception, so the kernel always sets it in leaf entries.
+        const ACCESSED = 1 << 10;
+
+        /// The entry is specific to the current address space and must be
+        /// flushed from the TLB when changing the address space. Contrary to
+        /// RISC-V, entries are global unless this bit is set.
+        const NOT_GLOBAL = 1 << 11;
+
+        /// The kernel cannot execute code from the entry.
+        const PRIVILEGED_EXECUTE_NEVER = 1 << 53;
+
+        /// User space cannot execute code from the entry.
+        const USER_EXECUTE_NEVER = 1 << 54;
+
+        /// The entry maps the registers of a device. This is one of the four
+        /// bits reserved for the software, and is ignored by the processor.
+        const DEVICE = 1 << 55;
+    }
+}
+
+/// Setup the MMU. This will create a kernel page table that maps the first
+/// 255 GiB of physical memory at the start of the kernel half of the address
+/// space, remaps the kernel image with the rights of its sections, and
+/// switches to this table.
+///
+/// The parts of the physical memory map that are not RAM are mapped as device
+/// memory: on AArch64, the registers of devices must not be mapped as normal
+/// memory, even if the kernel never accesses them through this mapping, since
+/// the processor may access normal memory speculatively.
+///
+/// # Panics
+/// This function should never panic. If it does, it means that there is a
+/// bug in the MMU implementation.
+pub fn setup(memory: &UsableMemory) {
+    log::info!("Initializing the MMU and remapping the kernel");
+    log::debug!("User address space :   0x0000000000000000 - 0x0000003FFFFFFFFF");
+    log::debug!("Kernel address space : 0xFFFFFFC000000000 - 0xFFFFFFFFFFFFFFFF");
+    log::debug!("Kernel heap :          0xFFFFFFFF80000000 - 0xFFFFFFFFBFFFFFFF");
+    log::debug!("Kernel image slide :   {:#x}", kernel_slide());
+
+    let mut table = KERNEL_TABLE
+        .call_once(|| lock::Mutex::new(RootTable::empty()))
+        .lock();
+
+    // Map the first 255 GiB of physical memory to the first 255 GiB
+    // of virtual memory in the kernel's address space. This will allow
+    // the kernel to access any physical address easily without having
+    // to manually map each page. The kernel never executes code through
+    // this mapping, so it is not executable.
+    for (i, entry) in table.kernel_space_mut().iter_mut().enumerate() {
+        let start = i * Frame1Gib::SIZE;
+        let ram = start < memory.ram_end && start + Frame1Gib::SIZE > memory.ram_start;
+        entry.set_address(Frame1Gib::from_index(i));
+        entry.set_rights(Rights::RW);
+        entry.set_flags(if ram {
+            Flags::GLOBAL
+        } else {
+            Flags::GLOBAL | Flags::DEVICE
+        });
+        entry.set_present(true);
+    }
+
+    // Map the kernel image to the last 1 GiB of virtual memory, using 4 KiB
+    // pages so that each section is mapped with its own rights: code is never
+    // writable, and data is never executable. The rest of this range, which
+    // includes the memory used by the firmware and the guard page below the
+    // boot stack, is left unmapped so that stray accesses fault.
+    //
+    // Together with the loop above and the kernel heap entry below, this
+    // populates every top-level entry of the kernel half. As a consequence,
+    // the kernel half never needs a top-level entry after boot, and the kernel
+    // table given to `TTBR1_EL1` never changes after boot.
+    //
+    // SAFETY: The kernel image tables are only accessed through the kernel
+    // table, whose lock is held.
+    let (image, pages) = unsafe {
+        let image = &raw mut KERNEL_IMAGE_TABLE;
+        let pages = &raw mut KERNEL_IMAGE_PAGES;
+        (&mut *image, &mut *pages)
+    };
+    let sections = kernel_sections();
+    let first = (sections[0].0.start - usize::from(KERNEL_VIRTUAL_BASE)) / Frame2Mib::SIZE;
+    for (range, rights) in sections {
+        for page in range.step_by(PAGE_SIZE) {
+            let offset = page - usize::from(KERNEL_VIRTUAL_BASE);
+            let region = offset / Frame2Mib::SIZE;
+            let pages = pages
+                .get_mut(region - first)
+                .expect("The kernel image is too large to be mapped");
+
+            let entry = &mut image[region];
+            if !entry.present() {
+                entry.set_next_table(translate_kernel_ptr(&raw const *pages));
+            }
+
+            let entry = &mut pages[(offset / PAGE_SIZE) % 512];
+            *entry = Entry::new(Frame4Kib::new(translate_virtual_kernel(
+                Virtual::<Kernel>::new(page),
+            )));
+            entry.set_rights(rights);
+            entry.set_flags(Flags::GLOBAL);
+            entry.set_page(true);
+            entry.set_present(true);
+        }
+    }
+    table
+        .last_kernel_entry_mut()
+        .set_next_table(translate_kernel_ptr(&raw const KERNEL_IMAGE_TABLE));
+
+    // Reserve the 1 GiB below the kernel for the kernel heap. Its entry points
+    // to a table instead of a 1 GiB block, so that pages can be mapped in
+    // this range after boot. Since all address spaces share this table, pages
+    // mapped in the heap are visible in all address spaces.
+    table.kernel_space_mut()[KERNEL_HEAP_ENTRY]
+        .set_next_table(translate_kernel_ptr(&raw const KERNEL_HEAP_TABLE));
+
+    // Give the kernel half of the table to `TTBR1_EL1`, and use the table as
+    // the user table until the first thread runs. The kernel image is mapped
+    // at the same addresses by the boot table and by this table, so the
+    // kernel can continue its execution after the switch. The whole TLB is
+    // flushed since it still holds the translations of the boot table.
+    //
+    // SAFETY: The kernel table was properly initialized and will not cause a
+    // page fault when set as the current page table. The kernel table lives
+    // in a static, so its kernel half stays valid while it is used.
+    unsafe {
+        let kernel = translate_kernel_ptr(&raw const *table).as_usize();
+        core::arch::asm!(
+            "dsb ishst",
+            "msr ttbr1_el1, {}",
+            "isb",
+            in(reg) kernel + 256 * core::mem::size_of::<Entry>(),
+            options(nostack)
+        );
+        table.set_current();
+    }
+    tlb::flush_all();
+
+    // Forbid the kernel from accessing user pages by default if the processor
+    // implements Privileged Access Never (`ID_AA64MMFR1_EL1.PAN`).
+    let features: usize;
+    // SAFETY: Reading the `ID_AA64MMFR1_EL1` register has no side effects.
+    unsafe {
+        core::arch::asm!("mrs {}, id_aa64mmfr1_el1", out(reg) features);
+    }
+    // The `SPAN` bit of `SCTLR_EL1` is also cleared, so that the processor
+    // forbids the access on each exception taken to the kernel.
+    if (features >> 20) & 0xF != 0 {
+        PAN.store(true, Ordering::Relaxed);
+        // SAFETY: Clearing the `SPAN` bit only changes the value of the PAN
+        // bit on exception entry.
+        unsafe {
+            core::arch::asm!(
+                "mrs {0}, sctlr_el1",
+                "bic {0}, {0}, #(1 << 23)",
+                "msr sctlr_el1, {0}",
+                "isb",
+                out(reg) _,
+                options(nostack)
+            );
+        }
+        forbid_user_page_access();
+    }
+    log::debug!(
+        "Privileged Access Never {}",
+        if PAN.load(Ordering::Relaxed) {
+            "enabled"
+        } else {
+            "not supported"
+        }
+    );
+}
+
+/// Return the ranges of virtual addresses of the sections of the kernel image
+/// along with the rights they must be mapped with, using the boundaries given
+/// by the linker script. Each range is page aligned.
+fn kernel_sections() -> [(core::ops::Range<usize>, Rights); 7] {
+    let range = |start: *const [u8; 0], end: *const [u8; 0]| start.addr()..end.addr();
+    [
+        // The boot code and the boot page table, which are no longer used
+        // once the kernel table is set up but are reclaimed after boot.
+        (
+            range(&raw const __reclaimable_start, &raw const __init_start),
+            Rights::READ,
+        ),
+        (
+            range(&raw const __init_start, &raw const __init_data_start),
+            Rights::RX,
+        ),
+        (
+            range(&raw const __init_data_start, &raw const __init_end),
+            Rights::READ,
+        ),
+        (
+            range(&raw const __text_start, &raw const __text_end),
+            Rights::RX,
+        ),
+        (
+            range(&raw const __rodata_start, &raw const __rodata_end),
+            Rights::READ,
+        ),
+        (
+            range(&raw const __data_start, &raw const __data_end),
+            Rights::RW,
+        ),
+        (
+            range(&raw const boot_stack_bottom, &raw const __end),
+            Rights::RW,
+        ),
+    ]
+}
+
+/// Map a physical address to a virtual address.
+///
+/// # Errors
+/// This function will return an error if any of the following conditions
+/// are met:
+/// - The virtual address is already mapped to a physical address.
+/// - Both [`Flags::HUGE_2MB`] and [`Flags::HUGE_1GB`] are given.
+/// - The frame or the virtual address is not aligned to the size of the page
+///   requested by the flags.
+/// - An intermediate table is missing and the kernel is unable to
+///   allocate a new table. For the kernel half, this can only happen before
+///   [`preallocate_kernel_tables`] is called, which never happens if
+///   [`crate::config::PREALLOCATE_KERNEL_TABLES`] is disabled.
+///
+/// # Panics
+/// Panics if an error occurs while traversing the page table, or if an
+/// intermediate table of the kernel half is missing after the kernel tables
+/// were preallocated. This should never happen, as the page table should be
+/// properly initialized.
+///
+/// # Safety
+/// This function is unsafe because mapping a physical address to a virtual
+/// address can lead to many issues if the caller is not careful. For example,
+/// this can lead to multiple mutable references to the same physical address
+/// if the caller maps the same physical address to multiple virtual addresses.
+/// This is not a problem by itself and can be very useful in some cases, but
+/// it can also create memory safety issues if the caller is not careful (e.g.,
+/// if the caller creates multiple mutable references to the same physical
+/// address and then writes to one of them, the other references will see
+/// the modified data, which can lead to undefined behavior).
+pub unsafe fn map<T: addr::virt::Type>(
+    root: &mut RootTable,
+    virt: Virtual<T>,
+    frame: Frame4Kib,
+    rights: Rights,
+    flags: Flags,
+) -> Result<(), MapError> {
+    // Find the level of the leaf entry from the size of the page requested,
+    // and verify that both the frame and the virtual address are aligned to
+    // this size.
+    let level = match (
+        flags.contains(Flags::HUGE_1GB),
+        flags.contains(Flags::HUGE_2MB),
+    ) {
+        (false, false) => 2,
+        (false, true) => 1,
+        (true, false) => 0,
+        (true, true) => return Err(MapError::InvalidFlagsCombination),
+    };
+    let size = paging::leaf_size(level);
+    if !usize::from(frame.into_inner()).is_multiple_of(size)
+        || !virt.as_usize().is_multiple_of(size)
+    {
+        return Err(MapError::FrameNotAligned);
+    }
+
+    // Walk the tables down to the level of the leaf, allocating the missing
+    // intermediate tables. The walk fails if the address is already mapped,
+    // to allow the caller to handle the situation properly instead of
+    // overwriting the existing mapping.
+    let indexes = virt.table_indexes();
+    let entry = paging::map(
+        &mut TableFrames::new(indexes),
+        &mut root.address_space_mut().0,
+        indexes,
+        level,
+    )?;
+
+    // Update the entry with the physical address, rights and flags given.
+    // We do not need to flush the TLB here, as the page was not previously
+    // mapped and the TLB does not contain entries for unmapped pages, but the
+    // update must be visible to the table walker before the page is used.
+    entry.set_address(frame);
+    entry.set_rights(rights);
+    entry.set_flags(flags);
+    entry.set_page(level == 2);
+    entry.set_present(true);
+    // SAFETY: Barriers cannot cause undefined behavior.
+    unsafe {
+        core::arch::asm!("dsb ishst", "isb", options(nostack));
+    }
+    Ok(())
+}
+
+/// Map a frame in the kernel half of the address space, using the kernel
+/// table. Since the kernel half is shared by all address spaces, the page is
+/// visible in all address spaces.
+///
+/// # Errors
+/// See [`map`] for the list of errors that can happen.
+///
+/// # Panics
+/// Panics if the kernel table is not initialized.
+///
+/// # Safety
+/// See [`map`]. In addition, the caller must ensure that the virtual address
+/// is not in a range mapped with 1 GiB leaves during boot, such as the kernel
+/// image or the physical memory map.
+pub unsafe fn map_kernel(
+    virt: Virtual<Kernel>,
+    frame: Frame4Kib,
+    rights: Rights,
+    flags: Flags,
+) -> Result<(), MapError> {
+    map(
+        &mut KERNEL_TABLE.get().unwrap().lock(),
+        virt,
+        frame,
+        rights,
+        flags,
+    )
+}
+
+/// Unmap a page from the kernel half of the address space, using the kernel
+/// table, and return the frame that was mapped to it.
+///
+/// # Errors
+/// See [`unmap`] for the list of errors that can happen.
+///
+/// # Panics
+/// Panics if the kernel table is not initialized.
+///
+/// # Safety
+/// See [`unmap`]. The caller must ensure that the page is no longer used by
+/// the kernel.
+pub unsafe fn unmap_kernel(virt: Virtual<Kernel>) -> Result<Frame4Kib, UnmapError> {
+    unmap(&mut KERNEL_TABLE.get().unwrap().lock(), virt)
+}
+
+/// Allocate the missing tables of the kernel heap range, so that all the
+/// intermediate tables of the kernel half exist: the top-level entries are
+/// already populated by [`setup`], and the tables of the kernel half are
+/// never freed. After this, mapping a page in the kernel half never allocates
+/// a table. Returns the number of tables allocated.
+///
+/// # Panics
+/// Panics if the kernel table is not initialized, or if the kernel runs out
+/// of memory while allocating the tables.
+#[must_use]
+pub fn preallocate_kernel_tables() -> usize {
+    let _table = KERNEL_TABLE.get().unwrap().lock();
+
+    // SAFETY: The heap table is only accessed while holding the lock of the
+    // kernel table.
+    let heap = &raw mut KERNEL_HEAP_TABLE;
+    let heap = unsafe { &mut *heap };
+    let mut allocated = 0;
+    for index in 0..512 {
+        let entry = &mut heap[index];
+        if entry.present() {
+            continue;
+        }
+
+        let flags = AllocationFlags::KERNEL | AllocationFlags::ZEROED;
+        let frame =
+            mm::phys::allocate_frame(flags).expect("Failed to allocate a kernel page table");
+        entry.set_next_table(frame);
+        allocated += 1;
+    }
+
+    KERNEL_TABLES_PREALLOCATED.store(true, Ordering::Release);
+    allocated
+}
+
+/// Get the rights granted to the page mapped at the given virtual address, by
+/// walking the given page table. Returns `None` if the address is not mapped.
+/// Pages mapped with a larger frame size (2 MiB or 1 GiB) are supported.
+#[must_use]
+pub fn rights<T: addr::virt::Type>(root: &RootTable, virt: Virtual<T>) -> Option<Rights> {
+    translate(root, virt).map(|(_, rights)| rights)
+}
+
+/// Translate the given virtual address by walking the given page table, and
+/// return the 4 KiB frame containing the address along with the rights granted
+/// to the page. Returns `None` if the address is not mapped. Pages mapped with
+/// a larger frame size (2 MiB or 1 GiB) are supported.
+#[must_use]
+pub fn translate<T: addr::virt::Type>(
+    root: &RootTable,
+    virt: Virtual<T>,
+) -> Option<(Frame4Kib, Rights)> {
+    let (entry, level) = leaf(root, virt)?;
+    let offset = (virt.as_usize() % paging::leaf_size(level)) & !(PAGE_SIZE - 1);
+    let frame = Frame4Kib::new(Physical::new(usize::from(entry.address()) + offset));
+    Some((frame, entry.rights()))
+}
+
+/// Return whether the page mapped at the given virtual address maps the
+/// registers of a device (see [`Flags::DEVICE`]). Returns `false` if the
+/// address is not mapped.
+#[must_use]
+pub fn is_device<T: addr::virt::Type>(root: &RootTable, virt: Virtual<T>) -> bool {
+    leaf(root, virt).is_some_and(|(entry, _)| entry.device())
+}
+
+/// Walk the given page table and return the leaf entry mapping the given
+/// virtual address, along with the level of the table containing it. Returns
+/// `None` if the address is not mapped. Contrary to [`leaf_mut`], huge pages
+/// are not split.
+fn leaf<T: addr::virt::Type>(root: &RootTable, virt: Virtual<T>) -> Option<(&Entry, usize)> {
+    let indexes = virt.table_indexes();
+    paging::leaf(&TableFrames::new(indexes), &root.address_space().0, indexes)
+}
+
+/// Change the rights of the page mapped at the given virtual address. If the
+/// page is part of a huge page, the huge page is first split so that only the
+/// requested page is affected.
+///
+/// # Errors
+/// Returns [`UnmapError::NotMapped`] if the address is not mapped, or
+/// [`UnmapError::OutOfMemory`] if a huge page could not be split.
+///
+/// # Safety
+/// The caller must ensure that removing rights from a page that is in use is
+/// properly handled, for example by the page fault handler.
+pub unsafe fn protect<T: addr::virt::Type>(
+    root: &mut RootTable,
+    virt: Virtual<T>,
+    rights: Rights,
+) -> Result<(), UnmapError> {
+    let entry = leaf_mut(root, virt)?;
+    entry.set_rights(rights);
+    tlb::shootdown_page(virt.as_usize());
+    Ok(())
+}
+
+/// Unmap a virtual address, returning the physical address that was previously
+/// mapped to it.
+///
+/// # Errors
+/// This function will return an error if the virtual address is not mapped to
+/// a physical address.
+///
+/// # Panics
+/// Panics if an error occurs while traversing the page table. This should
+/// never happen, as the page table should be properly initialized.
+///
+/// # Safety
+/// This function is unsafe because unmapping a virtual address can lead to
+/// many issues if the caller is not careful. For example, if the caller
+/// unmaps a virtual address that is still in use, ugly bugs may occur when
+/// that address is accessed again. The caller must ensure that the virtual
+/// address being unmapped is no longer in use by any part of the system or
+/// that the page fault handler can properly handle the resulting page fault.
+pub unsafe fn unmap<T: addr::virt::Type>(
+    root: &mut RootTable,
+    virt: Virtual<T>,
+) -> Result<Frame4Kib, UnmapError> {
+    let entry = leaf_mut(root, virt)?;
+
+    // Get the physical address that was previously mapped to the given virtual
+    // address, unmap it, and invalidate the translation on all CPUs before
+    // returning the physical address, so that the frame can safely be reused.
+    let address = entry.address_and_clear();
+    tlb::shootdown_page(virt.as_usize());
+
+    // Free the intermediate tables of the user space that became empty. The
+    // kernel space tables are shared by all address spaces and are never
+    // freed.
+    let indexes = virt.table_indexes();
+    if indexes[0] < 256 {
+        let frames = &mut TableFrames::new(indexes);
+        paging::prune(frames, &mut root.address_space_mut().0, indexes);
+    }
+    Ok(Frame4Kib::new_unchecked(address))
+}
+
+/// Walk the given page table and return the 4 KiB leaf entry mapping the given
+/// virtual address. If the address is mapped by a huge page, the huge page is
+/// split into smaller pages so that the returned entry only maps the page
+/// containing the address.
+///
+/// # Errors
+/// Returns [`UnmapError::NotMapped`] if the address is not mapped, or
+/// [`UnmapError::OutOfMemory`] if a huge page could not be split.
+fn leaf_mut<T: addr::virt::Type>(
+    root: &mut RootTable,
+    virt: Virtual<T>,
+) -> Result<&mut Entry, UnmapError> {
+    let indexes = virt.table_indexes();
+    let frames = &mut TableFrames::new(indexes);
+    Ok(paging::leaf_mut(
+        frames,
+        &mut root.address_space_mut().0,
+        indexes,
+    )?)
+}
+
+/// The number of parts in which the user space can be released with
+/// [`release_user_space_part`]. Each part corresponds to an entry of the
+/// root table, covering 1 GiB of virtual memory.
+pub const USER_SPACE_PARTS: usize = 256;
+
+/// Release a part of the user space of the given table, freeing all the
+/// tables and frames mapped in it. This allows the user space of a table to
+/// be released incrementally, instead of in a single long operation when the
+/// table is dropped. Returns the number of frames released, including the
+/// frames used by the intermediate tables.
+///
+/// # Panics
+/// Panics if `part` is not lower than [`USER_SPACE_PARTS`].
+///
+/// # Safety
+/// The caller must ensure that the memory mapped in the given part of the
+/// user space is no longer in use. The kernel table is set as the current
+/// page table to ensure that the given table is not in use while it is being
+/// modified.
+pub unsafe fn release_user_space_part(root: &mut RootTable, part: usize) -> usize {
+    unsafe {
+        use_kernel_table();
+        paging::release(
+            &mut TableFrames::default(),
+            &mut root.user_space_mut()[part..=part],
+            0,
+        )
+    }
+}
+
+/// Use the kernel page table as the current page table. This will switch the
+/// current address space to a table only containing the kernel mappings. This
+/// is useful when destroying a user process, to avoid using a page table that
+/// is destroyed, thus leading to undefined behavior.
+///
+/// # Safety
+/// The caller must ensure that the kernel page table is properly initialized
+/// before calling this function, and must also ensure that no user space mappings
+/// will be accessed while the kernel page table is in use.
+///
+/// # Panics
+/// This function will panic if the kernel page table is not initialized.
+pub unsafe fn use_kernel_table() {
+    KERNEL_TABLE.get().unwrap().lock().set_current();
+}
+
+/// Allow the kernel to access user pages by clearing the PAN bit of the
+/// `PSTATE` register, if the processor implements Privileged Access Never.
+/// This is useful when the kernel needs to access user pages, for example
+/// when copying data between user and kernel space.
+pub fn allow_user_page_access() {
+    if PAN.load(Ordering::Relaxed) {
+        // SAFETY: Allowing the kernel to access user pages cannot cause
+        // undefined behavior by itself. The register is written with its
+        // generic name since the assembler may not know about the extension.
+        unsafe {
+            core::arch::asm!("msr s3_0_c4_c2_3, xzr", options(nostack));
+        }
+    }
+}
+
+/// Forbid the kernel from accessing user pages by setting the PAN bit of the
+/// `PSTATE` register, if the processor implements Privileged Access Never.
+/// This is useful to prevent the kernel from accidentally accessing user
+/// pages, which can lead to security issues. The processor also sets this
+/// bit on each exception taken to the kernel.
+pub fn forbid_user_page_access() {
+    if PAN.load(Ordering::Relaxed) {
+        // SAFETY: Forbidding the kernel from accessing user pages cannot
+        // cause undefined behavior, the kernel will simply fault if it tries.
+        unsafe {
+            core::arch::asm!("msr s3_0_c4_c2_3, {}", in(reg) 1usize << 22, options(nostack));
+        }
+    }
+}
+
+/// Make the instructions written at the given kernel address visible to
+/// instruction fetches. The instruction cache is not coherent with the data
+/// cache on AArch64, so the data cache lines holding the code are cleaned to
+/// the point of unification and the whole instruction cache is invalidated,
+/// which also covers instruction caches that are not physically indexed.
+pub fn sync_instructions(start: Virtual<Kernel>, len: usize) {
+    let ctr: usize;
+    // SAFETY: Reading the `CTR_EL0` register has no side effects.
+    unsafe {
+        core::arch::asm!("mrs {}, ctr_el0", out(reg) ctr);
+    }
+
+    let line = 4 << ((ctr >> 16) & 0xF);
+    let start = start.as_usize();
+    for address in (start & !(line - 1)..start + len).step_by(line) {
+        // SAFETY: Cleaning a cache line cannot cause undefined behavior, and
+        // the address is mapped since the caller just wrote to it.
+        unsafe {
+            core::arch::asm!("dc cvau, {}", in(reg) address, options(nostack));
+        }
+    }
+
+    // SAFETY: Invalidating the instruction cache cannot cause undefined
+    // behavior, instructions will simply be fetched again from memory.
+    unsafe {
+        core::arch::asm!("dsb ish", "ic ialluis", "dsb ish", "isb", options(nostack));
+    }
+}
+
+/// Translate a physical address to a virtual address. If the translation
+/// cannot be done because the physical address is greater than the maximum
+/// virtual address representable by the system, this function will return
+/// `None`.
+#[must_use]
+pub fn translate_physical(phys: impl Into<Physical>) -> Option<Virtual<Kernel>> {
+    Some(Virtual::<Kernel>::new(
+        usize::from(KERNEL_START) + usize::from(phys.into()),
+    ))
+}
+
+/// Return the address where the kernel can access the registers of a device,
+/// in the `size` bytes starting at the given physical address. They are
+/// accessed through the physical memory map, where the parts of the physical
+/// address space that are not RAM are mapped with the `Device-nGnRnE` memory
+/// type (see [`setup`]).
+///
+/// Returns `None` if the range is empty, or if part of it is mapped as normal
+/// memory: accesses to the registers of a device must not be cached, merged
+/// or reordered like accesses to RAM may be.
+#[must_use]
+pub fn map_mmio(base: Physical, size: usize) -> Option<Virtual<Kernel>> {
+    let start = translate_physical(base)?;
+    let last = usize::from(start).checked_add(size.checked_sub(1)?)?;
+    let table = KERNEL_TABLE.get()?.lock();
+    (usize::from(start)..=last)
+        .step_by(Frame1Gib::SIZE)
+        .chain(core::iter::once(last))
+        .all(|address| is_device(&table, Virtual::<Kernel>::new(address)))
+        .then_some(start)
+}
+
+/// Translate a virtual address in the kernel's address space to a physical
+/// address.
+///
+/// # Panics
+/// Panics if the virtual address is not located in the kernel's address space,
+/// i.e. if it is not greater than or equal to `KERNEL_START`, or if it is in
+/// the kernel heap range but is not mapped.
+#[must_use]
+pub fn translate_virtual_kernel(virt: Virtual<Kernel>) -> Physical {
+    let heap = usize::from(KERNEL_HEAP_START)..usize::from(KERNEL_HEAP_START) + KERNEL_HEAP_SIZE;
+    if heap.contains(&usize::from(virt)) {
+        translate_heap(virt)
+    } else if virt >= KERNEL_VIRTUAL_BASE {
+        Physical::new(
+            usize::from(virt) - usize::from(KERNEL_VIRTUAL_BASE) - kernel_slide()
+                + KERNEL_PHYSICAL_BASE.as_usize(),
+        )
+    } else {
+        Physical::new(usize::from(virt) - usize::from(KERNEL_START))
+    }
+}
+
+/// Return the offset between the address where the kernel image is mapped
+/// and the address where it was linked. This must be subtracted from the
+/// addresses printed in backtraces to find them in the kernel binary.
+#[must_use]
+pub fn kernel_slide() -> usize {
+    KERNEL_SLIDE.load(Ordering::Relaxed)
+}
+
+/// Record the slide applied to the kernel image by the boot code.
+///
+/// # Safety
+/// This must be called once, at the very start of the boot process, with the
+/// slide actually applied to the kernel image by the boot code.
+pub unsafe fn set_kernel_slide(slide: usize) {
+    KERNEL_SLIDE.store(slide, Ordering::Relaxed);
+}
+
+/// Translate a virtual address in the kernel heap range to a physical address.
+/// Contrary to the rest of the kernel space, the heap is not linearly mapped,
+/// so the translation requires walking the heap table.
+///
+/// # Panics
+/// Panics if the address is not mapped.
+fn translate_heap(virt: Virtual<Kernel>) -> Physical {
+    let vpn = virt.table_indexes();
+
+    // SAFETY: The heap table is only modified while holding the lock of the
+    // kernel table, and the entries used to translate a mapped address are
+    // not modified until the address is unmapped.
+    let table = unsafe { (&raw const KERNEL_HEAP_TABLE).as_ref().unwrap_unchecked() };
+    let entry = unsafe { table[vpn[1]].next_table() }
+        .map(|table| table[vpn[2]])
+        .filter(Entry::present)
+        .expect("Kernel heap address not mapped");
+    Physical::new(usize::from(entry.address()) + usize::from(virt) % PAGE_SIZE)
+}
+
+/// Translate a kernel pointer to a physical address.
+///
+/// # Panics
+/// Panics if the pointer is not located in the kernel's address space,
+/// i.e. if it is not greater than or equal to `KERNEL_VIRTUAL_BASE`.
+#[must_use]
+pub fn translate_kernel_ptr<T>(ptr: *const T) -> Physical {
+    translate_virtual_kernel(Virtual::<Kernel>::new(ptr.addr()))
+}
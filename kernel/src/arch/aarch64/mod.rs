@@ -0,0 +1,116 @@
+use crate::arch::{generic, memory::UsableMemory};
+use macros::init;
+
+pub mod addr;
+pub mod console;
+pub mod cpu;
+pub mod device;
+pub mod irq;
+pub mod log;
+pub mod memory;
+pub mod mmu;
+pub mod psci;
+pub mod semihosting;
+pub mod thread;
+pub mod timer;
+pub mod tlb;
+pub mod trap;
+pub mod uart;
+pub mod user;
+
+mod lang;
+
+/// Setup the aarch64 architecture
+///
+/// # Safety
+/// This function is unsafe because this function should only be called once
+/// and during the boot process. During this time, we must assume some
+/// invariants that we cannot guarantee in safe code.
+///
+/// # Panics
+/// This function panics if the device tree is invalid or if the memory
+/// regions are invalid.
+#[must_use]
+#[init]
+pub unsafe fn setup(cpu: usize, device_tree: usize) -> UsableMemory {
+    #[cfg(feature = "logging")]
+    generic::log::setup();
+
+    ::log::info!("Booting the aarch64 kernel");
+    ::log::info!("Hello world ! Booting on cpu {:#x}", cpu);
+
+    // Parse the device tree using the `fdt` crate
+    // SAFETY: We must assume that the device tree pointer is valid
+    let fdt = unsafe {
+        fdt::Fdt::from_ptr(core::ptr::with_exposed_provenance(device_tree))
+            .expect("Failed to parse the device tree")
+    };
+    crate::cmdline::setup(bootargs(&fdt));
+    generic::log::configure();
+
+    let mut memory = UsableMemory::new(&fdt);
+    // SAFETY: The device tree was given by the bootloader, and is accessed
+    // through the physical memory map.
+    unsafe { crate::devicetree::setup(&mut memory, device_tree) };
+    crate::random::seed(random_seed(&fdt));
+
+    mmu::setup(&memory);
+    psci::setup(&fdt);
+    uart::setup(&fdt);
+    generic::log::ready();
+    device::setup(&fdt);
+    irq::setup(&fdt, cpu);
+    trap::setup();
+    timer::setup(&fdt);
+
+    memory
+}
+
+/// Find the kernel command line given by the bootloader in the `bootargs`
+/// property of the `/chosen` node of the device tree. Returns an empty command
+/// line if the bootloader did not provide one.
+fn bootargs<'a>(device_tree: &fdt::Fdt<'a>) -> &'a [u8] {
+    device_tree
+        .find_node("/chosen")
+        .and_then(|chosen| chosen.property("bootargs"))
+        .map_or(&[], |property| property.value)
+}
+
+/// Find the random seed given by the bootloader in the device tree, in the
+/// `rng-seed` property of the `/chosen` node set by QEMU and U-Boot, or in the
+/// `kaslr-seed` property used for the same purpose by older bootloaders.
+/// Returns an empty seed if the bootloader did not provide one.
+fn random_seed<'a>(device_tree: &fdt::Fdt<'a>) -> &'a [u8] {
+    device_tree
+        .find_node("/chosen")
+        .and_then(|chosen| {
+            chosen
+                .property("rng-seed")
+                .or_else(|| chosen.property("kaslr-seed"))
+        })
+        .map_or(&[], |property| property.value)
+}
+
+/// Shutdown the computer
+#[inline]
+pub fn shutdown() -> ! {
+    psci::system_off()
+}
+
+/// Shutdown the computer, reporting whether the integration tests passed to
+/// QEMU through semihosting so that it exits with a matching status. QEMU
+/// must be started with the `-semihosting` option.
+#[inline]
+pub fn exit(success: bool) -> ! {
+    semihosting::exit(u32::from(!success))
+}
+
+/// Reboot the computer. If for some reason the PSCI call fails, we will just
+/// perform a shutdown instead.
+#[inline]
+pub fn reboot() -> ! {
+    ::log::info!("Rebooting the computer");
+    psci::system_reset();
+    ::log::warn!("Failed to reboot the computer, trying to shutdown instead");
+    psci::system_off()
+}
@@ -0,0 +1,79 @@
+//! Power State Coordination Interface, used to power off and reset the
+//! machine. The firmware (or the hypervisor, or QEMU itself) implements PSCI
+//! and is called through the instruction given by the `method` property of
+//! the `/psci` node of the device tree.
+
+/// The function identifier of the `SYSTEM_OFF` call.
+const SYSTEM_OFF: u32 = 0x8400_0008;
+
+/// The function identifier of the `SYSTEM_RESET` call.
+const SYSTEM_RESET: u32 = 0x8400_0009;
+
+/// The instruction used to call the PSCI implementation, found in the device
+/// tree. Left uninitialized if the device tree does not describe PSCI.
+static CONDUIT: spin::Once<Conduit> = spin::Once::new();
+
+/// The instruction used to call the PSCI implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Conduit {
+    /// PSCI is implemented by a hypervisor at EL2.
+    Hvc,
+
+    /// PSCI is implemented by the secure firmware at EL3.
+    Smc,
+}
+
+/// Find how to call the PSCI implementation in the device tree. Without it,
+/// the kernel cannot power off the machine and freezes instead.
+pub fn setup(device_tree: &fdt::Fdt) {
+    let method = device_tree
+        .find_node("/psci")
+        .and_then(|node| node.property("method"))
+        .and_then(fdt::node::NodeProperty::as_str);
+
+    match method {
+        Some("hvc") => _ = CONDUIT.call_once(|| Conduit::Hvc),
+        Some("smc") => _ = CONDUIT.call_once(|| Conduit::Smc),
+        _ => log::warn!("No PSCI implementation found, the machine cannot be powered off"),
+    }
+}
+
+/// Call the given PSCI function without arguments. Returns only if the call
+/// failed or if there is no PSCI implementation.
+fn call(function: u32) {
+    match CONDUIT.get() {
+        // SAFETY: The PSCI functions called by the kernel do not return on
+        // success, and only clobber the registers that the SMC calling
+        // convention allows them to clobber.
+        Some(Conduit::Hvc) => unsafe {
+            core::arch::asm!(
+                "hvc #0",
+                inout("x0") u64::from(function) => _,
+                out("x1") _, out("x2") _, out("x3") _,
+                options(nostack)
+            );
+        },
+        // SAFETY: See above.
+        Some(Conduit::Smc) => unsafe {
+            core::arch::asm!(
+                "smc #0",
+                inout("x0") u64::from(function) => _,
+                out("x1") _, out("x2") _, out("x3") _,
+                options(nostack)
+            );
+        },
+        None => {}
+    }
+}
+
+/// Power off the machine. If the call fails, the CPU is frozen instead.
+pub fn system_off() -> ! {
+    super::irq::disable();
+    call(SYSTEM_OFF);
+    super::cpu::freeze()
+}
+
+/// Reset the machine. This function only returns if the reset failed.
+pub fn system_reset() {
+    call(SYSTEM_RESET);
+}
@@ -0,0 +1,30 @@
+//! Arm semihosting, used to ask the emulator to exit with a given status. The
+//! semihosting calls are made with the `hlt #0xf000` instruction, which is
+//! only trapped by QEMU when it is started with the `-semihosting` option:
+//! otherwise, it raises an undefined instruction exception.
+
+/// The identifier of the `SYS_EXIT` semihosting call.
+const SYS_EXIT: u64 = 0x18;
+
+/// The reason given to `SYS_EXIT` when the application exited by itself. The
+/// exit status is then given as the subcode of the call.
+const APPLICATION_EXIT: u64 = 0x2_0026;
+
+/// Asks the emulator to exit with the given status. If the call fails, the
+/// CPU is frozen instead.
+pub fn exit(status: u32) -> ! {
+    super::irq::disable();
+    let block = [APPLICATION_EXIT, u64::from(status)];
+
+    // SAFETY: The parameter block is valid for reads during the call, and the
+    // call does not return when semihosting is enabled.
+    unsafe {
+        core::arch::asm!(
+            "hlt #0xf000",
+            inout("x0") SYS_EXIT => _,
+            in("x1") block.as_ptr(),
+            options(nostack, readonly)
+        );
+    }
+    super::cpu::freeze()
+}
@@ -0,0 +1,298 @@
+use super::trap::{self, Exception, Kind};
+use crate::{arch::trap::Trap, mm::space::AddressSpace, utils::lock};
+use ::syscall::task::Registers;
+use alloc::{boxed::Box, sync::Arc};
+
+/// Threads can be single-stepped with the software step mechanism of the
+/// processor.
+pub const SINGLE_STEP: bool = true;
+
+core::arch::global_asm!(include_str!("asm/thread.asm"));
+
+unsafe extern "C" {
+    fn thread_execute(context: &mut trap::Context);
+    fn thread_save_fpu(fpu: &mut Fpu);
+    fn thread_restore_fpu(fpu: &Fpu);
+}
+
+/// The floating point and SIMD state of a thread: the `q0`-`q31` registers
+/// and the `FPCR` and `FPSR` control and status registers. User space is
+/// allowed to use them (see `CPACR_EL1` in the boot code), so they must be
+/// switched along with the general purpose registers.
+#[derive(Debug, Clone)]
+#[repr(C, align(16))]
+struct Fpu {
+    registers: [u128; 32],
+    fpcr: u64,
+    fpsr: u64,
+}
+
+impl Fpu {
+    /// Create a new floating point state, with all the registers zeroed and
+    /// the default rounding mode.
+    const fn new() -> Self {
+        Self {
+            registers: [0; 32],
+            fpcr: 0,
+            fpsr: 0,
+        }
+    }
+}
+
+/// A thread is a sequence of instructions that can be executed independently
+/// of other code. On AArch64, a thread is represented by a `Context` that
+/// contains a copy of all the general purpose registers, a copy of the
+/// floating point and SIMD registers and an [`AddressSpace`] that contains
+/// the page table of the thread. The address space may be shared with other
+/// threads of the same task, in which case it is released when the last of
+/// them is dropped.
+#[derive(Debug)]
+pub struct Thread {
+    context: Box<trap::Context>,
+    fpu: Box<Fpu>,
+    space: Arc<lock::Mutex<AddressSpace>>,
+
+    /// Whether the thread is single-stepped (see [`set_single_step`]).
+    single_step: bool,
+}
+
+impl Thread {
+    /// Create a new thread with an empty user address space.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            context: Box::new(trap::Context::new()),
+            fpu: Box::new(Fpu::new()),
+            space: Arc::new(lock::Mutex::new(AddressSpace::new())),
+            single_step: false,
+        }
+    }
+
+    /// Return a mutable reference to the context of the thread.
+    #[must_use]
+    pub fn context_mut(&mut self) -> &mut trap::Context {
+        &mut self.context
+    }
+
+    /// Return a reference to the context of the thread.
+    #[must_use]
+    pub fn context(&self) -> &trap::Context {
+        &self.context
+    }
+
+    /// Lock and return the address space of the thread. Since the address
+    /// space may be shared with other threads, the lock must not be held
+    /// while accessing user memory or while calling a function that may lock
+    /// it again.
+    #[must_use]
+    pub fn address_space(&self) -> lock::MutexGuard<'_, AddressSpace> {
+        self.space.lock()
+    }
+
+    /// Return whether the address space of the thread is shared with other
+    /// threads that are still alive.
+    #[must_use]
+    pub fn shares_address_space(&self) -> bool {
+        Arc::strong_count(&self.space) > 1
+    }
+}
+
+impl Default for Thread {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Create a new thread with the given instruction pointer and stack pointer.
+/// This will create a thread with a default context and an empty user page
+/// table (but still containing the kernel mappings).
+#[must_use]
+pub fn create(ip: usize, stack: usize) -> Thread {
+    let mut thread = Thread::new();
+    thread.context.set_sp(stack);
+    thread.context.set_ip(ip);
+    thread
+}
+
+/// Create a new thread with the given instruction pointer and stack pointer,
+/// sharing the address space of the given thread.
+#[must_use]
+pub fn create_sibling(thread: &Thread, ip: usize, stack: usize) -> Thread {
+    let mut sibling = Thread {
+        context: Box::new(trap::Context::new()),
+        fpu: Box::new(Fpu::new()),
+        space: Arc::clone(&thread.space),
+        single_step: false,
+    };
+    sibling.context.set_sp(stack);
+    sibling.context.set_ip(ip);
+    sibling
+}
+
+/// Set an argument given to the entry point of the given thread. On AArch64,
+/// the arguments of a function are stored in the x0-x7 registers.
+pub fn set_entry_argument(thread: &mut Thread, index: usize, value: usize) {
+    thread.context.set_register(index, value);
+}
+
+/// Save state of the current thread that was not saved by the trap handler.
+/// The trap handler only saves the general purpose registers, since the
+/// kernel is built without floating point support and never touches the
+/// floating point and SIMD registers. Those are saved here, and must be saved
+/// before another thread is executed on the same core: [`execute`] does it as
+/// soon as the thread traps, since the thread may then wait for an event
+/// while other threads run.
+pub fn save(thread: &mut Thread) {
+    // SAFETY: The kernel does not use the floating point and SIMD registers,
+    // so they still hold the state of the last thread executed on this core.
+    unsafe {
+        thread_save_fpu(&mut thread.fpu);
+    }
+}
+
+/// Execute the current thread. This function will switch to the page table
+/// of the thread and execute the thread. When a trap will occur, the trap
+/// handler will be called and the thread will be paused. The trap handler
+/// will invoke some incantations and will return to the caller of this
+/// function.
+pub fn execute(thread: &mut Thread) -> Trap {
+    // The kernel must not be interrupted while it switches to the thread,
+    // since the trap handler would mistake the kernel for the thread. External
+    // interrupts masked while the kernel was running are unmasked, since they
+    // can be handled when the thread is interrupted.
+    let enabled = super::irq::enabled();
+    super::irq::disable();
+    super::irq::unmask_external();
+
+    // Single-stepping is enabled for the whole core, so it must be enabled
+    // or disabled for each thread executed.
+    set_core_single_step(thread.single_step);
+
+    // Switch to the thread's page table, restore its floating point state
+    // and execute the thread.
+    unsafe {
+        thread.address_space().root().set_current();
+        thread_restore_fpu(&thread.fpu);
+        thread_execute(&mut thread.context);
+    }
+    save(thread);
+
+    if enabled {
+        // SAFETY: Interrupts were enabled before executing the thread.
+        unsafe {
+            super::irq::enable();
+        }
+    }
+
+    // Here, we have returned from a trap. Determine the cause of the trap
+    // from the entry of the vector table taken and, for synchronous
+    // exceptions, from the exception class, and return it to the caller to
+    // handle it.
+    match thread.context.kind() {
+        Kind::Sync => match Exception::from_syndrome(trap::syndrome()) {
+            Exception::Syscall => Trap::Syscall,
+            Exception::SoftwareStep => Trap::Step,
+            _ => Trap::Exception,
+        },
+        Kind::SError => Trap::Exception,
+        Kind::Irq | Kind::Fiq => Trap::Interrupt,
+    }
+}
+
+/// Get the current stack pointer of the given thread. On AArch64, the stack
+/// pointer of user threads is stored in the `SP_EL0` register.
+#[must_use]
+pub fn get_stack_pointer(thread: &Thread) -> usize {
+    thread.context.sp()
+}
+
+/// Set the stack pointer of the given thread. On AArch64, the stack pointer
+/// of user threads is stored in the `SP_EL0` register.
+pub fn set_stack_pointer(thread: &mut Thread, sp: usize) {
+    thread.context.set_sp(sp);
+}
+
+/// Get the syscall identifier from the given thread. On AArch64, the
+/// syscall identifier is stored in the x8 register.
+#[must_use]
+pub fn get_syscall_id(thread: &Thread) -> usize {
+    thread.context.get_register(8)
+}
+
+/// Get the raw syscall arguments from the given thread. On AArch64, the
+/// syscall arguments are stored in the x0-x5 registers.
+#[must_use]
+pub fn get_syscall_args(thread: &Thread) -> [usize; 6] {
+    [
+        thread.context.get_register(0),
+        thread.context.get_register(1),
+        thread.context.get_register(2),
+        thread.context.get_register(3),
+        thread.context.get_register(4),
+        thread.context.get_register(5),
+    ]
+}
+
+/// Set the return value of the syscall for the given thread. On AArch64,
+/// the return value is stored in the x0 register.
+pub fn set_syscall_return(thread: &mut Thread, value: isize) {
+    thread.context.set_register(0, value.cast_unsigned());
+}
+
+/// Get the registers of the given thread, as seen by a debugger. On AArch64,
+/// the stack pointer is given after the `x0`-`x30` registers.
+#[must_use]
+pub fn get_registers(thread: &Thread) -> Registers {
+    let mut registers = Registers::default();
+    for (index, register) in registers.general.iter_mut().take(31).enumerate() {
+        *register = thread.context.get_register(index) as u64;
+    }
+    registers.general[31] = thread.context.sp() as u64;
+    registers.pc = thread.context.ip() as u64;
+    registers.flags = thread.context.flags() as u64;
+    registers.tls = thread.context.tls() as u64;
+    registers
+}
+
+/// Set the registers of the given thread from the registers given by a
+/// debugger. Only the condition flags of the program status can be changed.
+#[allow(clippy::cast_possible_truncation)]
+pub fn set_registers(thread: &mut Thread, registers: &Registers) {
+    for (index, &register) in registers.general.iter().take(31).enumerate() {
+        thread.context.set_register(index, register as usize);
+    }
+    thread.context.set_sp(registers.general[31] as usize);
+    thread.context.set_ip(registers.pc as usize);
+    thread.context.set_flags(registers.flags as usize);
+    thread.context.set_tls(registers.tls as usize);
+}
+
+/// Enable or disable the single-stepping of the given thread. A single-stepped
+/// thread executes a single instruction each time it is executed, and then
+/// traps with [`Trap::Step`]. On AArch64, this uses the software step
+/// mechanism of the processor, so this always succeeds.
+pub fn set_single_step(thread: &mut Thread, enabled: bool) -> bool {
+    thread.single_step = enabled;
+    thread.context.set_step(enabled);
+    true
+}
+
+/// Enable or disable single-stepping for the threads executed on the current
+/// core, by setting the software step bit of the `MDSCR_EL1` register. The
+/// register is only written when the bit changes, since this is the rare case.
+fn set_core_single_step(enabled: bool) {
+    let mdscr: usize;
+    // SAFETY: Reading the `MDSCR_EL1` register has no side effects.
+    unsafe {
+        core::arch::asm!("mrs {}, mdscr_el1", out(reg) mdscr);
+    }
+
+    let updated = if enabled { mdscr | 1 } else { mdscr & !1 };
+    if updated != mdscr {
+        // SAFETY: The software step bit only affects the threads executed at
+        // EL0, whose step exceptions are handled by the kernel.
+        unsafe {
+            core::arch::asm!("msr mdscr_el1, {}", "isb", in(reg) updated);
+        }
+    }
+}
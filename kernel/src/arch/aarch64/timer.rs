@@ -0,0 +1,119 @@
+use seqlock::Seqlock;
+
+/// The interrupt number of the virtual timer used when the device tree does
+/// not describe the timer. This is the interrupt recommended by the Server
+/// Base System Architecture, and the one used by QEMU.
+const DEFAULT_VIRTUAL_TIMER_IRQ: usize = 27;
+
+/// The index of the virtual timer interrupt in the `interrupts` property of
+/// the timer node, after the secure and non-secure physical timers.
+const VIRTUAL_TIMER_INDEX: usize = 2;
+
+/// Enable the timer.
+const CTL_ENABLE: u64 = 1 << 0;
+
+/// Allow EL0 to read the virtual counter, in the `CNTKCTL_EL1` register.
+const CNTKCTL_EL0VCTEN: u64 = 1 << 1;
+
+/// The duration of a single internal tick, in nanoseconds.
+static INTERNAL_TICK: Seqlock<u64> = Seqlock::new(0);
+
+/// The interrupt number of the virtual timer.
+static IRQ: spin::Once<usize> = spin::Once::new();
+
+/// Setup the timer subsystem. The generic timer of the CPU is used: its
+/// frequency is read from the `CNTFRQ_EL0` register, and the virtual timer
+/// is used to raise interrupts so that the kernel also works when it runs
+/// under a hypervisor.
+pub fn setup(device_tree: &fdt::Fdt) {
+    log::info!("Initializing timer");
+
+    let frequency: u64;
+    // SAFETY: Reading the frequency of the counter has no side effects.
+    unsafe {
+        core::arch::asm!("mrs {}, cntfrq_el0", out(reg) frequency, options(nomem, nostack));
+    }
+    INTERNAL_TICK.write(1_000_000_000 / frequency);
+
+    log::debug!("Internal timer tick: {} ns", internal_tick());
+    log::debug!("Internal timer frequency: {} Hz", internal_frequency());
+
+    let irq = device_tree
+        .find_compatible(&["arm,armv8-timer", "arm,armv7-timer"])
+        .and_then(|node| super::irq::gic::interrupts(&node).nth(VIRTUAL_TIMER_INDEX))
+        .unwrap_or(DEFAULT_VIRTUAL_TIMER_IRQ);
+    IRQ.call_once(|| irq);
+    super::irq::enable_timer(irq);
+
+    // Allow user space to read the virtual counter, used to compute the
+    // remaining quantum from the shared page.
+    // SAFETY: This only gives user space read access to the counter.
+    unsafe {
+        core::arch::asm!("msr cntkctl_el1, {}", in(reg) CNTKCTL_EL0VCTEN, options(nomem, nostack));
+    }
+}
+
+/// Return the interrupt number of the timer, or `None` if the timer was not
+/// set up yet.
+#[must_use]
+pub fn irq() -> Option<usize> {
+    IRQ.get().copied()
+}
+
+/// Shutdown the timer, preventing any further interrupts from being raised.
+pub fn shutdown() {
+    // SAFETY: Disabling the timer has no side effects other than preventing
+    // its interrupts.
+    unsafe {
+        core::arch::asm!("msr cntv_ctl_el0, xzr", "isb", options(nomem, nostack));
+    }
+}
+
+/// Set the next timer trigger to the given duration from now. An interrupt
+/// will be raised when the timer will reach the given duration.
+pub fn next_event(next: core::time::Duration) {
+    let secs = next.as_secs() * 1_000_000_000;
+    let nanos = u64::from(next.subsec_nanos());
+
+    // Read the current counter value and add the duration to it, converted
+    // to internal ticks. The comparator is set before the timer is enabled,
+    // so that a stale comparator never raises a spurious interrupt.
+    let next = current_time_ticks() + (secs + nanos) / internal_tick();
+    // SAFETY: Programming the timer has no side effects other than raising
+    // an interrupt when it expires.
+    unsafe {
+        core::arch::asm!(
+            "msr cntv_cval_el0, {}",
+            "msr cntv_ctl_el0, {}",
+            "isb",
+            in(reg) next,
+            in(reg) CTL_ENABLE,
+            options(nomem, nostack)
+        );
+    }
+}
+
+/// The internal frequency of the timer, in Hertz.
+#[must_use]
+pub fn internal_frequency() -> u64 {
+    1_000_000_000 / internal_tick()
+}
+
+/// The duration of a single internal tick, in nanoseconds.
+#[must_use]
+pub fn internal_tick() -> u64 {
+    INTERNAL_TICK.read()
+}
+
+/// Get the current time since the system booted, in internal ticks.
+#[must_use]
+pub fn current_time_ticks() -> u64 {
+    let ticks: u64;
+    // SAFETY: Reading the virtual counter has no side effects. The `isb`
+    // prevents the read from being speculated before the previous
+    // instructions.
+    unsafe {
+        core::arch::asm!("isb", "mrs {}, cntvct_el0", out(reg) ticks, options(nomem, nostack));
+    }
+    ticks
+}
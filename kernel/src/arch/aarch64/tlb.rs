@@ -0,0 +1,102 @@
+//! TLB maintenance. Contrary to RISC-V, AArch64 can broadcast invalidations
+//! to all the CPUs of the inner shareable domain without interrupting them,
+//! so shootdowns are simply the inner shareable variants of the local
+//! invalidations. The `dsb` before each invalidation ensures that the update
+//! of the page table is visible to the table walkers, and the `dsb` after it
+//! waits for the invalidation to complete on all the CPUs.
+
+/// Invalidate the translations of the page containing the given address in
+/// the TLB of the current CPU, including global translations.
+#[inline]
+pub fn flush_page(address: usize) {
+    // SAFETY: Invalidating translations cannot cause undefined behavior, the
+    // page table will simply be walked again on the next access.
+    unsafe {
+        core::arch::asm!(
+            "dsb ishst",
+            "tlbi vaae1, {}",
+            "dsb nsh",
+            "isb",
+            in(reg) operand(address),
+            options(nostack)
+        );
+    }
+}
+
+/// Invalidate all the non-global translations in the TLB of the current CPU.
+/// This is enough when switching between address spaces, since the kernel
+/// half is mapped with global translations that are shared by all address
+/// spaces and never change after boot.
+#[inline]
+pub fn flush_user() {
+    // SAFETY: Invalidating translations cannot cause undefined behavior, the
+    // page table will simply be walked again on the next access. The kernel
+    // does not use ASIDs, so all the user translations use the ASID 0.
+    unsafe {
+        core::arch::asm!(
+            "dsb ishst",
+            "tlbi aside1, xzr",
+            "dsb nsh",
+            "isb",
+            options(nostack)
+        );
+    }
+}
+
+/// Invalidate all the translations in the TLB of the current CPU, including
+/// global translations.
+#[inline]
+pub fn flush_all() {
+    // SAFETY: Invalidating translations cannot cause undefined behavior, the
+    // page table will simply be walked again on the next access.
+    unsafe {
+        core::arch::asm!(
+            "dsb ishst",
+            "tlbi vmalle1",
+            "dsb nsh",
+            "isb",
+            options(nostack)
+        );
+    }
+}
+
+/// Invalidate the translations of the page containing the given address on
+/// all the CPUs that may have cached it. This function returns once all the
+/// CPUs have invalidated the translation, so the page can safely be reused
+/// afterwards.
+pub fn shootdown_page(address: usize) {
+    // SAFETY: Invalidating translations cannot cause undefined behavior, the
+    // page table will simply be walked again on the next access.
+    unsafe {
+        core::arch::asm!(
+            "dsb ishst",
+            "tlbi vaae1is, {}",
+            "dsb ish",
+            "isb",
+            in(reg) operand(address),
+            options(nostack)
+        );
+    }
+}
+
+/// Invalidate all the translations on all the CPUs that may have cached them.
+/// See [`shootdown_page`] for more details.
+pub fn shootdown_all() {
+    // SAFETY: Invalidating translations cannot cause undefined behavior, the
+    // page table will simply be walked again on the next access.
+    unsafe {
+        core::arch::asm!(
+            "dsb ishst",
+            "tlbi vmalle1is",
+            "dsb ish",
+            "isb",
+            options(nostack)
+        );
+    }
+}
+
+/// Returns the operand of the invalidation instructions for a single page,
+/// which holds the bits 55:12 of the address of the page.
+const fn operand(address: usize) -> usize {
+    (address >> 12) & 0x0FFF_FFFF_FFFF
+}
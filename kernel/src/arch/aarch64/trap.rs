@@ -0,0 +1,570 @@
+use crate::{
+    arch::{
+        self,
+        thread::Thread,
+        trap::{FaultCause, FaultKind, Resume},
+    },
+    config, user,
+    utils::lock,
+};
+
+core::arch::global_asm!(include_str!("asm/trap.asm"));
+
+/// The trap that the kernel could not recover from on each core, indexed by
+/// the index of the core. It is recorded just before panicking so that the
+/// panic handler can print the registers of the kernel at the time of the
+/// trap and start the backtrace from there.
+static FATAL_TRAPS: [lock::Mutex<Option<KernelTrap>>; config::MAX_CPUS] =
+    [const { lock::Mutex::new(None) }; config::MAX_CPUS];
+
+/// The kind of exception taken, which depends on the entry of the vector
+/// table used by the processor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A synchronous exception, caused by the instruction being executed.
+    Sync,
+
+    /// An interrupt request.
+    Irq,
+
+    /// A fast interrupt request.
+    Fiq,
+
+    /// A system error, which is an asynchronous abort often caused by an
+    /// external memory error.
+    SError,
+}
+
+impl From<usize> for Kind {
+    fn from(kind: usize) -> Self {
+        match kind {
+            0 => Kind::Sync,
+            1 => Kind::Irq,
+            2 => Kind::Fiq,
+            _ => Kind::SError,
+        }
+    }
+}
+
+/// The cause of a synchronous exception, decoded from the exception class and
+/// the fault status code of the `ESR_EL1` register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    /// A `svc` instruction executed by a user thread.
+    Syscall,
+
+    /// A fault while fetching an instruction.
+    InstructionAbort(Abort),
+
+    /// A fault while reading or writing data.
+    DataAbort(Abort),
+
+    /// A misaligned program counter or stack pointer.
+    Misaligned,
+
+    /// An undefined instruction, a breakpoint or an illegal execution state.
+    IllegalInstruction,
+
+    /// A user thread executed a single instruction while being single-stepped
+    /// (see [`super::thread::set_single_step`]).
+    SoftwareStep,
+
+    /// Any other exception, with its raw exception class.
+    Other(usize),
+}
+
+/// The cause of an instruction or data abort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Abort {
+    /// The address is not mapped.
+    Translation,
+
+    /// The address is mapped, but the access flag of the entry is not set.
+    AccessFlag,
+
+    /// The address is mapped without the rights needed by the access.
+    Permission,
+
+    /// The access is not aligned.
+    Alignment,
+
+    /// Any other abort, such as an external abort, with its raw fault status
+    /// code.
+    Other(usize),
+}
+
+impl Exception {
+    /// Decode the cause of a synchronous exception from the value of the
+    /// `ESR_EL1` register.
+    #[must_use]
+    pub const fn from_syndrome(esr: usize) -> Self {
+        match (esr >> 26) & 0x3F {
+            0x15 => Exception::Syscall,
+            0x20 | 0x21 => Exception::InstructionAbort(Abort::from_syndrome(esr)),
+            0x24 | 0x25 => Exception::DataAbort(Abort::from_syndrome(esr)),
+            0x22 | 0x26 => Exception::Misaligned,
+            0x00 | 0x0E | 0x3C => Exception::IllegalInstruction,
+            0x32 => Exception::SoftwareStep,
+            class => Exception::Other(class),
+        }
+    }
+}
+
+impl Abort {
+    /// Decode the cause of an abort from the fault status code of the value
+    /// of the `ESR_EL1` register.
+    #[must_use]
+    pub const fn from_syndrome(esr: usize) -> Self {
+        match esr & 0x3F {
+            0x04..=0x07 => Abort::Translation,
+            0x08..=0x0B => Abort::AccessFlag,
+            0x0C..=0x0F => Abort::Permission,
+            0x21 => Abort::Alignment,
+            status => Abort::Other(status),
+        }
+    }
+}
+
+/// A trap that occurred while the kernel was running, along with the state of
+/// the kernel when it occurred.
+#[derive(Debug, Clone)]
+pub struct KernelTrap {
+    /// The kind of the trap.
+    pub kind: Kind,
+
+    /// The value of the `ESR_EL1` register, which holds the cause of
+    /// synchronous exceptions.
+    pub esr: usize,
+
+    /// The value of the `FAR_EL1` register, which holds the faulting address
+    /// for memory faults.
+    pub far: usize,
+
+    /// The registers of the kernel when the trap occurred.
+    pub context: Context,
+}
+
+impl KernelTrap {
+    /// Returns the value of the frame pointer (`x29`) when the trap occurred,
+    /// from where a backtrace of the kernel can be walked.
+    #[must_use]
+    pub const fn frame_pointer(&self) -> usize {
+        self.context.registers[29]
+    }
+}
+
+unsafe extern "C" {
+    fn kernel_vectors();
+}
+
+/// The context of the trap. This struct is used to store the state
+/// of the CPU when the trap occured.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(C, align(16))]
+pub struct Context {
+    registers: [usize; 31],
+    sp: usize,
+    elr: usize,
+    spsr: usize,
+
+    /// The thread pointer of the thread, held by the `TPIDR_EL0` register.
+    tpidr: usize,
+
+    /// The kind of the last exception taken by the thread (see [`Kind`]),
+    /// stored by the trap handler.
+    kind: usize,
+}
+
+impl Context {
+    /// The `NZCV` condition flags of the `SPSR_EL1` register.
+    const SPSR_NZCV: usize = 0xF000_0000;
+
+    /// The software step bit of the `SPSR_EL1` register.
+    const SPSR_SS: usize = 1 << 21;
+
+    /// Create a new context. The saved program status is zero, which makes
+    /// the thread run at EL0 with interrupts enabled.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            registers: [0; 31],
+            sp: 0,
+            elr: 0,
+            spsr: 0,
+            tpidr: 0,
+            kind: 0,
+        }
+    }
+
+    /// Get the value of the given register (x0 - x30).
+    ///
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    #[must_use]
+    pub fn get_register(&self, index: usize) -> usize {
+        match index {
+            0..=30 => self.registers[index],
+            _ => panic!("Register index out of bounds: {}", index),
+        }
+    }
+
+    /// Set the value of the given register (x0 - x30).
+    ///
+    /// # Panics
+    /// Panics if the index is out of bounds.
+    pub fn set_register(&mut self, index: usize, value: usize) {
+        match index {
+            0..=30 => self.registers[index] = value,
+            _ => panic!("Register index out of bounds: {}", index),
+        }
+    }
+
+    /// Get the stack pointer.
+    #[must_use]
+    pub const fn sp(&self) -> usize {
+        self.sp
+    }
+
+    /// Set the stack pointer.
+    pub fn set_sp(&mut self, sp: usize) {
+        self.sp = sp;
+    }
+
+    /// Get the instruction pointer.
+    #[must_use]
+    pub const fn ip(&self) -> usize {
+        self.elr
+    }
+
+    /// Set the instruction pointer.
+    pub fn set_ip(&mut self, ip: usize) {
+        self.elr = ip;
+    }
+
+    /// Get the `NZCV` condition flags of the saved program status, at their
+    /// position in the `SPSR_EL1` register.
+    #[must_use]
+    pub const fn flags(&self) -> usize {
+        self.spsr & Self::SPSR_NZCV
+    }
+
+    /// Set the `NZCV` condition flags of the saved program status. The other
+    /// bits of the given value are ignored, so that the thread can never be
+    /// made to run at another exception level or with interrupts masked.
+    pub fn set_flags(&mut self, flags: usize) {
+        self.spsr = (self.spsr & !Self::SPSR_NZCV) | (flags & Self::SPSR_NZCV);
+    }
+
+    /// Set or clear the software step bit of the saved program status, which
+    /// makes the thread execute a single instruction before taking a software
+    /// step exception when single-stepping is enabled in `MDSCR_EL1`.
+    pub fn set_step(&mut self, step: bool) {
+        if step {
+            self.spsr |= Self::SPSR_SS;
+        } else {
+            self.spsr &= !Self::SPSR_SS;
+        }
+    }
+
+    /// Get the thread pointer, held by the `TPIDR_EL0` register.
+    #[must_use]
+    pub const fn tls(&self) -> usize {
+        self.tpidr
+    }
+
+    /// Set the thread pointer, held by the `TPIDR_EL0` register.
+    pub fn set_tls(&mut self, tls: usize) {
+        self.tpidr = tls;
+    }
+
+    /// Returns the kind of the last exception taken by the thread.
+    #[must_use]
+    pub fn kind(&self) -> Kind {
+        Kind::from(self.kind)
+    }
+}
+
+impl core::fmt::Display for Context {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        const NAMES: [&str; 31] = [
+            "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13",
+            "x14", "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25",
+            "x26", "x27", "x28", "fp", "lr",
+        ];
+
+        writeln!(
+            f,
+            "  elr: {:#018x}  spsr: {:#018x}  sp: {:#018x}",
+            self.elr, self.spsr, self.sp
+        )?;
+        for (i, (name, value)) in NAMES.iter().zip(self.registers.iter()).enumerate() {
+            write!(f, "  {name:>4}: {value:#018x}")?;
+            if i % 4 == 3 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn setup() {
+    log::info!("Initializing trap handling");
+    // SAFETY: The vector table `kernel_vectors` is defined in the assembly
+    // file `trap.asm` and is designed to handle all interrupts and
+    // exceptions.
+    unsafe {
+        core::arch::asm!(
+            "msr vbar_el1, {}",
+            "isb",
+            in(reg) kernel_vectors as usize
+        );
+    }
+
+    // The OS lock may be set when the core comes out of reset, which prevents
+    // the core from taking the debug exceptions used to single-step user
+    // threads.
+    // SAFETY: Clearing the OS lock only allows debug exceptions to be taken
+    // from EL0, which are handled like any other exception of a thread.
+    unsafe {
+        core::arch::asm!("msr oslar_el1, xzr", "isb");
+    }
+}
+
+/// Returns the value of the `ESR_EL1` register, which holds the cause of the
+/// last synchronous exception.
+#[must_use]
+pub fn syndrome() -> usize {
+    let esr: usize;
+    // SAFETY: Reading the `ESR_EL1` register has no side effects.
+    unsafe {
+        core::arch::asm!("mrs {}, esr_el1", out(reg) esr);
+    }
+    esr
+}
+
+/// Returns the value of the `FAR_EL1` register, which holds the faulting
+/// address of the last abort.
+#[must_use]
+pub fn fault_register() -> usize {
+    let far: usize;
+    // SAFETY: Reading the `FAR_EL1` register has no side effects.
+    unsafe {
+        core::arch::asm!("mrs {}, far_el1", out(reg) far);
+    }
+    far
+}
+
+/// Handle an exception raised by the given thread. Translation faults caused
+/// by the first access to a page of the user stack are resolved, and any
+/// other exception terminates the thread with a diagnostic including a dump
+/// of its registers.
+pub fn handle_exception(thread: &mut Thread) -> Resume {
+    let esr = syndrome();
+    let far = fault_register();
+    let elr = thread.context().elr;
+    let exception = Exception::from_syndrome(esr);
+    let resume = match (thread.context().kind(), exception) {
+        (Kind::Sync, Exception::DataAbort(Abort::Translation)) => {
+            let cause = FaultCause::classify(thread.address_space().root(), far);
+            match cause {
+                // The user stack is allocated lazily, so a translation fault
+                // on an unmapped page may simply be the first access to a
+                // page of the stack.
+                FaultCause::NotPresent => user::stack::handle_page_fault(thread, far),
+                FaultCause::Protection => Resume::Fault,
+            }
+        }
+        _ => Resume::Fault,
+    };
+
+    if resume == Resume::Fault {
+        match (thread.context().kind(), exception) {
+            (Kind::Sync, Exception::InstructionAbort(_) | Exception::DataAbort(_)) => {
+                log::error!(
+                    "Page fault: {:?} ({:?}, far: {:#x}, elr: {:#x})",
+                    exception,
+                    FaultCause::classify(thread.address_space().root(), far),
+                    far,
+                    elr
+                );
+            }
+            (Kind::Sync, exception) => log::error!(
+                "Unhandled exception: {:?} (esr: {:#x}, elr: {:#x})",
+                exception,
+                esr,
+                elr
+            ),
+            (kind, _) => log::error!("Unhandled exception: {:?} (elr: {:#x})", kind, elr),
+        }
+        log::error!("Registers of the faulting thread:\n{}", thread.context());
+    }
+    resume
+}
+
+/// Return the kind of the last exception, based on the `ESR_EL1` register.
+#[must_use]
+pub fn fault_kind() -> FaultKind {
+    match Exception::from_syndrome(syndrome()) {
+        Exception::InstructionAbort(Abort::Translation | Abort::AccessFlag | Abort::Permission)
+        | Exception::DataAbort(Abort::Translation | Abort::AccessFlag | Abort::Permission) => {
+            FaultKind::PageFault
+        }
+        Exception::InstructionAbort(Abort::Other(_)) | Exception::DataAbort(Abort::Other(_)) => {
+            FaultKind::AccessFault
+        }
+        Exception::IllegalInstruction => FaultKind::IllegalInstruction,
+        Exception::Misaligned
+        | Exception::InstructionAbort(Abort::Alignment)
+        | Exception::DataAbort(Abort::Alignment) => FaultKind::Misaligned,
+        Exception::Syscall | Exception::SoftwareStep | Exception::Other(_) => FaultKind::Unknown,
+    }
+}
+
+/// Return the faulting address of the last exception if it was caused by an
+/// invalid memory access, or `None` otherwise. On AArch64, the faulting
+/// address is stored in the `FAR_EL1` register.
+#[must_use]
+pub fn fault_address() -> Option<usize> {
+    match Exception::from_syndrome(syndrome()) {
+        Exception::InstructionAbort(_) | Exception::DataAbort(_) => Some(fault_register()),
+        _ => None,
+    }
+}
+
+/// Handle an interrupt raised while the given thread was running. The timer
+/// interrupt is used to preempt the currently running thread and switch to
+/// the next one if the current thread has used up its time slice. Since the
+/// timer interrupt is routed through the GIC like the interrupts of the
+/// devices, both are handled by [`super::irq::handle_external`].
+pub fn handle_interrupt(_thread: &mut Thread) -> Resume {
+    // Yield after handling the interrupt, so that the drivers woken up by the
+    // interrupt can service their device without waiting for the end of the
+    // quantum of the current thread.
+    if super::irq::handle_external() {
+        Resume::Yield
+    } else {
+        Resume::Continue
+    }
+}
+
+/// Handle a syscall trap. This function delegates the syscall handling to
+/// the `user::syscall::handle_syscall` function. Contrary to RISC-V, the
+/// `svc` instruction saves the address of the next instruction in `ELR_EL1`,
+/// so the program counter does not need to be advanced.
+pub async fn handle_syscall(thread: &mut crate::arch::thread::Thread) -> Resume {
+    user::syscall::handle_syscall(thread).await
+}
+
+/// Handle a trap that occurred while the kernel was running. The only traps
+/// that the kernel can recover from are faults that occur while it accesses
+/// user memory on behalf of a syscall: in this case, the execution resumes in
+/// the fault handler of the user copy routine which reports the fault to the
+/// syscall.
+///
+/// When the watchdog is enabled, the kernel can also be interrupted. The timer
+/// interrupt runs the checks of the watchdog, and external interrupts are
+/// masked until the kernel is ready to handle them.
+///
+/// # Panics
+/// Panics on any other trap, since it is caused by a kernel bug, or if the
+/// watchdog detects that the core is locked up.
+#[unsafe(no_mangle)]
+pub extern "C" fn kernel_trap_handler(registers: &[usize; 31], kind: usize) {
+    let kind = Kind::from(kind);
+    let esr = syndrome();
+    let far = fault_register();
+    let elr: usize;
+    let spsr: usize;
+    // SAFETY: Reading the `ELR_EL1` and `SPSR_EL1` registers has no side
+    // effects.
+    unsafe {
+        core::arch::asm!("mrs {}, elr_el1", out(reg) elr);
+        core::arch::asm!("mrs {}, spsr_el1", out(reg) spsr);
+    }
+
+    if kind == Kind::Sync
+        && matches!(Exception::from_syndrome(esr), Exception::DataAbort(_))
+        && user::op::in_operation()
+        && let Some(fixup) = super::user::fixup(elr)
+    {
+        log::debug!(
+            "Fault while accessing user memory at {:#x} (elr: {:#x})",
+            far,
+            elr
+        );
+        // SAFETY: The fixup address is in the user copy routine, which
+        // reports the fault to its caller.
+        unsafe {
+            core::arch::asm!("msr elr_el1, {}", in(reg) fixup);
+        }
+        return;
+    }
+
+    let stalled = match kind {
+        Kind::Irq | Kind::Fiq => {
+            // The timer has the highest priority, so it is the pending
+            // interrupt whenever it is raised. It is level-sensitive, and
+            // rearming the timer is enough to lower it without acknowledging
+            // it.
+            let timer = super::timer::irq();
+            if timer.is_none() || super::irq::gic::pending() != timer {
+                super::irq::mask_external();
+                return;
+            }
+            match crate::watchdog::check() {
+                Some(stalled) => Some(stalled),
+                None => return,
+            }
+        }
+        Kind::Sync | Kind::SError => None,
+    };
+
+    // The stack pointer was saved after room was made for the registers on
+    // the stack, so the size of the saved registers is added back to get the
+    // stack pointer at the time of the trap.
+    let context = Context {
+        registers: *registers,
+        sp: (&raw const *registers).addr() + 32 * core::mem::size_of::<usize>(),
+        elr,
+        spsr,
+        tpidr: 0,
+        kind: kind as usize,
+    };
+    *FATAL_TRAPS[arch::cpu::id()].lock() = Some(KernelTrap {
+        kind,
+        esr,
+        far,
+        context,
+    });
+
+    if let Some(stalled) = stalled {
+        panic!(
+            "Lockup detected: core {} made no progress for {:?} (elr: {:#x})",
+            arch::cpu::id(),
+            stalled,
+            elr
+        );
+    }
+    panic!(
+        "Unhandled kernel trap: {:?} {:?} (esr: {:#x}, far: {:#x}, elr: {:#x})",
+        kind,
+        Exception::from_syndrome(esr),
+        esr,
+        far,
+        elr
+    );
+}
+
+/// Returns the trap that the kernel could not recover from on the current
+/// core, if the current panic was caused by such a trap. This never blocks,
+/// since it is intended to be called from the panic handler.
+#[must_use]
+pub fn fatal_trap() -> Option<KernelTrap> {
+    FATAL_TRAPS[arch::cpu::id()].try_lock()?.clone()
+}
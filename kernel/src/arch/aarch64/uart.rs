@@ -0,0 +1,167 @@
+use super::irq::gic;
+use crate::arch::{self, mmio::MmioRegion, mmu::PAGE_SIZE, target::addr::Physical};
+
+/// The data register: reading it pops a byte from the receive FIFO, and
+/// writing it pushes a byte to the transmit FIFO.
+const REG_DR: usize = 0x00;
+
+/// The flag register.
+const REG_FR: usize = 0x18;
+
+/// The line control register.
+const REG_LCR_H: usize = 0x2C;
+
+/// The control register.
+const REG_CR: usize = 0x30;
+
+/// The interrupt mask set/clear register.
+const REG_IMSC: usize = 0x38;
+
+/// The interrupt clear register.
+const REG_ICR: usize = 0x44;
+
+/// The receive FIFO is empty.
+const FR_RX_EMPTY: u32 = 1 << 4;
+
+/// The transmit FIFO is full.
+const FR_TX_FULL: u32 = 1 << 5;
+
+/// 8 data bits, no parity and one stop bit, with the FIFOs enabled.
+const LCR_H_8N1_FIFO: u32 = (3 << 5) | (1 << 4);
+
+/// Enable the UART, its transmitter and its receiver.
+const CR_ENABLE: u32 = (1 << 0) | (1 << 8) | (1 << 9);
+
+/// Raise an interrupt when the receive FIFO reaches its trigger level, or
+/// when it holds data that was not read for a while.
+const IMSC_RX: u32 = (1 << 4) | (1 << 6);
+
+/// Clear all the interrupts of the UART.
+const ICR_ALL: u32 = 0x7FF;
+
+/// The UART of the system, if one was found in the device tree.
+static UART: spin::Once<Uart> = spin::Once::new();
+
+/// An ARM PL011 UART, as found on the QEMU `virt` machine and on most aarch64
+/// boards. The baud rate is left as configured by the firmware.
+#[derive(Debug)]
+struct Uart {
+    /// The registers of the UART.
+    registers: MmioRegion<u32>,
+
+    /// The interrupt number of the UART, if the device tree gives one.
+    irq: Option<usize>,
+}
+
+impl Uart {
+    /// Reads the given register.
+    fn read(&self, register: usize) -> u32 {
+        self.registers.read(register)
+    }
+
+    /// Writes the given value to the given register.
+    fn write(&self, register: usize, value: u32) {
+        self.registers.write(register, value);
+    }
+
+    /// Moves the bytes waiting in the receive FIFO of the UART to the console
+    /// input. Emptying the FIFO also clears the receive interrupts. Bytes
+    /// received while the console input is full are dropped.
+    #[allow(clippy::cast_possible_truncation)]
+    fn drain(&self) {
+        while self.read(REG_FR) & FR_RX_EMPTY == 0 {
+            arch::console::receive(self.read(REG_DR) as u8);
+        }
+    }
+}
+
+/// Find the UART in the device tree and initialize it, enabling the receive
+/// interrupt. If no compatible UART is found, the console output is dropped.
+pub fn setup(device_tree: &fdt::Fdt) {
+    let Some(node) = device_tree.find_compatible(&["arm,pl011"]) else {
+        log::info!("No UART found, the console is disabled");
+        return;
+    };
+    let Some(region) = node.reg().and_then(|mut reg| reg.next()) else {
+        log::warn!("UART without registers, the console is disabled");
+        return;
+    };
+
+    let physical = Physical::new(region.starting_address.addr());
+    let size = region.size.unwrap_or(PAGE_SIZE);
+    let Some(registers) = arch::mmu::map_mmio(physical, size) else {
+        log::warn!("UART registers cannot be mapped, the console is disabled");
+        return;
+    };
+    let uart = Uart {
+        registers,
+        irq: gic::interrupts(&node).next(),
+    };
+
+    uart.write(REG_CR, 0);
+    uart.write(REG_IMSC, 0);
+    uart.write(REG_ICR, ICR_ALL);
+    uart.write(REG_LCR_H, LCR_H_8N1_FIFO);
+    uart.write(REG_CR, CR_ENABLE);
+    uart.write(REG_IMSC, IMSC_RX);
+
+    log::info!(
+        "UART at {:#010x} (irq {:?})",
+        region.starting_address.addr(),
+        uart.irq
+    );
+    UART.call_once(|| uart);
+}
+
+/// Write the given bytes to the UART, waiting for the transmitter to accept
+/// each byte. Returns `false` without writing anything if there is no UART.
+pub fn write(bytes: &[u8]) -> bool {
+    let Some(uart) = UART.get() else {
+        return false;
+    };
+    for &byte in bytes {
+        while uart.read(REG_FR) & FR_TX_FULL != 0 {
+            core::hint::spin_loop();
+        }
+        uart.write(REG_DR, u32::from(byte));
+    }
+    true
+}
+
+/// Move the bytes waiting in the receive FIFO of the UART to the console
+/// input. This is called before reading the console input, so that input is
+/// not lost on platforms where the interrupt of the UART is not delivered to
+/// the kernel.
+pub fn poll() {
+    if let Some(uart) = UART.get() {
+        uart.drain();
+    }
+}
+
+/// Handle an interrupt raised by the UART, moving the received bytes to the
+/// console input. This must be called with interrupts disabled.
+pub fn handle_interrupt() {
+    if let Some(uart) = UART.get() {
+        uart.drain();
+    }
+}
+
+/// Return whether the bytes received by the UART are delivered by its
+/// interrupt, so that a task waiting for input can sleep until it arrives.
+#[must_use]
+pub fn interrupt_driven() -> bool {
+    irq().is_some() && arch::irq::controller_available()
+}
+
+/// Return the interrupt number of the UART, or `None` if there is no UART or
+/// if the device tree does not give its interrupt.
+#[must_use]
+pub fn irq() -> Option<usize> {
+    UART.get().and_then(|uart| uart.irq)
+}
+
+/// Return whether a UART was found and initialized.
+#[must_use]
+pub fn available() -> bool {
+    UART.get().is_some()
+}
@@ -0,0 +1,57 @@
+use crate::{
+    config,
+    future::{self, channel::Channel},
+    utils::lock,
+};
+
+/// A lock serializing the writes to the console, so that the output of
+/// concurrent writers is not interleaved in the middle of a write. The kernel
+/// log does not take this lock, and may still be interleaved with it.
+static LOCK: lock::Mutex<()> = lock::Mutex::new(());
+
+/// The bytes received by the console that were not read yet. It is filled by
+/// the interrupt handler of the UART, or when the console is polled.
+static INPUT: Channel<u8, { config::CONSOLE_INPUT_SIZE }> = Channel::new();
+
+/// Serializes the tasks waiting for console input, since only one task can
+/// wait on [`INPUT`] at a time.
+static READER: future::sync::Mutex<()> = future::sync::Mutex::new(());
+
+/// Write raw bytes to the console. Contrary to the log, the bytes are written
+/// as is, without any prefix or formatting. On most platforms, this writes to
+/// the serial port.
+pub fn write(bytes: &[u8]) {
+    let _guard = LOCK.lock();
+    crate::arch::target::console::write(bytes);
+}
+
+/// Read a byte from the console without blocking, or return `None` if no
+/// byte is available.
+#[must_use]
+pub fn read() -> Option<u8> {
+    crate::arch::target::console::poll();
+    INPUT.try_recv()
+}
+
+/// Read a byte from the console, waiting until one is available. The task
+/// sleeps until the interrupt of the UART delivers a byte, or yields until
+/// one is received on platforms where the console must be polled.
+pub async fn read_wait() -> u8 {
+    let _reader = READER.lock().await;
+    loop {
+        if let Some(byte) = read() {
+            return byte;
+        }
+        if crate::arch::target::console::interrupt_driven() {
+            return INPUT.recv().await;
+        }
+        future::YieldFuture::new().await;
+    }
+}
+
+/// Hand a byte received by the console to the tasks reading it. The byte is
+/// dropped if [`config::CONSOLE_INPUT_SIZE`] bytes are already waiting to be
+/// read. This never blocks, so it can be called from a trap handler.
+pub fn receive(byte: u8) {
+    _ = INPUT.try_send(byte);
+}
@@ -11,6 +11,22 @@ pub fn relax() {
     crate::arch::target::cpu::relax();
 }
 
+/// Puts the CPU in the lowest power state it can leave when an interrupt is
+/// pending, which may be deeper than the state entered by [`relax`]. This is
+/// used by the executor when there is no task to run, once the timer is armed
+/// for the next event the core must handle.
+pub fn idle() {
+    crate::arch::target::cpu::idle();
+}
+
+/// Returns the index of the current CPU core, between 0 and
+/// [`crate::config::MAX_CPUS`] (exclusive). This is used to select the data
+/// owned by the current core.
+#[must_use]
+pub fn id() -> usize {
+    crate::arch::target::cpu::id()
+}
+
 /// Halt the current CPU core forever. This function will stop the CPU
 /// core and will not return. This function is useful when the kernel
 /// encounters a critical error and cannot recover from it.
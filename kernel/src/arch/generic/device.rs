@@ -0,0 +1,37 @@
+use crate::arch::memory::Region;
+
+/// The kind of a device discovered by the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A virtio device using the MMIO transport.
+    VirtioMmio,
+}
+
+/// A device discovered by the kernel and driven by a user space driver. The
+/// kernel does not drive these devices itself: it only describes them, and
+/// maps their registers in the address space of their driver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Device {
+    /// The kind of the device.
+    pub kind: Kind,
+
+    /// The model of the device, whose meaning depends on its kind. For virtio
+    /// devices, this is the virtio device ID.
+    pub model: u32,
+
+    /// The physical memory holding the registers of the device. It is
+    /// page-aligned, so that it can be mapped in user space without exposing
+    /// the registers of another device.
+    pub region: Region,
+
+    /// The interrupt source of the device, if any.
+    pub irq: Option<usize>,
+}
+
+/// Return the device with the given index, or `None` if there are fewer
+/// devices. Devices are indexed from 0 in the order in which they were
+/// discovered, and the index of a device never changes.
+#[must_use]
+pub fn get(index: usize) -> Option<Device> {
+    crate::arch::target::device::get(index)
+}
@@ -1,10 +1,16 @@
 /// Enable IRQs.
 ///
+/// # Panics
+/// With the `lock-debug` feature, panics if the current core holds a spinlock,
+/// since a trap handler acquiring it would deadlock.
+///
 /// # Safety
 /// The caller must ensure that the kernel can handle IRQs correctly and that
 /// enabling them is safe and will not cause any undefined behavior or memory
 /// unsafety.
+#[track_caller]
 pub unsafe fn enable() {
+    crate::utils::lock::assert_unlocked("while enabling interrupts");
     crate::arch::target::irq::enable();
 }
 
@@ -25,7 +31,8 @@ pub fn enabled() -> bool {
 
 /// Execute the given closure with IRQs disabled, returning the result of the
 /// closure. If IRQs were already disabled, they will remain disabled after the
-/// execution of the closure.
+/// execution of the closure. Restoring IRQs is not checked by the `lock-debug`
+/// feature, since the closure may be called with a spinlock held.
 pub fn without<F, R>(f: F) -> R
 where
     F: FnOnce() -> R,
@@ -40,7 +47,47 @@ where
         // Thus, it is safe to assume that enabling them again is safe since
         // it should not cause any undefined behavior for the caller if they
         // were already enabled and working correctly.
-        unsafe { enable() };
+        unsafe { crate::arch::target::irq::enable() };
     }
     ret
 }
+
+/// Check if the given interrupt source can be bound to a user space driver.
+/// Sources that do not exist or that are used by the kernel itself cannot.
+#[must_use]
+pub fn bindable(irq: usize) -> bool {
+    crate::arch::target::irq::bindable(irq)
+}
+
+/// Check if the platform has an interrupt controller driven by the kernel.
+/// Without one, devices can only be polled.
+#[must_use]
+pub fn controller_available() -> bool {
+    crate::arch::target::irq::controller_available()
+}
+
+/// Mask the given interrupt source, preventing it from raising interrupts.
+pub fn mask(irq: usize) {
+    crate::arch::target::irq::mask(irq);
+}
+
+/// Unmask the given interrupt source, allowing it to raise interrupts.
+pub fn unmask(irq: usize) {
+    crate::arch::target::irq::unmask(irq);
+}
+
+/// Route the given interrupt source to the CPU with the given index, so that
+/// its interrupts are raised on this CPU. Returns `false` if the source does
+/// not exist or if the interrupt controller cannot route it to this CPU. The
+/// interrupt controller is only set up for the boot CPU, so this is the only
+/// CPU that can receive the interrupts of devices for now.
+#[must_use]
+pub fn set_affinity(irq: usize, cpu: usize) -> bool {
+    crate::arch::target::irq::set_affinity(irq, cpu)
+}
+
+/// Handle the external interrupts that became pending while the kernel was
+/// running with IRQs disabled.
+pub fn handle_pending() {
+    crate::arch::target::irq::handle_pending();
+}
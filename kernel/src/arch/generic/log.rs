@@ -1,11 +1,196 @@
-use core::fmt::Write;
+use crate::{arch, cmdline, config, time::Instant, utils::lock};
+use ::syscall::log::{MAX_MODULE_LEN, MAX_RECORD_LEN, Record};
+use alloc::vec::Vec;
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use heapless::{Deque, String};
+use log::LevelFilter;
+use zerocopy::FromZeros;
+
+/// The global level of the log and the levels of the modules that override
+/// it. It must only be locked with interrupts disabled.
+static LEVELS: lock::Mutex<Levels> = lock::Mutex::new(Levels::new());
+
+/// Whether the console is ready to print messages. Until then, messages are
+/// kept in [`EARLY`] so that they are not lost if the console is not usable
+/// yet, and printed once the architecture calls [`ready`].
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// The messages logged before the console is ready. It must only be locked
+/// with interrupts disabled.
+static EARLY: lock::Mutex<Early> = lock::Mutex::new(Early::new());
+
+/// The most recent messages of the log, which user space can read with the
+/// `LogRead` syscall. It must only be locked with interrupts disabled.
+static RING: lock::Mutex<Ring> = lock::Mutex::new(Ring::new());
+
+/// Errors that may occur when changing the level of a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelError {
+    /// The name of the module is longer than [`MAX_MODULE_LEN`].
+    ModuleTooLong,
+
+    /// [`config::LOG_MODULE_LEVELS`] modules already have their own level.
+    TooManyModules,
+}
+
+/// The levels of the log.
+struct Levels {
+    /// The level of the messages of the modules that do not have their own.
+    global: LevelFilter,
+
+    /// The modules that have their own level, named by their path without
+    /// the name of the kernel crate.
+    modules: heapless::Vec<(String<MAX_MODULE_LEN>, LevelFilter), { config::LOG_MODULE_LEVELS }>,
+}
+
+impl Levels {
+    /// Creates the default levels, where all the modules use the global
+    /// `info` level.
+    const fn new() -> Self {
+        Self {
+            global: LevelFilter::Info,
+            modules: heapless::Vec::new(),
+        }
+    }
+
+    /// Returns the level of the messages logged by the given target, which is
+    /// the path of the module that logged them. The most specific module that
+    /// has its own level wins, and the global level applies otherwise.
+    fn of(&self, target: &str) -> LevelFilter {
+        let path = strip_crate(target);
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                path.strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.global, |(_, level)| *level)
+    }
+
+    /// Returns the most verbose of all the levels. It is given to the `log`
+    /// crate, which then discards the messages that no level allows without
+    /// calling the logger.
+    fn max(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.global, Ord::max)
+    }
+}
+
+/// The messages logged before the console is ready.
+struct Early {
+    /// The formatted messages, one per line.
+    text: String<{ config::EARLY_LOG_CAPACITY }>,
+
+    /// The number of messages that did not fit in the buffer.
+    lost: usize,
+}
+
+impl Early {
+    /// Creates an empty buffer.
+    const fn new() -> Self {
+        Self {
+            text: String::new(),
+            lost: 0,
+        }
+    }
+
+    /// Appends a message to the buffer, or drops it entirely if it does not
+    /// fit.
+    fn push(&mut self, message: core::fmt::Arguments) {
+        let len = self.text.len();
+        if writeln!(self.text, "{message}").is_err() {
+            self.text.truncate(len);
+            self.lost += 1;
+        }
+    }
+}
+
+/// The most recent messages of the log.
+struct Ring {
+    /// The messages, from the oldest to the most recent.
+    records: Deque<Record, { config::LOG_RING_CAPACITY }>,
+
+    /// The number of the next message.
+    next: u64,
+}
+
+impl Ring {
+    /// Creates an empty ring.
+    const fn new() -> Self {
+        Self {
+            records: Deque::new(),
+            next: 0,
+        }
+    }
+
+    /// Appends a message to the ring, overwriting the oldest message if the
+    /// ring is full.
+    fn push(&mut self, level: log::Level, message: core::fmt::Arguments) {
+        let mut record = Record::new_zeroed();
+        record.sequence = self.next;
+        record.timestamp = Instant::now().as_nanos();
+        record.level = raw_level(level) as u32;
+        _ = Text {
+            record: &mut record,
+            truncated: false,
+        }
+        .write_fmt(message);
+
+        if self.records.is_full() {
+            self.records.pop_front();
+        }
+        _ = self.records.push_back(record);
+        self.next += 1;
+    }
+}
+
+/// Writes the text of a message into a [`Record`], truncating it at a
+/// character boundary if it is too long.
+struct Text<'a> {
+    /// The record where the text is written.
+    record: &'a mut Record,
+
+    /// Whether the text was truncated, in which case the rest is ignored.
+    truncated: bool,
+}
+
+impl core::fmt::Write for Text<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        if self.truncated {
+            return Ok(());
+        }
+
+        let start = self.record.len as usize;
+        let mut len = s.len().min(MAX_RECORD_LEN - start);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.truncated = len < s.len();
+        self.record.text[start..start + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.record.len += u32::try_from(len).unwrap_or(0);
+        Ok(())
+    }
+}
 
 /// A simple logger that use the architecture's log implementation.
 struct Logger {}
 
 impl log::Log for Logger {
-    fn enabled(&self, _metadata: &log::Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        // If the levels are being changed at the same time, which includes a
+        // panic while changing them, the message is printed rather than
+        // waiting for the lock that may never be released.
+        arch::irq::without(|| {
+            LEVELS
+                .try_lock()
+                .is_none_or(|levels| metadata.level() <= levels.of(metadata.target()))
+        })
     }
 
     fn log(&self, record: &log::Record) {
@@ -17,7 +202,27 @@ impl log::Log for Logger {
                 log::Level::Debug => "\x1B[1m\x1b[34m[#]\x1b[0m",
                 log::Level::Trace => "\x1B[1m\x1b[35m[~]\x1b[0m",
             };
-            _ = writeln!(Logger {}, "{} {}", level, record.args());
+
+            arch::irq::without(|| {
+                if let Some(mut ring) = RING.try_lock() {
+                    ring.push(record.level(), *record.args());
+                }
+            });
+
+            // Errors are printed at once, even if the console is not ready,
+            // so that a panic during the boot process is always reported.
+            if READY.load(Ordering::Acquire) {
+                _ = writeln!(Logger {}, "{} {}", level, record.args());
+            } else if record.level() == log::Level::Error {
+                flush();
+                _ = writeln!(Logger {}, "{} {}", level, record.args());
+            } else {
+                arch::irq::without(|| {
+                    EARLY
+                        .lock()
+                        .push(format_args!("{} {}", level, record.args()));
+                });
+            }
         }
     }
 
@@ -32,18 +237,152 @@ impl core::fmt::Write for Logger {
 }
 
 /// Setup the logging subsystem. All log submitted to the logging subsystem
-/// will be ignored until this function is called.
+/// will be ignored until this function is called. Messages are kept in
+/// memory until [`ready`] is called.
 ///
 /// # Panics
 /// This function will panic if the logging system is already initialized,
 /// meaning that this function was called more than once.
 #[cfg(feature = "logging")]
 pub fn setup() {
-    log::set_max_level(log::LevelFilter::Info);
+    log::set_max_level(LevelFilter::Info);
     log::set_logger(&Logger {}).unwrap();
     log::trace!("Logger initialized");
 }
 
+/// Applies the log options of the kernel command line. The `loglevel` option
+/// sets the global level (`off`, `error`, `warn`, `info`, `debug` or `trace`),
+/// and the `log.<module>` options set the level of a module and its
+/// submodules, such as `log.ipc=debug`. This must be called after the command
+/// line was set up.
+pub fn configure() {
+    if let Some(level) = cmdline::parse::<LevelFilter>("loglevel") {
+        _ = set_global_level(level);
+    }
+
+    for (key, value) in cmdline::options() {
+        let Some(module) = key.strip_prefix("log.") else {
+            continue;
+        };
+        let Ok(level) = value.parse::<LevelFilter>() else {
+            log::warn!("Ignoring invalid value \"{}\" for option {}", value, key);
+            continue;
+        };
+        if let Err(error) = set_module_level(module, Some(level)) {
+            log::warn!("Ignoring option {}: {:?}", key, error);
+        }
+    }
+}
+
+/// Marks the console as ready and prints the messages logged until now. This
+/// is called by the architecture once its console is set up.
+pub fn ready() {
+    READY.store(true, Ordering::Release);
+    flush();
+}
+
+/// Sets the global level of the log, and returns the previous one.
+#[must_use]
+pub fn set_global_level(level: LevelFilter) -> LevelFilter {
+    arch::irq::without(|| {
+        let mut levels = LEVELS.lock();
+        let previous = core::mem::replace(&mut levels.global, level);
+        log::set_max_level(levels.max());
+        previous
+    })
+}
+
+/// Sets the level of the given module and its submodules, or makes them use
+/// the level of their parent module again if `level` is `None`. The name of
+/// the module may start with the name of the kernel crate. Returns the level
+/// that the module previously had, if any.
+///
+/// # Errors
+/// Returns [`LevelError::ModuleTooLong`] if the name of the module is too
+/// long, or [`LevelError::TooManyModules`] if too many modules already have
+/// their own level.
+pub fn set_module_level(
+    module: &str,
+    level: Option<LevelFilter>,
+) -> Result<Option<LevelFilter>, LevelError> {
+    let module = String::try_from(strip_crate(module)).map_err(|_| LevelError::ModuleTooLong)?;
+    arch::irq::without(|| {
+        let mut levels = LEVELS.lock();
+        let index = levels.modules.iter().position(|(name, _)| *name == module);
+        let previous = match (index, level) {
+            (Some(index), Some(level)) => {
+                Some(core::mem::replace(&mut levels.modules[index].1, level))
+            }
+            (Some(index), None) => Some(levels.modules.swap_remove(index).1),
+            (None, Some(level)) => {
+                levels
+                    .modules
+                    .push((module, level))
+                    .map_err(|_| LevelError::TooManyModules)?;
+                None
+            }
+            (None, None) => None,
+        };
+        log::set_max_level(levels.max());
+        Ok(previous)
+    })
+}
+
+/// Returns at most `capacity` messages of the log, starting at the message
+/// with the given number, or at the oldest message kept if that message was
+/// already overwritten.
+#[must_use]
+pub fn read(sequence: u64, capacity: usize) -> Vec<Record> {
+    arch::irq::without(|| {
+        RING.lock()
+            .records
+            .iter()
+            .filter(|record| record.sequence >= sequence)
+            .take(capacity)
+            .copied()
+            .collect()
+    })
+}
+
+/// Converts a level of the `log` crate into a level of the syscall
+/// interface.
+fn raw_level(level: log::Level) -> ::syscall::log::Level {
+    match level {
+        log::Level::Error => ::syscall::log::Level::Error,
+        log::Level::Warn => ::syscall::log::Level::Warn,
+        log::Level::Info => ::syscall::log::Level::Info,
+        log::Level::Debug => ::syscall::log::Level::Debug,
+        log::Level::Trace => ::syscall::log::Level::Trace,
+    }
+}
+
+/// Removes the name of the kernel crate at the start of the given module
+/// path, if any.
+fn strip_crate(path: &str) -> &str {
+    path.strip_prefix(concat!(env!("CARGO_CRATE_NAME"), "::"))
+        .unwrap_or(path)
+}
+
+/// Prints the messages logged before the console was ready, if any. This
+/// does nothing if the buffer is already being printed or filled, which
+/// happens if the kernel panics while doing so. This is called when the
+/// console becomes ready, and before the system goes down.
+pub fn flush() {
+    arch::irq::without(|| {
+        let Some(mut early) = EARLY.try_lock() else {
+            return;
+        };
+        if !early.text.is_empty() {
+            write(&early.text);
+            early.text.clear();
+        }
+        if early.lost > 0 {
+            _ = writeln!(Logger {}, "{} early log messages lost", early.lost);
+            early.lost = 0;
+        }
+    });
+}
+
 /// Write a message to the log. This function is only by the internal
 /// logging functions, only included if the `log` feature is enabled.
 /// On most platforms, this function will write to the serial port or
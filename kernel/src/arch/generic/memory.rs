@@ -1,4 +1,4 @@
-use crate::arch::{self};
+use crate::arch::{self, mmu::Align};
 use heapless::Vec;
 
 pub use crate::arch::target::addr::{Frame, Frame1Gib, Frame2Mib, Frame4Kib, Physical};
@@ -26,6 +26,11 @@ pub struct UsableMemory {
 
     /// The end address of the RAM.
     pub ram_end: usize,
+
+    /// The physical memory holding the initial ramdisk given by the
+    /// bootloader, if any. This memory is not part of the usable regions, and
+    /// must be released once the ramdisk is no longer needed.
+    pub initrd: Option<Region>,
 }
 
 impl UsableMemory {
@@ -119,6 +124,44 @@ impl UsableMemory {
             .map_or(Physical::zero(), |addr| addr.page_align_down())
     }
 
+    /// Remove the given range of memory from the usable memory regions, so
+    /// that it is never allocated. The range is extended to whole pages. This
+    /// is used to preserve the memory filled by the bootloader until the kernel
+    /// is done with it.
+    ///
+    /// # Panics
+    /// Panics if a region must be split in two but there are already too many
+    /// regions to do so.
+    pub fn reserve(&mut self, reserved: Region) {
+        let start = reserved.start.page_align_down();
+        let end = reserved.end().page_align_up();
+
+        let mut regions = Vec::<Region, 32>::new();
+        for region in &self.regions {
+            if region.end() <= start || region.start >= end {
+                regions.push(*region).expect("Too many memory regions");
+                continue;
+            }
+            if region.start < start {
+                regions
+                    .push(Region {
+                        start: region.start,
+                        length: start - region.start,
+                    })
+                    .expect("Too many memory regions");
+            }
+            if region.end() > end {
+                regions
+                    .push(Region {
+                        start: end,
+                        length: region.end() - end,
+                    })
+                    .expect("Too many memory regions");
+            }
+        }
+        self.regions = regions;
+    }
+
     /// Convert the usable memory into a list of free memory regions.
     #[must_use]
     pub fn into_free_regions(self) -> Vec<Region, 32> {
@@ -152,3 +195,12 @@ impl Region {
         self.start + self.length
     }
 }
+
+/// Return the range of virtual addresses of the kernel image that is only used
+/// during boot, such as the code and data put in the `.init` section by the
+/// `init` and `initdata` macros. The range is page aligned, and can be
+/// reclaimed once the kernel has booted.
+#[must_use]
+pub fn reclaimable_range() -> core::ops::Range<usize> {
+    crate::arch::target::memory::reclaimable_range()
+}
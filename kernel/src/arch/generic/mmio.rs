@@ -0,0 +1,102 @@
+use crate::arch::target::addr::{Virtual, virt::Kernel};
+use core::marker::PhantomData;
+
+/// The registers of a device, mapped in the kernel address space with the
+/// memory attributes required by devices (see [`crate::arch::mmu::map_mmio`]).
+///
+/// The registers of the region are of type `T`, and are designated by their
+/// offset in bytes from the start of the region. They are always accessed with
+/// volatile reads and writes of the size of `T`, so that each access reaches
+/// the device exactly once and in program order.
+#[derive(Debug)]
+pub struct MmioRegion<T> {
+    /// The virtual address of the start of the region.
+    base: usize,
+
+    /// The size of the region, in bytes.
+    size: usize,
+
+    _register: PhantomData<T>,
+}
+
+impl<T: Copy> MmioRegion<T> {
+    /// Creates a region covering the `size` bytes mapped at the given
+    /// address.
+    ///
+    /// # Safety
+    /// The caller must ensure that the range is mapped with the memory
+    /// attributes of a device for as long as the region is used, and that it
+    /// only holds the registers of a device, never memory used by the kernel.
+    #[must_use]
+    pub const unsafe fn new(base: Virtual<Kernel>, size: usize) -> Self {
+        Self {
+            base: base.as_usize(),
+            size,
+            _register: PhantomData,
+        }
+    }
+
+    /// Returns the size of the region, in bytes.
+    #[must_use]
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Reads the register at the given offset.
+    ///
+    /// # Panics
+    /// Panics if the register is not entirely in the region, or if the offset
+    /// is not aligned to the size of a register.
+    #[must_use]
+    pub fn read(&self, offset: usize) -> T {
+        // SAFETY: The register is in the region, which is mapped as device
+        // memory, and the offset is properly aligned.
+        unsafe { self.register(offset).read_volatile() }
+    }
+
+    /// Writes the given value to the register at the given offset.
+    ///
+    /// # Panics
+    /// Panics if the register is not entirely in the region, or if the offset
+    /// is not aligned to the size of a register.
+    pub fn write(&self, offset: usize, value: T) {
+        // SAFETY: The register is in the region, which is mapped as device
+        // memory, and the offset is properly aligned.
+        unsafe { self.register(offset).write_volatile(value) }
+    }
+
+    /// Returns a region covering the `size` bytes at the given offset of this
+    /// region, whose registers are of type `U`. This allows a block of
+    /// registers of a different size to be accessed, such as 64-bit registers
+    /// in a device whose registers are mostly 32-bit.
+    ///
+    /// # Panics
+    /// Panics if the subregion is not entirely in the region.
+    #[must_use]
+    pub fn subregion<U: Copy>(&self, offset: usize, size: usize) -> MmioRegion<U> {
+        assert!(
+            offset.checked_add(size).is_some_and(|end| end <= self.size),
+            "MMIO subregion out of bounds (offset: {offset:#x}, size: {size:#x})"
+        );
+        MmioRegion {
+            base: self.base + offset,
+            size,
+            _register: PhantomData,
+        }
+    }
+
+    /// Returns a pointer to the register at the given offset, after checking
+    /// that it is entirely in the region and properly aligned.
+    fn register(&self, offset: usize) -> *mut T {
+        assert!(
+            offset
+                .checked_add(size_of::<T>())
+                .is_some_and(|end| end <= self.size),
+            "MMIO access out of bounds (offset: {offset:#x}, size: {:#x})",
+            self.size
+        );
+        let ptr = core::ptr::with_exposed_provenance_mut::<T>(self.base + offset);
+        assert!(ptr.is_aligned(), "Misaligned MMIO access at {offset:#x}");
+        ptr
+    }
+}
@@ -1,5 +1,8 @@
-pub use crate::arch::target::mmu::{PAGE_SHIFT, PAGE_SIZE};
+pub use crate::arch::target::mmu::{
+    KERNEL_HEAP_SIZE, KERNEL_HEAP_START, PAGE_SHIFT, PAGE_SIZE, USER_SPACE_PARTS,
+};
 use crate::arch::{
+    mmio::MmioRegion,
     target::addr::{self, Frame4Kib, Physical, Virtual, virt::Kernel},
     target::mmu::RootTable,
 };
@@ -108,6 +111,19 @@ bitflags! {
         /// to security issues or strange bugs that will be very, very hard
         /// to debug.
         const GLOBAL = 1 << 0;
+
+        /// Map a 2 MiB huge page instead of a 4 KiB page. Both the virtual
+        /// address and the frame must be aligned to 2 MiB.
+        const HUGE_2MB = 1 << 1;
+
+        /// Map a 1 GiB huge page instead of a 4 KiB page. Both the virtual
+        /// address and the frame must be aligned to 1 GiB.
+        const HUGE_1GB = 1 << 2;
+
+        /// The frame holds the registers of a device rather than memory. It is
+        /// not owned by the address space, and is not given back to the frame
+        /// allocator when the page is unmapped.
+        const DEVICE = 1 << 3;
     }
 }
 
@@ -115,11 +131,13 @@ bitflags! {
 /// physical address to a virtual address.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MapError {
-    /// A invalid combination of flags was given.
+    /// A invalid combination of flags was given, for example both
+    /// [`Flags::HUGE_2MB`] and [`Flags::HUGE_1GB`].
     InvalidFlagsCombination,
 
-    /// The frame is not aligned to the required size. The frame must be
-    /// aligned to either 4 KiB, 2 MiB or 1 GiB, depending on the flags given.
+    /// The frame or the virtual address is not aligned to the required size.
+    /// They must be aligned to either 4 KiB, 2 MiB or 1 GiB, depending on the
+    /// flags given.
     FrameNotAligned,
 
     /// The given frame was already mapped to another virtual address.
@@ -139,9 +157,28 @@ pub enum UnmapError {
     /// The given virtual address was not mapped to any physical address.
     NotMapped,
 
-    /// The mapped frame has a size that is not supported by the kernel.
-    /// Currently, only 4 KiB frames are supported by the unmap function.
-    UnsupportedFrameSize,
+    /// The address was mapped by a huge page that had to be split into
+    /// smaller pages, but the kernel ran out of memory while allocating the
+    /// page table needed to do so. The mapping is left unchanged.
+    OutOfMemory,
+}
+
+impl From<kcore::paging::MapError> for MapError {
+    fn from(error: kcore::paging::MapError) -> Self {
+        match error {
+            kcore::paging::MapError::AlreadyMapped => Self::AlreadyMapped,
+            kcore::paging::MapError::OutOfMemory => Self::OutOfMemory,
+        }
+    }
+}
+
+impl From<kcore::paging::UnmapError> for UnmapError {
+    fn from(error: kcore::paging::UnmapError) -> Self {
+        match error {
+            kcore::paging::UnmapError::NotMapped => Self::NotMapped,
+            kcore::paging::UnmapError::OutOfMemory => Self::OutOfMemory,
+        }
+    }
 }
 
 /// Map a physical address to a virtual address, allowing the kernel to
@@ -169,7 +206,9 @@ pub unsafe fn map<T: addr::virt::Type>(
 
 /// Unmap a virtual address, returning the physical address that was
 /// previously mapped to it. If the virtual address was not mapped to
-/// any physical address, this function will return an error.
+/// any physical address, this function will return an error. Only the 4 KiB
+/// page containing the address is unmapped: if it is part of a huge page, the
+/// huge page is first split into smaller pages.
 ///
 /// # Errors
 /// For an exhaustive list of errors that can happen when trying to unmap a
@@ -186,6 +225,110 @@ pub unsafe fn unmap<T: addr::virt::Type>(
     crate::arch::target::mmu::unmap(table, virt)
 }
 
+/// Map a frame in the kernel half of the address space. The page will be
+/// visible in all address spaces.
+///
+/// # Errors
+/// For an exhaustive list of errors that can happen when trying to map a
+/// physical address to a virtual address, see the [`MapError`] enum.
+///
+/// # Safety
+/// This function is unsafe because mapping a physical address to a virtual
+/// address can lead to memory safety issues in many ways. See the
+/// documentation in the architecture-specific implementation for more details.
+pub unsafe fn map_kernel(
+    virt: Virtual<Kernel>,
+    frame: Frame4Kib,
+    rights: Rights,
+    flags: Flags,
+) -> Result<(), MapError> {
+    crate::arch::target::mmu::map_kernel(virt, frame, rights, flags)
+}
+
+/// Allocate all the intermediate tables of the kernel half that are not yet
+/// allocated, so that mapping a page in the kernel half never allocates a
+/// table afterwards. Returns the number of tables allocated.
+///
+/// # Panics
+/// Panics if the kernel runs out of memory while allocating the tables.
+#[must_use]
+pub fn preallocate_kernel_tables() -> usize {
+    crate::arch::target::mmu::preallocate_kernel_tables()
+}
+
+/// Unmap a page from the kernel half of the address space, returning the
+/// frame that was mapped to it.
+///
+/// # Errors
+/// For an exhaustive list of errors that can happen when trying to unmap a
+/// virtual address, see the [`UnmapError`] enum.
+///
+/// # Safety
+/// This function is unsafe because unmapping a page still in use by the
+/// kernel leads to memory safety issues. See the documentation in the
+/// architecture-specific implementation for more details.
+pub unsafe fn unmap_kernel(virt: Virtual<Kernel>) -> Result<Frame4Kib, UnmapError> {
+    crate::arch::target::mmu::unmap_kernel(virt)
+}
+
+/// Get the rights granted to the page mapped at the given virtual address in
+/// the given table. Returns `None` if the address is not mapped.
+#[must_use]
+pub fn rights<T: addr::virt::Type>(table: &RootTable, virt: Virtual<T>) -> Option<Rights> {
+    crate::arch::target::mmu::rights(table, virt)
+}
+
+/// Translate the given virtual address using the given table, and return the
+/// 4 KiB frame containing the address along with the rights granted to the
+/// page. Returns `None` if the address is not mapped.
+#[must_use]
+pub fn translate<T: addr::virt::Type>(
+    table: &RootTable,
+    virt: Virtual<T>,
+) -> Option<(Frame4Kib, Rights)> {
+    crate::arch::target::mmu::translate(table, virt)
+}
+
+/// Check if the page mapped at the given virtual address in the given table
+/// maps the registers of a device (see [`Flags::DEVICE`]). Returns `false` if
+/// the address is not mapped.
+#[must_use]
+pub fn is_device<T: addr::virt::Type>(table: &RootTable, virt: Virtual<T>) -> bool {
+    crate::arch::target::mmu::is_device(table, virt)
+}
+
+/// Change the rights of the page mapped at the given virtual address in the
+/// given table.
+///
+/// # Errors
+/// For an exhaustive list of errors that can happen when trying to change the
+/// rights of a page, see the [`UnmapError`] enum.
+///
+/// # Safety
+/// This function is unsafe because removing rights from a page in use can
+/// lead to unexpected faults. See the documentation in the
+/// architecture-specific implementation for more details.
+pub unsafe fn protect<T: addr::virt::Type>(
+    table: &mut RootTable,
+    virt: Virtual<T>,
+    rights: Rights,
+) -> Result<(), UnmapError> {
+    crate::arch::target::mmu::protect(table, virt, rights)
+}
+
+/// Release a part of the user space of the given table, freeing all the
+/// tables and frames mapped in it. The user space is split into
+/// [`USER_SPACE_PARTS`] parts, allowing it to be released incrementally.
+/// Returns the number of frames released.
+///
+/// # Safety
+/// The caller must ensure that the memory mapped in the given part of the
+/// user space is no longer in use. See the documentation in the
+/// architecture-specific implementation for more details.
+pub unsafe fn release_user_space_part(table: &mut RootTable, part: usize) -> usize {
+    crate::arch::target::mmu::release_user_space_part(table, part)
+}
+
 /// Translate a physical address to a virtual address. If the translation
 /// cannot be done, this function will return `None`. This often happens when
 /// the physical address cannot be mapped to a virtual address because the
@@ -196,6 +339,33 @@ pub fn translate_physical(phys: impl Into<Physical>) -> Option<Virtual<Kernel>>
     crate::arch::target::mmu::translate_physical(phys)
 }
 
+/// Map the registers of a device, in the `size` bytes starting at the given
+/// physical address, in the kernel address space with the memory attributes
+/// required by devices, and return a region to access them. Contrary to
+/// [`translate_physical`], which gives access to RAM, accesses through this
+/// region are never cached nor reordered by the processor.
+///
+/// Returns `None` if the range is empty, or if the registers cannot be mapped
+/// with the memory attributes of a device, for example because they are in
+/// the same range as RAM.
+#[must_use]
+pub fn map_mmio<T: Copy>(base: Physical, size: usize) -> Option<MmioRegion<T>> {
+    let start = crate::arch::target::mmu::map_mmio(base, size)?;
+    // SAFETY: The range is mapped as device memory in the physical memory map
+    // of the kernel, which is never changed after boot.
+    Some(unsafe { MmioRegion::new(start, size) })
+}
+
+/// Translate a pointer in the kernel's address space to a physical address.
+///
+/// # Panics
+/// Panics if the pointer is not located in the kernel's address space, or if
+/// it is in the kernel heap range but is not mapped.
+#[must_use]
+pub fn translate_kernel_ptr<T>(ptr: *const T) -> Physical {
+    crate::arch::target::mmu::translate_kernel_ptr(ptr)
+}
+
 /// Set the current page table to the kernel page table. This will switch the
 /// current address space to a table only containing the kernel mappings. This
 /// is useful when destroying a user process, to avoid using a page table that
@@ -226,3 +396,11 @@ pub fn allow_user_page_access() {
 pub fn forbid_user_page_access() {
     crate::arch::target::mmu::forbid_user_page_access();
 }
+
+/// Make the instructions written at the given kernel address visible to
+/// instruction fetches. This must be called after the kernel writes code
+/// that will be executed, since the instruction cache is not kept coherent
+/// with the data cache on all architectures.
+pub fn sync_instructions(start: Virtual<Kernel>, len: usize) {
+    crate::arch::target::mmu::sync_instructions(start, len);
+}
@@ -1,11 +1,15 @@
+pub mod console;
 pub mod cpu;
+pub mod device;
 pub mod irq;
 pub mod log;
 pub mod memory;
+pub mod mmio;
 pub mod mmu;
 pub mod thread;
 pub mod timer;
 pub mod trap;
+pub mod user;
 
 /// Shutdown the system.
 pub fn shutdown() -> ! {
@@ -16,3 +20,10 @@ pub fn shutdown() -> ! {
 pub fn reboot() -> ! {
     crate::arch::target::reboot();
 }
+
+/// Shutdown the system, reporting to the emulator whether the integration
+/// tests passed so that it exits with a matching status (see
+/// [`crate::testing`]).
+pub fn exit(success: bool) -> ! {
+    crate::arch::target::exit(success);
+}
@@ -1,5 +1,6 @@
-pub use crate::arch::target::thread::Thread;
+pub use crate::arch::target::thread::{SINGLE_STEP, Thread};
 use crate::arch::trap::Trap;
+use ::syscall::task::Registers;
 
 /// Create a new thread with the given instruction pointer and stack pointer.
 #[must_use]
@@ -7,12 +8,37 @@ pub fn create(ip: usize, stack: usize) -> Thread {
     crate::arch::target::thread::create(ip, stack)
 }
 
+/// Create a new thread with the given instruction pointer and stack pointer,
+/// sharing the address space of the given thread.
+#[must_use]
+pub fn create_sibling(thread: &Thread, ip: usize, stack: usize) -> Thread {
+    crate::arch::target::thread::create_sibling(thread, ip, stack)
+}
+
+/// Set the argument at the given index (starting from zero) given to the
+/// entry point of the given thread. This must be called before the thread
+/// starts executing, and the index must be lower than 8.
+pub fn set_entry_argument(thread: &mut Thread, index: usize, value: usize) {
+    crate::arch::target::thread::set_entry_argument(thread, index, value);
+}
+
 /// Execute the given thread until a trap occurs and return to the caller.
 #[must_use]
 pub fn execute(thread: &mut Thread) -> Trap {
     crate::arch::target::thread::execute(thread)
 }
 
+/// Get the current stack pointer of the given thread.
+#[must_use]
+pub fn get_stack_pointer(thread: &Thread) -> usize {
+    crate::arch::target::thread::get_stack_pointer(thread)
+}
+
+/// Set the stack pointer of the given thread.
+pub fn set_stack_pointer(thread: &mut Thread, sp: usize) {
+    crate::arch::target::thread::set_stack_pointer(thread, sp);
+}
+
 /// Get the syscall identifier from the given thread.
 #[must_use]
 pub fn get_syscall_id(thread: &Thread) -> usize {
@@ -29,3 +55,23 @@ pub fn get_syscall_args(thread: &Thread) -> [usize; 6] {
 pub fn set_syscall_return(thread: &mut Thread, value: isize) {
     crate::arch::target::thread::set_syscall_return(thread, value);
 }
+
+/// Get the registers of the given thread, as seen by a debugger.
+#[must_use]
+pub fn get_registers(thread: &Thread) -> Registers {
+    crate::arch::target::thread::get_registers(thread)
+}
+
+/// Set the registers of the given thread from the registers given by a
+/// debugger.
+pub fn set_registers(thread: &mut Thread, registers: &Registers) {
+    crate::arch::target::thread::set_registers(thread, registers);
+}
+
+/// Enable or disable the single-stepping of the given thread: once enabled,
+/// the thread traps with [`Trap::Step`] after executing a single instruction.
+/// Returns `false` if single-stepping is not supported by the architecture
+/// (see [`SINGLE_STEP`]).
+pub fn set_single_step(thread: &mut Thread, enabled: bool) -> bool {
+    crate::arch::target::thread::set_single_step(thread, enabled)
+}
@@ -1,4 +1,10 @@
-use crate::config;
+use crate::{
+    arch::target::{
+        addr::{Virtual, virt::User},
+        mmu::RootTable,
+    },
+    config,
+};
 
 /// The stack used by the kernel to handle interrupts and exceptions. Kiwi
 /// has made the choice to use a single stack per core to handle interrupts
@@ -76,6 +82,11 @@ pub enum Trap {
     /// A syscall, which is a synchronous event directly triggered
     /// by the user-space application.
     Syscall,
+
+    /// A debug exception, raised after the thread executed a single
+    /// instruction while it was single-stepped (see
+    /// [`crate::arch::thread::set_single_step`]).
+    Step,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -83,10 +94,16 @@ pub enum Resume {
     /// Continue the execution of the thread where it was interrupted.
     Continue,
 
-    /// Terminate the execution of the thread. This is used when the
-    /// thread has finished its execution with an exit syscall.
+    /// Terminate the execution of the thread and of all the other threads
+    /// of its task. This is used when the thread has finished its execution
+    /// with a task exit syscall.
     Terminate(i32),
 
+    /// Terminate the execution of the thread, but not of the other threads
+    /// of its task. This is used when the thread exits with a thread exit
+    /// syscall.
+    ExitThread(i32),
+
     /// Yield the CPU to another thread.
     Yield,
 
@@ -94,6 +111,66 @@ pub enum Resume {
     Fault,
 }
 
+/// The kind of fault that caused a thread to be terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    /// A fault that does not fit in any other category.
+    Unknown,
+
+    /// An access to an unmapped page, or a page mapped without the required
+    /// permissions.
+    PageFault,
+
+    /// An access to a physical address that does not exist or cannot be
+    /// accessed.
+    AccessFault,
+
+    /// An invalid instruction.
+    IllegalInstruction,
+
+    /// A misaligned memory access.
+    Misaligned,
+}
+
+/// The cause of a page fault, determined by walking the page table of the
+/// faulting address space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultCause {
+    /// The faulting address is not mapped. Depending on the address, the
+    /// kernel may be able to resolve the fault by mapping the page.
+    NotPresent,
+
+    /// The faulting address is mapped, but the rights of the page do not
+    /// allow the access, or the address is outside of the user address space.
+    Protection,
+}
+
+impl FaultCause {
+    /// Classify a page fault at the given address in the given page table.
+    #[must_use]
+    pub fn classify(table: &RootTable, address: usize) -> Self {
+        match Virtual::<User>::try_new(address) {
+            Some(virt) if crate::arch::mmu::rights(table, virt).is_none() => Self::NotPresent,
+            _ => Self::Protection,
+        }
+    }
+}
+
+/// Return the kind of the last exception. This must be called right after
+/// the exception was handled, before any other trap can occur.
+#[must_use]
+pub fn fault_kind() -> FaultKind {
+    crate::arch::target::trap::fault_kind()
+}
+
+/// Return the faulting address of the last exception if it was caused by an
+/// invalid memory access, or `None` otherwise. This must be called right
+/// after the exception was handled, before any other trap can occur.
+#[must_use]
+pub fn fault_address() -> Option<usize> {
+    crate::arch::target::trap::fault_address()
+}
+
 pub fn handle_exception(thread: &mut crate::arch::thread::Thread) -> Resume {
     crate::arch::target::trap::handle_exception(thread)
 }
@@ -0,0 +1,23 @@
+/// A fault that occurred while the kernel was accessing user memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fault {
+    /// The faulting address.
+    pub address: usize,
+}
+
+/// Copy `len` bytes from `src` to `dst`, where one of them is in the user
+/// address space. Contrary to a plain copy, a fault during the copy does not
+/// crash the kernel but is reported to the caller.
+///
+/// # Errors
+/// Returns a [`Fault`] if the copy faulted, for example because the user
+/// memory is not mapped. In this case, the content of the destination is
+/// unspecified.
+///
+/// # Safety
+/// The caller must ensure that the kernel memory is valid for the copy and
+/// that access to user pages is allowed. Interrupts must be disabled during
+/// the copy.
+pub unsafe fn copy(dst: *mut u8, src: *const u8, len: usize) -> Result<(), Fault> {
+    crate::arch::target::user::copy(dst, src, len)
+}
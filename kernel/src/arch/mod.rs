@@ -7,6 +7,11 @@ pub mod riscv64;
 #[cfg(target_arch = "riscv64")]
 pub use riscv64 as target;
 
+#[cfg(target_arch = "aarch64")]
+pub mod aarch64;
+#[cfg(target_arch = "aarch64")]
+pub use aarch64 as target;
+
 pub mod generic;
 pub use generic::*;
 
@@ -1,10 +1,21 @@
-.equ KERNEL_VIRTUAL_BASE, 0xFFFFFFFFC0000000
-.equ KERNEL_PHYSICAL_BASE, 0x80000000
+.equ KERNEL_START, 0xFFFFFFC000000000
 
-.macro LA_FAR, reg, sym
-	lui \reg, %hi(\sym)
-	addi \reg, \reg, %lo(\sym)
-.endm
+# The difference between the physical address and the link address of the
+# sections of the kernel image linked in the kernel virtual base
+.equ PHYSICAL_OFFSET, 0x80000000 - 0xFFFFFFFFC0000000
+
+# The size of the granularity of the kernel slide, and the number of 2 MiB
+# regions mapped for the kernel image by the boot page table
+.equ SLIDE_ALIGN_SHIFT, 21
+.equ IMAGE_REGIONS, {image_regions}
+
+# The index of the entry of the boot image table mapping the start of the
+# kernel image when the kernel is not slid
+.equ IMAGE_FIRST_REGION, 1
+
+# The type of the only relocations found in the kernel image. The linker may
+# also leave empty relocations, whose type is zero, which are skipped
+.equ R_RISCV_RELATIVE, 3
 
 .section .early, "ax"
 .globl _start
@@ -17,46 +28,159 @@ _start:
   csrw sie, zero
   csrc sip, zero
 
-  # Setup satp with sv39 mode and the boot page table
-  la t0, boot_page_table
+  # Choose the slide of the kernel image in s1, a multiple of 2 MiB derived
+  # from the time counter. This is the only source of entropy available this
+  # early, before the device tree can be parsed
+  li s1, 0
+  li t0, {randomize}
+  beqz t0, .slide_chosen
+  rdtime t0
+  li t1, 0x9E3779B97F4A7C15
+  mul t0, t0, t1
+  srli t0, t0, 64 - {slide_bits}
+  slli s1, t0, SLIDE_ALIGN_SHIFT
+.slide_chosen:
+
+  # Apply the relocations of the kernel image. Paging is still disabled,
+  # so each relocated word is written through its physical address. The
+  # addresses of the symbols of the kernel image are computed from their
+  # offsets to `_start`, since they are too far away to be addressed
+  # relative to the program counter
+  lla t6, _start
+  li t5, PHYSICAL_OFFSET
+  add t5, t5, t6
+  ld t0, .rela_start_offset
+  ld t1, .rela_end_offset
+  add t0, t0, t5
+  add t1, t1, t5
+  li t2, PHYSICAL_OFFSET
+.relocate:
+  bgeu t0, t1, .relocated
+  ld t3, 0(t0)
+  ld t4, 8(t0)
+  addi t0, t0, 24
+  beqz t4, .relocate
+  li a3, R_RISCV_RELATIVE
+  bne t4, a3, .halt
+  ld t4, -8(t0)
+  add t3, t3, t2
+  add t4, t4, s1
+  sd t4, 0(t3)
+  j .relocate
+.relocated:
+
+  # Clear the BSS section with zeros, also through its physical address
+  ld t0, .bss_start_offset
+  ld t1, .bss_end_offset
+  add t0, t0, t5
+  add t1, t1, t5
+.bss_clear:
+  bgeu t0, t1, .bss_cleared
+  sd zero, (t0)
+  addi t0, t0, 8
+  j .bss_clear
+.bss_cleared:
+
+  # Map the kernel image at its slid address with 2 MiB pages in the boot
+  # image table, and link the table in the last entry of the boot page table
+  lla t0, boot_image_table
+  srli t1, s1, SLIDE_ALIGN_SHIFT - 3
+  add t0, t0, t1
+  addi t0, t0, IMAGE_FIRST_REGION * 8
+  srli t1, t6, 12
+  slli t1, t1, 10
+  ori t1, t1, 0xF
+  li t2, 1 << (SLIDE_ALIGN_SHIFT - 2)
+  li t3, IMAGE_REGIONS
+.map_image:
+  sd t1, 0(t0)
+  add t1, t1, t2
+  addi t0, t0, 8
+  addi t3, t3, -1
+  bnez t3, .map_image
+
+  lla t0, boot_image_table
   srli t0, t0, 12
+  slli t0, t0, 10
+  ori t0, t0, 0x1
+  lla t1, boot_page_table
+  li t2, 511 * 8
+  add t2, t2, t1
+  sd t0, 0(t2)
+
+  # Setup satp with sv39 mode and the boot page table
+  srli t0, t1, 12
   li t1, 8
   slli t1, t1, 60
   or t0, t0, t1
   csrw satp, t0
+  sfence.vma
 
-  # Clear the BSS section with zeros
-	LA_FAR t2, __bss_start
-  LA_FAR t3, __bss_end
-.bss_clear:
-	sd zero, (t2)
-	addi t2, t2, 8
-	bltu t2, t3, .bss_clear
-
-  # Update the pointer in a1 to use the kernel virtual base
-  la t0, KERNEL_PHYSICAL_BASE
-  la t1, KERNEL_VIRTUAL_BASE
-  sub a1, a1, t0
-  add a1, a1, t1
-
-  # Setup the stack pointer and jump to the entry point
-  LA_FAR sp, boot_stack_top
-  LA_FAR t0, entry
+  # Update the device tree pointer in a1 to go through the physical memory
+  # map, since the kernel virtual base only maps the kernel image once the
+  # kernel table is set up, and give the slide to the entry point in a2
+  li t0, KERNEL_START
+  add a1, a1, t0
+  mv a2, s1
+
+  # Setup the stack pointer and jump to the entry point, both at their slid
+  # addresses. The frame pointer is cleared so that backtraces stop at the
+  # entry point
+  ld sp, .stack_top_offset
+  add sp, sp, t6
+  add sp, sp, s1
+  ld t0, .entry_offset
+  add t0, t0, t6
+  add t0, t0, s1
+  mv s0, zero
   jr t0
 
+  # An unexpected relocation was found, and the kernel cannot be relocated
+.halt:
+  wfi
+  j .halt
+
   .option pop
 
-# The boot page table
+# The offsets of the symbols used above to `_start`, computed by the linker
+.align 3
+.rela_start_offset:
+  .quad __rela_start - _start
+.rela_end_offset:
+  .quad __rela_end - _start
+.bss_start_offset:
+  .quad __bss_start - _start
+.bss_end_offset:
+  .quad __bss_end - _start
+.stack_top_offset:
+  .quad boot_stack_top - _start
+.entry_offset:
+  .quad entry - _start
+
+# The boot page table. It identity maps the first 3 GiB of physical memory,
+# maps the first 3 GiB of the physical memory map at `KERNEL_START`, and the
+# kernel image at the kernel virtual base through the boot image table
 .align 12
 boot_page_table:
   .quad 0x000000000000000F
   .quad 0x000000001000000F
   .quad 0x000000002000000F
-  .fill 508, 8, 0
+  .fill 253, 8, 0
+  .quad 0x000000000000000F
+  .quad 0x000000001000000F
   .quad 0x000000002000000F
+  .fill 253, 8, 0
+
+# The boot image table, mapping the last 1 GiB of virtual memory
+.align 12
+boot_image_table:
+  .fill 512, 8, 0
 
-# Reserve 64 KiB for the boot stack
-.section .bss
+# Reserve 64 KiB for the boot stack, in its own section so that the linker
+# script can put a guard page below it
+.section .stack, "aw", @nobits
+.globl boot_stack_bottom
+.globl boot_stack_top
 boot_stack_bottom:
 .space 64 * 1024
 boot_stack_top:
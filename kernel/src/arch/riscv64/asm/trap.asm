@@ -51,6 +51,8 @@ kernel_trap:
   sd x30, 29*8(sp)
   sd x31, 30*8(sp)
 
+  # Give the saved registers to the handler
+  mv a0, sp
   call kernel_trap_handler
 
   # Restore all registers
@@ -0,0 +1,51 @@
+# Copy bytes between user and kernel memory. If the copy faults, the kernel
+# trap handler resumes the execution at `user_copy_fault` instead of panicking,
+# which reports the fault to the caller.
+#
+# Parameters:
+#   a0: destination address
+#   a1: source address
+#   a2: number of bytes to copy
+#
+# Returns:
+#   a0: 0 if the copy succeeded, 1 if a fault occurred during the copy
+.section .text
+.globl user_copy
+.globl user_copy_end
+.globl user_copy_fault
+.align 4
+user_copy:
+  # Copy 8 bytes at a time if both addresses are aligned on 8 bytes
+  or t1, a0, a1
+  andi t1, t1, 7
+  bnez t1, 2f
+  li t2, 8
+1:
+  bltu a2, t2, 2f
+  ld t0, 0(a1)
+  sd t0, 0(a0)
+  addi a0, a0, 8
+  addi a1, a1, 8
+  addi a2, a2, -8
+  j 1b
+
+  # Copy the remaining bytes one by one
+2:
+  beqz a2, 3f
+  lb t0, 0(a1)
+  sb t0, 0(a0)
+  addi a0, a0, 1
+  addi a1, a1, 1
+  addi a2, a2, -1
+  j 2b
+3:
+  li a0, 0
+  ret
+user_copy_end:
+
+# The trap handler jumps here if a fault occurs between `user_copy` and
+# `user_copy_end`. Since `user_copy` is a leaf function, the return address
+# is still valid and we can directly return to the caller.
+user_copy_fault:
+  li a0, 1
+  ret
@@ -0,0 +1,30 @@
+use super::uart;
+
+/// Write the given bytes to the UART, or to the SBI console if there is no
+/// UART.
+pub fn write(bytes: &[u8]) {
+    if !uart::write(bytes) {
+        for &c in bytes {
+            sbi::legacy::console_putchar(c);
+        }
+    }
+}
+
+/// Move the bytes received by the UART, or by the SBI console if there is no
+/// UART, to the console input.
+pub fn poll() {
+    if uart::available() {
+        uart::poll();
+    } else {
+        while let Some(byte) = sbi::legacy::console_getchar() {
+            crate::arch::console::receive(byte);
+        }
+    }
+}
+
+/// Return whether the console input is delivered by an interrupt. This is
+/// never the case for the SBI console, which must be polled.
+#[must_use]
+pub fn interrupt_driven() -> bool {
+    uart::interrupt_driven()
+}
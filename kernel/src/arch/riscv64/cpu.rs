@@ -1,16 +1,111 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// The extension was not probed yet.
+const HSM_UNKNOWN: u8 = 0;
+
+/// The extension is implemented, and is used to suspend idle harts.
+const HSM_AVAILABLE: u8 = 1;
+
+/// The extension is not implemented, and idle harts use `wfi`.
+const HSM_UNAVAILABLE: u8 = 2;
+
+/// Whether the firmware implements the SBI Hart State Management extension,
+/// probed the first time the hart goes idle: [`HSM_UNKNOWN`] until then, and
+/// [`HSM_AVAILABLE`] or [`HSM_UNAVAILABLE`] afterwards.
+static HSM: AtomicU8 = AtomicU8::new(HSM_UNKNOWN);
+
 /// Relaxes the CPU by waiting for an interrupt. This function use the `wfi`
 /// instruction to wait for an interrupt and give an hint to the CPU that it
 /// can enter a low power state. However, the caller should not rely on this
 /// function to put the CPU in a low power state, as this instruction can be
 /// implemented as a no-op on some platforms according to the RISC-V
 /// specification.
+///
+/// Interrupts are disabled while waiting and external interrupts are
+/// unmasked, so that an external interrupt masked while the kernel was running
+/// still wakes up the CPU. Pending interrupts are taken when interrupts are
+/// enabled again, if they were enabled before.
 #[inline]
 pub fn relax() {
+    wait(wfi);
+}
+
+/// Puts the hart in a low power state until an interrupt is pending, like
+/// [`relax`]. The hart is suspended with a retentive suspend of the SBI Hart
+/// State Management extension when the firmware implements it, which lets the
+/// firmware pick a deeper state than `wfi` alone, and waits with `wfi`
+/// otherwise or if the suspend fails.
+pub fn idle() {
+    wait(|| {
+        // SAFETY: A retentive suspend preserves all the registers and CSRs,
+        // and returns like a normal SBI call when the hart is woken up.
+        let suspended = hsm_available()
+            && unsafe {
+                sbi::hart_state_management::hart_suspend(
+                    sbi::hart_state_management::SuspendType::DefaultRetentive,
+                )
+            }
+            .is_ok();
+        if !suspended {
+            wfi();
+        }
+    });
+}
+
+/// Calls `sleep` with interrupts disabled and external interrupts unmasked,
+/// so that any interrupt wakes up the hart without being taken, then restores
+/// the interrupt state.
+fn wait(sleep: impl FnOnce()) {
+    let enabled = super::irq::enabled();
+    super::irq::disable();
+    super::irq::unmask_external();
+    sleep();
+    if enabled {
+        // SAFETY: Interrupts were enabled before waiting.
+        unsafe {
+            super::irq::enable();
+        }
+    }
+}
+
+/// Waits for an interrupt with the `wfi` instruction.
+fn wfi() {
+    // SAFETY: Waiting for an interrupt has no side effects.
     unsafe {
         core::arch::asm!("wfi");
     }
 }
 
+/// Returns whether the firmware implements the SBI Hart State Management
+/// extension, probing it the first time.
+fn hsm_available() -> bool {
+    match HSM.load(Ordering::Relaxed) {
+        HSM_AVAILABLE => true,
+        HSM_UNAVAILABLE => false,
+        _ => {
+            let available =
+                sbi::base::probe_extension(sbi::hart_state_management::EXTENSION_ID).is_available();
+            let state = if available {
+                HSM_AVAILABLE
+            } else {
+                HSM_UNAVAILABLE
+            };
+            HSM.store(state, Ordering::Relaxed);
+            available
+        }
+    }
+}
+
+/// Returns the index of the current hart. Kiwi currently only runs on the boot
+/// hart, which is always given the index 0 regardless of its hart identifier.
+/// When SMP support is added, the index of each hart will be stored in its
+/// `tp` register when it is started.
+#[inline]
+#[must_use]
+pub const fn id() -> usize {
+    0
+}
+
 /// Freezes the CPU by entering an infinite loop. This function is used to
 /// stop the CPU from executing instructions and is used to halt the CPU.
 /// This function should not return and should be used to stop the CPU from
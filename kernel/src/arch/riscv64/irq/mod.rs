@@ -0,0 +1,145 @@
+use super::uart;
+
+pub mod plic;
+
+/// Enable interrupts.
+///
+/// # Safety
+/// This function is unsafe because it can break invariants of other code.
+/// Enabling interrupts could lead to memory unsafety, race conditions,
+/// deadlocks, and other undefined behavior.
+pub unsafe fn enable() {
+    riscv::register::sstatus::set_sie();
+}
+
+/// Disable interrupts. No interrupt will be triggered until interrupts
+/// are enabled again. However, exceptions will still be triggered.
+pub fn disable() {
+    // SAFETY: Disabling interrupts should be safe and should
+    // not cause any side effect that could lead to undefined
+    // behavior.
+    unsafe {
+        riscv::register::sstatus::clear_sie();
+    }
+}
+
+/// Check if interrupts are enabled.
+#[must_use]
+pub fn enabled() -> bool {
+    riscv::register::sstatus::read().sie()
+}
+
+/// Initialize the interrupt controller of the given hart and enable external
+/// interrupts. The interrupt of the UART is routed to the kernel, and all the
+/// other sources stay disabled until they are bound to a user space driver.
+pub fn setup(device_tree: &fdt::Fdt, hart: usize) {
+    plic::setup(device_tree, hart);
+    let Some(plic) = plic::get() else {
+        return;
+    };
+
+    if let Some(irq) = uart::irq() {
+        plic.enable(irq);
+    }
+
+    // SAFETY: External interrupts are only taken while a user thread runs or
+    // when the kernel polls them, and both paths handle them.
+    unsafe {
+        riscv::register::sie::set_sext();
+    }
+}
+
+/// Return whether the given source can be bound to a user space driver: it
+/// must exist, and must not be used by the kernel itself.
+#[must_use]
+pub fn bindable(irq: usize) -> bool {
+    plic::get().is_some_and(|plic| plic.contains(irq)) && uart::irq() != Some(irq)
+}
+
+/// Return whether the platform has an interrupt controller driven by the
+/// kernel.
+#[must_use]
+pub fn controller_available() -> bool {
+    plic::get().is_some()
+}
+
+/// Mask the given source, preventing it from raising interrupts.
+pub fn mask(irq: usize) {
+    if let Some(plic) = plic::get() {
+        plic.disable(irq);
+    }
+}
+
+/// Unmask the given source, allowing it to raise interrupts again.
+pub fn unmask(irq: usize) {
+    if let Some(plic) = plic::get() {
+        plic.enable(irq);
+    }
+}
+
+/// Route the given source to the hart with the given index. Returns `false`
+/// if the source does not exist or if the PLIC has no context for this hart.
+#[must_use]
+pub fn set_affinity(irq: usize, cpu: usize) -> bool {
+    plic::get().is_some_and(|plic| plic.route(irq, cpu))
+}
+
+/// Handle all the pending external interrupts. The interrupts of the UART are
+/// handled by the kernel, while the others are masked and forwarded to the
+/// task they are bound to by the executor, which unmasks them once it has
+/// serviced its device. Sources that are not bound to any task stay masked.
+pub fn handle_external() {
+    let Some(plic) = plic::get() else {
+        return;
+    };
+
+    while let Some(irq) = plic.claim() {
+        if uart::irq() == Some(irq) {
+            uart::handle_interrupt();
+        } else {
+            plic.disable(irq);
+            crate::future::deferred::defer(notify, irq);
+        }
+        plic.complete(irq);
+    }
+}
+
+/// Notify the task bound to the given source that it raised an interrupt.
+/// This is deferred to the executor by [`handle_external`].
+fn notify(irq: usize) {
+    if !crate::user::irq::notify(irq) {
+        log::warn!("Spurious interrupt {} not bound to any task", irq);
+    }
+}
+
+/// Handle the external interrupts that are pending while the kernel was not
+/// running a user thread. The kernel does not handle external interrupts
+/// while it runs, so they would otherwise only be handled the next time a user
+/// thread is interrupted.
+pub fn handle_pending() {
+    if riscv::register::sip::read().sext() {
+        handle_external();
+    }
+    unmask_external();
+}
+
+/// Mask external interrupts until [`unmask_external`] is called. When the
+/// watchdog lets the timer interrupt the kernel, an external interrupt can
+/// also be raised while the kernel runs. It cannot be handled there since the
+/// kernel may hold the locks needed to handle it, so external interrupts are
+/// masked and left pending until the kernel is ready to handle them.
+pub fn mask_external() {
+    // SAFETY: Masking external interrupts only delays their handling.
+    unsafe {
+        riscv::register::sie::clear_sext();
+    }
+}
+
+/// Unmask external interrupts masked by [`mask_external`].
+pub fn unmask_external() {
+    // SAFETY: External interrupts are only taken while a user thread runs or
+    // when the kernel polls them, and both paths handle them.
+    unsafe {
+        riscv::register::sie::set_sext();
+    }
+}
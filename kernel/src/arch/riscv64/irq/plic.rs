@@ -0,0 +1,226 @@
+use crate::arch::{self, mmio::MmioRegion, target::addr::Physical};
+
+/// The index of the boot hart, whose context is the only one initialized.
+const BOOT_CPU: usize = 0;
+
+/// The offset of the priority registers, one 32-bit register per source.
+const PRIORITY_OFFSET: usize = 0x0000;
+
+/// The offset of the enable bits of the first context. Each context has 1024
+/// bits, one per source.
+const ENABLE_OFFSET: usize = 0x2000;
+
+/// The size of the enable bits of a context, in bytes.
+const ENABLE_STRIDE: usize = 0x80;
+
+/// The offset of the threshold register of the first context. The claim and
+/// complete register of a context is located just after its threshold.
+const CONTEXT_OFFSET: usize = 0x20_0000;
+
+/// The size of the registers of a context, in bytes.
+const CONTEXT_STRIDE: usize = 0x1000;
+
+/// The offset of the claim and complete register from the threshold register
+/// of a context.
+const CLAIM_OFFSET: usize = 4;
+
+/// The maximum number of sources supported by the PLIC specification. The
+/// source 0 does not exist and is used to report that no interrupt is
+/// pending.
+const MAX_SOURCES: usize = 1024;
+
+/// The cause reported in the `interrupts-extended` property of the PLIC for a
+/// context that targets the supervisor mode of a hart.
+const SUPERVISOR_EXTERNAL: u32 = 9;
+
+/// The PLIC of the system, if one was found in the device tree.
+static PLIC: spin::Once<Plic> = spin::Once::new();
+
+/// A RISC-V Platform-Level Interrupt Controller, which routes the interrupts
+/// of devices to the harts. Only the supervisor context of the boot hart is
+/// used, since the kernel runs on a single hart.
+///
+/// All sources are given the same priority and the threshold is left at 0,
+/// so that the PLIC is only used to enable or disable sources: a source is
+/// delivered if and only if it is enabled.
+#[derive(Debug)]
+pub struct Plic {
+    /// The registers of the PLIC.
+    registers: MmioRegion<u32>,
+
+    /// The number of sources, given by the `riscv,ndev` property. Sources are
+    /// numbered from 1 to this value inclusive.
+    sources: usize,
+
+    /// The context of the PLIC that targets the supervisor mode of the boot
+    /// hart.
+    context: usize,
+}
+
+impl Plic {
+    /// Reads the 32-bit register at the given offset.
+    fn read(&self, offset: usize) -> u32 {
+        self.registers.read(offset)
+    }
+
+    /// Writes the 32-bit register at the given offset.
+    fn write(&self, offset: usize, value: u32) {
+        self.registers.write(offset, value);
+    }
+
+    /// Returns the offset of the threshold register of the context used by
+    /// the kernel.
+    const fn context_offset(&self) -> usize {
+        CONTEXT_OFFSET + self.context * CONTEXT_STRIDE
+    }
+
+    /// Returns the offset of the enable register holding the bit of the given
+    /// source, and the mask of this bit.
+    fn enable_bit(&self, source: usize) -> (usize, u32) {
+        let offset = ENABLE_OFFSET + self.context * ENABLE_STRIDE + (source / 32) * 4;
+        (offset, 1 << (source % 32))
+    }
+
+    /// Returns whether the given source exists.
+    #[must_use]
+    pub const fn contains(&self, source: usize) -> bool {
+        source != 0 && source <= self.sources
+    }
+
+    /// Enables the given source, allowing it to raise interrupts. Sources that
+    /// do not exist are ignored.
+    pub fn enable(&self, source: usize) {
+        if self.contains(source) {
+            let (offset, bit) = self.enable_bit(source);
+            self.write(offset, self.read(offset) | bit);
+        }
+    }
+
+    /// Disables the given source. An interrupt already claimed from it must
+    /// still be completed. Sources that do not exist are ignored.
+    pub fn disable(&self, source: usize) {
+        if self.contains(source) {
+            let (offset, bit) = self.enable_bit(source);
+            self.write(offset, self.read(offset) & !bit);
+        }
+    }
+
+    /// Routes the given source to the hart with the given index. A source is
+    /// routed to a hart by enabling it in the context of this hart, but only
+    /// the context of the boot hart is initialized: sources are already routed
+    /// to it, and cannot be routed to the other harts. Returns whether the
+    /// source exists and is routed to the hart.
+    #[must_use]
+    pub const fn route(&self, source: usize, cpu: usize) -> bool {
+        self.contains(source) && cpu == BOOT_CPU
+    }
+
+    /// Claims the pending interrupt with the highest priority, and returns its
+    /// source. Returns `None` if no interrupt is pending. The source will not
+    /// raise another interrupt until the claim is completed with
+    /// [`Self::complete`].
+    #[must_use]
+    pub fn claim(&self) -> Option<usize> {
+        let source = self.read(self.context_offset() + CLAIM_OFFSET) as usize;
+        (source != 0).then_some(source)
+    }
+
+    /// Completes the claim of an interrupt of the given source, allowing the
+    /// source to raise interrupts again.
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn complete(&self, source: usize) {
+        self.write(self.context_offset() + CLAIM_OFFSET, source as u32);
+    }
+}
+
+/// Find the PLIC in the device tree and initialize the context targeting the
+/// supervisor mode of the given hart, with all sources disabled. If no PLIC
+/// is found, external interrupts are not supported and devices can only be
+/// polled.
+pub fn setup(device_tree: &fdt::Fdt, hart: usize) {
+    let Some(node) = device_tree.find_compatible(&["riscv,plic0", "sifive,plic-1.0.0"]) else {
+        log::info!("No PLIC found, external interrupts are disabled");
+        return;
+    };
+    let Some(region) = node.reg().and_then(|mut reg| reg.next()) else {
+        log::warn!("PLIC without registers, external interrupts are disabled");
+        return;
+    };
+    let Some(context) = supervisor_context(device_tree, &node, hart) else {
+        log::warn!(
+            "No PLIC context for hart {}, external interrupts are disabled",
+            hart
+        );
+        return;
+    };
+
+    let physical = Physical::new(region.starting_address.addr());
+    let size = region
+        .size
+        .unwrap_or(CONTEXT_OFFSET + (context + 1) * CONTEXT_STRIDE);
+    let Some(registers) = arch::mmu::map_mmio(physical, size) else {
+        log::warn!("PLIC registers cannot be mapped, external interrupts are disabled");
+        return;
+    };
+    let plic = Plic {
+        registers,
+        sources: node
+            .property("riscv,ndev")
+            .and_then(fdt::node::NodeProperty::as_usize)
+            .unwrap_or(0)
+            .min(MAX_SOURCES - 1),
+        context,
+    };
+
+    for source in 1..=plic.sources {
+        plic.write(PRIORITY_OFFSET + source * 4, 1);
+        plic.disable(source);
+    }
+    plic.write(plic.context_offset(), 0);
+
+    log::info!(
+        "PLIC at {:#010x} with {} sources (context {})",
+        region.starting_address.addr(),
+        plic.sources,
+        plic.context
+    );
+    PLIC.call_once(|| plic);
+}
+
+/// Return the PLIC of the system, or `None` if there is none.
+#[must_use]
+pub fn get() -> Option<&'static Plic> {
+    PLIC.get()
+}
+
+/// Find the context of the PLIC that targets the supervisor mode of the given
+/// hart. The `interrupts-extended` property of the PLIC lists its contexts in
+/// order, each one as a pair of the phandle of the interrupt controller of a
+/// hart and the cause raised on this hart.
+fn supervisor_context(
+    device_tree: &fdt::Fdt,
+    plic: &fdt::node::FdtNode,
+    hart: usize,
+) -> Option<usize> {
+    let intc = device_tree
+        .find_node("/cpus")?
+        .children()
+        .find(|cpu| {
+            cpu.property("reg")
+                .and_then(fdt::node::NodeProperty::as_usize)
+                == Some(hart)
+        })?
+        .children()
+        .find(|child| child.name == "interrupt-controller")?
+        .property("phandle")?
+        .as_usize()?;
+
+    plic.property("interrupts-extended")?
+        .value
+        .chunks_exact(8)
+        .position(|pair| {
+            let phandle = u32::from_be_bytes([pair[0], pair[1], pair[2], pair[3]]);
+            let cause = u32::from_be_bytes([pair[4], pair[5], pair[6], pair[7]]);
+            phandle as usize == intc && cause == SUPERVISOR_EXTERNAL
+        })
+}
@@ -1,7 +1,5 @@
-/// Write a message to the sbi console.
+/// Write a message to the console. Before the UART is initialized, and on
+/// platforms without a UART, the message is written to the SBI console.
 pub fn write(message: &str) {
-    message
-        .as_bytes()
-        .iter()
-        .for_each(|&c| sbi::legacy::console_putchar(c));
+    super::console::write(message.as_bytes());
 }
@@ -97,13 +97,49 @@ impl UsableMemory {
             );
         }
 
-        Self {
+        let mut memory = Self {
             regions,
             firmware_memory,
             kernel_memory,
             total_memory,
             ram_start,
             ram_end,
+            initrd: None,
+        };
+
+        // Keep the initial ramdisk loaded by the bootloader out of the usable
+        // memory, so that it is not overwritten before being parsed.
+        if let Some(initrd) = initrd(device_tree) {
+            ::log::info!(
+                "Initial ramdisk: {:#010x} - {:#010x}",
+                initrd.start,
+                initrd.end()
+            );
+            memory.reserve(initrd);
+            memory.initrd = Some(initrd);
         }
+        memory
     }
 }
+
+/// Find the initial ramdisk loaded by the bootloader in the device tree. Its
+/// location is given by the `linux,initrd-start` and `linux,initrd-end`
+/// properties of the `/chosen` node, which are used by both QEMU and U-Boot.
+/// Returns `None` if there is no initial ramdisk or if it is empty.
+fn initrd(device_tree: &fdt::Fdt) -> Option<Region> {
+    let chosen = device_tree.find_node("/chosen")?;
+    let start = chosen.property("linux,initrd-start")?.as_usize()?;
+    let end = chosen.property("linux,initrd-end")?.as_usize()?;
+    (end > start).then(|| Region {
+        start,
+        length: end - start,
+    })
+}
+
+/// Return the range of virtual addresses of the kernel image that is only used
+/// during boot: the `.early` section holding the boot code and the boot page
+/// table, and the `.init` section. Both are page aligned by the linker script.
+#[must_use]
+pub fn reclaimable_range() -> core::ops::Range<usize> {
+    (&raw const __reclaimable_start).addr()..(&raw const __reclaimable_end).addr()
+}
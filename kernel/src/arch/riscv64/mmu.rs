@@ -2,13 +2,24 @@
 //! implementation only handle SV39 paging, which should be supported by all
 //! RISC-V64 systems and should be enough for most use cases. However, it is
 //! possible to add support for other paging modes in the future.
-use super::addr::{self, Frame1Gib, Frame4Kib, Physical, Virtual, virt::Kernel};
+use super::{
+    addr::{self, Frame1Gib, Frame2Mib, Frame4Kib, Physical, Virtual, virt::Kernel},
+    tlb,
+};
 use crate::{
-    arch::mmu::{Flags, MapError, Rights, UnmapError},
+    arch::{
+        memory::UsableMemory,
+        mmu::{Flags, MapError, Rights, UnmapError},
+    },
     mm::{self, phys::AllocationFlags},
+    utils::lock,
 };
 use bitflags::bitflags;
-use core::ops::{Index, IndexMut};
+use core::{
+    ops::{Index, IndexMut},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+use kcore::paging;
 use usize_cast::IntoUsize;
 
 /// The virtual address where the kernel base starts. The last 1 GiB of
@@ -29,6 +40,21 @@ pub const KERNEL_PHYSICAL_BASE: Frame1Gib =
 /// up to the last address of the virtual address space.
 pub const KERNEL_START: Virtual<Kernel> = Virtual::<Kernel>::new(0xFFFF_FFC0_0000_0000);
 
+/// The start of the virtual memory range reserved for the kernel heap. This
+/// range covers the 1 GiB of virtual memory just below the kernel, and is
+/// populated on demand with [`map_kernel`] as the heap grows.
+pub const KERNEL_HEAP_START: Virtual<Kernel> = Virtual::<Kernel>::new(0xFFFF_FFFF_8000_0000);
+
+/// The size of the virtual memory range reserved for the kernel heap.
+pub const KERNEL_HEAP_SIZE: usize = 1024 * 1024 * 1024;
+
+/// The index of the kernel space entry of the root table covering the kernel
+/// heap range.
+const KERNEL_HEAP_ENTRY: usize = 254;
+
+/// The mask of the physical page number bits of an entry.
+const PPN_MASK: u64 = 0x003F_FFFF_FFFF_FC00;
+
 /// The size of a page in bytes.
 pub const PAGE_SIZE: usize = 4096;
 
@@ -38,7 +64,63 @@ pub const PAGE_SHIFT: usize = 12;
 /// The kernel's page table. This table is used by the kernel to identity
 /// map the physical memory of the system, allowing the kernel to easily
 /// access the physical memory of the system.
-static KERNEL_TABLE: spin::Once<spin::Mutex<RootTable>> = spin::Once::new();
+static KERNEL_TABLE: spin::Once<lock::Mutex<RootTable>> = spin::Once::new();
+
+/// The table mapping the kernel heap range. It is statically allocated since
+/// the physical memory manager is not yet initialized when the MMU is set up.
+/// It is only accessed through the kernel table, while holding its lock.
+static mut KERNEL_HEAP_TABLE: Table = Table::empty();
+
+/// Whether all the intermediate tables of the kernel half have been allocated
+/// by [`preallocate_kernel_tables`]. Once set, mapping a page in the kernel
+/// half must never allocate a table.
+static KERNEL_TABLES_PREALLOCATED: AtomicBool = AtomicBool::new(false);
+
+/// The table mapping the kernel image, in the last 1 GiB of virtual memory.
+/// Like the kernel heap table, it is statically allocated and only accessed
+/// through the kernel table.
+static mut KERNEL_IMAGE_TABLE: Table = Table::empty();
+
+/// The maximum number of 2 MiB regions spanned by the kernel image.
+pub const KERNEL_IMAGE_PAGE_TABLES: usize = 4;
+
+/// The number of bits of entropy of the slide of the kernel image. The image
+/// is slid by a multiple of 2 MiB, so that the boot code can map it with 2 MiB
+/// pages, and this allows slides of up to 512 MiB.
+pub const KERNEL_SLIDE_BITS: usize = 8;
+
+/// The offset between the address where the kernel image is mapped and the
+/// address where it was linked. It is chosen and applied by the boot code when
+/// [`crate::config::RANDOMIZE_KERNEL_BASE`] is set, and is zero otherwise.
+static KERNEL_SLIDE: AtomicUsize = AtomicUsize::new(0);
+
+/// The tables mapping the kernel image with 4 KiB pages, one for each 2 MiB
+/// region spanned by the image. The kernel image is mapped with 4 KiB pages
+/// so that each section is mapped with its own rights, and so that the
+/// sections only used during boot can be unmapped afterwards.
+static mut KERNEL_IMAGE_PAGES: [Table; KERNEL_IMAGE_PAGE_TABLES] =
+    [const { Table::empty() }; KERNEL_IMAGE_PAGE_TABLES];
+
+unsafe extern "C" {
+    static __reclaimable_start: [u8; 0];
+    static __init_start: [u8; 0];
+    static __init_data_start: [u8; 0];
+    static __init_end: [u8; 0];
+    static __text_start: [u8; 0];
+    static __text_end: [u8; 0];
+    static __rodata_start: [u8; 0];
+    static __rodata_end: [u8; 0];
+    static __data_start: [u8; 0];
+    static __data_end: [u8; 0];
+    static boot_stack_bottom: [u8; 0];
+    static __end: [u8; 0];
+}
+
+/// Whether the hart implements the Svpbmt extension, which allows the memory
+/// type of a page to be chosen in its entry instead of being given by the
+/// physical memory attributes of the platform. It is detected at boot, before
+/// any page is mapped.
+static SVPBMT: AtomicBool = AtomicBool::new(false);
 
 /// The root page table type.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -88,8 +170,17 @@ impl RootTable {
         let ppn = translate_kernel_ptr(self).as_usize() >> PAGE_SHIFT;
 
         if ppn != current_ppn {
+            // When paging is enabled for the first time, the TLB may contain
+            // anything and must be entirely flushed. Otherwise, only the user
+            // translations need to be flushed since the kernel half is mapped
+            // with global translations shared by all address spaces.
+            let paging = riscv::register::satp::read().mode() != riscv::register::satp::Mode::Bare;
             riscv::register::satp::set(riscv::register::satp::Mode::Sv39, 0, ppn);
-            riscv::asm::sfence_vma_all();
+            if paging {
+                tlb::flush_user();
+            } else {
+                tlb::flush_all();
+            }
         }
     }
 
@@ -157,7 +248,7 @@ impl Drop for RootTable {
         // space, and should not have directs references in user space.
         unsafe {
             use_kernel_table();
-            unmap_all(self.user_space_mut());
+            paging::release(&mut TableFrames::default(), self.user_space_mut(), 0);
         }
     }
 }
@@ -188,6 +279,12 @@ impl Table {
     pub const fn empty() -> Self {
         Self([Entry::missing(); 512], core::marker::PhantomPinned)
     }
+
+    /// Returns whether no entry of the table is present.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|entry| !entry.present())
+    }
 }
 
 impl Index<usize> for Table {
@@ -244,6 +341,7 @@ impl Entry {
     /// Set the flags of the entry.
     pub fn set_flags(&mut self, flags: Flags) {
         self.set_global(flags.contains(Flags::GLOBAL));
+        self.set_device(flags.contains(Flags::DEVICE));
     }
 
     /// Set or clear the present bit of the entry. If this bit is set, the
@@ -319,6 +417,28 @@ impl Entry {
         }
     }
 
+    /// Set or clear the device bit of the entry. This bit is reserved for
+    /// the software by the RISC-V specification and ignored by the MMU: the
+    /// kernel uses it to remember that the frame mapped by the entry holds
+    /// the registers of a device, and must not be freed when unmapped.
+    ///
+    /// When the hart implements the Svpbmt extension, the entry is also given
+    /// the I/O memory type, so that accesses to the device are neither cached
+    /// nor reordered regardless of the physical memory attributes of the
+    /// platform.
+    pub fn set_device(&mut self, device: bool) {
+        if device {
+            self.0 |= EntryFlags::DEVICE.bits();
+        } else {
+            self.0 &= !EntryFlags::DEVICE.bits();
+        }
+
+        self.0 &= !EntryFlags::PBMT.bits();
+        if device && SVPBMT.load(Ordering::Relaxed) {
+            self.0 |= EntryFlags::PBMT_IO.bits();
+        }
+    }
+
     /// Set or clear the accessed bit of the entry.
     pub fn set_accessed(&mut self, accessed: bool) {
         if accessed {
@@ -341,7 +461,7 @@ impl Entry {
     /// must be properly aligned, depending on the level of the page table that
     /// this entry is part of.
     pub fn set_address<T: Into<Frame4Kib>>(&mut self, frame: T) {
-        self.0 &= 0x3FF;
+        self.0 &= !PPN_MASK;
         self.0 |= (frame.into().inner().as_u64() & !0xFFF) >> 2;
     }
 
@@ -396,6 +516,13 @@ impl Entry {
         self.0 & EntryFlags::GLOBAL.bits() != 0
     }
 
+    /// Check if the entry maps the registers of a device. See
+    /// [`Self::set_device`] for more details.
+    #[must_use]
+    pub fn device(&self) -> bool {
+        self.0 & EntryFlags::DEVICE.bits() != 0
+    }
+
     /// Check if the entry was accessed, meaning that the page was read from
     /// or written to. This bit is set by the processor when a read access is
     /// made to the page, but is never cleared by the processor: it must be
@@ -421,7 +548,7 @@ impl Entry {
         // SAFETY: This is safe because the table entry cannot physically
         // contains an invalid physical address (not enough bits to have
         // an address greater than [`Physical::MAX`])
-        unsafe { Physical::new_unchecked((self.0.into_usize() & !0x3FF) << 2) }
+        unsafe { Physical::new_unchecked((self.0 & PPN_MASK).into_usize() << 2) }
     }
 
     /// Check if the entry is a leaf entry, meaning that it points to a
@@ -431,6 +558,17 @@ impl Entry {
         self.readable() | self.writable() | self.executable()
     }
 
+    /// Get the rights granted by the entry.
+    #[must_use]
+    pub fn rights(&self) -> Rights {
+        let mut rights = Rights::empty();
+        rights.set(Rights::USER, self.user());
+        rights.set(Rights::READ, self.readable());
+        rights.set(Rights::WRITE, self.writable());
+        rights.set(Rights::EXECUTE, self.executable());
+        rights
+    }
+
     /// Get the physical address that the entry points to and clear the entry. This is
     /// equivalent to calling `address()` followed by `clear()`, but is more convenient.
     #[must_use]
@@ -463,6 +601,26 @@ impl Entry {
     /// as this would require a machine with more than 128 GiB of RAM, which
     /// is not supported by Kiwi.
     #[must_use]
+    pub unsafe fn next_table(&self) -> Option<&Table> {
+        if self.is_leaf() || !self.present() {
+            None
+        } else {
+            let table = translate_physical(self.address())
+                .expect("Failed to translate table physical address")
+                .as_ptr::<Table>();
+            Some(&*(table))
+        }
+    }
+
+    /// Get a mutable reference to the next table. See [`Entry::next_table`]
+    /// for more details.
+    ///
+    /// # Safety
+    /// See [`Entry::next_table`].
+    ///
+    /// # Panics
+    /// See [`Entry::next_table`].
+    #[must_use]
     pub unsafe fn next_table_mut(&mut self) -> Option<&mut Table> {
         if self.is_leaf() || !self.present() {
             None
@@ -475,6 +633,100 @@ impl Entry {
     }
 }
 
+impl paging::Entry for Entry {
+    const BREAK_BEFORE_MAKE: bool = false;
+
+    fn present(&self) -> bool {
+        Entry::present(self)
+    }
+
+    fn is_leaf(&self) -> bool {
+        Entry::is_leaf(self)
+    }
+
+    fn device(&self) -> bool {
+        Entry::device(self)
+    }
+
+    fn address(&self) -> usize {
+        usize::from(Entry::address(self))
+    }
+
+    fn clear(&mut self) {
+        Entry::clear(self);
+    }
+
+    fn set_table(&mut self, table: usize) {
+        self.set_address(Physical::new(table));
+        self.set_present(true);
+    }
+
+    fn split(&self, address: usize, _level: usize) -> Self {
+        let mut page = *self;
+        page.set_address(Physical::new(address));
+        page
+    }
+}
+
+/// The frames holding the page tables, allocated from the physical memory
+/// manager and accessed through the physical memory map of the kernel.
+#[derive(Debug, Default)]
+struct TableFrames {
+    /// Whether the walk is done in the kernel half of the address space,
+    /// where no table may be allocated once [`preallocate_kernel_tables`] was
+    /// called.
+    kernel: bool,
+}
+
+impl TableFrames {
+    /// Returns the frames used to walk the tables translating the address
+    /// with the given VPNs.
+    fn new(vpn: [usize; 3]) -> Self {
+        Self {
+            kernel: vpn[0] >= 256,
+        }
+    }
+}
+
+impl paging::Frames for TableFrames {
+    type Entry = Entry;
+
+    fn allocate(&mut self) -> Option<usize> {
+        // Once preallocated, the kernel half is entirely populated, so a
+        // table should only be allocated in the user half of the address
+        // space.
+        assert!(
+            !self.kernel || !KERNEL_TABLES_PREALLOCATED.load(Ordering::Acquire),
+            "Intermediate kernel page table allocated after boot"
+        );
+        let flags = AllocationFlags::KERNEL | AllocationFlags::ZEROED;
+        mm::phys::allocate_frame(flags).map(|frame| usize::from(Physical::from(frame)))
+    }
+
+    fn deallocate(&mut self, frame: usize, count: usize) {
+        let frame = Physical::new(frame);
+        if count == 1 {
+            mm::phys::deallocate_frame(frame);
+        } else {
+            mm::phys::deallocate_range(frame, count);
+        }
+    }
+
+    unsafe fn table<'a>(&self, address: usize) -> &'a mut [Entry; 512] {
+        let table = translate_physical(Physical::new(address))
+            .expect("Failed to translate table physical address")
+            .as_mut_ptr::<[Entry; 512]>();
+
+        // SAFETY: The caller guarantees that the address is the address of a
+        // table that is not otherwise referenced.
+        unsafe { &mut *table }
+    }
+
+    fn flush(&mut self) {
+        tlb::shootdown_all();
+    }
+}
+
 bitflags! {
     /// A set of flags that can be used to control the behavior of a virtual
     /// memory region.
@@ -510,6 +762,22 @@ bitflags! {
         /// write access is made to the page, but is never cleared by the
         /// processor: it must be cleared by the OS.
         const DIRTY = 1 << 7;
+
+        /// The entry maps the registers of a device. This is one of the two
+        /// bits reserved for the software, and is ignored by the processor.
+        const DEVICE = 1 << 8;
+
+        /// The memory type of the entry, with the Svpbmt extension. When
+        /// both bits are clear, the type is given by the physical memory
+        /// attributes of the platform.
+        const PBMT = 0b11 << 61;
+
+        /// The non-cacheable, idempotent, weakly-ordered memory type.
+        const PBMT_NC = 1 << 61;
+
+        /// The non-cacheable, non-idempotent, strongly-ordered I/O memory
+        /// type, used for the registers of devices.
+        const PBMT_IO = 1 << 62;
     }
 }
 
@@ -518,40 +786,102 @@ bitflags! {
 /// This will allow the kernel to access the physical memory of the system
 /// without having to manually map each page.
 ///
+/// The parts of the physical memory map that are not RAM are mapped as device
+/// memory, so that the registers of devices can be accessed through this
+/// mapping (see [`map_mmio`]). With the Svpbmt extension, this gives them the
+/// I/O memory type, and the kernel relies on the physical memory attributes
+/// of the platform otherwise.
+///
 /// # Panics
 /// This function should never panic. If it does, it means that there is a bug
 /// in the MMU implementation.
-pub fn setup() {
+pub fn setup(memory: &UsableMemory, device_tree: &fdt::Fdt) {
+    SVPBMT.store(svpbmt_available(device_tree), Ordering::Relaxed);
     log::info!("Initializing the MMU and remapping the kernel");
     log::debug!("Using SV39 paging mode (3 levels of page tables)");
     log::debug!("User address space :   0x0000000000000000 - 0x00007FFFFFFFFFFF");
     log::debug!("Kernel address space : 0xFFFFFFFFC0000000 - 0xFFFFFFFFFFFFFFFF");
+    log::debug!("Kernel heap :          0xFFFFFFFF80000000 - 0xFFFFFFFFBFFFFFFF");
+    log::debug!("Kernel image slide :   {:#x}", kernel_slide());
+    log::debug!("Svpbmt extension :     {}", SVPBMT.load(Ordering::Relaxed));
 
     let mut table = KERNEL_TABLE
-        .call_once(|| spin::Mutex::new(RootTable::empty()))
+        .call_once(|| lock::Mutex::new(RootTable::empty()))
         .lock();
 
     // Map the first 255 GiB of physical memory to the first 255 GiB
     // of virtual memory in the kernel's address space. This will allow
     // the kernel to access any physical address easily without having
-    // to manually map each page.
+    // to manually map each page. The kernel never executes code through
+    // this mapping, so it is not executable.
     for (i, entry) in table.kernel_space_mut().iter_mut().enumerate() {
+        let start = i * Frame1Gib::SIZE;
+        let ram = start < memory.ram_end && start + Frame1Gib::SIZE > memory.ram_start;
         entry.set_address(Frame1Gib::from_index(i));
-        entry.set_executable(true);
         entry.set_writable(true);
         entry.set_readable(true);
         entry.set_present(true);
         entry.set_global(true);
+        entry.set_device(!ram);
     }
 
-    // Map the kernel to the last 1 GiB of virtual memory.
+    // Map the kernel image to the last 1 GiB of virtual memory, using 4 KiB
+    // pages so that each section is mapped with its own rights: code is never
+    // writable, and data is never executable. The rest of this range, which
+    // includes the memory used by the firmware and the guard page below the
+    // boot stack, is left unmapped so that stray accesses fault.
+    //
+    // Together with the loop above and the kernel heap entry below, this
+    // populates every top-level entry of the kernel half. As a consequence,
+    // the kernel half never needs a top-level entry after boot, and every
+    // address space created with `copy_kernel_space` shares exactly the same
+    // kernel mappings without needing to be kept in sync afterwards.
+    //
+    // SAFETY: The kernel image tables are only accessed through the kernel
+    // table, whose lock is held.
+    let (image, pages) = unsafe {
+        let image = &raw mut KERNEL_IMAGE_TABLE;
+        let pages = &raw mut KERNEL_IMAGE_PAGES;
+        (&mut *image, &mut *pages)
+    };
+    let sections = kernel_sections();
+    let first = (sections[0].0.start - usize::from(KERNEL_VIRTUAL_BASE)) / Frame2Mib::SIZE;
+    for (range, rights) in sections {
+        for page in range.step_by(PAGE_SIZE) {
+            let offset = page - usize::from(KERNEL_VIRTUAL_BASE);
+            let region = offset / Frame2Mib::SIZE;
+            let pages = pages
+                .get_mut(region - first)
+                .expect("The kernel image is too large to be mapped");
+
+            let entry = &mut image[region];
+            if !entry.present() {
+                entry.set_address(translate_kernel_ptr(&raw const *pages));
+                entry.set_present(true);
+            }
+
+            let entry = &mut pages[(offset / PAGE_SIZE) % 512];
+            *entry = Entry::new(Frame4Kib::new(translate_virtual_kernel(
+                Virtual::<Kernel>::new(page),
+            )));
+            entry.set_rights(rights);
+            entry.set_present(true);
+            entry.set_global(true);
+        }
+    }
     let entry = table.last_kernel_entry_mut();
-    entry.set_address(KERNEL_PHYSICAL_BASE);
-    entry.set_executable(true);
-    entry.set_writable(true);
-    entry.set_readable(true);
+    entry.clear();
+    entry.set_address(translate_kernel_ptr(&raw const KERNEL_IMAGE_TABLE));
+    entry.set_present(true);
+
+    // Reserve the 1 GiB below the kernel for the kernel heap. Its entry points
+    // to a table instead of a 1 GiB leaf, so that pages can be mapped in this
+    // range after boot. Since all address spaces share this table, pages
+    // mapped in the heap are visible in all address spaces.
+    let entry = &mut table.kernel_space_mut()[KERNEL_HEAP_ENTRY];
+    entry.clear();
+    entry.set_address(translate_kernel_ptr(&raw const KERNEL_HEAP_TABLE));
     entry.set_present(true);
-    entry.set_global(true);
 
     // SAFETY: The kernel table was properly initialized and will not cause
     // a page fault when set as the current page table.
@@ -560,18 +890,88 @@ pub fn setup() {
     }
 }
 
+/// Return whether all the harts implement the Svpbmt extension, according to
+/// the `riscv,isa-extensions` property of their node in the device tree, or to
+/// their `riscv,isa` string with older device trees.
+fn svpbmt_available(device_tree: &fdt::Fdt) -> bool {
+    let mut cpus = device_tree.cpus().peekable();
+    cpus.peek().is_some()
+        && cpus.all(|cpu| {
+            if let Some(extensions) = cpu.property("riscv,isa-extensions") {
+                extensions
+                    .value
+                    .split(|&byte| byte == 0)
+                    .any(|extension| extension == b"svpbmt")
+            } else {
+                cpu.property("riscv,isa")
+                    .and_then(fdt::node::NodeProperty::as_str)
+                    .is_some_and(|isa| {
+                        isa.split('_')
+                            .skip(1)
+                            .any(|extension| extension.eq_ignore_ascii_case("svpbmt"))
+                    })
+            }
+        })
+}
+
+/// Return the ranges of virtual addresses of the sections of the kernel image
+/// along with the rights they must be mapped with, using the boundaries given
+/// by the linker script. Each range is page aligned.
+fn kernel_sections() -> [(core::ops::Range<usize>, Rights); 7] {
+    let range = |start: *const [u8; 0], end: *const [u8; 0]| start.addr()..end.addr();
+    [
+        // The boot code and the boot page table, which are no longer used
+        // once the kernel table is set up but are reclaimed after boot.
+        (
+            range(&raw const __reclaimable_start, &raw const __init_start),
+            Rights::READ,
+        ),
+        (
+            range(&raw const __init_start, &raw const __init_data_start),
+            Rights::RX,
+        ),
+        (
+            range(&raw const __init_data_start, &raw const __init_end),
+            Rights::READ,
+        ),
+        (
+            range(&raw const __text_start, &raw const __text_end),
+            Rights::RX,
+        ),
+        (
+            range(&raw const __rodata_start, &raw const __rodata_end),
+            Rights::READ,
+        ),
+        (
+            range(&raw const __data_start, &raw const __data_end),
+            Rights::RW,
+        ),
+        (
+            range(&raw const boot_stack_bottom, &raw const __end),
+            Rights::RW,
+        ),
+    ]
+}
+
 /// Map a physical address to a virtual address.
 ///
 /// # Errors
 /// This function will return an error if any of the following conditions
 /// are met:
 /// - The virtual address is already mapped to a physical address.
+/// - Both [`Flags::HUGE_2MB`] and [`Flags::HUGE_1GB`] are given.
+/// - The frame or the virtual address is not aligned to the size of the page
+///   requested by the flags.
 /// - An intermediate table is missing and the kernel is unable to
-///   allocate a new table.
+///   allocate a new table. For the kernel half, this can only happen before
+///   [`preallocate_kernel_tables`] is called, which never happens if
+///   [`crate::config::PREALLOCATE_KERNEL_TABLES`] is disabled.
 ///
 /// # Panics
-/// Panics if an error occurs while traversing the page table. This should
-/// never happen, as the page table should be properly initialized.
+/// Panics if an error occurs while traversing the page table, or if an
+/// intermediate table of the kernel half is missing after the kernel tables
+/// were preallocated. This should never happen, as the page table should be
+/// properly initialized.
 ///
 /// # Safety
 /// This function is unsafe because mapping a physical address to a virtual
@@ -590,41 +990,36 @@ pub unsafe fn map<T: addr::virt::Type>(
     rights: Rights,
     flags: Flags,
 ) -> Result<(), MapError> {
-    // Extract the VPNs from the virtual address.
-    let vpn = virt.vpn_sv39();
-    let mut entry = &mut root.address_space_mut()[vpn[0]];
-    for i in 1..3 {
-        // If we reach a leaf entry before the last level, this means that
-        // the page was mapped with a larger frame size (2 MiB or 1 GiB). This
-        // is currently not supported by this function, and somehow this has
-        // happened... In this case, I simply choose to tell the caller that
-        // the address is already mapped. But if the caller tries to unmap the
-        // address later, it will get an UnsupportedFrameSize error by the
-        // unmap function.
-        if entry.is_leaf() {
-            return Err(MapError::AlreadyMapped);
-        }
-
-        // If the intermediate table is missing, allocate a new table and
-        // update the entry to point to the new table.
-        if !entry.present() {
-            let allocation_flags = AllocationFlags::KERNEL | AllocationFlags::ZEROED;
-            let frame = mm::phys::allocate_frame(allocation_flags).ok_or(MapError::OutOfMemory)?;
-            entry.set_address(frame);
-            entry.set_present(true);
-        }
-
-        // Get the next table from the current entry and continue the traversal
-        let table = unsafe { entry.next_table_mut().unwrap() };
-        entry = &mut table[vpn[i]];
+    // Find the level of the leaf entry from the size of the page requested,
+    // and verify that both the frame and the virtual address are aligned to
+    // this size.
+    let level = match (
+        flags.contains(Flags::HUGE_1GB),
+        flags.contains(Flags::HUGE_2MB),
+    ) {
+        (false, false) => 2,
+        (false, true) => 1,
+        (true, false) => 0,
+        (true, true) => return Err(MapError::InvalidFlagsCombination),
+    };
+    let size = paging::leaf_size(level);
+    if !usize::from(frame.into_inner()).is_multiple_of(size)
+        || !virt.as_usize().is_multiple_of(size)
+    {
+        return Err(MapError::FrameNotAligned);
     }
 
-    // If the address is already mapped, return an error instead of
-    // overwriting the existing mapping, to allow the caller to handle
-    // the situation properly.
-    if entry.present() {
-        return Err(MapError::AlreadyMapped);
-    }
+    // Walk the tables down to the level of the leaf, allocating the missing
+    // intermediate tables. The walk fails if the address is already mapped,
+    // to allow the caller to handle the situation properly instead of
+    // overwriting the existing mapping.
+    let vpn = virt.vpn_sv39();
+    let entry = paging::map(
+        &mut TableFrames::new(vpn),
+        &mut root.address_space_mut().0,
+        vpn,
+        level,
+    )?;
 
     // Update the entry with the physical address, rights and flags given.
     // We do not need to flush the TLB here, as the page was not previously
@@ -636,6 +1031,149 @@ pub unsafe fn map<T: addr::virt::Type>(
     Ok(())
 }
 
+/// Map a frame in the kernel half of the address space, using the kernel
+/// table. Since the kernel half is shared by all address spaces, the page is
+/// visible in all address spaces.
+///
+/// # Errors
+/// See [`map`] for the list of errors that can happen.
+///
+/// # Panics
+/// Panics if the kernel table is not initialized.
+///
+/// # Safety
+/// See [`map`]. In addition, the caller must ensure that the virtual address
+/// is not in a range mapped with 1 GiB leaves during boot, such as the kernel
+/// image or the physical memory map.
+pub unsafe fn map_kernel(
+    virt: Virtual<Kernel>,
+    frame: Frame4Kib,
+    rights: Rights,
+    flags: Flags,
+) -> Result<(), MapError> {
+    map(
+        &mut KERNEL_TABLE.get().unwrap().lock(),
+        virt,
+        frame,
+        rights,
+        flags,
+    )
+}
+
+/// Unmap a page from the kernel half of the address space, using the kernel
+/// table, and return the frame that was mapped to it.
+///
+/// # Errors
+/// See [`unmap`] for the list of errors that can happen.
+///
+/// # Panics
+/// Panics if the kernel table is not initialized.
+///
+/// # Safety
+/// See [`unmap`]. The caller must ensure that the page is no longer used by
+/// the kernel.
+pub unsafe fn unmap_kernel(virt: Virtual<Kernel>) -> Result<Frame4Kib, UnmapError> {
+    unmap(&mut KERNEL_TABLE.get().unwrap().lock(), virt)
+}
+
+/// Allocate the missing tables of the kernel heap range, so that all the
+/// intermediate tables of the kernel half exist: the top-level entries are
+/// already populated by [`setup`], and the tables of the kernel half are
+/// never freed. After this, mapping a page in the kernel half never allocates
+/// a table. Returns the number of tables allocated.
+///
+/// # Panics
+/// Panics if the kernel table is not initialized, or if the kernel runs out
+/// of memory while allocating the tables.
+#[must_use]
+pub fn preallocate_kernel_tables() -> usize {
+    let _table = KERNEL_TABLE.get().unwrap().lock();
+
+    // SAFETY: The heap table is only accessed while holding the lock of the
+    // kernel table.
+    let heap = &raw mut KERNEL_HEAP_TABLE;
+    let heap = unsafe { &mut *heap };
+    let mut allocated = 0;
+    for index in 0..512 {
+        let entry = &mut heap[index];
+        if entry.present() {
+            continue;
+        }
+
+        let flags = AllocationFlags::KERNEL | AllocationFlags::ZEROED;
+        let frame =
+            mm::phys::allocate_frame(flags).expect("Failed to allocate a kernel page table");
+        entry.set_address(frame);
+        entry.set_present(true);
+        allocated += 1;
+    }
+
+    KERNEL_TABLES_PREALLOCATED.store(true, Ordering::Release);
+    allocated
+}
+
+/// Get the rights granted to the page mapped at the given virtual address, by
+/// walking the given page table. Returns `None` if the address is not mapped.
+/// Pages mapped with a larger frame size (2 MiB or 1 GiB) are supported.
+#[must_use]
+pub fn rights<T: addr::virt::Type>(root: &RootTable, virt: Virtual<T>) -> Option<Rights> {
+    translate(root, virt).map(|(_, rights)| rights)
+}
+
+/// Translate the given virtual address by walking the given page table, and
+/// return the 4 KiB frame containing the address along with the rights granted
+/// to the page. Returns `None` if the address is not mapped. Pages mapped with
+/// a larger frame size (2 MiB or 1 GiB) are supported.
+#[must_use]
+pub fn translate<T: addr::virt::Type>(
+    root: &RootTable,
+    virt: Virtual<T>,
+) -> Option<(Frame4Kib, Rights)> {
+    let (entry, level) = leaf(root, virt)?;
+    let offset = (virt.as_usize() % paging::leaf_size(level)) & !(PAGE_SIZE - 1);
+    let frame = Frame4Kib::new(Physical::new(usize::from(entry.address()) + offset));
+    Some((frame, entry.rights()))
+}
+
+/// Return whether the page mapped at the given virtual address maps the
+/// registers of a device (see [`Flags::DEVICE`]). Returns `false` if the
+/// address is not mapped.
+#[must_use]
+pub fn is_device<T: addr::virt::Type>(root: &RootTable, virt: Virtual<T>) -> bool {
+    leaf(root, virt).is_some_and(|(entry, _)| entry.device())
+}
+
+/// Walk the given page table and return the leaf entry mapping the given
+/// virtual address, along with the level of the table containing it. Returns
+/// `None` if the address is not mapped. Contrary to [`leaf_mut`], huge pages
+/// are not split.
+fn leaf<T: addr::virt::Type>(root: &RootTable, virt: Virtual<T>) -> Option<(&Entry, usize)> {
+    let vpn = virt.vpn_sv39();
+    paging::leaf(&TableFrames::new(vpn), &root.address_space().0, vpn)
+}
+
+/// Change the rights of the page mapped at the given virtual address. If the
+/// page is part of a huge page, the huge page is first split so that only the
+/// requested page is affected.
+///
+/// # Errors
+/// Returns [`UnmapError::NotMapped`] if the address is not mapped, or
+/// [`UnmapError::OutOfMemory`] if a huge page could not be split.
+///
+/// # Safety
+/// The caller must ensure that removing rights from a page that is in use is
+/// properly handled, for example by the page fault handler.
+pub unsafe fn protect<T: addr::virt::Type>(
+    root: &mut RootTable,
+    virt: Virtual<T>,
+    rights: Rights,
+) -> Result<(), UnmapError> {
+    let entry = leaf_mut(root, virt)?;
+    entry.set_rights(rights);
+    tlb::shootdown_page(virt.as_usize());
+    Ok(())
+}
+
 /// Unmap a virtual address, returning the physical address that was previously
 /// mapped to it.
 ///
@@ -658,58 +1196,73 @@ pub unsafe fn unmap<T: addr::virt::Type>(
     root: &mut RootTable,
     virt: Virtual<T>,
 ) -> Result<Frame4Kib, UnmapError> {
-    let vpn = virt.vpn_sv39();
-    let mut entry = &mut root.address_space_mut()[vpn[0]];
-    for i in 1..3 {
-        // If we reach a leaf entry before the last level, this means that
-        // the page was mapped with a larger frame size (2 MiB or 1 GiB), which
-        // is not supported by this function.
-        if entry.is_leaf() {
-            return Err(UnmapError::UnsupportedFrameSize);
-        } else if !entry.present() {
-            return Err(UnmapError::NotMapped);
-        }
-
-        let table = unsafe { entry.next_table_mut().unwrap() };
-        entry = &mut table[vpn[i]];
-    }
-
-    // If the entry is not a leaf, this means that the page table is corrupted
-    // and is more than 3 levels deep. This should never happen in SV39 paging mode,
-    // and we panic in this case.
-    assert!(entry.is_leaf());
-
-    // If the entry is not present, return an error.
-    if !entry.present() {
-        return Err(UnmapError::NotMapped);
-    }
+    let entry = leaf_mut(root, virt)?;
 
     // Get the physical address that was previously mapped to the given virtual
-    // address, unmap it, flush the TLB and return the physical address. The
-    // kernel does not use ASIDs, so we can just use 0 as the ASID.
+    // address, unmap it, and invalidate the translation on all harts before
+    // returning the physical address, so that the frame can safely be reused.
     let address = entry.address_and_clear();
-    riscv::asm::sfence_vma(0, virt.as_usize());
+    tlb::shootdown_page(virt.as_usize());
+
+    // Free the intermediate tables of the user space that became empty. The
+    // kernel space tables are shared by all address spaces and are never
+    // freed.
+    let vpn = virt.vpn_sv39();
+    if vpn[0] < 256 {
+        let frames = &mut TableFrames::new(vpn);
+        paging::prune(frames, &mut root.address_space_mut().0, vpn);
+    }
     Ok(Frame4Kib::new_unchecked(address))
 }
 
-/// Unmap all the entries in the given table recursively, freeing all the tables
-/// and frames mapped by the table. This function is used to unmap a range of
-/// entries in a page table when deleting an entire address space.
+/// Walk the given page table and return the 4 KiB leaf entry mapping the given
+/// virtual address. If the address is mapped by a huge page, the huge page is
+/// split into smaller pages so that the returned entry only maps the page
+/// containing the address.
+///
+/// # Errors
+/// Returns [`UnmapError::NotMapped`] if the address is not mapped, or
+/// [`UnmapError::OutOfMemory`] if a huge page could not be split.
+fn leaf_mut<T: addr::virt::Type>(
+    root: &mut RootTable,
+    virt: Virtual<T>,
+) -> Result<&mut Entry, UnmapError> {
+    let vpn = virt.vpn_sv39();
+    let frames = &mut TableFrames::new(vpn);
+    Ok(paging::leaf_mut(
+        frames,
+        &mut root.address_space_mut().0,
+        vpn,
+    )?)
+}
+
+/// The number of parts in which the user space can be released with
+/// [`release_user_space_part`]. Each part corresponds to an entry of the
+/// root table, covering 1 GiB of virtual memory.
+pub const USER_SPACE_PARTS: usize = 256;
+
+/// Release a part of the user space of the given table, freeing all the
+/// tables and frames mapped in it. This allows the user space of a table to
+/// be released incrementally, instead of in a single long operation when the
+/// table is dropped. Returns the number of frames released, including the
+/// frames used by the intermediate tables.
+///
+/// # Panics
+/// Panics if `part` is not lower than [`USER_SPACE_PARTS`].
 ///
 /// # Safety
-/// This function is unsafe because unmapping all user space mappings can lead
-/// to memory safety issues (obviously). Usually, this function should only be called
-/// when deleting an entire address space that is no longer in use.
-unsafe fn unmap_all(entries: &mut [Entry]) {
-    for entry in entries.iter_mut() {
-        if let Some(table) = unsafe { entry.next_table_mut() } {
-            unmap_all(&mut table.0);
-            let frame = entry.address_and_clear();
-            mm::phys::deallocate_frame(frame);
-        } else if entry.present() {
-            let frame = entry.address_and_clear();
-            mm::phys::deallocate_frame(frame);
-        }
+/// The caller must ensure that the memory mapped in the given part of the
+/// user space is no longer in use. The kernel table is set as the current
+/// page table to ensure that the given table is not in use while it is being
+/// modified.
+pub unsafe fn release_user_space_part(root: &mut RootTable, part: usize) -> usize {
+    unsafe {
+        use_kernel_table();
+        paging::release(
+            &mut TableFrames::default(),
+            &mut root.user_space_mut()[part..=part],
+            0,
+        )
     }
 }
 
@@ -748,6 +1301,18 @@ pub fn forbid_user_page_access() {
     }
 }
 
+/// Make the instructions written at the given kernel address visible to
+/// instruction fetches of the current hart with a `fence.i` instruction. The
+/// kernel only runs on the boot hart, so the other harts do not need to be
+/// synchronized.
+pub fn sync_instructions(_start: Virtual<Kernel>, _len: usize) {
+    // SAFETY: Synchronizing the instruction stream cannot cause undefined
+    // behavior, instructions will simply be fetched again from memory.
+    unsafe {
+        core::arch::asm!("fence.i", options(nostack));
+    }
+}
+
 /// Translate a physical address to a virtual address. If the translation
 /// cannot be done because the physical address is greater than the maximum
 /// virtual address representable by the system, this function will return
@@ -759,23 +1324,86 @@ pub fn translate_physical(phys: impl Into<Physical>) -> Option<Virtual<Kernel>>
     ))
 }
 
+/// Return the address where the kernel can access the registers of a device,
+/// in the `size` bytes starting at the given physical address. They are
+/// accessed through the physical memory map, where the parts of the physical
+/// address space that are not RAM are mapped as device memory (see [`setup`]).
+/// With the Svpbmt extension, they have the I/O memory type.
+///
+/// Returns `None` if the range is empty, or if part of it is mapped as normal
+/// memory: accesses to the registers of a device must not be cached, merged
+/// or reordered like accesses to RAM may be.
+#[must_use]
+pub fn map_mmio(base: Physical, size: usize) -> Option<Virtual<Kernel>> {
+    let start = translate_physical(base)?;
+    let last = usize::from(start).checked_add(size.checked_sub(1)?)?;
+    let table = KERNEL_TABLE.get()?.lock();
+    (usize::from(start)..=last)
+        .step_by(Frame1Gib::SIZE)
+        .chain(core::iter::once(last))
+        .all(|address| is_device(&table, Virtual::<Kernel>::new(address)))
+        .then_some(start)
+}
+
 /// Translate a virtual address in the kernel's address space to a physical
 /// address.
 ///
 /// # Panics
 /// Panics if the virtual address is not located in the kernel's address space,
-/// i.e. if it is not greater than or equal to `KERNEL_START`.
+/// i.e. if it is not greater than or equal to `KERNEL_START`, or if it is in
+/// the kernel heap range but is not mapped.
 #[must_use]
 pub fn translate_virtual_kernel(virt: Virtual<Kernel>) -> Physical {
-    if virt >= KERNEL_VIRTUAL_BASE {
+    let heap = usize::from(KERNEL_HEAP_START)..usize::from(KERNEL_HEAP_START) + KERNEL_HEAP_SIZE;
+    if heap.contains(&usize::from(virt)) {
+        translate_heap(virt)
+    } else if virt >= KERNEL_VIRTUAL_BASE {
         Physical::new(
-            usize::from(virt) - usize::from(KERNEL_VIRTUAL_BASE) + KERNEL_PHYSICAL_BASE.as_usize(),
+            usize::from(virt) - usize::from(KERNEL_VIRTUAL_BASE) - kernel_slide()
+                + KERNEL_PHYSICAL_BASE.as_usize(),
         )
     } else {
         Physical::new(usize::from(virt) - usize::from(KERNEL_START))
     }
 }
 
+/// Return the offset between the address where the kernel image is mapped
+/// and the address where it was linked. This must be subtracted from the
+/// addresses printed in backtraces to find them in the kernel binary.
+#[must_use]
+pub fn kernel_slide() -> usize {
+    KERNEL_SLIDE.load(Ordering::Relaxed)
+}
+
+/// Record the slide applied to the kernel image by the boot code.
+///
+/// # Safety
+/// This must be called once, at the very start of the boot process, with the
+/// slide actually applied to the kernel image by the boot code.
+pub unsafe fn set_kernel_slide(slide: usize) {
+    KERNEL_SLIDE.store(slide, Ordering::Relaxed);
+}
+
+/// Translate a virtual address in the kernel heap range to a physical address.
+/// Contrary to the rest of the kernel space, the heap is not linearly mapped,
+/// so the translation requires walking the heap table.
+///
+/// # Panics
+/// Panics if the address is not mapped.
+fn translate_heap(virt: Virtual<Kernel>) -> Physical {
+    let vpn = virt.vpn_sv39();
+
+    // SAFETY: The heap table is only modified while holding the lock of the
+    // kernel table, and the entries used to translate a mapped address are
+    // not modified until the address is unmapped.
+    let table = unsafe { (&raw const KERNEL_HEAP_TABLE).as_ref().unwrap_unchecked() };
+    let entry = unsafe { table[vpn[1]].next_table() }
+        .map(|table| table[vpn[2]])
+        .filter(Entry::present)
+        .expect("Kernel heap address not mapped");
+    Physical::new(usize::from(entry.address()) + usize::from(virt) % PAGE_SIZE)
+}
+
 /// Translate a kernel pointer to a physical address.
 ///
 /// # Panics
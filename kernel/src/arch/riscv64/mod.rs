@@ -2,14 +2,19 @@ use crate::arch::{generic, memory::UsableMemory};
 use macros::init;
 
 pub mod addr;
+pub mod console;
 pub mod cpu;
+pub mod device;
 pub mod irq;
 pub mod log;
 pub mod memory;
 pub mod mmu;
 pub mod thread;
 pub mod timer;
+pub mod tlb;
 pub mod trap;
+pub mod uart;
+pub mod user;
 
 mod lang;
 
@@ -38,21 +43,73 @@ pub unsafe fn setup(hart: usize, device_tree: usize) -> UsableMemory {
         fdt::Fdt::from_ptr(core::ptr::with_exposed_provenance(device_tree))
             .expect("Failed to parse the device tree")
     };
-    let memory = UsableMemory::new(&fdt);
+    crate::cmdline::setup(bootargs(&fdt));
+    generic::log::configure();
 
-    mmu::setup();
+    let mut memory = UsableMemory::new(&fdt);
+    // SAFETY: The device tree was given by the bootloader, and is accessed
+    // through the physical memory map.
+    unsafe { crate::devicetree::setup(&mut memory, device_tree) };
+    crate::random::seed(random_seed(&fdt));
+
+    tlb::register_hart(hart);
+    mmu::setup(&memory, &fdt);
+    uart::setup(&fdt);
+    generic::log::ready();
+    device::setup(&fdt);
+    irq::setup(&fdt, hart);
     trap::setup();
     timer::setup(&fdt);
 
     memory
 }
 
+/// Find the kernel command line given by the bootloader in the `bootargs`
+/// property of the `/chosen` node of the device tree. Returns an empty command
+/// line if the bootloader did not provide one.
+fn bootargs<'a>(device_tree: &fdt::Fdt<'a>) -> &'a [u8] {
+    device_tree
+        .find_node("/chosen")
+        .and_then(|chosen| chosen.property("bootargs"))
+        .map_or(&[], |property| property.value)
+}
+
+/// Find the random seed given by the bootloader in the device tree, in the
+/// `rng-seed` property of the `/chosen` node set by QEMU and U-Boot, or in the
+/// `kaslr-seed` property used for the same purpose by older bootloaders.
+/// Returns an empty seed if the bootloader did not provide one.
+fn random_seed<'a>(device_tree: &fdt::Fdt<'a>) -> &'a [u8] {
+    device_tree
+        .find_node("/chosen")
+        .and_then(|chosen| {
+            chosen
+                .property("rng-seed")
+                .or_else(|| chosen.property("kaslr-seed"))
+        })
+        .map_or(&[], |property| property.value)
+}
+
 /// Shutdown the computer
 #[inline]
 pub fn shutdown() -> ! {
     sbi::legacy::shutdown()
 }
 
+/// Shutdown the computer, giving the reason of the shutdown to the SBI. When
+/// running under QEMU, the SBI implementation reports a system failure to the
+/// test device of the `virt` machine, which makes QEMU exit with a non-zero
+/// status.
+#[inline]
+pub fn exit(success: bool) -> ! {
+    let reason = if success {
+        sbi::system_reset::ResetReason::NoReason
+    } else {
+        sbi::system_reset::ResetReason::SystemFailure
+    };
+    _ = sbi::system_reset::system_reset(sbi::system_reset::ResetType::Shutdown, reason);
+    sbi::legacy::shutdown()
+}
+
 /// Reboot the computer. If for some reason the SBI call fails, we will just
 /// perform a shutdown instead.
 #[inline]
@@ -1,8 +1,13 @@
-use super::{mmu, trap};
-use crate::arch::trap::Trap;
-use alloc::boxed::Box;
+use super::trap;
+use crate::{arch::trap::Trap, mm::space::AddressSpace, utils::lock};
+use ::syscall::task::Registers;
+use alloc::{boxed::Box, sync::Arc};
 use riscv::register::scause::{self, Exception};
 
+/// Threads cannot be single-stepped, since this requires the debug triggers of
+/// the firmware.
+pub const SINGLE_STEP: bool = false;
+
 core::arch::global_asm!(include_str!("asm/thread.asm"));
 
 unsafe extern "C" {
@@ -11,21 +16,23 @@ unsafe extern "C" {
 
 /// A thread is a sequence of instructions that can be executed independently
 /// of other code. On RISC-V, a thread is represented by a `Context` that
-/// contains a copy of all the registers and a `Table` that contains the page
-/// table of the thread.
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// contains a copy of all the registers and an [`AddressSpace`] that contains
+/// the page table of the thread. The address space may be shared with other
+/// threads of the same task, in which case it is released when the last of
+/// them is dropped.
+#[derive(Debug)]
 pub struct Thread {
     context: Box<trap::Context>,
-    table: Box<mmu::RootTable>,
+    space: Arc<lock::Mutex<AddressSpace>>,
 }
 
 impl Thread {
-    /// Create a new thread with an empty page table.
+    /// Create a new thread with an empty user address space.
     #[must_use]
     pub fn new() -> Self {
         Self {
             context: Box::new(trap::Context::new()),
-            table: Box::new(mmu::RootTable::empty()),
+            space: Arc::new(lock::Mutex::new(AddressSpace::new())),
         }
     }
 
@@ -41,16 +48,20 @@ impl Thread {
         &self.context
     }
 
-    /// Return a mutable reference to the root page table of the thread.
+    /// Lock and return the address space of the thread. Since the address
+    /// space may be shared with other threads, the lock must not be held
+    /// while accessing user memory or while calling a function that may lock
+    /// it again.
     #[must_use]
-    pub fn root_table_mut(&mut self) -> &mut mmu::RootTable {
-        &mut self.table
+    pub fn address_space(&self) -> lock::MutexGuard<'_, AddressSpace> {
+        self.space.lock()
     }
 
-    /// Return a reference to the root page table of the thread.
+    /// Return whether the address space of the thread is shared with other
+    /// threads that are still alive.
     #[must_use]
-    pub fn root_table(&self) -> &mmu::RootTable {
-        &self.table
+    pub fn shares_address_space(&self) -> bool {
+        Arc::strong_count(&self.space) > 1
     }
 }
 
@@ -66,12 +77,30 @@ impl Default for Thread {
 #[must_use]
 pub fn create(ip: usize, stack: usize) -> Thread {
     let mut thread = Thread::new();
-    thread.table.copy_kernel_space();
     thread.context.set_sp(stack);
     thread.context.set_ip(ip);
     thread
 }
 
+/// Create a new thread with the given instruction pointer and stack pointer,
+/// sharing the address space of the given thread.
+#[must_use]
+pub fn create_sibling(thread: &Thread, ip: usize, stack: usize) -> Thread {
+    let mut sibling = Thread {
+        context: Box::new(trap::Context::new()),
+        space: Arc::clone(&thread.space),
+    };
+    sibling.context.set_sp(stack);
+    sibling.context.set_ip(ip);
+    sibling
+}
+
+/// Set an argument given to the entry point of the given thread. On RISC-V,
+/// the arguments of a function are stored in the a0-a7 registers (x10-x17).
+pub fn set_entry_argument(thread: &mut Thread, index: usize, value: usize) {
+    thread.context.set_register(10 + index, value);
+}
+
 /// Save state of the current thread that was not saved by the trap handler
 /// for efficient trap handling. The trap handler will only save the minimal
 /// state required to run the trap handler without conflicting with the
@@ -89,13 +118,28 @@ pub fn save(_thread: &mut Thread) {
 /// will invoke some incantations and will return to the caller of this
 /// function.
 pub fn execute(thread: &mut Thread) -> Trap {
+    // The kernel must not be interrupted while it switches to the thread,
+    // since the trap handler would mistake the kernel for the thread. External
+    // interrupts masked while the kernel was running are unmasked, since they
+    // can be handled when the thread is interrupted.
+    let enabled = super::irq::enabled();
+    super::irq::disable();
+    super::irq::unmask_external();
+
     // TODO: Restore FPU state
     // Switch to the thread's page table and execute the thread.
     unsafe {
-        thread.root_table().set_current();
+        thread.address_space().root().set_current();
         thread_execute(&mut thread.context);
     }
 
+    if enabled {
+        // SAFETY: Interrupts were enabled before executing the thread.
+        unsafe {
+            super::irq::enable();
+        }
+    }
+
     // Here, we have returned from a trap. Determine the cause of the trap,
     // and return it to the caller to handle it.
     match riscv::register::scause::read().cause() {
@@ -105,6 +149,19 @@ pub fn execute(thread: &mut Thread) -> Trap {
     }
 }
 
+/// Get the current stack pointer of the given thread. On RISC-V, the stack
+/// pointer is stored in the sp register (x2).
+#[must_use]
+pub fn get_stack_pointer(thread: &Thread) -> usize {
+    thread.context.get_register(2)
+}
+
+/// Set the stack pointer of the given thread. On RISC-V, the stack pointer
+/// is stored in the sp register (x2).
+pub fn set_stack_pointer(thread: &mut Thread, sp: usize) {
+    thread.context.set_sp(sp);
+}
+
 /// Get the syscall identifier from the given thread. On RISC-V, the
 /// syscall identifier is stored in the a7 register (x17).
 #[must_use]
@@ -131,3 +188,33 @@ pub fn get_syscall_args(thread: &Thread) -> [usize; 6] {
 pub fn set_syscall_return(thread: &mut Thread, value: isize) {
     thread.context.set_register(10, value.cast_unsigned());
 }
+
+/// Get the registers of the given thread, as seen by a debugger. On RISC-V,
+/// the general purpose registers include the stack pointer (x2) and the
+/// thread pointer (x4).
+#[must_use]
+pub fn get_registers(thread: &Thread) -> Registers {
+    let mut registers = Registers::default();
+    for (index, register) in registers.general.iter_mut().enumerate() {
+        *register = thread.context.get_register(index) as u64;
+    }
+    registers.pc = thread.context.ip() as u64;
+    registers
+}
+
+/// Set the registers of the given thread from the registers given by a
+/// debugger. Writes to the x0 register are ignored since it is always zero.
+#[allow(clippy::cast_possible_truncation)]
+pub fn set_registers(thread: &mut Thread, registers: &Registers) {
+    for (index, &register) in registers.general.iter().enumerate() {
+        thread.context.set_register(index, register as usize);
+    }
+    thread.context.set_ip(registers.pc as usize);
+}
+
+/// Enable or disable the single-stepping of the given thread. RISC-V does not
+/// provide a way to single-step user code without the debug triggers of the
+/// firmware, so this fails when enabling it.
+pub fn set_single_step(_thread: &mut Thread, enabled: bool) -> bool {
+    !enabled
+}
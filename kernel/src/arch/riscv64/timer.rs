@@ -24,9 +24,11 @@ pub fn setup(device_tree: &fdt::Fdt) {
     log::debug!("Internal timer tick: {} ns", internal_tick());
     log::debug!("Internal timer frequency: {} Hz", internal_frequency());
 
-    // Enable timer interrupts.
+    // Enable timer interrupts and allow user space to read the `time`
+    // counter, used to compute the remaining quantum from the shared page.
     unsafe {
         riscv::register::sie::set_stimer();
+        riscv::register::scounteren::set_tm();
     }
 }
 
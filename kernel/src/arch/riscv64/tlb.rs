@@ -0,0 +1,97 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A bitmask of the harts that have enabled paging, and may therefore hold
+/// cached translations that must be invalidated when a mapping is removed.
+static ONLINE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// The identifier of the hart executing the kernel. The kernel currently
+/// only runs on the boot hart, so a single identifier is enough: once
+/// secondary harts are started, this must become a per-hart value.
+static CURRENT_HART: AtomicUsize = AtomicUsize::new(0);
+
+/// Register the given hart as the current hart and as a hart that must take
+/// part in TLB shootdowns. This must be called by each hart before enabling
+/// paging.
+///
+/// # Panics
+/// Panics if the hart identifier does not fit in the hart mask.
+pub fn register_hart(hart: usize) {
+    assert!(hart < usize::BITS as usize, "Hart identifier too large");
+    CURRENT_HART.store(hart, Ordering::Relaxed);
+    ONLINE_HARTS.fetch_or(1 << hart, Ordering::SeqCst);
+}
+
+/// Invalidate the translations of the page containing the given address in
+/// the TLB of the current hart, including global translations.
+#[inline]
+pub fn flush_page(address: usize) {
+    // SAFETY: Invalidating translations cannot cause undefined behavior, the
+    // page table will simply be walked again on the next access.
+    unsafe {
+        core::arch::asm!("sfence.vma {}, zero", in(reg) address, options(nostack));
+    }
+}
+
+/// Invalidate all the non-global translations in the TLB of the current hart.
+/// This is enough when switching between address spaces, since the kernel
+/// half is mapped with global translations that are shared by all address
+/// spaces and never change after boot.
+#[inline]
+pub fn flush_user() {
+    // SAFETY: Invalidating translations cannot cause undefined behavior, the
+    // page table will simply be walked again on the next access. The kernel
+    // does not use ASIDs, so all the user translations use the ASID 0.
+    unsafe {
+        core::arch::asm!("sfence.vma zero, {}", in(reg) 0, options(nostack));
+    }
+}
+
+/// Invalidate all the translations in the TLB of the current hart, including
+/// global translations.
+#[inline]
+pub fn flush_all() {
+    riscv::asm::sfence_vma_all();
+}
+
+/// Invalidate the translations of the page containing the given address on
+/// all the harts that may have cached it. The local TLB is flushed directly,
+/// and the other harts are asked to flush theirs with an inter-processor
+/// interrupt sent through the SBI. This function returns once all the harts
+/// have flushed their TLB, so the page can safely be reused afterwards.
+///
+/// # Panics
+/// Panics if the SBI fails to deliver the request to the other harts, since
+/// they could then keep using a stale translation to a page that may be
+/// reused.
+pub fn shootdown_page(address: usize) {
+    flush_page(address);
+    remote_flush(address, super::mmu::PAGE_SIZE);
+}
+
+/// Invalidate all the translations on all the harts that may have cached
+/// them. See [`shootdown_page`] for more details.
+///
+/// # Panics
+/// Panics if the SBI fails to deliver the request to the other harts.
+pub fn shootdown_all() {
+    flush_all();
+    // The SBI specification treats a size of `usize::MAX` as a request to
+    // flush the whole address space.
+    remote_flush(0, usize::MAX);
+}
+
+/// Ask all the online harts except the current one to invalidate the
+/// translations of the given range. This does nothing if the current hart is
+/// the only online hart, which avoids a costly SBI call in the common case.
+fn remote_flush(start: usize, size: usize) {
+    let current = CURRENT_HART.load(Ordering::Relaxed);
+    let others = ONLINE_HARTS.load(Ordering::SeqCst) & !(1 << current);
+    if others == 0 {
+        return;
+    }
+
+    let mask = (0..usize::BITS as usize)
+        .filter(|hart| others & (1 << hart) != 0)
+        .fold(sbi::HartMask::new(0), sbi::HartMask::with);
+    sbi::rfence::remote_sfence_vma(mask, start, size).expect("TLB shootdown failed");
+}
@@ -1,7 +1,11 @@
-use super::timer;
 use crate::{
-    arch::{thread::Thread, trap::Resume},
-    user,
+    arch::{
+        self,
+        thread::Thread,
+        trap::{FaultCause, FaultKind, Resume},
+    },
+    config, user,
+    utils::lock,
 };
 use riscv::register::{
     scause::{Exception, Interrupt, Trap},
@@ -10,6 +14,37 @@ use riscv::register::{
 
 core::arch::global_asm!(include_str!("asm/trap.asm"));
 
+/// The trap that the kernel could not recover from on each core, indexed by
+/// the index of the core. It is recorded just before panicking so that the
+/// panic handler can print the registers of the kernel at the time of the
+/// trap and start the backtrace from there.
+static FATAL_TRAPS: [lock::Mutex<Option<KernelTrap>>; config::MAX_CPUS] =
+    [const { lock::Mutex::new(None) }; config::MAX_CPUS];
+
+/// A trap that occurred while the kernel was running, along with the state of
+/// the kernel when it occurred.
+#[derive(Debug, Clone)]
+pub struct KernelTrap {
+    /// The cause of the trap.
+    pub cause: Trap,
+
+    /// The value of the `stval` register, which holds the faulting address
+    /// for memory faults.
+    pub stval: usize,
+
+    /// The registers of the kernel when the trap occurred.
+    pub context: Context,
+}
+
+impl KernelTrap {
+    /// Returns the value of the frame pointer (`s0`) when the trap occurred,
+    /// from where a backtrace of the kernel can be walked.
+    #[must_use]
+    pub const fn frame_pointer(&self) -> usize {
+        self.context.registers[7]
+    }
+}
+
 unsafe extern "C" {
     fn kernel_enter();
 }
@@ -73,12 +108,41 @@ impl Context {
         self.registers[1] = sp;
     }
 
+    /// Get the instruction pointer.
+    #[must_use]
+    pub const fn ip(&self) -> usize {
+        self.sepc
+    }
+
     /// Set the instruction pointer.
     pub fn set_ip(&mut self, ip: usize) {
         self.sepc = ip;
     }
 }
 
+impl core::fmt::Display for Context {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        const NAMES: [&str; 31] = [
+            "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4",
+            "a5", "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3",
+            "t4", "t5", "t6",
+        ];
+
+        writeln!(
+            f,
+            "  sepc: {:#018x}  sstatus: {:#018x}",
+            self.sepc, self.sstatus
+        )?;
+        for (i, (name, value)) in NAMES.iter().zip(self.registers.iter()).enumerate() {
+            write!(f, "  {name:>4}: {value:#018x}")?;
+            if i % 4 == 3 {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Default for Context {
     fn default() -> Self {
         Self::new()
@@ -95,38 +159,88 @@ pub fn setup() {
     }
 }
 
-pub fn handle_exception(_thread: &mut Thread) -> Resume {
+/// Handle an exception raised by the given thread. Page faults caused by the
+/// first access to a page of the user stack are resolved, and any other
+/// exception terminates the thread with a diagnostic including a dump of its
+/// registers.
+pub fn handle_exception(thread: &mut Thread) -> Resume {
     let scause = riscv::register::scause::read();
     let stval = riscv::register::stval::read();
     let sepc = riscv::register::sepc::read();
-    match scause.cause() {
-        Trap::Exception(Exception::InstructionFault) => {
-            log::error!(
-                "Instruction fault: {:?} (stval: {:#x}, sepc: {:#x})",
-                scause.cause(),
-                stval,
-                sepc
-            );
-            Resume::Fault
+    let resume = match scause.cause() {
+        Trap::Exception(Exception::LoadPageFault | Exception::StorePageFault) => {
+            let cause = FaultCause::classify(thread.address_space().root(), stval);
+            match cause {
+                // The user stack is allocated lazily, so a page fault on an
+                // unmapped page may simply be the first access to a page of
+                // the stack.
+                FaultCause::NotPresent => user::stack::handle_page_fault(thread, stval),
+                FaultCause::Protection => Resume::Fault,
+            }
         }
-        Trap::Exception(Exception::IllegalInstruction) => {
-            log::error!(
-                "Illegal instruction: {:?} (stval: {:#x}, sepc: {:#x})",
+        _ => Resume::Fault,
+    };
+
+    if resume == Resume::Fault {
+        match scause.cause() {
+            Trap::Exception(
+                Exception::InstructionPageFault
+                | Exception::LoadPageFault
+                | Exception::StorePageFault,
+            ) => log::error!(
+                "Page fault: {:?} ({:?}, stval: {:#x}, sepc: {:#x})",
                 scause.cause(),
+                FaultCause::classify(thread.address_space().root(), stval),
                 stval,
                 sepc
-            );
-            Resume::Fault
-        }
-        _ => {
-            log::error!(
+            ),
+            cause => log::error!(
                 "Unhandled exception: {:?} (stval: {:#x}, sepc: {:#x})",
-                scause.cause(),
+                cause,
                 stval,
                 sepc
-            );
-            Resume::Fault
+            ),
         }
+        log::error!("Registers of the faulting thread:\n{}", thread.context());
+    }
+    resume
+}
+
+/// Return the kind of the last exception, based on the `scause` register.
+#[must_use]
+pub fn fault_kind() -> FaultKind {
+    match riscv::register::scause::read().cause() {
+        Trap::Exception(
+            Exception::InstructionPageFault | Exception::LoadPageFault | Exception::StorePageFault,
+        ) => FaultKind::PageFault,
+        Trap::Exception(
+            Exception::InstructionFault | Exception::LoadFault | Exception::StoreFault,
+        ) => FaultKind::AccessFault,
+        Trap::Exception(Exception::IllegalInstruction) => FaultKind::IllegalInstruction,
+        Trap::Exception(
+            Exception::InstructionMisaligned
+            | Exception::LoadMisaligned
+            | Exception::StoreMisaligned,
+        ) => FaultKind::Misaligned,
+        _ => FaultKind::Unknown,
+    }
+}
+
+/// Return the faulting address of the last exception if it was caused by an
+/// invalid memory access, or `None` otherwise. On RISC-V, the faulting
+/// address is stored in the `stval` register.
+#[must_use]
+pub fn fault_address() -> Option<usize> {
+    match riscv::register::scause::read().cause() {
+        Trap::Exception(
+            Exception::InstructionFault
+            | Exception::InstructionPageFault
+            | Exception::LoadFault
+            | Exception::LoadPageFault
+            | Exception::StoreFault
+            | Exception::StorePageFault,
+        ) => Some(riscv::register::stval::read()),
+        _ => None,
     }
 }
 
@@ -136,13 +250,19 @@ pub fn handle_interrupt(_thread: &mut Thread) -> Resume {
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             // The timer interrupt is used to preempt the currently running
             // thread and switch to the next one if the current thread has
-            // used up its time slice. Also disable the timer to avoid getting
-            // another interrupt while handling this one.
-            timer::shutdown();
+            // used up its time slice. The timer is rearmed for the watchdog
+            // (or disabled if it is disabled) to avoid getting another
+            // interrupt while handling this one, and the expired timers are
+            // delivered by the executor.
+            crate::watchdog::arm();
+            crate::future::deferred::defer(|_| crate::time::wheel::poll(), 0);
             Resume::Yield
         }
         Trap::Interrupt(Interrupt::SupervisorExternal) => {
-            log::warn!("External interrupt");
+            // Yield after handling the interrupt, so that the drivers woken
+            // up by the interrupt can service their device without waiting
+            // for the end of the quantum of the current thread.
+            super::irq::handle_external();
             Resume::Yield
         }
         _ => {
@@ -164,7 +284,101 @@ pub async fn handle_syscall(thread: &mut crate::arch::thread::Thread) -> Resume
     resume
 }
 
+/// Handle a trap that occurred while the kernel was running. The only traps
+/// that the kernel can recover from are faults that occur while it accesses
+/// user memory on behalf of a syscall: in this case, the execution resumes in
+/// the fault handler of the user copy routine which reports the fault to the
+/// syscall.
+///
+/// When the watchdog is enabled, the kernel can also be interrupted. The timer
+/// interrupt runs the checks of the watchdog, and external interrupts are
+/// masked until the kernel is ready to handle them.
+///
+/// # Panics
+/// Panics on any other trap, since it is caused by a kernel bug, or if the
+/// watchdog detects that the core is locked up.
 #[unsafe(no_mangle)]
-pub extern "C" fn kernel_trap_handler() {
-    unimplemented!("Kernel trap handler");
+pub extern "C" fn kernel_trap_handler(registers: &[usize; 31]) {
+    let scause = riscv::register::scause::read();
+    let stval = riscv::register::stval::read();
+    let sepc = riscv::register::sepc::read();
+
+    let memory_fault = matches!(
+        scause.cause(),
+        Trap::Exception(
+            Exception::LoadPageFault
+                | Exception::StorePageFault
+                | Exception::LoadFault
+                | Exception::StoreFault
+        )
+    );
+
+    if memory_fault
+        && user::op::in_operation()
+        && let Some(fixup) = super::user::fixup(sepc)
+    {
+        log::debug!(
+            "Fault while accessing user memory at {:#x} (sepc: {:#x})",
+            stval,
+            sepc
+        );
+        riscv::register::sepc::write(fixup);
+        return;
+    }
+
+    let stalled = match scause.cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => match crate::watchdog::check() {
+            Some(stalled) => Some(stalled),
+            None => return,
+        },
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            super::irq::mask_external();
+            return;
+        }
+        _ => None,
+    };
+
+    // The stack pointer was saved after room was made for the registers on
+    // the stack, so the size of the saved registers is added back to get the
+    // stack pointer at the time of the trap.
+    let sstatus: usize;
+    // SAFETY: Reading the `sstatus` register has no side effects.
+    unsafe {
+        core::arch::asm!("csrr {}, sstatus", out(reg) sstatus);
+    }
+    let mut context = Context {
+        registers: *registers,
+        sstatus,
+        sepc,
+        padding: 0,
+    };
+    context.registers[1] += 32 * core::mem::size_of::<usize>();
+    *FATAL_TRAPS[arch::cpu::id()].lock() = Some(KernelTrap {
+        cause: scause.cause(),
+        stval,
+        context,
+    });
+
+    if let Some(stalled) = stalled {
+        panic!(
+            "Lockup detected: core {} made no progress for {:?} (sepc: {:#x})",
+            arch::cpu::id(),
+            stalled,
+            sepc
+        );
+    }
+    panic!(
+        "Unhandled kernel trap: {:?} (stval: {:#x}, sepc: {:#x})",
+        scause.cause(),
+        stval,
+        sepc
+    );
+}
+
+/// Returns the trap that the kernel could not recover from on the current
+/// core, if the current panic was caused by such a trap. This never blocks,
+/// since it is intended to be called from the panic handler.
+#[must_use]
+pub fn fatal_trap() -> Option<KernelTrap> {
+    FATAL_TRAPS[arch::cpu::id()].try_lock()?.clone()
 }
@@ -0,0 +1,164 @@
+use crate::arch::{self, mmio::MmioRegion, mmu::PAGE_SIZE, target::addr::Physical};
+
+/// The receive buffer register (read) and the transmit holding register
+/// (write).
+const REG_DATA: usize = 0;
+
+/// The interrupt enable register.
+const REG_IER: usize = 1;
+
+/// The FIFO control register (write only).
+const REG_FCR: usize = 2;
+
+/// The line control register.
+const REG_LCR: usize = 3;
+
+/// The line status register.
+const REG_LSR: usize = 5;
+
+/// Raise an interrupt when received data is available.
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+
+/// Enable the FIFOs and clear both of them.
+const FCR_ENABLE_AND_CLEAR: u8 = 0x07;
+
+/// 8 data bits, no parity and one stop bit, with the divisor latch disabled.
+const LCR_8N1: u8 = 0x03;
+
+/// Received data is available in the receive buffer register.
+const LSR_DATA_READY: u8 = 1 << 0;
+
+/// The transmit holding register is empty and can accept a new byte.
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+/// The UART of the system, if one was found in the device tree.
+static UART: spin::Once<Uart> = spin::Once::new();
+
+/// A ns16550-compatible UART, as found on the QEMU `virt` machine and on most
+/// riscv64 boards. The baud rate is left as configured by the firmware.
+#[derive(Debug)]
+struct Uart {
+    /// The registers of the UART.
+    registers: MmioRegion<u8>,
+
+    /// The number of bits by which register indexes are shifted to get their
+    /// offset from the base address, given by the `reg-shift` property.
+    shift: usize,
+
+    /// The interrupt number of the UART, if the device tree gives one.
+    irq: Option<usize>,
+}
+
+impl Uart {
+    /// Reads the given register.
+    fn read(&self, register: usize) -> u8 {
+        self.registers.read(register << self.shift)
+    }
+
+    /// Writes the given value to the given register.
+    fn write(&self, register: usize, value: u8) {
+        self.registers.write(register << self.shift, value);
+    }
+
+    /// Moves the bytes waiting in the receive FIFO of the UART to the console
+    /// input. Bytes received while the console input is full are dropped.
+    fn drain(&self) {
+        while self.read(REG_LSR) & LSR_DATA_READY != 0 {
+            arch::console::receive(self.read(REG_DATA));
+        }
+    }
+}
+
+/// Find the UART in the device tree and initialize it, enabling the receive
+/// interrupt. If no compatible UART is found, the console keeps using the
+/// SBI console.
+pub fn setup(device_tree: &fdt::Fdt) {
+    let Some(node) = device_tree.find_compatible(&["ns16550a", "ns16550"]) else {
+        log::info!("No UART found, using the SBI console");
+        return;
+    };
+    let Some(region) = node.reg().and_then(|mut reg| reg.next()) else {
+        log::warn!("UART without registers, using the SBI console");
+        return;
+    };
+
+    let physical = Physical::new(region.starting_address.addr());
+    let size = region.size.unwrap_or(PAGE_SIZE);
+    let Some(registers) = arch::mmu::map_mmio(physical, size) else {
+        log::warn!("UART registers cannot be mapped, using the SBI console");
+        return;
+    };
+    let uart = Uart {
+        registers,
+        shift: node
+            .property("reg-shift")
+            .and_then(fdt::node::NodeProperty::as_usize)
+            .unwrap_or(0),
+        irq: node.interrupts().and_then(|mut irqs| irqs.next()),
+    };
+
+    uart.write(REG_IER, 0);
+    uart.write(REG_LCR, LCR_8N1);
+    uart.write(REG_FCR, FCR_ENABLE_AND_CLEAR);
+    uart.write(REG_IER, IER_RX_AVAILABLE);
+
+    log::info!(
+        "UART at {:#010x} (irq {:?})",
+        region.starting_address.addr(),
+        uart.irq
+    );
+    UART.call_once(|| uart);
+}
+
+/// Write the given bytes to the UART, waiting for the transmitter to accept
+/// each byte. Returns `false` without writing anything if there is no UART.
+pub fn write(bytes: &[u8]) -> bool {
+    let Some(uart) = UART.get() else {
+        return false;
+    };
+    for &byte in bytes {
+        while uart.read(REG_LSR) & LSR_THR_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        uart.write(REG_DATA, byte);
+    }
+    true
+}
+
+/// Move the bytes waiting in the receive FIFO of the UART to the console
+/// input. This is called before reading the console input, so that input is
+/// not lost on platforms where the interrupt of the UART is not delivered to
+/// the kernel.
+pub fn poll() {
+    if let Some(uart) = UART.get() {
+        uart.drain();
+    }
+}
+
+/// Handle an interrupt raised by the UART, moving the received bytes to the
+/// console input. This must be called with interrupts disabled.
+pub fn handle_interrupt() {
+    if let Some(uart) = UART.get() {
+        uart.drain();
+    }
+}
+
+/// Return whether the bytes received by the UART are delivered by its
+/// interrupt, so that a task waiting for input can sleep until it arrives.
+#[must_use]
+pub fn interrupt_driven() -> bool {
+    irq().is_some() && arch::irq::controller_available()
+}
+
+/// Return the interrupt number of the UART, or `None` if there is no UART or
+/// if the device tree does not give its interrupt.
+#[must_use]
+pub fn irq() -> Option<usize> {
+    UART.get().and_then(|uart| uart.irq)
+}
+
+/// Return whether a UART was found and initialized.
+#[must_use]
+pub fn available() -> bool {
+    UART.get().is_some()
+}
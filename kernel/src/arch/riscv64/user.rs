@@ -0,0 +1,43 @@
+use crate::arch::user::Fault;
+
+core::arch::global_asm!(include_str!("asm/user.asm"));
+
+unsafe extern "C" {
+    fn user_copy(dst: *mut u8, src: *const u8, len: usize) -> usize;
+    fn user_copy_end();
+    fn user_copy_fault();
+}
+
+/// Copy `len` bytes from `src` to `dst`, where one of them is in the user
+/// address space. If a fault occurs during the copy, the copy is aborted and
+/// the faulting address is returned as an error.
+///
+/// # Errors
+/// Returns a [`Fault`] if the copy faulted. In this case, the content of the
+/// destination is unspecified.
+///
+/// # Safety
+/// The caller must ensure that the kernel memory is valid for the copy and
+/// that access to user pages is allowed. Interrupts must be disabled during
+/// the copy, so that the fault address is not overwritten by another trap.
+pub unsafe fn copy(dst: *mut u8, src: *const u8, len: usize) -> Result<(), Fault> {
+    if unsafe { user_copy(dst, src, len) } == 0 {
+        Ok(())
+    } else {
+        Err(Fault {
+            address: riscv::register::stval::read(),
+        })
+    }
+}
+
+/// Return the address where the execution should resume if a fault occurs at
+/// the given program counter, or `None` if the program counter is not in the
+/// user copy routine and the fault cannot be recovered.
+#[must_use]
+pub fn fixup(pc: usize) -> Option<usize> {
+    let start = user_copy as usize;
+    let end = user_copy_end as usize;
+    (start..end)
+        .contains(&pc)
+        .then_some(user_copy_fault as usize)
+}
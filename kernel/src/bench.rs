@@ -0,0 +1,155 @@
+use crate::{arch, config, future::task::Identifier, stats, time::Instant};
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// The time spent by the kernel to handle a syscall that did not wait for
+/// anything, from the trap of the thread until it is about to return to user
+/// mode.
+static SYSCALL_ENTRY: Measure = Measure::new();
+
+/// The time between a thread giving up its core and the next thread resuming
+/// its execution in user mode on the same core.
+static CONTEXT_SWITCH: Measure = Measure::new();
+
+/// The time between an IPC message being sent and its reply being received
+/// by the sender.
+static IPC_ROUND_TRIP: Measure = Measure::new();
+
+/// The instant at which the last thread of each core gave up its core, in
+/// nanoseconds since boot, indexed by the index of the core. Zero means that
+/// no switch is in progress on the core.
+static SWITCH_START: [AtomicU64; config::MAX_CPUS] =
+    [const { AtomicU64::new(0) }; config::MAX_CPUS];
+
+/// The task that drives the benchmark. The results are reported and the
+/// computer is shut down when this task terminates.
+static DRIVER: spin::Once<Identifier> = spin::Once::new();
+
+/// Statistics about a series of durations, updated with atomic operations so
+/// that they can be recorded from any core without locking.
+struct Measure {
+    /// The number of durations recorded.
+    count: AtomicU64,
+
+    /// The sum of all the durations recorded, in nanoseconds.
+    total: AtomicU64,
+
+    /// The shortest duration recorded, in nanoseconds.
+    min: AtomicU64,
+
+    /// The longest duration recorded, in nanoseconds.
+    max: AtomicU64,
+}
+
+impl Measure {
+    /// Creates a new measure without any duration recorded.
+    const fn new() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    /// Records the given duration.
+    #[allow(clippy::cast_possible_truncation)]
+    fn record(&self, duration: Duration) {
+        let nanos = duration.as_nanos() as u64;
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.total.fetch_add(nanos, Ordering::Relaxed);
+        self.min.fetch_min(nanos, Ordering::Relaxed);
+        self.max.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    /// Logs the statistics of the measure under the given name.
+    fn report(&self, name: &str) {
+        let count = self.count.load(Ordering::Relaxed);
+        if count == 0 {
+            log::info!("{name}: no sample");
+            return;
+        }
+
+        log::info!(
+            "{}: {} samples, average {} ns, min {} ns, max {} ns",
+            name,
+            count,
+            self.total.load(Ordering::Relaxed) / count,
+            self.min.load(Ordering::Relaxed),
+            self.max.load(Ordering::Relaxed)
+        );
+    }
+}
+
+/// Records the time spent by the kernel to handle a syscall that did not wait
+/// for anything. This does nothing unless [`config::BENCHMARK`] is enabled.
+pub fn record_syscall(duration: Duration) {
+    if config::BENCHMARK {
+        SYSCALL_ENTRY.record(duration);
+    }
+}
+
+/// Records the round-trip time of an IPC message. This does nothing unless
+/// [`config::BENCHMARK`] is enabled.
+pub fn record_ipc_round_trip(duration: Duration) {
+    if config::BENCHMARK {
+        IPC_ROUND_TRIP.record(duration);
+    }
+}
+
+/// Marks the start of a context switch on the current core, when the running
+/// thread gives up its core. This does nothing unless [`config::BENCHMARK`]
+/// is enabled.
+pub fn switch_out() {
+    if config::BENCHMARK {
+        SWITCH_START[arch::cpu::id()].store(Instant::now().as_nanos(), Ordering::Relaxed);
+    }
+}
+
+/// Marks the end of a context switch on the current core, when a thread is
+/// about to resume its execution in user mode, and records its duration if a
+/// switch was in progress. This does nothing unless [`config::BENCHMARK`] is
+/// enabled.
+pub fn switch_in() {
+    if config::BENCHMARK {
+        let start = SWITCH_START[arch::cpu::id()].swap(0, Ordering::Relaxed);
+        if start != 0 {
+            CONTEXT_SWITCH.record(Duration::from_nanos(
+                Instant::now().as_nanos().saturating_sub(start),
+            ));
+        }
+    }
+}
+
+/// Sets the task that drives the benchmark. When it terminates, the results
+/// are reported and the computer is shut down (see [`task_terminated`]).
+pub fn set_driver(id: Identifier) {
+    DRIVER.call_once(|| id);
+}
+
+/// Notifies the benchmark that the given task has terminated. If the task is
+/// the driver of the benchmark, the results are reported and the computer is
+/// shut down.
+pub fn task_terminated(id: Identifier) {
+    if config::BENCHMARK && DRIVER.get() == Some(&id) {
+        report();
+        arch::shutdown();
+    }
+}
+
+/// Logs the results of the benchmark, along with the activity counters of the
+/// kernel since boot.
+pub fn report() {
+    log::info!("Benchmark results:");
+    SYSCALL_ENTRY.report("Syscall entry");
+    CONTEXT_SWITCH.report("Context switch");
+    IPC_ROUND_TRIP.report("IPC round trip");
+    log::info!(
+        "Counters: {} syscalls, {} context switches, {} IPC messages",
+        stats::syscalls(),
+        stats::context_switches(),
+        stats::ipc_messages()
+    );
+}
@@ -0,0 +1,120 @@
+use crate::config;
+use core::str::FromStr;
+
+/// The kernel command line given by the bootloader. It is a list of options
+/// separated by whitespace, each of them being either a `key=value` pair or a
+/// single `key` used as a flag, which lets subsystems be configured at boot
+/// without recompiling the kernel. The command line is copied at boot, so
+/// that options can be read without walking the device tree.
+static CMDLINE: spin::Once<Cmdline> = spin::Once::new();
+
+/// A copy of the kernel command line.
+#[derive(Debug)]
+struct Cmdline {
+    /// The bytes of the command line. Only the first `len` bytes are used,
+    /// and they are always valid UTF-8.
+    bytes: [u8; config::CMDLINE_CAPACITY],
+
+    /// The length of the command line, in bytes.
+    len: usize,
+}
+
+impl Cmdline {
+    /// Returns the command line as a string.
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+}
+
+/// Saves the command line given by the bootloader. The trailing NUL bytes of
+/// the device tree property are ignored, and a command line that is not valid
+/// UTF-8 is ignored entirely. A command line longer than
+/// [`config::CMDLINE_CAPACITY`] is truncated before its first option that
+/// does not fit, so that options are never cut in the middle. Calling this
+/// function more than once has no effect.
+pub fn setup(bootargs: &[u8]) {
+    CMDLINE.call_once(|| {
+        let mut cmdline = Cmdline {
+            bytes: [0; config::CMDLINE_CAPACITY],
+            len: 0,
+        };
+
+        let Ok(text) = core::str::from_utf8(bootargs) else {
+            log::warn!("Ignoring the kernel command line: not valid UTF-8");
+            return cmdline;
+        };
+
+        for option in text.trim_end_matches('\0').split_whitespace() {
+            let separator = usize::from(cmdline.len > 0);
+            let end = cmdline.len + separator + option.len();
+            if end > config::CMDLINE_CAPACITY {
+                log::warn!("Kernel command line truncated before \"{}\"", option);
+                break;
+            }
+            if separator > 0 {
+                cmdline.bytes[cmdline.len] = b' ';
+            }
+            cmdline.bytes[end - option.len()..end].copy_from_slice(option.as_bytes());
+            cmdline.len = end;
+        }
+
+        if cmdline.len > 0 {
+            log::info!("Kernel command line: {}", cmdline.as_str());
+        }
+        cmdline
+    });
+}
+
+/// Returns an iterator over the options of the command line, as `(key, value)`
+/// pairs. The value of an option without `=` is an empty string. Nothing is
+/// returned if the command line was not set up.
+pub fn options() -> impl Iterator<Item = (&'static str, &'static str)> {
+    CMDLINE
+        .get()
+        .map(Cmdline::as_str)
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|option| option.split_once('=').unwrap_or((option, "")))
+}
+
+/// Returns the value of the given option, or an empty string if the option is
+/// given without a value. When an option is given several times, the last
+/// occurrence wins, so that options appended by the bootloader override the
+/// default ones. Returns `None` if the option is not on the command line.
+#[must_use]
+pub fn get(key: &str) -> Option<&'static str> {
+    options()
+        .filter(|(name, _)| *name == key)
+        .last()
+        .map(|(_, value)| value)
+}
+
+/// Returns whether the given boolean option is enabled. An option given
+/// without a value is enabled, and `1`, `on`, `yes`, `true`, `0`, `off`,
+/// `no` and `false` are accepted as values. Returns `None` if the option is
+/// not on the command line or if its value is invalid, so that the caller can
+/// fall back to the compile-time default.
+#[must_use]
+pub fn flag(key: &str) -> Option<bool> {
+    match get(key)? {
+        "" | "1" | "on" | "yes" | "true" => Some(true),
+        "0" | "off" | "no" | "false" => Some(false),
+        value => {
+            log::warn!("Ignoring invalid value \"{}\" for option {}", value, key);
+            None
+        }
+    }
+}
+
+/// Parses the value of the given option. Returns `None` if the option is not
+/// on the command line or if its value cannot be parsed, so that the caller
+/// can fall back to the compile-time default.
+#[must_use]
+pub fn parse<T: FromStr>(key: &str) -> Option<T> {
+    let value = get(key)?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        log::warn!("Ignoring invalid value \"{}\" for option {}", value, key);
+    }
+    parsed
+}
@@ -1,8 +1,18 @@
 use core::time::Duration;
 
-/// The maximum number of tasks that can be created. The kernel will use this
-/// constant to allocate memory for the task control blocks and other data
-/// during initialization. Diminishing this value will reduce the memory usage
+/// Whether the intermediate page tables of the kernel half are all allocated
+/// during boot. The top-level entries of the kernel half are always populated
+/// when the MMU is set up, and this also allocates the tables of the whole
+/// kernel heap range, which costs 2 MiB of memory. Mapping a page in the
+/// kernel half after boot then never allocates a table, so growing the kernel
+/// heap cannot fail halfway through a mapping. When disabled, the tables of
+/// the kernel heap are allocated on demand as the heap grows.
+pub const PREALLOCATE_KERNEL_TABLES: bool = true;
+
+/// The maximum number of tasks that can exist at the same time. The kernel
+/// uses this constant to preallocate a slot for each task in the tables of
+/// the executor and of the task local data, and refuses to create a task once
+/// all the slots are taken. Diminishing this value will reduce the memory usage
 /// of the kernel, but it will also limit the number of tasks that can be run
 /// concurrently.
 ///
@@ -11,6 +21,12 @@ use core::time::Duration;
 /// this will work well enough.
 pub const MAX_TASKS: u16 = 32;
 
+/// The maximum number of CPU cores supported by the kernel. The executor has a
+/// run queue for each of them, which is allocated even if the core is not
+/// present. Kiwi currently only runs on the boot core, but the executor already
+/// balances its tasks between several cores.
+pub const MAX_CPUS: usize = 4;
+
 /// The size of the kernel stack. This should be a multiple of the page size,
 /// which is 4096 bytes on almost all systems. The kernel stack is used by the
 /// kernel to handle syscalls, interrupts, and exceptions.
@@ -34,3 +50,209 @@ pub const KERNEL_STACK_SIZE: usize = 4096 * 4;
 /// based on the specific requirements of the system and the nature of the tasks
 /// being run.
 pub const THREAD_MAX_RUN_DURATION: Duration = Duration::from_millis(25);
+
+/// The maximum duration of kernel work that a long-running kernel operation
+/// can perform before voluntarily yielding to the executor, such as when
+/// destroying a large address space. It is derived from the thread quantum
+/// so that a single operation never delays other tasks by more than a
+/// fraction of their quantum.
+#[allow(clippy::cast_possible_truncation)]
+pub const KERNEL_WORK_BUDGET: Duration =
+    Duration::from_nanos(THREAD_MAX_RUN_DURATION.as_nanos() as u64 / 4);
+
+/// Whether task identifiers are allocated from a random offset. By default,
+/// dynamically allocated identifiers always start right after the range of
+/// reserved identifiers, so that the same boot sequence always produces the
+/// same identifiers. This is relied upon by the integration tests that compare
+/// the output of the kernel against a golden output.
+///
+/// When enabled, dynamically allocated identifiers start at an offset that
+/// depends on the boot time, to discourage user space from relying on the
+/// value of task identifiers. This can be enabled with the `random-ids`
+/// feature, and overridden at boot with the `random-ids` option of the kernel
+/// command line.
+pub const RANDOM_TASK_IDS: bool = cfg!(feature = "random-ids");
+
+/// Whether the random generators of the kernel are deterministic. When
+/// enabled, the seed given by the bootloader and the timing of interrupts are
+/// ignored, so that the same boot sequence always produces the same random
+/// numbers and the same random bytes for user space. This is useful to
+/// reproduce a bug that depends on them, but must never be enabled on a real
+/// system. This can be enabled with the `deterministic-random` feature, and
+/// overridden at boot with the `deterministic-random` option of the kernel
+/// command line.
+pub const DETERMINISTIC_RANDOM: bool = cfg!(feature = "deterministic-random");
+
+/// Whether the kernel records trace events into the per-core trace buffers
+/// (see [`crate::trace`]) by default. When disabled, recording an event only
+/// checks a flag and the trace buffers are never allocated. This can be
+/// enabled with the `trace` feature, and overridden at boot with the `trace`
+/// option of the kernel command line.
+pub const TRACING: bool = cfg!(feature = "trace");
+
+/// Whether the virtual address of the kernel image is randomized at boot. The
+/// image is slid by a random multiple of 2 MiB, so that an attacker cannot
+/// rely on the addresses of the code and data of the kernel. This can be
+/// disabled with the `deterministic-layout` feature, so that the addresses
+/// printed in backtraces match the kernel binary when debugging.
+pub const RANDOMIZE_KERNEL_BASE: bool = !cfg!(feature = "deterministic-layout");
+
+/// Whether the addresses of the user stack and of the heap are randomized
+/// for each task, so that an attacker cannot rely on them to exploit a bug of
+/// a task. This can be disabled with the `deterministic-layout` feature, so
+/// that the same program always runs with the same addresses when debugging.
+pub const RANDOMIZE_USER_LAYOUT: bool = !cfg!(feature = "deterministic-layout");
+
+/// Whether the kernel runs the benchmark suite. The `pong` and `ping` programs
+/// are spawned at boot, the kernel measures the cost of syscalls, context
+/// switches and IPC round trips while they run, and reports the results
+/// before shutting down once `ping` terminates (see [`crate::bench`]). This is
+/// enabled with the `bench` feature.
+pub const BENCHMARK: bool = cfg!(feature = "bench");
+
+/// Whether the kernel runs the integration test suite. The test cases of the
+/// `integration` program are spawned at boot, and the kernel checks how each
+/// of them terminates before shutting down with a status telling whether all
+/// of them passed (see [`crate::testing`]). This is enabled with the
+/// `integration` feature.
+pub const INTEGRATION_TEST: bool = cfg!(feature = "integration");
+
+/// Whether the computer should be rebooted instead of shut down when the
+/// kernel panics. Shutting down keeps the panic report on the screen, which is
+/// more convenient during development, while rebooting lets an unattended
+/// system recover from a kernel bug.
+pub const REBOOT_ON_PANIC: bool = false;
+
+/// Whether the watchdog is enabled. The watchdog detects cores that stop
+/// making progress, for example because a task spins in the kernel, which
+/// would otherwise manifest as a silent hang. Enabling it lets the timer
+/// interrupt the kernel, and wakes up idle cores periodically.
+pub const WATCHDOG: bool = true;
+
+/// The duration without progress after which the watchdog considers that a
+/// core is locked up. This must be much longer than the longest time the
+/// kernel may legitimately spend polling a single task.
+pub const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether the watchdog terminates the task that locked up a core instead of
+/// panicking at once. Since the kernel cannot interrupt a task in the middle
+/// of a poll, the task is terminated when its poll returns, and the kernel
+/// still panics if the core has not made progress after another
+/// [`WATCHDOG_TIMEOUT`].
+pub const WATCHDOG_TERMINATES_TASK: bool = false;
+
+/// The number of events that the trace buffer of each core can hold. When a
+/// buffer is full, its oldest events are overwritten.
+pub const TRACE_BUFFER_CAPACITY: usize = 512;
+
+/// The duration that the background tasks of the kernel are given to complete
+/// when the system goes down, after which they are cancelled (see
+/// [`crate::future::executor::shutdown`]).
+pub const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The duration during which the termination information of a task is kept
+/// if no task collects it. Without this limit, the termination information of
+/// tasks that nobody waits for would accumulate forever.
+pub const TERMINATION_INFO_LIFETIME: Duration = Duration::from_secs(60);
+
+/// The maximum number of IPC messages that can be pending in the mailbox of a
+/// task. When the mailbox of a task is full, senders wait until the task
+/// receives a message before delivering theirs. A larger value allows more
+/// clients to have requests pending simultaneously, at the cost of more memory
+/// used by tasks that do not keep up with their incoming messages.
+pub const IPC_MAILBOX_CAPACITY: usize = 16;
+
+/// The maximum number of free frames kept in the frame cache of each CPU core.
+/// Most frame allocations are served from this cache without taking the lock
+/// of the physical memory allocator. When the cache is empty it is refilled
+/// with half of its capacity, and when it is full half of it is returned to
+/// the allocator, so that the cost of the lock is amortized over many
+/// allocations.
+pub const FRAME_CACHE_CAPACITY: usize = 64;
+
+/// The maximum number of synchronization objects that a task can own at the
+/// same time. Objects live in kernel memory until their owner destroys them or
+/// terminates, so this prevents a single task from exhausting the kernel heap.
+pub const SYNC_OBJECTS_PER_TASK: usize = 64;
+
+/// The maximum number of timers that a task can own at the same time. Each
+/// timer expires periodically until its owner destroys it or terminates, so
+/// this bounds the work that a single task can give to the timer wheel.
+pub const TIMERS_PER_TASK: usize = 16;
+
+/// The maximum number of threads that a task can have at the same time,
+/// including its first thread. Each thread is run by its own executor task and
+/// therefore counts against [`MAX_TASKS`], so this prevents a single task from
+/// exhausting them.
+pub const MAX_THREADS_PER_TASK: usize = 8;
+
+/// The maximum number of tasks that a restricted service can accept at the
+/// same time. Accepted tasks are kept in kernel memory as long as the service
+/// is registered, so this prevents a service from exhausting the kernel heap.
+pub const MAX_ACCEPTED_TASKS: usize = 32;
+
+/// The maximum number of connections to services that a task can hold at the
+/// same time. Connections are kept in the handle table of the task until they
+/// are closed, so this prevents a task from exhausting the kernel heap by
+/// connecting again and again.
+pub const MAX_CONNECTIONS_PER_TASK: usize = 64;
+
+/// The maximum length of the kernel command line, in bytes. The command line
+/// is copied from the device tree at boot into a buffer of this size, before
+/// the kernel heap is available. A longer command line is truncated before
+/// its first option that does not fit.
+pub const CMDLINE_CAPACITY: usize = 512;
+
+/// The maximum number of modules of the kernel that can have their own log
+/// level, set with the kernel command line or the `LogControl` syscall.
+pub const LOG_MODULE_LEVELS: usize = 16;
+
+/// The size of the buffer holding the log messages printed before the console
+/// is ready, in bytes. The messages that do not fit are dropped, and their
+/// number is reported once the buffer is flushed to the console.
+pub const EARLY_LOG_CAPACITY: usize = 4096;
+
+/// The number of messages kept in the kernel log, which user space can read
+/// with the `LogRead` syscall. When the log is full, its oldest messages are
+/// overwritten.
+pub const LOG_RING_CAPACITY: usize = 128;
+
+/// The percentage of the usable memory under which the memory pressure is
+/// [`::syscall::memory::Pressure::Low`]. The kernel then runs its reclaim hooks
+/// and notifies the subscribed tasks, so that caches are released before
+/// allocations start failing.
+pub const LOW_MEMORY_WATERMARK: usize = 10;
+
+/// The percentage of the usable memory under which the memory pressure is
+/// [`::syscall::memory::Pressure::Critical`]. It must be lower than
+/// [`LOW_MEMORY_WATERMARK`].
+pub const CRITICAL_MEMORY_WATERMARK: usize = 3;
+
+/// The maximum number of reclaim hooks that can be registered (see
+/// [`crate::mm::pressure::register`]). Each object cache of the kernel
+/// registers a hook when it allocates its first object.
+pub const RECLAIM_HOOKS: usize = 16;
+
+/// The maximum number of calls that each core can defer to the executor (see
+/// [`crate::future::deferred::defer`]). When the queue of a core is full,
+/// the work is run immediately by the trap handler deferring it.
+pub const DEFERRED_WORK: usize = 64;
+
+/// The maximum number of spinlocks that a core can hold at the same time for
+/// them to be checked by the `lock-debug` feature (see [`crate::utils::lock`]).
+/// The locks acquired beyond this limit are not checked.
+pub const LOCK_DEBUG_DEPTH: usize = 16;
+
+/// The maximum number of orders between two kinds of spinlocks that the
+/// `lock-debug` feature remembers (see [`crate::utils::lock`]). The orders
+/// seen once this limit is reached are not checked.
+pub const LOCK_DEBUG_ORDERS: usize = 512;
+
+/// The maximum number of task-local keys that the kernel can declare (see
+/// [`crate::future::local`]). Each task has room for the value of every key,
+/// so raising this increases the memory used by each task.
+pub const TASK_LOCAL_KEYS: usize = 16;
+
+/// The number of bytes received by the console that can wait to be read.
+/// Bytes received while this many bytes are waiting are dropped.
+pub const CONSOLE_INPUT_SIZE: usize = 256;
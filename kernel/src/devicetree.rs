@@ -0,0 +1,75 @@
+use crate::arch::{
+    self,
+    memory::{Region, UsableMemory},
+};
+
+/// The device tree given by the bootloader. It is kept in memory after boot
+/// so that user space drivers can find the registers and the interrupts of the
+/// devices that the kernel does not describe itself (see
+/// [`crate::arch::device`]), which only covers the devices it probes.
+static DEVICE_TREE: spin::Once<fdt::Fdt<'static>> = spin::Once::new();
+
+/// The errors that may occur when reading a property of the device tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryError {
+    /// No device tree was kept at boot.
+    Unavailable,
+
+    /// There is no node with the given path.
+    NodeNotFound,
+
+    /// The node has no property with the given name.
+    PropertyNotFound,
+}
+
+/// Keeps the device tree at the given address for the lifetime of the kernel,
+/// and removes the memory holding it from the usable memory so that it is
+/// never overwritten. An invalid device tree is ignored. Calling this function
+/// more than once has no effect.
+///
+/// # Safety
+/// The address must be the one of the device tree given by the bootloader in
+/// the physical memory map, and nothing else may modify the memory holding it.
+pub unsafe fn setup(memory: &mut UsableMemory, address: usize) {
+    let ptr = core::ptr::with_exposed_provenance::<u8>(address);
+    // SAFETY: The caller guarantees that the address is the one of the device
+    // tree, which is never modified.
+    let Ok(device_tree) = (unsafe { fdt::Fdt::from_ptr(ptr) }) else {
+        log::warn!("Invalid device tree, it will not be available to drivers");
+        return;
+    };
+
+    DEVICE_TREE.call_once(|| {
+        let region = Region {
+            start: arch::mmu::translate_kernel_ptr(ptr).as_usize(),
+            length: device_tree.total_size(),
+        };
+        memory.reserve(region);
+        log::debug!(
+            "Device tree: {:#010x} - {:#010x}",
+            region.start,
+            region.end()
+        );
+        device_tree
+    });
+}
+
+/// Returns the raw value of the property with the given name, in the node
+/// with the given path. The path starts with a `/`, and the unit address of
+/// a node can be omitted to find the first node with that name. Aliases
+/// defined in the `/aliases` node are resolved as well.
+///
+/// # Errors
+/// Returns [`QueryError::Unavailable`] if no device tree was kept at boot,
+/// [`QueryError::NodeNotFound`] if there is no node with this path, or
+/// [`QueryError::PropertyNotFound`] if the node has no property with this
+/// name.
+pub fn property(path: &str, name: &str) -> Result<&'static [u8], QueryError> {
+    let device_tree = DEVICE_TREE.get().ok_or(QueryError::Unavailable)?;
+    let node = device_tree
+        .find_node(path)
+        .ok_or(QueryError::NodeNotFound)?;
+    node.property(name)
+        .map(|property| property.value)
+        .ok_or(QueryError::PropertyNotFound)
+}
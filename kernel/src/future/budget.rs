@@ -0,0 +1,43 @@
+use crate::{config, future, time::Instant};
+
+/// A budget of kernel work. Since the kernel is cooperative, a long-running
+/// kernel operation cannot be preempted and would starve all other tasks until
+/// its completion. Such operations should instead be split into small steps,
+/// and call [`Budget::checkpoint`] between each step to voluntarily yield to
+/// the executor when the budget is exhausted.
+#[derive(Debug)]
+pub struct Budget {
+    deadline: Instant,
+}
+
+impl Budget {
+    /// Creates a new budget of [`config::KERNEL_WORK_BUDGET`], starting now.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            deadline: Instant::now() + config::KERNEL_WORK_BUDGET,
+        }
+    }
+
+    /// Returns whether the budget is exhausted.
+    #[must_use]
+    pub fn exhausted(&self) -> bool {
+        self.deadline.has_passed()
+    }
+
+    /// Yields to the executor if the budget is exhausted, and refills the
+    /// budget once the task is polled again. Otherwise, this returns
+    /// immediately.
+    pub async fn checkpoint(&mut self) {
+        if self.exhausted() {
+            future::yield_once().await;
+            *self = Self::new();
+        }
+    }
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,132 @@
+//! A bounded channel handing values from any context to an async task. The
+//! values are sent without blocking with [`Channel::try_send`], which never
+//! allocates and can therefore be called from a trap handler, and received by
+//! a task that sleeps with [`Channel::recv`] until a value is available.
+//!
+//! A channel can have any number of senders but a single receiver: only the
+//! last task waiting on [`Channel::recv`] is woken up when a value is sent, so
+//! subsystems with several receivers must serialize them themselves.
+use crate::{arch, utils::lock};
+use core::{
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use futures::Future;
+use heapless::Deque;
+
+/// A bounded channel holding at most `N` values that were sent but not yet
+/// received. The channel is usable in a static, since it does not need the
+/// kernel heap.
+pub struct Channel<T, const N: usize> {
+    /// The state of the channel. It is locked with interrupts disabled, so
+    /// that a trap handler sending a value never spins on a lock held by the
+    /// code it interrupted.
+    state: lock::Mutex<State<T, N>>,
+}
+
+/// The state of a [`Channel`].
+struct State<T, const N: usize> {
+    /// The values sent and not yet received, from the oldest to the most
+    /// recent.
+    values: Deque<T, N>,
+
+    /// The waker of the task waiting to receive a value, if any.
+    receiver: Option<Waker>,
+}
+
+impl<T, const N: usize> Channel<T, N> {
+    /// Creates an empty channel.
+    #[must_use]
+    #[track_caller]
+    pub const fn new() -> Self {
+        Self {
+            state: lock::Mutex::new(State {
+                values: Deque::new(),
+                receiver: None,
+            }),
+        }
+    }
+
+    /// Sends a value into the channel without blocking, waking up the task
+    /// waiting to receive it if any. This can be called from a trap handler.
+    ///
+    /// # Errors
+    /// Returns the value back if the channel is full.
+    pub fn try_send(&self, value: T) -> Result<(), T> {
+        let receiver = arch::irq::without(|| {
+            let mut state = self.state.lock();
+            state.values.push_back(value)?;
+            Ok(state.receiver.take())
+        })?;
+
+        // The receiver is woken up once the channel is unlocked, so that it
+        // can take the value right away if it runs on another core.
+        if let Some(receiver) = receiver {
+            receiver.wake();
+        }
+        Ok(())
+    }
+
+    /// Receives the oldest value of the channel without blocking, or returns
+    /// `None` if the channel is empty.
+    pub fn try_recv(&self) -> Option<T> {
+        arch::irq::without(|| self.state.lock().values.pop_front())
+    }
+
+    /// Returns a future receiving the oldest value of the channel, which waits
+    /// until a value is sent if the channel is empty.
+    pub fn recv(&self) -> Recv<'_, T, N> {
+        Recv { channel: self }
+    }
+
+    /// Returns the number of values sent and not yet received.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        arch::irq::without(|| self.state.lock().values.len())
+    }
+
+    /// Returns whether no value is waiting to be received.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T, const N: usize> Default for Channel<T, N> {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> core::fmt::Debug for Channel<T, N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Channel")
+            .field("len", &self.len())
+            .field("capacity", &N)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A future receiving a value from a [`Channel`], returned by
+/// [`Channel::recv`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct Recv<'a, T, const N: usize> {
+    channel: &'a Channel<T, N>,
+}
+
+impl<T, const N: usize> Future for Recv<'_, T, N> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        arch::irq::without(|| {
+            let mut state = self.channel.state.lock();
+            if let Some(value) = state.values.pop_front() {
+                return Poll::Ready(value);
+            }
+            state.receiver = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+}
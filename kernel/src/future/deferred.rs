@@ -0,0 +1,52 @@
+//! Work deferred by the trap handlers to the executor. A trap handler must
+//! return quickly and cannot take the locks that the interrupted code may
+//! hold, so it only does what is urgent, such as masking the source of an
+//! interrupt, and defers the rest of the handling to the executor, which runs
+//! the deferred work between two tasks with no lock held.
+//!
+//! Each core has its own queue, so that work is run on the core that deferred
+//! it and deferring work never contends with another core.
+use crate::{config, utils::percpu::PerCpu};
+
+/// The work deferred by the current core and not run yet.
+static QUEUE: PerCpu<heapless::Deque<Work, { config::DEFERRED_WORK }>> =
+    PerCpu::new(heapless::Deque::new());
+
+/// A function to run later with its argument.
+#[derive(Debug, Clone, Copy)]
+struct Work {
+    /// The function to run.
+    function: fn(usize),
+
+    /// The argument given to the function.
+    argument: usize,
+}
+
+/// Defers the call of the given function with the given argument, until the
+/// executor runs the deferred work of the current core. Work is run in the
+/// order in which it was deferred. This never allocates, so it can be called
+/// from a trap handler.
+///
+/// If [`config::DEFERRED_WORK`] calls are already deferred on the current
+/// core, the function is called immediately instead, so that no work is ever
+/// lost.
+pub fn defer(function: fn(usize), argument: usize) {
+    let work = Work { function, argument };
+    if let Err(work) = QUEUE.with(|queue| queue.push_back(work)) {
+        (work.function)(work.argument);
+    }
+}
+
+/// Runs the work deferred by the current core. The work deferred while this
+/// runs is left for the next call, so that work deferring itself again does
+/// not prevent the executor from making progress. This is called by the
+/// executor between two tasks and each time the core wakes up.
+pub fn run() {
+    let pending = QUEUE.with(|queue| queue.len());
+    for _ in 0..pending {
+        let Some(work) = QUEUE.with(heapless::Deque::pop_front) else {
+            break;
+        };
+        (work.function)(work.argument);
+    }
+}
@@ -1,17 +1,29 @@
 use crate::{
-    arch, config,
+    arch, cmdline, config,
     future::{
-        task::{self, Task},
-        user::thread_loop,
+        self, deferred, group,
+        slot::SlotTable,
+        task::{self, Affinity, Kind, LocalDataSet, Priority, Task},
+        user::{Exit, thread_loop},
+        waker::Waker,
     },
+    mm, stats, time, trace,
+    user::limit::Limits,
+    utils::lock,
+    watchdog,
+};
+use ::syscall::{capability::Capabilities, trace::EventKind};
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec::Vec};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU8, AtomicU64, Ordering},
 };
-use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
-use core::sync::atomic::{AtomicU64, Ordering};
 use crossbeam::queue::ArrayQueue;
 
-/// The global executor instance, used to run all user-space tasks. This
-/// executor replace the traditional term "scheduler" in the context of
-/// user-space tasks.
+/// The global executor instance, used to run all user-space tasks and the
+/// background tasks of the kernel. This executor replace the traditional term
+/// "scheduler" in the context of user-space tasks.
 ///
 /// The main advantage of having an executor instead of a scheduler is that
 /// it allow to use cooperative multitasking inside the kernel with a single
@@ -24,13 +36,170 @@ static EXECUTOR: spin::Once<Executor> = spin::Once::new();
 /// wants to know if it has yielded since the last time it checked.
 static POLL_GENERATION: ExecutorGeneration = ExecutorGeneration::new();
 
-/// The identifier of the currently running task on this core. This is
-/// used to identify the task that is currently running on this core.
-/// This is useful for syscalls that need to know the current task
-/// identifier. If no task is running, this will be `None`.
-static CURRENT_TASK_ID: spin::Mutex<Option<task::Identifier>> = spin::Mutex::new(None);
+/// The state of the executor, as a [`State`]. It only moves forward, from
+/// [`State::Running`] to [`State::Halted`].
+static STATE: AtomicU8 = AtomicU8::new(State::Running as u8);
+
+/// The queue where [`shutdown`] sleeps while the background tasks of the
+/// kernel complete. It is woken up each time one of them completes.
+static KERNEL_EXITS: spin::Lazy<future::wait::Queue> = spin::Lazy::new(future::wait::Queue::new);
+
+/// The maximum number of tasks taken from the global injector queue by a core
+/// each time it looks for work, so that the tasks that can run on any core are
+/// spread between all the cores rather than being all taken by the first one.
+const INJECTOR_BATCH: usize = 8;
+
+/// The queues where woken tasks are pushed, before being inserted in the run
+/// queue of a core by the executor. Pushing a task identifier in these queues
+/// never takes a lock, so that tasks can be woken up from any context.
+#[derive(Debug)]
+pub struct Wakeups {
+    /// The tasks that can run on any core. Each core takes a batch of tasks
+    /// from this queue when it looks for work.
+    injector: ArrayQueue<task::Identifier>,
+
+    /// The tasks that prefer to run on a specific core, indexed by core.
+    local: Vec<ArrayQueue<task::Identifier>>,
+}
+
+impl Wakeups {
+    /// Create the queues of woken tasks, each of them being able to hold
+    /// `config::MAX_TASKS` tasks. The capacity can be raised at boot with the
+    /// `executor.queue` option of the kernel command line, but never below
+    /// `config::MAX_TASKS` since every task may be woken at the same time.
+    #[must_use]
+    fn new() -> Self {
+        let capacity = cmdline::parse::<usize>("executor.queue")
+            .unwrap_or_default()
+            .max(usize::from(config::MAX_TASKS));
+        Self {
+            injector: ArrayQueue::new(capacity),
+            local: (0..config::MAX_CPUS)
+                .map(|_| ArrayQueue::new(capacity))
+                .collect(),
+        }
+    }
+
+    /// Push a woken task in the queue matching its affinity. A task that
+    /// prefers a core that does not exist is treated as if it could run on
+    /// any core.
+    ///
+    /// # Panics
+    /// Panics if the queue is full.
+    pub fn push(&self, id: task::Identifier, affinity: Affinity) {
+        let queue = match affinity {
+            Affinity::Prefer(cpu) if cpu < config::MAX_CPUS => &self.local[cpu],
+            _ => &self.injector,
+        };
+        queue.push(id).expect("Queue is full");
+    }
+
+    /// Return true if there are woken tasks that the given core can take.
+    #[must_use]
+    fn pending(&self, cpu: usize) -> bool {
+        !self.injector.is_empty() || !self.local[cpu].is_empty()
+    }
+}
+
+/// The number of scheduling classes, one for each variant of [`Priority`].
+const PRIORITIES: usize = Priority::ALL.len();
+
+/// The tasks that are ready to be executed on a core, with a queue for each
+/// scheduling class. The tasks of a class only run when the queues of the
+/// higher classes are empty.
+///
+/// In each queue, tasks are sorted by their virtual runtime: The task with the
+/// lowest virtual runtime is at the front of the queue and will be executed
+/// next. Other cores may steal tasks from the back of the queues when they
+/// have nothing to run.
+struct RunQueue {
+    queues: [BTreeMap<u64, task::Identifier>; PRIORITIES],
+}
+
+impl RunQueue {
+    /// Create an empty run queue.
+    const fn new() -> Self {
+        Self {
+            queues: [const { BTreeMap::new() }; PRIORITIES],
+        }
+    }
+
+    /// Return the lowest virtual runtime of the ready tasks of the given
+    /// class, or 0 if there are none.
+    fn lowest_vruntime(&self, priority: Priority) -> u64 {
+        self.queues[priority as usize]
+            .keys()
+            .next()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Insert a task of the given class with the given virtual runtime, and
+    /// return the virtual runtime actually used as its key.
+    fn insert(&mut self, priority: Priority, id: task::Identifier, vruntime: u64) -> u64 {
+        // Increment the vruntime slightly if there is already a task with the
+        // same vruntime in the queue to avoid duplicated keys in the BTreeMap
+        // that would overwrite the previous task stored with the same
+        // vruntime. This should not happen often, and even if it does, the
+        // increment is very small (1 nanosecond) and should not impact the
+        // scheduling fairness.
+        let queue = &mut self.queues[priority as usize];
+        let mut vruntime = vruntime;
+        while queue.contains_key(&vruntime) {
+            vruntime += 1;
+        }
+        queue.insert(vruntime, id);
+        vruntime
+    }
+
+    /// Remove and return the next task to run: the task with the lowest
+    /// virtual runtime of the highest class that has ready tasks.
+    fn pop(&mut self) -> Option<task::Identifier> {
+        self.queues
+            .iter_mut()
+            .find_map(|queue| queue.pop_first().map(|(_, id)| id))
+    }
+
+    /// Return true if there are no ready tasks.
+    fn is_empty(&self) -> bool {
+        self.queues.iter().all(BTreeMap::is_empty)
+    }
+
+    /// Return the number of ready tasks.
+    fn len(&self) -> usize {
+        self.queues.iter().map(BTreeMap::len).sum()
+    }
+}
+
+/// The state of the executor owned by each core.
+struct Core {
+    /// The tasks that are ready to be executed on this core.
+    run_queue: lock::Mutex<RunQueue>,
+
+    /// The waker of the task currently running on this core, if any. It is
+    /// used to identify the running task, and to change its affinity while
+    /// it is not in the tasks table.
+    current: lock::Mutex<Option<Arc<Waker>>>,
+
+    /// The scheduling class requested for the task currently running on this
+    /// core while it was not in the tasks table. It is applied once the task is
+    /// put back in the map.
+    pending_priority: lock::Mutex<Option<Priority>>,
+}
+
+impl Core {
+    /// Create the state of a core without any task.
+    const fn new() -> Self {
+        Self {
+            run_queue: lock::Mutex::new(RunQueue::new()),
+            current: lock::Mutex::new(None),
+            pending_priority: lock::Mutex::new(None),
+        }
+    }
+}
 
-/// The executor is responsible to run all user-space tasks.
+/// The executor is responsible to run all user-space tasks, along with the
+/// background tasks of the kernel (see [`spawn_kernel`]).
 ///
 /// # A cooperative scheduler for user-space tasks ?
 /// You may wonder why we use a cooperative scheduler instead of a preemptive
@@ -44,132 +213,205 @@ static CURRENT_TASK_ID: spin::Mutex<Option<task::Identifier>> = spin::Mutex::new
 /// syscall...). Since userspace run with interrupts enabled (contrary to the
 /// kernel), the kernel can preempt the user-space tasks at any time using a
 /// cooperative approach in the kernel ;)
+///
+/// # Load balancing
+/// Each core has its own run queue, so that cores do not contend on a single
+/// queue. Woken tasks are pushed in the queue of the core they prefer (see
+/// [`Affinity`]), or in a global injector queue shared by all cores. A core
+/// that has nothing to run steals half of the tasks of another core, starting
+/// with the tasks that would run last there.
 pub struct Executor<'a> {
-    /// All tasks that was not running are stored in this map. The key is
-    /// the task identifier and the value is the task itself. Tasks that
-    /// are currently running are not stored in this map to avoid locking
-    /// the map for every task poll, that would lead to an single-threaded
-    /// executor...
-    tasks: spin::Mutex<BTreeMap<task::Identifier, Task<'a>>>,
-
-    /// The queue of tasks that are ready to be executed. Tasks are sorted
-    /// by their virtual runtime: The task with the lowest virtual runtime is
-    /// at the front of the queue and will be executed next.
-    ready_queue: spin::Mutex<BTreeMap<u64, task::Identifier>>,
-
-    /// The queue of tasks identifier that are ready to be executed, but was
-    /// not yet inserted in the `ready_queue` map. This is used to avoid
-    /// locking the `ready_queue` map for every task wake-up.
-    ready_ids: Arc<ArrayQueue<task::Identifier>>,
+    /// All tasks that was not running are stored in this table, in the slot
+    /// of their identifier. Tasks that are currently running are not stored
+    /// in this table to avoid locking their slot for every task poll, that
+    /// would prevent the task from being woken up while it runs...
+    tasks: SlotTable<Task<'a>>,
+
+    /// The state of each core, indexed by core.
+    cores: Vec<Core>,
+
+    /// The queues of tasks identifier that are ready to be executed, but was
+    /// not yet inserted in the run queue of a core. This is used to avoid
+    /// locking a run queue for every task wake-up.
+    wakeups: Arc<Wakeups>,
 }
 
 impl Executor<'_> {
     /// Create a new executor instance that can handle a maximum of
-    /// `config::MAX_TASKS` tasks.
+    /// `config::MAX_TASKS` tasks on `config::MAX_CPUS` cores.
     #[must_use]
     pub fn new() -> Self {
         Self {
-            tasks: spin::Mutex::new(BTreeMap::new()),
-            ready_queue: spin::Mutex::new(BTreeMap::new()),
-            ready_ids: Arc::new(ArrayQueue::new(usize::from(config::MAX_TASKS))),
+            tasks: SlotTable::new(),
+            cores: (0..config::MAX_CPUS).map(|_| Core::new()).collect(),
+            wakeups: Arc::new(Wakeups::new()),
         }
     }
 
-    /// Run the next task that is ready to run. If there are no tasks ready
+    /// Run the next task that is ready to run on the current core, stealing
+    /// tasks from the other cores if it has none. If there are no tasks ready
     /// to run, this function does nothing.
     ///
     /// # Panics
     /// Panics if this function encounters a duplicated task identifier. This
-    /// should never happen because the task identifier is unique and encoded
-    /// into a u64 that can handle up to 2^64 - 1 tasks and cannot be
-    /// overflowed in a reasonable time.
+    /// should never happen because two tasks alive at the same time never
+    /// have the same identifier, nor share a slot (see
+    /// [`task::Identifier::slot`]).
     pub fn run_once(&self) {
-        self.process_ready_ids();
+        let cpu = arch::cpu::id();
+        let state = &self.cores[cpu];
+        self.process_wakeups(cpu);
 
         // Get the next task to run.
-        if let Some((_, id)) = self.ready_queue.lock().pop_first() {
-            // If the task is not found in the map, this means that the
-            // task has completed and was removed from the map. Therefore,
+        let next = state.run_queue.lock().pop();
+        if let Some(id) = next.or_else(|| self.steal(cpu)) {
+            // If the task is not found in the table, this means that the
+            // task has completed and was removed from the table. Therefore,
             // we can safely ignore it.
-            let Some(mut task) = self.tasks.lock().remove(&id) else {
+            let Some(mut task) = self.tasks.remove(id) else {
                 log::trace!("Task {:?} already completed", usize::from(id));
                 return;
             };
 
-            // Set the current task ID to the task that is being run now.
-            set_current_task_id(id);
-            match task.poll() {
+            // Set the current task to the task that is being run now.
+            *state.current.lock() = Some(Arc::clone(task.waker()));
+            trace::record(EventKind::ContextSwitch, [0, 0]);
+            stats::count_context_switch();
+            let poll = task.poll();
+            lock::assert_unlocked("across an await point");
+            match poll {
                 core::task::Poll::Ready(()) => {
-                    // The task has completed. Therefore, we have nothing to
-                    // do because the task was already removed from the map.
+                    // The task has completed and was already removed from the
+                    // table. It is dropped at once so that it is no longer
+                    // counted when waking up those waiting for a background
+                    // task of the kernel to complete.
                     log::trace!("Task {:?} completed", usize::from(id));
+                    let kind = task.kind();
+                    drop(task);
+                    if kind == Kind::Kernel {
+                        KERNEL_EXITS.wake_all();
+                    }
                 }
                 core::task::Poll::Pending => {
                     // The task is not yet completed. Therefore, we must
-                    // put it back in the map for the next run. The task
-                    // identifier will be added to the ready queue by the
+                    // put it back in the table for the next run. The task
+                    // identifier will be added to a run queue by the
                     // task's waker when the task will be ready to run again.
-                    assert!(self.tasks.lock().insert(id, task).is_none());
+                    if let Some(priority) = state.pending_priority.lock().take() {
+                        task.set_priority(priority);
+                    }
+                    assert!(self.tasks.insert(id, task).is_ok());
                 }
             }
 
-            // Clear the current task ID because no task is running now and
+            // Clear the current task because no task is running now and
             // increment the poll generation to indicate that we have polled
             // one more task.
             POLL_GENERATION.increment();
-            clear_current_task_id();
+            *state.current.lock() = None;
+            *state.pending_priority.lock() = None;
+
+            // If the poll locked up the core for too long, the watchdog asked
+            // for the task to be terminated now that it has returned.
+            if watchdog::take_runaway(id) {
+                log::error!("Task {} locked up core {}, terminating it", id, cpu);
+                group::terminate(id, Exit::Terminate(-1));
+            }
         }
     }
 
-    /// Process all the ready task identifiers and insert them into the
-    /// ready queue, sorted by their virtual runtime.
-    fn process_ready_ids(&self) {
-        let mut ready_queue = self.ready_queue.lock();
-        let lowest_vruntime = ready_queue.keys().next().copied().unwrap_or(0);
-        let mut tasks = self.tasks.lock();
-
-        while let Some(id) = self.ready_ids.pop() {
-            if let Some(task) = tasks.get_mut(&id) {
-                // Insert the task into the ready queue, using its virtual
+    /// Process the woken tasks that the given core can take and insert them
+    /// into its run queue, sorted by their class and their virtual runtime.
+    /// All the tasks that prefer this core are taken, but only a batch of the
+    /// tasks that can run on any core.
+    fn process_wakeups(&self, cpu: usize) {
+        let mut run_queue = self.cores[cpu].run_queue.lock();
+
+        let local = core::iter::from_fn(|| self.wakeups.local[cpu].pop());
+        let injected = core::iter::from_fn(|| self.wakeups.injector.pop()).take(INJECTOR_BATCH);
+        for id in local.chain(injected) {
+            let queued = self.tasks.with_mut(id, |task| {
+                let Some(task) = task else {
+                    return false;
+                };
+                // Insert the task into the run queue, using its virtual
                 // runtime as the key. We ensure that the virtual runtime
                 // is at least the lowest virtual runtime of all ready
-                // tasks to avoid the case where a task has slept for a
+                // tasks of its class to avoid the case where a task has slept for a
                 // long time and has a very low virtual runtime that would
                 // starve all other tasks.
                 // TODO: Since the task has slept for a long time, maybe we
                 // should give it a small boost ? This may help interactive
                 // tasks to be more responsive.
-                let mut vruntime = task.vruntime().max(lowest_vruntime);
-
-                // Increment the vruntime slightly if there is already a task
-                // with the same vruntime in the ready queue to avoid
-                // duplicated keys in the BTreeMap that would overwrite the
-                // previous task stored with the same vruntime. This should not
-                // happen often, and even if it does, the increment is very
-                // small (1 nanosecond) and should not impact the scheduling
-                // fairness.
-                while ready_queue.contains_key(&vruntime) {
-                    vruntime += 1;
-                }
-
-                ready_queue.insert(vruntime, id);
+                let priority = task.priority();
+                let lowest_vruntime = run_queue.lowest_vruntime(priority);
+                let vruntime = run_queue.insert(priority, id, task.vruntime().max(lowest_vruntime));
                 task.set_vruntime(vruntime);
-            } else {
-                log::warn!("Task #{:?} not found in tasks map", usize::from(id));
+                true
+            });
+            if !queued {
+                log::warn!("Task #{:?} not found in tasks table", usize::from(id));
+            }
+        }
+    }
+
+    /// Steal half of the ready tasks of each class of the first other core
+    /// that has tasks that the given core can take, and return the one to run
+    /// first. The stolen tasks are those with the highest virtual runtime,
+    /// that would have run last on their core. Tasks that prefer the core they
+    /// are queued on are never stolen.
+    fn steal(&self, cpu: usize) -> Option<task::Identifier> {
+        let mut stolen = Vec::new();
+        for victim in (1..config::MAX_CPUS).map(|i| (cpu + i) % config::MAX_CPUS) {
+            let mut run_queue = self.cores[victim].run_queue.lock();
+            for (priority, queue) in Priority::ALL.into_iter().zip(&mut run_queue.queues) {
+                let stealable = queue
+                    .iter()
+                    .rev()
+                    .filter(|&(_, &id)| {
+                        self.tasks.with(id, |task| {
+                            task.is_some_and(|task| task.affinity() != Affinity::Prefer(victim))
+                        })
+                    })
+                    .map(|(&vruntime, &id)| (vruntime, id))
+                    .collect::<Vec<_>>();
+
+                let count = stealable.len().div_ceil(2);
+                for &(vruntime, id) in &stealable[..count] {
+                    queue.remove(&vruntime);
+                    stolen.push((priority, vruntime, id));
+                }
+            }
+            if !stolen.is_empty() {
+                break;
             }
         }
+
+        // The run queue of the core is only locked once the locks of the
+        // victim are released, since the victim may be stealing from this
+        // core at the same time.
+        let mut run_queue = self.cores[cpu].run_queue.lock();
+        for (priority, vruntime, id) in stolen {
+            run_queue.insert(priority, id, vruntime);
+        }
+        run_queue.pop()
     }
 
-    /// Return true if there are tasks ready to run.
+    /// Return true if there are tasks ready to run on the current core,
+    /// including tasks that it could steal from the other cores.
     #[must_use]
     pub fn tasks_ready_to_run(&self) -> bool {
-        !self.ready_queue.lock().is_empty() || !self.ready_ids.is_empty()
+        self.wakeups.pending(arch::cpu::id())
+            || self
+                .cores
+                .iter()
+                .any(|core| !core.run_queue.lock().is_empty())
     }
 
-    /// Return a reference to the ready queue.
+    /// Return a reference to the queues of woken tasks.
     #[must_use]
-    pub const fn ready_ids(&self) -> &Arc<ArrayQueue<task::Identifier>> {
-        &self.ready_ids
+    pub const fn wakeups(&self) -> &Arc<Wakeups> {
+        &self.wakeups
     }
 }
 
@@ -218,52 +460,449 @@ impl PartialEq for ExecutorGeneration {
 
 impl Eq for ExecutorGeneration {}
 
+/// The state of the executor while the system goes down (see [`shutdown`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// The executor runs tasks and accepts new ones.
+    Running = 0,
+
+    /// The system is going down: no new task is accepted, and the background
+    /// tasks of the kernel are given a last chance to complete.
+    Draining = 1,
+
+    /// The background tasks of the kernel are gone and the buffers of the
+    /// kernel are flushed: the system can be powered off.
+    Halted = 2,
+}
+
+impl State {
+    /// Returns the current state of the executor.
+    #[must_use]
+    pub fn current() -> Self {
+        match STATE.load(Ordering::Acquire) {
+            0 => Self::Running,
+            1 => Self::Draining,
+            _ => Self::Halted,
+        }
+    }
+}
+
+/// Return whether the system is going down. Background tasks of the kernel
+/// should check this regularly and complete as soon as possible when it
+/// returns `true`, since they are cancelled once the shutdown deadline
+/// passes.
+#[must_use]
+pub fn shutting_down() -> bool {
+    State::current() != State::Running
+}
+
+/// Prepare the executor for the system going down. New tasks are refused at
+/// once, then the background tasks of the kernel are waited for until they
+/// complete or until the given deadline passes, after which the remaining
+/// ones are cancelled. Finally, the kernel log and the trace buffers are
+/// flushed. The caller is expected to power off or reboot the system right
+/// after, since user tasks are not stopped.
+///
+/// If the system is already going down, this returns at once without
+/// waiting, leaving the first caller in charge of draining the executor.
+///
+/// # Panics
+/// Panics if the executor is not initialized (i.e. `setup` was not called).
+pub async fn shutdown(deadline: time::Instant) {
+    let executor = EXECUTOR.get().expect("Executor not initialized");
+    if STATE
+        .compare_exchange(
+            State::Running as u8,
+            State::Draining as u8,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        return;
+    }
+
+    // The background tasks of the kernel are woken up so that they notice
+    // that the system is going down, and this task is woken up at the deadline
+    // in case some of them never complete. A task that completes between the
+    // check of the count and the wait only delays the drain until then.
+    log::info!("Draining {} kernel task(s)", Kind::Kernel.count());
+    for id in kernel_tasks(executor) {
+        wake(id);
+    }
+    if let Some(current) = current_task_id() {
+        time::wheel::wake_at(deadline, current);
+    }
+    while Kind::Kernel.count() > 0 && !deadline.has_passed() {
+        future::wait::wait(&KERNEL_EXITS).await;
+    }
+
+    // The tasks that did not complete in time are cancelled. A task running
+    // on another core cannot be cancelled, and is simply abandoned.
+    let remaining = kernel_tasks(executor)
+        .into_iter()
+        .filter(|&id| cancel(id))
+        .count();
+    if remaining > 0 {
+        log::warn!(
+            "Cancelled {} kernel task(s) that did not complete in time",
+            remaining
+        );
+    }
+
+    trace::flush();
+    arch::log::flush();
+    STATE.store(State::Halted as u8, Ordering::Release);
+}
+
+/// Return the identifiers of the background tasks of the kernel that are not
+/// currently running.
+fn kernel_tasks(executor: &Executor) -> Vec<task::Identifier> {
+    executor
+        .tasks
+        .ids()
+        .into_iter()
+        .filter(|&id| executor.tasks.with(id, |task| task.map(Task::kind)) == Some(Kind::Kernel))
+        .collect()
+}
+
 /// Setup the global executor instance.
 pub fn setup() {
     log::info!("Setting up the kernel executor");
+    if cmdline::flag("random-ids").unwrap_or(config::RANDOM_TASK_IDS) {
+        let ticks = arch::timer::current_time_ticks() % 4096;
+        task::Identifier::seed(u32::try_from(ticks).unwrap_or(0));
+    }
     EXECUTOR.call_once(Executor::new);
 }
 
 /// Return the identifier of the currently running task on this core. If no
 /// task is running, this will return `None`.
 pub fn current_task_id() -> Option<task::Identifier> {
-    *CURRENT_TASK_ID.lock()
+    let executor = EXECUTOR.get()?;
+    executor.cores[arch::cpu::id()]
+        .current
+        .lock()
+        .as_ref()
+        .map(|waker| waker.id)
+}
+
+/// Return the identifier of the task currently running on this core, like
+/// [`current_task_id`], but without blocking. Returns `None` if no task is
+/// running or if the state of the core is locked. This is intended to be used
+/// by the panic handler.
+#[must_use]
+pub fn peek_current_task_id() -> Option<task::Identifier> {
+    let executor = EXECUTOR.get()?;
+    executor.cores[arch::cpu::id()]
+        .current
+        .try_lock()?
+        .as_ref()
+        .map(|waker| waker.id)
+}
+
+/// Return the number of tasks known by the executor, including the tasks that
+/// are currently running.
+///
+/// # Panics
+/// Panics if the executor is not initialized (i.e. `setup` was not called).
+#[must_use]
+pub fn task_count() -> usize {
+    let executor = EXECUTOR.get().expect("Executor not initialized");
+    let running = executor
+        .cores
+        .iter()
+        .filter(|core| core.current.lock().is_some())
+        .count();
+    executor.tasks.len() + running
+}
+
+/// Return the number of tasks ready to run on the given core, including the
+/// woken tasks that prefer this core and were not yet inserted into its run
+/// queue. Returns 0 if the core does not exist.
+///
+/// # Panics
+/// Panics if the executor is not initialized (i.e. `setup` was not called).
+#[must_use]
+pub fn ready_tasks(cpu: usize) -> usize {
+    let executor = EXECUTOR.get().expect("Executor not initialized");
+    let Some(core) = executor.cores.get(cpu) else {
+        return 0;
+    };
+    core.run_queue.lock().len() + executor.wakeups.local[cpu].len()
+}
+
+/// Return the number of woken tasks that can run on any core and were not yet
+/// taken by a core.
+///
+/// # Panics
+/// Panics if the executor is not initialized (i.e. `setup` was not called).
+#[must_use]
+pub fn pending_wakeups() -> usize {
+    let executor = EXECUTOR.get().expect("Executor not initialized");
+    executor.wakeups.injector.len()
+}
+
+/// Set the affinity of the task with the given identifier, which may be the
+/// task currently running on this core. The affinity is taken into account
+/// the next time the task is woken up. Returns `false` if the task does not
+/// exist, is running on another core, or if the affinity names a core that
+/// does not exist.
+///
+/// # Panics
+/// Panics if the executor is not initialized (i.e. `setup` was not called).
+pub fn set_affinity(id: task::Identifier, affinity: Affinity) -> bool {
+    let executor = EXECUTOR.get().expect("Executor not initialized");
+    if matches!(affinity, Affinity::Prefer(cpu) if cpu >= config::MAX_CPUS) {
+        return false;
+    }
+
+    let waker = executor
+        .tasks
+        .with(id, |task| task.map(|task| Arc::clone(task.waker())));
+    if let Some(waker) = waker {
+        waker.set_affinity(affinity);
+        return true;
+    }
+    match &*executor.cores[arch::cpu::id()].current.lock() {
+        Some(waker) if waker.id == id => {
+            waker.set_affinity(affinity);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Set the scheduling class of the task with the given identifier, which may
+/// be the task currently running on this core. The class is taken into account
+/// the next time the task is woken up. Returns `false` if the task does not
+/// exist or is running on another core.
+///
+/// # Panics
+/// Panics if the executor is not initialized (i.e. `setup` was not called).
+pub fn set_priority(id: task::Identifier, priority: Priority) -> bool {
+    let executor = EXECUTOR.get().expect("Executor not initialized");
+    let found = executor
+        .tasks
+        .with_mut(id, |task| task.map(|task| task.set_priority(priority)));
+    if found.is_some() {
+        return true;
+    }
+
+    let core = &executor.cores[arch::cpu::id()];
+    if core
+        .current
+        .lock()
+        .as_ref()
+        .is_some_and(|waker| waker.id == id)
+    {
+        *core.pending_priority.lock() = Some(priority);
+        return true;
+    }
+    false
 }
 
-/// Spawn a new future into the executor.
+/// Spawn a new future into the executor, and return the identifier of the
+/// new task. Returns `None` if [`config::MAX_TASKS`] tasks already exist, or
+/// if the system is going down.
 ///
 /// # Panics
 /// Panics if the executor is not initialized (i.e. `setup` was not called).
-pub fn spawn(thread: arch::thread::Thread) {
+#[must_use]
+pub fn spawn(thread: arch::thread::Thread) -> Option<task::Identifier> {
+    let id = generate()?;
+    spawn_with_id(thread, id);
+    Some(id)
+}
+
+/// Spawn a new future into the executor with the given identifier. This is
+/// intended to be used for well-known system tasks that must always have the
+/// same identifier, such as [`task::Identifier::INIT`].
+///
+/// # Panics
+/// Panics if the executor is not initialized, if a task with the same
+/// identifier already exists, or if the ready queue is full.
+pub fn spawn_with_id(thread: arch::thread::Thread, id: task::Identifier) {
+    spawn_group(thread, id, Capabilities::ALL, Limits::default());
+}
+
+/// Spawn a new future into the executor holding only the given capabilities
+/// and subject to the given resource limits, and return the identifier of the
+/// new task. The capabilities and the limits are set before the task can run,
+/// so it never holds more rights than those given here. Returns `None` if
+/// [`config::MAX_TASKS`] tasks already exist, or if the system is going down.
+///
+/// # Panics
+/// Panics if the executor is not initialized or if the ready queue is full.
+#[must_use]
+pub fn spawn_with_capabilities(
+    thread: arch::thread::Thread,
+    capabilities: Capabilities,
+    limits: Limits,
+) -> Option<task::Identifier> {
+    let id = generate()?;
+    spawn_group(thread, id, capabilities, limits);
+    Some(id)
+}
+
+/// Spawn a new thread in the same task as the given thread, sharing its
+/// handles and its capabilities, and return the identifier of the executor
+/// task running the new thread. The given thread must have been created with
+/// [`arch::thread::create_sibling`] from a thread of the same task.
+///
+/// Returns `None` if the given thread does not exist, if its task cannot have
+/// more threads, if [`config::MAX_TASKS`] tasks already exist, or if the
+/// system is going down.
+///
+/// # Panics
+/// Panics if the executor is not initialized or if the ready queue is full.
+#[must_use]
+pub fn spawn_sibling(
+    thread: arch::thread::Thread,
+    sibling: task::Identifier,
+) -> Option<task::Identifier> {
+    let local = task::try_with_local_set_from(sibling, |set| set.map(LocalDataSet::sibling))?;
+    let group = group::of(sibling)?;
+    let id = generate()?;
+    if !group::add(group, id) {
+        id.release();
+        return None;
+    }
+    spawn_task(Box::pin(thread_loop(thread)), id, local, Kind::User);
+    Some(id)
+}
+
+/// Spawn a background task of the kernel running the given future, and
+/// return its identifier. Contrary to user tasks, the task belongs to no
+/// thread group and holds no capability, so it can neither be killed nor
+/// invoke syscalls: it runs until its future completes, or until it is
+/// cancelled when the system goes down (see [`shutting_down`]). Returns `None`
+/// if [`config::MAX_TASKS`] tasks already exist, or if the system is going
+/// down.
+///
+/// # Panics
+/// Panics if the executor is not initialized or if the ready queue is full.
+#[must_use]
+pub fn spawn_kernel<F>(future: F) -> Option<task::Identifier>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let id = generate()?;
+    let local = LocalDataSet::default();
+    *local.capabilities.lock() = Capabilities::NONE;
+    local.essential.store(true, Ordering::Relaxed);
+    spawn_task(Box::pin(future), id, local, Kind::Kernel);
+    Some(id)
+}
+
+/// Wake up the task with the given identifier, which may be running on any
+/// core, so that it is polled again. Returns `false` if the task does not
+/// exist.
+///
+/// # Panics
+/// Panics if the executor is not initialized (i.e. `setup` was not called).
+pub fn wake(id: task::Identifier) -> bool {
+    let executor = EXECUTOR.get().expect("Executor not initialized");
+    let waker = executor
+        .tasks
+        .with(id, |task| task.map(|task| Arc::clone(task.waker())))
+        .or_else(|| {
+            executor.cores.iter().find_map(|core| {
+                core.current
+                    .lock()
+                    .as_ref()
+                    .filter(|waker| waker.id == id)
+                    .map(Arc::clone)
+            })
+        });
+
+    waker.map(alloc::task::Wake::wake).is_some()
+}
+
+/// Cancel the task with the given identifier: its future is dropped without
+/// being polled again. Returns `false` if the task does not exist or is
+/// currently running, in which case it cannot be cancelled.
+///
+/// # Panics
+/// Panics if the executor is not initialized (i.e. `setup` was not called).
+pub fn cancel(id: task::Identifier) -> bool {
+    let executor = EXECUTOR.get().expect("Executor not initialized");
+    let Some(task) = executor.tasks.remove(id) else {
+        return false;
+    };
+
+    // The task is dropped once its slot is unlocked, since dropping its future
+    // may wake up other tasks.
+    drop(task);
+    log::trace!("Task {:?} cancelled", usize::from(id));
+    true
+}
+
+/// Generate the identifier of a new task, unless the system is going down.
+fn generate() -> Option<task::Identifier> {
+    if shutting_down() {
+        return None;
+    }
+    task::Identifier::generate()
+}
+
+/// Spawn the first thread of a new user task with the given identifier,
+/// capabilities and resource limits, creating its thread group.
+fn spawn_group(
+    thread: arch::thread::Thread,
+    id: task::Identifier,
+    capabilities: Capabilities,
+    limits: Limits,
+) {
+    assert!(!task::exists(id), "Task identifier {id} already in use");
+    group::create(id);
+    let local = LocalDataSet::default();
+    *local.capabilities.lock() = capabilities;
+    *local.limits.lock() = limits;
+    spawn_task(Box::pin(thread_loop(thread)), id, local, Kind::User);
+}
+
+/// Spawn the given future into the executor as a task of the given kind, with
+/// the given identifier and local data set.
+fn spawn_task(
+    future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    id: task::Identifier,
+    local: LocalDataSet,
+    kind: Kind,
+) {
     let executor = EXECUTOR.get().expect("Executor not initialized");
 
     // Compute the virtual runtime of the new task. We take the lowest
-    // virtual runtime of all ready tasks to ensure that the new task does
-    // not starve other tasks since they will all have a higher virtual
-    // runtime. If there are no ready tasks, we set the virtual runtime to 0.
-    let vruntime = executor
-        .ready_queue
+    // virtual runtime of all tasks ready on this core to ensure that the new
+    // task does not starve other tasks since they will all have a higher
+    // virtual runtime. If there are no ready tasks, we set the virtual
+    // runtime to 0.
+    let vruntime = executor.cores[arch::cpu::id()]
+        .run_queue
         .lock()
-        .keys()
-        .next()
-        .copied()
-        .unwrap_or(0);
+        .lowest_vruntime(Priority::Normal);
 
-    let task = Task::new(executor, Box::pin(thread_loop(thread)), vruntime);
-    let id = task.id();
+    let task = Task::new(executor, future, vruntime, id, local, kind);
 
-    // Insert the task into the tasks map. If the task identifier already
-    // exists in the map, this means that the task identifier is duplicated.
+    // Insert the task into the tasks table. If the slot of the task is
+    // already occupied, this means that the task identifier is duplicated.
     // This should never happen because the task identifier is unique, and
     // is a serious bug that must be fixed.
-    assert!(executor.tasks.lock().insert(id, task).is_none());
-    executor.ready_ids.push(id).expect("Ready queue full");
+    assert!(executor.tasks.insert(id, task).is_ok());
+    executor.wakeups.push(id, Affinity::Any);
     log::trace!("Task {:?} spawned", usize::from(id));
 }
 
 /// Run the executor forever. If there are no tasks ready to run, the
-/// executor will put the current core to a low-power state until a task
-/// is ready to run.
+/// executor will put the current core to a low-power state until an
+/// interrupt or the next timer deadline makes a task ready to run.
+///
+/// The work deferred by the trap handlers, which includes polling the timer
+/// wheel after a timer interrupt, is run after each task and each time the
+/// core wakes up. The timer wheel is also polled each time the core wakes up,
+/// since the interrupt waking up an idle core is not handled by a trap
+/// handler. The memory pressure is polled after each task, so that the reclaim
+/// daemon is woken up as soon as memory runs low.
 ///
 /// # Panics
 /// Panics if the executor is not initialized (i.e. `setup` was not called).
@@ -272,8 +911,20 @@ pub fn run() -> ! {
 
     loop {
         executor.run_once();
+        deferred::run();
+        mm::pressure::poll();
+        watchdog::pet();
         while !executor.tasks_ready_to_run() {
-            arch::cpu::relax();
+            // Program the timer for the next deadline of the timer wheel or
+            // of the watchdog before sleeping, so that the end of the quantum
+            // of the last thread does not wake up the core for nothing, and
+            // the timer stays off when nothing is scheduled.
+            watchdog::arm();
+            arch::cpu::idle();
+            arch::irq::handle_pending();
+            deferred::run();
+            time::wheel::poll();
+            watchdog::pet();
         }
     }
 }
@@ -290,13 +941,3 @@ pub fn poll_generation() -> ExecutorGeneration {
 pub fn has_yielded(since: &ExecutorGeneration) -> bool {
     POLL_GENERATION.get() != since.get()
 }
-
-/// Set the identifier of the currently running task on this core.
-fn set_current_task_id(id: task::Identifier) {
-    *CURRENT_TASK_ID.lock() = Some(id);
-}
-
-/// Clear the identifier of the currently running task on this core.
-fn clear_current_task_id() {
-    *CURRENT_TASK_ID.lock() = None;
-}
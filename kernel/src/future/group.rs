@@ -0,0 +1,331 @@
+use crate::{
+    config,
+    future::{self, task::Identifier, user::Exit},
+    ipc,
+    utils::lock,
+};
+use alloc::vec::Vec;
+use core::{
+    pin::{Pin, pin},
+    task::{Context, Poll},
+};
+use futures::future::Either;
+use hashbrown::HashMap;
+use spin::Lazy;
+
+/// The thread groups of all user tasks, along with the group of each thread.
+static REGISTRY: Lazy<lock::Mutex<Registry>> = Lazy::new(|| lock::Mutex::new(Registry::default()));
+
+#[derive(Debug, Default)]
+struct Registry {
+    /// The groups, indexed by their identifier.
+    groups: HashMap<Identifier, Group>,
+
+    /// The identifier of the group of each thread that is still alive.
+    members: HashMap<Identifier, Identifier>,
+}
+
+/// A thread group: the threads of a user task, which share the same address
+/// space and the same resources. Each thread is run by its own executor task,
+/// and the group is identified by the identifier of its first thread, which
+/// is the identifier of the user task as seen by other tasks. The group lives
+/// as long as one of its threads is alive, even if the first thread exited.
+#[derive(Debug)]
+struct Group {
+    /// The threads of the group that are still alive.
+    threads: Vec<Identifier>,
+
+    /// The thread occupying each slot of the group. The slot of a thread
+    /// selects its shared page (see [`crate::user::vdso::SharedPage`]), and
+    /// is reused by another thread once the thread terminates.
+    slots: [Option<Identifier>; config::MAX_THREADS_PER_TASK],
+
+    /// The exit codes of the threads that terminated and were not joined yet.
+    exits: HashMap<Identifier, i32>,
+
+    /// A queue where threads waiting for the termination of another thread of
+    /// the group sleep. All waiters are woken up each time a thread of the
+    /// group terminates.
+    queue: future::wait::Queue,
+
+    /// How the whole group terminates, set as soon as one of its threads
+    /// exits the task or faults. The other threads are then terminated.
+    exit: Option<Exit>,
+}
+
+impl Group {
+    /// Removes the given thread from the group, freeing its slot.
+    fn remove(&mut self, thread: Identifier) {
+        self.threads.retain(|&other| other != thread);
+        self.slots
+            .iter_mut()
+            .filter(|slot| **slot == Some(thread))
+            .for_each(|slot| *slot = None);
+    }
+}
+
+/// Creates a new group whose only thread is the given one. This must be
+/// called when the first thread of a user task is spawned.
+///
+/// # Panics
+/// Panics if the thread already belongs to a group.
+pub fn create(thread: Identifier) {
+    let mut registry = REGISTRY.lock();
+    assert!(
+        registry.members.insert(thread, thread).is_none(),
+        "Thread {thread} already belongs to a group"
+    );
+    registry.groups.insert(
+        thread,
+        Group {
+            threads: alloc::vec![thread],
+            slots: core::array::from_fn(|slot| (slot == 0).then_some(thread)),
+            exits: HashMap::new(),
+            queue: future::wait::Queue::new(),
+            exit: None,
+        },
+    );
+}
+
+/// Adds a new thread to the given group. Returns `false` if the group does not
+/// exist, is terminating, or already has [`config::MAX_THREADS_PER_TASK`]
+/// threads.
+#[must_use]
+pub fn add(group: Identifier, thread: Identifier) -> bool {
+    let mut registry = REGISTRY.lock();
+    let Some(entry) = registry.groups.get_mut(&group) else {
+        return false;
+    };
+    if entry.exit.is_some() {
+        return false;
+    }
+    let Some(slot) = entry.slots.iter_mut().find(|slot| slot.is_none()) else {
+        return false;
+    };
+
+    *slot = Some(thread);
+    entry.threads.push(thread);
+    registry.members.insert(thread, group);
+    true
+}
+
+/// Returns the identifier of the group of the given thread, or `None` if the
+/// thread does not exist.
+#[must_use]
+pub fn of(thread: Identifier) -> Option<Identifier> {
+    REGISTRY.lock().members.get(&thread).copied()
+}
+
+/// Returns the slot of the given thread in its group, or `None` if the thread
+/// does not exist. The first thread of a group is always in the slot 0.
+#[must_use]
+pub fn slot(thread: Identifier) -> Option<usize> {
+    let registry = REGISTRY.lock();
+    let group = registry.groups.get(registry.members.get(&thread)?)?;
+    group.slots.iter().position(|&slot| slot == Some(thread))
+}
+
+/// Returns the identifier of the group of the currently running thread. This
+/// is the identifier that owns the resources allocated by the thread, since
+/// they are shared with all the threads of the task.
+///
+/// # Panics
+/// Panics if there is no currently running task.
+#[must_use]
+pub fn current() -> Identifier {
+    let id = future::executor::current_task_id().unwrap();
+    of(id).unwrap_or(id)
+}
+
+/// Returns whether the given group exists, meaning that at least one of its
+/// threads is still alive or that its termination was not recorded yet.
+#[must_use]
+pub fn exists(group: Identifier) -> bool {
+    REGISTRY.lock().groups.contains_key(&group)
+}
+
+/// Returns whether the group of the given thread is terminating, in which
+/// case the thread must stop as soon as possible.
+#[must_use]
+pub fn terminating(thread: Identifier) -> bool {
+    let registry = REGISTRY.lock();
+    registry
+        .members
+        .get(&thread)
+        .and_then(|group| registry.groups.get(group))
+        .is_some_and(|group| group.exit.is_some())
+}
+
+/// Terminates the group of the given thread with the given exit status. All
+/// the other threads of the group that are not running are cancelled at once,
+/// and those that are running on another core stop the next time they check
+/// [`terminating`]. If the group is already terminating, its exit status is
+/// left unchanged.
+pub fn terminate(thread: Identifier, exit: Exit) {
+    let (id, others) = {
+        let mut registry = REGISTRY.lock();
+        let Some(&id) = registry.members.get(&thread) else {
+            return;
+        };
+        let Some(group) = registry.groups.get_mut(&id) else {
+            return;
+        };
+        group.exit.get_or_insert(exit);
+        let others = group
+            .threads
+            .iter()
+            .copied()
+            .filter(|&other| other != thread)
+            .collect::<Vec<_>>();
+        (id, others)
+    };
+
+    // The threads are cancelled without holding the lock, since dropping a
+    // thread may wake up other tasks.
+    let cancelled = others
+        .into_iter()
+        .filter(|&other| future::executor::cancel(other))
+        .collect::<Vec<_>>();
+
+    let mut registry = REGISTRY.lock();
+    for other in &cancelled {
+        registry.members.remove(other);
+    }
+    if let Some(group) = registry.groups.get_mut(&id) {
+        for &other in &cancelled {
+            group.remove(other);
+        }
+    }
+}
+
+/// Kills the given group with the given exit status, which is usually
+/// [`Exit::Killed`] with the task on whose behalf it is killed. Contrary to
+/// [`terminate`], no thread is cancelled: all the threads of the group are
+/// woken up, and abandon the syscall they were waiting in (see
+/// [`interruptible`]) or stop at their next trap, so that the last of them
+/// releases the resources of the task as if it had exited. The services
+/// provided by the threads are unregistered at once, failing the tasks waiting
+/// to send them a message.
+///
+/// Returns `false` if the group does not exist or is already terminating.
+#[must_use]
+pub async fn kill(group: Identifier, exit: Exit) -> bool {
+    let threads = {
+        let mut registry = REGISTRY.lock();
+        let Some(entry) = registry.groups.get_mut(&group) else {
+            return false;
+        };
+        if entry.exit.is_some() {
+            return false;
+        }
+        entry.exit = Some(exit);
+        entry.threads.clone()
+    };
+
+    // The registry is unlocked before waking up the threads, since they may
+    // run on another core and check whether they are terminating at once.
+    for thread in threads {
+        _ = ipc::service::unregister(thread).await;
+        future::executor::wake(thread);
+    }
+    true
+}
+
+/// Returns the identifiers of all the groups, including those that are
+/// terminating.
+#[must_use]
+pub fn all() -> Vec<Identifier> {
+    REGISTRY.lock().groups.keys().copied().collect()
+}
+
+/// Returns the threads of the given group that are still alive, or `None` if
+/// the group does not exist.
+#[must_use]
+pub fn threads(group: Identifier) -> Option<Vec<Identifier>> {
+    REGISTRY
+        .lock()
+        .groups
+        .get(&group)
+        .map(|group| group.threads.clone())
+}
+
+/// A future that completes once the group of the given thread is terminating.
+/// It does not register its waker anywhere, and relies on [`kill`] waking up
+/// the thread directly.
+struct Terminating(Identifier);
+
+impl Future for Terminating {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+        if terminating(self.0) {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Runs the given future on behalf of the given thread until it completes, and
+/// returns its output. If the group of the thread starts terminating before,
+/// the future is dropped without being polled again and `None` is returned.
+pub async fn interruptible<F: Future>(thread: Identifier, future: F) -> Option<F::Output> {
+    match futures::future::select(pin!(future), Terminating(thread)).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(((), _)) => None,
+    }
+}
+
+/// Removes the given thread from its group, recording its exit code for the
+/// threads that may join it. Returns the identifier of the group and how it
+/// terminates if the thread was the last one of the group, in which case the
+/// caller must release the resources of the task and then call [`dissolve`].
+/// The task terminates with the exit status given to [`terminate`], or with
+/// the exit code of its last thread if it was never called.
+#[must_use]
+pub fn leave(thread: Identifier, code: i32) -> Option<(Identifier, Exit)> {
+    let mut registry = REGISTRY.lock();
+    let id = registry.members.remove(&thread)?;
+    let group = registry.groups.get_mut(&id)?;
+    group.remove(thread);
+    if group.threads.is_empty() {
+        return Some((id, group.exit.unwrap_or(Exit::Terminate(code))));
+    }
+
+    group.exits.insert(thread, code);
+    group.queue.wake_all();
+    None
+}
+
+/// Removes the given group once all of its threads have terminated and its
+/// termination was recorded.
+pub fn dissolve(group: Identifier) {
+    REGISTRY.lock().groups.remove(&group);
+}
+
+/// Waits until the given thread terminates and returns its exit code. Only a
+/// thread of the same group as the current thread can be joined, and only
+/// once. Returns `None` if the thread is not a thread of the group of the
+/// current thread, or if it was already joined.
+///
+/// # Panics
+/// Panics if there is no currently running task.
+pub async fn join(thread: Identifier) -> Option<i32> {
+    let current = future::executor::current_task_id().unwrap();
+    loop {
+        let queue = {
+            let mut registry = REGISTRY.lock();
+            let id = *registry.members.get(&current)?;
+            let group = registry.groups.get_mut(&id)?;
+            if let Some(code) = group.exits.remove(&thread) {
+                return Some(code);
+            }
+            if !group.threads.contains(&thread) {
+                return None;
+            }
+            group.queue.clone()
+        };
+
+        future::wait::wait(&queue).await;
+    }
+}
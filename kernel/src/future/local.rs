@@ -0,0 +1,185 @@
+//! Task-local storage. A subsystem that needs to keep some state for each
+//! task declares a key with the [`task_local!`] macro, instead of adding a
+//! field to [`LocalDataSet`]. The value of a key is created the first time it
+//! is accessed for a task, and is dropped along with the task.
+//!
+//! A key is either local to each thread of a user task, or shared by all the
+//! threads of a task (see [`LocalDataSet::sibling`]), in which case the value
+//! is created once for the whole task.
+//!
+//! ```ignore
+//! task_local! {
+//!     /// The number of times the task was woken up.
+//!     static WAKEUPS: AtomicUsize = AtomicUsize::new(0);
+//!
+//!     /// The timers owned by the task, shared with its sibling threads.
+//!     shared static TIMERS: lock::Mutex<Vec<Timer>> = lock::Mutex::new(Vec::new());
+//! }
+//!
+//! WAKEUPS.with_current(|wakeups| wakeups.fetch_add(1, Ordering::Relaxed));
+//! ```
+use crate::{
+    config,
+    future::{
+        self,
+        task::{Identifier, LocalDataSet},
+    },
+};
+use alloc::boxed::Box;
+use core::{
+    any::Any,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The number of keys that were given an index so far.
+static KEYS: AtomicUsize = AtomicUsize::new(0);
+
+/// A key identifying a value of type `T` kept for each task. Keys are declared
+/// as statics with the [`task_local!`] macro. Since the value may be accessed
+/// by other tasks, it must be `Sync` and be mutated through interior
+/// mutability.
+pub struct Key<T> {
+    /// Creates the value of a task the first time it is accessed.
+    init: fn() -> T,
+
+    /// Whether the value is shared by all the threads of a task.
+    shared: bool,
+
+    /// The index of the value in the storage of each task, given the first
+    /// time the key is used.
+    index: spin::Once<usize>,
+}
+
+impl<T: Any + Send + Sync> Key<T> {
+    /// Creates a key whose value is local to each thread.
+    #[must_use]
+    pub const fn new(init: fn() -> T) -> Self {
+        Self {
+            init,
+            shared: false,
+            index: spin::Once::new(),
+        }
+    }
+
+    /// Creates a key whose value is shared by all the threads of a task.
+    #[must_use]
+    pub const fn shared(init: fn() -> T) -> Self {
+        Self {
+            init,
+            shared: true,
+            index: spin::Once::new(),
+        }
+    }
+
+    /// Executes a closure with the value of the task with the given
+    /// identifier, creating it if this is the first access. Returns `None` if
+    /// the task does not exist.
+    pub fn with<F, R>(&self, id: Identifier, f: F) -> Option<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        future::task::try_with_local_set_from(id, |set| set.map(|set| f(self.get(set))))
+    }
+
+    /// Executes a closure with the value of the currently running task,
+    /// creating it if this is the first access.
+    ///
+    /// # Panics
+    /// Panics if there is no currently running task.
+    pub fn with_current<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        future::task::with_current_local_set(|set| f(self.get(set)))
+    }
+
+    /// Returns the value of this key in the given local data set, creating
+    /// it if needed.
+    fn get<'a>(&self, set: &'a LocalDataSet) -> &'a T {
+        let storage = if self.shared {
+            &*set.shared_locals
+        } else {
+            &set.locals
+        };
+        storage.values[self.index()]
+            .call_once(|| Box::new((self.init)()))
+            .downcast_ref()
+            .expect("Task-local value of the wrong type")
+    }
+
+    /// Returns the index of the key, giving it one if this is its first use.
+    ///
+    /// # Panics
+    /// Panics if more than [`config::TASK_LOCAL_KEYS`] keys are used.
+    fn index(&self) -> usize {
+        *self.index.call_once(|| {
+            let index = KEYS.fetch_add(1, Ordering::Relaxed);
+            assert!(
+                index < config::TASK_LOCAL_KEYS,
+                "Too many task-local keys, raise config::TASK_LOCAL_KEYS"
+            );
+            index
+        })
+    }
+}
+
+impl<T> core::fmt::Debug for Key<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Key")
+            .field("shared", &self.shared)
+            .field("index", &self.index.get())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The values of the task-local keys of a task, indexed by key. Each value is
+/// created once, so a reference to it stays valid as long as the task exists.
+pub struct Storage {
+    values: [spin::Once<Box<dyn Any + Send + Sync>>; config::TASK_LOCAL_KEYS],
+}
+
+impl Storage {
+    /// Creates a storage where no value was created yet.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            values: [const { spin::Once::new() }; config::TASK_LOCAL_KEYS],
+        }
+    }
+}
+
+impl Default for Storage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for Storage {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let created = self.values.iter().filter(|value| value.is_completed());
+        f.debug_struct("Storage")
+            .field("created", &created.count())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Declares task-local keys, local to each thread or shared by all the
+/// threads of a task when prefixed with `shared`. The initializer is run for
+/// each task the first time its value is accessed.
+macro_rules! task_local {
+    () => {};
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr; $($rest:tt)*) => {
+        $(#[$attr])*
+        $vis static $name: $crate::future::local::Key<$ty> =
+            $crate::future::local::Key::new(|| $init);
+        $crate::future::local::task_local!($($rest)*);
+    };
+    ($(#[$attr:meta])* $vis:vis shared static $name:ident: $ty:ty = $init:expr; $($rest:tt)*) => {
+        $(#[$attr])*
+        $vis static $name: $crate::future::local::Key<$ty> =
+            $crate::future::local::Key::shared(|| $init);
+        $crate::future::local::task_local!($($rest)*);
+    };
+}
+
+pub(crate) use task_local;
@@ -4,9 +4,16 @@ use core::{
 };
 use futures::Future;
 
+pub mod budget;
+pub mod channel;
+pub mod deferred;
 pub mod executor;
-pub mod mutex;
+pub mod group;
+pub mod local;
+pub mod slot;
+pub mod sync;
 pub mod task;
+pub mod termination;
 pub mod user;
 pub mod wait;
 pub mod waker;
@@ -0,0 +1,144 @@
+use crate::future::task::Identifier;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::RwLock;
+
+/// A table holding a value for each task, preallocated with a slot for each
+/// task that can exist at the same time (see [`Identifier::SLOTS`]). The slot
+/// of a task is selected by the index of its identifier, so finding the value
+/// of a task never allocates nor searches the table.
+///
+/// Each slot has its own lock, so that accessing the values of different tasks
+/// never contends. A slot also keeps the whole identifier of the task that
+/// owns it, so that a stale identifier, whose index was reused by a later task
+/// with another generation, never finds the value of the later task.
+pub struct SlotTable<T> {
+    slots: [RwLock<Option<(Identifier, T)>>; Identifier::SLOTS],
+
+    /// The number of occupied slots.
+    len: AtomicUsize,
+}
+
+impl<T> SlotTable<T> {
+    /// Creates a table where all the slots are free.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { RwLock::new(None) }; Identifier::SLOTS],
+            len: AtomicUsize::new(0),
+        }
+    }
+
+    /// Stores the value of the task with the given identifier. Returns the
+    /// value back if the slot of the task is already occupied, which can only
+    /// happen if the identifier is already used by a running task.
+    ///
+    /// # Errors
+    /// Returns the value if the slot of the task is already occupied.
+    pub fn insert(&self, id: Identifier, value: T) -> Result<(), T> {
+        let mut slot = self.slots[id.slot()].write();
+        if slot.is_some() {
+            return Err(value);
+        }
+        *slot = Some((id, value));
+        self.len.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Removes the value of the task with the given identifier and returns it,
+    /// or returns `None` if the table holds no value for this task.
+    pub fn remove(&self, id: Identifier) -> Option<T> {
+        let mut slot = self.slots[id.slot()].write();
+        if slot.as_ref().is_none_or(|&(owner, _)| owner != id) {
+            return None;
+        }
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        slot.take().map(|(_, value)| value)
+    }
+
+    /// Returns whether the table holds a value for the task with the given
+    /// identifier.
+    #[must_use]
+    pub fn contains(&self, id: Identifier) -> bool {
+        self.with(id, |value| value.is_some())
+    }
+
+    /// Executes a closure with the value of the task with the given
+    /// identifier, or with `None` if the table holds no value for this task.
+    /// Nested calls are allowed, since the slot is only locked for reading.
+    pub fn with<F, R>(&self, id: Identifier, f: F) -> R
+    where
+        F: FnOnce(Option<&T>) -> R,
+    {
+        let slot = self.slots[id.slot()].read();
+        f(Self::owned_by(slot.as_ref(), id))
+    }
+
+    /// Executes a closure with the value of the task with the given
+    /// identifier like [`SlotTable::with`], but without blocking. Returns
+    /// `None` if the slot of the task is being modified.
+    pub fn try_with<F, R>(&self, id: Identifier, f: F) -> Option<R>
+    where
+        F: FnOnce(Option<&T>) -> R,
+    {
+        let slot = self.slots[id.slot()].try_read()?;
+        Some(f(Self::owned_by(slot.as_ref(), id)))
+    }
+
+    /// Executes a closure with a mutable reference to the value of the task
+    /// with the given identifier, or with `None` if the table holds no value
+    /// for this task.
+    pub fn with_mut<F, R>(&self, id: Identifier, f: F) -> R
+    where
+        F: FnOnce(Option<&mut T>) -> R,
+    {
+        let mut slot = self.slots[id.slot()].write();
+        f(slot
+            .as_mut()
+            .filter(|(owner, _)| *owner == id)
+            .map(|(_, value)| value))
+    }
+
+    /// Returns the identifiers of the tasks that have a value in the table,
+    /// in the order of their slots.
+    #[must_use]
+    pub fn ids(&self) -> Vec<Identifier> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.read().as_ref().map(|&(id, _)| id))
+            .collect()
+    }
+
+    /// Returns the number of tasks that have a value in the table.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether no task has a value in the table.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value stored in a slot if it belongs to the task with the
+    /// given identifier.
+    fn owned_by(slot: Option<&(Identifier, T)>, id: Identifier) -> Option<&T> {
+        slot.filter(|(owner, _)| *owner == id)
+            .map(|(_, value)| value)
+    }
+}
+
+impl<T> Default for SlotTable<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> core::fmt::Debug for SlotTable<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SlotTable")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
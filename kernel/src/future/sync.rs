@@ -0,0 +1,484 @@
+//! Locks for the data shared by tasks, which put the tasks waiting for them to
+//! sleep instead of spinning. Contrary to the spinlocks of the kernel (see
+//! [`crate::utils::lock`]), they can be held across an await point, which
+//! makes them suitable for data accessed by futures for longer periods.
+//!
+//! Both locks are fair: the tasks waiting for a lock acquire it in the order
+//! in which they started waiting, and a task asking for a lock that others
+//! wait for waits behind them even if the lock is available, so that a task
+//! cannot be starved by a stream of others. When a lock is released, it is
+//! handed over directly to the first waiting task, or to all the readers at
+//! the front of the queue for a [`RwLock`], and they are woken up.
+use crate::utils::lock;
+use alloc::{collections::VecDeque, vec::Vec};
+use core::{
+    cell::UnsafeCell,
+    fmt::Debug,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// How a lock is acquired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    /// The lock is shared with other readers.
+    Shared,
+
+    /// The lock is held by a single task.
+    Exclusive,
+}
+
+/// A task waiting for a lock.
+#[derive(Debug)]
+struct Waiter {
+    /// A number identifying the waiter in the queue.
+    ticket: u64,
+
+    /// How the task wants to acquire the lock.
+    access: Access,
+
+    /// The waker of the task.
+    waker: Waker,
+
+    /// Whether the lock was handed over to the task, which will take it the
+    /// next time it is polled.
+    granted: bool,
+}
+
+/// The state of a lock.
+#[derive(Debug)]
+struct State {
+    /// The number of readers holding the lock.
+    readers: usize,
+
+    /// Whether a task holds the lock exclusively.
+    writer: bool,
+
+    /// The tasks waiting for the lock, in the order they started waiting.
+    waiters: VecDeque<Waiter>,
+
+    /// The ticket of the next waiter.
+    next_ticket: u64,
+}
+
+impl State {
+    /// Returns whether the lock can be acquired with the given access right
+    /// now, without regard to the waiting tasks.
+    const fn available(&self, access: Access) -> bool {
+        match access {
+            Access::Shared => !self.writer,
+            Access::Exclusive => !self.writer && self.readers == 0,
+        }
+    }
+
+    /// Acquires the lock with the given access if it is available and no task
+    /// is waiting for it. Returns whether the lock was acquired.
+    fn try_acquire(&mut self, access: Access) -> bool {
+        let waiting = self.waiters.iter().any(|waiter| !waiter.granted);
+        if waiting || !self.available(access) {
+            return false;
+        }
+        self.take(access);
+        true
+    }
+
+    /// Marks the lock as held with the given access.
+    const fn take(&mut self, access: Access) {
+        match access {
+            Access::Shared => self.readers += 1,
+            Access::Exclusive => self.writer = true,
+        }
+    }
+
+    /// Marks the lock as released by a holder with the given access.
+    const fn release(&mut self, access: Access) {
+        match access {
+            Access::Shared => self.readers -= 1,
+            Access::Exclusive => self.writer = false,
+        }
+    }
+
+    /// Hands the lock over to the tasks at the front of the queue that can
+    /// take it, and returns their wakers so that they are woken up once the
+    /// state is unlocked.
+    fn grant(&mut self) -> Vec<Waker> {
+        let mut woken = Vec::new();
+        for index in 0..self.waiters.len() {
+            let waiter = &self.waiters[index];
+            if waiter.granted {
+                continue;
+            }
+            let access = waiter.access;
+            if !self.available(access) {
+                break;
+            }
+
+            self.take(access);
+            let waiter = &mut self.waiters[index];
+            waiter.granted = true;
+            woken.push(waiter.waker.clone());
+            if access == Access::Exclusive {
+                break;
+            }
+        }
+        woken
+    }
+}
+
+/// The queue of a lock, shared by [`Mutex`] and [`RwLock`].
+#[derive(Debug)]
+struct Queue {
+    state: lock::Mutex<State>,
+}
+
+impl Queue {
+    /// Creates the queue of an unlocked lock.
+    const fn new() -> Self {
+        Self {
+            state: lock::Mutex::new(State {
+                readers: 0,
+                writer: false,
+                waiters: VecDeque::new(),
+                next_ticket: 0,
+            }),
+        }
+    }
+
+    /// Acquires the lock with the given access, waiting for it if needed.
+    async fn acquire(&self, access: Access) {
+        Acquire {
+            queue: self,
+            access,
+            ticket: None,
+        }
+        .await;
+    }
+
+    /// Acquires the lock with the given access if it is available and no task
+    /// is waiting for it. Returns whether the lock was acquired.
+    fn try_acquire(&self, access: Access) -> bool {
+        self.state.lock().try_acquire(access)
+    }
+
+    /// Releases the lock held with the given access, and hands it over to the
+    /// next waiting tasks.
+    fn release(&self, access: Access) {
+        let woken = {
+            let mut state = self.state.lock();
+            state.release(access);
+            state.grant()
+        };
+        woken.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// A future acquiring a lock. If it is dropped while waiting, the task leaves
+/// the queue, and releases the lock if it was already handed over to it.
+struct Acquire<'a> {
+    queue: &'a Queue,
+    access: Access,
+
+    /// The ticket of the task in the queue, if it is waiting.
+    ticket: Option<u64>,
+}
+
+impl Future for Acquire<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.queue.state.lock();
+        let Some(ticket) = self.ticket else {
+            if state.try_acquire(self.access) {
+                return Poll::Ready(());
+            }
+            let ticket = state.next_ticket;
+            state.next_ticket += 1;
+            state.waiters.push_back(Waiter {
+                ticket,
+                access: self.access,
+                waker: context.waker().clone(),
+                granted: false,
+            });
+            drop(state);
+            self.ticket = Some(ticket);
+            return Poll::Pending;
+        };
+
+        let index = state
+            .waiters
+            .iter()
+            .position(|waiter| waiter.ticket == ticket)
+            .expect("Lock waiter not in the queue");
+        if state.waiters[index].granted {
+            state.waiters.remove(index);
+            drop(state);
+            self.ticket = None;
+            Poll::Ready(())
+        } else {
+            state.waiters[index].waker.clone_from(context.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        let Some(ticket) = self.ticket else {
+            return;
+        };
+
+        let woken = {
+            let mut state = self.queue.state.lock();
+            if let Some(index) = state
+                .waiters
+                .iter()
+                .position(|waiter| waiter.ticket == ticket)
+                && let Some(waiter) = state.waiters.remove(index)
+                && waiter.granted
+            {
+                state.release(waiter.access);
+            }
+            // The task may have prevented the tasks behind it from taking the
+            // lock, such as readers waiting behind a writer.
+            state.grant()
+        };
+        woken.into_iter().for_each(Waker::wake);
+    }
+}
+
+/// A mutual exclusion lock protecting data of type `T`, which puts the tasks
+/// waiting for it to sleep.
+pub struct Mutex<T: ?Sized> {
+    queue: Queue,
+    data: UnsafeCell<T>,
+}
+
+/// SAFETY: The `Mutex` type is safe to be send between threads as long
+/// as the inner type is `Send`.
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+
+/// SAFETY: The `Mutex` type is safe to be shared between threads as long
+/// as the inner type is `Send` because the inner type is protected by
+/// the `Mutex` type against concurrent access.
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    /// Creates a new unlocked mutex protecting the given data.
+    #[must_use]
+    pub const fn new(data: T) -> Self {
+        Self {
+            queue: Queue::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes the mutex and returns the data it protects.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Locks the mutex, waiting until all the tasks that asked for it before
+    /// have released it.
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        self.queue.acquire(Access::Exclusive).await;
+        MutexGuard { mutex: self }
+    }
+
+    /// Locks the mutex if it is unlocked and no task is waiting for it, and
+    /// returns `None` otherwise.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.queue
+            .try_acquire(Access::Exclusive)
+            .then(|| MutexGuard { mutex: self })
+    }
+
+    /// Returns a mutable reference to the data. Since this borrows the mutex
+    /// mutably, no locking is needed.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized> Debug for Mutex<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Mutex")
+            .field("queue", &self.queue)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard giving exclusive access to the data of a [`Mutex`], which is
+/// unlocked when the guard is dropped.
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The mutex is held for as long as the guard lives.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The mutex is held exclusively for as long as the guard
+        // lives, and the guard is borrowed mutably.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.queue.release(Access::Exclusive);
+    }
+}
+
+/// A reader-writer lock protecting data of type `T`, which allows either many
+/// readers or a single writer at a time, and puts the tasks waiting for it to
+/// sleep. A reader waits if a writer is waiting before it, so that writers are
+/// never starved by readers.
+pub struct RwLock<T: ?Sized> {
+    queue: Queue,
+    data: UnsafeCell<T>,
+}
+
+/// SAFETY: The `RwLock` type is safe to be send between threads as long
+/// as the inner type is `Send`.
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+
+/// SAFETY: The `RwLock` type gives shared access to the inner type to several
+/// threads at once, so the inner type must be `Sync` as well as `Send`.
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new unlocked reader-writer lock protecting the given data.
+    #[must_use]
+    pub const fn new(data: T) -> Self {
+        Self {
+            queue: Queue::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes the lock and returns the data it protects.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Locks the data for reading, waiting until the writers that asked for it
+    /// before have released it.
+    pub async fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.queue.acquire(Access::Shared).await;
+        RwLockReadGuard { lock: self }
+    }
+
+    /// Locks the data for writing, waiting until all the tasks that asked for
+    /// it before have released it.
+    pub async fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.queue.acquire(Access::Exclusive).await;
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// Locks the data for reading if no writer holds it and no task is waiting
+    /// for it, and returns `None` otherwise.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+        self.queue
+            .try_acquire(Access::Shared)
+            .then(|| RwLockReadGuard { lock: self })
+    }
+
+    /// Locks the data for writing if it is unlocked and no task is waiting for
+    /// it, and returns `None` otherwise.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        self.queue
+            .try_acquire(Access::Exclusive)
+            .then(|| RwLockWriteGuard { lock: self })
+    }
+
+    /// Returns a mutable reference to the data. Since this borrows the lock
+    /// mutably, no locking is needed.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized> Debug for RwLock<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RwLock")
+            .field("queue", &self.queue)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A guard giving shared access to the data of a [`RwLock`], which is
+/// unlocked when the guard is dropped.
+pub struct RwLockReadGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T: ?Sized> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The lock is held for reading for as long as the guard lives,
+        // so no writer can access the data.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.queue.release(Access::Shared);
+    }
+}
+
+/// A guard giving exclusive access to the data of a [`RwLock`], which is
+/// unlocked when the guard is dropped.
+pub struct RwLockWriteGuard<'a, T: ?Sized> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T: ?Sized> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The lock is held exclusively for as long as the guard lives.
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for RwLockWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The lock is held exclusively for as long as the guard lives,
+        // and the guard is borrowed mutably.
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.queue.release(Access::Exclusive);
+    }
+}
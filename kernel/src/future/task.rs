@@ -1,28 +1,40 @@
 use crate::{
-    future::{self, executor::Executor, waker::Waker},
-    ipc, time,
+    config,
+    future::{self, executor::Executor, slot::SlotTable, waker::Waker},
+    ipc, mm, time, user,
+    utils::lock,
 };
-use alloc::{boxed::Box, sync::Arc};
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc};
 use core::{
     future::Future,
     hash::Hash,
     pin::Pin,
-    sync::atomic::{AtomicUsize, Ordering},
+    sync::{
+        Exclusive,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
 };
-use hashbrown::HashMap;
-use spin::{Lazy, RwLock};
+
+pub use ::syscall::task::Priority;
+
+/// The allocator of the identifiers returned by [`Identifier::generate`].
+static ALLOCATOR: lock::Mutex<Allocator> = lock::Mutex::new(Allocator::new());
 
 /// The local data associated with each task.
-static TASK_LOCAL_DATA_MAP: Lazy<RwLock<HashMap<Identifier, LocalDataSet>>> =
-    Lazy::new(|| RwLock::new(HashMap::new()));
+static TASK_LOCAL_DATA: SlotTable<LocalDataSet> = SlotTable::new();
+
+/// The number of existing tasks of each kind, indexed by [`Kind`].
+static TASKS_BY_KIND: [AtomicUsize; 2] = [const { AtomicUsize::new(0) }; 2];
 
 /// A task that can be executed by an executor.
 pub struct Task<'a> {
     /// The executor that owns the task.
     executor: &'a Executor<'a>,
 
-    /// The future that the task is running.
-    future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    /// The future that the task is running. It is only reachable through a
+    /// mutable reference, so that tasks can be shared between cores in the
+    /// tables of the executor even if the future itself is not `Sync`.
+    future: Exclusive<Pin<Box<dyn Future<Output = ()> + Send>>>,
 
     /// The virtual runtime of the task. The virtual terminology is borrowed
     /// from the Completely Fair Scheduler (CFS) in Linux. It represents the
@@ -31,35 +43,50 @@ pub struct Task<'a> {
     /// quantum of all tasks in the system when the task was created.
     vruntime: u64,
 
+    /// The scheduling class of the task. The virtual runtime of the task is
+    /// only compared with the tasks of the same class.
+    priority: Priority,
+
     /// The waker of the task.
     waker: Arc<Waker>,
 
     /// The identifier of the task.
     id: Identifier,
+
+    /// What the task runs.
+    kind: Kind,
 }
 
 impl<'a> Task<'a> {
-    /// Creates a new task with the given executor and future. It also creates
-    /// the local data set for the task.
+    /// Creates a new task of the given kind with the given executor, future,
+    /// identifier and local data set, in the [`Priority::Normal`] class. The local data set
+    /// is registered for the task until the task is dropped.
+    ///
+    /// # Panics
+    /// Panics if the identifier is already used by another task.
     pub fn new(
         executor: &'a Executor<'a>,
         future: Pin<Box<dyn Future<Output = ()> + Send>>,
         vruntime: u64,
+        id: Identifier,
+        local: LocalDataSet,
+        kind: Kind,
     ) -> Self {
-        let id = Identifier::generate();
-        let waker = Arc::new(Waker::new(Arc::clone(executor.ready_ids()), id));
-
-        // Create the local data set for the task
-        TASK_LOCAL_DATA_MAP
-            .write()
-            .insert(id, LocalDataSet::default());
+        let waker = Arc::new(Waker::new(Arc::clone(executor.wakeups()), id));
+        assert!(
+            TASK_LOCAL_DATA.insert(id, local).is_ok(),
+            "Task identifier {id} already in use"
+        );
+        TASKS_BY_KIND[kind as usize].fetch_add(1, Ordering::Relaxed);
 
         Self {
             executor,
-            future,
+            future: Exclusive::new(future),
             vruntime,
+            priority: Priority::Normal,
             waker,
             id,
+            kind,
         }
     }
 
@@ -69,7 +96,8 @@ impl<'a> Task<'a> {
     pub fn poll(&mut self) -> core::task::Poll<()> {
         let waker = Arc::clone(&self.waker).into();
         let mut context = core::task::Context::from_waker(&waker);
-        let (output, elapsed) = time::spent_into(|| self.future.as_mut().poll(&mut context));
+        let (output, elapsed) =
+            time::spent_into(|| self.future.get_mut().as_mut().poll(&mut context));
         self.vruntime += elapsed.as_nanos() as u64;
         output
     }
@@ -85,12 +113,42 @@ impl<'a> Task<'a> {
         self.vruntime
     }
 
+    /// Returns the scheduling class of the task.
+    #[must_use]
+    pub const fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// Sets the scheduling class of the task. It is taken into account the
+    /// next time the task is woken up.
+    pub fn set_priority(&mut self, priority: Priority) {
+        self.priority = priority;
+    }
+
+    /// Returns the waker of the task.
+    #[must_use]
+    pub(super) fn waker(&self) -> &Arc<Waker> {
+        &self.waker
+    }
+
+    /// Returns the CPU core on which the task prefers to run.
+    #[must_use]
+    pub fn affinity(&self) -> Affinity {
+        self.waker.affinity()
+    }
+
     /// Returns the executor that owns the task.
     #[must_use]
     pub const fn executor(&'a self) -> &'a Executor<'a> {
         self.executor
     }
 
+    /// Returns what the task runs.
+    #[must_use]
+    pub const fn kind(&self) -> Kind {
+        self.kind
+    }
+
     /// Returns the identifier of the task.
     #[must_use]
     pub const fn id(&self) -> Identifier {
@@ -100,45 +158,222 @@ impl<'a> Task<'a> {
 
 impl Drop for Task<'_> {
     fn drop(&mut self) {
-        // Remove the local data set for the task
-        TASK_LOCAL_DATA_MAP.write().remove(&self.id);
+        // Remove the local data set for the task, after which its identifier
+        // does not designate any task and its index can be reused.
+        TASK_LOCAL_DATA.remove(self.id);
+        self.id.release();
+        TASKS_BY_KIND[self.kind as usize].fetch_sub(1, Ordering::Relaxed);
     }
 }
 
-/// A unique identifier for a task.
+/// What a task runs, which tells the threads of user tasks apart from the
+/// background tasks of the kernel in the statistics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A thread of a user task, running its user code (see
+    /// [`crate::future::user::thread_loop`]).
+    User = 0,
+
+    /// A background task of the kernel, running a kernel future. It belongs
+    /// to no user task, never enters user mode and cannot be killed.
+    Kernel = 1,
+}
+
+impl Kind {
+    /// Returns the number of existing tasks of this kind, including the ones
+    /// that are currently running.
+    #[must_use]
+    pub fn count(self) -> usize {
+        TASKS_BY_KIND[self as usize].load(Ordering::Relaxed)
+    }
+}
+
+/// A hint telling the executor on which CPU core a task should run. Tasks that
+/// prefer a core are always woken up on that core, and are never stolen by the
+/// other cores. However, the core may still steal tasks from the other cores
+/// when it has nothing else to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Affinity {
+    /// The task can run on any core. It is woken up on the first core that
+    /// picks it from the global queue.
+    #[default]
+    Any,
+
+    /// The task prefers to run on the core with the given index.
+    Prefer(usize),
+}
+
+/// A unique identifier for a task. The index of the identifier may be reused
+/// once the task terminates, but with a different generation, so that the
+/// identifier as a whole is never reused (see [`::syscall::task::TaskId`]).
+/// All the maps of the kernel are keyed by the whole identifier, so looking up
+/// a stale identifier never finds the task that reused its index.
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
-pub struct Identifier(usize);
+pub struct Identifier(::syscall::task::TaskId);
 
 impl Identifier {
+    /// The sender of the messages sent by the kernel itself (see
+    /// [`::syscall::ipc::KERNEL_SENDER`]). No task ever has this identifier.
+    pub const KERNEL: Self = Self(::syscall::task::TaskId::new(0, 0));
+
+    /// The identifier reserved for the init task.
+    pub const INIT: Self = Self(::syscall::task::TaskId::new(1, 0));
+
+    /// The first index that can be returned by [`Identifier::generate`].
+    /// All indexes below this value are reserved for well-known system tasks
+    /// and can only be used with [`crate::future::executor::spawn_with_id`].
+    const FIRST_DYNAMIC: u32 = 16;
+
+    /// The number of slots of the tables indexed by task identifiers (see
+    /// [`SlotTable`]): one for each reserved index, and one for each of the
+    /// [`config::MAX_TASKS`] tasks that can exist at the same time.
+    pub const SLOTS: usize = Self::FIRST_DYNAMIC as usize + config::MAX_TASKS as usize;
+
     /// Creates a new task identifier. The identifier is guaranteed to be unique
-    /// across the entire kernel runtime.
-    pub fn generate() -> Self {
-        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
-        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    /// across the entire kernel runtime, and never collides with a reserved
+    /// identifier. It reuses the index of a terminated task if there is one.
+    /// Returns `None` if [`config::MAX_TASKS`] tasks already exist.
+    #[must_use]
+    pub fn generate() -> Option<Self> {
+        ALLOCATOR.lock().allocate().map(Self)
+    }
+
+    /// Creates a task identifier from an identifier given by user space.
+    /// Returns `None` if the identifier cannot designate any task.
+    #[must_use]
+    pub const fn from_user(raw: usize) -> Option<Self> {
+        match ::syscall::task::TaskId::from_raw(raw) {
+            Some(id) => Some(Self(id)),
+            None => None,
+        }
     }
-}
 
-impl From<usize> for Identifier {
-    fn from(id: usize) -> Self {
-        Self(id)
+    /// Returns the index of the identifier, which may be shared with the
+    /// terminated tasks that had the same index.
+    #[must_use]
+    pub const fn index(&self) -> u32 {
+        self.0.index()
+    }
+
+    /// Returns the generation of the identifier, which distinguishes it from
+    /// the other identifiers with the same index.
+    #[must_use]
+    pub const fn generation(&self) -> u32 {
+        self.0.generation()
+    }
+
+    /// Returns the slot of the task in the tables indexed by task identifiers.
+    /// Reserved identifiers have a slot of their own, while the indexes
+    /// allocated by [`Identifier::generate`] always lie in a range of
+    /// [`config::MAX_TASKS`] consecutive indexes, and therefore never share a
+    /// slot.
+    #[must_use]
+    pub const fn slot(&self) -> usize {
+        let index = self.index();
+        if index < Self::FIRST_DYNAMIC {
+            index as usize
+        } else {
+            let offset = (index - Self::FIRST_DYNAMIC) % config::MAX_TASKS as u32;
+            (Self::FIRST_DYNAMIC + offset) as usize
+        }
+    }
+
+    /// Returns whether the identifier is reserved for a well-known system task.
+    #[must_use]
+    pub const fn is_reserved(&self) -> bool {
+        self.0.index() < Self::FIRST_DYNAMIC
+    }
+
+    /// Offsets all the indexes that will be allocated by the given seed. This
+    /// must be called before any identifier is generated.
+    pub fn seed(seed: u32) {
+        let mut allocator = ALLOCATOR.lock();
+        allocator.first = Self::FIRST_DYNAMIC + seed;
+        allocator.next = allocator.first;
+    }
+
+    /// Allows the index of the identifier to be reused by a later task, with
+    /// the next generation. This must only be called once the identifier no
+    /// longer designates a running task. Reserved identifiers are never
+    /// reused.
+    pub fn release(self) {
+        if !self.is_reserved() {
+            ALLOCATOR.lock().release(self.0);
+        }
     }
 }
 
 impl From<Identifier> for usize {
     fn from(id: Identifier) -> usize {
-        id.0
+        usize::from(id.0)
+    }
+}
+
+/// The allocator of the indexes of the task identifiers. Released indexes are
+/// reused in the order they were released, to delay their reuse as much as
+/// possible. The allocated indexes are the [`config::MAX_TASKS`] indexes
+/// starting at `first`, so that each of them has its own slot (see
+/// [`Identifier::slot`]).
+struct Allocator {
+    /// The first index that can be allocated.
+    first: u32,
+
+    /// The first index that was never allocated.
+    next: u32,
+
+    /// The next identifiers of the released indexes, from the oldest to the
+    /// most recently released.
+    free: VecDeque<::syscall::task::TaskId>,
+}
+
+impl Allocator {
+    /// Creates an allocator that never allocated any index.
+    const fn new() -> Self {
+        Self {
+            first: Identifier::FIRST_DYNAMIC,
+            next: Identifier::FIRST_DYNAMIC,
+            free: VecDeque::new(),
+        }
+    }
+
+    /// Allocates a new identifier, reusing a released index if possible.
+    /// Returns `None` if all the indexes are in use.
+    fn allocate(&mut self) -> Option<::syscall::task::TaskId> {
+        if let Some(id) = self.free.pop_front() {
+            return Some(id);
+        }
+        if self.next - self.first >= u32::from(config::MAX_TASKS) {
+            return None;
+        }
+        let index = self.next;
+        self.next += 1;
+        Some(::syscall::task::TaskId::new(index, 0))
+    }
+
+    /// Releases the index of the given identifier. The index is never reused
+    /// if its generation cannot be increased anymore.
+    fn release(&mut self, id: ::syscall::task::TaskId) {
+        if let Some(next) = id.next_generation() {
+            self.free.push_back(next);
+        }
     }
 }
 
 impl core::fmt::Display for Identifier {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", usize::from(self.0))
     }
 }
 
 /// The local data set associated with a task. This data is specific to each
 /// task and is not shared between tasks, although a task can access the local
-/// data of other tasks through interior mutability.
+/// data of other tasks through interior mutability. The only exception are the
+/// handles, the capabilities and the resource limits, which are shared by all
+/// the threads of a user task (see [`LocalDataSet::sibling`]).
+///
+/// Subsystems outside of the core of the kernel should rather keep their
+/// per-task state in task-local keys (see [`future::local`]), which are
+/// stored here without this struct having to know about them.
 #[derive(Debug)]
 pub struct LocalDataSet {
     /// A queue where this task can sleep waiting to receive an IPC message.
@@ -147,17 +382,59 @@ pub struct LocalDataSet {
     /// A queue where tasks that are waiting for a reply from this task can sleep.
     pub ipc_reply_queue: future::wait::Queue,
 
-    /// A queue of tasks waiting to send IPC messages to this task.
+    /// A queue of tasks waiting for room in the mailbox of this task to send
+    /// it IPC messages.
     pub ipc_send_queue: future::wait::Queue,
 
-    /// An incoming IPC message for the task.
-    pub ipc_message: spin::Mutex<Option<Box<ipc::message::Message>>>,
+    /// The IPC state of the task: its incoming messages, the reply to its
+    /// last message, what it waits for and the senders it must reply to. The
+    /// mailbox holds at most as many messages as allowed by the limits of the
+    /// task, which never exceeds [`crate::config::IPC_MAILBOX_CAPACITY`], and
+    /// senders must wait on `ipc_send_queue` when it is full.
+    pub ipc: lock::Mutex<kcore::ipc::Endpoint<mm::slab::SlabBox<ipc::message::Message>>>,
+
+    /// A number unique to this task across all the kernel runtime. It is
+    /// attached to the messages sent by the task, so that their receivers can
+    /// tell it apart from any other task without decoding its identifier.
+    pub generation: u64,
+
+    /// The handles owned by the task, shared with its sibling threads.
+    pub handles: Arc<lock::Mutex<ipc::handle::Table>>,
+
+    /// The capabilities held by the task, restricting which syscalls it is
+    /// allowed to invoke. They are shared with its sibling threads.
+    pub capabilities: Arc<lock::Mutex<::syscall::capability::Capabilities>>,
+
+    /// The resource limits of the task and its usage of the limited
+    /// resources. They are shared with its sibling threads.
+    pub limits: Arc<lock::Mutex<user::limit::Limits>>,
+
+    /// Whether the task is essential to the system, in which case it is never
+    /// killed by the out-of-memory handler. It is shared with its sibling
+    /// threads, and is not inherited by the children of the task.
+    pub essential: Arc<AtomicBool>,
+
+    /// The number of the syscall that the kernel is handling on behalf of the
+    /// task, or [`NO_SYSCALL`] if the task is not in a syscall. This is only
+    /// used to give context when the kernel panics.
+    pub syscall: AtomicUsize,
+
+    /// The values of the task-local keys that are local to this thread.
+    pub locals: future::local::Storage,
+
+    /// The values of the task-local keys that are shared with the sibling
+    /// threads of the task.
+    pub shared_locals: Arc<future::local::Storage>,
+}
 
-    /// The reply message sent to this task.
-    pub ipc_reply: spin::Mutex<Option<Box<ipc::message::Message>>>,
+/// The value of [`LocalDataSet::syscall`] when the task is not in a syscall.
+pub const NO_SYSCALL: usize = usize::MAX;
 
-    /// The IPC state of the task.
-    pub ipc_waiting_state: spin::Mutex<ipc::message::IpcWaitingState>,
+/// Returns a new value for [`LocalDataSet::generation`]. Generations start at
+/// 1, so that 0 never identifies a task.
+fn next_generation() -> u64 {
+    static NEXT_GENERATION: AtomicU64 = AtomicU64::new(1);
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
 }
 
 impl Default for LocalDataSet {
@@ -166,9 +443,40 @@ impl Default for LocalDataSet {
             ipc_receive_queue: future::wait::Queue::new(),
             ipc_reply_queue: future::wait::Queue::new(),
             ipc_send_queue: future::wait::Queue::new(),
-            ipc_message: spin::Mutex::new(None),
-            ipc_reply: spin::Mutex::new(None),
-            ipc_waiting_state: spin::Mutex::new(ipc::message::IpcWaitingState::None),
+            ipc: lock::Mutex::new(kcore::ipc::Endpoint::new()),
+            generation: next_generation(),
+            handles: Arc::new(lock::Mutex::new(ipc::handle::Table::new())),
+            capabilities: Arc::new(lock::Mutex::new(::syscall::capability::Capabilities::ALL)),
+            limits: Arc::new(lock::Mutex::new(user::limit::Limits::default())),
+            essential: Arc::new(AtomicBool::new(false)),
+            syscall: AtomicUsize::new(NO_SYSCALL),
+            locals: future::local::Storage::new(),
+            shared_locals: Arc::new(future::local::Storage::new()),
+        }
+    }
+}
+
+impl LocalDataSet {
+    /// Creates the local data set of a new thread of the same task as this
+    /// one. The handles, the capabilities, the limits, the essential flag and
+    /// the shared task-local values are shared with this set, while the IPC
+    /// state and the other task-local values start empty since they are
+    /// specific to each thread.
+    #[must_use]
+    pub fn sibling(&self) -> Self {
+        Self {
+            ipc_receive_queue: future::wait::Queue::new(),
+            ipc_reply_queue: future::wait::Queue::new(),
+            ipc_send_queue: future::wait::Queue::new(),
+            ipc: lock::Mutex::new(kcore::ipc::Endpoint::new()),
+            generation: next_generation(),
+            handles: Arc::clone(&self.handles),
+            capabilities: Arc::clone(&self.capabilities),
+            limits: Arc::clone(&self.limits),
+            essential: Arc::clone(&self.essential),
+            syscall: AtomicUsize::new(NO_SYSCALL),
+            locals: future::local::Storage::new(),
+            shared_locals: Arc::clone(&self.shared_locals),
         }
     }
 }
@@ -188,10 +496,11 @@ impl Drop for LocalDataSet {
 
 /// Checks if a task with the given identifier exists. It verifies the
 /// existence of the local data set for the task, since the local data set is
-/// created and destroyed along with the task itself.
+/// created and destroyed along with the task itself. A stale identifier whose
+/// index was reused by a later task does not exist, since its generation
+/// differs.
 pub fn exists(id: Identifier) -> bool {
-    let map = TASK_LOCAL_DATA_MAP.read();
-    map.contains_key(&id)
+    TASK_LOCAL_DATA.contains(id)
 }
 
 /// Executes a closure with access to the local data set of the task with
@@ -205,9 +514,19 @@ pub fn try_with_local_set_from<F, R>(id: Identifier, f: F) -> R
 where
     F: FnOnce(Option<&LocalDataSet>) -> R,
 {
-    let map = TASK_LOCAL_DATA_MAP.read();
-    let local_data_set = map.get(&id);
-    f(local_data_set)
+    TASK_LOCAL_DATA.with(id, f)
+}
+
+/// Returns the number of the syscall that the kernel is handling on behalf of
+/// the task with the given identifier, or `None` if the task is not in a
+/// syscall or does not exist. This never blocks, since it is intended to be
+/// called from the panic handler, so it also returns `None` if the local data
+/// set of the task is being added or removed.
+#[must_use]
+pub fn peek_syscall(id: Identifier) -> Option<usize> {
+    let syscall = TASK_LOCAL_DATA
+        .try_with(id, |set| set.map(|set| set.syscall.load(Ordering::Relaxed)))??;
+    (syscall != NO_SYSCALL).then_some(syscall)
 }
 
 /// Executes a closure with access to the local data set of the task with
@@ -221,9 +540,7 @@ pub fn with_local_set_from<F, R>(id: Identifier, f: F) -> R
 where
     F: FnOnce(&LocalDataSet) -> R,
 {
-    let map = TASK_LOCAL_DATA_MAP.read();
-    let local_data_set = map.get(&id).expect("Task local data set not found");
-    f(local_data_set)
+    TASK_LOCAL_DATA.with(id, |set| f(set.expect("Task local data set not found")))
 }
 
 /// Executes a closure with access to the local data set of the currently
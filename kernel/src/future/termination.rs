@@ -0,0 +1,69 @@
+use crate::{
+    config,
+    future::{self, task::Identifier, user::Exit},
+    time::Instant,
+    utils::lock,
+};
+use hashbrown::HashMap;
+use spin::Lazy;
+
+/// The termination information of tasks that were not yet collected, along
+/// with the instant at which each task terminated.
+static TERMINATIONS: Lazy<lock::Mutex<HashMap<Identifier, (Termination, Instant)>>> =
+    Lazy::new(|| lock::Mutex::new(HashMap::new()));
+
+/// How a task terminated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Termination {
+    /// The exit status of the task.
+    pub exit: Exit,
+
+    /// Whether the task terminated while it was still handling IPC requests
+    /// that it had not replied to.
+    pub unclean: bool,
+}
+
+/// A queue where tasks waiting for the termination of another task sleep.
+/// All waiters are woken up each time a task terminates, and must check if
+/// the task they are waiting for is the one that terminated.
+static QUEUE: Lazy<future::wait::Queue> = Lazy::new(future::wait::Queue::new);
+
+/// Records the termination of the given task, and wakes up all tasks waiting
+/// for a termination. The information is kept until it is collected with
+/// [`wait`], or until it expires after [`config::TERMINATION_INFO_LIFETIME`].
+pub fn record(id: Identifier, termination: Termination) {
+    let now = Instant::now();
+    let mut terminations = TERMINATIONS.lock();
+    terminations
+        .retain(|_, (_, instant)| now.duration_since(*instant) < config::TERMINATION_INFO_LIFETIME);
+    terminations.insert(id, (termination, now));
+    drop(terminations);
+    QUEUE.wake_all();
+}
+
+/// Waits until the given task terminates and returns how it terminated. The
+/// termination information is consumed, meaning that only one task can
+/// collect it. Returns `None` if the task does not exist, or if its
+/// termination information was already collected or has expired.
+pub async fn wait(id: Identifier) -> Option<Termination> {
+    loop {
+        if let Some((termination, _)) = TERMINATIONS.lock().remove(&id) {
+            return Some(termination);
+        }
+
+        if !future::group::exists(id) {
+            return None;
+        }
+
+        future::wait::wait(&QUEUE).await;
+    }
+}
+
+/// Waits until the given task terminates and its resources are released,
+/// without consuming its termination information. Returns at once if the task
+/// does not exist.
+pub async fn released(id: Identifier) {
+    while future::group::exists(id) && !TERMINATIONS.lock().contains_key(&id) {
+        future::wait::wait(&QUEUE).await;
+    }
+}
@@ -4,41 +4,169 @@ use crate::{
         self,
         trap::{Resume, Trap},
     },
+    bench,
     config::THREAD_MAX_RUN_DURATION,
-    future,
-    time::Instant,
+    future::{self, task::Identifier},
+    ipc, mm, testing,
+    time::{self, Instant},
+    trace,
+    user::{self, vdso::SharedPage},
 };
+use ::syscall::trace::EventKind;
 
-/// Thread exit status
+/// Task exit status
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Exit {
     /// Normal termination with exit code
     Terminate(i32),
 
-    /// Termination due to a fault
-    Fault,
+    /// Termination requested by the task with the given identifier.
+    Killed(Identifier),
+
+    /// Termination by the out-of-memory handler to free memory (see
+    /// [`crate::mm::oom`]).
+    OutOfMemory,
+
+    /// Termination due to a fault. The address is the faulting address if
+    /// the fault was caused by an invalid memory access.
+    Fault {
+        kind: arch::trap::FaultKind,
+        address: Option<usize>,
+    },
 }
 
 /// The thread execution loop future. This future runs the given thread
-/// until it terminates, either normally or due to a fault.
+/// until it terminates, either normally or due to a fault. When the thread
+/// is the last thread of its task, the resources of the task are released and
+/// the termination of the task is recorded.
 pub async fn thread_loop(mut thread: arch::thread::Thread) {
+    let Some(id) = future::executor::current_task_id() else {
+        log::error!("Thread loop running outside of a task");
+        return;
+    };
+
+    // Each thread has its own shared page, selected by its slot in its task.
+    // Its address is given to the thread in its second argument register,
+    // since the thread has not started yet.
+    let shared = future::group::slot(id).and_then(|slot| SharedPage::map(&mut thread, slot));
+    let code = if let Some(shared) = shared {
+        arch::thread::set_entry_argument(&mut thread, 1, shared.address().as_usize());
+        run(&mut thread, &shared, id).await
+    } else {
+        log::error!("Failed to map the shared page of the thread");
+        -1
+    };
+
+    log::debug!("Thread {} exited with code {}", id, code);
+    let Some((group, exit)) = future::group::leave(id, code) else {
+        return;
+    };
+
+    // This was the last thread of the task: the other threads have dropped
+    // their reference to the address space, and the resources of the task
+    // can be released.
+    log::info!("Task {} terminated with {:?}", group, exit);
+    release_address_space(&mut thread).await;
+    user::irq::release(group);
+    user::device::release(group);
+    user::dma::release(group);
+    user::sync::release(group);
+    user::timer::release(group);
+    mm::pressure::release(group);
+    let in_flight = ipc::message::in_flight();
+    if in_flight > 0 {
+        log::warn!(
+            "Task {} terminated with {} requests in flight",
+            group,
+            in_flight
+        );
+    }
+    future::termination::record(
+        group,
+        future::termination::Termination {
+            exit,
+            unclean: in_flight > 0,
+        },
+    );
+    future::group::dissolve(group);
+    bench::task_terminated(group);
+    testing::task_terminated(group, exit);
+}
+
+/// Executes the given thread until it exits, and returns its exit code. When
+/// the thread exits its task or faults, the whole task is terminated. The
+/// thread also stops as soon as its task is terminated by another thread.
+async fn run(thread: &mut arch::thread::Thread, shared: &SharedPage, id: Identifier) -> i32 {
     let mut poll_generation = future::executor::poll_generation();
     let mut deadline = Instant::now() + THREAD_MAX_RUN_DURATION;
 
-    let exit = loop {
-        // Set the next timer event
-        arch::timer::next_event(Instant::now().duration_until(deadline));
+    loop {
+        // Another thread of the task may have terminated it while this
+        // thread was running on another core or waiting for a syscall.
+        if future::group::terminating(id) {
+            return -1;
+        }
+
+        // A debugger may have suspended the task. The thread then stops here,
+        // where its registers are not in use, until it is resumed, and gets
+        // a full quantum afterwards.
+        if user::debug::must_stop(id) {
+            if user::debug::stop(thread, id).await.is_none() {
+                return -1;
+            }
+            poll_generation = future::executor::poll_generation();
+            deadline = Instant::now() + THREAD_MAX_RUN_DURATION;
+        }
+
+        // Deliver the expired timers, then set the next timer event at the
+        // end of the quantum or at the next deadline of the timer wheel, and
+        // publish the deadline of the current quantum to the thread, allowing
+        // it to yield voluntarily before being preempted.
+        time::wheel::poll();
+        let quantum = Instant::now().duration_until(deadline);
+        arch::timer::next_event(
+            time::wheel::until_next().map_or(quantum, |next| next.min(quantum)),
+        );
+        shared.set_quantum_deadline(deadline);
 
         // Execute the thread until it traps, and measure the elapsed time
         // to update the remaining quantum of continuous user execution.
-        let trap = arch::thread::execute(&mut thread);
+        bench::switch_in();
+        let trap = arch::thread::execute(thread);
 
         // Handle the trap and determine whether to continue executing
-        // the thread or terminate it.
+        // the thread or terminate it. A syscall waiting for an event is
+        // abandoned if the task is killed in the meantime.
         let mut resume = match trap {
-            Trap::Exception => arch::trap::handle_exception(&mut thread),
-            Trap::Interrupt => arch::trap::handle_interrupt(&mut thread),
-            Trap::Syscall => arch::trap::handle_syscall(&mut thread).await,
+            Trap::Exception => {
+                let resume = arch::trap::handle_exception(thread);
+                if trace::enabled() && arch::trap::fault_kind() == arch::trap::FaultKind::PageFault
+                {
+                    let address = arch::trap::fault_address().unwrap_or(0);
+                    let resolved = u64::from(resume != Resume::Fault);
+                    trace::record(EventKind::PageFault, [address as u64, resolved]);
+                }
+                resume
+            }
+            Trap::Interrupt => arch::trap::handle_interrupt(thread),
+            Trap::Step => {
+                user::debug::stepped(thread, id);
+                Resume::Continue
+            }
+            Trap::Syscall => {
+                let start = Instant::now();
+                let syscall = arch::trap::handle_syscall(thread);
+                let Some(resume) = future::group::interruptible(id, syscall).await else {
+                    return -1;
+                };
+
+                // Only syscalls that did not wait for anything measure the
+                // cost of entering and leaving the kernel.
+                if !future::executor::has_yielded(&poll_generation) {
+                    bench::record_syscall(start.elapsed());
+                }
+                resume
+            }
         };
 
         if future::executor::has_yielded(&poll_generation) {
@@ -56,20 +184,76 @@ pub async fn thread_loop(mut thread: arch::thread::Thread) {
         }
 
         match resume {
-            Resume::Terminate(code) => break Exit::Terminate(code),
+            Resume::Terminate(code) => {
+                future::group::terminate(id, Exit::Terminate(code));
+                return code;
+            }
+            Resume::ExitThread(code) => return code,
             Resume::Yield => {
                 // Reset the quantum and yield to the scheduler. We reset
                 // the quantum because the thread voluntarily yielded, so
                 // we reset its continuous execution time in order to give
                 // it a full quantum when it is rescheduled.
+                bench::switch_out();
                 yield_once().await;
                 poll_generation = future::executor::poll_generation();
                 deadline = Instant::now() + THREAD_MAX_RUN_DURATION;
             }
-            Resume::Fault => break Exit::Fault,
+            Resume::Fault => {
+                diagnose_fault(shared);
+                let exit = Exit::Fault {
+                    kind: arch::trap::fault_kind(),
+                    address: arch::trap::fault_address(),
+                };
+                future::group::terminate(id, exit);
+                return -1;
+            }
             Resume::Continue => (),
         }
+    }
+}
+
+/// Releases the user address space of a terminated task, returning all its
+/// frames and page tables to the physical memory allocator. A large address
+/// space can take a long time to release, so this is done incrementally to
+/// avoid starving other tasks, yielding to the executor whenever the work
+/// budget is exhausted.
+async fn release_address_space(thread: &mut arch::thread::Thread) {
+    let mut budget = future::budget::Budget::new();
+    let mut released = 0;
+    for part in 0..arch::mmu::USER_SPACE_PARTS {
+        // SAFETY: All the threads of the task have terminated and will never be
+        // executed again, so its user space is no longer in use.
+        released += unsafe { thread.address_space().release_part(part) };
+        budget.checkpoint().await;
+    }
+
+    log::debug!(
+        "Released {} frames of the address space ({} frames still allocated)",
+        released,
+        mm::phys::allocated_memory_pages()
+    );
+}
+
+/// The size of the area below the stack of a green thread where a fault is
+/// considered as a stack overflow.
+const STACK_OVERFLOW_WINDOW: usize = 4 * arch::mmu::PAGE_SIZE;
+
+/// Try to give a more precise diagnostic about the last fault of a thread. If
+/// the thread has published the extent of its current stack in its shared
+/// page (green threads), a fault just below the stack is reported as a stack
+/// overflow.
+fn diagnose_fault(shared: &SharedPage) {
+    let (Some(address), Some(stack)) = (arch::trap::fault_address(), shared.stack_extent()) else {
+        return;
     };
 
-    log::info!("Thread terminated with {:?}", exit);
+    if address < stack.start && address >= stack.start.saturating_sub(STACK_OVERFLOW_WINDOW) {
+        log::error!(
+            "Stack overflow in green thread (stack: {:#x} - {:#x}, address: {:#x})",
+            stack.start,
+            stack.end,
+            address
+        );
+    }
 }
@@ -1,15 +1,27 @@
-use alloc::sync::Arc;
+use crate::{
+    future::{self, task::Identifier},
+    utils::lock,
+};
+use alloc::{collections::VecDeque, sync::Arc, vec::Vec};
 use core::{
     pin::Pin,
     sync::atomic::{AtomicBool, Ordering},
     task::{Context, Poll, Waker},
 };
-use crossbeam::queue::SegQueue;
+
+/// A waker registered in a wait queue, along with the identifier of the task
+/// it belongs to, if any. The identifier allows a specific task to be woken
+/// up without waking up the other tasks waiting on the same queue.
+#[derive(Debug)]
+struct Waiter {
+    task: Option<Identifier>,
+    waker: Waker,
+}
 
 /// A wait queue that can hold wakers to be woken up later.
 #[derive(Default, Debug, Clone)]
 pub struct Queue {
-    waiting: Arc<SegQueue<Waker>>,
+    waiting: Arc<lock::Mutex<VecDeque<Waiter>>>,
     poisoned: Arc<AtomicBool>,
 }
 
@@ -18,21 +30,47 @@ impl Queue {
     #[must_use]
     pub fn new() -> Self {
         Self {
-            waiting: Arc::new(SegQueue::new()),
+            waiting: Arc::new(lock::Mutex::new(VecDeque::new())),
             poisoned: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Returns whether no waker is waiting in the queue.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.waiting.lock().is_empty()
+    }
+
     /// Wake one waiting waker, if any.
     pub fn wake_one(&self) {
-        if let Some(waker) = self.waiting.pop() {
-            waker.wake();
+        let waiter = self.waiting.lock().pop_front();
+        if let Some(waiter) = waiter {
+            waiter.waker.wake();
         }
     }
 
     /// Wake all waiting wakers, emptying the queue.
     pub fn wake_all(&self) {
-        while let Some(waker) = self.waiting.pop() {
+        let waiting = core::mem::take(&mut *self.waiting.lock());
+        for waiter in waiting {
+            waiter.waker.wake();
+        }
+    }
+
+    /// Wake all the wakers registered by the given task, leaving the wakers of
+    /// other tasks in the queue. This avoids waking up all the tasks waiting
+    /// on the queue when only one of them can make progress.
+    pub fn wake_task(&self, task: Identifier) {
+        let mut woken = Vec::new();
+        self.waiting.lock().retain_mut(|waiter| {
+            if waiter.task == Some(task) {
+                woken.push(waiter.waker.clone());
+                false
+            } else {
+                true
+            }
+        });
+        for waker in woken {
             waker.wake();
         }
     }
@@ -82,7 +120,10 @@ impl Future for WaitFuture<'_> {
         if self.polled {
             return Poll::Ready(());
         }
-        self.queue.waiting.push(context.waker().clone());
+        self.queue.waiting.lock().push_back(Waiter {
+            task: future::executor::current_task_id(),
+            waker: context.waker().clone(),
+        });
 
         // Check if the queue was poisoned after registering the waker to
         // ensure that we will not miss a wake-up and prevent getting stuck
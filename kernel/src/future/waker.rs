@@ -1,32 +1,66 @@
-use super::task::{self};
+use super::{
+    executor::Wakeups,
+    task::{self, Affinity},
+};
 use alloc::sync::Arc;
-use crossbeam::queue::ArrayQueue;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The raw value of the affinity of a waker when the task can run on any CPU
+/// core.
+const ANY_CPU: usize = usize::MAX;
 
 /// A waker that can wake up a task.
 #[derive(Debug)]
 pub struct Waker {
-    /// The queue to push the task identifier to when waking
-    /// up the task.
-    queue: Arc<ArrayQueue<task::Identifier>>,
+    /// The queues to push the task identifier to when waking up the task.
+    wakeups: Arc<Wakeups>,
+
+    /// The CPU core on which the task prefers to run, or [`ANY_CPU`]. It is
+    /// stored in the waker, rather than in the task, so that it can be read
+    /// without locking the tasks of the executor when the task is woken up.
+    affinity: AtomicUsize,
 
     /// The identifier of the task to wake up.
     pub id: task::Identifier,
 }
 
 impl Waker {
-    /// Create a new waker.
+    /// Create a new waker for a task that can run on any CPU core.
     #[must_use]
-    pub fn new(queue: Arc<ArrayQueue<task::Identifier>>, id: task::Identifier) -> Self {
-        Waker { queue, id }
+    pub fn new(wakeups: Arc<Wakeups>, id: task::Identifier) -> Self {
+        Waker {
+            wakeups,
+            affinity: AtomicUsize::new(ANY_CPU),
+            id,
+        }
+    }
+
+    /// Returns the affinity of the task.
+    #[must_use]
+    pub fn affinity(&self) -> Affinity {
+        match self.affinity.load(Ordering::Relaxed) {
+            ANY_CPU => Affinity::Any,
+            cpu => Affinity::Prefer(cpu),
+        }
+    }
+
+    /// Sets the affinity of the task. It is taken into account the next time
+    /// the task is woken up.
+    pub fn set_affinity(&self, affinity: Affinity) {
+        let raw = match affinity {
+            Affinity::Any => ANY_CPU,
+            Affinity::Prefer(cpu) => cpu,
+        };
+        self.affinity.store(raw, Ordering::Relaxed);
     }
 }
 
 impl alloc::task::Wake for Waker {
     fn wake(self: Arc<Self>) {
-        self.queue.push(self.id).expect("Queue is full");
+        self.wake_by_ref();
     }
 
     fn wake_by_ref(self: &Arc<Self>) {
-        self.queue.push(self.id).expect("Queue is full");
+        self.wakeups.push(self.id, self.affinity());
     }
 }
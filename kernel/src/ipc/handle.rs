@@ -0,0 +1,95 @@
+use crate::{config, ipc};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// A handle to a kernel object held by a task. Handles are local to each task
+/// and are simple indexes into the handle table of the task, meaning that a
+/// handle has no meaning outside of the task that owns it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Handle(usize);
+
+impl From<usize> for Handle {
+    fn from(handle: usize) -> Self {
+        Self(handle)
+    }
+}
+
+impl From<Handle> for usize {
+    fn from(handle: Handle) -> usize {
+        handle.0
+    }
+}
+
+/// A connection to a service. A task can only send messages to a service if
+/// it holds a connection to it, which can only be obtained by connecting to
+/// the service by its name.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Connection {
+    /// The registration of the service at the time of the connection. If the
+    /// service is unregistered, the connection becomes unusable.
+    pub service: ipc::service::Registration,
+
+    /// The badge of the connection, attached by the kernel to every message
+    /// sent through it. Each connection has a different badge that is never
+    /// reused, so that a service can tell its clients apart even if they are
+    /// not identified by their task.
+    pub badge: u64,
+}
+
+impl Connection {
+    /// Creates a new connection to the given service, with a new badge.
+    #[must_use]
+    pub fn new(service: ipc::service::Registration) -> Self {
+        // Badges start at 1, so that 0 never identifies a connection.
+        static NEXT_BADGE: AtomicU64 = AtomicU64::new(1);
+        Self {
+            service,
+            badge: NEXT_BADGE.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+}
+
+/// The table of handles owned by a task. Freed slots are reused when a new
+/// handle is inserted to keep the table as small as possible, and the table
+/// never holds more than [`config::MAX_CONNECTIONS_PER_TASK`] connections.
+#[derive(Debug, Default)]
+pub struct Table {
+    connections: Vec<Option<Connection>>,
+}
+
+impl Table {
+    /// Creates a new empty handle table.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            connections: Vec::new(),
+        }
+    }
+
+    /// Inserts a connection into the table and returns the handle that can
+    /// be used to refer to it later, or `None` if the table is full.
+    pub fn insert(&mut self, connection: Connection) -> Option<Handle> {
+        if let Some(index) = self.connections.iter().position(Option::is_none) {
+            self.connections[index] = Some(connection);
+            Some(Handle(index))
+        } else if self.connections.len() < config::MAX_CONNECTIONS_PER_TASK {
+            self.connections.push(Some(connection));
+            Some(Handle(self.connections.len() - 1))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the connection associated with the given handle, or `None` if
+    /// the handle is not valid.
+    #[must_use]
+    pub fn get(&self, handle: Handle) -> Option<Connection> {
+        self.connections.get(handle.0).copied().flatten()
+    }
+
+    /// Removes the connection associated with the given handle from the
+    /// table and returns it, or `None` if the handle is not valid.
+    pub fn remove(&mut self, handle: Handle) -> Option<Connection> {
+        self.connections.get_mut(handle.0).and_then(Option::take)
+    }
+}
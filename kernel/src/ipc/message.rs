@@ -1,6 +1,16 @@
-use alloc::boxed::Box;
-
-use crate::future::{self};
+use crate::{
+    bench,
+    future::{self},
+    ipc,
+    mm::slab::{ObjectCache, SlabBox},
+    stats, time, trace,
+};
+use ::syscall::trace::EventKind;
+
+/// The cache from which IPC messages and replies are allocated. Messages are
+/// allocated and freed for every IPC exchange, so they are allocated from a
+/// dedicated cache instead of the general purpose heap.
+static MESSAGE_CACHE: ObjectCache<Message> = ObjectCache::new();
 
 /// Represents a message sent between tasks.
 #[derive(Debug, Clone)]
@@ -22,6 +32,10 @@ pub struct Message {
     /// receiver.
     pub payload_len: usize,
 
+    /// Information about the sender verified by the kernel, given to the
+    /// receiver along with the message.
+    pub metadata: Metadata,
+
     /// The payload of the message. This is a fixed-size array to ensure
     /// that messages have a consistent size. If the actual payload is smaller
     /// than `MAX_PAYLOAD_SIZE`, the remaining bytes should be considered
@@ -36,38 +50,57 @@ impl Message {
     pub const MAX_PAYLOAD_SIZE: usize = 256;
 }
 
-/// Represents the IPC waiting state of a task. This enum defines the
-/// different states a task can be when waiting for IPC operations.
-#[derive(Debug)]
-pub enum IpcWaitingState {
-    /// The task does not wait for anything.
-    None,
-
-    /// The task is waiting to send a message.
-    WaitingForSend,
-
-    /// The task is waiting to receive a message.
-    WaitingForMessage,
-
-    /// The task is waiting for a reply to a previously sent message by
-    /// the specified task identifier.
-    WaitingForReply(future::task::Identifier),
+/// Information about the sender of a message, filled in by the kernel when the
+/// message is sent. Contrary to the payload, the receiver can trust it. Zero
+/// means that the information is not available, since badges, generations and
+/// registration serials all start at 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// The badge of the connection the message was sent through (see
+    /// [`ipc::handle::Connection::badge`]).
+    pub badge: u64,
+
+    /// The generation of the sender (see
+    /// [`future::task::LocalDataSet::generation`]).
+    pub generation: u64,
+
+    /// The serial of the registration of the service provided by the sender,
+    /// if any.
+    pub service: u64,
+
+    /// Whether the sender does not wait for a reply, in which case the
+    /// receiver must not reply to the message. This is the case of the
+    /// messages sent with [`send_oneway`] and of those sent by the kernel.
+    pub oneway: bool,
 }
 
-impl IpcWaitingState {
-    /// Sets the IPC state to `WaitingForReply`.
-    pub fn set_waiting_for_reply(&mut self, from: future::task::Identifier) {
-        *self = IpcWaitingState::WaitingForReply(from);
+impl Metadata {
+    /// Returns the metadata of a message sent by the given task through the
+    /// given connection. The task must exist.
+    async fn new(sender: future::task::Identifier, connection: ipc::handle::Connection) -> Self {
+        let generation = future::task::try_with_local_set_from(sender, |set| {
+            set.map_or(0, |local_set| local_set.generation)
+        });
+        Self {
+            badge: connection.badge,
+            generation,
+            service: ipc::service::registration_of(sender)
+                .await
+                .map_or(0, |r| r.serial()),
+            oneway: false,
+        }
     }
+}
+
+impl kcore::ipc::Envelope for SlabBox<Message> {
+    type Id = future::task::Identifier;
 
-    /// Sets the IPC state to `WaitingForMessage`
-    pub fn set_waiting_for_message(&mut self) {
-        *self = IpcWaitingState::WaitingForMessage;
+    fn sender(&self) -> Self::Id {
+        self.sender
     }
 
-    /// Sets the IPC state to `WaitingForSend`.
-    pub fn set_waiting_for_send(&mut self) {
-        *self = IpcWaitingState::WaitingForSend;
+    fn oneway(&self) -> bool {
+        self.metadata.oneway
     }
 }
 
@@ -82,6 +115,18 @@ pub enum SendError {
 
     /// The target task has been destroyed before the message could be sent.
     TaskDestroyed,
+
+    /// The target service has been unregistered before the message could
+    /// be sent.
+    ServiceUnregistered,
+
+    /// The target service terminated after the message was delivered but
+    /// before replying to it.
+    ServiceDied,
+
+    /// The mailbox of the target service is full, and the message was
+    /// dropped. This is only returned by [`send_oneway`].
+    MailboxFull,
 }
 
 /// Represents errors that can occur when replying to a message.
@@ -103,8 +148,53 @@ pub enum ReplyError {
     TaskDestroyed,
 }
 
-/// Sends a message from one process to another and waits until a reply is
-/// received.
+/// Represents errors that can occur when forwarding a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardError {
+    /// The payload size exceeds the maximum allowed size.
+    PayloadTooLarge,
+
+    /// The client is not waiting for a reply from the current task.
+    NotWaitingForReply,
+
+    /// The target task does not exist.
+    TaskDoesNotExist,
+
+    /// The target task has been destroyed before the message could be
+    /// forwarded.
+    TaskDestroyed,
+
+    /// The target service has been unregistered before the message could be
+    /// forwarded.
+    ServiceUnregistered,
+}
+
+impl From<kcore::ipc::ReplyError> for ReplyError {
+    fn from(error: kcore::ipc::ReplyError) -> Self {
+        match error {
+            kcore::ipc::ReplyError::NotWaitingForReply => ReplyError::NotWaitingForReply,
+            kcore::ipc::ReplyError::UnexpectedSender => ReplyError::UnexpectedSender,
+        }
+    }
+}
+
+impl From<SendError> for ForwardError {
+    fn from(error: SendError) -> Self {
+        match error {
+            SendError::PayloadTooLarge => ForwardError::PayloadTooLarge,
+            SendError::TaskDoesNotExist => ForwardError::TaskDoesNotExist,
+            SendError::TaskDestroyed | SendError::ServiceDied | SendError::MailboxFull => {
+                ForwardError::TaskDestroyed
+            }
+            SendError::ServiceUnregistered => ForwardError::ServiceUnregistered,
+        }
+    }
+}
+
+/// Sends a message through a connection to a service and waits until a reply
+/// is received. The message carries the badge of the connection along with
+/// the [`Metadata`] of the current task. If the service collects statistics,
+/// the latency of the message is recorded in [`ipc::stats`].
 ///
 /// # Errors
 /// Returns a [`SendError`] if the message could not be sent or if the reply
@@ -116,10 +206,12 @@ pub enum ReplyError {
 /// function is called during kernel initialization, before any tasks have been
 /// created, and is a serious programming error.
 pub async fn send(
-    to: future::task::Identifier,
+    connection: ipc::handle::Connection,
     operation: usize,
     payload: &[u8],
-) -> Result<Box<Message>, SendError> {
+) -> Result<SlabBox<Message>, SendError> {
+    let service = connection.service;
+    let to = service.task;
     if payload.len() > Message::MAX_PAYLOAD_SIZE {
         return Err(SendError::PayloadTooLarge);
     }
@@ -130,12 +222,14 @@ pub async fn send(
     }
 
     // Create the message to be sent
+    let start = time::Instant::now();
     let from = future::executor::current_task_id().unwrap();
-    let message = Box::new(Message {
+    let message = MESSAGE_CACHE.allocate(Message {
         sender: from,
         receiver: to,
         operation,
         payload_len: payload.len(),
+        metadata: Metadata::new(from, connection).await,
         payload: {
             let mut buf = [0; Message::MAX_PAYLOAD_SIZE];
             buf[..payload.len()].copy_from_slice(payload);
@@ -143,136 +237,289 @@ pub async fn send(
         },
     });
 
-    // Send the message by changing the IPC state of the receiver process
-    // if it is waiting for messages. Otherwise, we change our own state to
-    // waiting for a reply, and wait until the receiver is ready to process it.
-    loop {
-        let send_queue = future::task::try_with_local_set_from(to, |set| {
-            if let Some(receiver_local_set) = set {
-                match &*receiver_local_set.ipc_waiting_state.lock() {
-                    IpcWaitingState::WaitingForMessage => {
-                        // The receiver is waiting for messages. Due to
-                        // borrowing rules, we cannot set the message directly
-                        // here since the compiler does not know that this
-                        // will be the last iteration before we break out of
-                        // the loop, and throws a borrow error. So we return
-                        // None to indicate that we can proceed to deliver
-                        // the message.
-                        Ok(None)
-                    }
-                    _ => Ok(Some(receiver_local_set.ipc_send_queue.clone())),
-                }
-            } else {
-                // The target task has been destroyed before we could
-                // send the message. Return an error to the caller.
-                Err(SendError::TaskDestroyed)
-            }
-        })?;
-
-        if let Some(queue) = send_queue {
-            // The receiver was not waiting for messages. We need to wait
-            // until it is ready to receive our message. Set our IPC state
-            // to waiting for send and wait on the associated queue.
-            future::task::with_current_local_set(|current_local_set| {
-                current_local_set
-                    .ipc_waiting_state
-                    .lock()
-                    .set_waiting_for_send();
-            });
-            future::wait::wait(&queue).await;
-        } else {
-            future::task::try_with_local_set_from(to, |set| {
-                if let Some(receiver_local_set) = set {
-                    // Wake up the receiver since it is waiting for messages,
-                    // and deliver the message to the receiver's local data
-                    // set.
-                    receiver_local_set.ipc_message.lock().replace(message);
-                    receiver_local_set.ipc_receive_queue.wake_one();
-                    Ok(())
-                } else {
-                    // The target task has been destroyed before we could
-                    // send the message. Return an error to the caller.
-                    Err(SendError::TaskDestroyed)
-                }
-            })?;
-            break;
-        }
-    }
+    // Deliver the message, waiting for room in the mailbox of the service
+    // if needed.
+    deliver(service, message).await?;
 
     // Now that the message has been sent, wait for the reply. Set our IPC
-    // state to waiting for reply and wait on the associated queue.
+    // state to waiting for reply and wait on the queue of the task that must
+    // reply. The service may forward the message to another task, which then
+    // replies in its place (see [`forward`]): the task that must reply is
+    // therefore read from our state on each iteration.
+    future::task::with_current_local_set(|current_local_set| {
+        current_local_set.ipc.lock().wait_for_reply(to);
+    });
     loop {
         let reply = future::task::with_current_local_set(|current_local_set| {
-            if let Some(reply) = current_local_set.ipc_reply.lock().take() {
-                // A reply has been received. Return it.
-                Ok(Some(reply))
-            } else if !future::task::exists(to) {
-                // The target task has been destroyed before sending
-                // the reply. Return an error to the caller.
-                Err(SendError::TaskDestroyed)
+            let replier = {
+                let mut endpoint = current_local_set.ipc.lock();
+                if let Some(reply) = endpoint.take_reply() {
+                    // A reply has been received. Return it.
+                    return Ok(Ok(reply));
+                }
+                endpoint.replier().unwrap_or(to)
+            };
+            if future::task::exists(replier) {
+                // No reply yet. Return the task that must reply, to wait on
+                // its reply queue and be woken up when the reply arrives.
+                Ok(Err(replier))
             } else {
-                // No reply yet. Set the state to waiting for reply from the
-                // receiver process, and return the associated reply queue
-                // to wait on and be woken up when the reply arrives.
-                current_local_set
-                    .ipc_waiting_state
-                    .lock()
-                    .set_waiting_for_reply(to);
-                Ok(None)
+                // The service has terminated while our message was in
+                // flight. Return an error to the caller.
+                Err(SendError::ServiceDied)
             }
         })?;
 
-        if let Some(reply) = reply {
-            break Ok(reply);
-        }
+        let replier = match reply {
+            Ok(reply) => {
+                if ipc::service::collects_statistics(to).await {
+                    ipc::stats::record(to, operation, start.elapsed());
+                }
+                bench::record_ipc_round_trip(start.elapsed());
+                break Ok(reply);
+            }
+            Err(replier) => replier,
+        };
 
         // We are still waiting for the reply. Sleep and wait to be woken up
-        // when the reply arrives, or when the target task is destroyed.
-        let queue = future::task::try_with_local_set_from(to, |receiver_local_set| {
-            if let Some(set) = receiver_local_set {
+        // when the reply arrives, when the message is forwarded, or when the
+        // task that must reply is destroyed.
+        let queue = future::task::try_with_local_set_from(replier, |replier_local_set| {
+            if let Some(set) = replier_local_set {
                 Ok(set.ipc_reply_queue.clone())
             } else {
-                // The target task has been destroyed before sending
-                // the reply. Return an error to the caller.
-                Err(SendError::TaskDestroyed)
+                // The service has terminated while our message was in
+                // flight. Return an error to the caller.
+                Err(SendError::ServiceDied)
             }
         })?;
         future::wait::wait(&queue).await;
     }
 }
 
-/// Receives a message for the specified receiver process. The function is
-/// asynchronous and yields control while waiting for a message to arrive.
+/// Delivers a message into the mailbox of the given service. If the mailbox is
+/// full, waits until the receiver takes a message from it and tries again.
+/// This provides backpressure to the senders when the receiver cannot keep up
+/// with the rate of incoming messages.
+///
+/// # Errors
+/// Returns a [`SendError`] if the service was unregistered or if its task was
+/// destroyed before the message could be delivered.
+async fn deliver(
+    service: ipc::service::Registration,
+    message: SlabBox<Message>,
+) -> Result<(), SendError> {
+    let mut message = Some(message);
+    loop {
+        let Some(queue) = enqueue(service, &mut message).await? else {
+            return Ok(());
+        };
+
+        // The mailbox of the receiver is full. Set our IPC state to waiting
+        // for send and wait until a slot is freed in the mailbox.
+        future::task::with_current_local_set(|current_local_set| {
+            current_local_set.ipc.lock().wait_for_send();
+        });
+        future::wait::wait(&queue).await;
+    }
+}
+
+/// Tries to put a message into the mailbox of the given service. On success,
+/// the message is taken out of `message` and `None` is returned. If the
+/// mailbox is full, the message is left untouched and the queue on which the
+/// senders wait for room in the mailbox is returned.
+///
+/// # Errors
+/// Returns a [`SendError`] if the service was unregistered or if its task was
+/// destroyed.
+async fn enqueue(
+    service: ipc::service::Registration,
+    message: &mut Option<SlabBox<Message>>,
+) -> Result<Option<future::wait::Queue>, SendError> {
+    // Check that the service is still registered. This must be checked on each
+    // attempt since the service may have been unregistered while the sender
+    // was waiting to deliver the message.
+    if !ipc::service::is_active(service).await {
+        return Err(SendError::ServiceUnregistered);
+    }
+
+    let to = service.task;
+    let operation = message.as_ref().map_or(0, |message| message.operation);
+    let send_queue = future::task::try_with_local_set_from(to, |set| {
+        if let Some(receiver_local_set) = set {
+            let mut endpoint = receiver_local_set.ipc.lock();
+            let capacity = receiver_local_set.limits.lock().messages();
+            if let Some(Err(rejected)) = message.take().map(|m| endpoint.deliver(m, capacity)) {
+                *message = Some(rejected);
+                Ok(Some(receiver_local_set.ipc_send_queue.clone()))
+            } else {
+                // There was room in the mailbox: the message has been
+                // delivered, so wake up the receiver if it is waiting for
+                // messages.
+                receiver_local_set.ipc_receive_queue.wake_one();
+                Ok(None)
+            }
+        } else {
+            // The target task has been destroyed before we could
+            // send the message. Return an error to the caller.
+            Err(SendError::TaskDestroyed)
+        }
+    })?;
+
+    if send_queue.is_none() {
+        trace::record(
+            EventKind::IpcSend,
+            [usize::from(to) as u64, operation as u64],
+        );
+        stats::count_ipc_message();
+    }
+    Ok(send_queue)
+}
+
+/// Sends a message through a connection to a service without waiting for a
+/// reply. The message is marked as one-way in its [`Metadata`], so that the
+/// service does not reply to it. This suits notifications for which the
+/// sender does not need an answer, such as log messages.
+///
+/// Unlike [`send`], this never waits: if the mailbox of the service is full,
+/// the message is dropped and [`SendError::MailboxFull`] is returned. The
+/// sender is therefore never blocked by a slow service, and it is up to the
+/// sender to retry later, to wait with [`send`] instead, or to give up.
+///
+/// # Errors
+/// Returns a [`SendError`] if the message could not be delivered.
 ///
 /// # Panics
 /// Panics if there is no current task context. This can only happen if this
 /// function is called during kernel initialization, before any tasks have been
 /// created, and is a serious programming error.
-pub async fn receive() -> Box<Message> {
-    loop {
-        // Check if there is a message for the receiver.
-        let message = future::task::with_current_local_set(|current_local_set| {
-            current_local_set.ipc_message.lock().take()
+pub async fn send_oneway(
+    connection: ipc::handle::Connection,
+    operation: usize,
+    payload: &[u8],
+) -> Result<(), SendError> {
+    let service = connection.service;
+    let to = service.task;
+    if payload.len() > Message::MAX_PAYLOAD_SIZE {
+        return Err(SendError::PayloadTooLarge);
+    }
+
+    if !future::task::exists(to) {
+        return Err(SendError::TaskDoesNotExist);
+    }
+
+    let from = future::executor::current_task_id().unwrap();
+    let mut message = Some(MESSAGE_CACHE.allocate(Message {
+        sender: from,
+        receiver: to,
+        operation,
+        payload_len: payload.len(),
+        metadata: Metadata {
+            oneway: true,
+            ..Metadata::new(from, connection).await
+        },
+        payload: {
+            let mut buf = [0; Message::MAX_PAYLOAD_SIZE];
+            buf[..payload.len()].copy_from_slice(payload);
+            buf
+        },
+    }));
+
+    match enqueue(service, &mut message).await? {
+        None => Ok(()),
+        Some(_) => Err(SendError::MailboxFull),
+    }
+}
+
+/// Delivers a message sent by the kernel into the mailbox of the given task,
+/// with [`future::task::Identifier::KERNEL`] as sender. The receiver must not
+/// reply to it. Unlike messages sent by tasks, this never waits: returns
+/// whether the message was delivered, which is not the case if the task does
+/// not exist or if its mailbox is full.
+#[must_use]
+pub fn notify(to: future::task::Identifier, operation: usize, payload: &[u8]) -> bool {
+    let len = payload.len().min(Message::MAX_PAYLOAD_SIZE);
+    future::task::try_with_local_set_from(to, |set| {
+        let Some(receiver_local_set) = set else {
+            return false;
+        };
+        let mut endpoint = receiver_local_set.ipc.lock();
+        let capacity = receiver_local_set.limits.lock().messages();
+        if endpoint.is_full(capacity) {
+            return false;
+        }
+
+        let message = MESSAGE_CACHE.allocate(Message {
+            sender: future::task::Identifier::KERNEL,
+            receiver: to,
+            operation,
+            payload_len: len,
+            metadata: Metadata {
+                oneway: true,
+                ..Metadata::default()
+            },
+            payload: {
+                let mut buf = [0; Message::MAX_PAYLOAD_SIZE];
+                buf[..len].copy_from_slice(&payload[..len]);
+                buf
+            },
         });
+        let delivered = endpoint.deliver(message, capacity).is_ok();
+        receiver_local_set.ipc_receive_queue.wake_one();
+        delivered
+    })
+}
 
-        // Yes, a message is available. Return it.
-        if let Some(message) = message {
+/// Receives the oldest message in the mailbox of the current task. The function
+/// is asynchronous and yields control while waiting for a message to arrive.
+///
+/// # Panics
+/// Panics if there is no current task context. This can only happen if this
+/// function is called during kernel initialization, before any tasks have been
+/// created, and is a serious programming error.
+pub async fn receive() -> SlabBox<Message> {
+    loop {
+        if let Some(message) = try_receive() {
             break message;
         }
 
         // No message available yet. Change the IPC state to indicate that we
-        // are waiting for a message, wake up any senders waiting to send us
-        // messages, and wait on our receive queue to be woken up when a
-        // message arrives.
+        // are waiting for a message, and wait on our receive queue to be woken
+        // up when a message arrives.
         let queue = future::task::with_current_local_set(|local_set| {
-            local_set.ipc_waiting_state.lock().set_waiting_for_message();
-            local_set.ipc_send_queue.wake_all();
+            local_set.ipc.lock().wait_for_message();
             local_set.ipc_receive_queue.clone()
         });
         future::wait::wait(&queue).await;
     }
 }
 
+/// Receives the oldest message in the mailbox of the current task, or returns
+/// `None` at once if the mailbox is empty.
+///
+/// # Panics
+/// Panics if there is no current task context. This can only happen if this
+/// function is called during kernel initialization, before any tasks have been
+/// created, and is a serious programming error.
+#[must_use]
+pub fn try_receive() -> Option<SlabBox<Message>> {
+    // Take the oldest message from our mailbox, if any. Since this frees a
+    // slot in the mailbox, wake up one of the senders waiting for room in the
+    // mailbox, and remember that we must reply to the sender of the message
+    // unless it does not wait for a reply.
+    let message = future::task::with_current_local_set(|current_local_set| {
+        let message = current_local_set.ipc.lock().receive()?;
+        current_local_set.ipc_send_queue.wake_one();
+        Some(message)
+    })?;
+
+    trace::record(
+        EventKind::IpcReceive,
+        [usize::from(message.sender) as u64, message.operation as u64],
+    );
+    Some(message)
+}
+
 /// Sends a reply message from one process to another.
 ///
 /// # Errors
@@ -297,11 +544,12 @@ pub fn reply(
 
     // Create the reply message
     let from = future::executor::current_task_id().unwrap();
-    let message = Box::new(Message {
+    let message = MESSAGE_CACHE.allocate(Message {
         sender: from,
         receiver: to,
         operation: status,
         payload_len: payload.len(),
+        metadata: Metadata::default(),
         payload: {
             let mut buf = [0; Message::MAX_PAYLOAD_SIZE];
             buf[..payload.len()].copy_from_slice(payload);
@@ -309,21 +557,12 @@ pub fn reply(
         },
     });
 
-    // Check if the receiver is waiting for a reply by checking its IPC state,
-    // and ensure that it is waiting for a reply from the correct sender. If
-    // so, deliver the reply message and wake up the receiver.
+    // Deliver the reply if the receiver is waiting for a reply from us. The
+    // receiver stops waiting for a reply as soon as it is accepted, so that a
+    // second reply cannot be returned to its next message.
     future::task::try_with_local_set_from(to, |set| {
         if let Some(receiver_local_set) = set {
-            match *receiver_local_set.ipc_waiting_state.lock() {
-                IpcWaitingState::WaitingForReply(expected_from) => {
-                    if expected_from != from {
-                        return Err(ReplyError::UnexpectedSender);
-                    }
-                    receiver_local_set.ipc_reply.lock().replace(message);
-                    Ok(())
-                }
-                _ => Err(ReplyError::NotWaitingForReply),
-            }
+            Ok(receiver_local_set.ipc.lock().accept_reply(message)?)
         } else {
             // The target task has been destroyed before we could
             // send the reply. Return an error to the caller.
@@ -331,13 +570,116 @@ pub fn reply(
         }
     })?;
 
-    // Since we can't know which task will be woken up (in case of multiple
-    // tasks waiting for a reply), we wake them all up. This can happen if
-    // the task handle multiple IPC receives before replying to any of them.
-    // TODO: Only wake up the task that we replied to.
+    // Multiple tasks may be waiting for a reply from us if we received
+    // multiple messages before replying to any of them. Only wake up the
+    // task that we replied to, since the others cannot make progress.
     future::task::with_current_local_set(|current_local_set| {
-        current_local_set.ipc_reply_queue.wake_all();
+        current_local_set.ipc.lock().complete(to);
+        current_local_set.ipc_reply_queue.wake_task(to);
     });
 
+    trace::record(EventKind::IpcReply, [usize::from(to) as u64, status as u64]);
     Ok(())
 }
+
+/// Forwards a message received by the current task from the given client to
+/// another service, and transfers the obligation to reply to the client to
+/// that service. The forwarded message appears to come from the client, so the
+/// service replies directly to the client, which never notices the
+/// forwarding. This allows a task routing requests to other services to avoid
+/// copying each reply through itself.
+///
+/// The operation and payload of the forwarded message may differ from the
+/// message received from the client. Its [`Metadata`] describes the client,
+/// except for the badge that is the one of the connection of the current task
+/// to the service. Once the message is forwarded, the current task can no
+/// longer reply to the client.
+///
+/// # Errors
+/// Returns a [`ForwardError`] if the client is not waiting for a reply from
+/// the current task, or if the message could not be delivered to the service.
+/// In this case, the current task must still reply to the client.
+///
+/// # Panics
+/// Panics if there is no current task context. This can only happen if this
+/// function is called during kernel initialization, before any tasks have been
+/// created, and is a serious programming error.
+pub async fn forward(
+    client: future::task::Identifier,
+    connection: ipc::handle::Connection,
+    operation: usize,
+    payload: &[u8],
+) -> Result<(), ForwardError> {
+    let service = connection.service;
+    if payload.len() > Message::MAX_PAYLOAD_SIZE {
+        return Err(ForwardError::PayloadTooLarge);
+    }
+
+    if !future::task::exists(service.task) {
+        return Err(ForwardError::TaskDoesNotExist);
+    }
+
+    // Check that we received a message from the client that we have not
+    // replied to yet.
+    let from = future::executor::current_task_id().unwrap();
+    let pending = future::task::with_current_local_set(|current_local_set| {
+        current_local_set.ipc.lock().is_pending(client)
+    });
+    if !pending || !is_waiting_for_reply(client, from) {
+        return Err(ForwardError::NotWaitingForReply);
+    }
+
+    // The message appears to come from the client, but through the connection
+    // of the current task to the service.
+    let message = MESSAGE_CACHE.allocate(Message {
+        sender: client,
+        receiver: service.task,
+        operation,
+        payload_len: payload.len(),
+        metadata: Metadata::new(client, connection).await,
+        payload: {
+            let mut buf = [0; Message::MAX_PAYLOAD_SIZE];
+            buf[..payload.len()].copy_from_slice(payload);
+            buf
+        },
+    });
+    deliver(service, message).await?;
+
+    // The client now waits for a reply from the service. Since the client is
+    // sleeping on our reply queue, wake it up so that it waits on the reply
+    // queue of the service instead. If the client was destroyed while we were
+    // waiting to deliver the message, the service will fail to reply to it.
+    future::task::try_with_local_set_from(client, |set| {
+        if let Some(client_local_set) = set {
+            client_local_set.ipc.lock().wait_for_reply(service.task);
+        }
+    });
+    future::task::with_current_local_set(|current_local_set| {
+        current_local_set.ipc.lock().complete(client);
+        current_local_set.ipc_reply_queue.wake_task(client);
+    });
+    Ok(())
+}
+
+/// Returns whether the given task is waiting for a reply from the given task.
+fn is_waiting_for_reply(task: future::task::Identifier, from: future::task::Identifier) -> bool {
+    future::task::try_with_local_set_from(task, |set| {
+        set.is_some_and(|local_set| local_set.ipc.lock().replier() == Some(from))
+    })
+}
+
+/// Returns the number of IPC messages received by the current task that have
+/// not yet been replied to. If the task terminates with requests in flight,
+/// it terminated uncleanly and the senders are failed with
+/// [`SendError::ServiceDied`].
+///
+/// # Panics
+/// Panics if there is no current task context. This can only happen if this
+/// function is called during kernel initialization, before any tasks have been
+/// created, and is a serious programming error.
+#[must_use]
+pub fn in_flight() -> usize {
+    future::task::with_current_local_set(|current_local_set| {
+        current_local_set.ipc.lock().in_flight()
+    })
+}
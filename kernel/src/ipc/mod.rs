@@ -1,2 +1,4 @@
+pub mod handle;
 pub mod message;
 pub mod service;
+pub mod stats;
@@ -1,12 +1,76 @@
-use crate::future;
-use alloc::string::String;
+use crate::{config, future, ipc};
+use alloc::{string::String, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
 use hashbrown::HashMap;
 
-/// A global registry for services provided by tasks. It maps task identifiers
-/// to their corresponding service names.
-static SERVICE_REGISTRY: spin::Once<spin::Mutex<HashMap<String, future::task::Identifier>>> =
+/// A global registry for services provided by tasks. It maps service names
+/// to their corresponding service. It is read each time a message is sent to
+/// a service, and only written when services are registered, unregistered or
+/// accept a task, so it is a reader-writer lock that lets concurrent senders
+/// read it together and puts them to sleep while it is written.
+static SERVICE_REGISTRY: spin::Once<future::sync::RwLock<HashMap<String, Service>>> =
     spin::Once::new();
 
+/// The queue where the tasks waiting for a service to be registered sleep.
+/// They are all woken up each time a service is registered, and go back to
+/// sleep if it is not the one they wait for.
+static REGISTRATION_QUEUE: spin::Lazy<future::wait::Queue> =
+    spin::Lazy::new(future::wait::Queue::new);
+
+/// A service registered in the registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Service {
+    /// The registration of the service.
+    registration: Registration,
+
+    /// Whether the kernel collects statistics about the messages sent to
+    /// the service.
+    collect_statistics: bool,
+
+    /// The tasks allowed to connect to the service if it is restricted, or
+    /// `None` if any task can connect to it.
+    accepted: Option<Vec<future::task::Identifier>>,
+}
+
+/// A registration of a service by a task. Each registration is unique, even
+/// if the same task registers, unregisters and registers again a service, so
+/// that connections made to a previous registration cannot be used to reach
+/// the new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Registration {
+    /// The identifier of the task providing the service.
+    pub task: future::task::Identifier,
+
+    /// A serial number unique to this registration.
+    serial: u64,
+}
+
+impl Registration {
+    /// Creates a new unique registration for the given task.
+    fn generate(task: future::task::Identifier) -> Self {
+        // Serials start at 1, so that 0 never identifies a registration.
+        static NEXT_SERIAL: AtomicU64 = AtomicU64::new(1);
+        Self {
+            task,
+            serial: NEXT_SERIAL.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the serial number of the registration, which identifies it to
+    /// user space.
+    #[must_use]
+    pub const fn serial(&self) -> u64 {
+        self.serial
+    }
+}
+
+/// Errors that may occur during service unregistration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceUnregisterError {
+    /// The task is not registered as a service provider.
+    NotRegistered,
+}
+
 /// Errors that may occur during service registration.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ServiceRegisterError {
@@ -17,12 +81,39 @@ pub enum ServiceRegisterError {
     TaskAlreadyRegistered,
 }
 
+/// Errors that may occur when connecting to a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceConnectError {
+    /// No service with the given name exists.
+    NotFound,
+
+    /// The service is restricted and did not accept the task.
+    AccessDenied,
+}
+
+/// Errors that may occur when allowing a task to connect to a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceAcceptError {
+    /// The task is not registered as a service provider.
+    NotRegistered,
+
+    /// The service is not restricted.
+    NotRestricted,
+
+    /// The service already accepts [`config::MAX_ACCEPTED_TASKS`] tasks.
+    TooManyTasks,
+}
+
 /// Initializes the service registry.
 pub fn setup() {
-    SERVICE_REGISTRY.call_once(|| spin::Mutex::new(HashMap::new()));
+    SERVICE_REGISTRY.call_once(|| future::sync::RwLock::new(HashMap::new()));
 }
 
-/// Registers a new service with the given name and task identifier.
+/// Registers a new service with the given name and task identifier. If
+/// `collect_statistics` is `false`, no statistics will be collected about
+/// the messages sent to the service. If `restricted` is `true`, only the
+/// tasks accepted with [`accept`] will be able to connect to the service.
+/// The tasks waiting for the service in [`connect_wait`] are woken up.
 ///
 /// # Errors
 /// This function may fail and return:
@@ -35,14 +126,22 @@ pub fn setup() {
 /// This function may panic if the service registry has not been initialized
 /// by calling `setup()` beforehand. This should never happen, and indicates a
 /// bug in the kernel.
-pub fn register(name: String, id: future::task::Identifier) -> Result<(), ServiceRegisterError> {
-    let mut registry = SERVICE_REGISTRY.get().unwrap().lock();
+pub async fn register(
+    name: String,
+    id: future::task::Identifier,
+    collect_statistics: bool,
+    restricted: bool,
+) -> Result<(), ServiceRegisterError> {
+    let mut registry = SERVICE_REGISTRY.get().unwrap().write().await;
 
     // Verify that the task is not already registered. It iterates through
     // the existing services in the registry and checks if any of them match
     // the provided name. This is kinda inefficient, but service registration
     // is not expected to be a frequent operation so this should be fine
-    if registry.values().any(|&task_id| task_id == id) {
+    if registry
+        .values()
+        .any(|service| service.registration.task == id)
+    {
         return Err(ServiceRegisterError::TaskAlreadyRegistered);
     }
 
@@ -51,17 +150,233 @@ pub fn register(name: String, id: future::task::Identifier) -> Result<(), Servic
         return Err(ServiceRegisterError::NameNotAvailable);
     }
 
-    registry.insert(name, id);
+    registry.insert(
+        name,
+        Service {
+            registration: Registration::generate(id),
+            collect_statistics,
+            accepted: restricted.then(Vec::new),
+        },
+    );
+    drop(registry);
+    REGISTRATION_QUEUE.wake_all();
+    Ok(())
+}
+
+/// Unregisters the service provided by the given task. The service name
+/// becomes immediately available for other registrations, and all the
+/// statistics collected for the service are discarded.
+///
+/// Tasks waiting to deliver a message to the service are woken up and their
+/// send operation fails. However, messages that were already delivered to the
+/// service are not affected, and the service is still expected to reply to
+/// them.
+///
+/// # Errors
+/// This function returns [`ServiceUnregisterError::NotRegistered`] if the
+/// task does not provide any service.
+///
+/// # Panics
+/// This function may panic if the service registry has not been initialized
+/// by calling `setup()` beforehand. This should never happen, and indicates a
+/// bug in the kernel.
+pub async fn unregister(id: future::task::Identifier) -> Result<(), ServiceUnregisterError> {
+    SERVICE_REGISTRY
+        .get()
+        .unwrap()
+        .write()
+        .await
+        .extract_if(|_, service| service.registration.task == id)
+        .next()
+        .ok_or(ServiceUnregisterError::NotRegistered)?;
+
+    ipc::stats::clear(id);
+    future::task::try_with_local_set_from(id, |set| {
+        if let Some(set) = set {
+            set.ipc_send_queue.wake_all();
+        }
+    });
+    Ok(())
+}
+
+/// Looks up a service by its name and returns its current registration. If
+/// no such service exists, `None` is returned.
+///
+/// # Panics
+/// This function may panic if the service registry has not been initialized
+/// by calling `setup()` beforehand. This should never happen, and indicates a
+/// bug in the kernel.
+pub async fn lookup(name: &str) -> Option<Registration> {
+    SERVICE_REGISTRY
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .get(name)
+        .map(|service| service.registration)
+}
+
+/// Looks up a service by its name on behalf of the given task, and returns its
+/// current registration if the task is allowed to connect to it. The task is
+/// the task as a whole, not one of its threads, since the connection is shared
+/// by all of them.
+///
+/// # Errors
+/// Returns [`ServiceConnectError::NotFound`] if no such service exists, or
+/// [`ServiceConnectError::AccessDenied`] if the service is restricted and did
+/// not accept the task.
+///
+/// # Panics
+/// This function may panic if the service registry has not been initialized
+/// by calling `setup()` beforehand. This should never happen, and indicates a
+/// bug in the kernel.
+pub async fn connect(
+    name: &str,
+    task: future::task::Identifier,
+) -> Result<Registration, ServiceConnectError> {
+    let registry = SERVICE_REGISTRY.get().unwrap().read().await;
+    let service = registry.get(name).ok_or(ServiceConnectError::NotFound)?;
+    match &service.accepted {
+        Some(accepted) if !accepted.contains(&task) => Err(ServiceConnectError::AccessDenied),
+        _ => Ok(service.registration),
+    }
+}
+
+/// Looks up a service by its name on behalf of the given task like
+/// [`connect`], but waits until a service with this name is registered if
+/// there is none yet.
+///
+/// # Errors
+/// Returns [`ServiceConnectError::AccessDenied`] if the service is restricted
+/// and did not accept the task.
+///
+/// # Panics
+/// This function may panic if the service registry has not been initialized
+/// by calling `setup()` beforehand. This should never happen, and indicates a
+/// bug in the kernel.
+pub async fn connect_wait(
+    name: &str,
+    task: future::task::Identifier,
+) -> Result<Registration, ServiceConnectError> {
+    loop {
+        match connect(name, task).await {
+            Err(ServiceConnectError::NotFound) => {
+                future::wait::wait(&REGISTRATION_QUEUE).await;
+            }
+            result => break result,
+        }
+    }
+}
+
+/// Allows the given task to connect to the restricted service provided by
+/// `provider`. Accepting a task that is already accepted does nothing. A later
+/// task reusing the index of the identifier of a terminated task has a
+/// different generation, so it cannot impersonate the accepted task.
+///
+/// # Errors
+/// Returns [`ServiceAcceptError::NotRegistered`] if `provider` does not
+/// provide any service, [`ServiceAcceptError::NotRestricted`] if its service
+/// is not restricted, or [`ServiceAcceptError::TooManyTasks`] if the service
+/// already accepts [`config::MAX_ACCEPTED_TASKS`] tasks.
+///
+/// # Panics
+/// This function may panic if the service registry has not been initialized
+/// by calling `setup()` beforehand. This should never happen, and indicates a
+/// bug in the kernel.
+pub async fn accept(
+    provider: future::task::Identifier,
+    task: future::task::Identifier,
+) -> Result<(), ServiceAcceptError> {
+    let mut registry = SERVICE_REGISTRY.get().unwrap().write().await;
+    let service = registry
+        .values_mut()
+        .find(|service| service.registration.task == provider)
+        .ok_or(ServiceAcceptError::NotRegistered)?;
+    let accepted = service
+        .accepted
+        .as_mut()
+        .ok_or(ServiceAcceptError::NotRestricted)?;
+
+    if !accepted.contains(&task) {
+        if accepted.len() >= config::MAX_ACCEPTED_TASKS {
+            return Err(ServiceAcceptError::TooManyTasks);
+        }
+        accepted.push(task);
+    }
     Ok(())
 }
 
-/// Looks up a service by its name and returns the corresponding task. If no
-/// such service exists, `None` is returned.
+/// Returns the name and the registration of all the registered services,
+/// sorted by name.
+///
+/// # Panics
+/// This function may panic if the service registry has not been initialized
+/// by calling `setup()` beforehand. This should never happen, and indicates a
+/// bug in the kernel.
+#[must_use]
+pub async fn list() -> Vec<(String, Registration)> {
+    let mut services: Vec<_> = SERVICE_REGISTRY
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .iter()
+        .map(|(name, service)| (name.clone(), service.registration))
+        .collect();
+    services.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    services
+}
+
+/// Returns the registration of the service provided by the given task, or
+/// `None` if the task does not provide any service.
+///
+/// # Panics
+/// This function may panic if the service registry has not been initialized
+/// by calling `setup()` beforehand. This should never happen, and indicates a
+/// bug in the kernel.
+#[must_use]
+pub async fn registration_of(task: future::task::Identifier) -> Option<Registration> {
+    SERVICE_REGISTRY
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .values()
+        .map(|service| service.registration)
+        .find(|registration| registration.task == task)
+}
+
+/// Returns whether the given registration is still active, meaning that the
+/// service has not been unregistered since.
+///
+/// # Panics
+/// This function may panic if the service registry has not been initialized
+/// by calling `setup()` beforehand. This should never happen, and indicates a
+/// bug in the kernel.
+pub async fn is_active(registration: Registration) -> bool {
+    SERVICE_REGISTRY
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .values()
+        .any(|service| service.registration == registration)
+}
+
+/// Returns whether statistics should be collected about the messages sent to
+/// the given task. This is only the case if the task provides a service that
+/// did not opt out of statistics collection.
 ///
 /// # Panics
 /// This function may panic if the service registry has not been initialized
 /// by calling `setup()` beforehand. This should never happen, and indicates a
 /// bug in the kernel.
-pub fn lookup(name: &str) -> Option<future::task::Identifier> {
-    SERVICE_REGISTRY.get().unwrap().lock().get(name).copied()
+pub async fn collects_statistics(id: future::task::Identifier) -> bool {
+    SERVICE_REGISTRY
+        .get()
+        .unwrap()
+        .read()
+        .await
+        .values()
+        .any(|service| service.registration.task == id && service.collect_statistics)
 }
@@ -0,0 +1,99 @@
+use crate::{future, utils::lock};
+use alloc::vec::Vec;
+use core::time::Duration;
+use hashbrown::HashMap;
+
+/// The maximum number of (service, kind) pairs tracked at the same time. When
+/// the table is full, the least recently used entry is evicted to make room
+/// for the new one. This bounds the memory used by the statistics, since the
+/// kind of a message is chosen by the sender and could take any value.
+const MAX_ENTRIES: usize = 256;
+
+/// The global statistics table, indexed by service and message kind.
+static STATISTICS: lock::Mutex<Option<Table>> = lock::Mutex::new(None);
+
+/// Statistics about the messages of a given kind sent to a service.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    /// The number of messages that received a reply.
+    pub count: u64,
+
+    /// The sum of the latencies of all these messages.
+    pub total_latency: Duration,
+
+    /// The value of the table clock when this entry was last updated. This
+    /// is used to find the least recently used entry.
+    last_used: u64,
+}
+
+/// A bounded table of statistics with LRU eviction.
+#[derive(Debug, Default)]
+struct Table {
+    entries: HashMap<(future::task::Identifier, usize), Entry>,
+    clock: u64,
+}
+
+impl Table {
+    /// Records a message of the given kind sent to the given service that
+    /// received a reply after the given latency.
+    fn record(&mut self, service: future::task::Identifier, kind: usize, latency: Duration) {
+        self.clock += 1;
+
+        if self.entries.len() >= MAX_ENTRIES && !self.entries.contains_key(&(service, kind)) {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            if let Some(key) = oldest {
+                self.entries.remove(&key);
+            }
+        }
+
+        let entry = self.entries.entry((service, kind)).or_insert(Entry {
+            count: 0,
+            total_latency: Duration::ZERO,
+            last_used: 0,
+        });
+        entry.count += 1;
+        entry.total_latency += latency;
+        entry.last_used = self.clock;
+    }
+}
+
+/// Records a message of the given kind sent to the given service that
+/// received a reply after the given latency.
+pub fn record(service: future::task::Identifier, kind: usize, latency: Duration) {
+    STATISTICS
+        .lock()
+        .get_or_insert_with(Table::default)
+        .record(service, kind, latency);
+}
+
+/// Returns the statistics of all message kinds currently tracked for the
+/// given service, sorted by kind.
+#[must_use]
+pub fn collect(service: future::task::Identifier) -> Vec<(usize, Entry)> {
+    let mut entries: Vec<_> = STATISTICS
+        .lock()
+        .as_ref()
+        .map(|table| {
+            table
+                .entries
+                .iter()
+                .filter(|((id, _), _)| *id == service)
+                .map(|((_, kind), entry)| (*kind, *entry))
+                .collect()
+        })
+        .unwrap_or_default();
+    entries.sort_unstable_by_key(|(kind, _)| *kind);
+    entries
+}
+
+/// Removes all the statistics of the given service. This must be called when
+/// a service is unregistered, so that stale data is not reported.
+pub fn clear(service: future::task::Identifier) {
+    if let Some(table) = STATISTICS.lock().as_mut() {
+        table.entries.retain(|(id, _), _| *id != service);
+    }
+}
@@ -3,25 +3,61 @@
 #![warn(clippy::all)]
 #![warn(clippy::pedantic)]
 #![feature(strict_provenance_lints)]
+#![feature(exclusive_wrapper)]
 #![warn(fuzzy_provenance_casts)]
 #![warn(lossy_provenance_casts)]
 #![allow(unsafe_op_in_unsafe_fn)]
 #![allow(clippy::module_name_repetitions)]
 
 pub mod arch;
+pub mod bench;
+pub mod cmdline;
 pub mod config;
+pub mod devicetree;
 pub mod future;
 pub mod ipc;
 pub mod mm;
+pub mod random;
+pub mod stats;
+pub mod testing;
 pub mod time;
+pub mod trace;
 pub mod user;
 pub mod utils;
+pub mod watchdog;
 
 extern crate alloc;
 
+/// The path of the binary of a user-space program built for the architecture
+/// of the kernel, relative to this file.
+#[cfg(target_arch = "riscv64")]
+macro_rules! user_program {
+    ($name:literal) => {
+        concat!(
+            "../../user/",
+            $name,
+            "/target/riscv64gc-unknown-none-elf/release/",
+            $name
+        )
+    };
+}
+#[cfg(target_arch = "aarch64")]
+macro_rules! user_program {
+    ($name:literal) => {
+        concat!(
+            "../../user/",
+            $name,
+            "/target/aarch64-unknown-none/release/",
+            $name
+        )
+    };
+}
+
 /// The initial user-space process that will be executed by the kernel. This
 /// is the only user-space process that is started directly by the kernel.
 /// All other user-space processes must be started by the `init` process.
+/// When the bootloader provides an initial ramdisk containing an `init`
+/// program, that program is used instead.
 ///
 /// # Why using `include_bytes!` this way?
 /// This approach ensures that the binary is included in the kernel binary
@@ -30,24 +66,48 @@ extern crate alloc;
 /// attribute on an &[u8] only put the reference to the data in that section,
 /// not the data itself.
 #[macros::initdata]
-static INIT: [u8; include_bytes!(
-    "../../user/init/target/riscv64gc-unknown-none-elf/release/init"
-)
-.len()] = *include_bytes!("../../user/init/target/riscv64gc-unknown-none-elf/release/init");
+static INIT: [u8; include_bytes!(user_program!("init")).len()] =
+    *include_bytes!(user_program!("init"));
 
 /// The echo user-space process binary. This process is used to demonstrate
 /// inter-process communication (IPC) capabilities of the kernel, and is not
-/// meant to stay here permanently and will be removed in future versions.
+/// meant to stay here permanently and will be removed in future versions. It
+/// is only started when there is no initial ramdisk, since `init` spawns the
+/// programs of the ramdisk otherwise.
+#[macros::initdata]
+static ECHO: [u8; include_bytes!(user_program!("echo")).len()] =
+    *include_bytes!(user_program!("echo"));
+
+/// The programs of the benchmark suite, only embedded when the kernel is built
+/// with the `bench` feature. The `pong` service replies to the messages of
+/// `ping`, which drives the benchmark (see [`bench`]).
+#[cfg(feature = "bench")]
+#[macros::initdata]
+static PING: [u8; include_bytes!(user_program!("ping")).len()] =
+    *include_bytes!(user_program!("ping"));
+
+#[cfg(feature = "bench")]
+#[macros::initdata]
+static PONG: [u8; include_bytes!(user_program!("pong")).len()] =
+    *include_bytes!(user_program!("pong"));
+
+/// The program running the test cases of the integration test suite, only
+/// embedded when the kernel is built with the `integration` feature. It is
+/// spawned once for each test case (see [`testing`]).
+#[cfg(feature = "integration")]
 #[macros::initdata]
-static ECHO: [u8; include_bytes!(
-    "../../user/echo/target/riscv64gc-unknown-none-elf/release/echo"
-)
-.len()] = *include_bytes!("../../user/echo/target/riscv64gc-unknown-none-elf/release/echo");
+static INTEGRATION: [u8; include_bytes!(user_program!("integration")).len()] =
+    *include_bytes!(user_program!("integration"));
 
 /// The `kiwi` function is called after the architecture-specific
 /// initialization was completed. It is responsible for setting up the
 /// kernel and starting the first user-space process.
 ///
+/// # Panics
+/// Panics if one of the programs embedded in the kernel or the `init` program
+/// of the initial ramdisk cannot be loaded, or if the initial ramdisk cannot be
+/// mapped in the address space of `init`.
+///
 /// # Safety
 /// This function should only be called once during the kernel boot
 /// process. Once the boot process is completed, the function will
@@ -55,18 +115,102 @@ static ECHO: [u8; include_bytes!(
 #[macros::init]
 #[unsafe(no_mangle)]
 pub unsafe extern "Rust" fn kiwi(memory: arch::memory::UsableMemory) -> ! {
+    let initrd = memory.initrd;
     mm::phys::setup(memory);
     mm::heap::setup();
+    trace::setup();
     future::executor::setup();
-    future::executor::spawn(user::elf::load(&INIT));
-    future::executor::spawn(user::elf::load(&ECHO));
+
+    // SAFETY: The initial ramdisk was reserved while setting up the physical
+    // memory manager, and is only released below.
+    let initrd = initrd.and_then(|region| unsafe { user::initrd::Initrd::new(region) });
+    let mut init = match &initrd {
+        Some(initrd) => {
+            let size = alloc::format!("{}={}", ::syscall::initrd::INITRD_SIZE_VAR, initrd.size());
+            let image = initrd.find("init").map_or(&INIT[..], |entry| entry.data);
+            // SAFETY: We are still in the kernel boot process.
+            unsafe { load_program(image, "init", &[&size]) }
+        }
+        // SAFETY: We are still in the kernel boot process.
+        None => unsafe { load_program(&INIT, "init", &[]) },
+    };
+
+    let spawn_echo = initrd.is_none();
+    if let Some(initrd) = initrd {
+        assert!(initrd.map(&mut init), "Failed to map the initial ramdisk");
+        initrd.release();
+    }
+    future::executor::spawn_with_id(init, future::task::Identifier::INIT);
+    if spawn_echo {
+        // SAFETY: We are still in the kernel boot process.
+        _ = future::executor::spawn(unsafe { load_program(&ECHO, "echo", &[]) });
+    }
+
+    #[cfg(feature = "bench")]
+    {
+        // SAFETY: We are still in the kernel boot process.
+        _ = future::executor::spawn(unsafe { load_program(&PONG, "pong", &[]) });
+        // SAFETY: We are still in the kernel boot process.
+        bench::set_driver(
+            future::executor::spawn(unsafe { load_program(&PING, "ping", &[]) })
+                .expect("Failed to spawn the ping program"),
+        );
+    }
+
+    #[cfg(feature = "integration")]
+    for test in testing::TESTS {
+        let case = alloc::format!("{}={}", testing::CASE_VAR, test.name);
+        // SAFETY: We are still in the kernel boot process.
+        let thread = unsafe { load_program(&INTEGRATION, "integration", &[&case]) };
+        let id = future::executor::spawn(thread).expect("Failed to spawn a test case");
+        testing::started(id, test);
+    }
 
     ipc::service::setup();
+    future::executor::spawn_kernel(mm::pressure::daemon())
+        .expect("Failed to spawn the reclaim daemon");
 
     let memory_usage = mm::phys::kernel_memory_pages() * 4;
     log::info!("Boot completed !");
     log::info!("Memory used by the kernel: {} Kib", memory_usage);
 
-    // Run the executor and start the first user-space process
+    // SAFETY: The trap handler was installed while setting up the
+    // architecture, and the kernel is ready to handle interrupts.
+    unsafe {
+        watchdog::setup();
+    }
+
+    finish_boot();
+}
+
+/// Reclaims the memory used only during boot, then runs the executor and
+/// starts the first user-space process. This is called at the very end of
+/// [`kiwi`] and must not be inlined into it, since its code is in the `.init`
+/// section that is unmapped here.
+#[inline(never)]
+fn finish_boot() -> ! {
+    // SAFETY: The boot process is completed, and the `init` functions that are
+    // still on the stack never return.
+    unsafe {
+        mm::reclaim::init_sections();
+    }
+
     future::executor::run();
 }
+
+/// Load one of the programs embedded in the kernel or in the initial ramdisk,
+/// giving it its name as its only argument and the given environment
+/// variables.
+///
+/// # Panics
+/// Panics if the program cannot be loaded.
+///
+/// # Safety
+/// This function must only be called during the kernel boot process.
+#[macros::init]
+unsafe fn load_program(image: &[u8], name: &str, vars: &[&str]) -> arch::thread::Thread {
+    let env = user::env::encode(&[name], vars);
+    let env = ::syscall::env::Block::new(&env).expect("Invalid environment block");
+    user::elf::load(image, &env)
+        .unwrap_or_else(|error| panic!("Failed to load the {name} program: {error:?}"))
+}
@@ -0,0 +1,84 @@
+use crate::{future, utils::lock};
+use core::sync::atomic::{AtomicU64, Ordering};
+use heapless::HistoryBuf;
+
+/// The number of most recent fault addresses remembered for each task.
+pub const RECENT_FAULTS: usize = 8;
+
+/// The number of minor faults resolved by the kernel since boot.
+static MINOR_FAULTS: AtomicU64 = AtomicU64::new(0);
+
+/// The number of major faults resolved by the kernel since boot.
+static MAJOR_FAULTS: AtomicU64 = AtomicU64::new(0);
+
+future::local::task_local! {
+    /// The page fault statistics of each task.
+    static TASK_FAULTS: lock::Mutex<TaskFaults> = lock::Mutex::new(TaskFaults::default());
+}
+
+/// The severity of a page fault resolved by the kernel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// A fault resolved without any I/O, for example by lazily allocating a
+    /// zeroed frame or by copying a frame shared with copy-on-write.
+    Minor,
+
+    /// A fault that required fetching the content of the page from somewhere
+    /// else, for example from a pager or a swap device.
+    Major,
+}
+
+/// The page fault statistics of a task.
+#[derive(Debug, Default)]
+pub struct TaskFaults {
+    /// The number of minor faults resolved for the task.
+    pub minor: u64,
+
+    /// The number of major faults resolved for the task.
+    pub major: u64,
+
+    /// The addresses of the most recent faults of the task.
+    pub recent: HistoryBuf<usize, RECENT_FAULTS>,
+}
+
+/// Records a page fault at the given address resolved by the kernel for the
+/// current task. This updates both the statistics of the task and the
+/// system-wide statistics.
+pub fn record(severity: Severity, address: usize) {
+    match severity {
+        Severity::Minor => MINOR_FAULTS.fetch_add(1, Ordering::Relaxed),
+        Severity::Major => MAJOR_FAULTS.fetch_add(1, Ordering::Relaxed),
+    };
+
+    if let Some(id) = future::executor::current_task_id() {
+        TASK_FAULTS.with(id, |faults| {
+            let mut faults = faults.lock();
+            match severity {
+                Severity::Minor => faults.minor += 1,
+                Severity::Major => faults.major += 1,
+            }
+            faults.recent.write(address);
+        });
+    }
+}
+
+/// Executes a closure with the page fault statistics of the task with the
+/// given identifier. Returns `None` if the task does not exist.
+pub fn with_task_faults<F, R>(id: future::task::Identifier, f: F) -> Option<R>
+where
+    F: FnOnce(&TaskFaults) -> R,
+{
+    TASK_FAULTS.with(id, |faults| f(&faults.lock()))
+}
+
+/// Returns the number of minor faults resolved by the kernel since boot.
+#[must_use]
+pub fn minor_faults() -> u64 {
+    MINOR_FAULTS.load(Ordering::Relaxed)
+}
+
+/// Returns the number of major faults resolved by the kernel since boot.
+#[must_use]
+pub fn major_faults() -> u64 {
+    MAJOR_FAULTS.load(Ordering::Relaxed)
+}
@@ -1,4 +1,15 @@
-use crate::{arch, mm};
+use crate::{
+    arch::{
+        self,
+        mmu::{Align, KERNEL_HEAP_SIZE, KERNEL_HEAP_START, PAGE_SIZE},
+        target::addr::{Virtual, virt::Kernel},
+    },
+    config, mm,
+};
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 /// The global heap allocator. This allocator is used to allocate
 /// memory on the kernel heap. However, the kernel heap should only
@@ -6,72 +17,232 @@ use crate::{arch, mm};
 /// allocations should be done using the virtual memory allocator
 ///(not yet implemented).
 #[global_allocator]
-static ALLOCATOR: talc::Talck<spin::Mutex<()>, OomHandler> =
-    talc::Talck::new(talc::Talc::new(OomHandler {}));
+static ALLOCATOR: Heap = Heap {
+    talc: talc::Talck::new(talc::Talc::new(OomHandler {
+        heap: talc::Span::empty(),
+        mapped: 0,
+    })),
+};
+
+/// The number of bytes currently mapped in the kernel heap range.
+static HEAP_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// The largest number of bytes ever mapped in the kernel heap range.
+static HEAP_PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// The kernel heap. It lives in a dedicated range of the kernel address space
+/// (see [`KERNEL_HEAP_START`]), which is grown on demand by mapping frames at
+/// its end when the allocator runs out of memory, and shrunk when large
+/// allocations are freed by unmapping the unused memory at its end.
+struct Heap {
+    talc: talc::Talck<spin::Mutex<()>, OomHandler>,
+}
+
+impl Heap {
+    /// The minimum size of an allocation that triggers an attempt to shrink
+    /// the heap when freed. Smaller allocations are too small to leave a
+    /// significant amount of unused memory at the end of the heap.
+    const SHRINK_THRESHOLD: usize = 64 * 1024;
+
+    /// The amount of unused memory kept at the end of the heap when shrinking
+    /// it, to avoid growing the heap again on the next allocation.
+    const SHRINK_SLACK: usize = OomHandler::GROWTH_SIZE;
+
+    /// Return the unused memory at the end of the heap to the physical memory
+    /// allocator, keeping [`Self::SHRINK_SLACK`] bytes of unused memory.
+    fn shrink(&self) {
+        let mut talc = self.talc.lock();
+        let heap = talc.oom_handler.heap;
+        let Some((base, _)) = heap.get_base_acme() else {
+            return;
+        };
+
+        // SAFETY: The heap span is the return value of the last heap
+        // manipulation function of the allocator.
+        let allocated = unsafe { talc.get_allocated_span(heap) };
+        let start = KERNEL_HEAP_START.as_mut_ptr::<u8>();
+        let used = allocated
+            .get_base_acme()
+            .map_or(0, |(_, acme)| acme.addr() - start.addr());
+        let keep = (used + Self::SHRINK_SLACK).page_align_up();
+        if keep >= talc.oom_handler.mapped {
+            return;
+        }
+
+        // SAFETY: The requested heap is contained in the current heap and
+        // contains all its allocated memory.
+        let truncated = talc::Span::new(base, start.wrapping_add(keep)).fit_over(allocated);
+        let heap = unsafe { talc.truncate(heap, truncated) };
+        let end = heap
+            .get_base_acme()
+            .map_or(0, |(_, acme)| acme.addr() - start.addr())
+            .page_align_up();
+        OomHandler::unmap(end, talc.oom_handler.mapped - end);
+        talc.oom_handler.heap = heap;
+        talc.oom_handler.mapped = end;
+        HEAP_SIZE.store(end, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        GlobalAlloc::alloc(&self.talc, layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        GlobalAlloc::dealloc(&self.talc, ptr, layout);
+        if layout.size() >= Self::SHRINK_THRESHOLD {
+            self.shrink();
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        GlobalAlloc::realloc(&self.talc, ptr, layout, new_size)
+    }
+}
 
 /// The global OOM handler when the kernel heap is exhausted. This
-/// handler will allocate enough physical memory to satisfy the
-/// allocation request. If the system is truly out of memory, the
-/// kernel will panic.
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
-struct OomHandler {}
+/// handler will map enough physical memory at the end of the heap
+/// to satisfy the allocation request. If the system is truly out
+/// of memory, the allocation will fail.
+#[derive(Debug)]
+struct OomHandler {
+    /// The span of the heap managed by the allocator, or an empty span if
+    /// the heap has not been initialized yet.
+    heap: talc::Span,
+
+    /// The number of bytes mapped from the start of the heap range.
+    mapped: usize,
+}
+
+/// SAFETY: The heap span only points to the kernel heap range, which is
+/// accessible from any thread, and is only accessed while holding the lock
+/// of the allocator.
+unsafe impl Send for OomHandler {}
 
 impl OomHandler {
-    /// The size of the allocation that will be attempted when
-    /// handling an OOM. The value should be not too small to
-    /// avoid invoking the OOM handler too often, but not too
-    /// large to avoid allocating too much contiguous physical
-    /// memory that could lead to an fatal OOM, even if there
-    /// is enough memory available (but not contiguous).
-    const ALLOCATION_SIZE: usize = 128 * 1024;
-
-    /// The number of frames that will be allocated when handling
-    /// an OOM. Directly related to the `ALLOCATION_SIZE` constant.
-    const ALLOCATION_FRAMES_COUNT: usize = const { Self::ALLOCATION_SIZE / arch::mmu::PAGE_SIZE };
+    /// The minimum number of bytes by which the heap is grown when
+    /// handling an OOM. The value should be not too small to avoid
+    /// invoking the OOM handler too often, but not too large to
+    /// avoid wasting memory.
+    const GROWTH_SIZE: usize = 128 * 1024;
+
+    /// Map fresh frames in the heap range, starting at `offset` bytes from
+    /// the start of the heap range for `size` bytes. Returns `false` if the
+    /// frames could not be allocated or mapped, in which case the pages
+    /// mapped by this call are unmapped.
+    fn map(offset: usize, size: usize) -> bool {
+        for page in (offset..offset + size).step_by(PAGE_SIZE) {
+            let Some(frame) = mm::phys::allocate_frame(mm::phys::AllocationFlags::KERNEL) else {
+                Self::unmap(offset, page - offset);
+                return false;
+            };
+
+            // SAFETY: The page is in the heap range and is not yet mapped, and
+            // the frame was just allocated.
+            let mapped = unsafe {
+                arch::mmu::map_kernel(
+                    Virtual::<Kernel>::new(KERNEL_HEAP_START.as_usize() + page),
+                    frame,
+                    arch::mmu::Rights::RW,
+                    arch::mmu::Flags::empty(),
+                )
+            };
+
+            if mapped.is_err() {
+                mm::phys::deallocate_frame(frame.into_inner());
+                Self::unmap(offset, page - offset);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Unmap the pages of the heap range starting at `offset` bytes from the
+    /// start of the heap range for `size` bytes, and free their frames.
+    fn unmap(offset: usize, size: usize) {
+        for page in (offset..offset + size).step_by(PAGE_SIZE) {
+            // SAFETY: The page is no longer part of the heap managed by the
+            // allocator, so it is no longer used.
+            let frame = unsafe {
+                arch::mmu::unmap_kernel(Virtual::<Kernel>::new(KERNEL_HEAP_START.as_usize() + page))
+            }
+            .expect("Kernel heap page not mapped");
+            mm::phys::deallocate_frame(frame.into_inner());
+        }
+    }
 }
 
 impl talc::OomHandler for OomHandler {
-    fn handle_oom(talc: &mut talc::Talc<Self>, layout: core::alloc::Layout) -> Result<(), ()> {
-        // The heap should not be used to allocate large chunks of
-        // memory. Since kiwi is designed to be a microkernel, this
-        // should never happen.
-        if layout.size() > Self::ALLOCATION_SIZE {
-            log::error!("Allocation request too large: {} bytes", layout.size());
+    fn handle_oom(talc: &mut talc::Talc<Self>, layout: Layout) -> Result<(), ()> {
+        // Grow the heap by at least the size of the allocation, with some
+        // margin for its alignment and the metadata of the allocator. If
+        // this is still not enough, the allocator will call this handler
+        // again.
+        let growth = (layout.size() + layout.align() + PAGE_SIZE)
+            .max(Self::GROWTH_SIZE)
+            .page_align_up();
+        let mapped = talc.oom_handler.mapped;
+        if mapped + growth > KERNEL_HEAP_SIZE {
+            log::error!(
+                "Kernel heap range exhausted ({} bytes requested)",
+                layout.size()
+            );
             return Err(());
         }
 
-        log::debug!(
-            "Kernel heap exhausted, attempting to allocate {} more bytes",
-            Self::ALLOCATION_SIZE
-        );
-
-        // Allocate a range of contiguous physical memory frames to
-        // satisfy the allocation request.
-        let base = mm::phys::allocate_range(
-            Self::ALLOCATION_FRAMES_COUNT,
-            mm::phys::AllocationFlags::ZEROED | mm::phys::AllocationFlags::KERNEL,
-        )
-        .ok_or(())?;
-
-        // Convert the physical address to a virtual address and
-        // compute the start and end pointers of the allocation.
-        let memory = arch::mmu::translate_physical(base).ok_or(())?;
-        let start = memory.as_mut_ptr::<u8>();
-        let end = memory
-            .as_mut_ptr::<u8>()
-            .wrapping_byte_add(Self::ALLOCATION_SIZE);
-
-        // SAFETY: The given span is valid, does not overlapp with any
-        // other span, is not in use anywhere else in the system and is
-        // valid for reads and writes.
-        unsafe { talc.claim(talc::Span::new(start, end)).map(|_| ()) }
+        log::debug!("Kernel heap exhausted, growing it by {} bytes", growth);
+        if !Self::map(mapped, growth) {
+            log::error!("Failed to grow the kernel heap by {} bytes", growth);
+            return Err(());
+        }
+
+        // Give the new memory to the allocator, either by creating the heap
+        // or by extending the existing one.
+        let base = KERNEL_HEAP_START.as_mut_ptr::<u8>();
+        let span = talc::Span::from_base_size(base, mapped + growth);
+        let heap = talc.oom_handler.heap;
+
+        // SAFETY: The given span is mapped, does not overlap with any other
+        // heap, is not in use anywhere else in the system and is valid for
+        // reads and writes.
+        talc.oom_handler.heap = if heap.is_empty() {
+            unsafe { talc.claim(span)? }
+        } else {
+            unsafe { talc.extend(heap, span) }
+        };
+        talc.oom_handler.mapped = mapped + growth;
+
+        HEAP_SIZE.store(mapped + growth, Ordering::Relaxed);
+        HEAP_PEAK.fetch_max(mapped + growth, Ordering::Relaxed);
+        Ok(())
     }
 }
 
-/// Setup the global kernel heap allocator.
+/// Setup the global kernel heap allocator. If
+/// [`config::PREALLOCATE_KERNEL_TABLES`] is enabled, the page tables of the
+/// whole heap range are allocated first, so that growing the heap never needs
+/// to allocate a page table.
 #[inline]
 pub fn setup() {
     log::info!("Setting up the kernel heap allocator");
+    if config::PREALLOCATE_KERNEL_TABLES {
+        let tables = arch::mmu::preallocate_kernel_tables();
+        log::debug!("Preallocated {} kernel page tables", tables);
+    }
+
     // The heap will be initialized by the global allocator when the
     // first allocation will be requested.
 }
+
+/// Returns the number of bytes currently mapped for the kernel heap.
+#[must_use]
+pub fn size() -> usize {
+    HEAP_SIZE.load(Ordering::Relaxed)
+}
+
+/// Returns the largest number of bytes ever mapped for the kernel heap.
+#[must_use]
+pub fn peak() -> usize {
+    HEAP_PEAK.load(Ordering::Relaxed)
+}
@@ -1,2 +1,8 @@
+pub mod fault;
 pub mod heap;
+pub mod oom;
 pub mod phys;
+pub mod pressure;
+pub mod reclaim;
+pub mod slab;
+pub mod space;
@@ -0,0 +1,74 @@
+//! The out-of-memory handler. When the kernel cannot allocate the memory
+//! needed by a task, even after releasing the memory that it keeps for
+//! performance only (see [`mm::pressure::reclaim`]), it kills another task to
+//! free memory instead of failing the allocation.
+//!
+//! The victim is the task that mapped the most pages with the `MemMap`
+//! syscall, among the tasks that are neither well-known system tasks nor
+//! marked as essential by `init`. It terminates as if it had been killed by
+//! another task, and the allocation is retried once its memory is released.
+use crate::{
+    future::{self, task::Identifier, user::Exit},
+    mm,
+};
+use core::sync::atomic::Ordering;
+
+/// Handles the failure of an allocation made on behalf of the current task,
+/// and returns whether the allocation should be retried.
+///
+/// The memory that the kernel can reclaim is released first. If there was
+/// none, a victim is killed and this waits until its memory is released.
+/// Returns `false` if no task can be killed, or if the victim is the current
+/// task itself, in which case the allocation must fail and the current task
+/// stops at its next trap.
+///
+/// # Panics
+/// Panics if there is no currently running task.
+pub async fn handle() -> bool {
+    let reclaimed = mm::pressure::reclaim();
+    if reclaimed > 0 {
+        log::debug!("Out of memory, reclaimed {} frames", reclaimed);
+        return true;
+    }
+
+    let Some((victim, mapped)) = select_victim() else {
+        log::error!("Out of memory, and no task can be killed to free memory");
+        return false;
+    };
+
+    // The victim may already be terminating, in which case its memory is
+    // about to be released anyway.
+    let current = future::group::current();
+    if future::group::kill(victim, Exit::OutOfMemory).await {
+        log::warn!(
+            "Out of memory: killed task {} ({} pages mapped) on behalf of task {}",
+            victim,
+            mapped,
+            current
+        );
+    }
+    if victim == current {
+        return false;
+    }
+
+    future::termination::released(victim).await;
+    true
+}
+
+/// Selects the task to kill to free memory: the task that mapped the most
+/// pages, excluding the well-known system tasks and the essential tasks.
+/// Returns its identifier and the number of pages it mapped, or `None` if no
+/// task can be killed.
+fn select_victim() -> Option<(Identifier, usize)> {
+    future::group::all()
+        .into_iter()
+        .filter(|group| !group.is_reserved())
+        .filter_map(|group| {
+            let thread = *future::group::threads(group)?.first()?;
+            future::task::try_with_local_set_from(thread, |set| {
+                set.filter(|set| !set.essential.load(Ordering::Relaxed))
+                    .map(|set| (group, set.limits.lock().mapped()))
+            })
+        })
+        .max_by_key(|&(_, mapped)| mapped)
+}
@@ -1,17 +1,19 @@
-use crate::arch::{
-    self,
-    mmu::{self, Align, PAGE_SIZE},
-    target::addr::{Frame4Kib, Physical},
+use crate::{
+    arch::{
+        self,
+        mmu::{self, Align, PAGE_SIZE},
+        target::addr::{Frame4Kib, Physical},
+    },
+    config::{self, FRAME_CACHE_CAPACITY},
+    utils::lock,
+    utils::percpu::PerCpu,
 };
+use ::syscall::memory::Pressure;
 use bitflags::bitflags;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use kcore::frame::{Bitmap, FrameFlags, FrameInfo};
 use seqlock::Seqlock;
 
-/// Informations about a frame.
-#[derive(Debug)]
-pub struct FrameInfo {
-    flags: FrameFlags,
-}
-
 bitflags! {
     /// Allocation flags that can be used to customize the behavior of
     /// the physical memory allocator or to provide additional information
@@ -26,27 +28,30 @@ bitflags! {
         /// The frame will be zeroed before it is returned to the caller.
         const ZEROED = 1 << 1;
     }
-
-    /// Some frame flags to indicate some specificities about the frame.
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-    pub struct FrameFlags: u8 {
-        /// If set, the frame is free to be allocated
-        const FREE = 1 << 0;
-
-        /// If set, the frame is used by the kernel. It cannot be set if the
-        /// `FREE` or `FIRMWARE` flags are set.
-        const KERNEL = 1 << 1;
-
-        /// If set, the frame is used by the firmware. It cannot be set if
-        /// the `FREE` or `KERNEL` flags are set.
-        const FIRMWARE = 1 << 2;
-    }
 }
 
 /// The number of total memory pages. This is the total number of pages that
 /// are available for allocation.
 static TOTAL_MEMORY_PAGES: Seqlock<usize> = Seqlock::new(0);
 
+/// The number of frames currently allocated through the allocator. Frames
+/// reserved during boot for the kernel image or the firmware are not counted.
+/// Comparing this counter before and after a workload allows detecting frames
+/// that are never returned to the allocator.
+static ALLOCATED_PAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of frames that can be allocated, including the frames cached by
+/// the CPU cores. It is compared to the watermarks to know the memory pressure.
+static FREE_PAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of free frames under which the memory pressure is
+/// [`Pressure::Low`] (see [`config::LOW_MEMORY_WATERMARK`]).
+static LOW_WATERMARK: Seqlock<usize> = Seqlock::new(0);
+
+/// The number of free frames under which the memory pressure is
+/// [`Pressure::Critical`] (see [`config::CRITICAL_MEMORY_WATERMARK`]).
+static CRITICAL_WATERMARK: Seqlock<usize> = Seqlock::new(0);
+
 /// The starting offset of the DRAM. This is useful for some architecture when
 /// the RAM does not start at the address 0 and allow reduce the memory used by
 /// the frame info array
@@ -58,7 +63,13 @@ static RAM_END: Seqlock<usize> = Seqlock::new(0);
 /// The bitmap allocator is used to allocate and deallocate physical frames
 /// using a bitmap. This allocator is very slow, but does not consume a lot
 /// of memory and is "good enought" for now.
-static BITMAP: spin::Mutex<&mut [FrameInfo]> = spin::Mutex::new(&mut []);
+static BITMAP: lock::Mutex<Bitmap<'static>> = lock::Mutex::new(Bitmap::new(&mut []));
+
+/// The frames cached by each CPU core. These frames are marked as used in the
+/// bitmap, but are not owned by anyone and are handed out first by
+/// [`allocate_frame`] and [`deallocate_frame`].
+static FRAME_CACHE: PerCpu<heapless::Vec<Frame4Kib, FRAME_CACHE_CAPACITY>> =
+    PerCpu::new(heapless::Vec::new());
 
 /// Initialize the physical memory manager
 ///
@@ -76,7 +87,7 @@ pub fn setup(mut memory: arch::memory::UsableMemory) {
 
     // Allocate the bitmap by using a free memory region from
     // the memory map big enough for the bitmap
-    let bitmap = unsafe {
+    let mut bitmap = unsafe {
         let base = memory
             .allocate_memory::<FrameInfo>(bitmap_size, 16)
             .expect("Failed to allocate bitmap");
@@ -88,18 +99,17 @@ pub fn setup(mut memory: arch::memory::UsableMemory) {
         // Initialize the bitmap before creating a slice (it would
         // be UB otherwise)
         for i in 0..frame_count {
-            ptr.add(i).write(FrameInfo {
-                flags: FrameFlags::KERNEL,
-            });
+            ptr.add(i).write(FrameInfo::RESERVED);
         }
 
-        // Create the slice
-        core::slice::from_raw_parts_mut(ptr, frame_count)
+        // Create the slice and the allocator managing it
+        Bitmap::new(core::slice::from_raw_parts_mut(ptr, frame_count))
     };
 
     TOTAL_MEMORY_PAGES.write(memory.total_memory.page_count_up());
     RAM_START.write(memory.ram_start);
     RAM_END.write(memory.ram_end);
+    let firmware = 0..memory.firmware_memory.div_ceil(PAGE_SIZE);
 
     // Add the free flags to all available memory pages
     memory
@@ -116,20 +126,20 @@ pub fn setup(mut memory: arch::memory::UsableMemory) {
                     .page_align_up()
                     .as_usize(),
             );
-            (start..end).for_each(|frame| {
-                bitmap[frame].flags &= !FrameFlags::KERNEL;
-                bitmap[frame].flags |= FrameFlags::FREE;
-            });
+            bitmap.add_free(start..end);
         });
 
-    // Reserve the memory used by the firmware (OpenSBI)
-    // TODO: Make this architecture agnostic
-    (0x8000_0000..0x8020_0000)
-        .step_by(arch::mmu::PAGE_SIZE)
-        .for_each(|addr| {
-            bitmap[phys2index(addr)].flags &= !FrameFlags::KERNEL;
-            bitmap[phys2index(addr)].flags |= FrameFlags::FIRMWARE;
-        });
+    // Reserve the memory used by the firmware, which is at the start of the
+    // RAM on all supported platforms (OpenSBI on riscv64, the device tree
+    // given by QEMU on aarch64)
+    bitmap.reserve_firmware(firmware);
+
+    // Compute the watermarks from the memory available once the kernel and
+    // the firmware are loaded
+    let usable = bitmap.count(FrameFlags::FREE);
+    FREE_PAGES.store(usable, Ordering::Relaxed);
+    LOW_WATERMARK.write(usable * config::LOW_MEMORY_WATERMARK / 100);
+    CRITICAL_WATERMARK.write(usable * config::CRITICAL_MEMORY_WATERMARK / 100);
 
     // Initialize the bitmap
     *BITMAP.lock() = bitmap;
@@ -137,9 +147,67 @@ pub fn setup(mut memory: arch::memory::UsableMemory) {
 
 /// Allocate a frame. Returns `None` if no frame is available, or a frame if a
 /// frame was successfully allocated.
+///
+/// Frames that are not used by the kernel are taken from the frame cache of
+/// the current core, which is refilled in batches from the bitmap when empty.
+/// This avoids taking the lock of the bitmap and searching for a free frame
+/// on most allocations.
+///
+/// # Panics
+/// Panics if the bitmap is not initialized (meaning that the physical memory
+/// manager is not initialized).
 #[must_use]
 pub fn allocate_frame(flags: AllocationFlags) -> Option<Frame4Kib> {
-    allocate_range(1, flags).map(Frame4Kib::new)
+    // Kernel frames must be flagged in the bitmap, so they bypass the cache
+    // and are allocated directly from the bitmap.
+    if flags.contains(AllocationFlags::KERNEL) {
+        return allocate_range(1, flags).map(Frame4Kib::new);
+    }
+
+    let frame = FRAME_CACHE.with(|cache| {
+        if cache.is_empty() {
+            refill(cache);
+        }
+        cache.pop()
+    })?;
+
+    if flags.contains(AllocationFlags::ZEROED) {
+        let ptr = arch::mmu::translate_physical(frame)
+            .expect("Failed to translate physical address")
+            .as_mut_ptr::<u8>();
+
+        // SAFETY: The frame was just taken from the cache, so it is not used
+        // by anything else.
+        unsafe {
+            core::ptr::write_bytes(ptr, 0, PAGE_SIZE);
+        }
+    }
+
+    ALLOCATED_PAGES.fetch_add(1, Ordering::Relaxed);
+    FREE_PAGES.fetch_sub(1, Ordering::Relaxed);
+    Some(frame)
+}
+
+/// Refill the given frame cache with up to half of its capacity of free
+/// frames taken from the bitmap, in a single pass over the bitmap.
+fn refill(cache: &mut heapless::Vec<Frame4Kib, FRAME_CACHE_CAPACITY>) {
+    let mut bitmap = BITMAP.lock();
+    for index in bitmap.take_free(FRAME_CACHE_CAPACITY / 2) {
+        // The cache was empty and receives at most half of its capacity.
+        _ = cache.push(Frame4Kib::new(Physical::from(index2frame(index))));
+    }
+}
+
+/// Return half of the frames of the given frame cache to the bitmap. The
+/// bitmap must be locked by the caller.
+fn drain(cache: &mut heapless::Vec<Frame4Kib, FRAME_CACHE_CAPACITY>, bitmap: &mut Bitmap) {
+    for _ in 0..FRAME_CACHE_CAPACITY / 2 {
+        let Some(frame) = cache.pop() else {
+            break;
+        };
+        let index = phys2index(usize::from(frame.into_inner()));
+        bitmap.put_free(index);
+    }
 }
 
 /// Allocate a contiguous range of frames. Returns `None` if no contiguous
@@ -153,27 +221,37 @@ pub fn allocate_frame(flags: AllocationFlags) -> Option<Frame4Kib> {
 /// manager is not initialized).
 #[must_use]
 pub fn allocate_range(count: usize, flags: AllocationFlags) -> Option<Physical> {
-    if count == 0 {
-        return None;
-    }
-
-    let mut bitmap = BITMAP.lock();
-
-    // Find the first range of contiguous free frames
-    let start = bitmap.windows(count).position(|frames| {
-        frames
-            .iter()
-            .all(|info| info.flags.contains(FrameFlags::FREE))
-    })?;
+    allocate_aligned_range(count, PAGE_SIZE, flags)
+}
 
-    // Mark the frames as used and add the kernel flags to
-    // frames if requested
-    for frame in start..start + count {
-        bitmap[frame].flags.remove(FrameFlags::FREE);
-        if flags.contains(AllocationFlags::KERNEL) {
-            bitmap[frame].flags |= FrameFlags::KERNEL;
-        }
-    }
+/// Allocate a contiguous range of frames whose physical address is aligned to
+/// `align` bytes. This is used to allocate the frames of huge pages, which
+/// must be aligned to their size. See [`allocate_range`] for more details.
+///
+/// # Panics
+/// Panics if the bitmap is not initialized, or if `align` is not a power of
+/// two multiple of the page size.
+#[must_use]
+pub fn allocate_aligned_range(
+    count: usize,
+    align: usize,
+    flags: AllocationFlags,
+) -> Option<Physical> {
+    assert!(align.is_power_of_two() && align >= PAGE_SIZE);
+
+    // Find the first range of contiguous free frames, only considering the
+    // frames whose address is properly aligned as the start of the range, and
+    // add the kernel flags to the frames if requested
+    let ram_start = RAM_START.read();
+    let first = (ram_start.next_multiple_of(align) - ram_start) / PAGE_SIZE;
+    let kernel = if flags.contains(AllocationFlags::KERNEL) {
+        FrameFlags::KERNEL
+    } else {
+        FrameFlags::empty()
+    };
+    let start = BITMAP
+        .lock()
+        .allocate(count, first, align / PAGE_SIZE, kernel)?;
 
     // Zero the frames if requested
     if flags.contains(AllocationFlags::ZEROED) {
@@ -188,22 +266,83 @@ pub fn allocate_range(count: usize, flags: AllocationFlags) -> Option<Physical>
         }
     }
 
+    ALLOCATED_PAGES.fetch_add(count, Ordering::Relaxed);
+    FREE_PAGES.fetch_sub(count, Ordering::Relaxed);
     Some(Physical::from(index2frame(start)))
 }
 
-/// Deallocate a frame
+/// Take an additional reference on an allocated frame, so that it can be
+/// shared by several owners (e.g. mapped in several address spaces). Each
+/// reference must be dropped with [`frame_put`] or [`deallocate_frame`], and
+/// the frame is only freed when the last one is dropped.
+///
+/// # Panics
+/// Panics if at least one of the following conditions is met:
+/// - The frame is not page-aligned
+/// - The frame is not allocated
+/// - The frame is outside of the bitmap (kernel bug ?)
+pub fn frame_get(frame: Physical) {
+    assert!(frame.is_page_aligned());
+    BITMAP.lock().get(phys2index(usize::from(frame)));
+}
+
+/// Drop a reference on an allocated frame. If this was the last reference,
+/// the frame is put in the frame cache of the current core to be reused by
+/// the next allocations, and `true` is returned. If the cache is full, half
+/// of it is returned to the bitmap. Under memory pressure, the frame is
+/// returned directly to the bitmap instead, so that the free memory is not
+/// scattered among the caches of the cores.
 ///
 /// # Panics
 /// Panics if at least one of the following conditions is met:
 /// - The frame is not page-aligned
 /// - The frame is not allocated (double free ?)
 /// - The frame is outside of the bitmap (kernel bug ?)
+pub fn frame_put(frame: Physical) -> bool {
+    let freed = FRAME_CACHE.with(|cache| {
+        let index = phys2index(usize::from(frame));
+        let mut bitmap = BITMAP.lock();
+
+        assert!(frame.is_page_aligned());
+        debug_assert!(
+            !cache.iter().any(|cached| cached.into_inner() == frame),
+            "Frame deallocated twice"
+        );
+
+        if !bitmap.put(index) {
+            return false;
+        }
+        if pressure() != Pressure::Normal {
+            bitmap.free(index..index + 1);
+            return true;
+        }
+        bitmap.disown(index);
+        if cache.is_full() {
+            drain(cache, &mut bitmap);
+        }
+        _ = cache.push(Frame4Kib::new(frame));
+        true
+    });
+    if freed {
+        ALLOCATED_PAGES.fetch_sub(1, Ordering::Relaxed);
+        FREE_PAGES.fetch_add(1, Ordering::Relaxed);
+    }
+    freed
+}
+
+/// Deallocate a frame, which drops the reference of the caller on it (see
+/// [`frame_put`]). The frame is only freed if it is not shared anymore.
+///
+/// # Panics
+/// Panics in the same conditions as [`frame_put`].
 pub fn deallocate_frame(frame: Physical) {
-    deallocate_range(frame, 1);
+    _ = frame_put(frame);
 }
 
-/// Deallocate a contiguous range of frames starting at the given base address.
-/// If the count parameter is 0, this function does nothing.
+/// Deallocate a contiguous range of frames starting at the given base address,
+/// which drops the reference of the caller on each frame of the range. Only
+/// the frames that are not shared anymore are freed. If the count parameter is
+/// 0, this function does nothing.
 ///
 /// # Panics
 /// Panics if at least one of the following conditions is met:
@@ -213,17 +352,52 @@ pub fn deallocate_frame(frame: Physical) {
 pub fn deallocate_range(base: Physical, count: usize) {
     let start = phys2index(usize::from(base));
     let end = start + count;
-    let mut bitmap = BITMAP.lock();
 
     assert!(base.is_page_aligned());
     assert!(start + count >= start);
-    assert!(start + count <= bitmap.len());
+    let mut bitmap = BITMAP.lock();
+    for index in start..end {
+        if bitmap.put(index) {
+            bitmap.free(index..index + 1);
+            ALLOCATED_PAGES.fetch_sub(1, Ordering::Relaxed);
+            FREE_PAGES.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
 
-    (start..end).for_each(|frame| {
-        assert!(!bitmap[frame].flags.contains(FrameFlags::FREE));
-        bitmap[frame].flags.remove(FrameFlags::KERNEL);
-        bitmap[frame].flags.insert(FrameFlags::FREE);
-    });
+/// Release a contiguous range of frames that was reserved during boot and
+/// never allocated through the allocator, such as the initial ramdisk loaded
+/// by the bootloader. The frames become available for allocation. If the count
+/// parameter is 0, this function does nothing.
+///
+/// # Panics
+/// Panics if at least one of the following conditions is met:
+/// - The base address is not page-aligned
+/// - A frame of the range is already free
+/// - The range is outside of the bitmap
+pub fn release_range(base: Physical, count: usize) {
+    let start = phys2index(usize::from(base));
+    let end = start + count;
+
+    assert!(base.is_page_aligned());
+    BITMAP.lock().free(start..end);
+    FREE_PAGES.fetch_add(count, Ordering::Relaxed);
+}
+
+/// Return all the frames cached by the current core to the bitmap, so that
+/// they can be allocated as contiguous ranges or by the other cores. Returns
+/// the number of frames returned. This is done when memory runs low (see
+/// [`crate::mm::pressure`]): the caches of the other cores are not touched,
+/// but they are bypassed by the frames freed under memory pressure.
+pub fn drain_cache() -> usize {
+    FRAME_CACHE.with(|cache| {
+        let mut bitmap = BITMAP.lock();
+        let count = cache.len();
+        while let Some(frame) = cache.pop() {
+            bitmap.put_free(phys2index(usize::from(frame.into_inner())));
+        }
+        count
+    })
 }
 
 /// Return the total number of memory pages in the system
@@ -232,6 +406,34 @@ pub fn total_memory_pages() -> usize {
     TOTAL_MEMORY_PAGES.read()
 }
 
+/// Return the number of memory pages that can be allocated, including the
+/// pages cached by the CPU cores.
+#[must_use]
+pub fn free_memory_pages() -> usize {
+    FREE_PAGES.load(Ordering::Relaxed)
+}
+
+/// Return the current memory pressure, by comparing the number of free pages
+/// to the watermarks computed when the allocator was set up.
+#[must_use]
+pub fn pressure() -> Pressure {
+    let free = free_memory_pages();
+    if free < CRITICAL_WATERMARK.read() {
+        Pressure::Critical
+    } else if free < LOW_WATERMARK.read() {
+        Pressure::Low
+    } else {
+        Pressure::Normal
+    }
+}
+
+/// Return the number of memory pages currently allocated through the physical
+/// memory allocator, either by the kernel or for user tasks.
+#[must_use]
+pub fn allocated_memory_pages() -> usize {
+    ALLOCATED_PAGES.load(Ordering::Relaxed)
+}
+
 /// Return the number of memory pages that are used by the kernel and are
 /// not available for allocation, including reserved memory by the firmware
 /// or the hardware
@@ -241,11 +443,7 @@ pub fn total_memory_pages() -> usize {
 /// manager is not initialized)
 #[must_use]
 pub fn kernel_memory_pages() -> usize {
-    BITMAP
-        .lock()
-        .iter()
-        .filter(|frame| frame.flags.contains(FrameFlags::KERNEL))
-        .count()
+    BITMAP.lock().count(FrameFlags::KERNEL)
 }
 
 /// Convert a frame index to a frame address.
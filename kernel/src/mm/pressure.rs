@@ -0,0 +1,144 @@
+//! The reaction of the kernel to memory pressure. The physical memory
+//! allocator compares the number of free frames to its watermarks (see
+//! [`mm::phys::pressure`]), and this module is polled by the executor to
+//! notice when the pressure level changes. The change is then handled by the
+//! reclaim daemon, a background task of the kernel (see [`daemon`]), so that
+//! the executor itself never runs the reclaim hooks.
+//!
+//! When the pressure rises, the frames cached by the current core are returned
+//! to the allocator, and the reclaim hooks registered by the kernel are run to
+//! release the memory that the kernel keeps for performance only, such as the
+//! free slabs of the object caches. Each change of level is then notified to
+//! the tasks that subscribed to it, so that user services can release their
+//! own caches cooperatively.
+use crate::{config, future, ipc, mm, utils::lock};
+use ::syscall::{
+    ipc::Payload,
+    memory::{Pressure, PressureNotice},
+};
+use hashbrown::HashMap;
+use spin::Lazy;
+
+/// Memory that the kernel can release when the system runs low on memory.
+pub trait Reclaim: Sync {
+    /// Releases as much memory as possible, and returns the number of frames
+    /// returned to the physical memory allocator. This is called by the
+    /// executor without any lock held.
+    fn reclaim(&self) -> usize;
+}
+
+/// The registered reclaim hooks.
+static HOOKS: lock::Mutex<heapless::Vec<&'static dyn Reclaim, { config::RECLAIM_HOOKS }>> =
+    lock::Mutex::new(heapless::Vec::new());
+
+/// The tasks subscribed to the changes of the memory pressure, with the
+/// operation of the messages notifying them.
+static SUBSCRIBERS: Lazy<lock::Mutex<HashMap<future::task::Identifier, usize>>> =
+    Lazy::new(|| lock::Mutex::new(HashMap::new()));
+
+/// The pressure level when the memory pressure was last handled by the
+/// reclaim daemon. It is locked while the level change is handled.
+static LEVEL: lock::Mutex<Pressure> = lock::Mutex::new(Pressure::Normal);
+
+/// The queue where the reclaim daemon sleeps until the pressure level changes.
+static DAEMON: Lazy<future::wait::Queue> = Lazy::new(future::wait::Queue::new);
+
+/// Registers a reclaim hook, which is run each time the memory pressure rises.
+/// Hooks cannot be unregistered, so they must live as long as the kernel. If
+/// [`config::RECLAIM_HOOKS`] hooks are already registered, the hook is ignored.
+pub fn register(hook: &'static dyn Reclaim) {
+    if HOOKS.lock().push(hook).is_err() {
+        log::warn!("Too many reclaim hooks, ignoring a hook");
+    }
+}
+
+/// Subscribes the given task to the changes of the memory pressure, which are
+/// sent to it as messages with the given operation. If the task is already
+/// subscribed, the operation of its messages is replaced.
+pub fn subscribe(task: future::task::Identifier, operation: usize) {
+    SUBSCRIBERS.lock().insert(task, operation);
+}
+
+/// Unsubscribes the given task from the changes of the memory pressure.
+/// Returns whether the task was subscribed.
+pub fn unsubscribe(task: future::task::Identifier) -> bool {
+    SUBSCRIBERS.lock().remove(&task).is_some()
+}
+
+/// Unsubscribes the given task if it is subscribed. This must be called when
+/// the task terminates.
+pub fn release(task: future::task::Identifier) {
+    _ = unsubscribe(task);
+}
+
+/// Returns the frames cached by the current core to the allocator, and then
+/// runs all the reclaim hooks. Returns the number of frames returned to the
+/// physical memory allocator.
+pub fn reclaim() -> usize {
+    // The hooks are copied so that they run without the lock held, since a
+    // hook may allocate objects from a cache that is not registered yet.
+    let hooks = HOOKS.lock().clone();
+    mm::phys::drain_cache() + hooks.iter().map(|hook| hook.reclaim()).sum::<usize>()
+}
+
+/// Wakes up the reclaim daemon if the memory pressure level changed since it
+/// last handled it. This is cheap enough to be called by the executor after
+/// each task, and does nothing while the daemon is handling a change.
+pub fn poll() {
+    let changed = LEVEL
+        .try_lock()
+        .is_some_and(|level| *level != mm::phys::pressure());
+    if changed {
+        DAEMON.wake_all();
+    }
+}
+
+/// The reclaim daemon, spawned as a background task of the kernel at boot. It
+/// sleeps until [`poll`] notices that the pressure level changed, and then
+/// handles the change. It completes as soon as it is woken up while the system
+/// is going down (see [`future::executor::shutdown`]), since the memory would
+/// not be used anymore.
+pub async fn daemon() {
+    while !future::executor::shutting_down() {
+        handle();
+        future::wait::wait(&DAEMON).await;
+    }
+    log::debug!("Reclaim daemon stopped");
+}
+
+/// Handles a change of the memory pressure since the last call. If the
+/// pressure rose, the reclaim hooks are run first. The subscribed tasks are
+/// then notified of the new level, unless reclaiming brought it back to the
+/// previous one. This does nothing if the level did not change.
+fn handle() {
+    let mut level = LEVEL.lock();
+    let mut current = mm::phys::pressure();
+    if current == *level {
+        return;
+    }
+
+    if current > *level {
+        let reclaimed = reclaim();
+        current = mm::phys::pressure();
+        log::info!(
+            "Memory pressure rose, reclaimed {} frames ({} free)",
+            reclaimed,
+            mm::phys::free_memory_pages()
+        );
+        if current == *level {
+            return;
+        }
+    }
+
+    *level = current;
+    let notice = PressureNotice {
+        level: current as u64,
+        free_pages: mm::phys::free_memory_pages() as u64,
+        total_pages: mm::phys::total_memory_pages() as u64,
+    };
+    for (&task, &operation) in SUBSCRIBERS.lock().iter() {
+        // A task whose mailbox is full misses the notice, but it will receive
+        // the next one.
+        _ = ipc::message::notify(task, operation, notice.as_payload());
+    }
+}
@@ -0,0 +1,46 @@
+use crate::arch::{
+    self,
+    mmu::PAGE_SIZE,
+    target::addr::{Virtual, virt::Kernel},
+};
+
+/// Reclaims the memory of the sections of the kernel image that are only used
+/// during boot. Their pages are unmapped, so that a late call to an `init`
+/// function faults loudly instead of executing stale bytes, and their frames
+/// are poisoned and returned to the physical memory allocator.
+///
+/// The frames are filled with zeros before being released: an all-zero
+/// instruction is defined as illegal on RISC-V, so that a stale translation
+/// still cached somewhere also traps instead of executing code.
+///
+/// # Panics
+/// Panics if a page of the sections is not mapped.
+///
+/// # Safety
+/// The caller must ensure that no code or data of the sections will ever be
+/// used again. In particular, this must not be called from an `init` function,
+/// since the code of the caller is unmapped.
+pub unsafe fn init_sections() {
+    let range = arch::memory::reclaimable_range();
+    for page in range.clone().step_by(PAGE_SIZE) {
+        // SAFETY: The caller guarantees that the page is no longer used.
+        let frame = unsafe { arch::mmu::unmap_kernel(Virtual::<Kernel>::new(page)) }
+            .expect("Failed to unmap a page of the init sections");
+
+        let ptr = arch::mmu::translate_physical(frame)
+            .expect("Failed to translate physical address")
+            .as_mut_ptr::<u8>();
+
+        // SAFETY: The frame was just unmapped from the kernel image and is not
+        // used by anything else.
+        unsafe {
+            core::ptr::write_bytes(ptr, 0, PAGE_SIZE);
+        }
+        crate::mm::phys::release_range(frame.into_inner(), 1);
+    }
+
+    log::info!(
+        "Reclaimed {} KiB of memory used during boot",
+        range.len() / 1024
+    );
+}
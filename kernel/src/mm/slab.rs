@@ -0,0 +1,272 @@
+use crate::{
+    arch::{self, mmu::PAGE_SIZE},
+    mm::{self, phys::AllocationFlags},
+    utils::lock,
+};
+use alloc::vec::Vec;
+use core::{
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// A slot of a slab. A free slot stores a pointer to the next free slot of
+/// the cache, and an allocated slot stores the object itself.
+union Slot<T> {
+    next: Option<NonNull<Slot<T>>>,
+    value: ManuallyDrop<T>,
+}
+
+/// The list of free slots of an object cache.
+struct FreeList<T> {
+    /// The first free slot, or `None` if all the slots are allocated.
+    head: Option<NonNull<Slot<T>>>,
+
+    /// The number of slabs allocated by the cache.
+    slabs: usize,
+}
+
+/// SAFETY: The free list only contains pointers to unused slots that are owned
+/// by the cache, so it can be sent between threads if the objects can.
+unsafe impl<T: Send> Send for FreeList<T> {}
+
+/// A cache of objects of the same type. Objects are allocated from slabs of
+/// one frame each, split into slots of the size of the object. Compared to the
+/// general purpose heap, this avoids fragmenting the heap with objects that
+/// are frequently allocated and freed, and allocating or freeing an object is
+/// only a matter of taking or putting back a slot in a free list.
+///
+/// Slabs are only returned to the physical memory allocator when the system
+/// runs low on memory: each cache registers itself as a reclaim hook (see
+/// [`mm::pressure`]) when it allocates its first object, and then gives back
+/// the slabs whose slots are all free.
+pub struct ObjectCache<T> {
+    free: lock::Mutex<FreeList<T>>,
+
+    /// Whether the cache is registered as a reclaim hook.
+    registered: AtomicBool,
+}
+
+impl<T> ObjectCache<T> {
+    /// The number of objects stored in each slab.
+    const SLOTS_PER_SLAB: usize = PAGE_SIZE / core::mem::size_of::<Slot<T>>();
+
+    /// Create a new empty object cache. Slabs are allocated lazily when the
+    /// first objects are allocated.
+    #[must_use]
+    pub const fn new() -> Self {
+        const {
+            assert!(
+                core::mem::size_of::<Slot<T>>() <= PAGE_SIZE,
+                "Object too large for a slab"
+            );
+            assert!(core::mem::align_of::<Slot<T>>() <= PAGE_SIZE);
+        };
+        Self {
+            free: lock::Mutex::new(FreeList {
+                head: None,
+                slabs: 0,
+            }),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    /// Allocate a slot from the cache and move the given value into it.
+    ///
+    /// # Panics
+    /// Panics if the cache has no free slot and a new slab cannot be
+    /// allocated, similarly to the allocation of a `Box` when the heap is
+    /// exhausted.
+    pub fn allocate(&'static self, value: T) -> SlabBox<T>
+    where
+        T: Send,
+    {
+        if !self.registered.load(Ordering::Relaxed)
+            && !self.registered.swap(true, Ordering::Relaxed)
+        {
+            mm::pressure::register(self);
+        }
+
+        let slot = {
+            let mut free = self.free.lock();
+            if free.head.is_none() {
+                Self::grow(&mut free);
+            }
+
+            let slot = free.head.expect("Failed to allocate a slab");
+            // SAFETY: The slot is in the free list, so it is unused and its
+            // `next` field is initialized.
+            free.head = unsafe { slot.as_ref().next };
+            slot
+        };
+
+        // SAFETY: The slot was just removed from the free list, so it is not
+        // used by anything else and can hold a `T`.
+        unsafe {
+            slot.as_ptr().write(Slot {
+                value: ManuallyDrop::new(value),
+            });
+        }
+        SlabBox { slot, cache: self }
+    }
+
+    /// Returns the number of slabs allocated by the cache.
+    #[must_use]
+    pub fn slabs(&self) -> usize {
+        self.free.lock().slabs
+    }
+
+    /// Return the slabs whose slots are all free to the physical memory
+    /// allocator, and return their number. The free list is searched as a
+    /// whole, so this is slow and should only be done when memory runs low.
+    pub fn shrink(&self) -> usize {
+        let mut free = self.free.lock();
+
+        // Count the free slots of each slab, identified by its address. The
+        // memory needed for this is reserved upfront, since it is needed
+        // when memory runs low: if it cannot be allocated, nothing is freed.
+        let mut slabs: Vec<(usize, usize)> = Vec::new();
+        if slabs.try_reserve_exact(free.slabs).is_err() {
+            return 0;
+        }
+        let mut next = free.head;
+        while let Some(slot) = next {
+            let base = slot.addr().get() & !(PAGE_SIZE - 1);
+            match slabs.binary_search_by_key(&base, |&(base, _)| base) {
+                Ok(index) => slabs[index].1 += 1,
+                Err(index) => slabs.insert(index, (base, 1)),
+            }
+            // SAFETY: The slot is in the free list, so it is unused and its
+            // `next` field is initialized.
+            next = unsafe { slot.as_ref().next };
+        }
+        slabs.retain(|&(_, count)| count == Self::SLOTS_PER_SLAB);
+        if slabs.is_empty() {
+            return 0;
+        }
+
+        // Unlink the slots of the empty slabs from the free list, and then
+        // free the slabs.
+        let empty = |slot: NonNull<Slot<T>>| {
+            let base = slot.addr().get() & !(PAGE_SIZE - 1);
+            slabs.binary_search_by_key(&base, |&(base, _)| base).is_ok()
+        };
+        let mut link = &raw mut free.head;
+        // SAFETY: The links are either the head of the free list or the
+        // `next` field of a slot in the free list, which is initialized. The
+        // list is not modified by anything else while its lock is held.
+        unsafe {
+            while let Some(slot) = *link {
+                if empty(slot) {
+                    *link = slot.as_ref().next;
+                } else {
+                    link = &raw mut (*slot.as_ptr()).next;
+                }
+            }
+        }
+        for &(base, _) in &slabs {
+            let slab = core::ptr::without_provenance::<u8>(base);
+            mm::phys::deallocate_frame(arch::mmu::translate_kernel_ptr(slab));
+        }
+        free.slabs -= slabs.len();
+        slabs.len()
+    }
+
+    /// Allocate a new slab and add all its slots to the free list. If no frame
+    /// is available, the free list is left empty.
+    fn grow(free: &mut FreeList<T>) {
+        let Some(frame) = mm::phys::allocate_frame(AllocationFlags::KERNEL) else {
+            return;
+        };
+        let Some(base) = arch::mmu::translate_physical(frame) else {
+            mm::phys::deallocate_frame(frame.into_inner());
+            return;
+        };
+
+        let slots = base.as_mut_ptr::<Slot<T>>();
+        for i in (0..Self::SLOTS_PER_SLAB).rev() {
+            // SAFETY: The slab was just allocated and is large enough to hold
+            // `SLOTS_PER_SLAB` slots, and its address is aligned to a page
+            // boundary, so every slot is properly aligned.
+            unsafe {
+                let slot = slots.add(i);
+                slot.write(Slot { next: free.head });
+                free.head = Some(NonNull::new_unchecked(slot));
+            }
+        }
+        free.slabs += 1;
+    }
+
+    /// Put the given slot back in the free list.
+    ///
+    /// # Safety
+    /// The slot must have been allocated from this cache, and its value must
+    /// have been dropped or moved out.
+    unsafe fn release(&self, slot: NonNull<Slot<T>>) {
+        let mut free = self.free.lock();
+        slot.as_ptr().write(Slot { next: free.head });
+        free.head = Some(slot);
+    }
+}
+
+impl<T: Send> mm::pressure::Reclaim for ObjectCache<T> {
+    fn reclaim(&self) -> usize {
+        self.shrink()
+    }
+}
+
+impl<T> Default for ObjectCache<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An object allocated from an [`ObjectCache`]. It behaves like a `Box`: it
+/// owns the object, and the object is dropped and its slot is returned to the
+/// cache when the `SlabBox` is dropped.
+pub struct SlabBox<T: 'static> {
+    slot: NonNull<Slot<T>>,
+    cache: &'static ObjectCache<T>,
+}
+
+/// SAFETY: A `SlabBox` uniquely owns its object, like a `Box`.
+unsafe impl<T: Send> Send for SlabBox<T> {}
+
+/// SAFETY: A `SlabBox` uniquely owns its object, like a `Box`.
+unsafe impl<T: Sync> Sync for SlabBox<T> {}
+
+impl<T> Deref for SlabBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The slot is allocated and holds an initialized value for
+        // the whole lifetime of the `SlabBox`.
+        unsafe { &self.slot.as_ref().value }
+    }
+}
+
+impl<T> DerefMut for SlabBox<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The slot is allocated and holds an initialized value for
+        // the whole lifetime of the `SlabBox`, which uniquely owns it.
+        unsafe { &mut self.slot.as_mut().value }
+    }
+}
+
+impl<T> Drop for SlabBox<T> {
+    fn drop(&mut self) {
+        // SAFETY: The value is initialized and is never used again after
+        // being dropped, and the slot was allocated from `self.cache`.
+        unsafe {
+            ManuallyDrop::drop(&mut self.slot.as_mut().value);
+            self.cache.release(self.slot);
+        }
+    }
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for SlabBox<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        (**self).fmt(f)
+    }
+}
@@ -0,0 +1,352 @@
+use crate::{
+    arch::{
+        self,
+        mmu::{Flags, MapError, PAGE_SIZE, Rights, UnmapError},
+        target::{
+            addr::{
+                Frame2Mib, Frame4Kib, Physical, Virtual,
+                virt::{Kernel, User},
+            },
+            mmu::RootTable,
+        },
+    },
+    mm::{self, phys::AllocationFlags},
+    user::layout::Layout,
+};
+use alloc::boxed::Box;
+
+/// A page mapped in an address space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    /// The 4 KiB frame mapped at the page.
+    pub frame: Frame4Kib,
+
+    /// The rights granted to the page.
+    pub rights: Rights,
+}
+
+/// The user address space of a thread. It owns the root page table of the
+/// thread and all the frames mapped in its user half, which are released when
+/// the address space is dropped.
+///
+/// This is the only way the rest of the kernel should modify the user mappings
+/// of a thread: it allocates the frames backing the pages itself, keeps the
+/// page tables consistent and frees the intermediate tables that become empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressSpace {
+    root: Box<RootTable>,
+    layout: Layout,
+}
+
+impl AddressSpace {
+    /// Create a new address space with an empty user half and a newly chosen
+    /// user layout. The kernel half is shared with all other address spaces.
+    ///
+    /// # Panics
+    /// Panics if the kernel page table is not initialized.
+    #[must_use]
+    pub fn new() -> Self {
+        let mut root = Box::new(RootTable::empty());
+        root.copy_kernel_space();
+        Self {
+            root,
+            layout: Layout::new(),
+        }
+    }
+
+    /// Returns the placement of the user stack and of the anonymous mappings
+    /// in the address space.
+    #[must_use]
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    /// Returns the root page table of the address space, to be used by the
+    /// MMU when the address space is active.
+    #[must_use]
+    pub fn root(&self) -> &RootTable {
+        &self.root
+    }
+
+    /// Map `size` bytes of fresh zeroed memory starting at `start` with the
+    /// given rights. The range is rounded to whole pages, and is mapped with
+    /// 2 MiB pages where the range is large and aligned enough and contiguous
+    /// memory is available.
+    ///
+    /// # Errors
+    /// Returns [`MapError::AlreadyMapped`] if any page of the range is already
+    /// mapped, in which case nothing is mapped, or [`MapError::OutOfMemory`]
+    /// if the kernel ran out of memory. In the latter case, the pages mapped
+    /// by this call are unmapped when possible, and are otherwise released
+    /// along with the address space.
+    pub fn map_range(
+        &mut self,
+        start: Virtual<User>,
+        size: usize,
+        rights: Rights,
+    ) -> Result<(), MapError> {
+        let (start, end) = page_bounds(start, size);
+        if (start..end)
+            .step_by(PAGE_SIZE)
+            .any(|page| self.query(Virtual::<User>::new(page)).is_some())
+        {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        let mut page = start;
+        while page < end {
+            match self.map_page(page, end, rights) {
+                Ok(size) => page += size,
+                Err(error) => {
+                    _ = self.unmap_range(Virtual::<User>::new(start), page - start);
+                    return Err(error);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Map the `count` frames starting at `base`, which hold the registers of
+    /// a device, at `start` with the given rights. The frames are not owned by
+    /// the address space: they are not freed when unmapped, nor when the
+    /// address space is released.
+    ///
+    /// # Errors
+    /// Returns [`MapError::AlreadyMapped`] if any page of the range is already
+    /// mapped, in which case nothing is mapped, or [`MapError::OutOfMemory`]
+    /// if a page table could not be allocated, in which case the pages mapped
+    /// by this call are unmapped.
+    ///
+    /// # Safety
+    /// The caller must ensure that the frames hold the registers of a device
+    /// that the task is allowed to drive, and not memory used by the kernel
+    /// or by another task.
+    pub unsafe fn map_device(
+        &mut self,
+        start: Virtual<User>,
+        base: Physical,
+        count: usize,
+        rights: Rights,
+    ) -> Result<(), MapError> {
+        let start = start.as_usize();
+        let end = start + count * PAGE_SIZE;
+        if (start..end)
+            .step_by(PAGE_SIZE)
+            .any(|page| self.query(Virtual::<User>::new(page)).is_some())
+        {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        for (i, page) in (start..end).step_by(PAGE_SIZE).enumerate() {
+            let frame = Frame4Kib::new(Physical::new(usize::from(base) + i * PAGE_SIZE));
+
+            // SAFETY: The range was verified to be unmapped, and the caller
+            // guarantees that the frames belong to a device.
+            let mapped = unsafe {
+                arch::mmu::map(
+                    &mut self.root,
+                    Virtual::<User>::new(page),
+                    frame,
+                    rights,
+                    Flags::DEVICE,
+                )
+            };
+            if let Err(error) = mapped {
+                _ = self.unmap_range(Virtual::<User>::new(start), page - start);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Unmap the pages covering `size` bytes starting at `start` and free the
+    /// frames mapped to them. Pages of the range that are not mapped are
+    /// ignored. Returns the number of pages unmapped.
+    ///
+    /// # Errors
+    /// Returns [`UnmapError::OutOfMemory`] if a huge page partially covered
+    /// by the range could not be split. The pages before the huge page are
+    /// unmapped, and the others are left untouched.
+    pub fn unmap_range(&mut self, start: Virtual<User>, size: usize) -> Result<usize, UnmapError> {
+        let (start, end) = page_bounds(start, size);
+        let mut unmapped = 0;
+        for page in (start..end).step_by(PAGE_SIZE) {
+            let page = Virtual::<User>::new(page);
+            let device = arch::mmu::is_device(&self.root, page);
+
+            // SAFETY: The frames mapped in the user half are owned by the
+            // address space, and the caller asked for them to be unmapped.
+            match unsafe { arch::mmu::unmap(&mut self.root, page) } {
+                Ok(frame) => {
+                    if !device {
+                        mm::phys::deallocate_frame(frame.into_inner());
+                    }
+                    unmapped += 1;
+                }
+                Err(UnmapError::NotMapped) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(unmapped)
+    }
+
+    /// Change the rights of the pages covering `size` bytes starting at
+    /// `start`. The translations of the pages are invalidated on all harts
+    /// before returning, so the new rights are enforced immediately.
+    ///
+    /// # Errors
+    /// Returns [`UnmapError::NotMapped`] if a page of the range is not mapped,
+    /// in which case no rights are changed, or [`UnmapError::OutOfMemory`] if
+    /// a huge page partially covered by the range could not be split, in which
+    /// case the rights of the pages before the huge page have already been
+    /// changed.
+    pub fn protect(
+        &mut self,
+        start: Virtual<User>,
+        size: usize,
+        rights: Rights,
+    ) -> Result<(), UnmapError> {
+        let (start, end) = page_bounds(start, size);
+        if (start..end)
+            .step_by(PAGE_SIZE)
+            .any(|page| self.query(Virtual::<User>::new(page)).is_none())
+        {
+            return Err(UnmapError::NotMapped);
+        }
+
+        for page in (start..end).step_by(PAGE_SIZE) {
+            // SAFETY: Removing rights from a user page can only make the user
+            // thread fault, which is handled by the page fault handler.
+            unsafe {
+                arch::mmu::protect(&mut self.root, Virtual::<User>::new(page), rights)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the mapping of the page containing the given address, or
+    /// `None` if the address is not mapped.
+    #[must_use]
+    pub fn query(&self, address: Virtual<User>) -> Option<Mapping> {
+        arch::mmu::translate(&self.root, address).map(|(frame, rights)| Mapping { frame, rights })
+    }
+
+    /// Copy the given data into the address space at the given address,
+    /// regardless of the rights of the pages. This is used to initialize the
+    /// memory of a thread before it runs.
+    ///
+    /// Returns `false` if a page covered by the data is not mapped, in which
+    /// case the data may have been partially copied.
+    #[must_use]
+    pub fn write(&mut self, address: Virtual<User>, data: &[u8]) -> bool {
+        let mut copied = 0;
+        while copied < data.len() {
+            let address = address.as_usize() + copied;
+            let offset = address % PAGE_SIZE;
+            let len = (PAGE_SIZE - offset).min(data.len() - copied);
+            let Some(mapping) = self.query(Virtual::<User>::new(address)) else {
+                return false;
+            };
+            let Some(page) = arch::mmu::translate_physical(mapping.frame) else {
+                return false;
+            };
+
+            // SAFETY: The frame is mapped in the address space, so it is owned
+            // by it, and the copy does not cross the page boundary.
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    data.as_ptr().add(copied),
+                    page.as_mut_ptr::<u8>().add(offset),
+                    len,
+                );
+            }
+
+            // The code written into an executable page, such as the segments
+            // of a program being loaded, must be visible to the instruction
+            // fetches of the threads that will execute it.
+            if mapping.rights.contains(Rights::EXECUTE) {
+                arch::mmu::sync_instructions(Virtual::<Kernel>::new(page.as_usize() + offset), len);
+            }
+            copied += len;
+        }
+        true
+    }
+
+    /// Release a part of the user half of the address space, freeing all the
+    /// frames and tables mapped in it. Returns the number of frames released.
+    /// See [`arch::mmu::release_user_space_part`] for more details.
+    ///
+    /// # Safety
+    /// The caller must ensure that the memory mapped in the given part is no
+    /// longer in use.
+    pub unsafe fn release_part(&mut self, part: usize) -> usize {
+        arch::mmu::release_user_space_part(&mut self.root, part)
+    }
+
+    /// Map a single page of fresh zeroed memory at `page`, in a range ending
+    /// at `end`. A 2 MiB page is used if the address is aligned to 2 MiB, if
+    /// the page fits in the range and if enough contiguous memory is
+    /// available. Returns the size of the page mapped.
+    fn map_page(&mut self, page: usize, end: usize, rights: Rights) -> Result<usize, MapError> {
+        if page.is_multiple_of(Frame2Mib::SIZE) && page + Frame2Mib::SIZE <= end {
+            let count = Frame2Mib::SIZE / PAGE_SIZE;
+            if let Some(frame) =
+                mm::phys::allocate_aligned_range(count, Frame2Mib::SIZE, AllocationFlags::ZEROED)
+            {
+                // SAFETY: The frames were just allocated and the range was
+                // verified to be unmapped.
+                let mapped = unsafe {
+                    arch::mmu::map(
+                        &mut self.root,
+                        Virtual::<User>::new(page),
+                        Frame4Kib::new(frame),
+                        rights,
+                        Flags::HUGE_2MB,
+                    )
+                };
+                return match mapped {
+                    Ok(()) => Ok(Frame2Mib::SIZE),
+                    Err(error) => {
+                        mm::phys::deallocate_range(frame, count);
+                        Err(error)
+                    }
+                };
+            }
+        }
+
+        let frame =
+            mm::phys::allocate_frame(AllocationFlags::ZEROED).ok_or(MapError::OutOfMemory)?;
+
+        // SAFETY: The frame was just allocated and the range was verified to
+        // be unmapped.
+        let mapped = unsafe {
+            arch::mmu::map(
+                &mut self.root,
+                Virtual::<User>::new(page),
+                frame,
+                rights,
+                Flags::empty(),
+            )
+        };
+        match mapped {
+            Ok(()) => Ok(PAGE_SIZE),
+            Err(error) => {
+                mm::phys::deallocate_frame(frame.into_inner());
+                Err(error)
+            }
+        }
+    }
+}
+
+impl Default for AddressSpace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the page-aligned bounds of the range of `size` bytes starting at
+/// `start`.
+fn page_bounds(start: Virtual<User>, size: usize) -> (usize, usize) {
+    let end = (start.as_usize() + size).next_multiple_of(PAGE_SIZE);
+    (start.as_usize() & !(PAGE_SIZE - 1), end)
+}
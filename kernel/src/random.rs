@@ -0,0 +1,133 @@
+use crate::{arch, cmdline, config, utils::lock};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use rand_chacha::{
+    ChaCha20Rng,
+    rand_core::{RngCore, SeedableRng},
+};
+
+/// The increment of the state of the generator between two numbers, as
+/// defined by the `SplitMix64` algorithm.
+const GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// The state of the generator. It is seeded at boot and advanced atomically,
+/// so that numbers can be generated from any core without locking.
+static STATE: AtomicU64 = AtomicU64::new(0);
+
+/// The entropy pool from which the random bytes given to user space are drawn.
+/// It is a `ChaCha20` generator keyed at boot, and rekeyed with the entropy
+/// collected since each time bytes are drawn from it. Contrary to the
+/// generator above, its output does not reveal its state.
+static POOL: lock::Mutex<Option<ChaCha20Rng>> = lock::Mutex::new(None);
+
+/// The entropy collected since the pool was last rekeyed, as a hash of the
+/// samples given to [`add_entropy`]. Zero means that nothing was collected.
+static JITTER: AtomicU64 = AtomicU64::new(0);
+
+/// Whether the generators are deterministic (see
+/// [`config::DETERMINISTIC_RANDOM`]).
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+/// Seeds the generator and the entropy pool with the given bytes, typically
+/// provided by the bootloader, mixed with the current value of the time
+/// counter. Without a seed, they only rely on the time elapsed since the
+/// computer was started, which is much easier to predict.
+///
+/// If the generators are deterministic, the seed and the time counter are
+/// ignored and the generators always start from the same state.
+pub fn seed(bytes: &[u8]) {
+    let deterministic =
+        cmdline::flag("deterministic-random").unwrap_or(config::DETERMINISTIC_RANDOM);
+    DETERMINISTIC.store(deterministic, Ordering::Relaxed);
+    let (ticks, bytes) = if deterministic {
+        (0, &[][..])
+    } else {
+        (arch::timer::current_time_ticks(), bytes)
+    };
+
+    STATE.store(absorb(mix(ticks), bytes), Ordering::Relaxed);
+
+    // Each word of the key of the pool is derived from a different initial
+    // state, so that the words differ from each other and from the state of
+    // the generator above.
+    let mut key = [0; 32];
+    for (word, domain) in key.chunks_mut(8).zip(1..) {
+        let state = absorb(mix(ticks ^ GAMMA.wrapping_mul(domain)), bytes);
+        word.copy_from_slice(&state.to_le_bytes());
+    }
+    *POOL.lock() = Some(ChaCha20Rng::from_seed(key));
+}
+
+/// Adds a sample of entropy to the pool, typically the value of the time
+/// counter when an interrupt is handled, whose lowest bits are hard to
+/// predict. Samples are only hashed here, and are mixed into the pool the next
+/// time bytes are drawn from it. This never blocks, so that it can be called
+/// from an interrupt handler, and does nothing if the generators are
+/// deterministic.
+pub fn add_entropy(sample: u64) {
+    if !DETERMINISTIC.load(Ordering::Relaxed) {
+        _ = JITTER.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |jitter| {
+            Some(mix(jitter ^ sample))
+        });
+    }
+}
+
+/// Fills the given buffer with random bytes drawn from the entropy pool. The
+/// entropy collected since the last call is mixed into the pool first.
+///
+/// # Panics
+/// Panics if the pool was not seeded yet.
+pub fn fill(buffer: &mut [u8]) {
+    let mut pool = POOL.lock();
+    let rng = pool.as_mut().expect("The entropy pool is not seeded");
+
+    let jitter = JITTER.swap(0, Ordering::Relaxed);
+    if jitter != 0 {
+        let mut key = [0; 32];
+        rng.fill_bytes(&mut key);
+        for (byte, sample) in key.iter_mut().zip(jitter.to_le_bytes()) {
+            *byte ^= sample;
+        }
+        *rng = ChaCha20Rng::from_seed(key);
+    }
+
+    rng.fill_bytes(buffer);
+}
+
+/// Returns a pseudo-random number generated with the `SplitMix64` algorithm.
+/// This is used to randomize the layout of the address spaces, and must not
+/// be used for anything that requires a cryptographically secure generator.
+#[must_use]
+pub fn next() -> u64 {
+    mix(STATE
+        .fetch_add(GAMMA, Ordering::Relaxed)
+        .wrapping_add(GAMMA))
+}
+
+/// Returns a pseudo-random number lower than the given bound, which must not
+/// be zero. The distribution is slightly biased unless the bound is a power
+/// of two, which does not matter for the uses of this generator.
+///
+/// # Panics
+/// Panics if the bound is zero.
+#[must_use]
+pub fn below(bound: usize) -> usize {
+    let bound = u64::try_from(bound).expect("The bound does not fit in 64 bits");
+    usize::try_from(next() % bound).expect("The number does not fit in an usize")
+}
+
+/// Mixes the given bytes into the given state, eight bytes at a time.
+fn absorb(mut state: u64, bytes: &[u8]) -> u64 {
+    for chunk in bytes.chunks(8) {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        state = mix(state ^ u64::from_le_bytes(word));
+    }
+    state
+}
+
+/// Scrambles the bits of the given value, using the finalizer of `SplitMix64`.
+const fn mix(mut value: u64) -> u64 {
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
@@ -0,0 +1,95 @@
+use crate::{arch, config};
+use ::syscall::table::TABLE;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// The activity counters of each core, indexed by the index of the core. Each
+/// core only increments its own counters, so that hot paths like syscalls do
+/// not contend on shared cache lines, and the counters of all cores are summed
+/// when they are read.
+static COUNTERS: [Counters; config::MAX_CPUS] = [const { Counters::new() }; config::MAX_CPUS];
+
+/// The activity counters of a core.
+struct Counters {
+    /// The number of invocations of each syscall, in the order of [`TABLE`].
+    syscalls: [AtomicU64; TABLE.len()],
+
+    /// The number of syscalls invoked, including the unknown ones.
+    total_syscalls: AtomicU64,
+
+    /// The number of times the executor switched to a task.
+    context_switches: AtomicU64,
+
+    /// The number of IPC messages delivered, excluding replies.
+    ipc_messages: AtomicU64,
+}
+
+impl Counters {
+    /// Creates a new set of counters, all set to zero.
+    const fn new() -> Self {
+        Self {
+            syscalls: [const { AtomicU64::new(0) }; TABLE.len()],
+            total_syscalls: AtomicU64::new(0),
+            context_switches: AtomicU64::new(0),
+            ipc_messages: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Returns the counters of the current core.
+fn current() -> &'static Counters {
+    &COUNTERS[arch::cpu::id()]
+}
+
+/// Counts an invocation of the syscall with the given number.
+pub fn count_syscall(op: usize) {
+    let counters = current();
+    counters.total_syscalls.fetch_add(1, Ordering::Relaxed);
+    if let Some(index) = TABLE.iter().position(|descriptor| descriptor.op == op) {
+        counters.syscalls[index].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Counts a switch of the executor to a task.
+pub fn count_context_switch() {
+    current().context_switches.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts the delivery of an IPC message.
+pub fn count_ipc_message() {
+    current().ipc_messages.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns the number of syscalls invoked since boot on all cores.
+#[must_use]
+pub fn syscalls() -> u64 {
+    sum(|counters| &counters.total_syscalls)
+}
+
+/// Returns the number of invocations of each syscall since boot on all cores,
+/// along with the number of the syscall, in the order of [`TABLE`].
+pub fn syscall_counts() -> impl Iterator<Item = (usize, u64)> {
+    TABLE
+        .iter()
+        .enumerate()
+        .map(|(index, descriptor)| (descriptor.op, sum(|counters| &counters.syscalls[index])))
+}
+
+/// Returns the number of context switches since boot on all cores.
+#[must_use]
+pub fn context_switches() -> u64 {
+    sum(|counters| &counters.context_switches)
+}
+
+/// Returns the number of IPC messages delivered since boot on all cores.
+#[must_use]
+pub fn ipc_messages() -> u64 {
+    sum(|counters| &counters.ipc_messages)
+}
+
+/// Sums the given counter over all cores.
+fn sum(counter: impl Fn(&Counters) -> &AtomicU64) -> u64 {
+    COUNTERS
+        .iter()
+        .map(|counters| counter(counters).load(Ordering::Relaxed))
+        .sum()
+}
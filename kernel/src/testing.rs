@@ -0,0 +1,149 @@
+use crate::{
+    arch::{self, trap::FaultKind},
+    config,
+    future::{task::Identifier, user::Exit},
+};
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The name of the environment variable that gives to the `integration`
+/// program the name of the test case that it must run.
+pub const CASE_VAR: &str = "TEST_CASE";
+
+/// The test cases of the integration test suite. Each case is run by its own
+/// instance of the `integration` program, and passes if the task terminates
+/// as expected.
+pub const TESTS: &[Test] = &[
+    Test {
+        name: "ipc",
+        expected: Expected::Exit(0),
+    },
+    Test {
+        name: "memory",
+        expected: Expected::Exit(0),
+    },
+    Test {
+        name: "service-rename",
+        expected: Expected::Exit(0),
+    },
+    Test {
+        name: "reconnect",
+        expected: Expected::Exit(0),
+    },
+    Test {
+        name: "spawn-exit",
+        expected: Expected::Exit(0),
+    },
+    Test {
+        name: "panic",
+        expected: Expected::Exit(-1),
+    },
+    Test {
+        name: "null-dereference",
+        expected: Expected::Fault(FaultKind::PageFault),
+    },
+    Test {
+        name: "read-only-write",
+        expected: Expected::Fault(FaultKind::PageFault),
+    },
+    Test {
+        name: "illegal-instruction",
+        expected: Expected::Fault(FaultKind::IllegalInstruction),
+    },
+];
+
+/// The test cases that are still running, with the identifier of the task
+/// running each of them.
+static RUNNING: spin::Mutex<Vec<(Identifier, &Test)>> = spin::Mutex::new(Vec::new());
+
+/// The number of test cases that passed.
+static PASSED: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of test cases that failed.
+static FAILED: AtomicUsize = AtomicUsize::new(0);
+
+/// A test case of the integration test suite.
+#[derive(Debug)]
+pub struct Test {
+    /// The name of the test case, given to the `integration` program in the
+    /// [`CASE_VAR`] environment variable.
+    pub name: &'static str,
+
+    /// How the task running the test case must terminate for it to pass.
+    pub expected: Expected,
+}
+
+/// How the task running a test case is expected to terminate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    /// The task exits with the given code.
+    Exit(i32),
+
+    /// The task is terminated by the kernel because of a fault of the given
+    /// kind, whatever the faulting address is.
+    Fault(FaultKind),
+}
+
+impl Expected {
+    /// Returns whether the given exit status is the expected one.
+    #[must_use]
+    pub fn matches(self, exit: Exit) -> bool {
+        match (self, exit) {
+            (Self::Exit(expected), Exit::Terminate(code)) => expected == code,
+            (Self::Fault(expected), Exit::Fault { kind, .. }) => expected == kind,
+            _ => false,
+        }
+    }
+}
+
+/// Records that the given test case is being run by the task with the given
+/// identifier.
+pub fn started(id: Identifier, test: &'static Test) {
+    RUNNING.lock().push((id, test));
+}
+
+/// Notifies the test suite that the given task has terminated. If the task
+/// was running a test case, the result of the case is logged, and the
+/// computer is shut down once all cases are done with a status telling
+/// whether all of them passed. This does nothing unless
+/// [`config::INTEGRATION_TEST`] is enabled.
+pub fn task_terminated(id: Identifier, exit: Exit) {
+    if !config::INTEGRATION_TEST {
+        return;
+    }
+
+    let mut running = RUNNING.lock();
+    let Some(index) = running.iter().position(|(task, _)| *task == id) else {
+        return;
+    };
+    let (_, test) = running.swap_remove(index);
+    let done = running.is_empty();
+    drop(running);
+
+    if test.expected.matches(exit) {
+        PASSED.fetch_add(1, Ordering::Relaxed);
+        log::info!("test {} ... ok", test.name);
+    } else {
+        FAILED.fetch_add(1, Ordering::Relaxed);
+        log::error!(
+            "test {} ... FAILED (expected {:?}, got {:?})",
+            test.name,
+            test.expected,
+            exit
+        );
+    }
+
+    if done {
+        report();
+    }
+}
+
+/// Logs the summary of the test suite and shuts down the computer, reporting
+/// to the emulator whether all test cases passed.
+fn report() -> ! {
+    let passed = PASSED.load(Ordering::Relaxed);
+    let failed = FAILED.load(Ordering::Relaxed);
+    let status = if failed == 0 { "ok" } else { "FAILED" };
+    log::info!("test result: {status}. {passed} passed; {failed} failed");
+    arch::exit(failed == 0)
+}
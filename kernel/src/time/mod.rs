@@ -5,6 +5,8 @@ use core::{
 
 use crate::arch;
 
+pub mod wheel;
+
 /// A measurement of a monotonically nondecreasing clock. This is very
 /// similar to `std::time::Instant`, but tailored for kernel use.
 ///
@@ -56,6 +58,13 @@ impl Instant {
     pub fn duration_until(&self, later: Instant) -> Duration {
         Duration::from_nanos(later.0.saturating_sub(self.0))
     }
+
+    /// Returns the number of nanoseconds elapsed between the boot of the
+    /// system and this instant.
+    #[must_use]
+    pub const fn as_nanos(&self) -> u64 {
+        self.0
+    }
 }
 
 impl Add<Duration> for Instant {
@@ -0,0 +1,185 @@
+//! A hashed timing wheel, which keeps the deadlines of the timers of the
+//! kernel, and of the tasks of the kernel sleeping until a deadline. Deadlines are hashed into a fixed number of slots according to
+//! their tick, so that scheduling a deadline is cheap and only the slots of the
+//! ticks elapsed since the last poll are searched for expired deadlines. A
+//! deadline more than one rotation away simply stays in its slot until a later
+//! rotation.
+//!
+//! The wheel is polled before a thread is resumed, after each timer interrupt
+//! as work deferred to the executor (see [`crate::future::deferred`]), and
+//! while the core is idle. The arch timer is armed for the earliest deadline,
+//! so that a thread is interrupted and an idle core wakes up in time (see
+//! [`crate::watchdog::arm`]).
+use crate::{future, time::Instant, user, utils::lock, watchdog};
+use alloc::vec::Vec;
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// The number of slots of the wheel.
+const SLOTS: usize = 256;
+
+/// The duration of a tick of the wheel, in nanoseconds. Deadlines are not
+/// rounded to the tick: a deadline expires at the first poll after it passed.
+const TICK: u64 = 1_000_000;
+
+/// The wheel of the kernel.
+static WHEEL: lock::Mutex<Wheel> = lock::Mutex::new(Wheel::new());
+
+/// The earliest deadline in the wheel, in nanoseconds since boot, or
+/// `u64::MAX` if the wheel is empty. It is only written with the wheel
+/// locked, but can be read without locking so that polling an idle wheel
+/// is cheap.
+static NEXT: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// What happens when a deadline of the wheel expires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expiry {
+    /// The timer with the given identifier expires (see [`user::timer`]).
+    Timer(usize),
+
+    /// The task with the given identifier is woken up (see [`wake_at`]).
+    Wake(future::task::Identifier),
+}
+
+/// A deadline scheduled in the wheel.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    /// When the deadline expires.
+    deadline: Instant,
+
+    /// What happens when it expires.
+    expiry: Expiry,
+}
+
+/// The slots of the wheel, and the tick up to which they were searched.
+#[derive(Debug)]
+struct Wheel {
+    /// The deadlines, hashed by tick.
+    slots: [Vec<Entry>; SLOTS],
+
+    /// The tick of the last poll. The slots of the ticks between this one and
+    /// the current one must be searched at the next poll.
+    tick: u64,
+}
+
+impl Wheel {
+    /// Creates an empty wheel.
+    const fn new() -> Self {
+        Self {
+            slots: [const { Vec::new() }; SLOTS],
+            tick: 0,
+        }
+    }
+
+    /// Returns the slot of the given tick.
+    #[allow(clippy::cast_possible_truncation)]
+    const fn slot(tick: u64) -> usize {
+        (tick % SLOTS as u64) as usize
+    }
+
+    /// Removes the entries whose deadline passed at the given instant from the
+    /// slots of the ticks elapsed since the last poll, and returns them.
+    fn expire(&mut self, now: Instant) -> Vec<Entry> {
+        let tick = now.as_nanos() / TICK;
+        let elapsed = tick.saturating_sub(self.tick).min(SLOTS as u64 - 1);
+        let mut expired = Vec::new();
+        for tick in tick - elapsed..=tick {
+            expired
+                .extend(self.slots[Self::slot(tick)].extract_if(.., |entry| entry.deadline <= now));
+        }
+        self.tick = tick;
+        expired
+    }
+
+    /// Returns the earliest deadline in the wheel, in nanoseconds since boot,
+    /// or `u64::MAX` if the wheel is empty.
+    fn earliest(&self) -> u64 {
+        self.slots
+            .iter()
+            .flatten()
+            .map(|entry| entry.deadline.as_nanos())
+            .min()
+            .unwrap_or(u64::MAX)
+    }
+}
+
+/// Schedules the expiration of the given timer at the given deadline. A
+/// deadline that already passed expires at the next poll.
+pub fn schedule(deadline: Instant, timer: usize) {
+    insert(deadline, Expiry::Timer(timer));
+}
+
+/// Wakes up the given task once the given deadline passes, allowing a task of
+/// the kernel to stop waiting for something after a deadline. The wake-up
+/// cannot be cancelled: the task must expect to be woken up spuriously.
+pub fn wake_at(deadline: Instant, task: future::task::Identifier) {
+    insert(deadline, Expiry::Wake(task));
+}
+
+/// Inserts an entry expiring at the given deadline into the wheel.
+fn insert(deadline: Instant, expiry: Expiry) {
+    let mut wheel = WHEEL.lock();
+
+    // A deadline whose slot was already searched is put in the slot of the
+    // current tick, which is searched again at the next poll.
+    let tick = (deadline.as_nanos() / TICK).max(wheel.tick);
+    wheel.slots[Wheel::slot(tick)].push(Entry { deadline, expiry });
+    NEXT.fetch_min(deadline.as_nanos(), Ordering::Relaxed);
+}
+
+/// Removes the expiration of the given timer scheduled at the given deadline,
+/// if it was not expired yet.
+pub fn cancel(deadline: Instant, timer: usize) {
+    let mut wheel = WHEEL.lock();
+    let tick = (deadline.as_nanos() / TICK).max(wheel.tick);
+    let slot = &mut wheel.slots[Wheel::slot(tick)];
+    slot.retain(|entry| entry.expiry != Expiry::Timer(timer) || entry.deadline != deadline);
+    if deadline.as_nanos() == NEXT.load(Ordering::Relaxed) {
+        NEXT.store(wheel.earliest(), Ordering::Relaxed);
+    }
+}
+
+/// Returns the duration until the earliest deadline of the wheel, which is
+/// zero if it already passed, or `None` if the wheel is empty.
+#[must_use]
+pub fn until_next() -> Option<Duration> {
+    match NEXT.load(Ordering::Relaxed) {
+        u64::MAX => None,
+        next => Some(Duration::from_nanos(
+            next.saturating_sub(Instant::now().as_nanos()),
+        )),
+    }
+}
+
+/// Expires the timers whose deadline passed, and rearms the arch timer for
+/// the next deadline. This does nothing if no deadline passed, or if the wheel
+/// is being polled by another core.
+pub fn poll() {
+    let now = Instant::now();
+    if now.as_nanos() < NEXT.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let expired = {
+        let Some(mut wheel) = WHEEL.try_lock() else {
+            return;
+        };
+        let expired = wheel.expire(now);
+        NEXT.store(wheel.earliest(), Ordering::Relaxed);
+        expired
+    };
+
+    // The timers are expired with the wheel unlocked, since expiring a
+    // periodic timer schedules its next deadline.
+    for entry in expired {
+        match entry.expiry {
+            Expiry::Timer(timer) => user::timer::expire(timer, entry.deadline, now),
+            Expiry::Wake(task) => {
+                future::executor::wake(task);
+            }
+        }
+    }
+    watchdog::arm();
+}
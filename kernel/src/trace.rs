@@ -0,0 +1,229 @@
+use crate::{
+    arch, cmdline, config,
+    future::{self, task::Identifier},
+    time::Instant,
+    utils::lock,
+};
+use ::syscall::trace::{Event, EventKind};
+use alloc::{collections::VecDeque, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether trace events are recorded. It defaults to [`config::TRACING`] and
+/// can be overridden at boot with the `trace` option of the kernel command
+/// line, so that tracing can be enabled without recompiling the kernel.
+static ENABLED: AtomicBool = AtomicBool::new(config::TRACING);
+
+/// Whether the syscalls of a task were traced since boot (see [`set_traced`]).
+/// The trace buffers can then be drained even if tracing is disabled, since
+/// they hold the events of the traced tasks.
+static TASKS_TRACED: AtomicBool = AtomicBool::new(false);
+
+future::local::task_local! {
+    /// Whether the syscalls of the task are traced in detail, shared by all
+    /// the threads of the task.
+    shared static TRACED: AtomicBool = AtomicBool::new(false);
+}
+
+/// The trace buffer of each core, indexed by the index of the core. Events are
+/// recorded in binary form without formatting anything, so that tracing hot
+/// paths like syscalls and IPC does not change their timing as much as logging
+/// does. The buffers are drained from user space with the `TraceRead` syscall.
+static BUFFERS: [lock::Mutex<Buffer>; config::MAX_CPUS] =
+    [const { lock::Mutex::new(Buffer::new()) }; config::MAX_CPUS];
+
+/// The trace buffer of a core. Its storage is allocated when the first event
+/// is recorded, and it never holds more than
+/// [`config::TRACE_BUFFER_CAPACITY`] events.
+#[derive(Debug)]
+struct Buffer {
+    /// The recorded events, from the oldest to the most recent.
+    events: VecDeque<Event>,
+
+    /// The number of events overwritten since the buffer was last drained.
+    lost: u64,
+}
+
+impl Buffer {
+    /// Creates a new empty buffer.
+    const fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            lost: 0,
+        }
+    }
+
+    /// Appends an event to the buffer, overwriting the oldest event if the
+    /// buffer is full.
+    fn push(&mut self, event: Event) {
+        if self.events.capacity() == 0 {
+            self.events.reserve_exact(config::TRACE_BUFFER_CAPACITY);
+        }
+        if self.events.len() >= config::TRACE_BUFFER_CAPACITY {
+            self.events.pop_front();
+            self.lost += 1;
+        }
+        self.events.push_back(event);
+    }
+}
+
+/// Applies the `trace` option of the kernel command line. This must be called
+/// after the command line was set up, and before the first event is recorded.
+pub fn setup() {
+    if let Some(enabled) = cmdline::flag("trace") {
+        ENABLED.store(enabled, Ordering::Relaxed);
+    }
+    if enabled() {
+        log::info!("Kernel tracing enabled");
+    }
+}
+
+/// Returns whether trace events are recorded.
+#[must_use]
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records an event of the given kind with the given arguments into the trace
+/// buffer of the current core, on behalf of the task running on this core.
+/// This does nothing if tracing is disabled.
+pub fn record(kind: EventKind, args: [u64; 2]) {
+    if enabled() {
+        push(&[(kind, args)]);
+    }
+}
+
+/// Enables or disables the detailed tracing of the syscalls of the task with
+/// the given identifier, and returns whether they were traced before, or
+/// `None` if the task does not exist. The setting is shared by all the threads
+/// of the task.
+pub fn set_traced(id: Identifier, traced: bool) -> Option<bool> {
+    if traced {
+        TASKS_TRACED.store(true, Ordering::Relaxed);
+    }
+    TRACED.with(id, |flag| flag.swap(traced, Ordering::Relaxed))
+}
+
+/// Returns whether the syscalls of the current task are traced in detail.
+///
+/// # Panics
+/// Panics if there is no currently running task.
+#[must_use]
+pub fn current_traced() -> bool {
+    TASKS_TRACED.load(Ordering::Relaxed) && TRACED.with_current(|flag| flag.load(Ordering::Relaxed))
+}
+
+/// Records that the current task invoked the syscall with the given number
+/// and raw arguments. If the syscalls of the task are traced, all the
+/// arguments are recorded, even if tracing is disabled, and the instant the
+/// syscall was entered is returned so that [`syscall_exit`] can record how
+/// long the syscall took.
+#[must_use]
+pub fn syscall_enter(id: usize, args: [usize; 6]) -> Option<Instant> {
+    let [a0, a1, a2, a3, a4, a5] = args.map(|arg| arg as u64);
+    if !current_traced() {
+        record(EventKind::SyscallEnter, [id as u64, a0]);
+        return None;
+    }
+
+    push(&[
+        (EventKind::SyscallEnter, [id as u64, a0]),
+        (EventKind::SyscallArgs, [a0, a1]),
+        (EventKind::SyscallArgs, [a2, a3]),
+        (EventKind::SyscallArgs, [a4, a5]),
+    ]);
+    Some(Instant::now())
+}
+
+/// Records that the syscall with the given number returned the given raw
+/// value to the current task. If the syscall was traced in detail, `start` is
+/// the instant returned by [`syscall_enter`], and the time spent in the
+/// syscall is recorded as well.
+#[allow(clippy::cast_possible_truncation)]
+pub fn syscall_exit(id: usize, ret: usize, start: Option<Instant>) {
+    let Some(start) = start else {
+        record(EventKind::SyscallExit, [id as u64, ret as u64]);
+        return;
+    };
+
+    let duration = start.elapsed().as_nanos() as u64;
+    push(&[
+        (EventKind::SyscallExit, [id as u64, ret as u64]),
+        (EventKind::SyscallDuration, [id as u64, duration]),
+    ]);
+}
+
+/// Records the given events into the trace buffer of the current core, on
+/// behalf of the task running on this core, whether tracing is enabled or
+/// not. The events are pushed at once, so that no event recorded by a trap
+/// handler ends up between them.
+#[allow(clippy::cast_possible_truncation)]
+fn push(events: &[(EventKind, [u64; 2])]) {
+    let cpu = arch::cpu::id();
+    let timestamp = Instant::now().as_nanos();
+    let task = future::executor::current_task_id().map_or(0, |id| usize::from(id) as u64);
+    arch::irq::without(|| {
+        let mut buffer = BUFFERS[cpu].lock();
+        for &(kind, args) in events {
+            buffer.push(Event {
+                timestamp,
+                task,
+                kind: kind as u32,
+                cpu: cpu as u32,
+                args,
+            });
+        }
+    });
+}
+
+/// Removes at most `capacity` events from the trace buffers and returns them,
+/// the oldest events of each core first. If events of a core were overwritten
+/// since its buffer was last drained, an [`EventKind::Lost`] event reporting
+/// how many is returned before the events of that core. Returns `None` if
+/// tracing is disabled and no task was ever traced.
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub fn drain(capacity: usize) -> Option<Vec<Event>> {
+    if !enabled() && !TASKS_TRACED.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let mut events = Vec::new();
+    for (cpu, buffer) in BUFFERS.iter().enumerate() {
+        arch::irq::without(|| {
+            let mut buffer = buffer.lock();
+            if buffer.lost > 0 && events.len() < capacity {
+                events.push(Event {
+                    timestamp: Instant::now().as_nanos(),
+                    task: 0,
+                    kind: EventKind::Lost as u32,
+                    cpu: cpu as u32,
+                    args: [buffer.lost, 0],
+                });
+                buffer.lost = 0;
+            }
+
+            let count = buffer.events.len().min(capacity - events.len());
+            events.extend(buffer.events.drain(..count));
+        });
+    }
+    Some(events)
+}
+
+/// Writes the events left in the trace buffers to the kernel log, since no
+/// task will read them once the system goes down. This does nothing if
+/// tracing is disabled and no task was ever traced.
+pub fn flush() {
+    let Some(events) = drain(usize::MAX) else {
+        return;
+    };
+    for event in events {
+        log::info!(
+            "Trace: {} ns on core {}, task {}: {:?} {:?}",
+            event.timestamp,
+            event.cpu,
+            event.task,
+            EventKind::from(event.kind),
+            event.args
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Support for debuggers running in user space. A debugger can suspend a task,
+//! read and modify the registers of its threads, and then resume the task or
+//! make one of its threads execute a single instruction.
+//!
+//! The registers of a thread are owned by the future running it (see
+//! [`crate::future::user`]), so they can only be accessed once the thread has
+//! stopped in its execution loop. A suspended thread stops the next time it
+//! goes through its loop: right away if it runs in user mode, since it is
+//! interrupted at the end of its quantum at the latest, but only once its
+//! current syscall completes if it is waiting inside one. While the thread is
+//! stopped, its registers are published in its debugging state where the
+//! debugger reads and modifies them, and they are loaded back into the thread
+//! when it is resumed.
+use crate::{
+    arch,
+    future::{self, task::Identifier},
+    utils::lock,
+};
+use ::syscall::task::Registers;
+use core::{
+    pin::Pin,
+    sync::atomic::{AtomicBool, Ordering},
+    task::{Context, Poll},
+};
+use futures::Future;
+
+/// Whether a task was suspended since boot. Threads only look up their
+/// debugging state once this is set, so that threads never debugged do not
+/// pay for it each time they trap.
+static DEBUGGED: AtomicBool = AtomicBool::new(false);
+
+future::local::task_local! {
+    /// The debugging state of each thread.
+    static STATE: lock::Mutex<State> = lock::Mutex::new(State::new());
+}
+
+/// The debugging state of a thread.
+#[derive(Debug)]
+struct State {
+    /// Whether the thread must stop, or stay stopped.
+    suspended: bool,
+
+    /// Whether the thread executes a single instruction and then stops again
+    /// once resumed.
+    step: bool,
+
+    /// The registers of the thread while it is stopped, or `None` while it
+    /// runs.
+    registers: Option<Registers>,
+
+    /// Whether the registers were modified by the debugger since the thread
+    /// stopped.
+    modified: bool,
+}
+
+impl State {
+    /// Creates the state of a thread that is not debugged.
+    const fn new() -> Self {
+        Self {
+            suspended: false,
+            step: false,
+            registers: None,
+            modified: false,
+        }
+    }
+}
+
+/// An error that occurred while accessing a thread being debugged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The thread does not exist.
+    NotFound,
+
+    /// The thread is not stopped.
+    NotStopped,
+}
+
+/// Asks the given threads to stop. A thread that already stopped stays
+/// stopped until it is resumed.
+pub fn suspend(threads: &[Identifier]) {
+    DEBUGGED.store(true, Ordering::Relaxed);
+    for &thread in threads {
+        STATE.with(thread, |state| state.lock().suspended = true);
+    }
+}
+
+/// Resumes the given threads, which continue where they stopped with the
+/// registers modified by the debugger, if any.
+pub fn resume(threads: &[Identifier]) {
+    for &thread in threads {
+        let suspended = STATE.with(thread, |state| {
+            let mut state = state.lock();
+            state.step = false;
+            core::mem::replace(&mut state.suspended, false)
+        });
+        if suspended == Some(true) {
+            future::executor::wake(thread);
+        }
+    }
+}
+
+/// Resumes the given stopped thread for a single instruction, after which it
+/// stops again. The other threads of its task stay stopped. This must only be
+/// used if the architecture supports single-stepping (see
+/// [`arch::thread::SINGLE_STEP`]).
+///
+/// # Errors
+/// Returns [`Error::NotFound`] if the thread does not exist, or
+/// [`Error::NotStopped`] if it is not stopped.
+pub fn step(thread: Identifier) -> Result<(), Error> {
+    STATE
+        .with(thread, |state| {
+            let mut state = state.lock();
+            if state.registers.is_none() {
+                return Err(Error::NotStopped);
+            }
+            state.suspended = false;
+            state.step = true;
+            Ok(())
+        })
+        .ok_or(Error::NotFound)??;
+
+    future::executor::wake(thread);
+    Ok(())
+}
+
+/// Returns the registers of the given stopped thread, including the changes
+/// made by the debugger since it stopped.
+///
+/// # Errors
+/// Returns [`Error::NotFound`] if the thread does not exist, or
+/// [`Error::NotStopped`] if it is not stopped.
+pub fn registers(thread: Identifier) -> Result<Registers, Error> {
+    STATE
+        .with(thread, |state| {
+            state.lock().registers.ok_or(Error::NotStopped)
+        })
+        .ok_or(Error::NotFound)?
+}
+
+/// Replaces the registers of the given stopped thread, which will be loaded
+/// into the thread when it is resumed.
+///
+/// # Errors
+/// Returns [`Error::NotFound`] if the thread does not exist, or
+/// [`Error::NotStopped`] if it is not stopped.
+pub fn set_registers(thread: Identifier, registers: Registers) -> Result<(), Error> {
+    STATE
+        .with(thread, |state| {
+            let mut state = state.lock();
+            let saved = state.registers.as_mut().ok_or(Error::NotStopped)?;
+            *saved = registers;
+            state.modified = true;
+            Ok(())
+        })
+        .ok_or(Error::NotFound)?
+}
+
+/// Returns whether the given thread must stop before being executed again.
+#[must_use]
+pub fn must_stop(thread: Identifier) -> bool {
+    DEBUGGED.load(Ordering::Relaxed)
+        && STATE
+            .with(thread, |state| state.lock().suspended)
+            .unwrap_or(false)
+}
+
+/// Stops the given thread until it is resumed, publishing its registers to
+/// the debugger in the meantime. When resumed, the registers modified by the
+/// debugger are loaded into the thread, and single-stepping is enabled if the
+/// debugger asked for it. Returns `None` if the task of the thread is
+/// terminated while it is stopped.
+pub async fn stop(thread: &mut arch::thread::Thread, id: Identifier) -> Option<()> {
+    let registers = arch::thread::get_registers(thread);
+    STATE.with(id, |state| {
+        let mut state = state.lock();
+        state.registers = Some(registers);
+        state.modified = false;
+    })?;
+    log::debug!("Thread {} stopped at {:#x}", id, registers.pc);
+
+    future::group::interruptible(id, Resumed(id)).await?;
+    let (registers, step) = STATE.with(id, |state| {
+        let mut state = state.lock();
+        let registers = state.registers.take().filter(|_| state.modified);
+        (registers, state.step)
+    })?;
+
+    if let Some(registers) = registers {
+        arch::thread::set_registers(thread, &registers);
+    }
+    if step {
+        arch::thread::set_single_step(thread, true);
+    }
+    Some(())
+}
+
+/// Records that the given thread executed the single instruction it was
+/// resumed for, so that it stops before being executed again.
+pub fn stepped(thread: &mut arch::thread::Thread, id: Identifier) {
+    arch::thread::set_single_step(thread, false);
+    STATE.with(id, |state| {
+        let mut state = state.lock();
+        state.step = false;
+        state.suspended = true;
+    });
+}
+
+/// A future that completes once the given thread is resumed. It does not
+/// register its waker anywhere, and relies on [`resume`] and [`step`] waking
+/// up the thread directly.
+struct Resumed(Identifier);
+
+impl Future for Resumed {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Self::Output> {
+        let suspended = STATE
+            .with(self.0, |state| state.lock().suspended)
+            .unwrap_or(false);
+        if suspended {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    }
+}
@@ -0,0 +1,24 @@
+use crate::{future, utils::lock};
+use hashbrown::HashMap;
+use spin::Lazy;
+
+/// The devices claimed by user space drivers, mapping the index of each
+/// device to the task driving it.
+static CLAIMS: Lazy<lock::Mutex<HashMap<usize, future::task::Identifier>>> =
+    Lazy::new(|| lock::Mutex::new(HashMap::new()));
+
+/// Claims the device with the given index for the given task, so that no other
+/// task can map its registers. Claiming a device already claimed by the same
+/// task succeeds, allowing a driver to map the registers several times.
+/// Returns `false` if the device is claimed by another task.
+#[must_use]
+pub fn claim(device: usize, task: future::task::Identifier) -> bool {
+    *CLAIMS.lock().entry(device).or_insert(task) == task
+}
+
+/// Releases all the devices claimed by the given task. This must be called
+/// when the task terminates, so that the devices can be driven by another
+/// task.
+pub fn release(task: future::task::Identifier) {
+    CLAIMS.lock().retain(|_, owner| *owner != task);
+}
@@ -0,0 +1,43 @@
+use crate::{arch::target::addr::Physical, future, mm, utils::lock};
+use alloc::vec::Vec;
+use hashbrown::HashMap;
+use spin::Lazy;
+
+/// A physically contiguous range of frames allocated for the devices driven by
+/// a task.
+#[derive(Debug, Clone, Copy)]
+struct Allocation {
+    /// The physical address of the first frame.
+    base: Physical,
+
+    /// The number of frames.
+    count: usize,
+}
+
+/// The ranges of frames allocated for the devices driven by each task.
+static ALLOCATIONS: Lazy<lock::Mutex<HashMap<future::task::Identifier, Vec<Allocation>>>> =
+    Lazy::new(|| lock::Mutex::new(HashMap::new()));
+
+/// Records that the given range of frames was allocated for the given task,
+/// so that it is returned to the frame allocator when the task terminates.
+pub fn record(task: future::task::Identifier, base: Physical, count: usize) {
+    ALLOCATIONS
+        .lock()
+        .entry(task)
+        .or_default()
+        .push(Allocation { base, count });
+}
+
+/// Returns all the ranges of frames allocated for the given task to the frame
+/// allocator. This must be called when the task terminates, after its address
+/// space has been released: the frames are mapped as device memory so they are
+/// not freed with the address space, and a device may still be accessing them
+/// until then if the driver did not reset it.
+pub fn release(task: future::task::Identifier) {
+    let Some(allocations) = ALLOCATIONS.lock().remove(&task) else {
+        return;
+    };
+    for allocation in allocations {
+        mm::phys::deallocate_range(allocation.base, allocation.count);
+    }
+}
@@ -1,120 +1,153 @@
 use crate::{
     arch::{
         self,
-        mmu::Align,
+        mmu::{Align, MapError, PAGE_SIZE},
         target::addr::{Virtual, virt::User},
     },
-    mm::{self, phys::AllocationFlags},
-    user::{USER_STACK_BOTTOM, USER_STACK_SIZE, USER_STACK_TOP},
+    mm::space::AddressSpace,
+    user::{self, USER_STACK_LIMIT, vdso::SharedPage},
 };
-use usize_cast::IntoUsize;
+use ::syscall::{env::Block, vdso::TlsTemplate};
+use kcore::elf::{Loader, Machine, Segment};
+
+pub use kcore::elf::LoadError;
+
+/// The address where position-independent executables are loaded. It is
+/// aligned on 2 MiB so that large segments can be mapped with huge pages, and
+/// leaves the first pages of the address space unmapped to catch null pointer
+/// dereferences.
+pub const PIE_LOAD_BIAS: usize = 0x0000_0000_0040_0000;
+
+/// The machine of the executables that can run on this architecture, and the
+/// types of the relocations that can be applied to them: the empty relocation
+/// and the relative relocation.
+#[cfg(target_arch = "riscv64")]
+const MACHINE: Machine = Machine {
+    id: elf::abi::EM_RISCV,
+    none: elf::abi::R_RISCV_NONE,
+    relative: elf::abi::R_RISCV_RELATIVE,
+};
+#[cfg(target_arch = "aarch64")]
+const MACHINE: Machine = Machine {
+    id: elf::abi::EM_AARCH64,
+    none: elf::abi::R_AARCH64_NONE,
+    relative: elf::abi::R_AARCH64_RELATIVE,
+};
+
+/// The validator of the executables loaded by the kernel. Segments must leave
+/// room for the user stack at the top of the address space.
+const LOADER: Loader = Loader::new(
+    MACHINE,
+    USER_STACK_LIMIT.as_usize(),
+    PAGE_SIZE,
+    PIE_LOAD_BIAS,
+);
 
 /// Load an ELF file into memory and return a thread that can be executed.
+/// Both static executables and position-independent executables are
+/// supported: the latter are loaded at [`PIE_LOAD_BIAS`] and their relative
+/// relocations are applied. The given block of arguments and environment
+/// variables is copied at the top of the user stack of the thread (see
+/// [`user::env::push`]).
 ///
-/// # Safety
-/// This function should only be called once to initialize thread during
-/// the boot process. After the boot process, the memory used by this
-/// function will be reclaimed by the kernel to reuse it for other purposes.
-///
-/// # Panics
-/// This function will panic if the ELF file cannot be parsed or if the ELF
-/// file contains an invalid segment (address outside of the user address
-/// space, offset overflow, etc.).
-#[must_use]
-#[macros::init]
-pub unsafe fn load(file: &[u8]) -> arch::thread::Thread {
-    let header = elf::ElfBytes::<elf::endian::LittleEndian>::minimal_parse(file)
-        .expect("Failed to parse ELF file");
+/// # Errors
+/// Returns a [`LoadError`] describing the problem if the file is malformed or
+/// not supported, or if the kernel ran out of memory. In that case, all the
+/// memory allocated for the thread is released.
+pub fn load(file: &[u8], env: &Block) -> Result<arch::thread::Thread, LoadError> {
+    // Validate the whole file before allocating anything.
+    let image = LOADER.parse(file)?;
 
-    let mut thread = arch::thread::create(
-        header.ehdr.e_entry.into_usize(),
-        usize::from(USER_STACK_TOP),
-    );
-
-    for segment in header
-        .segments()
-        .unwrap()
-        .iter()
-        .filter(|phdr| phdr.p_type == elf::abi::PT_LOAD)
+    // The stack pointer is set when the environment is pushed on the stack
+    // below, since the top of the stack depends on the layout chosen for the
+    // address space of the thread.
+    let mut thread = arch::thread::create(image.entry, 0);
     {
-        let segment_file_size = segment.p_filesz.into_usize();
-        let segment_mem_start = segment.p_vaddr.into_usize();
-        let segment_mem_size = segment.p_memsz.into_usize();
-        let segment_mem_end = segment_mem_start + segment_mem_size;
+        let mut space = thread.address_space();
+        for segment in &image.segments {
+            log::trace!(
+                "Loading elf segment 0x{:x} - 0x{:x}",
+                segment.start,
+                segment.end,
+            );
+            load_segment(&mut space, segment)?;
+        }
 
-        // Compute the aligned memory start address and the misalignment
-        // of the segment in memory
-        let mut misalign = segment_mem_start % arch::mmu::PAGE_SIZE;
-        let segment_aligned_mem_start = segment_mem_start - misalign;
+        for relocation in image.relocations() {
+            let (target, value) = relocation?;
+            if !space.write(Virtual::<User>::new(target), &value.to_le_bytes()) {
+                return Err(LoadError::InvalidRelocation);
+            }
+        }
+    }
 
-        log::trace!(
-            "Loading elf segment 0x{:x} - 0x{:x} (misalign: 0x{:x})",
-            segment_mem_start,
-            segment_mem_end,
-            misalign
-        );
+    // The template of the thread-local storage is published in the shared
+    // page of the first thread, where the runtime reads it to set up the TLS
+    // block of each thread.
+    if let Some(tls) = image.tls {
+        SharedPage::map(&mut thread, 0)
+            .ok_or(LoadError::OutOfMemory)?
+            .set_tls_template(TlsTemplate {
+                image: tls.image as u64,
+                file_size: tls.file_size as u64,
+                mem_size: tls.mem_size as u64,
+                align: tls.align as u64,
+            });
+    }
 
-        // Map each page in the segment into the thread's page table. If the
-        // start address of the segment is not page aligned, the first page
-        // will be partially filled with data from the ELF file and the rest
-        // of the page will handled normally.
-        for page in (segment_aligned_mem_start..segment_mem_end).step_by(arch::mmu::PAGE_SIZE) {
-            let section_offset = page + misalign - segment_mem_start;
-            let file_offset = segment.p_offset.into_usize() + section_offset;
-            log::trace!("Mapping page 0x{:x} with offset 0x{:x}", page, file_offset);
-            let addr = Virtual::<User>::new(page);
+    // Only the top of the user stack holding the environment is allocated
+    // here: the other pages are lazily allocated by the page fault handler
+    // when the thread first touches them.
+    if !user::env::push(&mut thread, env) {
+        return Err(LoadError::OutOfMemory);
+    }
+    log::debug!("Loaded ELF file at 0x{:x}", image.entry);
+    Ok(thread)
+}
 
-            let frame = mm::phys::allocate_frame(AllocationFlags::ZEROED)
-                .expect("Failed to allocate zeroed page");
+/// Map the given segment in the address space and copy its data from the
+/// file. Large segments are mapped with 2 MiB pages when possible to reduce
+/// TLB pressure.
+///
+/// Two consecutive segments may share a page when they are not page-aligned.
+/// In that case, the shared page was already mapped by the previous segment
+/// and is reused. The fresh pages are already zeroed, but the part of the
+/// shared page that belongs to this segment is zeroed explicitly so that the
+/// BSS never depends on what the previous segment left there.
+fn load_segment(space: &mut AddressSpace, segment: &Segment) -> Result<(), LoadError> {
+    let first_page = segment.start.page_align_down();
+    let map_start = if space.query(Virtual::<User>::new(first_page)).is_some() {
+        first_page + PAGE_SIZE
+    } else {
+        first_page
+    };
 
-            // Map the page into the thread's page table
-            arch::mmu::map(
-                thread.root_table_mut(),
-                addr,
-                frame,
+    if map_start < segment.end {
+        space
+            .map_range(
+                Virtual::<User>::new(map_start),
+                segment.end - map_start,
                 arch::mmu::Rights::RWXU,
-                arch::mmu::Flags::empty(),
             )
-            .expect("Failed to map page");
-
-            // Compute the size of the data to copy into the physical
-            // page and compute the source and destination pointers
-            let remaning = segment_file_size.saturating_sub(section_offset);
-            let size = core::cmp::min(arch::mmu::PAGE_SIZE - misalign, remaning);
-            let src = file.as_ptr().wrapping_add(file_offset);
-            let dst = arch::mmu::translate_physical(frame)
-                .expect("Failed to translate physical address")
-                .as_mut_ptr::<u8>()
-                .wrapping_add(misalign);
-
-            // Copy the data into the physical page
-            unsafe {
-                core::ptr::copy_nonoverlapping(src, dst, size);
-            }
-
-            misalign = 0;
-        }
+            .map_err(|error| match error {
+                MapError::OutOfMemory => LoadError::OutOfMemory,
+                _ => LoadError::InvalidSegment,
+            })?;
     }
 
-    // Allocate and set up the user stack for the thread
-    // TODO: Delegate stack allocation to a user virtual memory manager
-    for page_idx in 0..USER_STACK_SIZE.page_count_up() {
-        let offset = page_idx * arch::mmu::PAGE_SIZE;
-        let addr = Virtual::<User>::new(usize::from(USER_STACK_BOTTOM) + offset);
-
-        let frame = mm::phys::allocate_frame(AllocationFlags::ZEROED)
-            .expect("Failed to allocate zeroed page for user stack");
+    let start = Virtual::<User>::new(segment.start);
+    if !space.write(start, segment.data) {
+        return Err(LoadError::InvalidSegment);
+    }
 
-        arch::mmu::map(
-            thread.root_table_mut(),
-            addr,
-            frame,
-            arch::mmu::Rights::RWU,
-            arch::mmu::Flags::empty(),
+    let bss = segment.zeroed(map_start);
+    if !bss.is_empty()
+        && !space.write(
+            Virtual::<User>::new(bss.start),
+            &[0; PAGE_SIZE][..bss.len()],
         )
-        .expect("Failed to map user stack page");
+    {
+        return Err(LoadError::InvalidSegment);
     }
-
-    log::debug!("Loaded ELF file at 0x{:x}", header.ehdr.e_entry);
-    thread
+    Ok(())
 }
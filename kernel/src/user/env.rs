@@ -0,0 +1,48 @@
+use crate::arch::{
+    self,
+    mmu::{Align, Rights},
+    target::addr::{Virtual, virt::User},
+    thread::Thread,
+};
+use ::syscall::env::Block;
+use alloc::{vec, vec::Vec};
+
+/// Encodes the given arguments and environment variables into a block that
+/// can be given to a new task.
+///
+/// # Panics
+/// Panics if the block could not be encoded. This should never happen since
+/// the buffer is allocated with the exact size of the block.
+#[must_use]
+pub fn encode(args: &[&str], vars: &[&str]) -> Vec<u8> {
+    let mut block = vec![0; ::syscall::env::encoded_size(args, vars)];
+    ::syscall::env::encode(&mut block, args, vars).expect("Failed to encode the environment");
+    block
+}
+
+/// Copies the given block at the top of the user stack of the thread, and
+/// makes the thread start with its stack pointer just below the block and
+/// with the address of the block as the argument of its entry point. The
+/// pages of the stack holding the block are allocated immediately, so the
+/// allocated part of the stack remains contiguous.
+///
+/// Returns `false` if the kernel ran out of memory while allocating the
+/// stack.
+#[must_use]
+pub fn push(thread: &mut Thread, block: &Block) -> bool {
+    let bytes = block.as_bytes();
+    let mut space = thread.address_space();
+    let top = space.layout().stack_top().as_usize();
+    let address = (top - bytes.len()) & !0xF;
+    let start = address.page_align_down();
+
+    let mapped = space.map_range(Virtual::<User>::new(start), top - start, Rights::RWU);
+    if mapped.is_err() || !space.write(Virtual::<User>::new(address), bytes) {
+        return false;
+    }
+    drop(space);
+
+    arch::thread::set_stack_pointer(thread, address);
+    arch::thread::set_entry_argument(thread, 0, address);
+    true
+}
@@ -0,0 +1,85 @@
+use crate::{
+    arch::{
+        self,
+        target::addr::{Physical, Virtual, virt::User},
+    },
+    future,
+    user::{object::Object, ptr::Pointer},
+    utils::lock,
+};
+use ::syscall::futex::{ALIGNMENT, WaitError, WakeError};
+use hashbrown::HashMap;
+use spin::Lazy;
+
+/// The queues of the tasks sleeping on a futex, indexed by the physical
+/// address of the futex word. A queue is removed as soon as no task sleeps on
+/// it anymore, so that the table only grows with the number of sleeping tasks.
+static FUTEXES: Lazy<lock::Mutex<HashMap<usize, future::wait::Queue>>> =
+    Lazy::new(|| lock::Mutex::new(HashMap::new()));
+
+/// Returns the physical address of the futex word pointed to by the given
+/// pointer, which identifies the futex. Returns `None` if the word is not
+/// properly aligned or not mapped.
+fn key(word: &Pointer<u32>) -> Option<usize> {
+    let address = word.inner().addr();
+    if !address.is_multiple_of(ALIGNMENT) {
+        return None;
+    }
+
+    let mapping = word
+        .thread()
+        .address_space()
+        .query(Virtual::<User>::new(address))?;
+    Some(Physical::from(mapping.frame).as_usize() + address % arch::mmu::PAGE_SIZE)
+}
+
+/// Puts the current task to sleep on the given futex word if it holds the
+/// expected value, until another task wakes it up with [`wake`]. The value is
+/// read while holding the lock of the futex table, so that a wake-up that
+/// follows a change of the value cannot be missed.
+///
+/// # Errors
+/// Returns [`WaitError::BadAddress`] if the word is not properly aligned or
+/// cannot be read, or [`WaitError::ValueMismatch`] if it does not hold the
+/// expected value.
+pub async fn wait(word: Pointer<'_, u32>, expected: u32) -> Result<(), WaitError> {
+    let key = key(&word).ok_or(WaitError::BadAddress)?;
+    let queue = {
+        let mut futexes = FUTEXES.lock();
+
+        // SAFETY: The pointer was validated when the `Pointer` was created in
+        // the syscall handler, and any `u32` value is valid.
+        let value = unsafe { Object::read(&word) }.map_err(|_| WaitError::BadAddress)?;
+        if value != expected {
+            return Err(WaitError::ValueMismatch);
+        }
+        futexes.entry(key).or_default().clone()
+    };
+
+    future::wait::wait(&queue).await;
+    Ok(())
+}
+
+/// Wakes up at most `count` tasks sleeping on the given futex word, in the
+/// order they went to sleep, and returns the number of tasks woken up.
+///
+/// # Errors
+/// Returns [`WakeError::BadAddress`] if the word is not properly aligned or
+/// not mapped.
+pub fn wake(word: &Pointer<'_, u32>, count: usize) -> Result<usize, WakeError> {
+    let key = key(word).ok_or(WakeError::BadAddress)?;
+    let mut futexes = FUTEXES.lock();
+    let Some(queue) = futexes.get(&key) else {
+        return Ok(0);
+    };
+
+    let mut woken = 0;
+    while woken < count && !queue.is_empty() {
+        queue.wake_one();
+        woken += 1;
+    }
+    if queue.is_empty() {
+        futexes.remove(&key);
+    }
+    Ok(woken)
+}
@@ -0,0 +1,89 @@
+use crate::{
+    arch::{
+        self,
+        memory::Region,
+        mmu::{Align, Rights},
+        target::addr::{Physical, Virtual, virt::User},
+        thread::Thread,
+    },
+    mm,
+};
+use ::syscall::initrd::{INITRD_ADDRESS, INITRD_MAX_SIZE};
+use cpio::{Archive, Entry};
+
+/// The initial ramdisk loaded in memory by the bootloader. It is a cpio
+/// archive in the "new ASCII" format containing the programs of the system,
+/// so that adding a program does not require rebuilding the kernel. The
+/// kernel only looks for the `init` program in it, and gives the whole archive
+/// to `init` that spawns the other programs.
+#[derive(Debug)]
+pub struct Initrd {
+    /// The physical memory holding the ramdisk.
+    region: Region,
+
+    /// The archive, read directly from the physical memory of the ramdisk.
+    archive: Archive<'static>,
+}
+
+impl Initrd {
+    /// Parses the initial ramdisk stored in the given physical memory region.
+    /// Returns `None` if the ramdisk is not a valid cpio archive or is too
+    /// large to be given to `init`.
+    ///
+    /// # Safety
+    /// The given region must have been filled by the bootloader and must be
+    /// reserved, so that it is not modified until [`Initrd::release`] is
+    /// called.
+    #[must_use]
+    pub unsafe fn new(region: Region) -> Option<Self> {
+        if region.length > INITRD_MAX_SIZE {
+            log::warn!("Initial ramdisk is too large, ignoring it");
+            return None;
+        }
+
+        let start = arch::mmu::translate_physical(Physical::new(region.start))?;
+        // SAFETY: The caller guarantees that the region is reserved for the
+        // ramdisk and is not modified until it is released, which consumes
+        // the ramdisk and the archive borrowing it.
+        let data = unsafe { core::slice::from_raw_parts(start.as_ptr::<u8>(), region.length) };
+        let Some(archive) = Archive::new(data) else {
+            log::warn!("Initial ramdisk is not a valid cpio archive, ignoring it");
+            return None;
+        };
+        Some(Self { region, archive })
+    }
+
+    /// Returns the regular file with the given name in the ramdisk, or `None`
+    /// if there is no such file.
+    #[must_use]
+    pub fn find(&self, name: &str) -> Option<Entry<'_>> {
+        self.archive.find(name).filter(Entry::is_file)
+    }
+
+    /// Returns the size of the ramdisk in bytes.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        self.archive.as_bytes().len()
+    }
+
+    /// Copies the ramdisk in the address space of the given thread, mapped
+    /// read-only at [`INITRD_ADDRESS`].
+    ///
+    /// Returns `false` if the kernel ran out of memory.
+    #[must_use]
+    pub fn map(&self, thread: &mut Thread) -> bool {
+        let mut space = thread.address_space();
+        let start = Virtual::<User>::new(INITRD_ADDRESS);
+        space
+            .map_range(start, self.size(), Rights::READ | Rights::USER)
+            .is_ok()
+            && space.write(start, self.archive.as_bytes())
+    }
+
+    /// Gives the physical memory of the ramdisk back to the frame allocator.
+    pub fn release(self) {
+        let start = self.region.start.page_align_down();
+        let end = self.region.end().page_align_up();
+        mm::phys::release_range(Physical::new(start), (end - start) / arch::mmu::PAGE_SIZE);
+    }
+}
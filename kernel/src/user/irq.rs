@@ -0,0 +1,188 @@
+use crate::{arch, future, random, utils::lock};
+use ::syscall::irq::{AffinityError, ControlError, RegisterError, WaitError};
+use hashbrown::HashMap;
+use spin::Lazy;
+
+/// The interrupt sources bound to user space drivers, indexed by source.
+static BINDINGS: Lazy<lock::Mutex<HashMap<usize, Binding>>> =
+    Lazy::new(|| lock::Mutex::new(HashMap::new()));
+
+/// An interrupt source bound to a task.
+#[derive(Debug)]
+struct Binding {
+    /// The task that drives the device raising the interrupt.
+    task: future::task::Identifier,
+
+    /// Whether the source raised an interrupt that the task has not waited
+    /// for yet. The source stays masked while this is set.
+    pending: bool,
+
+    /// Whether the task masked the source with [`mask`]. The source then
+    /// stays masked until the task unmasks it, even when the task waits.
+    masked: bool,
+
+    /// The queue where the task sleeps while waiting for an interrupt.
+    queue: future::wait::Queue,
+}
+
+/// Binds the given interrupt source to the given task. The source stays
+/// masked until the task waits for it with [`wait`].
+///
+/// # Errors
+/// Returns a [`RegisterError`] if there is no interrupt controller, if the
+/// source cannot be bound, or if it is already bound to a task.
+pub fn register(irq: usize, task: future::task::Identifier) -> Result<(), RegisterError> {
+    if !arch::irq::controller_available() {
+        return Err(RegisterError::NoController);
+    }
+    if !arch::irq::bindable(irq) {
+        return Err(RegisterError::InvalidIrq);
+    }
+
+    let mut bindings = BINDINGS.lock();
+    if bindings.contains_key(&irq) {
+        return Err(RegisterError::AlreadyBound);
+    }
+    bindings.insert(
+        irq,
+        Binding {
+            task,
+            pending: false,
+            masked: false,
+            queue: future::wait::Queue::new(),
+        },
+    );
+    log::debug!("Interrupt {} bound to task {}", irq, task);
+    Ok(())
+}
+
+/// Acknowledges the previous interrupt of the given source and waits until
+/// it raises another one. If an interrupt was raised since the last wait, this
+/// returns immediately and the source stays masked until the next wait. The
+/// source is not unmasked if it was masked by the task with [`mask`], so this
+/// only returns once another thread of the task unmasks it and it raises an
+/// interrupt.
+///
+/// # Errors
+/// Returns [`WaitError::NotBound`] if the source is not bound to the given
+/// task.
+pub async fn wait(irq: usize, task: future::task::Identifier) -> Result<(), WaitError> {
+    let queue = {
+        let mut bindings = BINDINGS.lock();
+        let binding = bindings
+            .get_mut(&irq)
+            .filter(|binding| binding.task == task)
+            .ok_or(WaitError::NotBound)?;
+        if core::mem::take(&mut binding.pending) {
+            return Ok(());
+        }
+        if !binding.masked {
+            arch::irq::unmask(irq);
+        }
+        binding.queue.clone()
+    };
+
+    loop {
+        future::wait::wait(&queue).await;
+        let mut bindings = BINDINGS.lock();
+        let binding = bindings
+            .get_mut(&irq)
+            .filter(|binding| binding.task == task)
+            .ok_or(WaitError::NotBound)?;
+        if core::mem::take(&mut binding.pending) {
+            return Ok(());
+        }
+    }
+}
+
+/// Masks the given source on behalf of the given task, until the task unmasks
+/// it with [`unmask`]. An interrupt raised before the source was masked is
+/// still reported by the next wait.
+///
+/// # Errors
+/// Returns [`ControlError::NotBound`] if the source is not bound to the given
+/// task.
+pub fn mask(irq: usize, task: future::task::Identifier) -> Result<(), ControlError> {
+    let mut bindings = BINDINGS.lock();
+    let binding = bindings
+        .get_mut(&irq)
+        .filter(|binding| binding.task == task)
+        .ok_or(ControlError::NotBound)?;
+    binding.masked = true;
+    arch::irq::mask(irq);
+    Ok(())
+}
+
+/// Unmasks the given source masked by the given task with [`mask`]. If the
+/// source raised an interrupt that the task has not waited for yet, it stays
+/// masked until the next wait, as if it had never been masked by the task.
+///
+/// # Errors
+/// Returns [`ControlError::NotBound`] if the source is not bound to the given
+/// task.
+pub fn unmask(irq: usize, task: future::task::Identifier) -> Result<(), ControlError> {
+    let mut bindings = BINDINGS.lock();
+    let binding = bindings
+        .get_mut(&irq)
+        .filter(|binding| binding.task == task)
+        .ok_or(ControlError::NotBound)?;
+    binding.masked = false;
+    if !binding.pending {
+        arch::irq::unmask(irq);
+    }
+    Ok(())
+}
+
+/// Routes the given source, bound to the given task, to the CPU with the
+/// given index.
+///
+/// # Errors
+/// Returns [`AffinityError::NotBound`] if the source is not bound to the given
+/// task, or [`AffinityError::InvalidCpu`] if the interrupt controller cannot
+/// route the source to this CPU.
+pub fn set_affinity(
+    irq: usize,
+    task: future::task::Identifier,
+    cpu: usize,
+) -> Result<(), AffinityError> {
+    let bindings = BINDINGS.lock();
+    bindings
+        .get(&irq)
+        .filter(|binding| binding.task == task)
+        .ok_or(AffinityError::NotBound)?;
+    if !arch::irq::set_affinity(irq, cpu) {
+        return Err(AffinityError::InvalidCpu);
+    }
+    log::debug!("Interrupt {} routed to cpu {}", irq, cpu);
+    Ok(())
+}
+
+/// Notifies the task bound to the given source that it raised an interrupt.
+/// The source must have been masked by the caller. This is called by the
+/// executor as deferred work (see [`crate::future::deferred`]), not by the
+/// trap handler. Returns `false` if the
+/// source is not bound to any task, in which case it should stay masked. The
+/// time at which the interrupt is handled is added to the entropy pool.
+pub fn notify(irq: usize) -> bool {
+    random::add_entropy(arch::timer::current_time_ticks());
+    let mut bindings = BINDINGS.lock();
+    let Some(binding) = bindings.get_mut(&irq) else {
+        return false;
+    };
+    binding.pending = true;
+    binding.queue.wake_all();
+    true
+}
+
+/// Releases all the interrupt sources bound to the given task, masking them.
+/// This must be called when the task terminates, so that the sources can be
+/// bound by another driver.
+pub fn release(task: future::task::Identifier) {
+    for (irq, _) in BINDINGS
+        .lock()
+        .extract_if(|_, binding| binding.task == task)
+    {
+        arch::irq::mask(irq);
+        log::debug!("Interrupt {} released by task {}", irq, task);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{
+    arch::{
+        mmu::PAGE_SIZE,
+        target::addr::{Virtual, virt::User},
+    },
+    config, random,
+    user::{USER_STACK_RANDOM_RANGE, USER_STACK_SIZE, USER_STACK_TOP},
+};
+use ::syscall::vdso::{MMAP_BASE, MMAP_RANDOM_RANGE};
+
+/// The placement of the user stack and of the anonymous mappings in an user
+/// address space. It is chosen when the address space is created and never
+/// changes afterwards. Unless the layout of user address spaces is not
+/// randomized (see [`config::RANDOMIZE_USER_LAYOUT`]), both addresses are
+/// randomly slid by a multiple of the page size, so that an attacker cannot
+/// rely on them to exploit a bug of a task.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    stack_top: usize,
+    mmap_base: usize,
+}
+
+impl Layout {
+    /// Chooses a new layout for an user address space.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            stack_top: USER_STACK_TOP.as_usize() - slide(USER_STACK_RANDOM_RANGE),
+            mmap_base: MMAP_BASE + slide(MMAP_RANDOM_RANGE),
+        }
+    }
+
+    /// The top address of the user stack, exclusive.
+    #[must_use]
+    pub const fn stack_top(&self) -> Virtual<User> {
+        Virtual::<User>::new(self.stack_top)
+    }
+
+    /// The bottom address of the user stack, inclusive.
+    #[must_use]
+    pub const fn stack_bottom(&self) -> Virtual<User> {
+        Virtual::<User>::new(self.stack_top - USER_STACK_SIZE)
+    }
+
+    /// The guard page of the user stack, located just below the stack and
+    /// never mapped. Any access to this page is considered as a stack
+    /// overflow and terminates the task, instead of silently corrupting the
+    /// memory below the stack.
+    #[must_use]
+    pub const fn stack_guard(&self) -> Virtual<User> {
+        Virtual::<User>::new(self.stack_top - USER_STACK_SIZE - PAGE_SIZE)
+    }
+
+    /// The address from which the runtime of the task places its heap and
+    /// its other anonymous mappings. It is published to the task in its
+    /// shared page.
+    #[must_use]
+    pub const fn mmap_base(&self) -> Virtual<User> {
+        Virtual::<User>::new(self.mmap_base)
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns a random multiple of the page size lower than the given range, or
+/// zero if the layout of user address spaces is not randomized.
+fn slide(range: usize) -> usize {
+    if config::RANDOMIZE_USER_LAYOUT {
+        random::below(range / PAGE_SIZE) * PAGE_SIZE
+    } else {
+        0
+    }
+}
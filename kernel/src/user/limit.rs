@@ -0,0 +1,144 @@
+use crate::config;
+use ::syscall::task::{Limit, UNLIMITED};
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The resource limits of a user task, along with its usage of the limited
+/// resources. They are shared by all the threads of the task, like its
+/// capabilities, and are checked each time the task allocates one of the
+/// limited resources, so that a single task cannot exhaust the memory or the
+/// tasks of the system.
+#[derive(Debug)]
+pub struct Limits {
+    /// The maximum number of pages that the task can map with the `MemMap`
+    /// syscall.
+    memory: usize,
+
+    /// The maximum number of children of the task alive at the same time.
+    children: usize,
+
+    /// The maximum number of IPC messages pending in the mailbox of the task.
+    messages: usize,
+
+    /// The number of pages mapped by the task with the `MemMap` syscall.
+    mapped: usize,
+
+    /// The number of children of the task that are still alive. It is shared
+    /// with the children, which decrement it when they terminate.
+    alive: Arc<AtomicUsize>,
+
+    /// The slot taken by the task in the children of its parent, if any. It
+    /// is never read, and only released when the last thread of the task
+    /// terminates and drops the limits.
+    _parent: Option<Child>,
+}
+
+/// A slot in the children of a task, released when dropped.
+#[derive(Debug)]
+pub struct Child(Arc<AtomicUsize>);
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Limits {
+    /// Changes the value of the given limit and returns its previous value.
+    /// Values above [`UNLIMITED`] are treated as [`UNLIMITED`]. Lowering a
+    /// limit below the current usage of the resource does not release
+    /// anything, but prevents the task from allocating more of it.
+    ///
+    /// Returns `None` if the limit is unknown, or if the value is not allowed
+    /// for this limit: the message limit must be between 1 and
+    /// [`config::IPC_MAILBOX_CAPACITY`].
+    pub fn set(&mut self, limit: Limit, value: usize) -> Option<usize> {
+        let slot = match limit {
+            Limit::Memory => &mut self.memory,
+            Limit::Children => &mut self.children,
+            Limit::Messages if (1..=config::IPC_MAILBOX_CAPACITY).contains(&value) => {
+                &mut self.messages
+            }
+            Limit::Messages | Limit::Unknown => return None,
+        };
+        Some(core::mem::replace(slot, value.min(UNLIMITED)))
+    }
+
+    /// Returns the maximum number of IPC messages pending in the mailbox of
+    /// the task.
+    #[must_use]
+    pub const fn messages(&self) -> usize {
+        self.messages
+    }
+
+    /// Returns the number of pages mapped by the task with the `MemMap`
+    /// syscall.
+    #[must_use]
+    pub const fn mapped(&self) -> usize {
+        self.mapped
+    }
+
+    /// Accounts for `pages` pages about to be mapped by the task. Returns
+    /// `false` if this would exceed the memory limit of the task, in which
+    /// case nothing is accounted for.
+    #[must_use]
+    pub fn charge_memory(&mut self, pages: usize) -> bool {
+        match self.mapped.checked_add(pages) {
+            Some(mapped) if mapped <= self.memory => {
+                self.mapped = mapped;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Gives back `pages` pages accounted for by [`Limits::charge_memory`]
+    /// that could not be mapped.
+    pub fn refund_memory(&mut self, pages: usize) {
+        self.mapped = self.mapped.saturating_sub(pages);
+    }
+
+    /// Creates the limits of a new child of the task, which inherits its
+    /// limits but not its usage. Returns `None` if the task already has as
+    /// many children alive as allowed by its limit.
+    #[must_use]
+    pub fn spawn_child(&self) -> Option<Self> {
+        self.alive
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |alive| {
+                (alive < self.children).then_some(alive + 1)
+            })
+            .ok()?;
+
+        Some(Self {
+            _parent: Some(Child(Arc::clone(&self.alive))),
+            ..Self::inherit(self)
+        })
+    }
+
+    /// Creates limits with the same values as the given ones, without any
+    /// usage nor parent.
+    fn inherit(limits: &Self) -> Self {
+        Self {
+            memory: limits.memory,
+            children: limits.children,
+            messages: limits.messages,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for Limits {
+    /// Creates the limits of a task spawned by the kernel, which does not
+    /// have any parent. Only the number of pending messages is limited, by
+    /// the capacity of the mailbox.
+    fn default() -> Self {
+        Self {
+            memory: UNLIMITED,
+            children: UNLIMITED,
+            messages: config::IPC_MAILBOX_CAPACITY,
+            mapped: 0,
+            alive: Arc::new(AtomicUsize::new(0)),
+            _parent: None,
+        }
+    }
+}
@@ -1,20 +1,50 @@
 use crate::arch::target::addr::{Virtual, virt::User};
 
+pub mod debug;
+pub mod device;
+pub mod dma;
 pub mod elf;
+pub mod env;
+pub mod futex;
+pub mod initrd;
+pub mod irq;
+pub mod layout;
+pub mod limit;
 pub mod object;
 pub mod op;
 pub mod ptr;
+pub mod slice;
+pub mod stack;
 pub mod string;
+pub mod sync;
 pub mod syscall;
+pub mod timer;
+pub mod vdso;
 
-/// The top address of the user stack, exclusive. This is located just below the
-/// last page of the user address space. We don't use the very last page since it
-/// already has caused security issues in the past in the Linux kernel.
+/// The highest top address of the user stack, exclusive. This is located just
+/// below the last page of the user address space. We don't use the very last
+/// page since it already has caused security issues in the past in the Linux
+/// kernel. The actual top of the stack of each task is chosen below this
+/// address when its address space is created (see [`layout`]).
 pub const USER_STACK_TOP: Virtual<User> = Virtual::<User>::new(0x0000_003F_FFFF_F000);
 
-/// By default, each task has a 64 kiB stack.
+/// The size of the range below [`USER_STACK_TOP`] in which the top of the user
+/// stack of each task is randomly placed, unless the layout of user address
+/// spaces is not randomized (see [`crate::config::RANDOMIZE_USER_LAYOUT`]).
+pub const USER_STACK_RANDOM_RANGE: usize = 0x0800_0000;
+
+/// By default, each task has a 64 kiB stack. The stack is not allocated when
+/// the task is created, but page by page when the task first touches it (see
+/// [`stack`]).
 pub const USER_STACK_SIZE: usize = 0x10000;
 
-/// The bottom address of the user stack, inclusive.
-pub const USER_STACK_BOTTOM: Virtual<User> =
-    Virtual::<User>::new(USER_STACK_TOP.as_usize() - USER_STACK_SIZE);
+/// The upper bound, exclusive, of the user addresses that can be mapped by a
+/// task. This is the lowest address that the guard page of the user stack
+/// can have, so that the mappings of a task never overlap its stack wherever
+/// the stack is placed.
+pub const USER_STACK_LIMIT: Virtual<User> = Virtual::<User>::new(
+    USER_STACK_TOP.as_usize()
+        - USER_STACK_RANDOM_RANGE
+        - USER_STACK_SIZE
+        - crate::arch::mmu::PAGE_SIZE,
+);
@@ -3,13 +3,37 @@ use zerocopy::{FromBytes, IntoBytes};
 
 use crate::{
     arch::thread::Thread,
-    user::{self, ptr::Pointer},
+    user::{
+        self,
+        op::Fault,
+        ptr::{Access, Pointer},
+        slice::UserSlice,
+    },
 };
 
 /// An object that is stored in the userland address space. It is a structure
 /// that holds a pointer to the object in the userland address space and a copy
 /// of the object in the kernel address space. This allows us to read and write
 /// the object in the userland address space safely.
+///
+/// # Snapshot model
+/// The other threads of a task can modify its memory at any time while the
+/// kernel handles a syscall, even between two reads of the same field. Every
+/// input of a syscall stored in user memory must therefore be copied exactly
+/// once into kernel memory, and only this copy may be validated and used: a
+/// value read twice could pass the validation and then be different when it
+/// is used.
+///
+/// An `Object` is such a snapshot. It is taken when the object is created,
+/// and the kernel only accesses the copy afterwards. An object whose size
+/// depends on one of its fields, such as an IPC message and its payload, can
+/// be copied in several steps (see [`Self::new_prefix`] and
+/// [`Self::extend`]): each step only copies the bytes that were not copied
+/// yet, so fields that were already validated can never change.
+///
+/// Variable-length inputs that are not described by a structure, such as
+/// strings or buffers, follow the same model: they are copied once with
+/// [`UserSlice::read`] into a kernel buffer that is then validated.
 #[derive(Debug)]
 pub struct Object<'a, T: FromBytes + IntoBytes> {
     /// A pointer to the object in the userland address space.
@@ -17,6 +41,10 @@ pub struct Object<'a, T: FromBytes + IntoBytes> {
 
     /// A copy of the object in the kernel address space.
     inner: T,
+
+    /// The number of bytes at the start of the object that were copied from
+    /// the userland memory. The remaining bytes of the copy are zeroed.
+    copied: usize,
 }
 
 impl<'a, T: FromBytes + IntoBytes> Object<'a, T> {
@@ -24,6 +52,10 @@ impl<'a, T: FromBytes + IntoBytes> Object<'a, T> {
     /// memory. This function will read the object from the userland memory and
     /// store it in the `Object` struct.
     ///
+    /// # Errors
+    /// Returns a [`Fault`] if the object could not be read from the userland
+    /// memory.
+    ///
     /// # Safety
     /// This function is unsafe because it dereference a raw user pointer and
     /// use the `copy_from` function to copy the object from the userland
@@ -31,19 +63,80 @@ impl<'a, T: FromBytes + IntoBytes> Object<'a, T> {
     /// object in userland memory has exactly the same layout as the
     /// object in the kernel: otherwise, this function will cause undefined
     /// behavior.
-    #[must_use]
-    pub unsafe fn new(ptr: Pointer<'a, T>) -> Self {
-        Self {
-            inner: Self::read(&ptr),
+    pub unsafe fn new(ptr: Pointer<'a, T>) -> Result<Self, Fault> {
+        Ok(Self {
+            inner: Self::read(&ptr)?,
+            copied: core::mem::size_of::<T>(),
             ptr,
+        })
+    }
+
+    /// Create an `Object` from the given pointer that resides in the userland
+    /// memory, copying only the first `len` bytes of the object. The other
+    /// bytes of the copy are zeroed until they are copied by [`Self::extend`].
+    /// This allows the header of an object to be copied and validated before
+    /// copying the part of the object that the header describes.
+    ///
+    /// # Errors
+    /// Returns a [`Fault`] if the object could not be read from the userland
+    /// memory.
+    ///
+    /// # Panics
+    /// Panics if `len` is greater than the size of the object.
+    pub fn new_prefix(ptr: Pointer<'a, T>, len: usize) -> Result<Self, Fault> {
+        let mut object = Self {
+            ptr,
+            inner: T::new_zeroed(),
+            copied: 0,
+        };
+        object.extend(len)?;
+        Ok(object)
+    }
+
+    /// Extend the copy of the object to its first `len` bytes. Only the bytes
+    /// that were not copied yet are read from the userland memory, so the
+    /// part of the object that was already copied is left untouched. Nothing
+    /// is copied if the first `len` bytes were already copied.
+    ///
+    /// # Errors
+    /// Returns a [`Fault`] if the object could not be read from the userland
+    /// memory. In this case, the part of the object that was not copied yet
+    /// is unspecified.
+    ///
+    /// # Panics
+    /// Panics if `len` is greater than the size of the object.
+    pub fn extend(&mut self, len: usize) -> Result<(), Fault> {
+        assert!(
+            len <= core::mem::size_of::<T>(),
+            "Length exceeds the size of the object"
+        );
+        if len <= self.copied {
+            return Ok(());
         }
+
+        let range = self.copied..len;
+        let src = self.ptr.inner().cast::<u8>().wrapping_add(range.start);
+        let slice =
+            UserSlice::new(self.ptr.thread(), src, range.len(), Access::Read).ok_or(Fault {
+                address: src.addr(),
+            })?;
+        slice.read_into(&mut self.inner.as_mut_bytes()[range])?;
+        self.copied = len;
+        Ok(())
+    }
+
+    /// Consume the object and return its copy in the kernel address space.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.inner
     }
 
     /// Create an `Object` from the given raw pointer that resides in the
     /// userland memory. This function will read the object from the userland
     /// memory and store it in the `Object` struct.
     ///
-    /// If the pointer is not fully in the userland memory, it returns `None`.
+    /// If the pointer is not fully in the userland memory or if the object
+    /// could not be read, it returns `None`.
     ///
     /// # Safety
     /// This function is unsafe because it dereference a raw user pointer and
@@ -53,22 +146,26 @@ impl<'a, T: FromBytes + IntoBytes> Object<'a, T> {
     /// the kernel: otherwise, this function will cause undefined behavior.
     #[must_use]
     pub unsafe fn from_raw(thread: &'a Thread, ptr: *const T) -> Option<Self> {
-        let user_ptr = Pointer::new(thread, ptr.cast_mut())?;
-        Some(Self::new(user_ptr))
+        let user_ptr = Pointer::new(thread, ptr.cast_mut(), Access::Read)?;
+        Self::new(user_ptr).ok()
     }
 
     /// Manually update the object in the userland memory. This function will
     /// write the object back to the userland memory, so the object in the
     /// userland memory will be updated.
     ///
+    /// # Errors
+    /// Returns a [`Fault`] if the object could not be written to the userland
+    /// memory.
+    ///
     /// # Safety
     /// This function is unsafe because it dereference a raw user pointer and
     /// use the `copy_from` function to copy the object from the userland
     /// memory. This function is safe if the pointer is valid and if the object
     /// in userland memory has exactly the same layout as the object in the
     /// kernel: otherwise, this function will cause undefined behavior.
-    pub unsafe fn update(&mut self) {
-        user::op::write(self.ptr.thread(), &raw const self.inner, self.ptr.inner());
+    pub unsafe fn update(&mut self) -> Result<(), Fault> {
+        user::op::write(self.ptr.thread(), &raw const self.inner, self.ptr.inner())
     }
 
     /// Read the object from the userland memory and return it. It return a
@@ -76,17 +173,20 @@ impl<'a, T: FromBytes + IntoBytes> Object<'a, T> {
     /// memory. This is advantageous to use this over using the `Object` struct
     /// if you do not need to modify the object in the userland +memory.
     ///
+    /// # Errors
+    /// Returns a [`Fault`] if the object could not be read from the userland
+    /// memory.
+    ///
     /// # Safety
     /// This function is unsafe because it dereference a raw user pointer and
     /// use the `copy_from` function to copy the object from the userland
     /// memory. This function is safe if the pointer is valid and if the object
     /// in userland memory has exactly the same layout as the object in the
     /// kernel: otherwise, this function will cause undefined behavior.
-    #[must_use]
-    pub unsafe fn read(src: &Pointer<T>) -> T {
+    pub unsafe fn read(src: &Pointer<T>) -> Result<T, Fault> {
         let mut dst = core::mem::MaybeUninit::<T>::uninit();
-        user::op::read(src.thread(), src.inner(), dst.as_mut_ptr());
-        dst.assume_init()
+        user::op::read(src.thread(), src.inner(), dst.as_mut_ptr())?;
+        Ok(dst.assume_init())
     }
 
     /// Write the object to the userland memory. This function will write the
@@ -95,14 +195,18 @@ impl<'a, T: FromBytes + IntoBytes> Object<'a, T> {
     /// the `Object` struct if you do not need to read the object from the
     /// userland memory, but only need to write it.
     ///
+    /// # Errors
+    /// Returns a [`Fault`] if the object could not be written to the userland
+    /// memory.
+    ///
     /// # Safety
     /// This function is unsafe because it dereference a raw user pointer and
     /// use the `copy_to` function to copy the object to the userland memory.
     /// This function is safe if the pointer is valid and if the object in
     /// userland memory has exactly the same layout as the object in the
     /// kernel: otherwise, this function will cause undefined behavior.
-    pub unsafe fn write(dst: &Pointer<T>, src: &T) {
-        user::op::write(dst.thread(), src, dst.inner());
+    pub unsafe fn write(dst: &Pointer<T>, src: &T) -> Result<(), Fault> {
+        user::op::write(dst.thread(), src, dst.inner())
     }
 }
 
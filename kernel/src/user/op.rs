@@ -3,13 +3,15 @@ use zerocopy::{FromBytes, IntoBytes};
 
 use crate::arch::{self, thread::Thread};
 
+pub use crate::arch::user::Fault;
+
 /// The `USER_OPERATION` variable is used to signal if the current CPU is
-/// performing a user operation or not. This is useful to not panic when a
-/// unrecoverable page fault occurs in kernel space: if an user operation
-/// was in progress, then we can try to kill the process because it is likely
-/// that the fault was caused by the user process who tried to access invalid
-/// memory or gave an invalid pointer to the kernel. If no user operation was
-/// in progress, then we can't do anything and we must panic.
+/// performing a user operation or not. When a fault occurs in kernel space
+/// while an user operation is in progress, the fault was caused by an invalid
+/// pointer given by the user task: the kernel trap handler then aborts the
+/// copy and the fault is reported to the syscall that requested it. If no
+/// user operation was in progress, the fault is a kernel bug and the kernel
+/// panics.
 static USER_OPERATION: AtomicBool = AtomicBool::new(false);
 
 /// Checks if the current CPU is currently performing a user operation.
@@ -18,70 +20,96 @@ pub fn in_operation() -> bool {
     USER_OPERATION.load(Ordering::Relaxed)
 }
 
-/// Copy `len` bytes from the given source address to the given destination
+/// Copy `len` elements from the given source address to the given destination
 /// address. This function should only be used to copy data from user space
 /// to kernel space. If you want to copy data from kernel space to user space,
 /// then you should use [`copy_to`].
 ///
+/// # Errors
+/// Returns a [`Fault`] if the user memory could not be read. In this case,
+/// the content of the destination is unspecified.
+///
 /// # Safety
 /// This function is unsafe because it dereferences a user raw pointer that
 /// could possibly be invalid: it is the caller's responsibility to ensure
 /// that the pointer is valid and does not overlap with kernel space. However,
 /// the caller does not need to ensure that the user memory is readable, as
-/// this function will handle page faults and kill the process if necessary.
-pub unsafe fn copy_from<T: FromBytes>(thread: &Thread, src: *const T, dst: *mut T, len: usize) {
-    thread.root_table().set_current();
+/// faults during the copy are reported to the caller.
+pub unsafe fn copy_from<T: FromBytes>(
+    thread: &Thread,
+    src: *const T,
+    dst: *mut T,
+    len: usize,
+) -> Result<(), Fault> {
+    thread.address_space().root().set_current();
     perform_user_operation(|| {
-        core::ptr::copy_nonoverlapping(src, dst, len);
-    });
+        arch::user::copy(dst.cast(), src.cast(), len * core::mem::size_of::<T>())
+    })
 }
 
-/// Copy `len` bytes from the given source address to the given destination
+/// Copy `len` elements from the given source address to the given destination
 /// address. This function should only be used to copy data from kernel space
 /// to user space. If you want to copy data from user space to kernel space,
 /// then you should use [`copy_from`].
 ///
+/// # Errors
+/// Returns a [`Fault`] if the user memory could not be written. In this case,
+/// the destination may have been partially written.
+///
 /// # Safety
 /// This function is unsafe because it dereferences a user raw pointer that
 /// could possibly be invalid: it is the caller's responsibility to ensure
 /// that the pointer is valid and does not overlap with kernel space. However,
 /// the caller does not need to ensure that the user memory is writable, as
-/// this function will handle page faults and kill the process if necessary.
-pub unsafe fn copy_to<T: IntoBytes>(thread: &Thread, src: *const T, dst: *mut T, len: usize) {
-    thread.root_table().set_current();
+/// faults during the copy are reported to the caller.
+pub unsafe fn copy_to<T: IntoBytes>(
+    thread: &Thread,
+    src: *const T,
+    dst: *mut T,
+    len: usize,
+) -> Result<(), Fault> {
+    thread.address_space().root().set_current();
     perform_user_operation(|| {
-        core::ptr::copy_nonoverlapping(src, dst, len);
-    });
+        arch::user::copy(dst.cast(), src.cast(), len * core::mem::size_of::<T>())
+    })
 }
 
-/// Write the given value to the given address. This function is implemented by
-/// a simple call to [`copy_from`] with the same source and destination
-/// address and a length of 1. This will copy one `T` from the userland memory
-/// to the kernel.
+/// Read the value at the given user address. This function is implemented by
+/// a simple call to [`copy_from`] with a length of 1. This will copy one `T`
+/// from the userland memory to the kernel.
+///
+/// # Errors
+/// Returns a [`Fault`] if the user memory could not be read.
 ///
 /// # Safety
 /// This function is unsafe because it dereferences a user raw pointer that
 /// could possibly be invalid: it is the caller's responsibility to ensure
 /// that the pointer is valid and does not overlap with kernel space. However,
-/// the caller does not need to ensure that the memory is readable, as this
-/// function will handle page faults and kill the process if necessary.
-pub unsafe fn read<T: FromBytes>(thread: &Thread, src: *const T, dst: *mut T) {
-    copy_from(thread, src, dst, 1);
+/// the caller does not need to ensure that the memory is readable, as faults
+/// during the copy are reported to the caller.
+pub unsafe fn read<T: FromBytes>(thread: &Thread, src: *const T, dst: *mut T) -> Result<(), Fault> {
+    copy_from(thread, src, dst, 1)
 }
 
 /// Write the given value to the given address. This function is implemented by
-/// a simple call to [`copy_to`] with the same source and destination address
-/// and a length of 1. This will copy one `T` from the kernel to the userland
-/// memory.
+/// a simple call to [`copy_to`] with a length of 1. This will copy one `T`
+/// from the kernel to the userland memory.
+///
+/// # Errors
+/// Returns a [`Fault`] if the user memory could not be written.
 ///
 /// # Safety
 /// This function is unsafe because it dereferences a user raw pointer that
 /// could possibly be invalid: it is the caller's responsibility to ensure that
 /// the pointer is valid and does not overlap with kernel space. However, the
-/// caller does not need to ensure that the memory is writable, as this function
-/// will handle page faults and kill the task if the access is invalid.
-pub unsafe fn write<T: IntoBytes>(thread: &Thread, src: *const T, dst: *mut T) {
-    copy_to(thread, src, dst, 1);
+/// caller does not need to ensure that the memory is writable, as faults
+/// during the copy are reported to the caller.
+pub unsafe fn write<T: IntoBytes>(
+    thread: &Thread,
+    src: *const T,
+    dst: *mut T,
+) -> Result<(), Fault> {
+    copy_to(thread, src, dst, 1)
 }
 
 /// Signal that the current CPU has started an user operation. This will enable
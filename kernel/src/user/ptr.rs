@@ -1,10 +1,36 @@
 use crate::arch::{
-    riscv64::addr::{Virtual, virt::User},
+    self,
+    mmu::Rights,
+    target::addr::{Virtual, virt::User},
     thread::Thread,
 };
+use kcore::user::{AddressSpace, Validator};
+
+pub use kcore::user::Access;
+
+/// The validator of the pointers given by user space, for the userland
+/// address space of the architecture.
+const VALIDATOR: Validator =
+    Validator::new(Virtual::<User>::END.as_usize() + 1, arch::mmu::PAGE_SIZE);
+
+impl AddressSpace for Thread {
+    /// Walks the page table of the thread to verify that the page is mapped
+    /// with the rights required by the access.
+    fn allows(&self, page: usize, access: Access) -> bool {
+        let required = match access {
+            Access::Read => Rights::USER.union(Rights::READ),
+            Access::Write => Rights::USER.union(Rights::WRITE),
+        };
+        self.address_space()
+            .query(Virtual::<User>::new(page))
+            .is_some_and(|mapping| mapping.rights.contains(required))
+    }
+}
 
 /// This structure encapsulate a pointer to an object in the userland memory:
-/// this structure guarantees that the pointer is in the userland memory. It
+/// this structure guarantees that the pointer is in the userland memory, and
+/// that the memory was mapped with the rights required by the intended access
+/// when the pointer was created. It
 /// also contains the thread that owns the userland memory, so that we can
 /// access the userland memory safely and can change to the correct address
 /// space lazily when we need to access the userland memory, allowing us to
@@ -18,7 +44,7 @@ use crate::arch::{
 /// is no data races in their program: the kernel cannot ensure this because
 /// not all user applications are written in Rust and follow the Rust memory
 /// safety rules.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy)]
 pub struct Pointer<'a, T> {
     thread: &'a Thread,
     inner: *mut T,
@@ -33,34 +59,37 @@ unsafe impl<T: Send> Send for Pointer<'_, T> {}
 
 impl<'a, T> Pointer<'a, T> {
     /// Tries to create a new user pointer. Returns `None` if the given pointer
-    /// is not fully in the userland memory. This is equivalent to calling
-    /// `Pointer::array` with a length of 1.
+    /// is not fully in the userland memory or cannot be accessed as requested.
+    /// This is equivalent to calling `Pointer::array` with a length of 1.
     #[must_use]
-    pub fn new(thread: &'a Thread, ptr: *mut T) -> Option<Self> {
-        Self::array(thread, ptr, 1)
+    pub fn new(thread: &'a Thread, ptr: *mut T, access: Access) -> Option<Self> {
+        Self::array(thread, ptr, 1, access)
     }
 
     /// Tries to create a new user pointer to an array of `len` elements. Returns
-    /// `None` if the given pointer is not fully in the userland memory.
+    /// `None` if the size of the array overflows, if the given pointer is not
+    /// fully in the userland memory, or if any page of the array is not mapped
+    /// in the address space of the thread with the rights required by the
+    /// given access. This is equivalent to calling `Pointer::sized` with the
+    /// size of the array in bytes.
     #[must_use]
-    pub fn array(thread: &'a Thread, ptr: *mut T, len: usize) -> Option<Self> {
-        let start = Virtual::<User>::try_new(ptr.cast::<u8>().addr());
-        let end = Virtual::<User>::try_new(
-            ptr.cast::<u8>()
-                .wrapping_add(core::mem::size_of::<T>() * len)
-                .addr(),
-        );
+    pub fn array(thread: &'a Thread, ptr: *mut T, len: usize, access: Access) -> Option<Self> {
+        let size = core::mem::size_of::<T>().checked_mul(len)?;
+        Self::sized(thread, ptr, size, access)
+    }
 
-        // Check that the whole range is in the userland address space and
-        // that the start address is lower than the end address (to prevent
-        // overflow that would make both addresses valid, but the range
-        // invalid).
-        if let (Some(start), Some(end)) = (start, end)
-            && start <= end
-        {
-            return Some(Self { thread, inner: ptr });
-        }
-        None
+    /// Tries to create a new user pointer to an object of `size` bytes. Returns
+    /// `None` if the object is not fully in the userland memory, or if any
+    /// page of the object is not mapped in the address space of the thread
+    /// with the rights required by the given access.
+    ///
+    /// Validating the pointer beforehand allows syscalls to report an invalid
+    /// buffer to the caller instead of faulting during the copy, which would
+    /// kill the task.
+    #[must_use]
+    pub fn sized(thread: &'a Thread, ptr: *mut T, size: usize, access: Access) -> Option<Self> {
+        VALIDATOR.check(thread, ptr.addr(), size, access)?;
+        Some(Self { thread, inner: ptr })
     }
 
     /// Get the thread that owns the userland memory.
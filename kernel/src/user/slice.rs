@@ -0,0 +1,158 @@
+use alloc::vec::Vec;
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+use crate::{
+    arch::{mmu::Align, thread::Thread},
+    user::{
+        self,
+        op::Fault,
+        ptr::{Access, Pointer},
+    },
+};
+
+/// A slice of `T` stored in the userland address space. Contrary to
+/// [`Pointer`], which is meant for objects of a fixed size, a `UserSlice` can
+/// have an arbitrary length that is only known at runtime, for example a
+/// string or the payload of an IPC message.
+///
+/// The slice is validated when it is created: it must be fully in the userland
+/// address space, and all its pages must be mapped with the rights required by
+/// the intended access. Copies are then split into chunks that never cross a
+/// page boundary, so that each chunk is copied with interrupts disabled for a
+/// bounded amount of time.
+#[derive(Debug, Clone, Copy)]
+pub struct UserSlice<'a, T> {
+    ptr: Pointer<'a, T>,
+    len: usize,
+    access: Access,
+}
+
+impl<'a, T> UserSlice<'a, T> {
+    /// Tries to create a new user slice of `len` elements starting at the
+    /// given pointer. Returns `None` if the slice is not fully in the userland
+    /// address space, or if it cannot be accessed as requested.
+    #[must_use]
+    pub fn new(thread: &'a Thread, ptr: *mut T, len: usize, access: Access) -> Option<Self> {
+        let ptr = Pointer::array(thread, ptr, len, access)?;
+        Some(Self { ptr, len, access })
+    }
+
+    /// Returns the number of elements in the slice.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the slice is empty.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the size of the slice, in bytes.
+    #[must_use]
+    pub const fn size(&self) -> usize {
+        self.len * core::mem::size_of::<T>()
+    }
+
+    /// Returns the thread that owns the userland memory.
+    #[must_use]
+    pub fn thread(&self) -> &'a Thread {
+        self.ptr.thread()
+    }
+
+    /// Returns an iterator over the chunks of the slice that do not cross a
+    /// page boundary, as `(offset, length)` pairs in bytes.
+    fn chunks(&self) -> impl Iterator<Item = (usize, usize)> {
+        let start = self.ptr.inner().addr();
+        let end = start + self.size();
+        let mut offset = 0;
+        core::iter::from_fn(move || {
+            let address = start + offset;
+            if address >= end {
+                return None;
+            }
+            let boundary = (address + 1).page_align_up().min(end);
+            let chunk = (offset, boundary - address);
+            offset += chunk.1;
+            Some(chunk)
+        })
+    }
+}
+
+impl<T: FromBytes + IntoBytes> UserSlice<'_, T> {
+    /// Copies the slice from the userland address space into the given
+    /// buffer.
+    ///
+    /// # Errors
+    /// Returns a [`Fault`] if the userland memory could not be read. In this
+    /// case, the content of the buffer is unspecified.
+    ///
+    /// # Panics
+    /// Panics if the length of the buffer differs from the length of the
+    /// slice.
+    pub fn read_into(&self, dst: &mut [T]) -> Result<(), Fault> {
+        assert_eq!(dst.len(), self.len, "Buffer length mismatch");
+        let src = self.ptr.inner().cast::<u8>();
+        let dst = dst.as_mut_bytes();
+        for (offset, len) in self.chunks() {
+            // SAFETY: The slice was verified to be fully in the userland
+            // address space and readable when it was created, and the chunk
+            // is within the bounds of both the slice and the buffer.
+            unsafe {
+                user::op::copy_from(
+                    self.thread(),
+                    src.add(offset),
+                    dst.as_mut_ptr().add(offset),
+                    len,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the slice from the userland address space into a new vector.
+    ///
+    /// # Errors
+    /// Returns a [`Fault`] if the userland memory could not be read.
+    ///
+    /// # Panics
+    /// Panics if the kernel runs out of memory while allocating the vector.
+    pub fn read(&self) -> Result<Vec<T>, Fault> {
+        let mut vector = T::new_vec_zeroed(self.len).expect("Failed to allocate memory");
+        self.read_into(&mut vector)?;
+        Ok(vector)
+    }
+}
+
+impl<T: IntoBytes + Immutable> UserSlice<'_, T> {
+    /// Copies the given buffer into the slice in the userland address space.
+    ///
+    /// # Errors
+    /// Returns a [`Fault`] if the userland memory could not be written. In
+    /// this case, the slice may have been partially written.
+    ///
+    /// # Panics
+    /// Panics if the length of the buffer differs from the length of the
+    /// slice, or if the slice was not created with [`Access::Write`].
+    pub fn write(&self, src: &[T]) -> Result<(), Fault> {
+        assert_eq!(src.len(), self.len, "Buffer length mismatch");
+        assert_eq!(self.access, Access::Write, "Slice is not writable");
+        let src = src.as_bytes();
+        let dst = self.ptr.inner().cast::<u8>();
+        for (offset, len) in self.chunks() {
+            // SAFETY: The slice was verified to be fully in the userland
+            // address space and writable when it was created, and the chunk
+            // is within the bounds of both the slice and the buffer.
+            unsafe {
+                user::op::copy_to(
+                    self.thread(),
+                    src.as_ptr().add(offset),
+                    dst.add(offset),
+                    len,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{
+    arch::{
+        self,
+        mmu::Align,
+        target::addr::{Virtual, virt::User},
+        thread::Thread,
+        trap::Resume,
+    },
+    mm,
+    user::layout::Layout,
+};
+
+/// Handles a page fault caused by an user access at the given address. If the
+/// address is in the user stack and the page is not yet allocated, the stack
+/// is allocated and the thread can continue its execution. An access to the
+/// guard page below the stack is reported as a stack overflow, and any other
+/// fault terminates the thread.
+pub fn handle_page_fault(thread: &mut Thread, address: usize) -> Resume {
+    let layout = thread.address_space().layout();
+    if (layout.stack_guard().as_usize()..layout.stack_bottom().as_usize()).contains(&address) {
+        log::error!(
+            "User stack overflow (stack: {:#x} - {:#x}, address: {:#x})",
+            layout.stack_bottom().as_usize(),
+            layout.stack_top().as_usize(),
+            address
+        );
+        return Resume::Fault;
+    }
+
+    // If the page is already mapped, the fault was caused by an access that
+    // the rights of the page do not allow (e.g. executing code on the stack),
+    // and allocating the stack would not resolve it.
+    let page = Virtual::<User>::new(address.page_align_down());
+    if !in_stack(&layout, address) || thread.address_space().query(page).is_some() {
+        return Resume::Fault;
+    }
+
+    if !populate(thread, address) {
+        log::error!("Failed to allocate the user stack at {:#x}", address);
+        return Resume::Fault;
+    }
+
+    mm::fault::record(mm::fault::Severity::Minor, address);
+    Resume::Continue
+}
+
+/// Allocates the user stack of the thread from the page containing the given
+/// address up to the top of the stack. Pages are always allocated up to the
+/// top of the stack, so the allocated part of the stack is contiguous and the
+/// allocation can stop at the first page already mapped. Addresses outside of
+/// the stack are ignored.
+///
+/// This is used before handling a syscall with the stack pointer of the
+/// thread, so that buffers located on the used part of the stack can be
+/// validated by the kernel even if the thread has not touched them yet.
+///
+/// Returns `false` if the kernel ran out of memory while allocating the
+/// stack.
+#[must_use]
+pub fn populate(thread: &mut Thread, address: usize) -> bool {
+    let mut space = thread.address_space();
+    let layout = space.layout();
+    if !in_stack(&layout, address) {
+        return true;
+    }
+
+    // Find the first page already mapped above the address: all the pages
+    // above it are mapped as well.
+    let top = layout.stack_top().as_usize();
+    let start = address.page_align_down();
+    let end = (start..top)
+        .step_by(arch::mmu::PAGE_SIZE)
+        .find(|&page| space.query(Virtual::<User>::new(page)).is_some())
+        .unwrap_or(top);
+
+    space
+        .map_range(
+            Virtual::<User>::new(start),
+            end - start,
+            arch::mmu::Rights::RWU,
+        )
+        .is_ok()
+}
+
+/// Returns whether the given address is in the user stack of the given
+/// layout.
+fn in_stack(layout: &Layout, address: usize) -> bool {
+    (layout.stack_bottom().as_usize()..layout.stack_top().as_usize()).contains(&address)
+}
@@ -1,6 +1,6 @@
 use crate::{
     arch::thread::Thread,
-    user::{self, ptr::Pointer},
+    user::{ptr::Access, slice::UserSlice},
 };
 
 /// A string that is stored in the userland address space. It is a structure
@@ -23,8 +23,7 @@ pub struct RawString {
 /// pointer that the [`RawString`] structure cannot make.
 #[derive(Debug)]
 pub struct String<'a> {
-    data: Pointer<'a, u8>,
-    len: usize,
+    data: UserSlice<'a, u8>,
 }
 
 impl<'a> String<'a> {
@@ -44,8 +43,8 @@ impl<'a> String<'a> {
     /// the userland address space, then this function will return `None`.
     #[must_use]
     pub fn new(thread: &'a Thread, ptr: *mut u8, len: usize) -> Option<Self> {
-        let data = Pointer::array(thread, ptr, len)?;
-        Some(Self { data, len })
+        let data = UserSlice::new(thread, ptr, len, Access::Read)?;
+        Some(Self { data })
     }
 
     /// Creates a new user string from a string from a syscall. This function
@@ -58,8 +57,8 @@ impl<'a> String<'a> {
     /// function will return `None`.
     #[must_use]
     pub fn from_raw(thread: &'a Thread, str: &RawString) -> Option<Self> {
-        let data = Pointer::array(thread, str.data, str.len)?;
-        Some(Self { data, len: str.len })
+        let data = UserSlice::new(thread, str.data, str.len, Access::Read)?;
+        Some(Self { data })
     }
 
     /// Fetches a string from the userland address space. This function will
@@ -76,28 +75,17 @@ impl<'a> String<'a> {
     /// - The string is not valid UTF-8
     pub fn fetch(&self) -> Result<alloc::string::String, FetchError> {
         // Check if the string is too long to be handled by the kernel.
-        if self.len > Self::MAX_LEN {
+        if self.data.len() > Self::MAX_LEN {
             return Err(FetchError::StringTooLong);
         }
 
-        // Allocate a vector with the same size as the string and prepare the copy
-        let mut vector = alloc::vec::Vec::with_capacity(self.len);
-        let dst = vector.as_mut_ptr();
-        let src = self.data.inner();
-        let len = self.len;
-
-        // SAFETY: This is safe because we checked that the string is entirely
-        // in the userland address space and that the string is not too long
-        // to be handled by the kernel. Data race are permitted here because
-        // the string resides in the userland address space and the kernel
-        // cannot prevent data races in the userland address space: it is the
-        // responsability of the user program. We also set the length of the
-        // vector after the copy to the correct length.
-        unsafe {
-            user::op::copy_from(self.data.thread(), src, dst, len);
-            vector.set_len(len);
-        }
-
+        // Data race are permitted here because the string resides in the
+        // userland address space and the kernel cannot prevent data races in
+        // the userland address space: it is the responsability of the user
+        // program. The string is copied only once, and the UTF-8 validation
+        // is done on the copy, so it cannot be modified after being validated
+        // (see the snapshot model of [`crate::user::object::Object`]).
+        let vector = self.data.read().map_err(|_| FetchError::InvalidMemory)?;
         Ok(alloc::string::String::from_utf8(vector)?)
     }
 }
@@ -0,0 +1,179 @@
+use crate::{config, future, utils::lock};
+use ::syscall::sync::{
+    ClearError, CreateError, DestroyError, Kind, MAX_VALUE, SignalError, WaitError,
+};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use hashbrown::HashMap;
+use spin::Lazy;
+
+/// The synchronization objects of the system, indexed by identifier.
+static OBJECTS: Lazy<lock::Mutex<HashMap<usize, Object>>> =
+    Lazy::new(|| lock::Mutex::new(HashMap::new()));
+
+/// The identifier of the next object. Identifiers are never reused, so that
+/// a task using the identifier of a destroyed object cannot accidentally use
+/// an unrelated object created later.
+static NEXT_IDENTIFIER: AtomicUsize = AtomicUsize::new(1);
+
+/// A synchronization object.
+#[derive(Debug)]
+struct Object {
+    /// The task that created the object, and that can destroy it.
+    owner: future::task::Identifier,
+
+    /// The kind of the object.
+    kind: Kind,
+
+    /// The value of the object, whose meaning depends on its kind.
+    value: usize,
+
+    /// The queue where the tasks waiting on the object sleep.
+    queue: future::wait::Queue,
+}
+
+impl Object {
+    /// Consumes the value of the object if it allows a wait to complete, and
+    /// returns the value before it was consumed. Returns `None` if the waiting
+    /// task must sleep.
+    fn take(&mut self) -> Option<usize> {
+        if self.value == 0 {
+            return None;
+        }
+        match self.kind {
+            Kind::Event => Some(self.value),
+            Kind::Semaphore => {
+                let value = self.value;
+                self.value -= 1;
+                Some(value)
+            }
+            Kind::Notification => Some(core::mem::take(&mut self.value)),
+        }
+    }
+}
+
+/// Creates a synchronization object of the given kind with the given initial
+/// value, owned by the given task, and returns its identifier.
+///
+/// # Errors
+/// Returns [`CreateError::InvalidValue`] if the value is not valid for the
+/// kind of the object, or [`CreateError::TooManyObjects`] if the task already
+/// owns [`config::SYNC_OBJECTS_PER_TASK`] objects.
+pub fn create(
+    kind: Kind,
+    value: usize,
+    owner: future::task::Identifier,
+) -> Result<usize, CreateError> {
+    if value > MAX_VALUE || (kind == Kind::Event && value > 1) {
+        return Err(CreateError::InvalidValue);
+    }
+
+    let mut objects = OBJECTS.lock();
+    let count = objects
+        .values()
+        .filter(|object| object.owner == owner)
+        .count();
+    if count >= config::SYNC_OBJECTS_PER_TASK {
+        return Err(CreateError::TooManyObjects);
+    }
+
+    let id = NEXT_IDENTIFIER.fetch_add(1, Ordering::Relaxed);
+    objects.insert(
+        id,
+        Object {
+            owner,
+            kind,
+            value,
+            queue: future::wait::Queue::new(),
+        },
+    );
+    Ok(id)
+}
+
+/// Returns whether the object with the given identifier exists.
+#[must_use]
+pub fn exists(id: usize) -> bool {
+    OBJECTS.lock().contains_key(&id)
+}
+
+/// Signals the given object with the given value, as described by its kind,
+/// and wakes up the tasks waiting on it. All of them are woken up, even if
+/// only some of them can complete their wait: the others go back to sleep.
+///
+/// # Errors
+/// Returns [`SignalError::NotFound`] if the object does not exist, or
+/// [`SignalError::Overflow`] if the value of the object would exceed
+/// [`MAX_VALUE`].
+pub fn signal(id: usize, value: usize) -> Result<(), SignalError> {
+    let mut objects = OBJECTS.lock();
+    let object = objects.get_mut(&id).ok_or(SignalError::NotFound)?;
+    let value = match object.kind {
+        Kind::Event => Some(1),
+        Kind::Semaphore => object.value.checked_add(value),
+        Kind::Notification => Some(object.value | value),
+    };
+    object.value = value
+        .filter(|&value| value <= MAX_VALUE)
+        .ok_or(SignalError::Overflow)?;
+    object.queue.wake_all();
+    Ok(())
+}
+
+/// Waits until the value of the given object allows the wait to complete, as
+/// described by its kind, and returns the value before it was consumed.
+///
+/// # Errors
+/// Returns [`WaitError::NotFound`] if the object does not exist, or if it was
+/// destroyed while waiting on it.
+pub async fn wait(id: usize) -> Result<usize, WaitError> {
+    loop {
+        let queue = {
+            let mut objects = OBJECTS.lock();
+            let object = objects.get_mut(&id).ok_or(WaitError::NotFound)?;
+            if let Some(value) = object.take() {
+                return Ok(value);
+            }
+            object.queue.clone()
+        };
+        future::wait::wait(&queue).await;
+    }
+}
+
+/// Resets the value of the given object to zero, without waking up any task.
+///
+/// # Errors
+/// Returns [`ClearError::NotFound`] if the object does not exist.
+pub fn clear(id: usize) -> Result<(), ClearError> {
+    let mut objects = OBJECTS.lock();
+    let object = objects.get_mut(&id).ok_or(ClearError::NotFound)?;
+    object.value = 0;
+    Ok(())
+}
+
+/// Destroys the given object, which must be owned by the given task. The tasks
+/// waiting on the object are woken up and their wait fails.
+///
+/// # Errors
+/// Returns [`DestroyError::NotFound`] if the object does not exist, or
+/// [`DestroyError::NotOwner`] if it is owned by another task.
+pub fn destroy(id: usize, task: future::task::Identifier) -> Result<(), DestroyError> {
+    let mut objects = OBJECTS.lock();
+    let object = objects.get(&id).ok_or(DestroyError::NotFound)?;
+    if object.owner != task {
+        return Err(DestroyError::NotOwner);
+    }
+    if let Some(object) = objects.remove(&id) {
+        object.queue.poison();
+        object.queue.wake_all();
+    }
+    Ok(())
+}
+
+/// Destroys all the objects owned by the given task, waking up the tasks
+/// waiting on them. This must be called when the task terminates.
+pub fn release(task: future::task::Identifier) {
+    for (id, object) in OBJECTS.lock().extract_if(|_, object| object.owner == task) {
+        object.queue.poison();
+        object.queue.wake_all();
+        log::debug!("Synchronization object {} released by task {}", id, task);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::{
+    arch::{self, thread::Thread, trap::Resume},
+    user::{
+        self,
+        ptr::{Access, Pointer},
+        slice::UserSlice,
+        syscall::SyscallReturnValue,
+    },
+};
+use alloc::vec::Vec;
+
+/// Writes the given user buffer to the console, and returns the number of
+/// bytes written.
+///
+/// # Errors
+/// Returns [`::syscall::console::WriteError::BadBuffer`] if the buffer is
+/// larger than [`::syscall::console::MAX_WRITE_SIZE`] or cannot be read.
+pub fn write(
+    thread: &Thread,
+    buffer: *const u8,
+    len: usize,
+) -> Result<SyscallReturnValue, ::syscall::console::WriteError> {
+    if len > ::syscall::console::MAX_WRITE_SIZE {
+        return Err(::syscall::console::WriteError::BadBuffer);
+    }
+
+    let bytes = UserSlice::new(thread, buffer.cast_mut(), len, Access::Read)
+        .ok_or(::syscall::console::WriteError::BadBuffer)?
+        .read()
+        .map_err(|_| ::syscall::console::WriteError::BadBuffer)?;
+    arch::console::write(&bytes);
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: len,
+    })
+}
+
+/// Reads the bytes available from the console into the given user buffer,
+/// without blocking, and returns the number of bytes read. This returns 0
+/// if no input is available.
+///
+/// # Errors
+/// Returns [`::syscall::console::ReadError::BadBuffer`] if the buffer cannot
+/// be written.
+pub fn read(
+    thread: &Thread,
+    buffer: *mut u8,
+    capacity: usize,
+) -> Result<SyscallReturnValue, ::syscall::console::ReadError> {
+    let mut bytes = Vec::new();
+    while bytes.len() < capacity {
+        let Some(byte) = arch::console::read() else {
+            break;
+        };
+        bytes.push(byte);
+    }
+
+    UserSlice::new(thread, buffer, bytes.len(), Access::Write)
+        .ok_or(::syscall::console::ReadError::BadBuffer)?
+        .write(&bytes)
+        .map_err(|_| ::syscall::console::ReadError::BadBuffer)?;
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: bytes.len(),
+    })
+}
+
+/// Reads the bytes available from the console into the given user buffer,
+/// waiting until at least one byte is available, and returns the number of
+/// bytes read. This returns 0 immediately if the buffer is empty.
+///
+/// # Errors
+/// Returns [`::syscall::console::ReadError::BadBuffer`] if the buffer cannot
+/// be written.
+pub async fn read_wait(
+    buffer: Pointer<'_, u8>,
+    capacity: usize,
+) -> Result<SyscallReturnValue, ::syscall::console::ReadError> {
+    let mut bytes = Vec::new();
+    if capacity > 0 {
+        bytes.push(arch::console::read_wait().await);
+    }
+    while bytes.len() < capacity {
+        let Some(byte) = arch::console::read() else {
+            break;
+        };
+        bytes.push(byte);
+    }
+
+    // SAFETY: The buffer was verified to be fully in user space and large
+    // enough to hold `capacity` bytes when creating the pointer.
+    unsafe {
+        user::op::copy_to(buffer.thread(), bytes.as_ptr(), buffer.inner(), bytes.len())
+            .map_err(|_| ::syscall::console::ReadError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: bytes.len(),
+    })
+}
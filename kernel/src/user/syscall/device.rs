@@ -0,0 +1,169 @@
+use crate::{
+    arch::{
+        self,
+        device::Kind,
+        mmu::{Align, PAGE_SIZE, Rights},
+        target::addr::{Physical, Virtual, virt::User},
+        thread::Thread,
+        trap::Resume,
+    },
+    devicetree, future,
+    user::{
+        self,
+        object::Object,
+        ptr::{Access, Pointer},
+        slice::UserSlice,
+        syscall::SyscallReturnValue,
+    },
+};
+use ::syscall::device::{MapError, QueryError, TreeQueryError};
+
+impl From<Kind> for ::syscall::device::DeviceKind {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::VirtioMmio => ::syscall::device::DeviceKind::VirtioMmio,
+        }
+    }
+}
+
+impl From<devicetree::QueryError> for TreeQueryError {
+    fn from(error: devicetree::QueryError) -> Self {
+        match error {
+            devicetree::QueryError::Unavailable => TreeQueryError::Unavailable,
+            devicetree::QueryError::NodeNotFound => TreeQueryError::NodeNotFound,
+            devicetree::QueryError::PropertyNotFound => TreeQueryError::PropertyNotFound,
+        }
+    }
+}
+
+/// Writes the description of the device with the given index to the given
+/// user buffer.
+///
+/// # Errors
+/// Returns [`QueryError::NotFound`] if there is no device with this index, or
+/// [`QueryError::BadBuffer`] if the buffer cannot be written.
+pub fn query(
+    device: usize,
+    info_ptr: Pointer<'_, ::syscall::device::Device>,
+) -> Result<SyscallReturnValue, QueryError> {
+    let device = arch::device::get(device).ok_or(QueryError::NotFound)?;
+    let info = ::syscall::device::Device {
+        kind: ::syscall::device::DeviceKind::from(device.kind) as u64,
+        model: u64::from(device.model),
+        base: device.region.start as u64,
+        size: device.region.length as u64,
+        irq: device.irq.unwrap_or(0) as u64,
+    };
+
+    // SAFETY: The pointer was validated when the `Pointer` was created in the
+    // syscall handler.
+    unsafe {
+        Object::write(&info_ptr, &info).map_err(|_| QueryError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Maps the registers of the device with the given index at `address` in the
+/// address space of the given thread, readable and writable. The device is
+/// claimed by the current task, so that no other task can map it until the
+/// current task terminates, even if the mapping fails. Returns the start
+/// address of the mapping.
+///
+/// # Errors
+/// Returns [`MapError::NotFound`] if there is no device with this index,
+/// [`MapError::AlreadyClaimed`] if the device is driven by another task,
+/// [`MapError::BadRange`] if the address is not page-aligned or the registers
+/// do not fit in the part of the address space available to the task,
+/// [`MapError::AlreadyMapped`] if a page of the range is already mapped or
+/// [`MapError::OutOfMemory`] if a page table could not be allocated.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn map(
+    thread: &mut Thread,
+    device: usize,
+    address: usize,
+) -> Result<SyscallReturnValue, MapError> {
+    let region = arch::device::get(device).ok_or(MapError::NotFound)?.region;
+    let end = address
+        .checked_add(region.length)
+        .ok_or(MapError::BadRange)?;
+    if address == 0 || !address.is_multiple_of(PAGE_SIZE) || end > user::USER_STACK_LIMIT.as_usize()
+    {
+        return Err(MapError::BadRange);
+    }
+
+    let id = future::group::current();
+    if !user::device::claim(device, id) {
+        return Err(MapError::AlreadyClaimed);
+    }
+
+    // SAFETY: The region holds the registers of a device discovered by the
+    // kernel, which is not used by the kernel and was claimed by the task.
+    let mapped = unsafe {
+        thread.address_space().map_device(
+            Virtual::<User>::new(address),
+            Physical::new(region.start),
+            region.length.page_count_up(),
+            Rights::USER | Rights::READ | Rights::WRITE,
+        )
+    };
+    mapped.map_err(|error| match error {
+        arch::mmu::MapError::AlreadyMapped => MapError::AlreadyMapped,
+        arch::mmu::MapError::OutOfMemory => MapError::OutOfMemory,
+        _ => MapError::Unknown,
+    })?;
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: address,
+    })
+}
+
+/// Copies the value of the property with the given name, in the node of the
+/// device tree with the given path, into the given user buffer. At most
+/// `capacity` bytes are written, and the length of the whole value is returned
+/// so that a value larger than the buffer can be detected. A null buffer with
+/// a capacity of zero can be given to only retrieve the length of the value.
+///
+/// # Errors
+/// Returns [`TreeQueryError::BadName`] if the path or the name cannot be read
+/// from user space, [`TreeQueryError::BadBuffer`] if the buffer cannot be
+/// written, or the error of [`devicetree::property`] if the property cannot be
+/// found.
+pub fn tree_query(
+    thread: &Thread,
+    path: *mut u8,
+    path_len: usize,
+    property: *mut u8,
+    property_len: usize,
+    buffer: *mut u8,
+    capacity: usize,
+) -> Result<SyscallReturnValue, TreeQueryError> {
+    let fetch = |ptr, len| {
+        user::string::String::new(thread, ptr, len)
+            .and_then(|string| string.fetch().ok())
+            .ok_or(TreeQueryError::BadName)
+    };
+    let path = fetch(path, path_len)?;
+    let property = fetch(property, property_len)?;
+    let value = devicetree::property(&path, &property)?;
+
+    let len = value.len().min(capacity);
+    if len > 0 {
+        UserSlice::new(thread, buffer, len, Access::Write)
+            .ok_or(TreeQueryError::BadBuffer)?
+            .write(&value[..len])
+            .map_err(|_| TreeQueryError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: value.len(),
+    })
+}
@@ -0,0 +1,33 @@
+use crate::{
+    arch::trap::Resume,
+    user::{self, ptr::Pointer, syscall::SyscallReturnValue},
+};
+use ::syscall::futex::{WaitError, WakeError};
+
+/// Puts the current task to sleep on the given futex word if it holds the
+/// expected value, until another task wakes it up.
+///
+/// # Errors
+/// Returns [`WaitError::BadAddress`] if the word is not properly aligned or
+/// cannot be read, or [`WaitError::ValueMismatch`] if it does not hold the
+/// expected value.
+pub async fn wait(word: Pointer<'_, u32>, expected: u32) -> Result<SyscallReturnValue, WaitError> {
+    user::futex::wait(word, expected).await?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Wakes up at most `count` tasks sleeping on the given futex word. Returns
+/// the number of tasks woken up.
+///
+/// # Errors
+/// Returns [`WakeError::BadAddress`] if the word is not properly aligned or
+/// not mapped.
+pub fn wake(word: &Pointer<'_, u32>, count: usize) -> Result<SyscallReturnValue, WakeError> {
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: user::futex::wake(word, count)?,
+    })
+}
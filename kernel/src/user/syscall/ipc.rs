@@ -1,8 +1,32 @@
 use crate::{
     arch::trap::Resume,
     future, ipc,
-    user::{object::Object, ptr::Pointer, syscall::SyscallReturnValue},
+    user::{
+        object::Object,
+        ptr::{Access, Pointer},
+        slice::UserSlice,
+        syscall::SyscallReturnValue,
+    },
 };
+use zerocopy::{Immutable, IntoBytes};
+
+/// The size of the header of an IPC message, i.e. everything before its
+/// payload.
+const MESSAGE_HEADER_SIZE: usize = core::mem::offset_of!(syscall::ipc::Message, payload);
+
+/// The size of the header of an IPC reply, i.e. everything before its payload.
+const REPLY_HEADER_SIZE: usize = core::mem::offset_of!(syscall::ipc::Reply, payload);
+
+/// Copies the first `len` bytes of the given kernel object to user space.
+/// This allows IPC structures to be copied without their unused payload
+/// bytes. Returns `false` if the user memory cannot be written.
+fn copy_out<T: IntoBytes + Immutable>(ptr: &Pointer<'_, T>, object: &T, len: usize) -> bool {
+    let dst = ptr.inner().cast::<u8>();
+    let Some(slice) = UserSlice::new(ptr.thread(), dst, len, Access::Write) else {
+        return false;
+    };
+    slice.write(&object.as_bytes()[..len]).is_ok()
+}
 
 impl From<ipc::message::SendError> for syscall::ipc::SendError {
     fn from(error: ipc::message::SendError) -> Self {
@@ -10,6 +34,31 @@ impl From<ipc::message::SendError> for syscall::ipc::SendError {
             ipc::message::SendError::PayloadTooLarge => syscall::ipc::SendError::PayloadTooLarge,
             ipc::message::SendError::TaskDoesNotExist => syscall::ipc::SendError::TaskDoesNotExist,
             ipc::message::SendError::TaskDestroyed => syscall::ipc::SendError::TaskDestroyed,
+            ipc::message::SendError::ServiceUnregistered => {
+                syscall::ipc::SendError::ServiceUnregistered
+            }
+            ipc::message::SendError::ServiceDied => syscall::ipc::SendError::ServiceDied,
+            ipc::message::SendError::MailboxFull => syscall::ipc::SendError::MailboxFull,
+        }
+    }
+}
+
+impl From<ipc::message::ForwardError> for syscall::ipc::ForwardError {
+    fn from(error: ipc::message::ForwardError) -> Self {
+        match error {
+            ipc::message::ForwardError::PayloadTooLarge => {
+                syscall::ipc::ForwardError::PayloadTooLarge
+            }
+            ipc::message::ForwardError::NotWaitingForReply => {
+                syscall::ipc::ForwardError::NotWaitingForReply
+            }
+            ipc::message::ForwardError::TaskDoesNotExist => {
+                syscall::ipc::ForwardError::TaskDoesNotExist
+            }
+            ipc::message::ForwardError::TaskDestroyed => syscall::ipc::ForwardError::TaskDestroyed,
+            ipc::message::ForwardError::ServiceUnregistered => {
+                syscall::ipc::ForwardError::ServiceUnregistered
+            }
         }
     }
 }
@@ -33,7 +82,9 @@ impl From<ipc::message::ReplyError> for syscall::ipc::ReplyError {
 }
 
 /// Sends an IPC message from the current task to another task and waits
-/// for a reply.
+/// for a reply. The receiver of the message is a handle to a connection
+/// previously established with the service, ensuring that a task can only
+/// send messages to services it has explicitly connected to.
 ///
 /// # Parameters
 /// - `thread`: The current thread context.
@@ -51,8 +102,11 @@ pub async fn send(
     message_ptr: Pointer<'_, syscall::ipc::Message>,
     reply_ptr: Pointer<'_, syscall::ipc::Reply>,
 ) -> Result<SyscallReturnValue, syscall::ipc::SendError> {
-    // Read the message from user space and get the current task ID.
-    let message = unsafe { Object::<syscall::ipc::Message>::new(message_ptr) };
+    // Take a snapshot of the header of the message, and then only of the
+    // valid part of its payload. The header is never read again from user
+    // space, so the validated payload size cannot change behind our back.
+    let mut message = Object::new_prefix(message_ptr, MESSAGE_HEADER_SIZE)
+        .map_err(|_| syscall::ipc::SendError::BadMessage)?;
 
     // Validate the payload size, ensuring it does not exceed the maximum
     // allowed size to avoid buffer overflows.
@@ -60,9 +114,24 @@ pub async fn send(
         return Err(syscall::ipc::SendError::PayloadTooLarge);
     }
 
+    message
+        .extend(MESSAGE_HEADER_SIZE + message.payload_len)
+        .map_err(|_| syscall::ipc::SendError::BadMessage)?;
+    let message = message.into_inner();
+
+    // Resolve the connection handle to the task providing the service. The
+    // handle must refer to a connection held by the current task.
+    let connection = future::task::with_current_local_set(|local_set| {
+        local_set
+            .handles
+            .lock()
+            .get(ipc::handle::Handle::from(message.receiver))
+    })
+    .ok_or(syscall::ipc::SendError::InvalidDestination)?;
+
     // Send the message and wait for the reply.
     let reply = ipc::message::send(
-        future::task::Identifier::from(message.receiver),
+        connection,
         message.kind,
         &message.payload[..message.payload_len],
     )
@@ -79,11 +148,10 @@ pub async fn send(
         },
     };
 
-    // Write the reply back to user space.
-    // SAFETY: This is safe because we have verified that the pointer is valid
-    // when creating the `Pointer<Reply>` in the syscall handler
-    unsafe {
-        Object::write(&reply_ptr, &reply);
+    // Write the reply back to user space, without the unused part of its
+    // payload.
+    if !copy_out(&reply_ptr, &reply, REPLY_HEADER_SIZE + reply.payload_len) {
+        return Err(syscall::ipc::SendError::BadMessage);
     }
 
     Ok(SyscallReturnValue {
@@ -92,6 +160,61 @@ pub async fn send(
     })
 }
 
+/// Sends an IPC message from the current task to another task without waiting
+/// for a reply. As with [`send`], the receiver of the message is a handle to a
+/// connection previously established with the service. The message is dropped
+/// if the mailbox of the service is full.
+///
+/// # Parameters
+/// - `message_ptr`: An user pointer to the message to be sent.
+///
+/// # Errors
+/// If the syscall fails, an appropriate [`syscall::ipc::SendError`] is
+/// returned describing the failure reason, notably
+/// [`syscall::ipc::SendError::MailboxFull`] if the message was dropped.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub async fn send_oneway(
+    message_ptr: Pointer<'_, syscall::ipc::Message>,
+) -> Result<SyscallReturnValue, syscall::ipc::SendError> {
+    // Take a snapshot of the header of the message, and then only of the
+    // valid part of its payload, as when sending a message waiting for a
+    // reply.
+    let mut message = Object::new_prefix(message_ptr, MESSAGE_HEADER_SIZE)
+        .map_err(|_| syscall::ipc::SendError::BadMessage)?;
+
+    if message.payload_len > syscall::ipc::MAX_PAYLOAD_SIZE {
+        return Err(syscall::ipc::SendError::PayloadTooLarge);
+    }
+
+    message
+        .extend(MESSAGE_HEADER_SIZE + message.payload_len)
+        .map_err(|_| syscall::ipc::SendError::BadMessage)?;
+    let message = message.into_inner();
+
+    let connection = future::task::with_current_local_set(|local_set| {
+        local_set
+            .handles
+            .lock()
+            .get(ipc::handle::Handle::from(message.receiver))
+    })
+    .ok_or(syscall::ipc::SendError::InvalidDestination)?;
+
+    ipc::message::send_oneway(
+        connection,
+        message.kind,
+        &message.payload[..message.payload_len],
+    )
+    .await?;
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
 /// Receives an IPC message for the current task. If no message is available,
 /// the function will yield until a message arrives.
 ///
@@ -111,13 +234,48 @@ pub async fn receive(
     message_ptr: Pointer<'_, syscall::ipc::Message>,
 ) -> Result<SyscallReturnValue, syscall::ipc::ReceiveError> {
     let received = ipc::message::receive().await;
+    deliver(&message_ptr, &received)
+}
+
+/// Receives an IPC message for the current task without waiting. This allows
+/// a service to poll its mailbox along with other sources of events.
+///
+/// # Errors
+/// Returns [`syscall::ipc::ReceiveError::NoMessage`] if the mailbox of the
+/// task is empty, or another [`syscall::ipc::ReceiveError`] describing the
+/// failure reason.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub fn try_receive(
+    message_ptr: &Pointer<'_, syscall::ipc::Message>,
+) -> Result<SyscallReturnValue, syscall::ipc::ReceiveError> {
+    let received = ipc::message::try_receive().ok_or(syscall::ipc::ReceiveError::NoMessage)?;
+    deliver(message_ptr, &received)
+}
 
+/// Writes a message received by the current task to user space.
+fn deliver(
+    message_ptr: &Pointer<'_, syscall::ipc::Message>,
+    received: &ipc::message::Message,
+) -> Result<SyscallReturnValue, syscall::ipc::ReceiveError> {
     // Construct the message to be sent back to user space.
     let message = syscall::ipc::Message {
         sender: usize::from(received.sender),
         receiver: usize::from(received.receiver),
         kind: received.operation,
         payload_len: received.payload_len,
+        sender_info: syscall::ipc::SenderInfo {
+            badge: received.metadata.badge,
+            generation: received.metadata.generation,
+            service: received.metadata.service,
+            flags: if received.metadata.oneway {
+                syscall::ipc::SenderInfo::ONEWAY
+            } else {
+                0
+            },
+        },
         payload: {
             let mut payload = [0u8; syscall::ipc::MAX_PAYLOAD_SIZE];
             payload[..received.payload_len]
@@ -126,11 +284,14 @@ pub async fn receive(
         },
     };
 
-    // Write the message back to user space.
-    // SAFETY: This is safe because we have verified that the pointer is valid
-    // when creating the `Pointer<Message>`
-    unsafe {
-        Object::write(&message_ptr, &message);
+    // Write the message back to user space, without the unused part of its
+    // payload.
+    if !copy_out(
+        message_ptr,
+        &message,
+        MESSAGE_HEADER_SIZE + message.payload_len,
+    ) {
+        return Err(syscall::ipc::ReceiveError::BadBuffer);
     }
 
     Ok(SyscallReturnValue {
@@ -156,17 +317,87 @@ pub fn reply(
     to: usize,
     reply: Pointer<syscall::ipc::Reply>,
 ) -> Result<SyscallReturnValue, syscall::ipc::ReplyError> {
-    // Read the reply from user space and get the current task ID.
-    let reply = unsafe { Object::<syscall::ipc::Reply>::new(reply) };
+    // Take a snapshot of the header of the reply, and then only of the valid
+    // part of its payload.
+    let mut reply = Object::new_prefix(reply, REPLY_HEADER_SIZE)
+        .map_err(|_| syscall::ipc::ReplyError::BadMessage)?;
+
+    if reply.payload_len > syscall::ipc::MAX_PAYLOAD_SIZE {
+        return Err(syscall::ipc::ReplyError::PayloadTooLarge);
+    }
+
+    reply
+        .extend(REPLY_HEADER_SIZE + reply.payload_len)
+        .map_err(|_| syscall::ipc::ReplyError::BadMessage)?;
+    let reply = reply.into_inner();
 
     // Reply to the message. This is a synchronous operation that is guaranteed
     // to complete immediately since the task being replied to is waiting for
     // the reply. If the task is not waiting for a reply, an error is returned.
-    ipc::message::reply(
-        future::task::Identifier::from(to),
-        reply.status,
-        &reply.payload[..reply.payload_len],
-    )?;
+    let to = future::task::Identifier::from_user(to)
+        .ok_or(syscall::ipc::ReplyError::TaskDoesNotExist)?;
+    ipc::message::reply(to, reply.status, &reply.payload[..reply.payload_len])?;
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Forwards an IPC message received from a client to another service, which
+/// then replies directly to the client in place of the current task.
+///
+/// # Parameters
+/// - `to`: The task ID of the client whose message is forwarded.
+/// - `message_ptr`: An user pointer to the message to forward. Its receiver
+///   is the handle of a connection to the service.
+///
+/// # Errors
+/// If the syscall fails, an appropriate [`syscall::ipc::ForwardError`] is
+/// returned describing the failure reason. The current task must then still
+/// reply to the client.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub async fn forward(
+    to: usize,
+    message_ptr: Pointer<'_, syscall::ipc::Message>,
+) -> Result<SyscallReturnValue, syscall::ipc::ForwardError> {
+    // Take a snapshot of the header of the message, and then only of the
+    // valid part of its payload.
+    let mut message = Object::new_prefix(message_ptr, MESSAGE_HEADER_SIZE)
+        .map_err(|_| syscall::ipc::ForwardError::BadMessage)?;
+
+    if message.payload_len > syscall::ipc::MAX_PAYLOAD_SIZE {
+        return Err(syscall::ipc::ForwardError::PayloadTooLarge);
+    }
+
+    message
+        .extend(MESSAGE_HEADER_SIZE + message.payload_len)
+        .map_err(|_| syscall::ipc::ForwardError::BadMessage)?;
+    let message = message.into_inner();
+
+    // Resolve the connection handle to the task providing the service. As
+    // when sending a message, the handle must refer to a connection held by
+    // the current task.
+    let connection = future::task::with_current_local_set(|local_set| {
+        local_set
+            .handles
+            .lock()
+            .get(ipc::handle::Handle::from(message.receiver))
+    })
+    .ok_or(syscall::ipc::ForwardError::InvalidDestination)?;
+
+    let to = future::task::Identifier::from_user(to)
+        .ok_or(syscall::ipc::ForwardError::TaskDoesNotExist)?;
+    ipc::message::forward(
+        to,
+        connection,
+        message.kind,
+        &message.payload[..message.payload_len],
+    )
+    .await?;
 
     Ok(SyscallReturnValue {
         resume: Resume::Continue,
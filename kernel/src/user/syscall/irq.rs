@@ -0,0 +1,97 @@
+use crate::{arch::trap::Resume, future, user, user::syscall::SyscallReturnValue};
+
+/// Binds the given interrupt source to the current task, so that it can wait
+/// for the interrupts of the source with [`wait`].
+///
+/// # Errors
+/// Returns a [`::syscall::irq::RegisterError`] if there is no interrupt
+/// controller, if the source does not exist or is used by the kernel, or if
+/// it is already bound to a task.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn register(irq: usize) -> Result<SyscallReturnValue, ::syscall::irq::RegisterError> {
+    let id = future::group::current();
+    user::irq::register(irq, id)?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Acknowledges the previous interrupt of the given source, and waits until
+/// the source raises another interrupt.
+///
+/// # Errors
+/// Returns [`::syscall::irq::WaitError::NotBound`] if the source is not bound
+/// to the current task.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub async fn wait(irq: usize) -> Result<SyscallReturnValue, ::syscall::irq::WaitError> {
+    let id = future::group::current();
+    user::irq::wait(irq, id).await?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Masks the given source until the current task unmasks it with [`unmask`].
+///
+/// # Errors
+/// Returns [`::syscall::irq::ControlError::NotBound`] if the source is not
+/// bound to the current task.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn mask(irq: usize) -> Result<SyscallReturnValue, ::syscall::irq::ControlError> {
+    let id = future::group::current();
+    user::irq::mask(irq, id)?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Unmasks the given source masked by the current task with [`mask`].
+///
+/// # Errors
+/// Returns [`::syscall::irq::ControlError::NotBound`] if the source is not
+/// bound to the current task.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn unmask(irq: usize) -> Result<SyscallReturnValue, ::syscall::irq::ControlError> {
+    let id = future::group::current();
+    user::irq::unmask(irq, id)?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Routes the given source to the CPU with the given index.
+///
+/// # Errors
+/// Returns a [`::syscall::irq::AffinityError`] if the source is not bound to
+/// the current task, or if it cannot be routed to this CPU.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn set_affinity(
+    irq: usize,
+    cpu: usize,
+) -> Result<SyscallReturnValue, ::syscall::irq::AffinityError> {
+    let id = future::group::current();
+    user::irq::set_affinity(irq, id, cpu)?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
@@ -0,0 +1,120 @@
+use crate::{
+    arch::{self, thread::Thread, trap::Resume},
+    user::{
+        self,
+        ptr::{Access, Pointer},
+        syscall::SyscallReturnValue,
+    },
+};
+use ::log::LevelFilter;
+use ::syscall::log::{ControlError, Level, MAX_MODULE_LEN, ReadError, Record};
+
+impl From<arch::log::LevelError> for ControlError {
+    fn from(error: arch::log::LevelError) -> Self {
+        match error {
+            arch::log::LevelError::ModuleTooLong => ControlError::BadModule,
+            arch::log::LevelError::TooManyModules => ControlError::TooManyModules,
+        }
+    }
+}
+
+/// Changes the level of the kernel log for the given module and its
+/// submodules, or the global level if the name of the module is empty.
+/// Returns the previous level, which is [`Level::Default`] if the module did
+/// not have its own level.
+///
+/// # Errors
+/// Returns [`ControlError::InvalidLevel`] if the level is unknown or if
+/// [`Level::Default`] is given as the global level,
+/// [`ControlError::BadModule`] if the name of the module cannot be read from
+/// user space, or [`ControlError::TooManyModules`] if too many modules
+/// already have their own level.
+pub fn control(
+    thread: &Thread,
+    module_ptr: *mut u8,
+    module_len: usize,
+    level: usize,
+) -> Result<SyscallReturnValue, ControlError> {
+    let level = match Level::from(level) {
+        Level::Unknown => return Err(ControlError::InvalidLevel),
+        Level::Default => None,
+        level => Some(to_filter(level)),
+    };
+
+    let previous = if module_len == 0 {
+        let level = level.ok_or(ControlError::InvalidLevel)?;
+        Some(arch::log::set_global_level(level))
+    } else {
+        if module_len > MAX_MODULE_LEN {
+            return Err(ControlError::BadModule);
+        }
+        let module = user::string::String::new(thread, module_ptr, module_len)
+            .ok_or(ControlError::BadModule)?
+            .fetch()
+            .map_err(|_| ControlError::BadModule)?;
+        arch::log::set_module_level(&module, level)?
+    };
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: previous.map_or(Level::Default, from_filter) as usize,
+    })
+}
+
+/// Converts a level of the syscall interface into a level of the `log`
+/// crate. [`Level::Unknown`] and [`Level::Default`] must have been handled by
+/// the caller.
+fn to_filter(level: Level) -> LevelFilter {
+    match level {
+        Level::Error => LevelFilter::Error,
+        Level::Warn => LevelFilter::Warn,
+        Level::Info => LevelFilter::Info,
+        Level::Debug => LevelFilter::Debug,
+        Level::Trace => LevelFilter::Trace,
+        Level::Off | Level::Unknown | Level::Default => LevelFilter::Off,
+    }
+}
+
+/// Converts a level of the `log` crate into a level of the syscall
+/// interface.
+fn from_filter(filter: LevelFilter) -> Level {
+    match filter {
+        LevelFilter::Off => Level::Off,
+        LevelFilter::Error => Level::Error,
+        LevelFilter::Warn => Level::Warn,
+        LevelFilter::Info => Level::Info,
+        LevelFilter::Debug => Level::Debug,
+        LevelFilter::Trace => Level::Trace,
+    }
+}
+
+/// Reads at most `capacity` messages of the kernel log into the user buffer,
+/// starting at the message with the given number, or at the oldest message
+/// kept if that message was already overwritten. Returns the number of
+/// messages written, which is zero if no message was logged since.
+///
+/// # Errors
+/// Returns [`ReadError::BadBuffer`] if the messages cannot be written into
+/// the user buffer.
+pub fn read(
+    thread: &Thread,
+    sequence: usize,
+    buffer_ptr: *mut Record,
+    capacity: usize,
+) -> Result<SyscallReturnValue, ReadError> {
+    let buffer =
+        Pointer::array(thread, buffer_ptr, capacity, Access::Write).ok_or(ReadError::BadBuffer)?;
+    let records = arch::log::read(sequence as u64, capacity);
+
+    // SAFETY: The buffer was verified to be fully in user space and large
+    // enough to hold `capacity` entries when creating the pointer.
+    unsafe {
+        user::op::copy_to(thread, records.as_ptr(), buffer.inner(), records.len())
+            .map_err(|_| ReadError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: records.len(),
+    })
+}
@@ -0,0 +1,295 @@
+use crate::{
+    arch::{
+        mmu::{Align, MapError, PAGE_SIZE, Rights, UnmapError},
+        target::addr::{Virtual, virt::User},
+        thread::Thread,
+        trap::Resume,
+    },
+    future,
+    mm::{self, phys::AllocationFlags},
+    user::{
+        self,
+        object::Object,
+        ptr::{Access, Pointer},
+        syscall::SyscallReturnValue,
+    },
+};
+use ::syscall::{
+    memory::{
+        AllocDmaError, MAX_DMA_SIZE, ProtectError, Protection, SubscribeError, UnsubscribeError,
+    },
+    power::NOTICE_OPERATION,
+};
+use alloc::sync::Arc;
+
+/// Maps `size` bytes of fresh zeroed memory starting at `address` in the
+/// address space of the given thread, with the given protection. Returns the
+/// start address of the mapping.
+///
+/// The range must not overlap the null page nor the region reserved for the
+/// user stack and its guard page, which is populated by the kernel on demand.
+///
+/// # Errors
+/// Returns [`::syscall::memory::MapError::BadRange`] if the address is not
+/// page-aligned, if the size is zero or if the range is not allowed,
+/// [`::syscall::memory::MapError::InvalidProtection`] if the protection
+/// contains unknown flags, [`::syscall::memory::MapError::AlreadyMapped`] if
+/// a page of the range is already mapped or
+/// [`::syscall::memory::MapError::OutOfMemory`] if the kernel ran out of
+/// memory and the out-of-memory handler could not free any (see
+/// [`mm::oom`]).
+pub async fn map(
+    thread: &mut Thread,
+    address: usize,
+    size: usize,
+    protection: Protection,
+) -> Result<SyscallReturnValue, ::syscall::memory::MapError> {
+    let protection = Protection::from_bits(protection.bits())
+        .ok_or(::syscall::memory::MapError::InvalidProtection)?;
+    let end = address
+        .checked_add(size)
+        .ok_or(::syscall::memory::MapError::BadRange)?;
+    if address == 0
+        || size == 0
+        || !address.is_multiple_of(PAGE_SIZE)
+        || end > user::USER_STACK_LIMIT.as_usize()
+    {
+        return Err(::syscall::memory::MapError::BadRange);
+    }
+
+    // Account for the pages before mapping them, so that threads of the task
+    // mapping memory at the same time cannot exceed the limit together.
+    let pages = size.div_ceil(PAGE_SIZE);
+    let limits = future::task::with_current_local_set(|set| Arc::clone(&set.limits));
+    if !limits.lock().charge_memory(pages) {
+        return Err(::syscall::memory::MapError::LimitExceeded);
+    }
+
+    // The pages mapped before running out of memory are unmapped, so mapping
+    // the range can be retried once the out-of-memory handler freed memory.
+    loop {
+        let result = thread.address_space().map_range(
+            Virtual::<User>::new(address),
+            size,
+            rights(protection),
+        );
+        match result {
+            Ok(()) => break,
+            Err(MapError::OutOfMemory) if mm::oom::handle().await => (),
+            Err(error) => {
+                limits.lock().refund_memory(pages);
+                return Err(match error {
+                    MapError::AlreadyMapped => ::syscall::memory::MapError::AlreadyMapped,
+                    MapError::OutOfMemory => ::syscall::memory::MapError::OutOfMemory,
+                    _ => ::syscall::memory::MapError::Unknown,
+                });
+            }
+        }
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: address,
+    })
+}
+
+/// Allocates `size` bytes of physically contiguous zeroed memory, maps it at
+/// `address` in the address space of the given thread, readable and writable,
+/// and writes its physical address to `physical`. Returns the start address
+/// of the mapping.
+///
+/// The memory is mapped as device memory, so it is not freed if the task
+/// unmaps it: a device may still be accessing it. Instead, it is recorded in
+/// [`user::dma`] and returned to the frame allocator when the task
+/// terminates.
+///
+/// # Errors
+/// Returns [`AllocDmaError::BadRange`] if the address is not page-aligned, if
+/// the size is zero or larger than [`MAX_DMA_SIZE`] or if the range is not
+/// allowed, [`AllocDmaError::BadBuffer`] if the physical address cannot be
+/// written to `physical`, [`AllocDmaError::AlreadyMapped`] if a page of the
+/// range is already mapped or [`AllocDmaError::OutOfMemory`] if there is not
+/// enough contiguous free memory. Nothing is allocated on error.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn alloc_dma(
+    thread: &mut Thread,
+    address: usize,
+    size: usize,
+    physical: *mut usize,
+) -> Result<SyscallReturnValue, AllocDmaError> {
+    let end = address.checked_add(size).ok_or(AllocDmaError::BadRange)?;
+    if address == 0
+        || size == 0
+        || size > MAX_DMA_SIZE
+        || !address.is_multiple_of(PAGE_SIZE)
+        || end > user::USER_STACK_LIMIT.as_usize()
+    {
+        return Err(AllocDmaError::BadRange);
+    }
+
+    // Check the buffer before allocating anything. It cannot be in the range
+    // being mapped, which must not be mapped yet, so it remains valid once the
+    // memory is mapped.
+    if Pointer::new(thread, physical, Access::Write).is_none() {
+        return Err(AllocDmaError::BadBuffer);
+    }
+
+    let count = size.page_count_up();
+    let base = mm::phys::allocate_range(count, AllocationFlags::ZEROED)
+        .ok_or(AllocDmaError::OutOfMemory)?;
+    let start = Virtual::<User>::new(address);
+
+    // SAFETY: The frames were just allocated for the task, and are not used
+    // by the kernel or by another task.
+    let mapped = unsafe {
+        thread.address_space().map_device(
+            start,
+            base,
+            count,
+            Rights::USER | Rights::READ | Rights::WRITE,
+        )
+    };
+    if let Err(error) = mapped {
+        mm::phys::deallocate_range(base, count);
+        return Err(match error {
+            MapError::AlreadyMapped => AllocDmaError::AlreadyMapped,
+            MapError::OutOfMemory => AllocDmaError::OutOfMemory,
+            _ => AllocDmaError::Unknown,
+        });
+    }
+
+    let written = Pointer::new(thread, physical, Access::Write).is_some_and(|ptr| {
+        // SAFETY: The pointer was validated when the `Pointer` was created.
+        unsafe { Object::write(&ptr, &usize::from(base)).is_ok() }
+    });
+    if !written {
+        // The pages are mapped as device memory, so unmapping them does not
+        // free the frames.
+        _ = thread.address_space().unmap_range(start, size);
+        mm::phys::deallocate_range(base, count);
+        return Err(AllocDmaError::BadBuffer);
+    }
+
+    let id = future::group::current();
+    user::dma::record(id, base, count);
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: address,
+    })
+}
+
+/// Changes the protection of the pages covering `size` bytes starting at
+/// `address` in the address space of the given thread. A size of zero is
+/// accepted and does nothing.
+///
+/// [`Protection::NONE`] is implemented by keeping the pages readable but
+/// removing the user bit, since a page table entry without any right would be
+/// interpreted by the MMU as a pointer to another table. The pages remain
+/// mapped, but any access from the user thread, or from the kernel on its
+/// behalf, faults.
+///
+/// # Errors
+/// Returns [`ProtectError::BadRange`] if the address is not page-aligned or
+/// if the range is not fully in the user address space,
+/// [`ProtectError::InvalidProtection`] if the protection contains unknown
+/// flags, [`ProtectError::NotMapped`] if a page of the range is not mapped or
+/// [`ProtectError::OutOfMemory`] if a huge page could not be split.
+pub fn protect(
+    thread: &mut Thread,
+    address: usize,
+    size: usize,
+    protection: Protection,
+) -> Result<SyscallReturnValue, ProtectError> {
+    let protection =
+        Protection::from_bits(protection.bits()).ok_or(ProtectError::InvalidProtection)?;
+    if !address.is_multiple_of(PAGE_SIZE) {
+        return Err(ProtectError::BadRange);
+    }
+
+    if size > 0 {
+        let last = address
+            .checked_add(size - 1)
+            .ok_or(ProtectError::BadRange)?;
+        let start = Virtual::<User>::try_new(address).ok_or(ProtectError::BadRange)?;
+        Virtual::<User>::try_new(last).ok_or(ProtectError::BadRange)?;
+
+        thread
+            .address_space()
+            .protect(start, size, rights(protection))
+            .map_err(|error| match error {
+                UnmapError::NotMapped => ProtectError::NotMapped,
+                UnmapError::OutOfMemory => ProtectError::OutOfMemory,
+            })?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Subscribes the current task to the changes of the memory pressure, which
+/// are sent to it as messages from the kernel with the given operation (see
+/// [`::syscall::memory::PressureNotice`]). Subscribing again replaces the
+/// operation of the messages.
+///
+/// # Errors
+/// Returns [`SubscribeError::InvalidOperation`] if the operation is reserved
+/// for the power notices.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn subscribe_pressure(operation: usize) -> Result<SyscallReturnValue, SubscribeError> {
+    if operation == NOTICE_OPERATION {
+        return Err(SubscribeError::InvalidOperation);
+    }
+    mm::pressure::subscribe(future::group::current(), operation);
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Unsubscribes the current task from the changes of the memory pressure.
+///
+/// # Errors
+/// Returns [`UnsubscribeError::NotSubscribed`] if the task is not subscribed.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn unsubscribe_pressure() -> Result<SyscallReturnValue, UnsubscribeError> {
+    if !mm::pressure::unsubscribe(future::group::current()) {
+        return Err(UnsubscribeError::NotSubscribed);
+    }
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Converts a protection requested by the user into the rights of the page
+/// table entries.
+fn rights(protection: Protection) -> Rights {
+    if protection == Protection::NONE {
+        return Rights::READ;
+    }
+
+    let mut rights = Rights::USER;
+    // Write-only pages are reserved by the RISC-V specification, so writable
+    // pages are always made readable.
+    if protection.contains(Protection::READ) || protection.contains(Protection::WRITE) {
+        rights |= Rights::READ;
+    }
+    if protection.contains(Protection::WRITE) {
+        rights |= Rights::WRITE;
+    }
+    if protection.contains(Protection::EXECUTE) {
+        rights |= Rights::EXECUTE;
+    }
+    rights
+}
@@ -1,12 +1,35 @@
 use crate::{
     arch::{self, trap::Resume},
     future,
-    user::{self, ptr::Pointer, syscall},
+    user::{
+        self,
+        ptr::{Access, Pointer},
+        syscall,
+    },
 };
-use ::syscall::SyscallOp;
+use ::syscall::{
+    SyscallOp,
+    args::{self, SyscallArgs, SyscallResult},
+};
+use core::sync::atomic::Ordering;
 
+pub mod console;
+pub mod device;
+pub mod futex;
 pub mod ipc;
+pub mod irq;
+pub mod log;
+pub mod memory;
+pub mod power;
+pub mod random;
 pub mod service;
+pub mod stats;
+pub mod sync;
+pub mod table;
+pub mod task;
+pub mod thread;
+pub mod timer;
+pub mod trace;
 
 /// Represents the return value of a syscall, including how the thread
 /// should resume execution.
@@ -25,52 +48,186 @@ pub struct SyscallReturnValue {
 ///   never happen in normal operation).
 #[must_use]
 #[allow(clippy::too_many_lines)]
-#[allow(clippy::cast_possible_wrap)]
-#[allow(clippy::cast_possible_truncation)]
 pub async fn handle_syscall(thread: &mut arch::thread::Thread) -> Resume {
-    let args = arch::thread::get_syscall_args(thread);
+    let registers = arch::thread::get_syscall_args(thread);
     let id = arch::thread::get_syscall_id(thread);
 
-    log::trace!("Handling syscall ID: {}", id);
-    let result = match SyscallOp::from(id) {
+    ::log::trace!("Handling syscall ID: {}", id);
+    let op = SyscallOp::from(id);
+    let start = crate::trace::syscall_enter(id, registers);
+    crate::stats::count_syscall(id);
+
+    // Verify that the task holds all the capabilities required by the
+    // syscall before doing anything else.
+    let capabilities = future::task::with_current_local_set(|set| *set.capabilities.lock());
+    if !::syscall::capability::allowed(op, capabilities) {
+        ::log::debug!("Syscall {:?} denied: missing capabilities", op);
+        let denied = -::syscall::capability::PERMISSION_DENIED;
+        crate::trace::syscall_exit(id, denied.cast_unsigned(), start);
+        arch::thread::set_syscall_return(thread, denied);
+        return Resume::Continue;
+    }
+
+    // Remember the syscall being handled, so that the panic handler can tell
+    // what the task was doing if the kernel panics while handling it.
+    future::task::with_current_local_set(|set| set.syscall.store(id, Ordering::Relaxed));
+
+    // Allocate the part of the user stack that is in use by the thread, so
+    // that buffers on the stack that the thread has not touched yet can be
+    // validated and accessed by the kernel.
+    if !user::stack::populate(thread, arch::thread::get_stack_pointer(thread)) {
+        ::log::warn!("Failed to allocate the user stack before syscall {:?}", op);
+    }
+
+    let result = match op {
         SyscallOp::Nop => Ok(SyscallReturnValue {
             resume: Resume::Continue,
             value: 0,
         }),
-        SyscallOp::TaskExit => Ok(SyscallReturnValue {
-            resume: Resume::Terminate(args[0] as i32),
-            value: 0,
-        }),
+        SyscallOp::TaskExit => {
+            let args = args::TaskExit::decode(&registers);
+            Ok(SyscallReturnValue {
+                resume: Resume::Terminate(args.code),
+                value: 0,
+            })
+        }
         SyscallOp::TaskYield => Ok(SyscallReturnValue {
             resume: Resume::Yield,
             value: 0,
         }),
+        SyscallOp::TaskSpawn => {
+            let args = args::TaskSpawn::decode(&registers);
+            syscall::task::spawn(
+                thread,
+                args.image,
+                args.image_len,
+                args.env,
+                args.env_len,
+                args.capabilities,
+            )
+            .map_err(isize::from)
+        }
+        SyscallOp::TaskWait => {
+            let args = args::TaskWait::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.info, Access::Write) {
+                syscall::task::wait(args.task, ptr)
+                    .await
+                    .map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::task::WaitError::BadBuffer))
+            }
+        }
+        SyscallOp::TaskUsage => {
+            let args = args::TaskUsage::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.usage, Access::Write) {
+                syscall::task::usage(args.task, ptr).map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::task::UsageError::BadBuffer))
+            }
+        }
+        SyscallOp::TaskDropCaps => {
+            let args = args::TaskDropCaps::decode(&registers);
+            Ok(syscall::task::drop_capabilities(args.capabilities))
+        }
+        SyscallOp::TaskKill => {
+            let args = args::TaskKill::decode(&registers);
+            syscall::task::kill(args.task).await.map_err(isize::from)
+        }
+        SyscallOp::TaskSetLimit => {
+            let args = args::TaskSetLimit::decode(&registers);
+            syscall::task::set_limit(args.task, args.limit, args.value).map_err(isize::from)
+        }
+        SyscallOp::TaskSetEssential => {
+            let args = args::TaskSetEssential::decode(&registers);
+            syscall::task::set_essential(args.task, args.essential).map_err(isize::from)
+        }
+        SyscallOp::TaskTrace => {
+            let args = args::TaskTrace::decode(&registers);
+            syscall::task::trace(args.task, args.enabled).map_err(isize::from)
+        }
+        SyscallOp::TaskSuspend => {
+            let args = args::TaskSuspend::decode(&registers);
+            syscall::task::suspend(args.task).map_err(isize::from)
+        }
+        SyscallOp::TaskResume => {
+            let args = args::TaskResume::decode(&registers);
+            syscall::task::resume(args.task, args.step).map_err(isize::from)
+        }
+        SyscallOp::TaskGetRegs => {
+            let args = args::TaskGetRegs::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.registers, Access::Write) {
+                syscall::task::get_registers(args.thread, ptr).map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::task::DebugError::BadBuffer))
+            }
+        }
+        SyscallOp::TaskSetRegs => {
+            let args = args::TaskSetRegs::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.registers.cast_mut(), Access::Read) {
+                syscall::task::set_registers(args.thread, ptr).map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::task::DebugError::BadBuffer))
+            }
+        }
+        SyscallOp::TaskSetPriority => {
+            let args = args::TaskSetPriority::decode(&registers);
+            syscall::task::set_priority(args.task, args.priority).map_err(isize::from)
+        }
+        SyscallOp::SystemStatistics => {
+            let args = args::SystemStatistics::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.statistics, Access::Write) {
+                syscall::stats::system(ptr).map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::stats::StatisticsError::BadBuffer))
+            }
+        }
         SyscallOp::ServiceRegister => {
-            let name_ptr = core::ptr::with_exposed_provenance_mut::<u8>(args[0]);
-            let name_len = args[1];
-            syscall::service::register(thread, name_ptr, name_len).map_err(isize::from)
+            let args = args::ServiceRegister::decode(&registers);
+            match syscall::service::fetch_name(thread, args.name.cast_mut(), args.name_len) {
+                Some(name) => syscall::service::register(name, args.flags)
+                    .await
+                    .map_err(isize::from),
+                None => Err(isize::from(::syscall::service::RegisterError::BadName)),
+            }
         }
         SyscallOp::ServiceUnregister => {
             // Currently, no arguments are needed for unregistration since
             // the service is associated with the current task itself.
-            syscall::service::unregister().map_err(isize::from)
+            syscall::service::unregister().await.map_err(isize::from)
         }
         SyscallOp::ServiceConnect => {
-            let name_ptr = core::ptr::with_exposed_provenance_mut::<u8>(args[0]);
-            let name_len = args[1];
-            syscall::service::connect(thread, name_ptr, name_len).map_err(isize::from)
+            let args = args::ServiceConnect::decode(&registers);
+            match syscall::service::fetch_name(thread, args.name.cast_mut(), args.name_len) {
+                Some(name) => syscall::service::connect(name).await.map_err(isize::from),
+                None => Err(isize::from(::syscall::service::ConnectionError::BadName)),
+            }
+        }
+        SyscallOp::ServiceConnectWait => {
+            let args = args::ServiceConnectWait::decode(&registers);
+            match syscall::service::fetch_name(thread, args.name.cast_mut(), args.name_len) {
+                Some(name) => syscall::service::connect_wait(name)
+                    .await
+                    .map_err(isize::from),
+                None => Err(isize::from(::syscall::service::ConnectionError::BadName)),
+            }
+        }
+        SyscallOp::ServiceDisconnect => {
+            let args = args::ServiceDisconnect::decode(&registers);
+            syscall::service::disconnect(args.handle)
+                .map_err(isize::from)
+        }
+        SyscallOp::ServiceAccept => {
+            let args = args::ServiceAccept::decode(&registers);
+            syscall::service::accept(args.task)
+                .await
+                .map_err(isize::from)
         }
         SyscallOp::IpcSend => {
-            let message_ptr =
-                core::ptr::with_exposed_provenance::<::syscall::ipc::Message>(args[0]);
-            let reply_ptr =
-                core::ptr::with_exposed_provenance_mut::<::syscall::ipc::Reply>(args[1]);
-            let message_ptr = Pointer::new(thread, message_ptr.cast_mut())
-                .ok_or(isize::from(::syscall::ipc::SendError::BadMessage));
-            let reply_ptr = Pointer::new(thread, reply_ptr)
-                .ok_or(isize::from(::syscall::ipc::SendError::BadMessage));
+            let args = args::IpcSend::decode(&registers);
+            let message_ptr = Pointer::new(thread, args.message.cast_mut(), Access::Read);
+            let reply_ptr = Pointer::new(thread, args.reply, Access::Write);
 
-            if let (Ok(msg_ptr), Ok(rpl_ptr)) = (message_ptr, reply_ptr) {
+            if let (Some(msg_ptr), Some(rpl_ptr)) = (message_ptr, reply_ptr) {
                 syscall::ipc::send(msg_ptr, rpl_ptr)
                     .await
                     .map_err(isize::from)
@@ -78,37 +235,272 @@ pub async fn handle_syscall(thread: &mut arch::thread::Thread) -> Resume {
                 Err(isize::from(::syscall::ipc::SendError::BadMessage))
             }
         }
+        SyscallOp::IpcSendOneway => {
+            let args = args::IpcSendOneway::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.message.cast_mut(), Access::Read) {
+                syscall::ipc::send_oneway(ptr).await.map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::ipc::SendError::BadMessage))
+            }
+        }
         SyscallOp::IpcReceive => {
-            let message_ptr =
-                core::ptr::with_exposed_provenance_mut::<::syscall::ipc::Message>(args[0]);
-            let message_ptr = Pointer::new(thread, message_ptr)
-                .ok_or(isize::from(::syscall::ipc::ReceiveError::BadBuffer));
-            if let Ok(ptr) = message_ptr {
+            let args = args::IpcReceive::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.message, Access::Write) {
                 syscall::ipc::receive(ptr).await.map_err(isize::from)
             } else {
                 Err(isize::from(::syscall::ipc::ReceiveError::BadBuffer))
             }
         }
+        SyscallOp::IpcForward => {
+            let args = args::IpcForward::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.message.cast_mut(), Access::Read) {
+                syscall::ipc::forward(args.to, ptr)
+                    .await
+                    .map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::ipc::ForwardError::BadMessage))
+            }
+        }
+        SyscallOp::IpcTryReceive => {
+            let args = args::IpcTryReceive::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.message, Access::Write) {
+                syscall::ipc::try_receive(&ptr).map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::ipc::ReceiveError::BadBuffer))
+            }
+        }
         SyscallOp::IpcReply => {
-            let to = args[0];
-            let reply_ptr = core::ptr::with_exposed_provenance::<::syscall::ipc::Reply>(args[1]);
-            let reply_ptr = Pointer::new(thread, reply_ptr.cast_mut())
-                .ok_or(isize::from(::syscall::ipc::ReplyError::BadMessage));
-            if let Ok(ptr) = reply_ptr {
-                syscall::ipc::reply(to, ptr).map_err(isize::from)
+            let args = args::IpcReply::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.reply.cast_mut(), Access::Read) {
+                syscall::ipc::reply(args.to, ptr).map_err(isize::from)
             } else {
                 Err(isize::from(::syscall::ipc::ReplyError::BadMessage))
             }
         }
+        SyscallOp::ServiceStatistics => {
+            let args = args::ServiceStatistics::decode(&registers);
+            let name = syscall::service::fetch_name(thread, args.name.cast_mut(), args.name_len);
+            let buffer = Pointer::array(thread, args.buffer, args.capacity, Access::Write);
+            match (name, buffer) {
+                (Some(name), Some(buffer)) => {
+                    syscall::service::statistics(name, buffer, args.capacity)
+                        .await
+                        .map_err(isize::from)
+                }
+                (None, _) => Err(isize::from(::syscall::service::StatisticsError::BadName)),
+                (_, None) => Err(isize::from(::syscall::service::StatisticsError::BadBuffer)),
+            }
+        }
+        SyscallOp::ServiceList => {
+            let args = args::ServiceList::decode(&registers);
+            if let Some(ptr) = Pointer::array(thread, args.buffer, args.capacity, Access::Write) {
+                syscall::service::list(ptr, args.capacity)
+                    .await
+                    .map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::service::ListError::BadBuffer))
+            }
+        }
+        SyscallOp::SyscallTable => {
+            let args = args::SyscallTable::decode(&registers);
+            syscall::table::syscalls(thread, args.buffer, args.capacity).map_err(isize::from)
+        }
+        SyscallOp::MemProtect => {
+            let args = args::MemProtect::decode(&registers);
+            syscall::memory::protect(thread, args.address, args.size, args.protection)
+                .map_err(isize::from)
+        }
+        SyscallOp::MemMap => {
+            let args = args::MemMap::decode(&registers);
+            syscall::memory::map(thread, args.address, args.size, args.protection)
+                .await
+                .map_err(isize::from)
+        }
+        SyscallOp::MemPressureSubscribe => {
+            let args = args::MemPressureSubscribe::decode(&registers);
+            syscall::memory::subscribe_pressure(args.operation).map_err(isize::from)
+        }
+        SyscallOp::MemPressureUnsubscribe => {
+            syscall::memory::unsubscribe_pressure().map_err(isize::from)
+        }
+        SyscallOp::ConsoleWrite => {
+            let args = args::ConsoleWrite::decode(&registers);
+            syscall::console::write(thread, args.buffer, args.len).map_err(isize::from)
+        }
+        SyscallOp::ConsoleRead => {
+            let args = args::ConsoleRead::decode(&registers);
+            syscall::console::read(thread, args.buffer, args.capacity).map_err(isize::from)
+        }
+        SyscallOp::ConsoleReadWait => {
+            let args = args::ConsoleReadWait::decode(&registers);
+            if let Some(ptr) = Pointer::array(thread, args.buffer, args.capacity, Access::Write) {
+                syscall::console::read_wait(ptr, args.capacity)
+                    .await
+                    .map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::console::ReadError::BadBuffer))
+            }
+        }
+        SyscallOp::IrqRegister => {
+            let args = args::IrqRegister::decode(&registers);
+            syscall::irq::register(args.irq).map_err(isize::from)
+        }
+        SyscallOp::IrqWait => {
+            let args = args::IrqWait::decode(&registers);
+            syscall::irq::wait(args.irq).await.map_err(isize::from)
+        }
+        SyscallOp::IrqMask => {
+            let args = args::IrqMask::decode(&registers);
+            syscall::irq::mask(args.irq).map_err(isize::from)
+        }
+        SyscallOp::IrqUnmask => {
+            let args = args::IrqUnmask::decode(&registers);
+            syscall::irq::unmask(args.irq).map_err(isize::from)
+        }
+        SyscallOp::IrqSetAffinity => {
+            let args = args::IrqSetAffinity::decode(&registers);
+            syscall::irq::set_affinity(args.irq, args.cpu).map_err(isize::from)
+        }
+        SyscallOp::DeviceQuery => {
+            let args = args::DeviceQuery::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.info, Access::Write) {
+                syscall::device::query(args.device, ptr).map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::device::QueryError::BadBuffer))
+            }
+        }
+        SyscallOp::DeviceTreeQuery => {
+            let args = args::DeviceTreeQuery::decode(&registers);
+            syscall::device::tree_query(
+                thread,
+                args.path.cast_mut(),
+                args.path_len,
+                args.property.cast_mut(),
+                args.property_len,
+                args.buffer,
+                args.capacity,
+            )
+            .map_err(isize::from)
+        }
+        SyscallOp::MemMapDevice => {
+            let args = args::MemMapDevice::decode(&registers);
+            syscall::device::map(thread, args.device, args.address).map_err(isize::from)
+        }
+        SyscallOp::MemAllocDma => {
+            let args = args::MemAllocDma::decode(&registers);
+            syscall::memory::alloc_dma(thread, args.address, args.size, args.physical)
+                .map_err(isize::from)
+        }
+        SyscallOp::SyncCreate => {
+            let args = args::SyncCreate::decode(&registers);
+            syscall::sync::create(args.kind, args.value).map_err(isize::from)
+        }
+        SyscallOp::SyncSignal => {
+            let args = args::SyncSignal::decode(&registers);
+            syscall::sync::signal(args.object, args.value).map_err(isize::from)
+        }
+        SyscallOp::SyncWait => {
+            let args = args::SyncWait::decode(&registers);
+            syscall::sync::wait(args.object).await.map_err(isize::from)
+        }
+        SyscallOp::SyncClear => {
+            let args = args::SyncClear::decode(&registers);
+            syscall::sync::clear(args.object).map_err(isize::from)
+        }
+        SyscallOp::SyncDestroy => {
+            let args = args::SyncDestroy::decode(&registers);
+            syscall::sync::destroy(args.object).map_err(isize::from)
+        }
+        SyscallOp::FutexWait => {
+            let args = args::FutexWait::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.address.cast_mut(), Access::Read) {
+                syscall::futex::wait(ptr, args.expected)
+                    .await
+                    .map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::futex::WaitError::BadAddress))
+            }
+        }
+        SyscallOp::FutexWake => {
+            let args = args::FutexWake::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.address.cast_mut(), Access::Read) {
+                syscall::futex::wake(&ptr, args.count).map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::futex::WakeError::BadAddress))
+            }
+        }
+        SyscallOp::ThreadCreate => {
+            let args = args::ThreadCreate::decode(&registers);
+            syscall::thread::create(thread, args.entry, args.stack, args.argument)
+                .map_err(isize::from)
+        }
+        SyscallOp::ThreadExit => {
+            let args = args::ThreadExit::decode(&registers);
+            Ok(SyscallReturnValue {
+                resume: Resume::ExitThread(args.code),
+                value: 0,
+            })
+        }
+        SyscallOp::ThreadJoin => {
+            let args = args::ThreadJoin::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.code, Access::Write) {
+                syscall::thread::join(args.thread, ptr)
+                    .await
+                    .map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::thread::JoinError::BadBuffer))
+            }
+        }
+        SyscallOp::KernelStatistics => {
+            let args = args::KernelStatistics::decode(&registers);
+            if let Some(ptr) = Pointer::new(thread, args.statistics, Access::Write) {
+                syscall::stats::kernel(thread, ptr, args.syscalls, args.capacity)
+                    .map_err(isize::from)
+            } else {
+                Err(isize::from(::syscall::stats::StatisticsError::BadBuffer))
+            }
+        }
+        SyscallOp::LogControl => {
+            let args = args::LogControl::decode(&registers);
+            syscall::log::control(thread, args.module.cast_mut(), args.module_len, args.level)
+                .map_err(isize::from)
+        }
+        SyscallOp::LogRead => {
+            let args = args::LogRead::decode(&registers);
+            syscall::log::read(thread, args.sequence, args.buffer, args.capacity)
+                .map_err(isize::from)
+        }
+        SyscallOp::GetRandom => {
+            let args = args::GetRandom::decode(&registers);
+            syscall::random::fill(thread, args.buffer, args.len).map_err(isize::from)
+        }
+        SyscallOp::TimerCreate => {
+            let args = args::TimerCreate::decode(&registers);
+            syscall::timer::create(args.interval, args.delivery, args.target, args.value)
+                .map_err(isize::from)
+        }
+        SyscallOp::TimerDestroy => {
+            let args = args::TimerDestroy::decode(&registers);
+            syscall::timer::destroy(args.timer).map_err(isize::from)
+        }
+        SyscallOp::SystemPower => {
+            let args = args::SystemPower::decode(&registers);
+            syscall::power::power(args.action)
+                .await
+                .map_err(isize::from)
+        }
+        SyscallOp::TraceRead => {
+            let args = args::TraceRead::decode(&registers);
+            syscall::trace::read(thread, args.buffer, args.capacity).map_err(isize::from)
+        }
         SyscallOp::DebugWrite => {
+            let args = args::DebugWrite::decode(&registers);
             let self_id = future::executor::current_task_id().unwrap();
-            let str_ptr = core::ptr::with_exposed_provenance_mut::<u8>(args[0]);
-            let str_len = args[1];
-            let user_str = user::string::String::new(thread, str_ptr, str_len)
+            let user_str = user::string::String::new(thread, args.string.cast_mut(), args.len)
                 .ok_or(::syscall::debug::WriteError::BadName);
             if let Ok(str) = user_str {
                 if let Ok(s) = str.fetch() {
-                    log::debug!("[task {}] {}", self_id, s);
+                    ::log::debug!("[task {}] {}", self_id, s);
                     Ok(SyscallReturnValue {
                         resume: Resume::Continue,
                         value: s.len(),
@@ -121,7 +513,7 @@ pub async fn handle_syscall(thread: &mut arch::thread::Thread) -> Resume {
             }
         }
         SyscallOp::Unknown => {
-            log::warn!("Unknown syscall ID: {}", id);
+            ::log::warn!("Unknown syscall ID: {}", id);
             Ok(SyscallReturnValue {
                 resume: Resume::Continue,
                 value: usize::MAX,
@@ -129,16 +521,17 @@ pub async fn handle_syscall(thread: &mut arch::thread::Thread) -> Resume {
         }
     };
 
-    match result {
-        Ok(ret) => {
-            log::trace!("Syscall completed successfully.");
-            arch::thread::set_syscall_return(thread, ret.value as isize);
-            ret.resume
-        }
-        Err(e) => {
-            log::trace!("Syscall failed with error code: {}", e);
-            arch::thread::set_syscall_return(thread, -e);
-            Resume::Continue
-        }
+    let resume = result.map_or(Resume::Continue, |ret| ret.resume);
+    let ret = SyscallResult::from(result.map(|ret| ret.value));
+    match ret.decode() {
+        Ok(_) => ::log::trace!("Syscall completed successfully."),
+        Err(e) => ::log::trace!("Syscall failed with error code: {}", e),
     }
+    future::task::with_current_local_set(|set| {
+        set.syscall
+            .store(future::task::NO_SYSCALL, Ordering::Relaxed);
+    });
+    crate::trace::syscall_exit(id, ret.raw(), start);
+    arch::thread::set_syscall_return(thread, ret.raw().cast_signed());
+    resume
 }
@@ -0,0 +1,62 @@
+use crate::{arch, config, future, ipc, time::Instant, user::syscall::SyscallReturnValue};
+use ::syscall::{
+    ipc::Payload,
+    power::{Action, GRACE_PERIOD_MS, NOTICE_OPERATION, Notice, PowerError},
+};
+use alloc::vec::Vec;
+use core::time::Duration;
+
+/// Powers off or reboots the system. Every registered service is first sent a
+/// notice of the action, and the system goes down once all of them have
+/// unregistered or terminated, or once the grace period has elapsed. The
+/// executor is then drained of the background tasks of the kernel (see
+/// [`future::executor::shutdown`]). This function only returns if the action
+/// is invalid.
+///
+/// # Errors
+/// Returns [`PowerError::InvalidAction`] if the action is unknown.
+pub async fn power(action: usize) -> Result<SyscallReturnValue, PowerError> {
+    let action = Action::from_raw(action).ok_or(PowerError::InvalidAction)?;
+    log::info!("System going down ({:?})", action);
+
+    let notice = Notice {
+        action: action as u64,
+    };
+    let notified: Vec<_> = ipc::service::list()
+        .await
+        .into_iter()
+        .filter(|(name, registration)| {
+            let delivered =
+                ipc::message::notify(registration.task, NOTICE_OPERATION, notice.as_payload());
+            if !delivered {
+                log::warn!("Service {} could not be notified", name);
+            }
+            delivered
+        })
+        .map(|(_, registration)| registration)
+        .collect();
+
+    // Let the services handle the notice. They are not waited for if they
+    // take longer than the grace period, since a stuck service must never
+    // prevent the system from going down.
+    let deadline = Instant::now() + Duration::from_millis(GRACE_PERIOD_MS);
+    while !deadline.has_passed() && any_active(&notified).await {
+        future::yield_once().await;
+    }
+
+    future::executor::shutdown(Instant::now() + config::SHUTDOWN_DRAIN_TIMEOUT).await;
+    match action {
+        Action::PowerOff => arch::shutdown(),
+        Action::Reboot => arch::reboot(),
+    }
+}
+
+/// Returns whether any of the given registrations is still active.
+async fn any_active(registrations: &[ipc::service::Registration]) -> bool {
+    for &registration in registrations {
+        if ipc::service::is_active(registration).await {
+            return true;
+        }
+    }
+    false
+}
@@ -0,0 +1,29 @@
+use crate::{
+    arch::{thread::Thread, trap::Resume},
+    random,
+    user::{ptr::Access, slice::UserSlice, syscall::SyscallReturnValue},
+};
+use ::syscall::random::{FillError, MAX_FILL_SIZE};
+use alloc::vec;
+
+/// Fills the given user buffer with random bytes drawn from the entropy pool
+/// of the kernel, and returns the number of bytes written.
+///
+/// # Errors
+/// Returns [`FillError::BadBuffer`] if the buffer is larger than
+/// [`MAX_FILL_SIZE`] or cannot be written.
+pub fn fill(thread: &Thread, buffer: *mut u8, len: usize) -> Result<SyscallReturnValue, FillError> {
+    if len > MAX_FILL_SIZE {
+        return Err(FillError::BadBuffer);
+    }
+
+    let slice = UserSlice::new(thread, buffer, len, Access::Write).ok_or(FillError::BadBuffer)?;
+    let mut bytes = vec![0; len];
+    random::fill(&mut bytes);
+    slice.write(&bytes).map_err(|_| FillError::BadBuffer)?;
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: len,
+    })
+}
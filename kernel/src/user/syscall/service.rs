@@ -1,8 +1,19 @@
 use crate::{
     arch::{thread::Thread, trap::Resume},
     future, ipc,
-    user::{self, syscall::SyscallReturnValue},
+    user::{self, ptr::Pointer, syscall::SyscallReturnValue},
 };
+use alloc::{string::String, vec::Vec};
+
+impl From<ipc::service::ServiceUnregisterError> for ::syscall::service::UnregisterError {
+    fn from(value: ipc::service::ServiceUnregisterError) -> Self {
+        match value {
+            ipc::service::ServiceUnregisterError::NotRegistered => {
+                ::syscall::service::UnregisterError::NotRegistered
+            }
+        }
+    }
+}
 
 impl From<ipc::service::ServiceRegisterError> for ::syscall::service::RegisterError {
     fn from(value: ipc::service::ServiceRegisterError) -> Self {
@@ -17,7 +28,38 @@ impl From<ipc::service::ServiceRegisterError> for ::syscall::service::RegisterEr
     }
 }
 
-/// Registers a new service with the given name pointer and length.
+impl From<ipc::service::ServiceConnectError> for ::syscall::service::ConnectionError {
+    fn from(value: ipc::service::ServiceConnectError) -> Self {
+        match value {
+            ipc::service::ServiceConnectError::NotFound => {
+                ::syscall::service::ConnectionError::ServiceNotFound
+            }
+            ipc::service::ServiceConnectError::AccessDenied => {
+                ::syscall::service::ConnectionError::AccessDenied
+            }
+        }
+    }
+}
+
+impl From<ipc::service::ServiceAcceptError> for ::syscall::service::AcceptError {
+    fn from(value: ipc::service::ServiceAcceptError) -> Self {
+        match value {
+            ipc::service::ServiceAcceptError::NotRegistered => {
+                ::syscall::service::AcceptError::NotRegistered
+            }
+            ipc::service::ServiceAcceptError::NotRestricted => {
+                ::syscall::service::AcceptError::NotRestricted
+            }
+            ipc::service::ServiceAcceptError::TooManyTasks => {
+                ::syscall::service::AcceptError::TooManyTasks
+            }
+        }
+    }
+}
+
+/// Registers a new service with the given name, fetched from user memory with
+/// [`fetch_name`]. The given flags allow the service to customize how the
+/// kernel handles it.
 ///
 /// # Errors
 /// This function returns `Ok(Resume::Continue)` if the service was registered
@@ -30,63 +72,256 @@ impl From<ipc::service::ServiceRegisterError> for ::syscall::service::RegisterEr
 /// - The executor does not have a current task when required (this should
 ///   never happen since service registration must be done within a task
 ///   context).
-pub fn register(
-    thread: &Thread,
-    name_ptr: *mut u8,
-    name_len: usize,
+pub async fn register(
+    name: String,
+    flags: ::syscall::service::RegisterFlags,
 ) -> Result<SyscallReturnValue, ::syscall::service::RegisterError> {
-    let name = user::string::String::new(thread, name_ptr, name_len)
-        .ok_or(::syscall::service::RegisterError::BadName)?
-        .fetch()
-        .map_err(|_| ::syscall::service::RegisterError::BadName)?;
+    if name.len() > ::syscall::service::MAX_NAME_LEN {
+        return Err(::syscall::service::RegisterError::BadName);
+    }
     let id = future::executor::current_task_id().unwrap();
 
-    ipc::service::register(name, id)?;
+    let collect_statistics = !flags.contains(::syscall::service::RegisterFlags::NO_STATISTICS);
+    let restricted = flags.contains(::syscall::service::RegisterFlags::RESTRICTED);
+    ipc::service::register(name, id, collect_statistics, restricted).await?;
     Ok(SyscallReturnValue {
         resume: Resume::Continue,
         value: 0,
     })
 }
 
-/// Unregisters the current service.
+/// Unregisters the service provided by the current task. Existing
+/// connections to the service become unusable, and tasks waiting to deliver
+/// a message to the service are woken up with an error. Messages already
+/// delivered to the service must still be replied to.
 ///
 /// # Errors
-/// This function always returns an error indicating that the service
-/// unregistration feature is not yet implemented. This is because the proper
-/// approach to handle service unregistration is unclear at this time. For
-/// example, we need to consider what happens to existing connections to
-/// the service, and how to handle pending requests. Therefore, this function
-/// is a placeholder for future implementation.
-pub fn unregister() -> Result<SyscallReturnValue, ::syscall::service::UnregisterError> {
-    log::warn!("Service unregistration is not yet implemented");
-    Err(::syscall::service::UnregisterError::NotImplemented)
+/// This function returns [`UnregisterError::NotRegistered`] if the current
+/// task does not provide any service.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub async fn unregister() -> Result<SyscallReturnValue, ::syscall::service::UnregisterError> {
+    let id = future::executor::current_task_id().unwrap();
+    ipc::service::unregister(id).await?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
 }
 
-/// Connects to a service by its name.
+/// Connects to a service by its name, fetched from user memory with
+/// [`fetch_name`]. If the service is restricted, the current task must have
+/// been accepted by the service beforehand.
 ///
 /// # Errors
-/// This function returns `Ok(Resume::ReturnValue(service_id))` if the service
+/// This function returns `Ok(Resume::ReturnValue(handle))` if the service
 /// was found and connected successfully. If there was an error during connection,
 /// it returns an appropriate [`ServiceConnectError`] describing the failure.
 ///
-/// The `service_id` can be used for subsequent IPC operations with the
-/// connected service. Since this is not really a connection in the traditional
-/// sense, but rather a lookup of the service ID, no actual connection state
-/// is maintained, and thus no disconnection is necessary.
-pub fn connect(
-    thread: &Thread,
-    name_ptr: *mut u8,
-    name_len: usize,
+/// The returned handle refers to a connection stored in the handle table of
+/// the current task, and must be used for subsequent IPC operations with the
+/// connected service. The handle is only meaningful for the current task.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub async fn connect(
+    name: String,
+) -> Result<SyscallReturnValue, ::syscall::service::ConnectionError> {
+    let registration = ipc::service::connect(&name, future::group::current()).await?;
+    open(registration)
+}
+
+/// Connects to a service by its name like [`connect`], but waits until a
+/// service with this name is registered if there is none yet, so that clients
+/// started before the service do not have to poll for it. The name is fetched
+/// from user memory with [`fetch_name`] before waiting.
+///
+/// # Errors
+/// Returns [`::syscall::service::ConnectionError::AccessDenied`] if the
+/// service is restricted and did not accept the current task.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub async fn connect_wait(
+    name: String,
 ) -> Result<SyscallReturnValue, ::syscall::service::ConnectionError> {
-    let name = user::string::String::new(thread, name_ptr, name_len)
-        .ok_or(::syscall::service::ConnectionError::BadName)?
+    let registration = ipc::service::connect_wait(&name, future::group::current()).await?;
+    open(registration)
+}
+
+/// Fetches the name of a service from user memory. This is done before calling
+/// the handlers of the service syscalls, which may wait for the service
+/// registry and therefore cannot hold raw user pointers. Returns `None` if the
+/// name is not valid UTF-8 or is not readable by the current task, in which
+/// case the syscall fails with its `BadName` error.
+#[must_use]
+pub fn fetch_name(thread: &Thread, name_ptr: *mut u8, name_len: usize) -> Option<String> {
+    user::string::String::new(thread, name_ptr, name_len)?
         .fetch()
-        .map_err(|_| ::syscall::service::ConnectionError::BadName)?;
-    let service_id =
-        ipc::service::lookup(&name).ok_or(::syscall::service::ConnectionError::ServiceNotFound)?;
+        .ok()
+}
+
+/// Stores a connection to the given registration in the handle table of the
+/// current task, and returns its handle as the return value of the syscall.
+///
+/// # Errors
+/// Returns [`::syscall::service::ConnectionError::TooManyConnections`] if the
+/// handle table of the current task is full.
+fn open(
+    registration: ipc::service::Registration,
+) -> Result<SyscallReturnValue, ::syscall::service::ConnectionError> {
+    let handle = future::task::with_current_local_set(|local_set| {
+        local_set
+            .handles
+            .lock()
+            .insert(ipc::handle::Connection::new(registration))
+    })
+    .ok_or(::syscall::service::ConnectionError::TooManyConnections)?;
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: usize::from(handle),
+    })
+}
+
+/// Closes the connection referred to by the given handle, removing it from
+/// the handle table of the current task. The handle may be reused by a later
+/// connection. Messages already sent through the connection are unaffected.
+///
+/// # Errors
+/// Returns [`::syscall::service::DisconnectError::InvalidHandle`] if the
+/// handle does not refer to a connection of the current task.
+pub fn disconnect(handle: usize) -> Result<SyscallReturnValue, ::syscall::service::DisconnectError> {
+    let handle = ipc::handle::Handle::from(handle);
+    future::task::with_current_local_set(|local_set| local_set.handles.lock().remove(handle))
+        .ok_or(::syscall::service::DisconnectError::InvalidHandle)?;
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Allows the given task to connect to the restricted service provided by the
+/// current task. Connections are only checked when they are made, so this
+/// must be done before the task connects to the service.
+///
+/// # Errors
+/// Returns [`::syscall::service::AcceptError::TaskNotFound`] if the task does
+/// not exist, or another [`::syscall::service::AcceptError`] if the current
+/// task does not provide a restricted service or if the service cannot accept
+/// more tasks.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub async fn accept(task: usize) -> Result<SyscallReturnValue, ::syscall::service::AcceptError> {
+    let task = future::task::Identifier::from_user(task)
+        .filter(|&task| future::group::exists(task))
+        .ok_or(::syscall::service::AcceptError::TaskNotFound)?;
+
+    let id = future::executor::current_task_id().unwrap();
+    ipc::service::accept(id, task).await?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Retrieves the per-kind statistics of the service with the given name, and
+/// writes them into the user buffer, which holds `capacity` entries. At most
+/// `capacity` entries are written, sorted by message kind.
+///
+/// # Errors
+/// This function returns `Ok(Resume::ReturnValue(count))` with the number of
+/// entries written if the statistics were retrieved successfully. Otherwise,
+/// it returns an appropriate [`StatisticsError`] describing the failure.
+#[allow(clippy::cast_possible_truncation)]
+pub async fn statistics(
+    name: String,
+    buffer: Pointer<'_, ::syscall::service::KindStatistics>,
+    capacity: usize,
+) -> Result<SyscallReturnValue, ::syscall::service::StatisticsError> {
+    let registration = ipc::service::lookup(&name)
+        .await
+        .ok_or(::syscall::service::StatisticsError::ServiceNotFound)?;
+
+    let statistics = ipc::stats::collect(registration.task)
+        .into_iter()
+        .take(capacity)
+        .map(|(kind, entry)| ::syscall::service::KindStatistics {
+            kind,
+            count: entry.count,
+            total_latency: entry.total_latency.as_nanos() as u64,
+        })
+        .collect::<Vec<_>>();
+
+    // SAFETY: The buffer was verified to be fully in user space and large
+    // enough to hold `capacity` entries when creating the pointer.
+    unsafe {
+        user::op::copy_to(
+            buffer.thread(),
+            statistics.as_ptr(),
+            buffer.inner(),
+            statistics.len(),
+        )
+        .map_err(|_| ::syscall::service::StatisticsError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: statistics.len(),
+    })
+}
+
+/// Enumerates the registered services and writes their information into the
+/// user buffer, which holds `capacity` entries. At most `capacity` entries are
+/// written, sorted by name.
+///
+/// # Errors
+/// This function returns `Ok(Resume::ReturnValue(count))` with the number of
+/// entries written if the services were enumerated successfully. Otherwise,
+/// it returns an appropriate [`ListError`] describing the failure.
+pub async fn list(
+    buffer: Pointer<'_, ::syscall::service::ServiceInfo>,
+    capacity: usize,
+) -> Result<SyscallReturnValue, ::syscall::service::ListError> {
+    let services = ipc::service::list()
+        .await
+        .into_iter()
+        .take(capacity)
+        .map(|(name, registration)| {
+            // Service names are checked against the maximum length during
+            // registration, so the name always fits in the buffer.
+            let mut info = ::syscall::service::ServiceInfo {
+                task: usize::from(registration.task),
+                registration: registration.serial(),
+                name_len: name.len(),
+                name: [0; ::syscall::service::MAX_NAME_LEN],
+            };
+            info.name[..name.len()].copy_from_slice(name.as_bytes());
+            info
+        })
+        .collect::<Vec<_>>();
+
+    // SAFETY: The buffer was verified to be fully in user space and large
+    // enough to hold `capacity` entries when creating the pointer.
+    unsafe {
+        user::op::copy_to(
+            buffer.thread(),
+            services.as_ptr(),
+            buffer.inner(),
+            services.len(),
+        )
+        .map_err(|_| ::syscall::service::ListError::BadBuffer)?;
+    }
 
     Ok(SyscallReturnValue {
         resume: Resume::Continue,
-        value: usize::from(service_id),
+        value: services.len(),
     })
 }
@@ -0,0 +1,104 @@
+use crate::{
+    arch::{thread::Thread, trap::Resume},
+    config, future, mm,
+    user::{
+        self,
+        object::Object,
+        ptr::{Access, Pointer},
+        syscall::SyscallReturnValue,
+    },
+};
+use alloc::vec::Vec;
+
+/// Retrieves the system-wide statistics and writes them into the user buffer.
+///
+/// # Errors
+/// This function only fails if the statistics could not be written to the
+/// user buffer.
+pub fn system(
+    statistics_ptr: Pointer<'_, syscall::stats::SystemStatistics>,
+) -> Result<SyscallReturnValue, syscall::stats::StatisticsError> {
+    let statistics = syscall::stats::SystemStatistics {
+        minor_faults: mm::fault::minor_faults(),
+        major_faults: mm::fault::major_faults(),
+        heap_size: mm::heap::size() as u64,
+        heap_peak: mm::heap::peak() as u64,
+    };
+
+    // SAFETY: This is safe because we have verified that the pointer is valid
+    // when creating the `Pointer<SystemStatistics>` in the syscall handler.
+    unsafe {
+        Object::write(&statistics_ptr, &statistics)
+            .map_err(|_| syscall::stats::StatisticsError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Retrieves the activity counters of the kernel and writes them into the
+/// first user buffer, and writes the number of invocations of each syscall
+/// into the second user buffer, in the order of the syscall table. At most
+/// `capacity` syscall entries are written. Returns the number of syscall
+/// entries written.
+///
+/// # Errors
+/// This function only fails if the statistics could not be written to the
+/// user buffers.
+///
+/// # Panics
+/// Panics if the executor is not initialized, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn kernel(
+    thread: &Thread,
+    statistics_ptr: Pointer<'_, syscall::stats::KernelStatistics>,
+    syscalls_ptr: *mut syscall::stats::SyscallCount,
+    capacity: usize,
+) -> Result<SyscallReturnValue, syscall::stats::StatisticsError> {
+    let syscalls = Pointer::array(thread, syscalls_ptr, capacity, Access::Write)
+        .ok_or(syscall::stats::StatisticsError::BadBuffer)?;
+
+    let cpus = config::MAX_CPUS.min(syscall::stats::MAX_CPUS);
+    let total = mm::phys::total_memory_pages() as u64;
+    let allocated = mm::phys::allocated_memory_pages() as u64;
+    let statistics = syscall::stats::KernelStatistics {
+        syscalls: crate::stats::syscalls(),
+        context_switches: crate::stats::context_switches(),
+        ipc_messages: crate::stats::ipc_messages(),
+        frames_total: total,
+        frames_allocated: allocated,
+        frames_free: total.saturating_sub(allocated),
+        tasks: future::executor::task_count() as u64,
+        pending_wakeups: future::executor::pending_wakeups() as u64,
+        cpus: cpus as u64,
+        ready: core::array::from_fn(|cpu| {
+            if cpu < cpus {
+                future::executor::ready_tasks(cpu) as u64
+            } else {
+                0
+            }
+        }),
+        kernel_tasks: future::task::Kind::Kernel.count() as u64,
+    };
+    let counts = crate::stats::syscall_counts()
+        .take(capacity)
+        .map(|(op, count)| syscall::stats::SyscallCount { op, count })
+        .collect::<Vec<_>>();
+
+    // SAFETY: This is safe because we have verified that the pointers are
+    // valid when creating them, and that the syscall buffer is large enough
+    // to hold `capacity` entries.
+    unsafe {
+        Object::write(&statistics_ptr, &statistics)
+            .map_err(|_| syscall::stats::StatisticsError::BadBuffer)?;
+        user::op::copy_to(thread, counts.as_ptr(), syscalls.inner(), counts.len())
+            .map_err(|_| syscall::stats::StatisticsError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: counts.len(),
+    })
+}
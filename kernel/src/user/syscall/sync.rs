@@ -0,0 +1,79 @@
+use crate::{arch::trap::Resume, future, user, user::syscall::SyscallReturnValue};
+use ::syscall::sync::{ClearError, CreateError, DestroyError, Kind, SignalError, WaitError};
+
+/// Creates a synchronization object of the given kind with the given initial
+/// value, owned by the current task. Returns the identifier of the object.
+///
+/// # Errors
+/// Returns a [`CreateError`] if the kind or the value is invalid, or if the
+/// current task owns too many objects.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn create(kind: usize, value: usize) -> Result<SyscallReturnValue, CreateError> {
+    let kind = Kind::from_raw(kind).ok_or(CreateError::InvalidKind)?;
+    let id = future::group::current();
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: user::sync::create(kind, value, id)?,
+    })
+}
+
+/// Signals the given object with the given value, waking up the tasks waiting
+/// on it.
+///
+/// # Errors
+/// Returns a [`SignalError`] if the object does not exist, or if its value
+/// would overflow.
+pub fn signal(object: usize, value: usize) -> Result<SyscallReturnValue, SignalError> {
+    user::sync::signal(object, value)?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Waits until the given object is signaled. Returns the value of the object
+/// before the wait consumed it.
+///
+/// # Errors
+/// Returns [`WaitError::NotFound`] if the object does not exist, or if it is
+/// destroyed while waiting.
+pub async fn wait(object: usize) -> Result<SyscallReturnValue, WaitError> {
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: user::sync::wait(object).await?,
+    })
+}
+
+/// Resets the value of the given object to zero.
+///
+/// # Errors
+/// Returns [`ClearError::NotFound`] if the object does not exist.
+pub fn clear(object: usize) -> Result<SyscallReturnValue, ClearError> {
+    user::sync::clear(object)?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Destroys the given object, which must have been created by the current
+/// task.
+///
+/// # Errors
+/// Returns a [`DestroyError`] if the object does not exist, or if it was
+/// created by another task.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn destroy(object: usize) -> Result<SyscallReturnValue, DestroyError> {
+    let id = future::group::current();
+    user::sync::destroy(object, id)?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
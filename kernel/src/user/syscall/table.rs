@@ -0,0 +1,43 @@
+use crate::{
+    arch::{thread::Thread, trap::Resume},
+    user::{
+        self,
+        ptr::{Access, Pointer},
+        syscall::SyscallReturnValue,
+    },
+};
+
+/// Writes the machine-readable description of all the syscalls supported by
+/// the kernel into the user buffer. At most `capacity` entries are written,
+/// sorted by syscall number.
+///
+/// # Errors
+/// This function returns `Ok(Resume::ReturnValue(count))` with the number of
+/// entries written if the table was retrieved successfully. Otherwise, it
+/// returns an appropriate [`TableError`] describing the failure.
+pub fn syscalls(
+    thread: &Thread,
+    buffer_ptr: *mut ::syscall::table::SyscallDescriptor,
+    capacity: usize,
+) -> Result<SyscallReturnValue, ::syscall::table::TableError> {
+    let buffer = Pointer::array(thread, buffer_ptr, capacity, Access::Write)
+        .ok_or(::syscall::table::TableError::BadBuffer)?;
+    let count = capacity.min(::syscall::table::TABLE.len());
+
+    // SAFETY: The buffer was verified to be fully in user space and large
+    // enough to hold `capacity` entries when creating the pointer.
+    unsafe {
+        user::op::copy_to(
+            thread,
+            ::syscall::table::TABLE.as_ptr(),
+            buffer.inner(),
+            count,
+        )
+        .map_err(|_| ::syscall::table::TableError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: count,
+    })
+}
@@ -1 +1,632 @@
+use crate::{
+    arch::{self, thread::Thread, trap::Resume},
+    future, mm,
+    user::{
+        self,
+        elf::LoadError,
+        object::Object,
+        ptr::{Access, Pointer},
+        slice::UserSlice,
+        syscall::SyscallReturnValue,
+    },
+};
+use ::syscall::capability::Capabilities;
+use alloc::sync::Arc;
+use core::sync::atomic::Ordering;
 
+impl From<arch::trap::FaultKind> for syscall::task::FaultKind {
+    fn from(kind: arch::trap::FaultKind) -> Self {
+        match kind {
+            arch::trap::FaultKind::Unknown => syscall::task::FaultKind::Unknown,
+            arch::trap::FaultKind::PageFault => syscall::task::FaultKind::PageFault,
+            arch::trap::FaultKind::AccessFault => syscall::task::FaultKind::AccessFault,
+            arch::trap::FaultKind::IllegalInstruction => {
+                syscall::task::FaultKind::IllegalInstruction
+            }
+            arch::trap::FaultKind::Misaligned => syscall::task::FaultKind::Misaligned,
+        }
+    }
+}
+
+impl From<user::debug::Error> for syscall::task::DebugError {
+    fn from(error: user::debug::Error) -> Self {
+        match error {
+            user::debug::Error::NotFound => syscall::task::DebugError::TaskNotFound,
+            user::debug::Error::NotStopped => syscall::task::DebugError::NotStopped,
+        }
+    }
+}
+
+impl From<future::user::Exit> for syscall::task::Termination {
+    fn from(exit: future::user::Exit) -> Self {
+        match exit {
+            future::user::Exit::Terminate(code) => syscall::task::Termination::Exited(code),
+            future::user::Exit::Killed(by) => syscall::task::Termination::Killed(usize::from(by)),
+            future::user::Exit::OutOfMemory => syscall::task::Termination::OutOfMemory,
+            future::user::Exit::Fault { kind, address } => syscall::task::Termination::Faulted {
+                kind: kind.into(),
+                address,
+            },
+        }
+    }
+}
+
+/// Spawns a new task from the ELF image in the user buffer, giving it the
+/// block of arguments and environment variables in the second user buffer.
+/// The new task inherits the capabilities of the current task that are in the
+/// given set, except [`Capabilities::POWER`], so a task can never grant more
+/// rights than it holds but can spawn a task with fewer rights. Returns the
+/// identifier of the new task.
+///
+/// # Errors
+/// If the syscall fails, an appropriate [`SpawnError`] is returned describing
+/// the failure reason.
+///
+/// # Panics
+/// Panics if the kernel runs out of memory while copying the buffers.
+pub fn spawn(
+    thread: &Thread,
+    image: *const u8,
+    image_len: usize,
+    env: *const u8,
+    env_len: usize,
+    capabilities: Capabilities,
+) -> Result<SyscallReturnValue, syscall::task::SpawnError> {
+    if image_len > syscall::task::MAX_IMAGE_SIZE {
+        return Err(syscall::task::SpawnError::BadImage);
+    }
+    if env_len > syscall::env::MAX_BLOCK_SIZE {
+        return Err(syscall::task::SpawnError::BadEnvironment);
+    }
+
+    let image = UserSlice::new(thread, image.cast_mut(), image_len, Access::Read)
+        .ok_or(syscall::task::SpawnError::BadImage)?
+        .read()
+        .map_err(|_| syscall::task::SpawnError::BadImage)?;
+    let env = UserSlice::new(thread, env.cast_mut(), env_len, Access::Read)
+        .ok_or(syscall::task::SpawnError::BadEnvironment)?
+        .read()
+        .map_err(|_| syscall::task::SpawnError::BadEnvironment)?;
+    let env = syscall::env::Block::new(&env).ok_or(syscall::task::SpawnError::BadEnvironment)?;
+
+    // Take a slot in the children of the task before loading the image, so
+    // that a task that reached its limit cannot make the kernel waste time
+    // and memory loading images.
+    let limits = future::task::with_current_local_set(|set| set.limits.lock().spawn_child())
+        .ok_or(syscall::task::SpawnError::LimitExceeded)?;
+    let child = user::elf::load(&image, &env).map_err(|error| match error {
+        LoadError::OutOfMemory => syscall::task::SpawnError::OutOfMemory,
+        _ => syscall::task::SpawnError::InvalidImage,
+    })?;
+
+    // The capability to power off the system is never inherited, so that
+    // only init holds it.
+    let held = future::task::with_current_local_set(|set| *set.capabilities.lock());
+    let capabilities = (held & capabilities).difference(Capabilities::POWER);
+    let id = future::executor::spawn_with_capabilities(child, capabilities, limits)
+        .ok_or(syscall::task::SpawnError::TooManyTasks)?;
+
+    // A traced task passes the tracing on to its children, so that tracing a
+    // service also traces the helpers it spawns.
+    if crate::trace::current_traced() {
+        crate::trace::set_traced(id, true);
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: usize::from(id),
+    })
+}
+
+/// Waits until the task with the given identifier terminates, and writes how
+/// it terminated into the user buffer.
+///
+/// # Errors
+/// If the syscall fails, an appropriate [`WaitError`] is returned describing
+/// the failure reason.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub async fn wait(
+    id: usize,
+    info_ptr: Pointer<'_, syscall::task::TerminationInfo>,
+) -> Result<SyscallReturnValue, syscall::task::WaitError> {
+    let id =
+        future::task::Identifier::from_user(id).ok_or(syscall::task::WaitError::TaskNotFound)?;
+    if future::group::current() == id {
+        return Err(syscall::task::WaitError::SelfWait);
+    }
+
+    let termination = future::termination::wait(id)
+        .await
+        .ok_or(syscall::task::WaitError::TaskNotFound)?;
+    let info = syscall::task::TerminationInfo {
+        unclean: usize::from(termination.unclean),
+        ..syscall::task::TerminationInfo::from(syscall::task::Termination::from(termination.exit))
+    };
+
+    // SAFETY: This is safe because we have verified that the pointer is valid
+    // when creating the `Pointer<TerminationInfo>` in the syscall handler.
+    unsafe {
+        Object::write(&info_ptr, &info).map_err(|_| syscall::task::WaitError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Retrieves the resource usage of the task with the given identifier, and
+/// writes it into the user buffer.
+///
+/// # Errors
+/// If the syscall fails, an appropriate [`UsageError`] is returned describing
+/// the failure reason.
+pub fn usage(
+    id: usize,
+    usage_ptr: Pointer<'_, syscall::task::Usage>,
+) -> Result<SyscallReturnValue, syscall::task::UsageError> {
+    let id =
+        future::task::Identifier::from_user(id).ok_or(syscall::task::UsageError::TaskNotFound)?;
+    let usage = mm::fault::with_task_faults(id, |faults| {
+        let mut usage = syscall::task::Usage {
+            minor_faults: faults.minor,
+            major_faults: faults.major,
+            recent_faults_len: faults.recent.len(),
+            recent_faults: [0; syscall::task::RECENT_FAULTS],
+        };
+        for (dst, src) in usage
+            .recent_faults
+            .iter_mut()
+            .zip(faults.recent.oldest_ordered())
+        {
+            *dst = *src;
+        }
+        usage
+    })
+    .ok_or(syscall::task::UsageError::TaskNotFound)?;
+
+    // SAFETY: This is safe because we have verified that the pointer is valid
+    // when creating the `Pointer<Usage>` in the syscall handler.
+    unsafe {
+        Object::write(&usage_ptr, &usage).map_err(|_| syscall::task::UsageError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Kills the task with the given identifier, or the task of the thread with
+/// the given identifier. The task stops as soon as possible, even if it is
+/// waiting in a syscall, and its resources are released as if it had exited.
+/// Its termination reports that it was killed by the current task.
+///
+/// Besides the capability required by the syscall, the current task must hold
+/// all the capabilities of the killed task, so that a task can never kill a
+/// more privileged task. Well-known system tasks cannot be killed.
+///
+/// # Errors
+/// Returns [`syscall::task::KillError::TaskNotFound`] if the task does not
+/// exist or is already terminating, or another [`syscall::task::KillError`]
+/// if the current task is not allowed to kill it.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub async fn kill(id: usize) -> Result<SyscallReturnValue, syscall::task::KillError> {
+    let group = future::task::Identifier::from_user(id)
+        .and_then(future::group::of)
+        .ok_or(syscall::task::KillError::TaskNotFound)?;
+    let current = future::group::current();
+    if group == current {
+        return Err(syscall::task::KillError::SelfKill);
+    }
+    if group.is_reserved() {
+        return Err(syscall::task::KillError::Protected);
+    }
+
+    let capabilities = with_task_local_set(group, |set| *set.capabilities.lock())
+        .ok_or(syscall::task::KillError::TaskNotFound)?;
+    let allowed = future::task::with_current_local_set(|set| *set.capabilities.lock());
+    if !allowed.contains(capabilities) {
+        return Err(syscall::task::KillError::NotAllowed);
+    }
+
+    if !future::group::kill(group, future::user::Exit::Killed(current)).await {
+        return Err(syscall::task::KillError::TaskNotFound);
+    }
+    log::info!("Task {} killed by task {}", group, current);
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Changes a resource limit of the task with the given identifier, or of the
+/// current task if the identifier is [`syscall::task::CURRENT_TASK`], and
+/// returns the previous value of the limit. The new value only applies to the
+/// future allocations of the task: lowering a limit below the current usage
+/// of the resource does not release anything.
+///
+/// Besides the capability required by the syscall, the current task must hold
+/// all the capabilities of the task whose limit is changed, unless it changes
+/// its own limits.
+///
+/// # Errors
+/// Returns [`syscall::task::SetLimitError::InvalidLimit`] if the limit is
+/// unknown or if the value is not allowed for this limit,
+/// [`syscall::task::SetLimitError::TaskNotFound`] if the task does not exist,
+/// or [`syscall::task::SetLimitError::NotAllowed`] if the current task is not
+/// allowed to change the limits of the task.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub fn set_limit(
+    id: usize,
+    limit: usize,
+    value: usize,
+) -> Result<SyscallReturnValue, syscall::task::SetLimitError> {
+    let current = future::group::current();
+    let group = if id == syscall::task::CURRENT_TASK {
+        current
+    } else {
+        future::task::Identifier::from_user(id)
+            .and_then(future::group::of)
+            .ok_or(syscall::task::SetLimitError::TaskNotFound)?
+    };
+
+    let (capabilities, limits) = with_task_local_set(group, |set| {
+        (*set.capabilities.lock(), Arc::clone(&set.limits))
+    })
+    .ok_or(syscall::task::SetLimitError::TaskNotFound)?;
+    let allowed = future::task::with_current_local_set(|set| *set.capabilities.lock());
+    if group != current && !allowed.contains(capabilities) {
+        return Err(syscall::task::SetLimitError::NotAllowed);
+    }
+
+    let previous = limits
+        .lock()
+        .set(syscall::task::Limit::from(limit), value)
+        .ok_or(syscall::task::SetLimitError::InvalidLimit)?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: previous,
+    })
+}
+
+/// Marks the task with the given identifier, or the current task if the
+/// identifier is [`syscall::task::CURRENT_TASK`], as essential or not, and
+/// returns 1 if it was essential before and 0 otherwise. Essential tasks are
+/// never killed by the out-of-memory handler (see [`crate::mm::oom`]).
+///
+/// Besides the capability required by the syscall, the current task must hold
+/// all the capabilities of the task, unless it marks itself.
+///
+/// # Errors
+/// Returns [`syscall::task::SetEssentialError::TaskNotFound`] if the task does
+/// not exist, or [`syscall::task::SetEssentialError::NotAllowed`] if the
+/// current task is not allowed to mark it.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub fn set_essential(
+    id: usize,
+    essential: usize,
+) -> Result<SyscallReturnValue, syscall::task::SetEssentialError> {
+    let current = future::group::current();
+    let group = if id == syscall::task::CURRENT_TASK {
+        current
+    } else {
+        future::task::Identifier::from_user(id)
+            .and_then(future::group::of)
+            .ok_or(syscall::task::SetEssentialError::TaskNotFound)?
+    };
+
+    let (capabilities, flag) = with_task_local_set(group, |set| {
+        (*set.capabilities.lock(), Arc::clone(&set.essential))
+    })
+    .ok_or(syscall::task::SetEssentialError::TaskNotFound)?;
+    let allowed = future::task::with_current_local_set(|set| *set.capabilities.lock());
+    if group != current && !allowed.contains(capabilities) {
+        return Err(syscall::task::SetEssentialError::NotAllowed);
+    }
+
+    let previous = flag.swap(essential != 0, Ordering::Relaxed);
+    log::info!(
+        "Task {} marked as {} by task {}",
+        group,
+        if essential != 0 {
+            "essential"
+        } else {
+            "non-essential"
+        },
+        current
+    );
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: usize::from(previous),
+    })
+}
+
+/// Enables or disables the detailed tracing of the syscalls invoked by the
+/// task with the given identifier, or by the current task if the identifier
+/// is [`syscall::task::CURRENT_TASK`], and returns whether they were traced
+/// before. The entry and exit of each syscall of a traced task are recorded
+/// in the trace buffers along with all its arguments and its duration, even
+/// if kernel tracing is disabled.
+///
+/// Besides the capability required by the syscall, the current task must hold
+/// all the capabilities of the task, unless it traces itself, since the
+/// arguments of its syscalls may reveal what these capabilities protect.
+///
+/// # Errors
+/// Returns [`syscall::task::TraceError::TaskNotFound`] if the task does not
+/// exist, or [`syscall::task::TraceError::NotAllowed`] if the current task is
+/// not allowed to trace it.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub fn trace(id: usize, enabled: usize) -> Result<SyscallReturnValue, syscall::task::TraceError> {
+    let current = future::group::current();
+    let group = if id == syscall::task::CURRENT_TASK {
+        current
+    } else {
+        future::task::Identifier::from_user(id)
+            .and_then(future::group::of)
+            .ok_or(syscall::task::TraceError::TaskNotFound)?
+    };
+
+    let capabilities = with_task_local_set(group, |set| *set.capabilities.lock())
+        .ok_or(syscall::task::TraceError::TaskNotFound)?;
+    let allowed = future::task::with_current_local_set(|set| *set.capabilities.lock());
+    if group != current && !allowed.contains(capabilities) {
+        return Err(syscall::task::TraceError::NotAllowed);
+    }
+
+    let previous = future::group::threads(group)
+        .and_then(|threads| threads.first().copied())
+        .and_then(|thread| crate::trace::set_traced(thread, enabled != 0))
+        .ok_or(syscall::task::TraceError::TaskNotFound)?;
+    log::info!(
+        "Syscall tracing of task {} {} by task {}",
+        group,
+        if enabled != 0 { "enabled" } else { "disabled" },
+        current
+    );
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: usize::from(previous),
+    })
+}
+
+/// Suspends the task with the given identifier, or the task of the thread with
+/// the given identifier, so that a debugger can inspect it. Each thread of the
+/// task stops the next time it would return to user mode, which may take a
+/// while for a thread waiting inside a syscall: the registers of a thread can
+/// only be accessed once it has stopped.
+///
+/// Besides the capability required by the syscall, the current task must hold
+/// all the capabilities of the task, since the debugger takes full control of
+/// it. A task cannot suspend itself.
+///
+/// # Errors
+/// Returns [`syscall::task::DebugError::TaskNotFound`] if the task does not
+/// exist, or [`syscall::task::DebugError::NotAllowed`] if the current task is
+/// not allowed to debug it.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub fn suspend(id: usize) -> Result<SyscallReturnValue, syscall::task::DebugError> {
+    let (_, group) = debuggee(id)?;
+    let threads = future::group::threads(group).ok_or(syscall::task::DebugError::TaskNotFound)?;
+    user::debug::suspend(&threads);
+    log::info!(
+        "Task {} suspended by task {}",
+        group,
+        future::group::current()
+    );
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Resumes all the threads of the task with the given identifier, or of the
+/// task of the thread with the given identifier. If `step` is not zero, only
+/// the given thread is resumed, for a single instruction after which it stops
+/// again. The same permissions as [`suspend`] are required.
+///
+/// # Errors
+/// Returns [`syscall::task::DebugError::TaskNotFound`] if the task does not
+/// exist, [`syscall::task::DebugError::NotAllowed`] if the current task is
+/// not allowed to debug it, [`syscall::task::DebugError::NotStopped`] if the
+/// thread to single-step is not stopped, or
+/// [`syscall::task::DebugError::StepUnsupported`] if threads cannot be
+/// single-stepped on this architecture.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub fn resume(id: usize, step: usize) -> Result<SyscallReturnValue, syscall::task::DebugError> {
+    let (thread, group) = debuggee(id)?;
+    if step != 0 {
+        if !arch::thread::SINGLE_STEP {
+            return Err(syscall::task::DebugError::StepUnsupported);
+        }
+        user::debug::step(thread)?;
+    } else {
+        let threads =
+            future::group::threads(group).ok_or(syscall::task::DebugError::TaskNotFound)?;
+        user::debug::resume(&threads);
+        log::info!(
+            "Task {} resumed by task {}",
+            group,
+            future::group::current()
+        );
+    }
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Writes the registers of the stopped thread with the given identifier into
+/// the user buffer. The same permissions as [`suspend`] are required.
+///
+/// # Errors
+/// Returns [`syscall::task::DebugError::TaskNotFound`] if the thread does not
+/// exist, [`syscall::task::DebugError::NotAllowed`] if the current task is
+/// not allowed to debug it, [`syscall::task::DebugError::NotStopped`] if the
+/// thread is not stopped, or [`syscall::task::DebugError::BadBuffer`] if the
+/// registers cannot be written.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub fn get_registers(
+    id: usize,
+    registers_ptr: Pointer<'_, syscall::task::Registers>,
+) -> Result<SyscallReturnValue, syscall::task::DebugError> {
+    let (thread, _) = debuggee(id)?;
+    let registers = user::debug::registers(thread)?;
+
+    // SAFETY: This is safe because we have verified that the pointer is valid
+    // when creating the `Pointer<Registers>` in the syscall handler.
+    unsafe {
+        Object::write(&registers_ptr, &registers)
+            .map_err(|_| syscall::task::DebugError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Replaces the registers of the stopped thread with the given identifier by
+/// the registers in the user buffer. They are loaded into the thread when it
+/// is resumed. The same permissions as [`suspend`] are required.
+///
+/// # Errors
+/// Returns [`syscall::task::DebugError::TaskNotFound`] if the thread does not
+/// exist, [`syscall::task::DebugError::NotAllowed`] if the current task is
+/// not allowed to debug it, [`syscall::task::DebugError::NotStopped`] if the
+/// thread is not stopped, or [`syscall::task::DebugError::BadBuffer`] if the
+/// registers cannot be read.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub fn set_registers(
+    id: usize,
+    registers_ptr: Pointer<'_, syscall::task::Registers>,
+) -> Result<SyscallReturnValue, syscall::task::DebugError> {
+    let (thread, _) = debuggee(id)?;
+
+    // SAFETY: This is safe because we have verified that the pointer is valid
+    // when creating the `Pointer<Registers>` in the syscall handler.
+    let registers =
+        unsafe { Object::read(&registers_ptr).map_err(|_| syscall::task::DebugError::BadBuffer)? };
+    user::debug::set_registers(thread, registers)?;
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Resolves the thread with the given identifier, and the task it belongs to,
+/// on behalf of a debugger. The current task must hold all the capabilities of
+/// the task, and cannot debug itself.
+fn debuggee(
+    id: usize,
+) -> Result<(future::task::Identifier, future::task::Identifier), syscall::task::DebugError> {
+    let thread =
+        future::task::Identifier::from_user(id).ok_or(syscall::task::DebugError::TaskNotFound)?;
+    let group = future::group::of(thread).ok_or(syscall::task::DebugError::TaskNotFound)?;
+    if group == future::group::current() {
+        return Err(syscall::task::DebugError::NotAllowed);
+    }
+
+    let capabilities = with_task_local_set(group, |set| *set.capabilities.lock())
+        .ok_or(syscall::task::DebugError::TaskNotFound)?;
+    let allowed = future::task::with_current_local_set(|set| *set.capabilities.lock());
+    if !allowed.contains(capabilities) {
+        return Err(syscall::task::DebugError::NotAllowed);
+    }
+    Ok((thread, group))
+}
+
+/// Executes a closure with access to the local data set of a thread of the
+/// given task, to access the state shared by all its threads, such as its
+/// capabilities or its limits. Returns `None` if the task does not have any
+/// thread alive.
+fn with_task_local_set<F, R>(group: future::task::Identifier, f: F) -> Option<R>
+where
+    F: FnOnce(&future::task::LocalDataSet) -> R,
+{
+    let thread = *future::group::threads(group)?.first()?;
+    future::task::try_with_local_set_from(thread, |set| set.map(f))
+}
+
+/// Changes the scheduling class of the task with the given identifier, or of
+/// the current task if the identifier is [`syscall::task::CURRENT_TASK`]. The
+/// permission to invoke this syscall is checked against the capabilities of
+/// the current task before the syscall is dispatched.
+///
+/// # Errors
+/// Returns [`syscall::task::SetPriorityError::InvalidPriority`] if the
+/// priority is unknown, or [`syscall::task::SetPriorityError::TaskNotFound`]
+/// if the task does not exist.
+///
+/// # Panics
+/// This function may panic if the current task ID cannot be retrieved. This
+/// should never happen since this function is called from a task context.
+pub fn set_priority(
+    id: usize,
+    priority: usize,
+) -> Result<SyscallReturnValue, syscall::task::SetPriorityError> {
+    let priority = syscall::task::Priority::from_raw(priority)
+        .ok_or(syscall::task::SetPriorityError::InvalidPriority)?;
+    let id = if id == syscall::task::CURRENT_TASK {
+        future::executor::current_task_id().unwrap()
+    } else {
+        future::task::Identifier::from_user(id)
+            .ok_or(syscall::task::SetPriorityError::TaskNotFound)?
+    };
+
+    if !future::executor::set_priority(id, priority) {
+        return Err(syscall::task::SetPriorityError::TaskNotFound);
+    }
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
+
+/// Removes the given capabilities from the current task, and returns the raw
+/// representation of the capabilities that it still holds. The capabilities
+/// are shared by all the threads of the task, so they all lose them at once.
+/// A task can never regain a capability it gave up, which allows a task to
+/// sandbox itself before running untrusted code. This syscall cannot fail.
+#[must_use]
+pub fn drop_capabilities(capabilities: Capabilities) -> SyscallReturnValue {
+    let remaining = future::task::with_current_local_set(|set| {
+        let mut held = set.capabilities.lock();
+        *held = held.difference(capabilities);
+        *held
+    });
+
+    SyscallReturnValue {
+        resume: Resume::Continue,
+        value: remaining.bits() as usize,
+    }
+}
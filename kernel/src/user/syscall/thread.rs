@@ -0,0 +1,88 @@
+use crate::{
+    arch::{
+        self,
+        target::addr::{Virtual, virt::User},
+        thread::Thread,
+        trap::Resume,
+    },
+    future,
+    user::{self, object::Object, ptr::Pointer, syscall::SyscallReturnValue},
+};
+use ::syscall::thread::{CreateError, JoinError};
+
+/// Creates a new thread in the current task, sharing the address space of the
+/// given thread. The new thread starts at the given entry point with the given
+/// stack pointer, and receives the given argument in its first argument
+/// register. Returns the identifier of the new thread.
+///
+/// # Errors
+/// Returns [`CreateError::BadAddress`] if the entry point or the stack pointer
+/// is not a user address, or [`CreateError::TooManyThreads`] if the task
+/// cannot have more threads.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn create(
+    thread: &Thread,
+    entry: usize,
+    stack: usize,
+    argument: usize,
+) -> Result<SyscallReturnValue, CreateError> {
+    Virtual::<User>::try_new(entry).ok_or(CreateError::BadAddress)?;
+    Virtual::<User>::try_new(stack).ok_or(CreateError::BadAddress)?;
+
+    let mut sibling = arch::thread::create_sibling(thread, entry, stack);
+    arch::thread::set_entry_argument(&mut sibling, 0, argument);
+
+    let current = future::executor::current_task_id().unwrap();
+    let id =
+        future::executor::spawn_sibling(sibling, current).ok_or(CreateError::TooManyThreads)?;
+
+    // The task may have been suspended by a debugger while this thread was
+    // running, in which case the new thread must not run either.
+    if user::debug::must_stop(current) {
+        user::debug::suspend(&[id]);
+    }
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: usize::from(id),
+    })
+}
+
+/// Waits until the given thread of the current task terminates, and writes
+/// its exit code into the user buffer.
+///
+/// # Errors
+/// Returns [`JoinError::SelfJoin`] if the thread is the current thread,
+/// [`JoinError::NotFound`] if it is not a thread of the current task or was
+/// already joined, or [`JoinError::BadBuffer`] if the exit code cannot be
+/// written.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub async fn join(
+    thread: usize,
+    code_ptr: Pointer<'_, i32>,
+) -> Result<SyscallReturnValue, JoinError> {
+    let thread = future::task::Identifier::from_user(thread).ok_or(JoinError::NotFound)?;
+    if future::executor::current_task_id().unwrap() == thread {
+        return Err(JoinError::SelfJoin);
+    }
+
+    let code = future::group::join(thread)
+        .await
+        .ok_or(JoinError::NotFound)?;
+
+    // SAFETY: This is safe because we have verified that the pointer is valid
+    // when creating the `Pointer<i32>` in the syscall handler.
+    unsafe {
+        Object::write(&code_ptr, &code).map_err(|_| JoinError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
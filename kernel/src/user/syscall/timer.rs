@@ -0,0 +1,81 @@
+use crate::{arch::trap::Resume, future, user, user::syscall::SyscallReturnValue};
+use ::syscall::{
+    capability::Capabilities,
+    power::NOTICE_OPERATION,
+    timer::{CreateError, Delivery, DestroyError},
+};
+use core::time::Duration;
+
+/// Creates a periodic timer owned by the current task, expiring every
+/// `interval` nanoseconds. Depending on the raw delivery, each expiration is
+/// sent to the task as an IPC message whose operation is `target`, or signals
+/// the synchronization object `target` with `value`. Returns the identifier
+/// of the timer.
+///
+/// # Errors
+/// Returns a [`CreateError`] if the interval or the delivery is invalid, if
+/// the task does not hold the capability needed by the delivery, if the object
+/// to signal does not exist, or if the task owns too many timers.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn create(
+    interval: usize,
+    delivery: usize,
+    target: usize,
+    value: usize,
+) -> Result<SyscallReturnValue, CreateError> {
+    let delivery = match Delivery::from_raw(delivery).ok_or(CreateError::InvalidDelivery)? {
+        Delivery::Message if target == NOTICE_OPERATION => {
+            return Err(CreateError::InvalidDelivery);
+        }
+        Delivery::Message => user::timer::Delivery::Message { operation: target },
+        Delivery::Signal => user::timer::Delivery::Signal {
+            object: target,
+            value,
+        },
+    };
+
+    // The timer acts on behalf of the task, so it must not deliver its
+    // expirations in a way that the task itself is not allowed to use.
+    let required = match delivery {
+        user::timer::Delivery::Message { .. } => Capabilities::IPC,
+        user::timer::Delivery::Signal { .. } => Capabilities::SYNC,
+    };
+    let capabilities = future::task::with_current_local_set(|set| *set.capabilities.lock());
+    if !capabilities.contains(required) {
+        return Err(CreateError::NotAllowed);
+    }
+    if let user::timer::Delivery::Signal { object, .. } = delivery
+        && !user::sync::exists(object)
+    {
+        return Err(CreateError::ObjectNotFound);
+    }
+
+    let interval = Duration::from_nanos(interval as u64);
+    let id = future::group::current();
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: user::timer::create(interval, delivery, id)?,
+    })
+}
+
+/// Destroys the given timer, which must have been created by the current
+/// task.
+///
+/// # Errors
+/// Returns a [`DestroyError`] if the timer does not exist, or if it was
+/// created by another task.
+///
+/// # Panics
+/// Panics if there is no current task, which should never happen since
+/// syscalls are always invoked by a task.
+pub fn destroy(timer: usize) -> Result<SyscallReturnValue, DestroyError> {
+    let id = future::group::current();
+    user::timer::destroy(timer, id)?;
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: 0,
+    })
+}
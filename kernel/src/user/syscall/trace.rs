@@ -0,0 +1,39 @@
+use crate::{
+    arch::{thread::Thread, trap::Resume},
+    trace,
+    user::{
+        self,
+        ptr::{Access, Pointer},
+        syscall::SyscallReturnValue,
+    },
+};
+
+/// Removes at most `capacity` events from the trace buffers and writes them
+/// into the user buffer. Returns the number of events written, which is zero
+/// if no event was recorded since the buffers were last drained.
+///
+/// # Errors
+/// Returns [`syscall::trace::ReadError::Disabled`] if tracing is disabled, or
+/// [`syscall::trace::ReadError::BadBuffer`] if the events cannot be written
+/// into the user buffer. In the latter case, the drained events are lost.
+pub fn read(
+    thread: &Thread,
+    buffer_ptr: *mut syscall::trace::Event,
+    capacity: usize,
+) -> Result<SyscallReturnValue, syscall::trace::ReadError> {
+    let buffer = Pointer::array(thread, buffer_ptr, capacity, Access::Write)
+        .ok_or(syscall::trace::ReadError::BadBuffer)?;
+    let events = trace::drain(capacity).ok_or(syscall::trace::ReadError::Disabled)?;
+
+    // SAFETY: The buffer was verified to be fully in user space and large
+    // enough to hold `capacity` entries when creating the pointer.
+    unsafe {
+        user::op::copy_to(thread, events.as_ptr(), buffer.inner(), events.len())
+            .map_err(|_| syscall::trace::ReadError::BadBuffer)?;
+    }
+
+    Ok(SyscallReturnValue {
+        resume: Resume::Continue,
+        value: events.len(),
+    })
+}
@@ -0,0 +1,168 @@
+use crate::{
+    config, future, ipc,
+    time::{self, Instant},
+    user,
+    utils::lock,
+};
+use ::syscall::{
+    ipc::Payload,
+    timer::{CreateError, DestroyError, Expiration},
+};
+use core::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use hashbrown::HashMap;
+use spin::Lazy;
+
+/// The timers of the system, indexed by identifier.
+static TIMERS: Lazy<lock::Mutex<HashMap<usize, Timer>>> =
+    Lazy::new(|| lock::Mutex::new(HashMap::new()));
+
+/// The identifier of the next timer. Identifiers are never reused, so that an
+/// expiration of a destroyed timer still in the wheel cannot be mistaken for
+/// an expiration of a timer created later.
+static NEXT_IDENTIFIER: AtomicUsize = AtomicUsize::new(1);
+
+/// How the expirations of a timer are delivered to its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delivery {
+    /// Each expiration is sent as an IPC message with the given operation.
+    Message { operation: usize },
+
+    /// Each expiration signals the given synchronization object with the
+    /// given value.
+    Signal { object: usize, value: usize },
+}
+
+/// A periodic timer.
+#[derive(Debug)]
+struct Timer {
+    /// The task that created the timer, and to which its expirations are
+    /// delivered.
+    owner: future::task::Identifier,
+
+    /// The interval between two expirations.
+    interval: Duration,
+
+    /// The next expiration of the timer, scheduled in the timer wheel.
+    deadline: Instant,
+
+    /// How the expirations are delivered.
+    delivery: Delivery,
+
+    /// The number of expirations that were not delivered yet, because the
+    /// mailbox of the owner was full.
+    pending: u64,
+}
+
+/// Creates a periodic timer owned by the given task, whose expirations are
+/// delivered as described by `delivery`, and returns its identifier. The first
+/// expiration happens one interval from now.
+///
+/// # Errors
+/// Returns [`CreateError::InvalidInterval`] if the interval is shorter than
+/// [`::syscall::timer::MIN_INTERVAL`], or [`CreateError::TooManyTimers`] if
+/// the task already owns [`config::TIMERS_PER_TASK`] timers.
+pub fn create(
+    interval: Duration,
+    delivery: Delivery,
+    owner: future::task::Identifier,
+) -> Result<usize, CreateError> {
+    if interval < Duration::from_nanos(::syscall::timer::MIN_INTERVAL as u64) {
+        return Err(CreateError::InvalidInterval);
+    }
+
+    let mut timers = TIMERS.lock();
+    let count = timers.values().filter(|timer| timer.owner == owner).count();
+    if count >= config::TIMERS_PER_TASK {
+        return Err(CreateError::TooManyTimers);
+    }
+
+    let id = NEXT_IDENTIFIER.fetch_add(1, Ordering::Relaxed);
+    let deadline = Instant::now() + interval;
+    timers.insert(
+        id,
+        Timer {
+            owner,
+            interval,
+            deadline,
+            delivery,
+            pending: 0,
+        },
+    );
+    time::wheel::schedule(deadline, id);
+    Ok(id)
+}
+
+/// Destroys the given timer, which must be owned by the given task. An
+/// expiration that was not delivered yet is lost.
+///
+/// # Errors
+/// Returns [`DestroyError::NotFound`] if the timer does not exist, or
+/// [`DestroyError::NotOwner`] if it is owned by another task.
+pub fn destroy(id: usize, task: future::task::Identifier) -> Result<(), DestroyError> {
+    let mut timers = TIMERS.lock();
+    let timer = timers.get(&id).ok_or(DestroyError::NotFound)?;
+    if timer.owner != task {
+        return Err(DestroyError::NotOwner);
+    }
+    if let Some(timer) = timers.remove(&id) {
+        time::wheel::cancel(timer.deadline, id);
+    }
+    Ok(())
+}
+
+/// Destroys all the timers owned by the given task. This must be called when
+/// the task terminates.
+pub fn release(task: future::task::Identifier) {
+    for (id, timer) in TIMERS.lock().extract_if(|_, timer| timer.owner == task) {
+        time::wheel::cancel(timer.deadline, id);
+        log::debug!("Timer {} released by task {}", id, task);
+    }
+}
+
+/// Delivers the expiration of the given timer scheduled at the given deadline,
+/// which passed at `now`, and schedules its next expiration. The periods that
+/// elapsed while the timer wheel was not polled are delivered at once, instead
+/// of as a burst of expirations. This does nothing if the timer was destroyed.
+pub fn expire(id: usize, deadline: Instant, now: Instant) {
+    let mut timers = TIMERS.lock();
+    let Some(timer) = timers.get_mut(&id) else {
+        return;
+    };
+    if timer.deadline != deadline {
+        return;
+    }
+
+    let interval = timer.interval.as_nanos();
+    let late = now.duration_since(deadline).as_nanos();
+    let periods = 1 + late / interval;
+    #[allow(clippy::cast_possible_truncation)]
+    let next = Duration::from_nanos((periods * interval) as u64);
+    timer.deadline = deadline + next;
+    time::wheel::schedule(timer.deadline, id);
+
+    #[allow(clippy::cast_possible_truncation)]
+    let count = timer.pending.saturating_add(periods as u64);
+    match timer.delivery {
+        Delivery::Message { operation } => {
+            let expiration = Expiration {
+                timer: id as u64,
+                count,
+            };
+            let delivered = ipc::message::notify(timer.owner, operation, expiration.as_payload());
+            timer.pending = if delivered { 0 } else { count };
+        }
+        Delivery::Signal { object, value } => {
+            if let Err(error) = user::sync::signal(object, value) {
+                log::debug!(
+                    "Timer {} failed to signal object {}: {:?}",
+                    id,
+                    object,
+                    error
+                );
+            }
+        }
+    }
+}
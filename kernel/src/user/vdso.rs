@@ -0,0 +1,121 @@
+use crate::{
+    arch::{
+        self,
+        target::addr::{Virtual, virt::Kernel, virt::User},
+        thread::Thread,
+    },
+    time::Instant,
+};
+use ::syscall::vdso::{SHARED_PAGE_ADDRESS, TlsTemplate};
+
+/// The page shared between the kernel and a user thread. The kernel keeps a
+/// kernel virtual address to the page to be able to update it at any time,
+/// while the thread can access it at the address of its slot in its task,
+/// starting from [`SHARED_PAGE_ADDRESS`] for the slot 0.
+///
+/// Since the thread can write anything into the page, all values read by the
+/// kernel from this page must be considered as untrusted hints and must never
+/// be used for anything else than diagnostics.
+///
+/// The frame backing the page is owned by the address space of the thread:
+/// it will be freed along with the other user frames when the page table of
+/// the thread is destroyed. Therefore, this structure must not outlive the
+/// thread it was mapped into.
+#[derive(Debug)]
+pub struct SharedPage {
+    page: Virtual<Kernel>,
+    address: Virtual<User>,
+}
+
+impl SharedPage {
+    /// Allocate a new shared page for the thread in the given slot of its
+    /// task, and map it into the address space of the thread at
+    /// [`SHARED_PAGE_ADDRESS`] plus the slot multiplied by the page size. The
+    /// page is mapped readable and writable for the user, and is initialized
+    /// with static informations that will never change during the lifetime of
+    /// the thread.
+    ///
+    /// The page of a slot is never unmapped while the task is alive: if it is
+    /// already mapped, either because the task is being loaded or because a
+    /// previous thread of the task used the same slot, the existing page is
+    /// reused and the stack extent published by its previous thread is reset.
+    ///
+    /// Returns `None` if the page could not be allocated or mapped.
+    #[must_use]
+    pub fn map(thread: &mut Thread, slot: usize) -> Option<Self> {
+        let address = Virtual::<User>::new(SHARED_PAGE_ADDRESS + slot * arch::mmu::PAGE_SIZE);
+        let mut space = thread.address_space();
+        if space.query(address).is_none() {
+            space
+                .map_range(address, arch::mmu::PAGE_SIZE, arch::mmu::Rights::RWU)
+                .ok()?;
+        }
+        let page = arch::mmu::translate_physical(space.query(address)?.frame)?;
+
+        let mmap_base = space.layout().mmap_base().as_usize() as u64;
+        let shared = Self { page, address };
+        // SAFETY: The page is mapped and is large enough to contain the
+        // shared page structure.
+        unsafe {
+            (&raw mut (*shared.as_mut_ptr()).tick_duration)
+                .write_volatile(arch::timer::internal_tick());
+            (&raw mut (*shared.as_mut_ptr()).mmap_base).write_volatile(mmap_base);
+            (&raw mut (*shared.as_mut_ptr()).stack_low).write_volatile(0);
+            (&raw mut (*shared.as_mut_ptr()).stack_high).write_volatile(0);
+        }
+        Some(shared)
+    }
+
+    /// Return the user virtual address where the page is mapped.
+    #[must_use]
+    pub const fn address(&self) -> Virtual<User> {
+        self.address
+    }
+
+    /// Update the deadline of the current quantum of the thread.
+    pub fn set_quantum_deadline(&self, deadline: Instant) {
+        // SAFETY: The page is mapped for the whole lifetime of this
+        // structure. We use a volatile write because the page may be
+        // concurrently read by user space.
+        unsafe {
+            (&raw mut (*self.as_mut_ptr()).quantum_deadline).write_volatile(deadline.as_nanos());
+        }
+    }
+
+    /// Publish the thread-local storage template of the executable of the
+    /// task, so that the runtime can set up the TLS block of each thread.
+    pub fn set_tls_template(&self, template: TlsTemplate) {
+        // SAFETY: The page is mapped for the whole lifetime of this
+        // structure. We use a volatile write because the page may be
+        // concurrently read by user space.
+        unsafe {
+            (&raw mut (*self.as_mut_ptr()).tls).write_volatile(template);
+        }
+    }
+
+    /// Return the bounds of the stack currently used by the thread, as
+    /// published by the thread itself. Returns `None` if the thread did not
+    /// publish any stack extent, meaning that it runs on the stack provided
+    /// by the kernel, or if the published extent is obviously invalid.
+    #[must_use]
+    pub fn stack_extent(&self) -> Option<core::ops::Range<usize>> {
+        // SAFETY: The page is mapped for the whole lifetime of this
+        // structure. We use a volatile read because the page may be
+        // concurrently written by user space.
+        let (low, high) = unsafe {
+            (
+                (&raw const (*self.as_mut_ptr()).stack_low).read_volatile(),
+                (&raw const (*self.as_mut_ptr()).stack_high).read_volatile(),
+            )
+        };
+
+        let low = usize::try_from(low).ok()?;
+        let high = usize::try_from(high).ok()?;
+        (low < high).then_some(low..high)
+    }
+
+    /// Return a raw pointer to the shared page structure.
+    fn as_mut_ptr(&self) -> *mut ::syscall::vdso::SharedPage {
+        self.page.as_mut_ptr()
+    }
+}
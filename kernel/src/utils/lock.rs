@@ -0,0 +1,347 @@
+//! The spinlocks of the kernel. They are thin wrappers around the spinlocks
+//! of the `spin` crate, which can check how they are used when the kernel is
+//! built with the `lock-debug` feature. Without this feature, they compile
+//! down to the spinlocks they wrap.
+//!
+//! With the `lock-debug` feature, each core keeps the list of the locks that
+//! it holds, along with the place where each one was acquired. The kernel then
+//! panics, printing the places where the locks involved were created and
+//! acquired, if:
+//! - a lock is acquired while already held by the same core, which would spin
+//!   forever;
+//! - two locks are acquired in the opposite order of a previous acquisition,
+//!   which could deadlock if both orders happen at the same time on two cores
+//!   or in a task and a trap handler. Locks are grouped by the place where they
+//!   are created, so that the order between locks of the same kind created for
+//!   each task is learned once for all of them;
+//! - a lock is still held when a task yields to the executor, which means that
+//!   it is held across an await point and will deadlock as soon as another
+//!   task tries to acquire it;
+//! - a lock is still held when interrupts are enabled (see
+//!   [`crate::arch::irq::enable`]), which will deadlock if a trap handler
+//!   tries to acquire it.
+use core::{
+    fmt::Debug,
+    ops::{Deref, DerefMut},
+};
+
+#[cfg(feature = "lock-debug")]
+use core::panic::Location;
+
+/// A spinlock protecting data of type `T`.
+pub struct Mutex<T: ?Sized> {
+    /// The place where the lock was created, which identifies the kind of
+    /// the lock when checking the order of acquisitions.
+    #[cfg(feature = "lock-debug")]
+    class: &'static Location<'static>,
+
+    inner: spin::Mutex<T>,
+}
+
+/// A guard giving access to the data of a [`Mutex`], which is unlocked when
+/// the guard is dropped.
+pub struct MutexGuard<'a, T: ?Sized> {
+    /// The address of the lock, which identifies it in the list of locks held
+    /// by the current core.
+    #[cfg(feature = "lock-debug")]
+    address: usize,
+
+    inner: spin::MutexGuard<'a, T>,
+}
+
+impl<T> Mutex<T> {
+    /// Creates a new unlocked spinlock protecting the given data.
+    #[must_use]
+    #[track_caller]
+    pub const fn new(data: T) -> Self {
+        Self {
+            #[cfg(feature = "lock-debug")]
+            class: Location::caller(),
+            inner: spin::Mutex::new(data),
+        }
+    }
+
+    /// Consumes the lock and returns the data it protects.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Locks the spinlock, spinning until it is available, and returns a
+    /// guard giving access to the data.
+    ///
+    /// # Panics
+    /// With the `lock-debug` feature, panics if the lock is already held by
+    /// the current core, or if acquiring it now inverts the order in which it
+    /// was acquired with a lock held by the current core.
+    #[track_caller]
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        #[cfg(feature = "lock-debug")]
+        debug::check(self.address(), self.class, Location::caller());
+        let inner = self.inner.lock();
+        #[cfg(feature = "lock-debug")]
+        debug::acquired(self.address(), self.class, Location::caller());
+        MutexGuard {
+            #[cfg(feature = "lock-debug")]
+            address: self.address(),
+            inner,
+        }
+    }
+
+    /// Tries to lock the spinlock without spinning. Returns `None` if it is
+    /// already locked. Since this cannot deadlock, the order in which this
+    /// lock is acquired is not checked.
+    #[track_caller]
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let inner = self.inner.try_lock()?;
+        #[cfg(feature = "lock-debug")]
+        debug::acquired(self.address(), self.class, Location::caller());
+        Some(MutexGuard {
+            #[cfg(feature = "lock-debug")]
+            address: self.address(),
+            inner,
+        })
+    }
+
+    /// Returns a mutable reference to the data. Since this borrows the lock
+    /// mutably, no locking is needed.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
+    /// Returns the address of the lock, which identifies it while it is held.
+    #[cfg(feature = "lock-debug")]
+    fn address(&self) -> usize {
+        core::ptr::from_ref(self).cast::<()>().addr()
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for Mutex<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+#[cfg(feature = "lock-debug")]
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        debug::released(self.address);
+    }
+}
+
+/// Panics if the current core holds a lock, printing where it was acquired
+/// and the given reason why no lock may be held at this point. This does
+/// nothing without the `lock-debug` feature.
+#[track_caller]
+pub fn assert_unlocked(reason: &str) {
+    #[cfg(feature = "lock-debug")]
+    debug::assert_unlocked(reason, Location::caller());
+    #[cfg(not(feature = "lock-debug"))]
+    let _ = reason;
+}
+
+#[cfg(feature = "lock-debug")]
+mod debug {
+    use crate::{config, utils::percpu::PerCpu};
+    use core::{
+        panic::Location,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    /// The locks held by the current core, in the order they were acquired.
+    static HELD: PerCpu<heapless::Vec<Held, { config::LOCK_DEBUG_DEPTH }>> =
+        PerCpu::new(heapless::Vec::new());
+
+    /// The orders in which two kinds of locks were acquired so far. This is
+    /// shared by all cores, and is a plain spinlock so that it is not checked
+    /// itself.
+    static ORDERS: spin::Mutex<heapless::Vec<Order, { config::LOCK_DEBUG_ORDERS }>> =
+        spin::Mutex::new(heapless::Vec::new());
+
+    /// Whether a misuse of a lock was reported. The checks stop after the
+    /// first one, so that the panic handler can take the locks it needs to
+    /// print the report.
+    static REPORTED: AtomicBool = AtomicBool::new(false);
+
+    /// Whether [`ORDERS`] was found full, so that this is only logged once.
+    static FULL: AtomicBool = AtomicBool::new(false);
+
+    type Site = &'static Location<'static>;
+
+    /// A lock held by the current core.
+    #[derive(Debug, Clone, Copy)]
+    struct Held {
+        /// The address of the lock.
+        address: usize,
+
+        /// The place where the lock was created.
+        class: Site,
+
+        /// The place where the lock was acquired.
+        site: Site,
+    }
+
+    /// A lock of kind `after` acquired while holding a lock of kind `before`,
+    /// along with the places where both locks were acquired the first time
+    /// this order was seen.
+    #[derive(Debug, Clone, Copy)]
+    struct Order {
+        before: Held,
+        after: Held,
+    }
+
+    /// Checks that the lock at the given address can be acquired by the
+    /// current core at the given site, and records the order between its kind
+    /// and the kinds of the locks already held. This is called before spinning
+    /// on the lock, so that a deadlock is reported instead of hanging.
+    pub fn check(address: usize, class: Site, site: Site) {
+        if REPORTED.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let lock = Held {
+            address,
+            class,
+            site,
+        };
+        // The report is printed after leaving the per-CPU data, since the
+        // panic handler and the logger take locks themselves.
+        let mut full = false;
+        let report =
+            HELD.with(|held| {
+                if let Some(&other) = held.iter().find(|other| other.address == address) {
+                    return Some(Report::Recursive { lock, held: other });
+                }
+
+                let mut orders = ORDERS.lock();
+                for &other in held.iter().filter(|other| other.class != class) {
+                    if let Some(&order) = orders.iter().find(|order| {
+                        order.before.class == class && order.after.class == other.class
+                    }) {
+                        return Some(Report::Inversion {
+                            lock,
+                            held: other,
+                            order,
+                        });
+                    }
+                    if !orders.iter().any(|order| {
+                        order.before.class == other.class && order.after.class == class
+                    }) {
+                        let order = Order {
+                            before: other,
+                            after: lock,
+                        };
+                        full |= orders.push(order).is_err();
+                    }
+                }
+                None
+            });
+
+        if let Some(report) = report {
+            REPORTED.store(true, Ordering::Relaxed);
+            report.panic();
+        }
+        if full && !FULL.swap(true, Ordering::Relaxed) {
+            log::warn!("Too many lock orders, the new ones will not be checked");
+        }
+    }
+
+    /// Records that the current core acquired the lock at the given address.
+    /// If the current core already holds [`config::LOCK_DEBUG_DEPTH`] locks,
+    /// the lock is not recorded and is therefore not checked.
+    pub fn acquired(address: usize, class: Site, site: Site) {
+        let lock = Held {
+            address,
+            class,
+            site,
+        };
+        _ = HELD.with(|held| held.push(lock));
+    }
+
+    /// Records that the current core released the lock at the given address.
+    pub fn released(address: usize) {
+        HELD.with(|held| {
+            if let Some(index) = held.iter().rposition(|lock| lock.address == address) {
+                held.remove(index);
+            }
+        });
+    }
+
+    /// Panics if the current core holds a lock.
+    pub fn assert_unlocked(reason: &str, site: Site) {
+        if REPORTED.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Some(lock) = HELD.with(|held| held.first().copied()) {
+            REPORTED.store(true, Ordering::Relaxed);
+            panic!(
+                "Lock created at {} and acquired at {} is held {} at {}",
+                lock.class, lock.site, reason, site
+            );
+        }
+    }
+
+    /// A misuse of a lock, detected before acquiring it.
+    enum Report {
+        /// The lock is already held by the current core.
+        Recursive { lock: Held, held: Held },
+
+        /// The lock is acquired while holding a lock that was previously
+        /// acquired while holding a lock of the same kind.
+        Inversion {
+            lock: Held,
+            held: Held,
+            order: Order,
+        },
+    }
+
+    impl Report {
+        fn panic(self) -> ! {
+            match self {
+                Report::Recursive { lock, held } => panic!(
+                    "Lock created at {} acquired at {} while already held since {}",
+                    lock.class, lock.site, held.site
+                ),
+                Report::Inversion { lock, held, order } => panic!(
+                    "Lock order inversion: lock created at {} acquired at {} while holding \
+                     the lock created at {} acquired at {}, but they were previously \
+                     acquired in the opposite order at {} and then {}",
+                    lock.class,
+                    lock.site,
+                    held.class,
+                    held.site,
+                    order.before.site,
+                    order.after.site
+                ),
+            }
+        }
+    }
+}
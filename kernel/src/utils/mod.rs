@@ -1 +1,3 @@
 pub mod align;
+pub mod lock;
+pub mod percpu;
@@ -0,0 +1,53 @@
+use core::cell::{Cell, UnsafeCell};
+
+/// Data owned by each CPU core. Since the data of a core is only ever
+/// accessed by that core with interrupts disabled, it can be accessed without
+/// taking any lock, which makes it suitable for caches on hot paths.
+///
+/// Kiwi currently only runs on the boot core, so a single instance of the
+/// data is stored. When SMP support is added, this will store one instance
+/// per core and select the instance of the current core.
+pub struct PerCpu<T> {
+    inner: UnsafeCell<T>,
+    borrowed: Cell<bool>,
+}
+
+/// SAFETY: The data is only accessed through [`PerCpu::with`], which disables
+/// interrupts and gives exclusive access to the data of the current core.
+/// Since each core only accesses its own data, it is never accessed
+/// concurrently.
+unsafe impl<T: Send> Sync for PerCpu<T> {}
+
+impl<T> PerCpu<T> {
+    /// Create a new per-CPU variable with the given initial value.
+    #[must_use]
+    pub const fn new(value: T) -> Self {
+        Self {
+            inner: UnsafeCell::new(value),
+            borrowed: Cell::new(false),
+        }
+    }
+
+    /// Execute the given closure with exclusive access to the data of the
+    /// current core. Interrupts are disabled during the execution of the
+    /// closure.
+    ///
+    /// # Panics
+    /// Panics if the closure tries to access the same per-CPU variable
+    /// recursively.
+    pub fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        crate::arch::irq::without(|| {
+            assert!(!self.borrowed.replace(true), "Recursive per-CPU access");
+            // SAFETY: Interrupts are disabled, the data is only accessed by
+            // the current core and recursive accesses are rejected above, so
+            // no other reference to the data can exist while the closure is
+            // running.
+            let ret = f(unsafe { &mut *self.inner.get() });
+            self.borrowed.set(false);
+            ret
+        })
+    }
+}
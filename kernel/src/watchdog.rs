@@ -0,0 +1,111 @@
+use crate::{
+    arch, config, future, random,
+    time::{self, Instant},
+};
+use core::time::Duration;
+use kcore::watchdog::{Clock, Detector, Policy};
+
+/// The interval between two checks of the watchdog. A lockup is detected
+/// between [`config::WATCHDOG_TIMEOUT`] and this interval later.
+const CHECK_INTERVAL: Duration = config::WATCHDOG_TIMEOUT.checked_div(4).unwrap();
+
+/// The watchdog state of each core, indexed by the index of the core. It is
+/// only updated with atomic operations, since it is read from the timer
+/// interrupt, which may interrupt the kernel while it holds any lock.
+static CORES: [Detector; config::MAX_CPUS] = [const { Detector::new() }; config::MAX_CPUS];
+
+/// The clock used by the watchdog, counting the nanoseconds since boot.
+struct Monotonic;
+
+impl Clock for Monotonic {
+    fn now(&self) -> u64 {
+        Instant::now().as_nanos()
+    }
+}
+
+/// Returns the watchdog state of the current core.
+fn current() -> &'static Detector {
+    &CORES[arch::cpu::id()]
+}
+
+/// Starts the watchdog on the current core. The timer interrupt must be able
+/// to interrupt the kernel for the watchdog to notice that a task spins in the
+/// kernel, so this enables interrupts while the kernel runs. This does nothing
+/// if the watchdog is disabled.
+///
+/// # Safety
+/// The trap handler of the kernel must be installed, and the caller must be
+/// prepared to be interrupted at any point.
+pub unsafe fn setup() {
+    if !config::WATCHDOG {
+        return;
+    }
+
+    log::info!(
+        "Starting the watchdog (timeout: {:?})",
+        config::WATCHDOG_TIMEOUT
+    );
+    current().start(&Monotonic);
+    arm();
+
+    // SAFETY: The caller guarantees that the kernel can be interrupted.
+    unsafe {
+        arch::irq::enable();
+    }
+}
+
+/// Arms the timer for the next check of the watchdog or the next deadline of
+/// the timer wheel, whichever comes first, or disables it if the watchdog is
+/// disabled and no timer is pending. This is used instead of disabling the
+/// timer after a timer interrupt, so that the watchdog keeps running while the
+/// kernel runs and an idle core wakes up when a timer expires.
+pub fn arm() {
+    let next = time::wheel::until_next();
+    if config::WATCHDOG {
+        arch::timer::next_event(next.map_or(CHECK_INTERVAL, |next| next.min(CHECK_INTERVAL)));
+    } else if let Some(next) = next {
+        arch::timer::next_event(next);
+    } else {
+        arch::timer::shutdown();
+    }
+}
+
+/// Signals that the current core made progress. This is called by the
+/// executor each time it finishes polling a task and while it waits for a
+/// task to become ready.
+pub fn pet() {
+    current().pet();
+}
+
+/// Checks whether the current core made progress since the last check, and
+/// arms the timer for the next check. This is called from the timer interrupt
+/// and therefore never blocks.
+///
+/// When the core has not made progress for [`config::WATCHDOG_TIMEOUT`], the
+/// core is considered locked up. If [`config::WATCHDOG_TERMINATES_TASK`] is
+/// set, the running task is first marked as runaway so that it is terminated
+/// as soon as its poll returns, and the core is given another timeout to
+/// recover. Returns the duration without progress if the core is still locked
+/// up, in which case the caller must panic.
+#[must_use]
+pub fn check() -> Option<Duration> {
+    arm();
+    random::add_entropy(arch::timer::current_time_ticks());
+
+    let policy = if config::WATCHDOG_TERMINATES_TASK {
+        Policy::TerminateTask
+    } else {
+        Policy::Panic
+    };
+    current().check(&Monotonic, config::WATCHDOG_TIMEOUT, policy, || {
+        future::executor::peek_current_task_id().map(usize::from)
+    })
+}
+
+/// Returns whether the task with the given identifier was marked as runaway
+/// by the watchdog on the current core, and clears the mark. This is called
+/// by the executor after polling the task.
+#[must_use]
+pub fn take_runaway(id: future::task::Identifier) -> bool {
+    current().take_runaway(usize::from(id))
+}
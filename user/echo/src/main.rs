@@ -1,18 +1,29 @@
 #![no_std]
 #![no_main]
 
+extern crate alloc;
+
+use alloc::vec::Vec;
+use xstd::echo::{Echo, EchoServer};
+
 /// A simple echo service that replies to any received message with the same
-/// payload and status code. It registers itself under the name "echo" and enters
-/// a loop to handle all incoming messages.
+/// payload. It registers itself under the name "echo" and serves the
+/// [`Echo`] protocol forever.
 ///
 /// This service can be used for testing IPC mechanisms by sending messages
 /// to it and verifying that the replies match the sent messages.
 #[xstd::main]
 pub fn main() {
-    xstd::service::register("echo").unwrap();
-    loop {
-        let msg = xstd::ipc::receive().unwrap();
+    xstd::service::register(xstd::echo::SERVICE_NAME).unwrap();
+    xstd::rpc::serve(&mut EchoServer(Service));
+}
+
+/// The implementation of the echo service.
+struct Service;
+
+impl Echo for Service {
+    fn echo(&mut self, bytes: &[u8]) -> Vec<u8> {
         _ = xstd::debug::write("Echo service received a message, replying...");
-        _ = xstd::ipc::reply(msg.sender, msg.kind, &msg.payload[..msg.payload_len]);
+        bytes.to_vec()
     }
 }
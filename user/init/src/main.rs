@@ -1,41 +1,105 @@
 #![no_std]
 #![no_main]
 
-/// An initialization service that connects to the "echo" service, sends a
-/// message, and verifies the response. If the response matches the sent
-/// message, it exits with a success code; otherwise, it exits with an error
-/// code. This service demonstrates basic IPC communication and service
-/// interaction.
+extern crate alloc;
+
+use xstd::{echo::EchoClient, task::Capabilities};
+
+/// An initialization service that spawns the programs of the initial ramdisk,
+/// copies its files into the ramfs file server if it was spawned, then connects to the "echo" service, sends a message, and verifies the
+/// response. If the response matches the sent message, it exits with a success
+/// code; otherwise, it exits with an error code. This service demonstrates
+/// basic IPC communication and service interaction.
 #[xstd::main]
 pub fn main() {
-    let echo = connect_until_success("echo");
-    let reply = xstd::ipc::send(echo, 42, b"Hello, world!").unwrap();
-    let payload = &reply.payload[..reply.payload_len];
+    spawn_initrd_programs();
+    populate_ramfs();
+    let echo = EchoClient::new(
+        xstd::service::connect_wait(xstd::echo::SERVICE_NAME).expect("Failed to connect to echo"),
+    );
+    let reply = echo.echo(b"Hello, world!").unwrap();
 
-    if reply.status == 42 && payload == b"Hello, world!" {
-        _ = xstd::debug::write("Echo service responded correctly !");
+    if reply == b"Hello, world!" {
+        xstd::println!("Echo service responded correctly !");
         xstd::task::exit(0)
     } else {
-        _ = xstd::debug::write("Echo service responded incorrectly !");
-        _ = xstd::debug::write("Note: This is probably the kernel's fault :) ");
+        xstd::println!("Echo service responded incorrectly !");
+        xstd::println!("Note: This is probably the kernel's fault :) ");
         xstd::task::exit(-1)
     }
 }
 
-/// Connects to a service by its name, retrying until successful. This is
-/// useful for services that may not be immediately available, such as during
-/// system startup.
-pub fn connect_until_success(name: &str) -> usize {
-    loop {
-        match xstd::service::connect(name) {
-            Ok(handle) => {
-                _ = xstd::debug::write("Successfully connected to the service !");
-                return handle;
-            }
-            Err(_) => {
-                _ = xstd::debug::write("Failed to connect to the service, retrying...");
-                xstd::task::yield_now()
+/// The programs of the initial ramdisk that are part of the system and are
+/// trusted with all the capabilities of `init`, since they provide services
+/// or drive devices. They are also marked as essential, so that the kernel
+/// never kills them when it runs out of memory.
+const TRUSTED_PROGRAMS: [&str; 3] = ["echo", "ramfs", "virtio-blk"];
+
+/// The capabilities given to the other programs of the initial ramdisk: they
+/// can use the services of the system, the console, synchronization objects
+/// and threads, but cannot provide services, drive devices, spawn tasks or
+/// change their scheduling class.
+const UNTRUSTED_CAPABILITIES: Capabilities = Capabilities::SERVICE_CONNECT
+    .union(Capabilities::IPC)
+    .union(Capabilities::CONSOLE)
+    .union(Capabilities::SYNC)
+    .union(Capabilities::THREAD);
+
+/// Spawns every executable file of the initial ramdisk, except `init` itself,
+/// giving each program its path in the archive as its name. The programs of
+/// [`TRUSTED_PROGRAMS`] inherit all the capabilities of `init`, while the
+/// others are sandboxed with [`UNTRUSTED_CAPABILITIES`]. Programs that cannot
+/// be spawned are reported and skipped. This does nothing if the kernel did
+/// not give an initial ramdisk to `init`.
+pub fn spawn_initrd_programs() {
+    let Some(archive) = xstd::initrd::archive() else {
+        return;
+    };
+
+    for entry in archive.entries().filter(xstd::initrd::Entry::is_executable) {
+        if entry.name == "init" {
+            continue;
+        }
+        let capabilities = if TRUSTED_PROGRAMS.contains(&entry.name) {
+            Capabilities::ALL
+        } else {
+            UNTRUSTED_CAPABILITIES
+        };
+        match xstd::task::spawn_with_capabilities(entry.data, &[entry.name], &[], capabilities) {
+            Ok(id) => {
+                xstd::println!("Spawned {} as task {}", entry.name, id);
+                if TRUSTED_PROGRAMS.contains(&entry.name)
+                    && let Err(error) = xstd::task::set_essential(id, true)
+                {
+                    xstd::println!("Failed to mark {} as essential: {:?}", entry.name, error);
+                }
             }
+            Err(error) => xstd::println!("Failed to spawn {}: {:?}", entry.name, error),
+        }
+    }
+}
+
+/// Copies every regular file of the initial ramdisk into the ramfs file
+/// server, at its path in the archive prefixed with `/`. This does nothing if
+/// the ramdisk does not contain the `ramfs` program, since there would be no
+/// file server to wait for.
+pub fn populate_ramfs() {
+    let Some(archive) = xstd::initrd::archive() else {
+        return;
+    };
+    if archive
+        .find("ramfs")
+        .is_none_or(|entry| !entry.is_executable())
+    {
+        return;
+    }
+
+    _ = xstd::service::connect_wait(xstd::fs::SERVICE_NAME);
+    for entry in archive.entries().filter(xstd::initrd::Entry::is_file) {
+        let path = alloc::format!("/{}", entry.name);
+        let copied = xstd::fs::File::create(&path).and_then(|mut file| file.write_all(entry.data));
+        if let Err(error) = copied {
+            xstd::println!("Failed to copy {} to ramfs: {:?}", path, error);
         }
     }
 }
@@ -0,0 +1,317 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
+use xstd::{echo::EchoClient, memory::Protection, rpc};
+
+/// The name of the environment variable giving the name of the test case to
+/// run. It must match the one used by the kernel.
+const CASE_VAR: &str = "TEST_CASE";
+
+/// The size of the memory mapped by the memory test cases.
+const MAPPING_SIZE: usize = 4 * 4096;
+
+/// The names under which the `service-rename` test case registers its echo
+/// service, before and after renaming it.
+const OLD_NAME: &str = "echo-old";
+const NEW_NAME: &str = "echo-new";
+
+/// The number of times the `reconnect` test case connects to the `echo`
+/// service and disconnects from it, which is far more than the number of
+/// connections that a task can hold at the same time.
+const RECONNECTIONS: usize = 1024;
+
+/// The program spawned by the `spawn-exit` test case, which exits as soon as
+/// it starts. The `template` program must therefore be built before this one.
+#[cfg(target_arch = "riscv64")]
+static CHILD: &[u8] =
+    include_bytes!("../../template/target/riscv64gc-unknown-none-elf/release/template");
+#[cfg(target_arch = "aarch64")]
+static CHILD: &[u8] = include_bytes!("../../template/target/aarch64-unknown-none/release/template");
+
+/// A lower bound of the number of frames used by a task spawned by the
+/// `spawn-exit` test case: its root table, its stack, its code and the tables
+/// mapping them. Spawning more children than the free frames divided by this
+/// number would exhaust the memory if their frames were leaked.
+const CHILD_FRAMES: u64 = 4;
+
+/// The number of children spawned by the `spawn-exit` test case before it
+/// measures the baseline of allocated frames, so that the kernel heap and
+/// caches have reached their steady state.
+const WARM_UP_CHILDREN: usize = 8;
+
+/// The maximum number of times the `spawn-exit` test case yields while the
+/// kernel releases the address spaces of the terminated children, which is
+/// done in the background after they are reported as terminated.
+const RELEASE_YIELDS: usize = 10_000;
+
+/// Set by the client thread of the `service-rename` test case right before it
+/// blocks sending a message to the service.
+static CLIENT_BLOCKING: AtomicBool = AtomicBool::new(false);
+
+/// Runs one of the test cases of the integration test suite, whose name is
+/// given by the kernel in the `TEST_CASE` environment variable. This program
+/// is only spawned when the kernel is built with the `integration` feature:
+/// the kernel then checks how each case terminates. The cases that pass exit
+/// with a zero code, while the cases checking that faults are caught are
+/// expected to be terminated by the kernel.
+#[xstd::main]
+pub fn main() {
+    let case = xstd::env::var(CASE_VAR).unwrap_or_default();
+    let passed = match case {
+        "ipc" => ipc(),
+        "memory" => memory(),
+        "service-rename" => service_rename(),
+        "reconnect" => reconnect(),
+        "spawn-exit" => spawn_exit(),
+        "panic" => panic!("Panicking on purpose"),
+        "null-dereference" => null_dereference(),
+        "read-only-write" => read_only_write(),
+        "illegal-instruction" => illegal_instruction(),
+        _ => {
+            xstd::println!("Unknown test case: {}", case);
+            false
+        }
+    };
+
+    xstd::task::exit(if passed { 0 } else { 1 })
+}
+
+/// Sends messages of various sizes to the `echo` service and checks that the
+/// replies match them.
+fn ipc() -> bool {
+    let echo = EchoClient::new(connect_until_success(xstd::echo::SERVICE_NAME));
+    [0, 1, 64, 1024].into_iter().all(|size| {
+        let message = (0..size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        echo.echo(&message).is_ok_and(|reply| reply == message)
+    })
+}
+
+/// Provides an echo service that unregisters and registers again under a new
+/// name, while a client thread is blocked sending a message to it under the
+/// old name. The client must fail with `ServiceUnregistered`, and then reach
+/// the service by connecting to the new name.
+fn service_rename() -> bool {
+    if xstd::service::register(OLD_NAME).is_err() {
+        return false;
+    }
+    let Ok(client) = xstd::thread::spawn(rename_client) else {
+        return false;
+    };
+
+    // Let the client fill the mailbox of the service and block on its next
+    // message before renaming the service.
+    while !CLIENT_BLOCKING.load(Ordering::Acquire) {
+        xstd::task::yield_now();
+    }
+    for _ in 0..16 {
+        xstd::task::yield_now();
+    }
+    if xstd::service::unregister().is_err() || xstd::service::register(NEW_NAME).is_err() {
+        return false;
+    }
+
+    // The one-way messages that filled the mailbox were delivered before the
+    // service was renamed, and are skipped. The first request is the one sent
+    // by the client through its new connection, and is echoed back.
+    loop {
+        let Ok(message) = xstd::ipc::receive() else {
+            return false;
+        };
+        if message.sender_info.is_oneway() {
+            continue;
+        }
+        let payload = &message.payload[..message.payload_len.min(message.payload.len())];
+        if xstd::ipc::reply(message.sender, rpc::Status::Ok as usize, payload).is_err() {
+            return false;
+        }
+        break;
+    }
+    client.join().unwrap_or(false)
+}
+
+/// The client thread of the `service-rename` test case. It fills the mailbox
+/// of the service with one-way messages so that its next message blocks until
+/// the service is renamed, and then sends a message under the new name.
+fn rename_client() -> bool {
+    let Ok(old) = xstd::service::connect(OLD_NAME) else {
+        return false;
+    };
+    while xstd::ipc::send_oneway(old, 0, &[]).is_ok() {}
+
+    CLIENT_BLOCKING.store(true, Ordering::Release);
+    let unregistered = EchoClient::new(old).echo(b"old")
+        == Err(rpc::Error::Send(xstd::ipc::SendError::ServiceUnregistered));
+
+    let new = EchoClient::new(connect_until_success(NEW_NAME));
+    unregistered && new.echo(b"new").is_ok_and(|reply| reply == b"new")
+}
+
+/// Connects to the `echo` service and disconnects from it many times, which
+/// must never run out of connections, then holds as many connections as the
+/// kernel allows and checks that closing one of them makes room for a new
+/// one.
+fn reconnect() -> bool {
+    let first = connect_until_success(xstd::echo::SERVICE_NAME);
+    if xstd::service::disconnect(first).is_err() {
+        return false;
+    }
+    for _ in 0..RECONNECTIONS {
+        let Ok(handle) = xstd::service::connect(xstd::echo::SERVICE_NAME) else {
+            return false;
+        };
+        if handle != first || xstd::service::disconnect(handle).is_err() {
+            return false;
+        }
+    }
+    if xstd::service::disconnect(first) != Err(xstd::service::DisconnectError::InvalidHandle) {
+        return false;
+    }
+
+    let mut handles = Vec::new();
+    let error = loop {
+        match xstd::service::connect(xstd::echo::SERVICE_NAME) {
+            Ok(handle) if handles.len() < RECONNECTIONS => handles.push(handle),
+            Ok(_) => return false,
+            Err(error) => break error,
+        }
+    };
+    let Some(&last) = handles.last() else {
+        return false;
+    };
+    let reused = xstd::service::disconnect(last).is_ok()
+        && xstd::service::connect(xstd::echo::SERVICE_NAME) == Ok(last);
+    let echoed = EchoClient::new(last)
+        .echo(b"last")
+        .is_ok_and(|reply| reply == b"last");
+    let closed = handles
+        .into_iter()
+        .all(|handle| xstd::service::disconnect(handle).is_ok());
+    error == xstd::service::ConnectionError::TooManyConnections && reused && echoed && closed
+}
+
+/// Spawns and waits on more children than the memory could hold if their
+/// frames were never released, then checks that the number of frames
+/// allocated by the kernel returns to what it was before.
+fn spawn_exit() -> bool {
+    if !(0..WARM_UP_CHILDREN).all(|_| spawn_and_wait()) {
+        return false;
+    }
+    let Some(baseline) = settle(None) else {
+        return false;
+    };
+    let Ok((statistics, _)) = xstd::stats::kernel(&mut []) else {
+        return false;
+    };
+
+    let children = statistics.frames_free / CHILD_FRAMES + 1;
+    (0..children).all(|_| spawn_and_wait()) && settle(Some(baseline)).is_some()
+}
+
+/// Spawns the child of the `spawn-exit` test case and waits for it to exit,
+/// returning whether it exited successfully.
+fn spawn_and_wait() -> bool {
+    let Ok(child) = xstd::task::spawn(CHILD, &["child"], &[]) else {
+        return false;
+    };
+    matches!(
+        xstd::task::wait(child),
+        Ok((xstd::task::Termination::Exited(0), _))
+    )
+}
+
+/// Yields while the kernel releases the address spaces of terminated tasks,
+/// and returns the lowest number of allocated frames observed. If a target is
+/// given, returns as soon as the number of allocated frames is at most the
+/// target, or `None` if it never is.
+fn settle(target: Option<u64>) -> Option<u64> {
+    let mut lowest = u64::MAX;
+    for _ in 0..RELEASE_YIELDS {
+        let (statistics, _) = xstd::stats::kernel(&mut []).ok()?;
+        lowest = lowest.min(statistics.frames_allocated);
+        if target.is_some_and(|target| lowest <= target) {
+            return Some(lowest);
+        }
+        xstd::task::yield_now();
+    }
+    target.is_none().then_some(lowest)
+}
+
+/// Grows the heap, then maps fresh memory and checks that it is zeroed and
+/// writable, and that it cannot be mapped twice.
+fn memory() -> bool {
+    let large = (0..256 * 1024).map(|i| i as u32).collect::<Vec<_>>();
+    if !large.iter().enumerate().all(|(i, &value)| value == i as u32) {
+        return false;
+    }
+    drop(large);
+
+    let Ok(address) = xstd::memory::map(free_address(), MAPPING_SIZE, Protection::READ_WRITE)
+    else {
+        return false;
+    };
+
+    // SAFETY: The memory was just mapped readable and writable, and nothing
+    // else uses it.
+    let memory = unsafe { core::slice::from_raw_parts_mut(address, MAPPING_SIZE) };
+    if memory.iter().any(|&byte| byte != 0) {
+        return false;
+    }
+    memory.fill(0xA5);
+
+    let remapped = xstd::memory::map(free_address(), MAPPING_SIZE, Protection::READ_WRITE);
+    memory.iter().all(|&byte| byte == 0xA5)
+        && remapped == Err(xstd::memory::MapError::AlreadyMapped)
+}
+
+/// Reads the null address, which must make the kernel terminate the task.
+fn null_dereference() -> bool {
+    // SAFETY: This is not safe at all: the read must fault.
+    unsafe { core::ptr::null::<u8>().read_volatile() };
+    false
+}
+
+/// Writes to read-only memory, which must make the kernel terminate the task.
+fn read_only_write() -> bool {
+    let Ok(address) = xstd::memory::map(free_address(), MAPPING_SIZE, Protection::READ) else {
+        return false;
+    };
+
+    // SAFETY: This is not safe at all: the write must fault.
+    unsafe { address.write_volatile(1) };
+    false
+}
+
+/// Executes an illegal instruction, which must make the kernel terminate the
+/// task.
+fn illegal_instruction() -> bool {
+    // SAFETY: This is not safe at all: the instruction must fault.
+    unsafe {
+        #[cfg(target_arch = "riscv64")]
+        core::arch::asm!("unimp");
+        #[cfg(target_arch = "aarch64")]
+        core::arch::asm!("udf #0");
+    }
+    false
+}
+
+/// Returns an address where memory can be mapped by the test cases. It is in
+/// the middle of the range reserved for the heap, which never grows that far
+/// during the tests.
+fn free_address() -> usize {
+    xstd::vdso::mmap_base() + xstd::heap::HEAP_MAX_SIZE / 2
+}
+
+/// Connects to a service by its name, yielding until it is registered since
+/// the service may not be started yet.
+fn connect_until_success(name: &str) -> usize {
+    loop {
+        match xstd::service::connect(name) {
+            Ok(handle) => return handle,
+            Err(_) => xstd::task::yield_now(),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+/// The number of runs of each measured operation. It is large enough for the
+/// average to be stable, while keeping the benchmark under a second.
+const RUNS: u64 = 10_000;
+
+/// The driver of the benchmark. It measures from user space the cost of a
+/// syscall that does nothing, of yielding to the scheduler and of an IPC round
+/// trip with the `pong` service, and prints the average of each operation.
+/// When the kernel is built with the `bench` feature, it reports its own
+/// measurements and shuts down once this program exits.
+#[xstd::main]
+pub fn main() {
+    let pong = xstd::service::connect_wait(xstd::bench::PONG_SERVICE_NAME)
+        .expect("Failed to connect to pong");
+
+    let syscall = xstd::bench::average(RUNS, xstd::syscall::nop);
+    let switch = xstd::bench::average(RUNS, xstd::task::yield_now);
+    let ipc = xstd::bench::average(RUNS, || {
+        _ = xstd::ipc::send(pong, 0, &[]);
+    });
+
+    xstd::println!("Benchmark of {} runs per operation:", RUNS);
+    xstd::println!("  Syscall: {} ns", syscall);
+    xstd::println!("  Yield: {} ns", switch);
+    xstd::println!("  IPC round trip: {} ns", ipc);
+}
@@ -0,0 +1,15 @@
+#![no_std]
+#![no_main]
+
+use xstd::service::{Response, Server};
+
+/// The service of the IPC benchmark. It registers itself under the name
+/// "pong" and replies to every message with an empty reply, so that the
+/// `ping` program measures the cost of IPC itself rather than the cost of
+/// handling the messages.
+#[xstd::main]
+pub fn main() {
+    Server::register(xstd::bench::PONG_SERVICE_NAME)
+        .unwrap()
+        .serve(|_| Response::status(0));
+}
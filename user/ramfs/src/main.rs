@@ -0,0 +1,221 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+use syscall::fs::{
+    self, FileError, FileKind, HandleRequest, MAX_READ_SIZE, Metadata, OpenFlags, OpenRequest,
+    Operation, Payload, ReadRequest, WriteRequest,
+};
+use xstd::service::{Response, Server};
+
+/// The maximum number of files that a client can open at the same time.
+const MAX_OPEN_FILES: usize = 64;
+
+/// The permissions reported for all files, since the protocol does not allow
+/// changing them.
+const FILE_MODE: u64 = 0o644;
+
+/// A file opened by a client.
+struct OpenFile {
+    /// The identifier of the task that opened the file. Only this task can
+    /// use the handle of the file.
+    client: usize,
+
+    /// The path of the file.
+    path: String,
+
+    /// The flags given when the file was opened.
+    flags: OpenFlags,
+}
+
+/// A file system whose files are stored in memory. It starts empty, and is
+/// filled by its clients: `init` copies the content of the initial ramdisk in
+/// it at boot.
+///
+/// Files opened by a client that terminates without closing them are never
+/// closed, since the server is not notified of the termination of its
+/// clients.
+struct RamFs {
+    /// The content of the files, indexed by their absolute path.
+    files: BTreeMap<String, Vec<u8>>,
+
+    /// The opened files, indexed by their handle.
+    handles: BTreeMap<u64, OpenFile>,
+
+    /// The handle of the next file opened.
+    next_handle: u64,
+}
+
+impl RamFs {
+    /// Creates an empty file system.
+    const fn new() -> Self {
+        Self {
+            files: BTreeMap::new(),
+            handles: BTreeMap::new(),
+            next_handle: 1,
+        }
+    }
+
+    /// Handles a request sent by the given client, and returns the status
+    /// value and the payload of the reply.
+    fn dispatch(
+        &mut self,
+        client: usize,
+        operation: Operation,
+        payload: &[u8],
+    ) -> Result<(usize, Vec<u8>), FileError> {
+        match operation {
+            Operation::Open => self.open(client, &parse(payload)?),
+            Operation::Read => self.read(client, &parse(payload)?),
+            Operation::Write => self.write(client, &parse(payload)?),
+            Operation::Close => self.close(client, &parse(payload)?),
+            Operation::Stat => self.stat(client, &parse(payload)?),
+            Operation::Unknown => Err(FileError::Unsupported),
+        }
+    }
+
+    /// Opens a file, creating or truncating it if requested, and returns its
+    /// handle.
+    fn open(
+        &mut self,
+        client: usize,
+        request: &OpenRequest,
+    ) -> Result<(usize, Vec<u8>), FileError> {
+        let path = request.path().ok_or(FileError::BadRequest)?;
+        let flags = request.flags().ok_or(FileError::BadRequest)?;
+        if !path.starts_with('/')
+            || (flags.contains(OpenFlags::TRUNCATE) && !flags.contains(OpenFlags::WRITE))
+        {
+            return Err(FileError::BadRequest);
+        }
+
+        let opened = self.handles.values().filter(|f| f.client == client);
+        if opened.count() >= MAX_OPEN_FILES {
+            return Err(FileError::TooManyOpenFiles);
+        }
+
+        match self.files.get_mut(path) {
+            Some(data) if flags.contains(OpenFlags::TRUNCATE) => data.clear(),
+            Some(_) => {}
+            None if flags.contains(OpenFlags::CREATE) => {
+                self.files.insert(String::from(path), Vec::new());
+            }
+            None => return Err(FileError::NotFound),
+        }
+
+        let handle = self.next_handle;
+        let value = usize::try_from(handle).map_err(|_| FileError::OutOfMemory)?;
+        self.next_handle += 1;
+        self.handles.insert(
+            handle,
+            OpenFile {
+                client,
+                path: String::from(path),
+                flags,
+            },
+        );
+        Ok((value, Vec::new()))
+    }
+
+    /// Reads from a file, and returns the data read.
+    fn read(&self, client: usize, request: &ReadRequest) -> Result<(usize, Vec<u8>), FileError> {
+        let file = self.file(client, request.handle)?;
+        if !file.flags.contains(OpenFlags::READ) {
+            return Err(FileError::PermissionDenied);
+        }
+
+        let data = &self.files[&file.path];
+        let start = usize::try_from(request.offset).map_or(data.len(), |o| o.min(data.len()));
+        let len = usize::try_from(request.len).map_or(MAX_READ_SIZE, |l| l.min(MAX_READ_SIZE));
+        let end = start + len.min(data.len() - start);
+        Ok((end - start, data[start..end].to_vec()))
+    }
+
+    /// Writes to a file, extending it if needed, and returns the number of
+    /// bytes written.
+    fn write(
+        &mut self,
+        client: usize,
+        request: &WriteRequest,
+    ) -> Result<(usize, Vec<u8>), FileError> {
+        // The handles are borrowed directly rather than through `Self::file`,
+        // so that the content of the file can be borrowed mutably.
+        let file = self
+            .handles
+            .get(&request.handle)
+            .filter(|file| file.client == client)
+            .ok_or(FileError::InvalidHandle)?;
+        if !file.flags.contains(OpenFlags::WRITE) {
+            return Err(FileError::PermissionDenied);
+        }
+
+        let data = request.data().ok_or(FileError::BadRequest)?;
+        let start = usize::try_from(request.offset).map_err(|_| FileError::BadRequest)?;
+        let end = start.checked_add(data.len()).ok_or(FileError::BadRequest)?;
+
+        let content = self.files.get_mut(&file.path).ok_or(FileError::NotFound)?;
+        if content.len() < end {
+            content
+                .try_reserve(end - content.len())
+                .map_err(|_| FileError::OutOfMemory)?;
+            content.resize(end, 0);
+        }
+        content[start..end].copy_from_slice(data);
+        Ok((data.len(), Vec::new()))
+    }
+
+    /// Closes a file.
+    fn close(
+        &mut self,
+        client: usize,
+        request: &HandleRequest,
+    ) -> Result<(usize, Vec<u8>), FileError> {
+        self.file(client, request.handle)?;
+        self.handles.remove(&request.handle);
+        Ok((0, Vec::new()))
+    }
+
+    /// Returns the metadata of a file.
+    fn stat(&self, client: usize, request: &HandleRequest) -> Result<(usize, Vec<u8>), FileError> {
+        let file = self.file(client, request.handle)?;
+        let metadata = Metadata {
+            size: self.files[&file.path].len() as u64,
+            kind: FileKind::Regular as u64,
+            mode: FILE_MODE,
+        };
+        Ok((0, metadata.as_payload().to_vec()))
+    }
+
+    /// Returns the file opened by the given client with the given handle.
+    fn file(&self, client: usize, handle: u64) -> Result<&OpenFile, FileError> {
+        self.handles
+            .get(&handle)
+            .filter(|file| file.client == client)
+            .ok_or(FileError::InvalidHandle)
+    }
+}
+
+/// Parses the request of an operation from the payload of a message.
+fn parse<T: Payload>(payload: &[u8]) -> Result<T, FileError> {
+    T::from_payload(payload).ok_or(FileError::BadRequest)
+}
+
+/// A file server that keeps its files in memory, registered under the name
+/// of the root file server. It serves any number of clients, each of them
+/// only being able to use the files it opened.
+#[xstd::main]
+pub fn main() {
+    let mut ramfs = RamFs::new();
+    Server::register(fs::SERVICE_NAME)
+        .unwrap()
+        .with_overflow_status(fs::encode_status(Err(FileError::Unknown)))
+        .serve(|request| {
+            let operation = Operation::from(request.kind());
+            match ramfs.dispatch(request.sender(), operation, request.payload()) {
+                Ok((value, data)) => Response::new(fs::encode_status(Ok(value)), data),
+                Err(error) => Response::status(fs::encode_status(Err(error))),
+            }
+        });
+}
@@ -0,0 +1,386 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use queue::{Buffer, Queue};
+use syscall::blk::{
+    self, BlockError, Info, MAX_READ_SIZE, Operation, Payload, ReadRequest, WriteRequest,
+};
+use xstd::{
+    device::{DeviceKind, VIRTIO_BLOCK},
+    memory::Dma,
+    service::{Response, Server},
+};
+
+mod queue;
+mod virtio;
+
+/// The address where the registers of the device are mapped.
+const REGISTERS_ADDRESS: usize = 0x0000_0030_0000_0000;
+
+/// The address where the memory of the virtqueue is allocated. The memory
+/// holding the requests follows it.
+const DMA_ADDRESS: usize = 0x0000_0030_0010_0000;
+
+/// The size of the memory holding the requests.
+const REQUEST_MEMORY_SIZE: usize = 0x1000;
+
+/// The offset of the header of the request in the memory of the requests.
+const HEADER_OFFSET: usize = 0x000;
+
+/// The offset of the status byte of the request in the memory of the requests.
+const STATUS_OFFSET: usize = 0x010;
+
+/// The offset of the data of the request in the memory of the requests.
+const DATA_OFFSET: usize = 0x200;
+
+/// The maximum number of sectors transferred by a request. A read or a write
+/// of the protocol never spans more than two sectors.
+const DATA_SECTORS: usize = 2;
+
+/// The request reads sectors from the device.
+const REQUEST_IN: u32 = 0;
+
+/// The request writes sectors to the device.
+const REQUEST_OUT: u32 = 1;
+
+/// The value of the status byte when the request succeeded.
+const STATUS_OK: u8 = 0;
+
+/// The value of the status byte when the request is not supported.
+const STATUS_UNSUPPORTED: u8 = 2;
+
+/// The size of the sectors of virtio block devices. The capacity of the
+/// device and the requests are always given in sectors of this size, whatever
+/// the size of the blocks of the underlying storage.
+const SECTOR_SIZE: u64 = 512;
+
+const _: () = assert!(MAX_READ_SIZE <= SECTOR_SIZE as usize);
+const _: () = assert!(DATA_OFFSET + DATA_SECTORS * SECTOR_SIZE as usize <= REQUEST_MEMORY_SIZE);
+
+/// The offset of the `capacity` field in the configuration space of a block
+/// device, in sectors.
+const CONFIG_CAPACITY: usize = 0x00;
+
+/// The device is read-only.
+const F_RO: u64 = 1 << 5;
+
+/// The header of a request, as read by the device.
+#[repr(C)]
+struct RequestHeader {
+    kind: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+/// A virtio block device. Requests are sent one at a time through its single
+/// virtqueue, and their completion is awaited before handling the next
+/// message.
+struct Block {
+    /// The registers of the device.
+    transport: virtio::Transport,
+
+    /// The virtqueue of the requests.
+    queue: Queue,
+
+    /// The memory holding the header, the data and the status of the request
+    /// in flight.
+    requests: Dma,
+
+    /// The interrupt source of the device, or `None` if the completion of the
+    /// requests is polled.
+    irq: Option<usize>,
+
+    /// Whether the device is read-only.
+    read_only: bool,
+}
+
+impl Block {
+    /// Initializes the block device behind the given transport, whose
+    /// interrupts are raised on the given source, if any. The device is marked
+    /// as failed if it cannot be initialized.
+    ///
+    /// # Errors
+    /// Returns a [`virtio::Error`] if the device cannot be initialized.
+    fn new(transport: virtio::Transport, irq: Option<usize>) -> Result<Self, virtio::Error> {
+        let features = transport.negotiate(VIRTIO_BLOCK as u32, F_RO)?;
+        let (queue, requests) = Self::setup_queue(&transport).inspect_err(|_| {
+            transport.add_status(virtio::status::FAILED);
+        })?;
+        transport.ready();
+
+        // Without its interrupt, the driver still works by polling the
+        // virtqueue, just less efficiently.
+        let irq = irq.filter(|&irq| xstd::irq::register(irq).is_ok());
+        Ok(Self {
+            transport,
+            queue,
+            requests,
+            irq,
+            read_only: features & F_RO != 0,
+        })
+    }
+
+    /// Allocates the memory of the virtqueue and of the requests, and sets up
+    /// the virtqueue.
+    ///
+    /// # Errors
+    /// Returns a [`virtio::Error`] if the memory cannot be allocated or if the
+    /// virtqueue cannot be used.
+    fn setup_queue(transport: &virtio::Transport) -> Result<(Queue, Dma), virtio::Error> {
+        let memory = xstd::memory::alloc_dma(DMA_ADDRESS, queue::MEMORY_SIZE)
+            .map_err(|_| virtio::Error::OutOfMemory)?;
+        let requests =
+            xstd::memory::alloc_dma(DMA_ADDRESS + queue::MEMORY_SIZE, REQUEST_MEMORY_SIZE)
+                .map_err(|_| virtio::Error::OutOfMemory)?;
+        Ok((Queue::new(transport, 0, memory)?, requests))
+    }
+
+    /// Handles a request, and returns the status value and the payload of the
+    /// reply.
+    fn dispatch(
+        &mut self,
+        operation: Operation,
+        payload: &[u8],
+    ) -> Result<(usize, Vec<u8>), BlockError> {
+        match operation {
+            Operation::Info => Ok((0, self.info().as_payload().to_vec())),
+            Operation::Read => self.read(&parse(payload)?),
+            Operation::Write => self.write(&parse(payload)?),
+            Operation::Unknown => Err(BlockError::Unsupported),
+        }
+    }
+
+    /// Returns the size of the device, in bytes. It is read from the device
+    /// every time, since the storage behind the device may be resized.
+    fn size(&self) -> u64 {
+        self.transport
+            .config_u64(CONFIG_CAPACITY)
+            .saturating_mul(SECTOR_SIZE)
+    }
+
+    /// Returns the geometry of the device.
+    fn info(&self) -> Info {
+        Info {
+            size: self.size(),
+            sector_size: SECTOR_SIZE,
+            read_only: u64::from(self.read_only),
+        }
+    }
+
+    /// Reads from the device, and returns the data read. Fewer bytes than
+    /// requested are read at the end of the device.
+    fn read(&mut self, request: &ReadRequest) -> Result<(usize, Vec<u8>), BlockError> {
+        let len = usize::try_from(request.len).map_or(MAX_READ_SIZE, |l| l.min(MAX_READ_SIZE));
+        let sectors = self.sectors(request.offset, len)?;
+        if sectors.len == 0 {
+            return Ok((0, Vec::new()));
+        }
+
+        self.transfer(REQUEST_IN, &sectors)?;
+        let data = &self.data()[sectors.skip..sectors.skip + sectors.len];
+        Ok((sectors.len, data.to_vec()))
+    }
+
+    /// Writes to the device, and returns the number of bytes written. Fewer
+    /// bytes than requested are written at the end of the device. Sectors
+    /// that are only partially written are read first, so that the rest of
+    /// their content is kept.
+    fn write(&mut self, request: &WriteRequest) -> Result<(usize, Vec<u8>), BlockError> {
+        if self.read_only {
+            return Err(BlockError::ReadOnly);
+        }
+        let data = request.data().ok_or(BlockError::BadRequest)?;
+        let sectors = self.sectors(request.offset, data.len())?;
+        if sectors.len == 0 {
+            return Ok((0, Vec::new()));
+        }
+
+        let size = sectors.count * SECTOR_SIZE as usize;
+        if sectors.skip != 0 || sectors.len != size {
+            self.transfer(REQUEST_IN, &sectors)?;
+        }
+        self.data()[sectors.skip..sectors.skip + sectors.len].copy_from_slice(&data[..sectors.len]);
+        self.transfer(REQUEST_OUT, &sectors)?;
+        Ok((sectors.len, Vec::new()))
+    }
+
+    /// Returns the sectors covering `len` bytes starting at the given offset,
+    /// truncated at the end of the device.
+    ///
+    /// # Errors
+    /// Returns [`BlockError::OutOfRange`] if the offset is past the end of the
+    /// device.
+    fn sectors(&self, offset: u64, len: usize) -> Result<Sectors, BlockError> {
+        let size = self.size();
+        if offset > size {
+            return Err(BlockError::OutOfRange);
+        }
+
+        let len = usize::try_from(size - offset).map_or(len, |remaining| remaining.min(len));
+        let first = offset / SECTOR_SIZE;
+        let end = offset + len as u64;
+        #[allow(clippy::cast_possible_truncation)]
+        Ok(Sectors {
+            first,
+            count: (end.div_ceil(SECTOR_SIZE) - first) as usize,
+            skip: (offset % SECTOR_SIZE) as usize,
+            len,
+        })
+    }
+
+    /// Returns the data buffer of the requests.
+    fn data(&mut self) -> &mut [u8] {
+        // SAFETY: The buffer is in the memory of the requests, which is only
+        // accessed by the device while a request is in flight, and the
+        // mutable borrow of the device prevents sending a request meanwhile.
+        unsafe {
+            core::slice::from_raw_parts_mut(
+                self.requests.address.add(DATA_OFFSET),
+                DATA_SECTORS * SECTOR_SIZE as usize,
+            )
+        }
+    }
+
+    /// Sends a request of the given kind for the given sectors, whose data is
+    /// in the data buffer, and waits for its completion.
+    ///
+    /// # Errors
+    /// Returns [`BlockError::Unsupported`] if the device does not support the
+    /// request, or [`BlockError::Io`] if it failed.
+    fn transfer(&mut self, kind: u32, sectors: &Sectors) -> Result<(), BlockError> {
+        let header = self.requests.address.wrapping_add(HEADER_OFFSET);
+        let status = self.requests.address.wrapping_add(STATUS_OFFSET);
+
+        // SAFETY: The header and the status are in the memory of the
+        // requests, which is not accessed by the device since no request is
+        // in flight.
+        unsafe {
+            header
+                .cast::<RequestHeader>()
+                .write_volatile(RequestHeader {
+                    kind,
+                    reserved: 0,
+                    sector: sectors.first,
+                });
+            status.write_volatile(u8::MAX);
+        }
+
+        let physical = self.requests.physical;
+        #[allow(clippy::cast_possible_truncation)]
+        let buffers = [
+            Buffer {
+                physical: physical + HEADER_OFFSET,
+                len: size_of::<RequestHeader>() as u32,
+                writable: false,
+            },
+            Buffer {
+                physical: physical + DATA_OFFSET,
+                len: (sectors.count * SECTOR_SIZE as usize) as u32,
+                writable: kind == REQUEST_IN,
+            },
+            Buffer {
+                physical: physical + STATUS_OFFSET,
+                len: 1,
+                writable: true,
+            },
+        ];
+        self.queue.submit(&self.transport, &buffers);
+
+        while !self.queue.completed() {
+            if let Some(irq) = self.irq {
+                _ = xstd::irq::wait(irq);
+                self.transport.ack_interrupt();
+            } else {
+                xstd::task::yield_now();
+            }
+        }
+
+        // SAFETY: The request has completed, so the device does not access
+        // the status anymore.
+        match unsafe { status.read_volatile() } {
+            STATUS_OK => Ok(()),
+            STATUS_UNSUPPORTED => Err(BlockError::Unsupported),
+            _ => Err(BlockError::Io),
+        }
+    }
+}
+
+/// The sectors covering a range of bytes of the device.
+struct Sectors {
+    /// The first sector.
+    first: u64,
+
+    /// The number of sectors, at most [`DATA_SECTORS`].
+    count: usize,
+
+    /// The offset of the range in the first sector.
+    skip: usize,
+
+    /// The length of the range, in bytes.
+    len: usize,
+}
+
+/// Parses the request of an operation from the payload of a message.
+fn parse<T: Payload>(payload: &[u8]) -> Result<T, BlockError> {
+    T::from_payload(payload).ok_or(BlockError::BadRequest)
+}
+
+/// Finds the first virtio block device of the system, maps its registers and
+/// initializes it. Returns `None` if there is no block device or if it cannot
+/// be initialized.
+fn probe() -> Option<Block> {
+    let (index, device) = xstd::device::list().find(|(_, device)| {
+        device.kind() == DeviceKind::VirtioMmio && device.model == VIRTIO_BLOCK
+    })?;
+
+    let registers = match xstd::device::map(index, REGISTERS_ADDRESS) {
+        Ok(registers) => registers,
+        Err(error) => {
+            xstd::println!("virtio-blk: cannot map the device: {:?}", error);
+            return None;
+        }
+    };
+
+    // SAFETY: The registers of the device were just mapped, and the device is
+    // claimed by this task so no other task can drive it.
+    let transport = unsafe { virtio::Transport::new(registers) };
+    match Block::new(transport, device.irq()) {
+        Ok(block) => {
+            xstd::println!(
+                "virtio-blk: {} KiB device at {:#x}{}",
+                block.size() / 1024,
+                device.base,
+                if block.read_only { " (read-only)" } else { "" }
+            );
+            Some(block)
+        }
+        Err(error) => {
+            xstd::println!("virtio-blk: cannot initialize the device: {:?}", error);
+            None
+        }
+    }
+}
+
+/// A driver for virtio block devices. It drives the first block device found,
+/// and serves the block protocol under [`blk::SERVICE_NAME`]. It exits if
+/// there is no block device.
+#[xstd::main]
+pub fn main() {
+    let Some(mut block) = probe() else {
+        return;
+    };
+
+    Server::register(blk::SERVICE_NAME)
+        .unwrap()
+        .with_overflow_status(blk::encode_status(Err(BlockError::Unknown)))
+        .serve(|request| {
+            let operation = Operation::from(request.kind());
+            match block.dispatch(operation, request.payload()) {
+                Ok((value, data)) => Response::new(blk::encode_status(Ok(value)), data),
+                Err(error) => Response::status(blk::encode_status(Err(error))),
+            }
+        });
+}
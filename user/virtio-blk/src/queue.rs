@@ -0,0 +1,182 @@
+use crate::virtio::{self, Transport};
+use xstd::memory::Dma;
+
+/// The number of descriptors of the virtqueue. The driver sends a single
+/// request at a time, and a request never needs more than a few descriptors.
+pub const QUEUE_SIZE: u16 = 8;
+
+/// The offset of the available ring in the memory of the virtqueue.
+const AVAIL_OFFSET: usize = 0x100;
+
+/// The offset of the used ring in the memory of the virtqueue.
+const USED_OFFSET: usize = 0x200;
+
+/// The size of the memory needed by the virtqueue.
+pub const MEMORY_SIZE: usize = 0x1000;
+
+/// The buffer continues in the descriptor given by the `next` field.
+const DESC_F_NEXT: u16 = 1;
+
+/// The buffer is written by the device, rather than read.
+const DESC_F_WRITE: u16 = 2;
+
+/// A descriptor of the descriptor table, describing a buffer.
+#[repr(C)]
+struct Descriptor {
+    address: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+/// The available ring, where the driver puts the chains of descriptors that
+/// the device should process.
+#[repr(C)]
+struct AvailRing {
+    flags: u16,
+    idx: u16,
+    ring: [u16; QUEUE_SIZE as usize],
+}
+
+/// An element of the used ring.
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+/// The used ring, where the device puts the chains of descriptors that it has
+/// processed.
+#[repr(C)]
+struct UsedRing {
+    flags: u16,
+    idx: u16,
+    ring: [UsedElem; QUEUE_SIZE as usize],
+}
+
+const _: () = assert!(size_of::<Descriptor>() * QUEUE_SIZE as usize <= AVAIL_OFFSET);
+const _: () = assert!(AVAIL_OFFSET + size_of::<AvailRing>() + 2 <= USED_OFFSET);
+const _: () = assert!(USED_OFFSET + size_of::<UsedRing>() + 2 <= MEMORY_SIZE);
+
+/// A buffer given to the device, by its physical address.
+#[derive(Debug, Clone, Copy)]
+pub struct Buffer {
+    /// The physical address of the buffer.
+    pub physical: usize,
+
+    /// The size of the buffer, in bytes.
+    pub len: u32,
+
+    /// Whether the device writes the buffer, rather than reading it.
+    pub writable: bool,
+}
+
+/// A split virtqueue (see section 2.7 of the virtio specification), whose
+/// descriptor table and rings are stored in DMA memory. Since a single request
+/// is in flight at a time, the descriptors of each request always start at
+/// the first one, and no free list is needed.
+pub struct Queue {
+    /// The index of the virtqueue on the device.
+    index: u32,
+
+    /// The memory holding the descriptor table and the rings.
+    memory: Dma,
+
+    /// The value of the `idx` field of the used ring when the last request
+    /// completed.
+    last_used: u16,
+}
+
+impl Queue {
+    /// Sets up the given virtqueue of the device, using the given memory that
+    /// must be at least [`MEMORY_SIZE`] bytes long.
+    ///
+    /// # Errors
+    /// Returns a [`virtio::Error`] if the virtqueue cannot be used.
+    pub fn new(transport: &Transport, index: u32, memory: Dma) -> Result<Self, virtio::Error> {
+        if memory.size < MEMORY_SIZE {
+            return Err(virtio::Error::OutOfMemory);
+        }
+        transport.setup_queue(
+            index,
+            QUEUE_SIZE,
+            memory.physical,
+            memory.physical + AVAIL_OFFSET,
+            memory.physical + USED_OFFSET,
+        )?;
+        Ok(Self {
+            index,
+            memory,
+            last_used: 0,
+        })
+    }
+
+    /// Makes a chain of descriptors from the given buffers, and notifies the
+    /// device that it is available. The previous request must have completed.
+    ///
+    /// # Panics
+    /// Panics if there are more buffers than descriptors.
+    pub fn submit(&mut self, transport: &Transport, buffers: &[Buffer]) {
+        assert!(buffers.len() <= usize::from(QUEUE_SIZE));
+
+        let descriptors = self.memory.address.cast::<Descriptor>();
+        for (i, buffer) in buffers.iter().enumerate() {
+            let mut flags = 0;
+            if buffer.writable {
+                flags |= DESC_F_WRITE;
+            }
+            if i + 1 < buffers.len() {
+                flags |= DESC_F_NEXT;
+            }
+
+            // SAFETY: The descriptor table holds `QUEUE_SIZE` descriptors, and
+            // the device does not read it while no request is in flight.
+            unsafe {
+                descriptors.add(i).write_volatile(Descriptor {
+                    address: buffer.physical as u64,
+                    len: buffer.len,
+                    flags,
+                    #[allow(clippy::cast_possible_truncation)]
+                    next: (i + 1) as u16,
+                });
+            }
+        }
+
+        let avail = self
+            .memory
+            .address
+            .wrapping_add(AVAIL_OFFSET)
+            .cast::<AvailRing>();
+        // SAFETY: The available ring is in the memory of the virtqueue, and
+        // is only written by the driver.
+        unsafe {
+            let idx = (&raw const (*avail).idx).read_volatile();
+            let slot = usize::from(idx % QUEUE_SIZE);
+            (&raw mut (*avail).ring[slot]).write_volatile(0);
+
+            // The device must see the new entry of the ring before its index.
+            virtio::barrier();
+            (&raw mut (*avail).idx).write_volatile(idx.wrapping_add(1));
+        }
+        transport.notify(self.index);
+    }
+
+    /// Returns whether the device has processed the last request submitted.
+    pub fn completed(&mut self) -> bool {
+        let used = self
+            .memory
+            .address
+            .wrapping_add(USED_OFFSET)
+            .cast::<UsedRing>();
+        // SAFETY: The used ring is in the memory of the virtqueue.
+        let idx = unsafe { (&raw const (*used).idx).read_volatile() };
+        if idx == self.last_used {
+            return false;
+        }
+
+        // The buffers written by the device must be read after the ring.
+        virtio::barrier();
+        self.last_used = idx;
+        true
+    }
+}
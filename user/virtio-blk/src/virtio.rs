@@ -0,0 +1,263 @@
+/// The value of the `MagicValue` register, "virt" in little endian.
+pub const MAGIC: u32 = 0x7472_6976;
+
+/// The version of the MMIO transport implemented by this driver. Version 1 is
+/// the legacy interface, that QEMU exposes unless told otherwise with
+/// `-global virtio-mmio.force-legacy=false`.
+pub const VERSION: u32 = 2;
+
+/// The device supports the version 1.0 of the virtio specification, and not
+/// only the legacy interface.
+pub const F_VERSION_1: u64 = 1 << 32;
+
+/// The offsets of the registers of the MMIO transport.
+mod reg {
+    pub const MAGIC_VALUE: usize = 0x000;
+    pub const VERSION: usize = 0x004;
+    pub const DEVICE_ID: usize = 0x008;
+    pub const DEVICE_FEATURES: usize = 0x010;
+    pub const DEVICE_FEATURES_SEL: usize = 0x014;
+    pub const DRIVER_FEATURES: usize = 0x020;
+    pub const DRIVER_FEATURES_SEL: usize = 0x024;
+    pub const QUEUE_SEL: usize = 0x030;
+    pub const QUEUE_NUM_MAX: usize = 0x034;
+    pub const QUEUE_NUM: usize = 0x038;
+    pub const QUEUE_READY: usize = 0x044;
+    pub const QUEUE_NOTIFY: usize = 0x050;
+    pub const INTERRUPT_STATUS: usize = 0x060;
+    pub const INTERRUPT_ACK: usize = 0x064;
+    pub const STATUS: usize = 0x070;
+    pub const QUEUE_DESC_LOW: usize = 0x080;
+    pub const QUEUE_DESC_HIGH: usize = 0x084;
+    pub const QUEUE_DRIVER_LOW: usize = 0x090;
+    pub const QUEUE_DRIVER_HIGH: usize = 0x094;
+    pub const QUEUE_DEVICE_LOW: usize = 0x0a0;
+    pub const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+    pub const CONFIG_GENERATION: usize = 0x0fc;
+    pub const CONFIG: usize = 0x100;
+}
+
+/// The bits of the device status register.
+pub mod status {
+    /// The driver has noticed the device.
+    pub const ACKNOWLEDGE: u32 = 1;
+
+    /// The driver knows how to drive the device.
+    pub const DRIVER: u32 = 2;
+
+    /// The driver is set up and ready to drive the device.
+    pub const DRIVER_OK: u32 = 4;
+
+    /// The driver has acknowledged the features it understands, and feature
+    /// negotiation is complete.
+    pub const FEATURES_OK: u32 = 8;
+
+    /// The driver has given up on the device.
+    pub const FAILED: u32 = 128;
+}
+
+/// An error that can occur while initializing a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The registers do not belong to a virtio MMIO transport, or the version
+    /// of the transport is not supported.
+    UnsupportedTransport,
+
+    /// The device is not of the expected type.
+    WrongDevice,
+
+    /// The device does not accept the features selected by the driver.
+    FeaturesRejected,
+
+    /// The virtqueue does not exist, is already in use or is too small.
+    QueueUnavailable,
+
+    /// The memory of a virtqueue could not be allocated.
+    OutOfMemory,
+}
+
+/// The registers of a virtio device using the MMIO transport (see section 4.2
+/// of the virtio specification).
+#[derive(Debug)]
+pub struct Transport {
+    base: usize,
+}
+
+impl Transport {
+    /// Creates a transport whose registers are mapped at the given address.
+    ///
+    /// # Safety
+    /// The registers of a virtio MMIO transport must be mapped at the given
+    /// address for the whole lifetime of the transport, and must not be used
+    /// by anything else.
+    pub unsafe fn new(base: *mut u8) -> Self {
+        Self { base: base.addr() }
+    }
+
+    /// Reads the 32-bit register at the given offset.
+    fn read(&self, offset: usize) -> u32 {
+        let ptr = core::ptr::with_exposed_provenance::<u32>(self.base + offset);
+        // SAFETY: The registers are mapped for the lifetime of the transport,
+        // as guaranteed by the caller of `Transport::new`.
+        unsafe { ptr.read_volatile() }
+    }
+
+    /// Writes the 32-bit register at the given offset.
+    fn write(&self, offset: usize, value: u32) {
+        let ptr = core::ptr::with_exposed_provenance_mut::<u32>(self.base + offset);
+        // SAFETY: The registers are mapped for the lifetime of the transport,
+        // as guaranteed by the caller of `Transport::new`.
+        unsafe { ptr.write_volatile(value) }
+    }
+
+    /// Returns the status of the device.
+    pub fn status(&self) -> u32 {
+        self.read(reg::STATUS)
+    }
+
+    /// Sets the given bits in the status of the device.
+    pub fn add_status(&self, bits: u32) {
+        self.write(reg::STATUS, self.status() | bits);
+    }
+
+    /// Resets the device, and starts the initialization sequence described in
+    /// section 3.1.1 of the virtio specification up to the feature
+    /// negotiation: the device is reset and acknowledged, and the features
+    /// offered by the device are intersected with the given features. Returns
+    /// the negotiated features.
+    ///
+    /// # Errors
+    /// Returns an [`Error`] if the transport is not supported, if the device
+    /// is not of the given type, or if it refused the negotiated features. In
+    /// the latter case, the device is marked as failed.
+    pub fn negotiate(&self, device_id: u32, features: u64) -> Result<u64, Error> {
+        if self.read(reg::MAGIC_VALUE) != MAGIC || self.read(reg::VERSION) != VERSION {
+            return Err(Error::UnsupportedTransport);
+        }
+        if self.read(reg::DEVICE_ID) != device_id {
+            return Err(Error::WrongDevice);
+        }
+
+        self.write(reg::STATUS, 0);
+        self.add_status(status::ACKNOWLEDGE);
+        self.add_status(status::DRIVER);
+
+        let features = self.device_features() & (features | F_VERSION_1);
+        self.set_driver_features(features);
+        self.add_status(status::FEATURES_OK);
+        if self.status() & status::FEATURES_OK == 0 {
+            self.add_status(status::FAILED);
+            return Err(Error::FeaturesRejected);
+        }
+        Ok(features)
+    }
+
+    /// Returns the features offered by the device.
+    fn device_features(&self) -> u64 {
+        self.write(reg::DEVICE_FEATURES_SEL, 0);
+        let low = self.read(reg::DEVICE_FEATURES);
+        self.write(reg::DEVICE_FEATURES_SEL, 1);
+        let high = self.read(reg::DEVICE_FEATURES);
+        (u64::from(high) << 32) | u64::from(low)
+    }
+
+    /// Sets the features accepted by the driver.
+    #[allow(clippy::cast_possible_truncation)]
+    fn set_driver_features(&self, features: u64) {
+        self.write(reg::DRIVER_FEATURES_SEL, 0);
+        self.write(reg::DRIVER_FEATURES, features as u32);
+        self.write(reg::DRIVER_FEATURES_SEL, 1);
+        self.write(reg::DRIVER_FEATURES, (features >> 32) as u32);
+    }
+
+    /// Returns the maximum size of the given virtqueue, or 0 if the virtqueue
+    /// does not exist.
+    pub fn queue_max_size(&self, queue: u32) -> u32 {
+        self.write(reg::QUEUE_SEL, queue);
+        self.read(reg::QUEUE_NUM_MAX)
+    }
+
+    /// Configures the given virtqueue with the given size and the physical
+    /// addresses of its descriptor table, its available ring and its used
+    /// ring, and enables it.
+    ///
+    /// # Errors
+    /// Returns [`Error::QueueUnavailable`] if the virtqueue is already enabled,
+    /// or if it is smaller than the given size.
+    pub fn setup_queue(
+        &self,
+        queue: u32,
+        size: u16,
+        desc: usize,
+        driver: usize,
+        device: usize,
+    ) -> Result<(), Error> {
+        if self.queue_max_size(queue) < u32::from(size) || self.read(reg::QUEUE_READY) != 0 {
+            return Err(Error::QueueUnavailable);
+        }
+
+        self.write(reg::QUEUE_NUM, u32::from(size));
+        self.write_address(reg::QUEUE_DESC_LOW, reg::QUEUE_DESC_HIGH, desc);
+        self.write_address(reg::QUEUE_DRIVER_LOW, reg::QUEUE_DRIVER_HIGH, driver);
+        self.write_address(reg::QUEUE_DEVICE_LOW, reg::QUEUE_DEVICE_HIGH, device);
+        self.write(reg::QUEUE_READY, 1);
+        Ok(())
+    }
+
+    /// Writes a 64-bit physical address into a pair of 32-bit registers.
+    #[allow(clippy::cast_possible_truncation)]
+    fn write_address(&self, low: usize, high: usize, address: usize) {
+        self.write(low, address as u32);
+        self.write(high, (address as u64 >> 32) as u32);
+    }
+
+    /// Notifies the device that new buffers are available in the given
+    /// virtqueue.
+    pub fn notify(&self, queue: u32) {
+        // The buffers must be visible to the device before it is notified.
+        barrier();
+        self.write(reg::QUEUE_NOTIFY, queue);
+    }
+
+    /// Acknowledges the pending interrupts of the device, so that it stops
+    /// raising its interrupt line.
+    pub fn ack_interrupt(&self) {
+        let status = self.read(reg::INTERRUPT_STATUS);
+        self.write(reg::INTERRUPT_ACK, status);
+    }
+
+    /// Tells the device that the driver is ready to drive it, which completes
+    /// the initialization sequence.
+    pub fn ready(&self) {
+        self.add_status(status::DRIVER_OK);
+    }
+
+    /// Reads the 64-bit field at the given offset of the configuration space
+    /// of the device. The field is read again if the device changed its
+    /// configuration in the middle of the read, as required by the
+    /// specification.
+    pub fn config_u64(&self, offset: usize) -> u64 {
+        loop {
+            let generation = self.read(reg::CONFIG_GENERATION);
+            let low = self.read(reg::CONFIG + offset);
+            let high = self.read(reg::CONFIG + offset + 4);
+            if self.read(reg::CONFIG_GENERATION) == generation {
+                return (u64::from(high) << 32) | u64::from(low);
+            }
+        }
+    }
+}
+
+/// Orders all the memory and device accesses before the barrier with the ones
+/// after it, so that the device sees the content of the virtqueues written by
+/// the driver before being notified, and the driver sees the buffers written
+/// by the device once it observed their completion.
+pub fn barrier() {
+    // SAFETY: A fence has no effect other than ordering memory accesses.
+    unsafe {
+        #[cfg(target_arch = "riscv64")]
+        core::arch::asm!("fence iorw, iorw", options(nostack, preserves_flags));
+        #[cfg(target_arch = "aarch64")]
+        core::arch::asm!("dsb sy", options(nostack, preserves_flags));
+    }
+}
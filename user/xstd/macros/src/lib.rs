@@ -1,5 +1,7 @@
 use proc_macro::TokenStream;
-use syn::{ItemFn, parse_macro_input};
+use syn::{ItemFn, ItemTrait, parse_macro_input};
+
+mod rpc;
 
 /// A macro to indicate that a function is the main entry point of a user
 /// application. This macro will create the necessary boilerplate to set up the
@@ -26,9 +28,25 @@ pub fn main(_: TokenStream, item: TokenStream) -> TokenStream {
         #input_fn
 
         #[unsafe(no_mangle)]
-        pub unsafe fn _start() -> ! {
+        pub unsafe extern "C" fn _start(env: *const u8) -> ! {
+            unsafe { xstd::tls::init() };
+            unsafe { xstd::env::init(env) };
             #input_fn_name();
             xstd::task::exit(0);
         }
     ))
 }
+
+/// A macro to generate the client and the server of a protocol from a trait
+/// describing it. For a trait named `Foo`, this generates a `FooClient` that
+/// sends each method of the trait as an IPC message through a connection, and
+/// a `FooServer` that wraps an implementation of the trait so that it can be
+/// served with `xstd::rpc::serve`. See `xstd::rpc` for the encoding of the
+/// messages.
+#[proc_macro_attribute]
+pub fn rpc(_: TokenStream, item: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(item as ItemTrait);
+    rpc::expand(&item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
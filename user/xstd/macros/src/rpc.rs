@@ -0,0 +1,287 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    FnArg, GenericArgument, ItemTrait, Pat, PathArguments, ReturnType, TraitItem, TraitItemFn,
+    Type, spanned::Spanned,
+};
+
+/// How an argument or a return value is carried in the payload of a message.
+enum Encoding {
+    /// The value is copied as raw bytes with zerocopy.
+    Value,
+
+    /// The value is a byte slice (for arguments) or a byte vector (for return
+    /// values) that takes the remaining bytes of the payload.
+    Bytes,
+}
+
+/// A method of the protocol.
+struct Method<'a> {
+    /// The definition of the method in the trait.
+    item: &'a TraitItemFn,
+
+    /// The names, types and encodings of the arguments, excluding the receiver.
+    args: Vec<(&'a syn::Ident, &'a Type, Encoding)>,
+
+    /// The encoding of the return value, or `None` if the method returns `()`.
+    output: Option<Encoding>,
+}
+
+/// Generates the client and the server adapter of the protocol described by
+/// the given trait. See `xstd::rpc` for the description of the protocol.
+pub fn expand(item: &ItemTrait) -> syn::Result<TokenStream> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            item.generics.span(),
+            "RPC traits cannot be generic",
+        ));
+    }
+
+    let methods = item
+        .items
+        .iter()
+        .map(|item| match item {
+            TraitItem::Fn(method) => parse_method(method),
+            _ => Err(syn::Error::new(
+                item.span(),
+                "RPC traits can only contain methods",
+            )),
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let vis = &item.vis;
+    let name = &item.ident;
+    let client = format_ident!("{}Client", name);
+    let server = format_ident!("{}Server", name);
+    let client_doc = format!("The client of the [`{name}`] protocol, generated by `xstd::rpc`.");
+    let server_doc = format!(
+        "Adapts an implementation of the [`{name}`] protocol to `xstd::rpc::serve`, generated by \
+         `xstd::rpc`."
+    );
+
+    let stubs = methods
+        .iter()
+        .enumerate()
+        .map(|(index, method)| client_stub(index, method));
+    let arms = methods
+        .iter()
+        .enumerate()
+        .map(|(index, method)| server_arm(index, method));
+
+    Ok(quote! {
+        #item
+
+        #[doc = #client_doc]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #vis struct #client {
+            handle: usize,
+        }
+
+        impl #client {
+            /// Creates a client that sends its calls through the connection
+            /// identified by the given handle.
+            #[must_use]
+            #vis const fn new(handle: usize) -> Self {
+                Self { handle }
+            }
+
+            /// Returns the handle of the connection used by the client.
+            #[must_use]
+            #vis const fn handle(&self) -> usize {
+                self.handle
+            }
+
+            #(#stubs)*
+        }
+
+        #[doc = #server_doc]
+        #[derive(Debug)]
+        #vis struct #server<T: #name>(pub T);
+
+        impl<T: #name> ::xstd::rpc::Dispatch for #server<T> {
+            fn dispatch(
+                &mut self,
+                method: usize,
+                request: &mut ::xstd::rpc::Decoder<'_>,
+                reply: &mut ::xstd::rpc::Encoder,
+            ) -> ::core::result::Result<(), ::xstd::rpc::Status> {
+                match method {
+                    #(#arms)*
+                    _ => ::core::result::Result::Err(::xstd::rpc::Status::UnknownMethod),
+                }
+            }
+        }
+    })
+}
+
+/// Checks that the given method can be called remotely and determines how
+/// its arguments and return value are encoded.
+fn parse_method(item: &TraitItemFn) -> syn::Result<Method<'_>> {
+    let signature = &item.sig;
+    if signature.asyncness.is_some() || !signature.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            signature.span(),
+            "RPC methods cannot be async or generic",
+        ));
+    }
+    if !matches!(signature.inputs.first(), Some(FnArg::Receiver(_))) {
+        return Err(syn::Error::new(
+            signature.span(),
+            "RPC methods must take `&self` or `&mut self`",
+        ));
+    }
+
+    let count = signature.inputs.len() - 1;
+    let mut args = Vec::with_capacity(count);
+    for (index, input) in signature.inputs.iter().skip(1).enumerate() {
+        let FnArg::Typed(arg) = input else {
+            unreachable!("Only the first argument can be a receiver");
+        };
+        let Pat::Ident(pattern) = &*arg.pat else {
+            return Err(syn::Error::new(
+                arg.pat.span(),
+                "RPC arguments must be named",
+            ));
+        };
+
+        let encoding = if is_byte_slice(&arg.ty) {
+            if index + 1 != count {
+                return Err(syn::Error::new(
+                    arg.ty.span(),
+                    "A `&[u8]` argument must be the last argument",
+                ));
+            }
+            Encoding::Bytes
+        } else {
+            Encoding::Value
+        };
+        args.push((&pattern.ident, &*arg.ty, encoding));
+    }
+
+    let output = match &signature.output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) if is_byte_vec(ty) => Some(Encoding::Bytes),
+        ReturnType::Type(..) => Some(Encoding::Value),
+    };
+
+    Ok(Method { item, args, output })
+}
+
+/// Generates the method of the client that calls the given method.
+fn client_stub(index: usize, method: &Method<'_>) -> TokenStream {
+    let docs = method
+        .item
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"));
+    let name = &method.item.sig.ident;
+    let params = method.args.iter().map(|(name, ty, _)| quote!(#name: #ty));
+    let encode = method
+        .args
+        .iter()
+        .map(|(name, _, encoding)| match encoding {
+            Encoding::Value => quote!(request.value(&#name)?;),
+            Encoding::Bytes => quote!(request.bytes(#name)?;),
+        });
+
+    let (output, decode) = match (&method.item.sig.output, &method.output) {
+        (ReturnType::Type(_, ty), Some(Encoding::Value)) => (
+            quote!(#ty),
+            quote! {
+                ::xstd::rpc::Decoder::new(&reply.payload[..reply.payload_len])
+                    .value()
+                    .map_err(|_| ::xstd::rpc::Error::BadReply)
+            },
+        ),
+        (ReturnType::Type(_, ty), Some(Encoding::Bytes)) => (
+            quote!(#ty),
+            quote!(::core::result::Result::Ok(
+                reply.payload[..reply.payload_len].to_vec()
+            )),
+        ),
+        _ => (quote!(()), quote!(::core::result::Result::Ok(()))),
+    };
+    let reply = if method.output.is_some() {
+        quote!(reply)
+    } else {
+        quote!(_)
+    };
+    let mutability = if method.args.is_empty() {
+        quote!()
+    } else {
+        quote!(mut)
+    };
+
+    quote! {
+        #(#docs)*
+        ///
+        /// # Errors
+        /// Returns an error if the request could not be sent, if the service
+        /// rejected it or if the reply could not be decoded.
+        pub fn #name(&self, #(#params),*) -> ::core::result::Result<#output, ::xstd::rpc::Error> {
+            let #mutability request = ::xstd::rpc::Encoder::new();
+            #(#encode)*
+            let #reply = ::xstd::rpc::call(self.handle, #index, &request)?;
+            #decode
+        }
+    }
+}
+
+/// Generates the arm of the server dispatch that calls the given method.
+fn server_arm(index: usize, method: &Method<'_>) -> TokenStream {
+    let name = &method.item.sig.ident;
+    let names = method.args.iter().map(|(name, _, _)| name);
+    let decode = method
+        .args
+        .iter()
+        .map(|(name, ty, encoding)| match encoding {
+            Encoding::Value => quote!(let #name: #ty = request.value()?;),
+            Encoding::Bytes => quote!(let #name = request.bytes();),
+        });
+    let call = quote!(self.0.#name(#(#names),*));
+    let encode = match method.output {
+        Some(Encoding::Value) => quote!(reply.value(&#call)),
+        Some(Encoding::Bytes) => quote!(reply.bytes(&#call)),
+        None => quote! {
+            #call;
+            ::core::result::Result::Ok(())
+        },
+    };
+
+    quote! {
+        #index => {
+            #(#decode)*
+            #encode
+        }
+    }
+}
+
+/// Returns whether the given type is `&[u8]`.
+fn is_byte_slice(ty: &Type) -> bool {
+    let Type::Reference(reference) = ty else {
+        return false;
+    };
+    reference.mutability.is_none()
+        && matches!(&*reference.elem, Type::Slice(slice) if is_u8(&slice.elem))
+}
+
+/// Returns whether the given type is `Vec<u8>`.
+fn is_byte_vec(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return false;
+    };
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return false;
+    };
+    segment.ident == "Vec"
+        && arguments.args.len() == 1
+        && matches!(&arguments.args[0], GenericArgument::Type(ty) if is_u8(ty))
+}
+
+/// Returns whether the given type is `u8`.
+fn is_u8(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.is_ident("u8"))
+}
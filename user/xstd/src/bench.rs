@@ -0,0 +1,15 @@
+/// The name under which the `pong` benchmark service is registered. It
+/// replies to every message with an empty reply, so that the `ping` program
+/// can measure the round-trip time of IPC messages.
+pub const PONG_SERVICE_NAME: &str = "pong";
+
+/// Runs the given function the given number of times, and returns the average
+/// duration of a run in nanoseconds, measured with [`crate::vdso::now`].
+/// Returns zero if the number of runs is zero.
+pub fn average(runs: u64, mut f: impl FnMut()) -> u64 {
+    let start = crate::vdso::now();
+    for _ in 0..runs {
+        f();
+    }
+    (crate::vdso::now() - start).checked_div(runs).unwrap_or(0)
+}
@@ -0,0 +1,52 @@
+use crate::syscall;
+
+/// Writes raw bytes to the console, and returns the number of bytes written.
+/// At most [`::syscall::console::MAX_WRITE_SIZE`] bytes are written at once.
+/// Most programs should use the buffered output of [`crate::io`] instead.
+///
+/// # Errors
+/// Returns a [`WriteError`] if the syscall fails, most notably if the task is
+/// not allowed to use the console.
+pub fn write(bytes: &[u8]) -> Result<usize, ::syscall::console::WriteError> {
+    let args = ::syscall::args::ConsoleWrite {
+        buffer: bytes.as_ptr(),
+        len: bytes.len().min(::syscall::console::MAX_WRITE_SIZE),
+    };
+
+    // SAFETY: The buffer is valid for reads during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
+
+/// Reads the bytes available from the console into the given buffer without
+/// blocking, and returns the number of bytes read. This returns 0 if no input
+/// is available.
+///
+/// # Errors
+/// Returns a [`ReadError`] if the syscall fails, most notably if the task is
+/// not allowed to use the console.
+pub fn read(buf: &mut [u8]) -> Result<usize, ::syscall::console::ReadError> {
+    let args = ::syscall::args::ConsoleRead {
+        buffer: buf.as_mut_ptr(),
+        capacity: buf.len(),
+    };
+
+    // SAFETY: The buffer is valid for writes during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
+
+/// Reads the bytes available from the console into the given buffer, waiting
+/// until at least one byte is available, and returns the number of bytes
+/// read. This returns 0 immediately if the buffer is empty.
+///
+/// # Errors
+/// Returns a [`ReadError`] if the syscall fails, most notably if the task is
+/// not allowed to use the console.
+pub fn read_wait(buf: &mut [u8]) -> Result<usize, ::syscall::console::ReadError> {
+    let args = ::syscall::args::ConsoleReadWait {
+        buffer: buf.as_mut_ptr(),
+        capacity: buf.len(),
+    };
+
+    // SAFETY: The buffer is valid for writes during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
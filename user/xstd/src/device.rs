@@ -0,0 +1,138 @@
+use crate::syscall;
+use alloc::{vec, vec::Vec};
+use core::mem::MaybeUninit;
+
+pub use ::syscall::device::{
+    Device, DeviceKind, MapError, QueryError, TreeQueryError, VIRTIO_BLOCK,
+};
+
+/// Returns the description of the device with the given index. Devices are
+/// indexed from 0, so all the devices can be enumerated by querying increasing
+/// indexes until [`QueryError::NotFound`] is returned.
+///
+/// # Errors
+/// Returns a [`QueryError`] describing the error if the syscall fails.
+pub fn query(device: usize) -> Result<Device, QueryError> {
+    let mut info = MaybeUninit::<Device>::uninit();
+    let args = ::syscall::args::DeviceQuery {
+        device,
+        info: info.as_mut_ptr(),
+    };
+
+    // SAFETY: The buffer is valid for writes during the whole syscall.
+    syscall::decode::<QueryError>(unsafe { syscall::invoke(&args) })?;
+
+    // SAFETY: The syscall succeeded, so the description was written by the
+    // kernel.
+    Ok(unsafe { info.assume_init() })
+}
+
+/// Returns an iterator over the devices of the system, along with their
+/// index.
+pub fn list() -> impl Iterator<Item = (usize, Device)> {
+    (0..).map_while(|index| query(index).ok().map(|device| (index, device)))
+}
+
+/// Maps the registers of the device with the given index at the given
+/// page-aligned address, and returns a pointer to the start of the mapping.
+/// The device is claimed by the current task until it terminates.
+///
+/// # Errors
+/// Returns a [`MapError`] describing the error if the syscall fails, most
+/// notably if the device is already driven by another task.
+pub fn map(device: usize, address: usize) -> Result<*mut u8, MapError> {
+    let args = ::syscall::args::MemMapDevice { device, address };
+
+    // SAFETY: The syscall only maps new pages, and fails if any page of the
+    // range is already mapped, so existing memory is never affected.
+    let address = syscall::decode::<MapError>(unsafe { syscall::invoke(&args) })?;
+    Ok(core::ptr::with_exposed_provenance_mut(address))
+}
+
+/// Returns the raw value of the property with the given name, in the node of
+/// the device tree with the given path, such as `/soc/serial@10000000`. The
+/// unit address of a node can be omitted to find the first node with that
+/// name, and the aliases of the `/aliases` node are resolved as well.
+///
+/// # Errors
+/// Returns a [`TreeQueryError`] describing the error if the syscall fails,
+/// most notably if the node or the property does not exist.
+pub fn tree_property(path: &str, name: &str) -> Result<Vec<u8>, TreeQueryError> {
+    let query = |buffer: &mut [u8]| {
+        let args = ::syscall::args::DeviceTreeQuery {
+            path: path.as_ptr(),
+            path_len: path.len(),
+            property: name.as_ptr(),
+            property_len: name.len(),
+            buffer: buffer.as_mut_ptr(),
+            capacity: buffer.len(),
+        };
+
+        // SAFETY: The path and the name are valid for reads, and the buffer
+        // is valid for writes of its length during the whole syscall.
+        syscall::decode::<TreeQueryError>(unsafe { syscall::invoke(&args) })
+    };
+
+    // The device tree never changes, so the length returned by the first
+    // query is the one of the value copied by the second one.
+    let mut value = vec![0; query(&mut [])?];
+    query(&mut value)?;
+    Ok(value)
+}
+
+/// Returns the value of the given property of the given node of the device
+/// tree as a list of 32-bit cells, such as the `interrupts` property whose
+/// meaning depends on the interrupt controller. Trailing bytes that do not
+/// form a whole cell are ignored.
+///
+/// # Errors
+/// Returns a [`TreeQueryError`] describing the error if the property cannot be
+/// read, as with [`tree_property`].
+pub fn tree_cells(path: &str, name: &str) -> Result<Vec<u32>, TreeQueryError> {
+    Ok(tree_property(path, name)?
+        .chunks_exact(4)
+        .map(|cell| u32::from_be_bytes([cell[0], cell[1], cell[2], cell[3]]))
+        .collect())
+}
+
+/// Returns the `(address, size)` pairs of the `reg` property of the node of
+/// the device tree with the given path, decoded with the `#address-cells` and
+/// `#size-cells` properties of its parent. The path must be a full path, not
+/// an alias, since the parent is found by removing its last component.
+/// Addresses are not translated through the `ranges` of the parents, which
+/// are identity mappings on the supported platforms.
+///
+/// # Errors
+/// Returns a [`TreeQueryError`] describing the error if the property cannot be
+/// read, as with [`tree_property`].
+pub fn tree_reg(path: &str) -> Result<Vec<(u64, u64)>, TreeQueryError> {
+    let parent = match path.trim_end_matches('/').rsplit_once('/') {
+        Some(("", _)) | None => "/",
+        Some((parent, _)) => parent,
+    };
+    let cells = |name, default| {
+        tree_cells(parent, name)
+            .ok()
+            .and_then(|cells| cells.first().copied())
+            .map_or(default, |cells| cells as usize)
+    };
+    let address_cells = cells("#address-cells", 2);
+    let size_cells = cells("#size-cells", 1);
+
+    let combine = |cells: &[u32]| {
+        cells
+            .iter()
+            .fold(0u64, |value, &cell| (value << 32) | u64::from(cell))
+    };
+    let reg = tree_cells(path, "reg")?;
+    if address_cells + size_cells == 0 {
+        return Ok(Vec::new());
+    }
+    Ok(reg
+        .chunks_exact(address_cells + size_cells)
+        .map(|entry| {
+            let (address, size) = entry.split_at(address_cells);
+            (combine(address), combine(size))
+        })
+        .collect())
+}
@@ -0,0 +1,12 @@
+use alloc::vec::Vec;
+
+/// The name under which the echo service is registered.
+pub const SERVICE_NAME: &str = "echo";
+
+/// The protocol of the echo service, which sends back what it receives. It is
+/// mostly used to check that IPC works.
+#[crate::rpc]
+pub trait Echo {
+    /// Returns the given bytes unchanged.
+    fn echo(&mut self, bytes: &[u8]) -> Vec<u8>;
+}
@@ -0,0 +1,91 @@
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use ::syscall::env::Block;
+use alloc::vec;
+
+/// The address of the block of arguments and environment variables given by
+/// the kernel, or null if the block was invalid.
+static BLOCK: AtomicPtr<u8> = AtomicPtr::new(core::ptr::null_mut());
+
+/// The size of the block, in bytes.
+static BLOCK_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Records the block of arguments and environment variables given by the
+/// kernel to the task. This is called by the entry point generated by
+/// [`crate::main`] before the main function of the task, and should not be
+/// called anywhere else.
+///
+/// # Safety
+/// The pointer must be the one given by the kernel to the entry point of the
+/// task, and the block must never be modified afterwards.
+#[doc(hidden)]
+pub unsafe fn init(block: *const u8) {
+    if block.is_null() {
+        return;
+    }
+
+    // SAFETY: The kernel always gives a block starting with a valid header,
+    // which is then fully validated before being used.
+    let size = unsafe { block.cast::<u64>().read_unaligned() };
+    let Ok(size) = usize::try_from(size) else {
+        return;
+    };
+    if size > ::syscall::env::MAX_BLOCK_SIZE {
+        return;
+    }
+
+    // SAFETY: The kernel copies the whole block on the stack, above the
+    // initial stack pointer, where it is never overwritten.
+    let bytes = unsafe { core::slice::from_raw_parts(block, size) };
+    if Block::new(bytes).is_some() {
+        BLOCK_SIZE.store(size, Ordering::Relaxed);
+        BLOCK.store(block.cast_mut(), Ordering::Release);
+    }
+}
+
+/// Returns the block of arguments and environment variables of the task, or
+/// `None` if the kernel did not give a valid block.
+fn block() -> Option<Block<'static>> {
+    let block = BLOCK.load(Ordering::Acquire);
+    if block.is_null() {
+        return None;
+    }
+
+    // SAFETY: The block was validated in `init` and is never modified.
+    let bytes = unsafe { core::slice::from_raw_parts(block, BLOCK_SIZE.load(Ordering::Relaxed)) };
+    Block::new(bytes)
+}
+
+/// Returns an iterator over the arguments of the task. By convention, the
+/// first argument is the name of the program.
+pub fn args() -> impl Iterator<Item = &'static str> {
+    block().into_iter().flat_map(Block::args)
+}
+
+/// Returns an iterator over the environment variables of the task, as
+/// `(key, value)` pairs. Variables without a `=` are returned with an empty
+/// value.
+pub fn vars() -> impl Iterator<Item = (&'static str, &'static str)> {
+    block()
+        .into_iter()
+        .flat_map(Block::vars)
+        .map(|var| var.split_once('=').unwrap_or((var, "")))
+}
+
+/// Returns the value of the environment variable with the given key, or
+/// `None` if the variable is not set.
+#[must_use]
+pub fn var(key: &str) -> Option<&'static str> {
+    vars()
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| value)
+}
+
+/// Encodes the given arguments and environment variables into a block that
+/// can be given to a new task.
+#[must_use]
+pub fn encode(args: &[&str], vars: &[&str]) -> alloc::vec::Vec<u8> {
+    let mut block = vec![0; ::syscall::env::encoded_size(args, vars)];
+    _ = ::syscall::env::encode(&mut block, args, vars);
+    block
+}
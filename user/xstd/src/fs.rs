@@ -0,0 +1,223 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use ::syscall::{
+    fs::{
+        HandleRequest, MAX_READ_SIZE, OpenRequest, Operation, Payload, ReadRequest, WriteRequest,
+    },
+    ipc::{Reply, SendError},
+    service::ConnectionError,
+};
+use alloc::vec::Vec;
+
+pub use ::syscall::fs::{FileError, FileKind, Metadata, OpenFlags, SERVICE_NAME};
+
+/// The value of [`CONNECTION`] when the task is not connected to the file
+/// server yet.
+const NOT_CONNECTED: usize = usize::MAX;
+
+/// The handle of the connection to the file server, shared by all the files
+/// of the task.
+static CONNECTION: AtomicUsize = AtomicUsize::new(NOT_CONNECTED);
+
+/// An error that can occur while using a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The task could not connect to the file server, most likely because it
+    /// is not started yet.
+    Connection(ConnectionError),
+
+    /// The request could not be sent to the file server, or the file server
+    /// terminated before replying to it.
+    Send(SendError),
+
+    /// The file server rejected the request.
+    File(FileError),
+}
+
+impl From<ConnectionError> for Error {
+    fn from(error: ConnectionError) -> Self {
+        Self::Connection(error)
+    }
+}
+
+impl From<SendError> for Error {
+    fn from(error: SendError) -> Self {
+        Self::Send(error)
+    }
+}
+
+impl From<FileError> for Error {
+    fn from(error: FileError) -> Self {
+        Self::File(error)
+    }
+}
+
+/// A file opened on the file server registered under
+/// [`::syscall::fs::SERVICE_NAME`]. The file keeps track of the current
+/// offset, which is advanced by reads and writes. The file is closed when
+/// dropped.
+#[derive(Debug)]
+pub struct File {
+    handle: u64,
+    offset: u64,
+}
+
+impl File {
+    /// Opens the file at the given absolute path with the given flags.
+    ///
+    /// # Errors
+    /// Returns an [`Error`] if the file server cannot be reached, or if it
+    /// refused to open the file. A path longer than
+    /// [`::syscall::fs::MAX_PATH_LEN`] is reported as a
+    /// [`FileError::BadRequest`].
+    pub fn open(path: &str, flags: OpenFlags) -> Result<Self, Error> {
+        let request = OpenRequest::new(path, flags).ok_or(FileError::BadRequest)?;
+        let (handle, _) = send(Operation::Open, request.as_payload())?;
+        Ok(Self {
+            handle: handle as u64,
+            offset: 0,
+        })
+    }
+
+    /// Opens the file at the given absolute path for writing, creating it if
+    /// it does not exist and truncating it otherwise.
+    ///
+    /// # Errors
+    /// See [`File::open`].
+    pub fn create(path: &str) -> Result<Self, Error> {
+        Self::open(
+            path,
+            OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE,
+        )
+    }
+
+    /// Reads data from the current offset of the file into the given buffer,
+    /// and returns the number of bytes read. At most
+    /// [`::syscall::fs::MAX_READ_SIZE`] bytes are read at once, and 0 is
+    /// returned at the end of the file.
+    ///
+    /// # Errors
+    /// Returns an [`Error`] if the request failed.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let request = ReadRequest {
+            handle: self.handle,
+            offset: self.offset,
+            len: buf.len().min(MAX_READ_SIZE) as u64,
+        };
+        let (len, reply) = send(Operation::Read, request.as_payload())?;
+        let len = len.min(reply.payload_len).min(buf.len());
+        buf[..len].copy_from_slice(&reply.payload[..len]);
+        self.offset += len as u64;
+        Ok(len)
+    }
+
+    /// Reads all the data from the current offset to the end of the file,
+    /// and appends it to the given vector. Returns the number of bytes read.
+    ///
+    /// # Errors
+    /// Returns an [`Error`] if a request failed, in which case the data read
+    /// before the failure is kept in the vector.
+    pub fn read_to_end(&mut self, data: &mut Vec<u8>) -> Result<usize, Error> {
+        let mut buf = [0; MAX_READ_SIZE];
+        let mut total = 0;
+        loop {
+            let len = self.read(&mut buf)?;
+            if len == 0 {
+                return Ok(total);
+            }
+            data.extend_from_slice(&buf[..len]);
+            total += len;
+        }
+    }
+
+    /// Writes the given data at the current offset of the file, and returns
+    /// the number of bytes written. At most [`::syscall::fs::MAX_WRITE_SIZE`]
+    /// bytes are written at once.
+    ///
+    /// # Errors
+    /// Returns an [`Error`] if the request failed.
+    pub fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
+        let request = WriteRequest::new(self.handle, self.offset, data);
+        let (len, _) = send(Operation::Write, request.as_payload())?;
+        self.offset += len as u64;
+        Ok(len)
+    }
+
+    /// Writes all the given data at the current offset of the file, sending
+    /// as many requests as needed.
+    ///
+    /// # Errors
+    /// Returns an [`Error`] if a request failed, in which case the data may
+    /// have been partially written.
+    pub fn write_all(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        while !data.is_empty() {
+            let len = self.write(data)?;
+            if len == 0 {
+                return Err(Error::File(FileError::Unknown));
+            }
+            data = &data[len.min(data.len())..];
+        }
+        Ok(())
+    }
+
+    /// Sets the offset of the next read or write. The offset can be past the
+    /// end of the file.
+    pub fn seek(&mut self, offset: u64) {
+        self.offset = offset;
+    }
+
+    /// Returns the metadata of the file.
+    ///
+    /// # Errors
+    /// Returns an [`Error`] if the request failed.
+    pub fn metadata(&self) -> Result<Metadata, Error> {
+        let request = HandleRequest {
+            handle: self.handle,
+        };
+        let (_, reply) = send(Operation::Stat, request.as_payload())?;
+        let payload = &reply.payload[..reply.payload_len.min(reply.payload.len())];
+        Ok(Metadata::from_payload(payload).ok_or(FileError::Unknown)?)
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        let request = HandleRequest {
+            handle: self.handle,
+        };
+        _ = send(Operation::Close, request.as_payload());
+    }
+}
+
+/// Sends a request to the file server, connecting to it first if needed, and
+/// returns the value of the status of the reply along with the reply.
+fn send(operation: Operation, payload: &[u8]) -> Result<(usize, Reply), Error> {
+    let reply = crate::ipc::send(connection()?, operation as usize, payload)?;
+    let value = ::syscall::fs::decode_status(reply.status)?;
+    Ok((value, reply))
+}
+
+/// Returns the handle of the connection to the file server, connecting to it
+/// if this is the first request of the task.
+fn connection() -> Result<usize, Error> {
+    let connection = CONNECTION.load(Ordering::Relaxed);
+    if connection != NOT_CONNECTED {
+        return Ok(connection);
+    }
+
+    // Two threads may connect at the same time, in which case the connection
+    // of the thread that loses the race is closed.
+    let connection = crate::service::connect(::syscall::fs::SERVICE_NAME)?;
+    match CONNECTION.compare_exchange(
+        NOT_CONNECTED,
+        connection,
+        Ordering::Relaxed,
+        Ordering::Relaxed,
+    ) {
+        Ok(_) => Ok(connection),
+        Err(existing) => {
+            _ = crate::service::disconnect(connection);
+            Ok(existing)
+        }
+    }
+}
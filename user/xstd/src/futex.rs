@@ -0,0 +1,37 @@
+use crate::syscall;
+use core::sync::atomic::AtomicU32;
+
+pub use ::syscall::futex::{WaitError, WakeError};
+
+/// Blocks until another task wakes up the tasks sleeping on the given word
+/// with [`wake`], if the word holds the expected value. This may also return
+/// spuriously, so the caller must check the word again after returning.
+///
+/// # Errors
+/// Returns [`WaitError::ValueMismatch`] if the word does not hold the expected
+/// value, in which case the task did not sleep.
+pub fn wait(word: &AtomicU32, expected: u32) -> Result<(), WaitError> {
+    let args = ::syscall::args::FutexWait {
+        address: word.as_ptr().cast_const(),
+        expected,
+    };
+
+    // SAFETY: The word is a valid reference, which the kernel only reads.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Wakes up at most `count` tasks sleeping on the given word, and returns the
+/// number of tasks woken up.
+///
+/// # Errors
+/// Returns a [`WakeError`] if the syscall fails, which should not happen for
+/// a valid reference.
+pub fn wake(word: &AtomicU32, count: usize) -> Result<usize, WakeError> {
+    let args = ::syscall::args::FutexWake {
+        address: word.as_ptr().cast_const(),
+        count,
+    };
+
+    // SAFETY: The word is a valid reference, which the kernel only reads.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
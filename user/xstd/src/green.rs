@@ -0,0 +1,141 @@
+use crate::vdso;
+
+/// The number of registers that must be preserved across a function call
+/// besides the return address and the stack pointer: `s0`-`s11` on riscv64,
+/// and `x19`-`x29` followed by `d8`-`d15` on aarch64.
+///
+/// The `fs0`-`fs11` registers are not saved on riscv64 even though the
+/// calling convention requires it: the kernel never enables the FPU for user
+/// threads (`sstatus.FS` is always off), so floating point is unsupported in
+/// user space on riscv64 and any floating point instruction, including the
+/// ones that would save these registers, is reported as an illegal
+/// instruction.
+#[cfg(target_arch = "riscv64")]
+const SAVED_REGISTERS: usize = 12;
+#[cfg(target_arch = "aarch64")]
+const SAVED_REGISTERS: usize = 19;
+
+/// The execution context of a green thread. It only contains the registers
+/// that must be preserved across a function call according to the calling
+/// convention of the architecture, since the switch is always done through a
+/// regular function call. It also contains the bounds of the stack used by
+/// the green thread, that are published to the kernel before switching to it.
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct Context {
+    ra: usize,
+    sp: usize,
+    saved: [usize; SAVED_REGISTERS],
+    stack_low: usize,
+    stack_high: usize,
+}
+
+impl Context {
+    /// Creates a new context that will start executing `entry` on the given
+    /// stack when switched to. The entry function must never return since
+    /// there is no caller to return to.
+    #[must_use]
+    pub fn new(stack: &'static mut [u8], entry: extern "C" fn() -> !) -> Self {
+        let low = stack.as_mut_ptr() as usize;
+        let high = low + stack.len();
+        Self {
+            ra: entry as usize,
+            sp: high & !0xF,
+            saved: [0; SAVED_REGISTERS],
+            stack_low: low,
+            stack_high: high,
+        }
+    }
+}
+
+/// Saves the current execution context into `from` and resumes the execution
+/// of the `to` context. This function returns when another green thread
+/// switches back to the `from` context.
+///
+/// Before switching, the stack bounds of the `to` context are published in
+/// the shared page so that the kernel can report stack overflows of the green
+/// thread. A context created with [`Context::default`] represents the stack
+/// provided by the kernel.
+///
+/// # Safety
+/// The `to` context must either have been created with [`Context::new`] and
+/// never been switched to, or have been saved by a previous call to this
+/// function. The stack of the `to` context must still be valid.
+pub unsafe fn switch(from: &mut Context, to: &Context) {
+    vdso::set_stack_extent(to.stack_low, to.stack_high);
+
+    // SAFETY: The caller guarantees that the `to` context is valid.
+    unsafe {
+        green_switch(from, to);
+    }
+}
+
+unsafe extern "C" {
+    fn green_switch(from: *mut Context, to: *const Context);
+}
+
+#[cfg(target_arch = "riscv64")]
+core::arch::global_asm!(
+    ".global green_switch",
+    "green_switch:",
+    "   sd ra, 0(a0)",
+    "   sd sp, 8(a0)",
+    "   sd s0, 16(a0)",
+    "   sd s1, 24(a0)",
+    "   sd s2, 32(a0)",
+    "   sd s3, 40(a0)",
+    "   sd s4, 48(a0)",
+    "   sd s5, 56(a0)",
+    "   sd s6, 64(a0)",
+    "   sd s7, 72(a0)",
+    "   sd s8, 80(a0)",
+    "   sd s9, 88(a0)",
+    "   sd s10, 96(a0)",
+    "   sd s11, 104(a0)",
+    "   ld ra, 0(a1)",
+    "   ld sp, 8(a1)",
+    "   ld s0, 16(a1)",
+    "   ld s1, 24(a1)",
+    "   ld s2, 32(a1)",
+    "   ld s3, 40(a1)",
+    "   ld s4, 48(a1)",
+    "   ld s5, 56(a1)",
+    "   ld s6, 64(a1)",
+    "   ld s7, 72(a1)",
+    "   ld s8, 80(a1)",
+    "   ld s9, 88(a1)",
+    "   ld s10, 96(a1)",
+    "   ld s11, 104(a1)",
+    "   ret",
+);
+
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(
+    ".global green_switch",
+    "green_switch:",
+    "   mov x9, sp",
+    "   stp x30, x9, [x0, #0]",
+    "   stp x19, x20, [x0, #16]",
+    "   stp x21, x22, [x0, #32]",
+    "   stp x23, x24, [x0, #48]",
+    "   stp x25, x26, [x0, #64]",
+    "   stp x27, x28, [x0, #80]",
+    "   str x29, [x0, #96]",
+    "   stp d8, d9, [x0, #104]",
+    "   stp d10, d11, [x0, #120]",
+    "   stp d12, d13, [x0, #136]",
+    "   stp d14, d15, [x0, #152]",
+    "   ldp x30, x9, [x1, #0]",
+    "   mov sp, x9",
+    "   ldp x19, x20, [x1, #16]",
+    "   ldp x21, x22, [x1, #32]",
+    "   ldp x23, x24, [x1, #48]",
+    "   ldp x25, x26, [x1, #64]",
+    "   ldp x27, x28, [x1, #80]",
+    "   ldr x29, [x1, #96]",
+    "   ldp d8, d9, [x1, #104]",
+    "   ldp d10, d11, [x1, #120]",
+    "   ldp d12, d13, [x1, #136]",
+    "   ldp d14, d15, [x1, #152]",
+    "   ret",
+);
@@ -0,0 +1,225 @@
+use crate::{
+    memory::{self, Protection},
+    vdso,
+};
+use core::{
+    alloc::{GlobalAlloc, Layout},
+    cell::UnsafeCell,
+    ptr::NonNull,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// The maximum size of the heap.
+pub const HEAP_MAX_SIZE: usize = 0x0000_0010_0000_0000;
+
+/// The minimum number of bytes by which the heap is grown when it runs out of
+/// free memory, to avoid invoking the kernel for every small allocation.
+const GROWTH_SIZE: usize = 64 * 1024;
+
+/// The size of a page, which is the granularity of the memory mappings.
+const PAGE_SIZE: usize = 4096;
+
+/// The granularity of the allocations. Every block is a multiple of this size
+/// and starts at an address aligned to it, so that any free block is large
+/// enough to hold a [`FreeBlock`] header.
+const UNIT: usize = 16;
+
+#[global_allocator]
+static ALLOCATOR: Heap = Heap {
+    locked: AtomicBool::new(false),
+    inner: UnsafeCell::new(Inner {
+        free: None,
+        start: 0,
+        end: 0,
+    }),
+};
+
+/// The header of a free block, stored at the start of the block itself.
+struct FreeBlock {
+    /// The size of the block, in bytes, including this header.
+    size: usize,
+
+    /// The next free block, at a higher address.
+    next: Option<NonNull<FreeBlock>>,
+}
+
+/// The state of the heap.
+struct Inner {
+    /// The free block with the lowest address. Free blocks are kept sorted by
+    /// address so that adjacent blocks can be merged when memory is freed.
+    free: Option<NonNull<FreeBlock>>,
+
+    /// The start of the heap, or zero until the heap is first grown.
+    start: usize,
+
+    /// The end of the memory mapped for the heap, exclusive.
+    end: usize,
+}
+
+/// The heap of the task. It is a first-fit allocator over a single region
+/// starting at the base of the anonymous mappings chosen by the kernel for
+/// the task (see [`vdso::mmap_base`]), which is grown by mapping anonymous
+/// memory at its end when no free block is large enough. Memory is never
+/// returned to the kernel.
+struct Heap {
+    locked: AtomicBool,
+    inner: UnsafeCell<Inner>,
+}
+
+/// SAFETY: The state of the heap is only accessed while holding the lock.
+unsafe impl Sync for Heap {}
+
+impl Heap {
+    /// Runs the given function with exclusive access to the state of the
+    /// heap. The lock is only contended when several threads of the task
+    /// allocate at the same time, since switching between green threads is
+    /// cooperative. Allocations are short, so waiting threads simply spin.
+    fn with<R>(&self, f: impl FnOnce(&mut Inner) -> R) -> R {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        // SAFETY: The lock is held, so no other reference to the state exists.
+        let result = f(unsafe { &mut *self.inner.get() });
+        self.locked.store(false, Ordering::Release);
+        result
+    }
+}
+
+impl Inner {
+    /// Allocates a block of `size` bytes aligned to `align`, growing the heap
+    /// if needed. Both values must be multiples of [`UNIT`].
+    fn allocate(&mut self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        if let Some(ptr) = self.take(size, align) {
+            return Some(ptr);
+        }
+        self.grow(size + align)?;
+        self.take(size, align)
+    }
+
+    /// Takes a block of `size` bytes aligned to `align` from the free list,
+    /// splitting the first free block large enough to contain it.
+    fn take(&mut self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        let mut previous: Option<NonNull<FreeBlock>> = None;
+        let mut current = self.free;
+        while let Some(block) = current {
+            // SAFETY: Blocks in the free list are valid free blocks.
+            let FreeBlock {
+                size: available,
+                next,
+            } = unsafe { block.read() };
+            let base = block.addr().get();
+            let start = base.next_multiple_of(align);
+            let padding = start - base;
+
+            if padding + size <= available {
+                // Unlink the block, then put back the parts before and after
+                // the allocation. Since everything is a multiple of `UNIT`,
+                // these parts are either empty or large enough for a header.
+                match previous {
+                    // SAFETY: The previous block is a valid free block.
+                    Some(previous) => unsafe { (*previous.as_ptr()).next = next },
+                    None => self.free = next,
+                }
+                if padding > 0 {
+                    self.release(base, padding);
+                }
+                let remaining = available - padding - size;
+                if remaining > 0 {
+                    self.release(start + size, remaining);
+                }
+                return NonNull::new(core::ptr::with_exposed_provenance_mut(start));
+            }
+
+            previous = current;
+            current = next;
+        }
+        None
+    }
+
+    /// Maps at least `size` more bytes at the end of the heap and adds them
+    /// to the free list.
+    fn grow(&mut self, size: usize) -> Option<()> {
+        if self.start == 0 {
+            self.start = vdso::mmap_base();
+            self.end = self.start;
+        }
+
+        let size = size.max(GROWTH_SIZE).next_multiple_of(PAGE_SIZE);
+        if self.end + size > self.start + HEAP_MAX_SIZE {
+            return None;
+        }
+
+        let start = memory::map(self.end, size, Protection::READ_WRITE).ok()?;
+        self.end += size;
+        self.release(start.expose_provenance(), size);
+        Some(())
+    }
+
+    /// Adds the block of `size` bytes at `address` to the free list, merging
+    /// it with the adjacent free blocks.
+    fn release(&mut self, address: usize, size: usize) {
+        let block = core::ptr::with_exposed_provenance_mut::<FreeBlock>(address);
+        let mut previous: Option<NonNull<FreeBlock>> = None;
+        let mut next = self.free;
+        while let Some(current) = next {
+            if current.addr().get() > address {
+                break;
+            }
+            previous = next;
+            // SAFETY: Blocks in the free list are valid free blocks.
+            next = unsafe { current.as_ref().next };
+        }
+
+        // SAFETY: The block is unused memory of the heap, large enough and
+        // aligned enough for a header, and the blocks in the free list are
+        // valid free blocks that do not overlap it.
+        unsafe {
+            block.write(FreeBlock { size, next });
+            if let Some(next) = next
+                && address + size == next.addr().get()
+            {
+                (*block).size += next.as_ref().size;
+                (*block).next = next.as_ref().next;
+            }
+
+            match previous {
+                Some(mut previous) => {
+                    let previous = previous.as_mut();
+                    let end = core::ptr::from_mut(previous).addr() + previous.size;
+                    if end == address {
+                        previous.size += (*block).size;
+                        previous.next = (*block).next;
+                    } else {
+                        previous.next = NonNull::new(block);
+                    }
+                }
+                None => self.free = NonNull::new(block),
+            }
+        }
+    }
+}
+
+/// Returns the size and alignment of the block used for the given layout.
+fn block_layout(layout: Layout) -> (usize, usize) {
+    let size = layout.size().max(1).next_multiple_of(UNIT);
+    let align = layout.align().max(UNIT);
+    (size, align)
+}
+
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (size, align) = block_layout(layout);
+        self.with(|inner| inner.allocate(size, align))
+            .map_or(core::ptr::null_mut(), NonNull::as_ptr)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = block_layout(layout);
+        self.with(|inner| inner.release(ptr.expose_provenance(), size));
+    }
+}
@@ -0,0 +1,25 @@
+use ::syscall::initrd::{INITRD_ADDRESS, INITRD_MAX_SIZE, INITRD_SIZE_VAR};
+
+pub use cpio::{Archive, Entries, Entry};
+
+/// Returns the initial ramdisk mapped by the kernel in the address space of
+/// the task, or `None` if there is none. The kernel only maps the ramdisk in
+/// the address space of `init`, which uses it to spawn the programs of the
+/// system without having to rebuild the kernel.
+#[must_use]
+pub fn archive() -> Option<Archive<'static>> {
+    let size = crate::env::var(INITRD_SIZE_VAR)?.parse::<usize>().ok()?;
+    if size > INITRD_MAX_SIZE {
+        return None;
+    }
+
+    // SAFETY: The kernel maps the ramdisk read-only at this address before
+    // the task starts when it sets the size variable, and never unmaps it.
+    let data = unsafe {
+        core::slice::from_raw_parts(
+            core::ptr::with_exposed_provenance::<u8>(INITRD_ADDRESS),
+            size,
+        )
+    };
+    Archive::new(data)
+}
@@ -0,0 +1,152 @@
+use core::{
+    cell::UnsafeCell,
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use ::syscall::console::ReadError;
+
+/// The size of the buffer of the standard output, in bytes.
+const STDOUT_BUFFER_SIZE: usize = 1024;
+
+/// The standard output of the task.
+static STDOUT: Stdout = Stdout {
+    locked: AtomicBool::new(false),
+    buffer: UnsafeCell::new(Buffer {
+        data: [0; STDOUT_BUFFER_SIZE],
+        len: 0,
+    }),
+};
+
+/// The bytes written to the standard output that were not written to the
+/// console yet.
+struct Buffer {
+    data: [u8; STDOUT_BUFFER_SIZE],
+    len: usize,
+}
+
+impl Buffer {
+    /// Writes the content of the buffer to the console and empties it. Bytes
+    /// that cannot be written are discarded, since there is nowhere else to
+    /// report the error.
+    fn flush(&mut self) {
+        let mut start = 0;
+        while start < self.len {
+            match crate::console::write(&self.data[start..self.len]) {
+                Ok(written) if written > 0 => start += written,
+                _ => break,
+            }
+        }
+        self.len = 0;
+    }
+}
+
+impl Write for Buffer {
+    /// Appends the string to the buffer, flushing it when it is full and
+    /// after each newline so that the output is line-buffered.
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for &byte in s.as_bytes() {
+            if self.len == self.data.len() {
+                self.flush();
+            }
+            self.data[self.len] = byte;
+            self.len += 1;
+            if byte == b'\n' {
+                self.flush();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The line-buffered standard output of the task, written to the console.
+struct Stdout {
+    locked: AtomicBool,
+    buffer: UnsafeCell<Buffer>,
+}
+
+/// SAFETY: The buffer is only accessed while holding the lock.
+unsafe impl Sync for Stdout {}
+
+impl Stdout {
+    /// Runs the given function with exclusive access to the buffer, or returns
+    /// `None` without running it if the buffer is already in use. This can
+    /// only happen if the task panicked while printing, or if a green thread
+    /// yielded while printing.
+    fn try_with<R>(&self, f: impl FnOnce(&mut Buffer) -> R) -> Option<R> {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        // SAFETY: The lock is held, so no other reference to the buffer exists.
+        let result = f(unsafe { &mut *self.buffer.get() });
+        self.locked.store(false, Ordering::Release);
+        Some(result)
+    }
+
+    /// Runs the given function with exclusive access to the buffer, yielding
+    /// the CPU until the buffer is available.
+    fn with<R>(&self, f: impl FnOnce(&mut Buffer) -> R) -> R {
+        let mut f = Some(f);
+        loop {
+            if let Some(result) = self.try_with(|buffer| f.take().unwrap()(buffer)) {
+                return result;
+            }
+            crate::task::yield_now();
+        }
+    }
+}
+
+/// Writes the given formatted arguments to the standard output. This is used
+/// by the [`print!`] and [`println!`] macros, and should not be called
+/// directly.
+#[doc(hidden)]
+pub fn _print(args: core::fmt::Arguments) {
+    STDOUT.with(|buffer| {
+        _ = buffer.write_fmt(args);
+    });
+}
+
+/// Writes the bytes buffered in the standard output to the console.
+pub fn flush() {
+    STDOUT.with(Buffer::flush);
+}
+
+/// Writes the bytes buffered in the standard output to the console if the
+/// standard output is not in use. This is called when the task exits, and
+/// must not block since the task may be exiting because it panicked while
+/// printing.
+pub(crate) fn flush_on_exit() {
+    _ = STDOUT.try_with(Buffer::flush);
+}
+
+/// Reads from the console into the given buffer, and returns the number of
+/// bytes read. This sleeps until at least one byte is available.
+///
+/// # Errors
+/// Returns a [`ReadError`] if the console cannot be read, most notably if the
+/// task is not allowed to use the console.
+pub fn read(buf: &mut [u8]) -> Result<usize, ReadError> {
+    crate::console::read_wait(buf)
+}
+
+/// Prints to the standard output of the task. The output is line-buffered:
+/// it is written to the console after each newline, and when the task exits.
+/// Use [`crate::io::flush`] to write a partial line immediately.
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => {
+        $crate::io::_print(format_args!($($arg)*))
+    };
+}
+
+/// Prints to the standard output of the task, with a newline. See
+/// [`print!`] for more details.
+#[macro_export]
+macro_rules! println {
+    () => {
+        $crate::print!("\n")
+    };
+    ($($arg:tt)*) => {
+        $crate::io::_print(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
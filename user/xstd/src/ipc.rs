@@ -1,115 +1,132 @@
 use core::mem::MaybeUninit;
 
-use crate::syscall::{self, SyscallCode};
-
-impl SyscallCode for ::syscall::ipc::SendError {
-    fn from_syscall_code(code: isize) -> Self {
-        match -code {
-            1 => ::syscall::ipc::SendError::InvalidDestination,
-            2 => ::syscall::ipc::SendError::BadMessage,
-            3 => ::syscall::ipc::SendError::PayloadTooLarge,
-            _ => ::syscall::ipc::SendError::Unknown,
-        }
-    }
-}
-
-impl SyscallCode for ::syscall::ipc::ReceiveError {
-    fn from_syscall_code(code: isize) -> Self {
-        match -code {
-            1 => ::syscall::ipc::ReceiveError::BadBuffer,
-            _ => ::syscall::ipc::ReceiveError::Unknown,
-        }
-    }
-}
+use crate::syscall;
 
-impl SyscallCode for ::syscall::ipc::ReplyError {
-    fn from_syscall_code(code: isize) -> Self {
-        match -code {
-            1 => ::syscall::ipc::ReplyError::InvalidDestination,
-            2 => ::syscall::ipc::ReplyError::BadMessage,
-            3 => ::syscall::ipc::ReplyError::PayloadTooLarge,
-            4 => ::syscall::ipc::ReplyError::NotWaitingForReply,
-            _ => ::syscall::ipc::ReplyError::Unknown,
-        }
-    }
-}
+pub use ::syscall::ipc::SendError;
 
-/// Sends an IPC message to the specified receiver task ID, and blocks until
-/// until a reply is received.
+/// Sends an IPC message through the connection identified by the given handle,
+/// and blocks until a reply is received. The handle must have been obtained
+/// with [`crate::service::connect`].
 ///
 /// # Errors
-/// Returns an [`IpcSendError`] describing the error if the syscall fails.
+/// Returns a [`SendError`] describing the error if the syscall fails.
 pub fn send(
-    receiver: usize,
+    handle: usize,
     kind: usize,
     payload: &[u8],
 ) -> Result<::syscall::ipc::Reply, ::syscall::ipc::SendError> {
     let mut message = ::syscall::ipc::Message {
         sender: 0,
-        receiver,
+        receiver: handle,
         kind,
         payload_len: payload.len(),
+        sender_info: ::syscall::ipc::SenderInfo::default(),
         payload: [0u8; ::syscall::ipc::MAX_PAYLOAD_SIZE],
     };
     let mut reply = MaybeUninit::<::syscall::ipc::Reply>::uninit();
-    let ret;
 
     message.payload[..payload.len().min(::syscall::ipc::MAX_PAYLOAD_SIZE)]
         .copy_from_slice(&payload[..payload.len().min(::syscall::ipc::MAX_PAYLOAD_SIZE)]);
 
-    unsafe {
-        core::arch::asm!("ecall",
-            in("a7") 6,             // syscall number for ipc_send
-            in("a0") &message,      // pointer to the message
-            in("a1") &mut reply,    // pointer to the reply
-            lateout("a0") ret,      // return value
-            options(nostack, preserves_flags)
-        );
-    }
+    let args = ::syscall::args::IpcSend {
+        message: &raw const message,
+        reply: reply.as_mut_ptr(),
+    };
 
-    if syscall::failed(ret) {
-        Err(::syscall::ipc::SendError::from_syscall_code(ret as isize))
-    } else {
-        // SAFETY: The syscall succeeded, so the reply should be properly
-        // initialized by the kernel. If we can't trust the kernel, we are
-        // already in trouble !
-        Ok(unsafe { reply.assume_init() })
-    }
+    // SAFETY: The message is valid for reads and the reply is valid for
+    // writes during the whole syscall.
+    syscall::decode::<::syscall::ipc::SendError>(unsafe { syscall::invoke(&args) })?;
+
+    // SAFETY: The syscall succeeded, so the reply should be properly
+    // initialized by the kernel. If we can't trust the kernel, we are
+    // already in trouble !
+    Ok(unsafe { reply.assume_init() })
+}
+
+/// Sends an IPC message through the connection identified by the given handle
+/// without waiting for a reply. The receiver sees the message as one-way (see
+/// [`::syscall::ipc::SenderInfo::is_oneway`]) and does not reply to it. This
+/// never blocks: if the mailbox of the service is full, the message is
+/// dropped.
+///
+/// # Errors
+/// Returns a [`SendError`] describing the error if the syscall fails, notably
+/// [`SendError::MailboxFull`] if the message was dropped.
+///
+/// [`SendError`]: ::syscall::ipc::SendError
+/// [`SendError::MailboxFull`]: ::syscall::ipc::SendError::MailboxFull
+pub fn send_oneway(
+    handle: usize,
+    kind: usize,
+    payload: &[u8],
+) -> Result<(), ::syscall::ipc::SendError> {
+    let mut message = ::syscall::ipc::Message {
+        sender: 0,
+        receiver: handle,
+        kind,
+        payload_len: payload.len(),
+        sender_info: ::syscall::ipc::SenderInfo::default(),
+        payload: [0u8; ::syscall::ipc::MAX_PAYLOAD_SIZE],
+    };
+
+    message.payload[..payload.len().min(::syscall::ipc::MAX_PAYLOAD_SIZE)]
+        .copy_from_slice(&payload[..payload.len().min(::syscall::ipc::MAX_PAYLOAD_SIZE)]);
+
+    let args = ::syscall::args::IpcSendOneway {
+        message: &raw const message,
+    };
+
+    // SAFETY: The message is valid for reads during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
 }
 
 /// Receives an IPC message sent to the current task, blocking until a message
 /// is available.
 ///
 /// # Errors
-/// Returns an [`ReceiveError`] describing the error if the syscall fails.
+/// Returns a [`ReceiveError`] describing the error if the syscall fails.
 pub fn receive() -> Result<::syscall::ipc::Message, ::syscall::ipc::ReceiveError> {
     let mut message = MaybeUninit::<::syscall::ipc::Message>::uninit();
-    let ret;
-
-    unsafe {
-        core::arch::asm!("ecall",
-            in("a7") 7,                     // syscall number for ipc_receive
-            in("a0") &mut message,          // pointer to the message buffer
-            lateout("a0") ret,              // return value
-            options(nostack, preserves_flags)
-        );
-    }
+    let args = ::syscall::args::IpcReceive {
+        message: message.as_mut_ptr(),
+    };
+
+    // SAFETY: The message buffer is valid for writes during the whole
+    // syscall.
+    syscall::decode::<::syscall::ipc::ReceiveError>(unsafe { syscall::invoke(&args) })?;
+
+    // SAFETY: The syscall succeeded, so the message should be properly
+    // initialized by the kernel.
+    Ok(unsafe { message.assume_init() })
+}
 
-    if syscall::failed(ret) {
-        Err(::syscall::ipc::ReceiveError::from_syscall_code(
-            ret as isize,
-        ))
-    } else {
+/// Receives an IPC message sent to the current task if one is available,
+/// without blocking. Returns `Ok(None)` if the mailbox of the task is empty,
+/// which allows a service to poll other sources of events in the meantime.
+///
+/// # Errors
+/// Returns a [`ReceiveError`] describing the error if the syscall fails.
+pub fn try_receive() -> Result<Option<::syscall::ipc::Message>, ::syscall::ipc::ReceiveError> {
+    let mut message = MaybeUninit::<::syscall::ipc::Message>::uninit();
+    let args = ::syscall::args::IpcTryReceive {
+        message: message.as_mut_ptr(),
+    };
+
+    // SAFETY: The message buffer is valid for writes during the whole
+    // syscall.
+    match syscall::decode::<::syscall::ipc::ReceiveError>(unsafe { syscall::invoke(&args) }) {
         // SAFETY: The syscall succeeded, so the message should be properly
         // initialized by the kernel.
-        Ok(unsafe { message.assume_init() })
+        Ok(_) => Ok(Some(unsafe { message.assume_init() })),
+        Err(::syscall::ipc::ReceiveError::NoMessage) => Ok(None),
+        Err(error) => Err(error),
     }
 }
 
 /// Replies to an IPC message sent from another task.
 ///
 /// # Errors
-/// Returns an [`IpcReplyError`] describing the error if the syscall fails.
+/// Returns a [`ReplyError`] describing the error if the syscall fails.
 /// Most notably, this can happen if the destination task is not waiting for
 /// a reply (meaning it did not send a message to this task).
 pub fn reply(to: usize, status: usize, payload: &[u8]) -> Result<(), ::syscall::ipc::ReplyError> {
@@ -118,24 +135,51 @@ pub fn reply(to: usize, status: usize, payload: &[u8]) -> Result<(), ::syscall::
         payload_len: payload.len(),
         payload: [0u8; ::syscall::ipc::MAX_PAYLOAD_SIZE],
     };
-    let ret;
 
     reply.payload[..payload.len().min(::syscall::ipc::MAX_PAYLOAD_SIZE)]
         .copy_from_slice(&payload[..payload.len().min(::syscall::ipc::MAX_PAYLOAD_SIZE)]);
 
-    unsafe {
-        core::arch::asm!("ecall",
-            in("a7") 8,                 // syscall number for ipc_reply
-            in("a0") to,                // destination task ID
-            in("a1") &reply,            // pointer to the reply
-            lateout("a0") ret,          // return value
-            options(nostack, preserves_flags)
-        );
-    }
+    let args = ::syscall::args::IpcReply {
+        to,
+        reply: &raw const reply,
+    };
 
-    if syscall::failed(ret) {
-        Err(::syscall::ipc::ReplyError::from_syscall_code(ret as isize))
-    } else {
-        Ok(())
-    }
+    // SAFETY: The reply is valid for reads during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Forwards an IPC message received from the task `to` to the service of the
+/// connection identified by the given handle. The service receives the
+/// message as if it was sent by the task `to`, and replies directly to it:
+/// the current task must not reply to it anymore. The kind and payload of the
+/// forwarded message may differ from the message that was received.
+///
+/// # Errors
+/// Returns a [`ForwardError`] describing the error if the syscall fails. In
+/// this case, the current task must still reply to the task `to`.
+pub fn forward(
+    to: usize,
+    handle: usize,
+    kind: usize,
+    payload: &[u8],
+) -> Result<(), ::syscall::ipc::ForwardError> {
+    let mut message = ::syscall::ipc::Message {
+        sender: 0,
+        receiver: handle,
+        kind,
+        payload_len: payload.len(),
+        sender_info: ::syscall::ipc::SenderInfo::default(),
+        payload: [0u8; ::syscall::ipc::MAX_PAYLOAD_SIZE],
+    };
+
+    message.payload[..payload.len().min(::syscall::ipc::MAX_PAYLOAD_SIZE)]
+        .copy_from_slice(&payload[..payload.len().min(::syscall::ipc::MAX_PAYLOAD_SIZE)]);
+
+    let args = ::syscall::args::IpcForward {
+        to,
+        message: &raw const message,
+    };
+
+    // SAFETY: The message is valid for reads during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
 }
@@ -0,0 +1,72 @@
+use crate::syscall;
+
+pub use ::syscall::irq::{AffinityError, ControlError, RegisterError, WaitError};
+
+/// Binds the given interrupt source to the current task. The source is
+/// masked until the first call to [`wait`], which a driver then calls in a
+/// loop, servicing its device each time it returns.
+///
+/// # Errors
+/// Returns a [`RegisterError`] if the syscall fails, most notably if the
+/// source is already bound to another task.
+pub fn register(irq: usize) -> Result<(), RegisterError> {
+    let args = ::syscall::args::IrqRegister { irq };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Acknowledges the previous interrupt of the given source, and blocks until
+/// the source raises another interrupt. The device must have been serviced
+/// before calling this function, otherwise the interrupt is raised again
+/// immediately.
+///
+/// # Errors
+/// Returns [`WaitError::NotBound`] if the source is not bound to the current
+/// task.
+pub fn wait(irq: usize) -> Result<(), WaitError> {
+    let args = ::syscall::args::IrqWait { irq };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Masks the given source until [`unmask`] is called, even across calls to
+/// [`wait`]. This lets a driver poll its device while it is busy instead of
+/// taking an interrupt for each event. An interrupt raised before the source
+/// was masked is still reported by the next call to [`wait`].
+///
+/// # Errors
+/// Returns [`ControlError::NotBound`] if the source is not bound to the
+/// current task.
+pub fn mask(irq: usize) -> Result<(), ControlError> {
+    let args = ::syscall::args::IrqMask { irq };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Unmasks the given source masked with [`mask`].
+///
+/// # Errors
+/// Returns [`ControlError::NotBound`] if the source is not bound to the
+/// current task.
+pub fn unmask(irq: usize) -> Result<(), ControlError> {
+    let args = ::syscall::args::IrqUnmask { irq };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Routes the given source to the CPU with the given index, such as the CPU
+/// running the driver.
+///
+/// # Errors
+/// Returns an [`AffinityError`] if the source is not bound to the current
+/// task, or if the interrupt controller cannot route it to this CPU.
+pub fn set_affinity(irq: usize, cpu: usize) -> Result<(), AffinityError> {
+    let args = ::syscall::args::IrqSetAffinity { irq, cpu };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
@@ -1,13 +1,45 @@
 #![no_std]
+#![feature(thread_local)]
 
-/// Re-export the main macro
-pub use macros::main;
+extern crate alloc;
 
+// Allow the code generated by the macros to refer to this crate as `xstd`,
+// even when it is used inside this crate.
+extern crate self as xstd;
+
+/// Re-export the macros
+pub use macros::{main, rpc};
+
+pub mod bench;
+pub mod console;
 pub mod debug;
+pub mod device;
+pub mod echo;
+pub mod env;
+pub mod fs;
+pub mod futex;
+pub mod green;
+pub mod heap;
+pub mod initrd;
+pub mod io;
 pub mod ipc;
+pub mod irq;
+pub mod log;
+pub mod memory;
+pub mod power;
+pub mod rand;
+pub mod rpc;
 pub mod service;
+pub mod stats;
+pub mod sync;
 pub mod syscall;
+pub mod table;
 pub mod task;
+pub mod thread;
+pub mod timer;
+pub mod tls;
+pub mod trace;
+pub mod vdso;
 
 /// The panic handler for user-space applications. When a panic occurs, this
 /// function will be called, and it will simply abort the current task by
@@ -0,0 +1,61 @@
+use crate::syscall;
+
+pub use ::syscall::log::{ControlError, Level, MAX_MODULE_LEN, MAX_RECORD_LEN, ReadError, Record};
+
+/// Sets the global level of the kernel log, which applies to the modules of
+/// the kernel that do not have their own level. Returns the previous global
+/// level.
+///
+/// # Errors
+/// Returns a [`ControlError`] describing the error if the syscall fails, most
+/// notably if the level is [`Level::Default`], which is not a valid global
+/// level.
+pub fn set_level(level: Level) -> Result<Level, ControlError> {
+    set_module_level("", level)
+}
+
+/// Sets the level of the kernel log for the given module of the kernel and
+/// its submodules, such as `ipc` or `user::syscall`. The module uses the level
+/// of its parent module or the global level again if the level is
+/// [`Level::Default`]. Returns the previous level of the module, which is
+/// [`Level::Default`] if it did not have its own level.
+///
+/// # Errors
+/// Returns a [`ControlError`] describing the error if the syscall fails, most
+/// notably if the name of the module is longer than [`MAX_MODULE_LEN`] or if
+/// too many modules already have their own level.
+pub fn set_module_level(module: &str, level: Level) -> Result<Level, ControlError> {
+    let args = ::syscall::args::LogControl {
+        module: module.as_ptr(),
+        module_len: module.len(),
+        level: level as usize,
+    };
+
+    // SAFETY: The name of the module is valid for reads during the whole
+    // syscall.
+    syscall::decode::<ControlError>(unsafe { syscall::invoke(&args) }).map(Level::from)
+}
+
+/// Reads the messages of the kernel log into the given buffer, starting at
+/// the message with the given number. Returns the number of messages written,
+/// which is at most the length of the buffer and is zero once all the
+/// messages were read. To read the whole log, start at zero and continue
+/// after the number of the last message read.
+///
+/// If the requested message was already overwritten, reading starts at the
+/// oldest message kept by the kernel, so the number of the first message
+/// returned tells how many messages were missed.
+///
+/// # Errors
+/// Returns a [`ReadError`] if the syscall fails, most notably if the buffer
+/// is invalid.
+pub fn read(sequence: u64, buffer: &mut [Record]) -> Result<usize, ReadError> {
+    let args = ::syscall::args::LogRead {
+        sequence: sequence as usize,
+        buffer: buffer.as_mut_ptr(),
+        capacity: buffer.len(),
+    };
+
+    // SAFETY: The buffer is valid for writes during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
@@ -0,0 +1,131 @@
+use crate::syscall;
+
+pub use ::syscall::memory::{
+    AllocDmaError, MAX_DMA_SIZE, MapError, Pressure, PressureNotice, ProtectError, Protection,
+    SubscribeError, UnsubscribeError,
+};
+
+/// Memory allocated with [`alloc_dma`], that devices can access.
+#[derive(Debug, Clone, Copy)]
+pub struct Dma {
+    /// A pointer to the start of the memory in the address space of the task.
+    pub address: *mut u8,
+
+    /// The physical address of the memory, to be given to the devices.
+    pub physical: usize,
+
+    /// The size of the memory, in bytes.
+    pub size: usize,
+}
+
+/// Maps `size` bytes of fresh zeroed memory at the given page-aligned address
+/// with the given protection, and returns a pointer to the start of the
+/// mapping.
+///
+/// # Errors
+/// Returns a [`MapError`] describing the error if the syscall fails.
+pub fn map(address: usize, size: usize, protection: Protection) -> Result<*mut u8, MapError> {
+    let args = ::syscall::args::MemMap {
+        address,
+        size,
+        protection,
+    };
+
+    // SAFETY: The syscall only maps new memory, and fails if any page of the
+    // range is already mapped, so existing memory is never affected.
+    let address = syscall::decode::<MapError>(unsafe { syscall::invoke(&args) })?;
+    Ok(core::ptr::with_exposed_provenance_mut(address))
+}
+
+/// Allocates `size` bytes of physically contiguous zeroed memory, and maps it
+/// readable and writable at the given page-aligned address. The memory is
+/// never freed before the task terminates, even if it is unmapped, since a
+/// device may still be accessing it.
+///
+/// # Errors
+/// Returns an [`AllocDmaError`] describing the error if the syscall fails.
+pub fn alloc_dma(address: usize, size: usize) -> Result<Dma, AllocDmaError> {
+    let mut physical = 0;
+    let args = ::syscall::args::MemAllocDma {
+        address,
+        size,
+        physical: &raw mut physical,
+    };
+
+    // SAFETY: The syscall only maps new memory, and fails if any page of the
+    // range is already mapped, so existing memory is never affected. The
+    // pointer to the physical address is valid for writes.
+    let address = syscall::decode::<AllocDmaError>(unsafe { syscall::invoke(&args) })?;
+    Ok(Dma {
+        address: core::ptr::with_exposed_provenance_mut(address),
+        physical,
+        size,
+    })
+}
+
+/// Changes the protection of the pages covering `size` bytes starting at the
+/// given page-aligned address. The new protection is enforced as soon as this
+/// function returns.
+///
+/// # Errors
+/// Returns a [`ProtectError`] describing the error if the syscall fails.
+///
+/// # Safety
+/// The caller must ensure that no code still relies on the rights being
+/// removed, for example by holding a reference to memory that becomes
+/// unreadable, or by executing code from pages that are no longer executable.
+pub unsafe fn protect(
+    address: *const u8,
+    size: usize,
+    protection: Protection,
+) -> Result<(), ProtectError> {
+    let args = ::syscall::args::MemProtect {
+        address: address.addr(),
+        size,
+        protection,
+    };
+
+    // SAFETY: The syscall does not access any memory of the caller.
+    syscall::decode::<ProtectError>(unsafe { syscall::invoke(&args) })?;
+    Ok(())
+}
+
+/// Subscribes the current task to the changes of the memory pressure. Each
+/// time the level changes, the kernel sends a message with the given kind to
+/// the task, from which [`pressure`] extracts the notice. Services keeping
+/// caches should release them when the pressure rises.
+///
+/// # Errors
+/// Returns a [`SubscribeError`] describing the error if the syscall fails.
+pub fn subscribe_pressure(kind: usize) -> Result<(), SubscribeError> {
+    let args = ::syscall::args::MemPressureSubscribe { operation: kind };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode::<SubscribeError>(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Unsubscribes the current task from the changes of the memory pressure.
+///
+/// # Errors
+/// Returns an [`UnsubscribeError`] describing the error if the syscall fails.
+pub fn unsubscribe_pressure() -> Result<(), UnsubscribeError> {
+    let args = ::syscall::args::MemPressureUnsubscribe {};
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode::<UnsubscribeError>(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Returns the notice carried by the given message, or `None` if the message
+/// was not sent by the kernel. The kind of the message must be checked by the
+/// caller to be the one given to [`subscribe_pressure`]. Such messages must
+/// not be replied to.
+#[must_use]
+pub fn pressure(message: &::syscall::ipc::Message) -> Option<PressureNotice> {
+    use ::syscall::ipc::Payload;
+
+    if message.sender != ::syscall::ipc::KERNEL_SENDER {
+        return None;
+    }
+    let len = message.payload_len.min(message.payload.len());
+    PressureNotice::from_payload(&message.payload[..len])
+}
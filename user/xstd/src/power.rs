@@ -0,0 +1,53 @@
+use crate::syscall;
+
+pub use ::syscall::power::{Action, GRACE_PERIOD_MS, NOTICE_OPERATION, Notice, PowerError};
+
+/// Powers off the system, after giving the registered services a chance to
+/// terminate. This requires the [`crate::task::Capabilities::POWER`]
+/// capability, which only `init` holds.
+///
+/// # Errors
+/// Returns a [`PowerError`] if the system cannot be powered off. The syscall
+/// fails with the permission denied error code if the task does not hold the
+/// capability.
+pub fn power_off() -> Result<(), PowerError> {
+    invoke(Action::PowerOff)
+}
+
+/// Reboots the system, after giving the registered services a chance to
+/// terminate. This requires the [`crate::task::Capabilities::POWER`]
+/// capability, which only `init` holds.
+///
+/// # Errors
+/// Returns a [`PowerError`] if the system cannot be rebooted.
+pub fn reboot() -> Result<(), PowerError> {
+    invoke(Action::Reboot)
+}
+
+/// Invokes the `SystemPower` syscall with the given action. It only returns
+/// if it fails.
+fn invoke(action: Action) -> Result<(), PowerError> {
+    let args = ::syscall::args::SystemPower {
+        action: action as usize,
+    };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode::<PowerError>(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Returns the action announced by the given message, or `None` if the
+/// message is not a notice sent by the kernel before the system goes down.
+/// Services receiving a notice should release their resources and terminate
+/// within [`GRACE_PERIOD_MS`] milliseconds. Such messages must not be replied
+/// to.
+#[must_use]
+pub fn notice(message: &::syscall::ipc::Message) -> Option<Action> {
+    use ::syscall::ipc::Payload;
+
+    if message.sender != ::syscall::ipc::KERNEL_SENDER || message.kind != NOTICE_OPERATION {
+        return None;
+    }
+    let len = message.payload_len.min(message.payload.len());
+    let notice = Notice::from_payload(&message.payload[..len])?;
+    Action::from_raw(usize::try_from(notice.action).ok()?)
+}
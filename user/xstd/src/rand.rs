@@ -0,0 +1,54 @@
+use crate::syscall;
+
+pub use ::syscall::random::{FillError, MAX_FILL_SIZE};
+
+/// Fills the given buffer with random bytes generated by the kernel from its
+/// entropy pool. Buffers larger than [`MAX_FILL_SIZE`] are filled with
+/// several syscalls.
+///
+/// # Errors
+/// Returns a [`FillError`] if the syscall fails, most notably if the buffer
+/// is invalid.
+pub fn fill(buffer: &mut [u8]) -> Result<(), FillError> {
+    for chunk in buffer.chunks_mut(MAX_FILL_SIZE) {
+        let args = ::syscall::args::GetRandom {
+            buffer: chunk.as_mut_ptr(),
+            len: chunk.len(),
+        };
+
+        // SAFETY: The buffer is valid for writes during the whole syscall.
+        syscall::decode::<FillError>(unsafe { syscall::invoke(&args) })?;
+    }
+    Ok(())
+}
+
+/// Returns a random 64-bit number generated by the kernel.
+///
+/// # Panics
+/// Panics if the kernel fails to generate the number, which cannot happen
+/// since the buffer is always valid.
+#[must_use]
+pub fn u64() -> u64 {
+    let mut bytes = [0; 8];
+    fill(&mut bytes).expect("Failed to get random bytes from the kernel");
+    u64::from_le_bytes(bytes)
+}
+
+/// Returns a random number lower than the given bound, without bias.
+///
+/// # Panics
+/// Panics if the bound is zero.
+#[must_use]
+pub fn below(bound: u64) -> u64 {
+    assert!(bound != 0, "The bound must not be zero");
+
+    // Reject the numbers of the last incomplete range of `bound` numbers,
+    // which would otherwise make the lowest numbers more likely.
+    let zone = u64::MAX - u64::MAX % bound;
+    loop {
+        let number = u64();
+        if number < zone {
+            return number % bound;
+        }
+    }
+}
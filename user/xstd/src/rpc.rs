@@ -0,0 +1,213 @@
+//! Typed remote procedure calls over IPC messages. A protocol is described by
+//! a trait annotated with [`crate::rpc`], which generates a client that sends
+//! each method as a message and a server adapter that decodes the messages
+//! and calls the implementation of the trait:
+//!
+//! ```ignore
+//! #[xstd::rpc]
+//! pub trait Echo {
+//!     fn echo(&mut self, text: &[u8]) -> Vec<u8>;
+//! }
+//! ```
+//!
+//! This generates an `EchoClient` that wraps the handle of a connection to
+//! the service, and an `EchoServer` that wraps an implementation of the trait
+//! and can be given to [`serve`].
+//!
+//! The kind of a message is the index of the method in the trait, and its
+//! payload holds the arguments in their declaration order. Arguments and
+//! return values are copied as raw bytes with zerocopy, so their types must
+//! implement [`IntoBytes`], [`FromBytes`] and [`Immutable`]. A `&[u8]`
+//! argument is also accepted as the last argument and a `Vec<u8>` as return
+//! value, which take the remaining bytes of the payload. The status of the
+//! reply is a [`Status`].
+use crate::service::{Response, Server};
+use ::syscall::ipc::{MAX_PAYLOAD_SIZE, Reply, SendError};
+use zerocopy::{FromBytes, Immutable, IntoBytes};
+
+/// The status of the reply to a remote procedure call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The call succeeded.
+    Ok = 0,
+
+    /// An unknown error occurred.
+    Unknown = 1,
+
+    /// The service does not have a method with the requested index, most
+    /// likely because it implements another version of the protocol.
+    UnknownMethod = 2,
+
+    /// The arguments could not be decoded from the payload of the request.
+    BadRequest = 3,
+
+    /// The arguments or the return value do not fit in a message.
+    TooLarge = 4,
+}
+
+impl From<usize> for Status {
+    fn from(status: usize) -> Self {
+        match status {
+            0 => Status::Ok,
+            2 => Status::UnknownMethod,
+            3 => Status::BadRequest,
+            4 => Status::TooLarge,
+            _ => Status::Unknown,
+        }
+    }
+}
+
+/// An error that can occur while calling a remote procedure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The request could not be sent to the service, or the service
+    /// terminated before replying to it.
+    Send(SendError),
+
+    /// The request could not be encoded, or the service rejected it.
+    Status(Status),
+
+    /// The return value could not be decoded from the payload of the reply.
+    BadReply,
+}
+
+impl From<SendError> for Error {
+    fn from(error: SendError) -> Self {
+        Self::Send(error)
+    }
+}
+
+impl From<Status> for Error {
+    fn from(status: Status) -> Self {
+        Self::Status(status)
+    }
+}
+
+/// Writes values one after the other into the payload of a message.
+#[derive(Debug)]
+pub struct Encoder {
+    payload: [u8; MAX_PAYLOAD_SIZE],
+    len: usize,
+}
+
+impl Encoder {
+    /// Creates an encoder for an empty payload.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            payload: [0; MAX_PAYLOAD_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Appends the bytes of the given value to the payload.
+    ///
+    /// # Errors
+    /// Returns [`Status::TooLarge`] if the value does not fit in the payload.
+    pub fn value<T: IntoBytes + Immutable>(&mut self, value: &T) -> Result<(), Status> {
+        self.bytes(value.as_bytes())
+    }
+
+    /// Appends the given bytes to the payload.
+    ///
+    /// # Errors
+    /// Returns [`Status::TooLarge`] if the bytes do not fit in the payload.
+    pub fn bytes(&mut self, bytes: &[u8]) -> Result<(), Status> {
+        let end = self.len + bytes.len();
+        if end > MAX_PAYLOAD_SIZE {
+            return Err(Status::TooLarge);
+        }
+        self.payload[self.len..end].copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    /// Returns the encoded payload.
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload[..self.len]
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads values one after the other from the payload of a message.
+#[derive(Debug)]
+pub struct Decoder<'a> {
+    payload: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    /// Creates a decoder for the given payload.
+    #[must_use]
+    pub const fn new(payload: &'a [u8]) -> Self {
+        Self { payload }
+    }
+
+    /// Reads the next value from the payload.
+    ///
+    /// # Errors
+    /// Returns [`Status::BadRequest`] if the payload is too short.
+    pub fn value<T: FromBytes>(&mut self) -> Result<T, Status> {
+        let (value, rest) = T::read_from_prefix(self.payload).map_err(|_| Status::BadRequest)?;
+        self.payload = rest;
+        Ok(value)
+    }
+
+    /// Returns all the remaining bytes of the payload.
+    pub fn bytes(&mut self) -> &'a [u8] {
+        core::mem::take(&mut self.payload)
+    }
+}
+
+/// The server side of a protocol, which is implemented by the server adapter
+/// generated by [`crate::rpc`].
+pub trait Dispatch {
+    /// Calls the method with the given index with the arguments decoded from
+    /// the given request, and encodes its return value into the reply.
+    ///
+    /// # Errors
+    /// Returns the status to reply with if the method does not exist or if the
+    /// request or the reply could not be encoded.
+    fn dispatch(
+        &mut self,
+        method: usize,
+        request: &mut Decoder<'_>,
+        reply: &mut Encoder,
+    ) -> Result<(), Status>;
+}
+
+/// Calls the method with the given index on the service of the connection
+/// identified by the given handle, and returns the reply if it succeeded.
+/// This is used by the clients generated by [`crate::rpc`].
+///
+/// # Errors
+/// Returns an [`Error`] if the request could not be sent or if the service
+/// rejected it.
+pub fn call(handle: usize, method: usize, request: &Encoder) -> Result<Reply, Error> {
+    let reply = crate::ipc::send(handle, method, request.payload())?;
+    match Status::from(reply.status) {
+        Status::Ok => Ok(reply),
+        status => Err(Error::Status(status)),
+    }
+}
+
+/// Receives the messages sent to the current task and dispatches them to the
+/// given server forever, replying to each message with the return value of
+/// the method or with the status describing why it failed.
+pub fn serve(server: &mut impl Dispatch) -> ! {
+    Server::new()
+        .with_overflow_status(Status::TooLarge as usize)
+        .serve(|request| {
+            let mut decoder = Decoder::new(request.payload());
+            let mut reply = Encoder::new();
+            match server.dispatch(request.kind(), &mut decoder, &mut reply) {
+                Ok(()) => Response::new(Status::Ok as usize, reply.payload().to_vec()),
+                Err(status) => Response::status(status as usize),
+            }
+        })
+}
@@ -1,109 +1,312 @@
-use crate::syscall::{self, SyscallCode};
-
-impl SyscallCode for ::syscall::service::RegisterError {
-    fn from_syscall_code(code: isize) -> Self {
-        match -code {
-            1 => ::syscall::service::RegisterError::BadName,
-            2 => ::syscall::service::RegisterError::NameNotAvailable,
-            3 => ::syscall::service::RegisterError::TaskAlreadyRegistered,
-            _ => ::syscall::service::RegisterError::Unknown,
-        }
-    }
-}
-
-impl SyscallCode for ::syscall::service::UnregisterError {
-    fn from_syscall_code(code: isize) -> Self {
-        match -code {
-            1 => ::syscall::service::UnregisterError::NotImplemented,
-            _ => ::syscall::service::UnregisterError::Unknown,
-        }
-    }
-}
-
-impl SyscallCode for ::syscall::service::ConnectionError {
-    fn from_syscall_code(code: isize) -> Self {
-        match -code {
-            1 => ::syscall::service::ConnectionError::BadName,
-            2 => ::syscall::service::ConnectionError::ServiceNotFound,
-            _ => ::syscall::service::ConnectionError::Unknown,
-        }
-    }
-}
+use crate::syscall;
+use ::syscall::ipc::{MAX_PAYLOAD_SIZE, Message};
+pub use ::syscall::service::{ConnectionError, DisconnectError};
+use alloc::vec::Vec;
 
 /// Registers the current task as a service provider with the given name. The
 /// name must be a valid UTF-8 string and unique among all registered services.
 ///
 /// # Errors
-/// This function returns a [`ServiceRegisterError`] if the registration fails
+/// This function returns a [`RegisterError`] if the registration fails
 /// for any reason, such as an invalid name or if the name is already taken by
 /// another service.
 pub fn register(name: &str) -> Result<(), ::syscall::service::RegisterError> {
-    let ret;
-    unsafe {
-        core::arch::asm!("ecall",
-            in("a7") 3,                 // syscall number for service_register
-            in("a0") name.as_ptr(),     // pointer to the service name
-            in("a1") name.len(),        // length of the service name
-            lateout("a0") ret,          // return value
-            options(nostack, preserves_flags)
-        );
-    }
+    register_with_flags(name, ::syscall::service::RegisterFlags::NONE)
+}
 
-    if syscall::failed(ret) {
-        Err(::syscall::service::RegisterError::from_syscall_code(
-            ret as isize,
-        ))
-    } else {
-        Ok(())
-    }
+/// Registers the current task as a service provider with the given name and
+/// flags. See [`register`] for more details.
+///
+/// # Errors
+/// This function returns a [`RegisterError`] if the registration fails
+/// for any reason, such as an invalid name or if the name is already taken by
+/// another service.
+pub fn register_with_flags(
+    name: &str,
+    flags: ::syscall::service::RegisterFlags,
+) -> Result<(), ::syscall::service::RegisterError> {
+    let args = ::syscall::args::ServiceRegister {
+        name: name.as_ptr(),
+        name_len: name.len(),
+        flags,
+    };
+
+    // SAFETY: The name is valid for reads during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
 }
 
-/// Unregisters the current task's service.
+/// Unregisters the current task's service. Existing connections to the
+/// service become unusable, but messages already received by the service
+/// must still be replied to. The task can then register again, under the
+/// same or a different name.
 ///
 /// # Errors
-/// This function returns a [`ServiceUnregisterError`] if the unregistration
-/// fails for any reason.
+/// This function returns an [`UnregisterError`] if the unregistration
+/// fails for any reason, such as when the task does not provide a service.
 pub fn unregister() -> Result<(), ::syscall::service::UnregisterError> {
-    let ret;
-    unsafe {
-        core::arch::asm!("ecall",
-            in("a7") 4,         // syscall number for service_unregister
-            lateout("a0") ret,  // return value
-            options(nostack, preserves_flags)
-        );
-    }
-
-    if syscall::failed(ret) {
-        Err(::syscall::service::UnregisterError::from_syscall_code(
-            ret as isize,
-        ))
-    } else {
-        Ok(())
-    }
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode(unsafe { syscall::invoke(&::syscall::args::ServiceUnregister {}) }).map(|_| ())
 }
 
 /// Connects to a service by its name and returns a handle to the service.
 ///
 /// # Errors
-/// This function returns a [`ServiceConnectError`] if the connection fails,
+/// This function returns a [`ConnectionError`] if the connection fails,
 /// such as when the service is not found or an invalid name is provided.
 pub fn connect(name: &str) -> Result<usize, ::syscall::service::ConnectionError> {
-    let ret;
-    unsafe {
-        core::arch::asm!("ecall",
-            in("a7") 5,                 // syscall number for service_connect
-            in("a0") name.as_ptr(),     // pointer to the service name
-            in("a1") name.len(),        // length of the service name
-            lateout("a0") ret,          // return value
-            options(nostack, preserves_flags)
-        );
+    let args = ::syscall::args::ServiceConnect {
+        name: name.as_ptr(),
+        name_len: name.len(),
+    };
+
+    // SAFETY: The name is valid for reads during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
+
+/// Connects to a service by its name like [`connect`], but waits until a
+/// service with this name is registered if there is none yet. This is meant
+/// for clients that may be started before the services they use.
+///
+/// # Errors
+/// This function returns a [`ConnectionError`] if the connection fails, such
+/// as when the service does not accept the current task or an invalid name is
+/// provided.
+pub fn connect_wait(name: &str) -> Result<usize, ::syscall::service::ConnectionError> {
+    let args = ::syscall::args::ServiceConnectWait {
+        name: name.as_ptr(),
+        name_len: name.len(),
+    };
+
+    // SAFETY: The name is valid for reads during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
+
+/// Closes the connection to a service referred to by the given handle. The
+/// handle must not be used afterwards, since it may be reused by a later
+/// connection. Tasks can only hold a limited number of connections at the
+/// same time, so connections that are no longer needed should be closed.
+///
+/// # Errors
+/// This function returns a [`DisconnectError`] if the handle does not refer
+/// to a connection of the current task.
+pub fn disconnect(handle: usize) -> Result<(), ::syscall::service::DisconnectError> {
+    let args = ::syscall::args::ServiceDisconnect { handle };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Allows the task with the given identifier to connect to the service of the
+/// current task, which must have been registered with
+/// [`::syscall::service::RegisterFlags::RESTRICTED`]. Other tasks fail to
+/// connect with [`::syscall::service::ConnectionError::AccessDenied`]. Only
+/// new connections are checked, so the task must be accepted before it
+/// connects.
+///
+/// # Errors
+/// This function returns an [`AcceptError`] if the task does not exist, if the
+/// current task does not provide a restricted service, or if the service
+/// cannot accept more tasks.
+pub fn accept(task: usize) -> Result<(), ::syscall::service::AcceptError> {
+    let args = ::syscall::args::ServiceAccept { task };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+}
+
+/// Retrieves the per-kind statistics of the service with the given name and
+/// writes them into the given buffer, sorted by message kind. Returns the
+/// number of entries written, which is at most the length of the buffer.
+///
+/// # Errors
+/// This function returns a [`StatisticsError`] if the statistics could not be
+/// retrieved, such as when the service is not found.
+pub fn statistics(
+    name: &str,
+    buffer: &mut [::syscall::service::KindStatistics],
+) -> Result<usize, ::syscall::service::StatisticsError> {
+    let args = ::syscall::args::ServiceStatistics {
+        name: name.as_ptr(),
+        name_len: name.len(),
+        buffer: buffer.as_mut_ptr(),
+        capacity: buffer.len(),
+    };
+
+    // SAFETY: The name is valid for reads and the buffer is valid for writes
+    // of `capacity` entries during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
+
+/// Enumerates the services registered in the kernel and writes their
+/// information into the given buffer, sorted by name. Returns the number of
+/// entries written, which is at most the length of the buffer.
+///
+/// # Errors
+/// This function returns a [`ListError`] if the services could not be
+/// enumerated.
+pub fn list(
+    buffer: &mut [::syscall::service::ServiceInfo],
+) -> Result<usize, ::syscall::service::ListError> {
+    let args = ::syscall::args::ServiceList {
+        buffer: buffer.as_mut_ptr(),
+        capacity: buffer.len(),
+    };
+
+    // SAFETY: The buffer is valid for writes of `capacity` entries during
+    // the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
+
+/// A request received by a [`Server`].
+pub struct Request {
+    message: Message,
+}
+
+impl Request {
+    /// Returns the identifier of the task that sent the request.
+    #[must_use]
+    pub const fn sender(&self) -> usize {
+        self.message.sender
+    }
+
+    /// Returns the information about the sender of the request verified by
+    /// the kernel, such as the badge of the connection it was sent through.
+    #[must_use]
+    pub const fn sender_info(&self) -> ::syscall::ipc::SenderInfo {
+        self.message.sender_info
+    }
+
+    /// Returns the kind of the request, whose meaning is defined by the
+    /// protocol of the service.
+    #[must_use]
+    pub const fn kind(&self) -> usize {
+        self.message.kind
+    }
+
+    /// Returns the valid part of the payload of the request.
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        &self.message.payload[..self.message.payload_len.min(MAX_PAYLOAD_SIZE)]
+    }
+}
+
+/// The response of a [`Server`] to a request, made of a status and of a
+/// payload whose meanings are defined by the protocol of the service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    status: usize,
+    payload: Vec<u8>,
+}
+
+impl Response {
+    /// Creates a response with the given status and payload.
+    #[must_use]
+    pub const fn new(status: usize, payload: Vec<u8>) -> Self {
+        Self { status, payload }
     }
 
-    if syscall::failed(ret) {
-        Err(::syscall::service::ConnectionError::from_syscall_code(
-            ret as isize,
-        ))
-    } else {
-        Ok(ret)
+    /// Creates a response with the given status and an empty payload, which
+    /// is typically used to report an error.
+    #[must_use]
+    pub const fn status(status: usize) -> Self {
+        Self::new(status, Vec::new())
+    }
+}
+
+/// The server side of a service: it receives the messages sent to the current
+/// task, gives them to a handler and replies to each of them with the
+/// response of the handler. Requests are handled one at a time, in the order
+/// they were received.
+///
+/// ```ignore
+/// Server::register("example")?.serve(|request| {
+///     Response::new(0, request.payload().to_vec())
+/// });
+/// ```
+#[derive(Debug)]
+pub struct Server {
+    overflow: usize,
+}
+
+impl Server {
+    /// Registers the current task as a service provider with the given name
+    /// (see [`register`]) and returns a server for its requests.
+    ///
+    /// # Errors
+    /// This function returns a [`RegisterError`] if the registration fails.
+    pub fn register(name: &str) -> Result<Self, ::syscall::service::RegisterError> {
+        Self::register_with_flags(name, ::syscall::service::RegisterFlags::NONE)
+    }
+
+    /// Registers the current task as a service provider with the given name
+    /// and flags (see [`register_with_flags`]) and returns a server for its
+    /// requests.
+    ///
+    /// # Errors
+    /// This function returns a [`RegisterError`] if the registration fails.
+    pub fn register_with_flags(
+        name: &str,
+        flags: ::syscall::service::RegisterFlags,
+    ) -> Result<Self, ::syscall::service::RegisterError> {
+        register_with_flags(name, flags)?;
+        Ok(Self::new())
+    }
+
+    /// Returns a server for the requests sent to the current task, which is
+    /// expected to be already registered as a service provider.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            overflow: usize::MAX,
+        }
+    }
+
+    /// Sets the status replied instead of a response whose payload does not
+    /// fit in a reply. By default, the status is `usize::MAX`, which most
+    /// protocols do not use.
+    #[must_use]
+    pub const fn with_overflow_status(mut self, status: usize) -> Self {
+        self.overflow = status;
+        self
+    }
+
+    /// Receives the requests sent to the current task forever, and replies to
+    /// each of them with the response returned by the given handler. Errors
+    /// while receiving a request or replying to it only concern a single
+    /// request, and are therefore ignored.
+    ///
+    /// One-way messages, such as those sent with [`crate::ipc::send_oneway`]
+    /// or by the kernel for the expirations of timers, are given to the
+    /// handler but never replied to. When the kernel announces that the system
+    /// goes down, the service unregisters and the task exits.
+    pub fn serve(&mut self, mut handler: impl FnMut(&Request) -> Response) -> ! {
+        loop {
+            let Ok(message) = crate::ipc::receive() else {
+                continue;
+            };
+
+            if message.sender_info.is_oneway() {
+                if crate::power::notice(&message).is_some() {
+                    _ = unregister();
+                    crate::task::exit(0);
+                }
+                _ = handler(&Request { message });
+                continue;
+            }
+
+            let request = Request { message };
+            let response = handler(&request);
+            _ = if response.payload.len() > MAX_PAYLOAD_SIZE {
+                crate::ipc::reply(request.sender(), self.overflow, &[])
+            } else {
+                crate::ipc::reply(request.sender(), response.status, &response.payload)
+            };
+        }
+    }
+}
+
+impl Default for Server {
+    fn default() -> Self {
+        Self::new()
     }
 }
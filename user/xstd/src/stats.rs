@@ -0,0 +1,48 @@
+use core::mem::MaybeUninit;
+
+use crate::syscall;
+
+/// Returns the system-wide statistics maintained by the kernel.
+///
+/// # Errors
+/// Returns a [`StatisticsError`] describing the error if the syscall fails.
+pub fn system() -> Result<::syscall::stats::SystemStatistics, ::syscall::stats::StatisticsError> {
+    let mut statistics = MaybeUninit::<::syscall::stats::SystemStatistics>::uninit();
+    let args = ::syscall::args::SystemStatistics {
+        statistics: statistics.as_mut_ptr(),
+    };
+
+    // SAFETY: The statistics buffer is valid for writes during the whole
+    // syscall.
+    syscall::decode::<::syscall::stats::StatisticsError>(unsafe { syscall::invoke(&args) })?;
+
+    // SAFETY: The syscall succeeded, so the statistics should be properly
+    // initialized by the kernel.
+    Ok(unsafe { statistics.assume_init() })
+}
+
+/// Returns the activity counters of the kernel, and writes the number of
+/// invocations of each syscall into the given buffer, in the order of
+/// [`::syscall::table::TABLE`]. Returns the statistics along with the number
+/// of syscall entries written, which is at most the length of the buffer.
+///
+/// # Errors
+/// Returns a [`StatisticsError`] describing the error if the syscall fails.
+pub fn kernel(
+    syscalls: &mut [::syscall::stats::SyscallCount],
+) -> Result<(::syscall::stats::KernelStatistics, usize), ::syscall::stats::StatisticsError> {
+    let mut statistics = MaybeUninit::<::syscall::stats::KernelStatistics>::uninit();
+    let args = ::syscall::args::KernelStatistics {
+        statistics: statistics.as_mut_ptr(),
+        syscalls: syscalls.as_mut_ptr(),
+        capacity: syscalls.len(),
+    };
+
+    // SAFETY: Both buffers are valid for writes during the whole syscall.
+    let count =
+        syscall::decode::<::syscall::stats::StatisticsError>(unsafe { syscall::invoke(&args) })?;
+
+    // SAFETY: The syscall succeeded, so the statistics should be properly
+    // initialized by the kernel.
+    Ok((unsafe { statistics.assume_init() }, count))
+}
@@ -0,0 +1,418 @@
+use crate::{futex, syscall};
+use core::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+pub use ::syscall::sync::{
+    ClearError, CreateError, DestroyError, Kind, MAX_VALUE, SignalError, WaitError,
+};
+
+/// A synchronization object of the kernel. It is destroyed when dropped if it
+/// was created by the current task, and simply forgotten otherwise.
+#[derive(Debug)]
+struct Object {
+    id: usize,
+    owned: bool,
+}
+
+impl Object {
+    /// Creates an object of the given kind with the given initial value.
+    fn create(kind: Kind, value: usize) -> Result<Self, CreateError> {
+        let args = ::syscall::args::SyncCreate {
+            kind: kind as usize,
+            value,
+        };
+
+        // SAFETY: This syscall does not take any pointer.
+        let id = syscall::decode::<CreateError>(unsafe { syscall::invoke(&args) })?;
+        Ok(Self { id, owned: true })
+    }
+
+    /// Uses an object created by another task.
+    const fn shared(id: usize) -> Self {
+        Self { id, owned: false }
+    }
+
+    /// Signals the object with the given value.
+    fn signal(&self, value: usize) -> Result<(), SignalError> {
+        let args = ::syscall::args::SyncSignal {
+            object: self.id,
+            value,
+        };
+
+        // SAFETY: This syscall does not take any pointer.
+        syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+    }
+
+    /// Blocks until the object is signaled, and returns its value before the
+    /// wait consumed it.
+    fn wait(&self) -> Result<usize, WaitError> {
+        let args = ::syscall::args::SyncWait { object: self.id };
+
+        // SAFETY: This syscall does not take any pointer.
+        syscall::decode(unsafe { syscall::invoke(&args) })
+    }
+
+    /// Resets the value of the object to zero.
+    fn clear(&self) -> Result<(), ClearError> {
+        let args = ::syscall::args::SyncClear { object: self.id };
+
+        // SAFETY: This syscall does not take any pointer.
+        syscall::decode(unsafe { syscall::invoke(&args) }).map(|_| ())
+    }
+}
+
+impl Drop for Object {
+    fn drop(&mut self) {
+        if self.owned {
+            let args = ::syscall::args::SyncDestroy { object: self.id };
+
+            // SAFETY: This syscall does not take any pointer.
+            _ = syscall::decode::<DestroyError>(unsafe { syscall::invoke(&args) });
+        }
+    }
+}
+
+/// A manual-reset event. Once set, all the waits on the event complete
+/// immediately until it is cleared.
+#[derive(Debug)]
+pub struct Event(Object);
+
+impl Event {
+    /// Creates a new event, initially set or not.
+    ///
+    /// # Errors
+    /// Returns a [`CreateError`] if the event cannot be created, most notably
+    /// if the task already owns too many synchronization objects.
+    pub fn new(set: bool) -> Result<Self, CreateError> {
+        Object::create(Kind::Event, usize::from(set)).map(Self)
+    }
+
+    /// Uses the event with the given identifier, created by another task.
+    /// The event is not destroyed when the returned value is dropped.
+    #[must_use]
+    pub const fn from_id(id: usize) -> Self {
+        Self(Object::shared(id))
+    }
+
+    /// Returns the identifier of the event, which can be given to other tasks
+    /// so that they can use it with [`Event::from_id`].
+    #[must_use]
+    pub const fn id(&self) -> usize {
+        self.0.id
+    }
+
+    /// Sets the event, waking up all the tasks waiting on it.
+    ///
+    /// # Errors
+    /// Returns [`SignalError::NotFound`] if the event was destroyed.
+    pub fn set(&self) -> Result<(), SignalError> {
+        self.0.signal(0)
+    }
+
+    /// Clears the event, so that the following waits block until it is set
+    /// again.
+    ///
+    /// # Errors
+    /// Returns [`ClearError::NotFound`] if the event was destroyed.
+    pub fn clear(&self) -> Result<(), ClearError> {
+        self.0.clear()
+    }
+
+    /// Blocks until the event is set.
+    ///
+    /// # Errors
+    /// Returns [`WaitError::NotFound`] if the event was destroyed, including
+    /// while waiting on it.
+    pub fn wait(&self) -> Result<(), WaitError> {
+        self.0.wait().map(|_| ())
+    }
+}
+
+/// A counting semaphore.
+#[derive(Debug)]
+pub struct Semaphore(Object);
+
+impl Semaphore {
+    /// Creates a new semaphore with the given initial count.
+    ///
+    /// # Errors
+    /// Returns a [`CreateError`] if the semaphore cannot be created, most
+    /// notably if the count is larger than [`MAX_VALUE`].
+    pub fn new(count: usize) -> Result<Self, CreateError> {
+        Object::create(Kind::Semaphore, count).map(Self)
+    }
+
+    /// Uses the semaphore with the given identifier, created by another task.
+    /// The semaphore is not destroyed when the returned value is dropped.
+    #[must_use]
+    pub const fn from_id(id: usize) -> Self {
+        Self(Object::shared(id))
+    }
+
+    /// Returns the identifier of the semaphore, which can be given to other
+    /// tasks so that they can use it with [`Semaphore::from_id`].
+    #[must_use]
+    pub const fn id(&self) -> usize {
+        self.0.id
+    }
+
+    /// Blocks until the count of the semaphore is not zero, then decrements
+    /// it.
+    ///
+    /// # Errors
+    /// Returns [`WaitError::NotFound`] if the semaphore was destroyed,
+    /// including while waiting on it.
+    pub fn acquire(&self) -> Result<(), WaitError> {
+        self.0.wait().map(|_| ())
+    }
+
+    /// Adds the given count to the semaphore, waking up the tasks waiting on
+    /// it.
+    ///
+    /// # Errors
+    /// Returns a [`SignalError`] if the semaphore was destroyed, or if its
+    /// count would exceed [`MAX_VALUE`].
+    pub fn release(&self, count: usize) -> Result<(), SignalError> {
+        self.0.signal(count)
+    }
+}
+
+/// A notification word, carrying a set of bits from the tasks signaling it to
+/// the task waiting on it.
+#[derive(Debug)]
+pub struct Notification(Object);
+
+impl Notification {
+    /// Creates a new notification, with no bit set.
+    ///
+    /// # Errors
+    /// Returns a [`CreateError`] if the notification cannot be created, most
+    /// notably if the task already owns too many synchronization objects.
+    pub fn new() -> Result<Self, CreateError> {
+        Object::create(Kind::Notification, 0).map(Self)
+    }
+
+    /// Uses the notification with the given identifier, created by another
+    /// task. The notification is not destroyed when the returned value is
+    /// dropped.
+    #[must_use]
+    pub const fn from_id(id: usize) -> Self {
+        Self(Object::shared(id))
+    }
+
+    /// Returns the identifier of the notification, which can be given to
+    /// other tasks so that they can use it with [`Notification::from_id`].
+    #[must_use]
+    pub const fn id(&self) -> usize {
+        self.0.id
+    }
+
+    /// Sets the given bits in the notification, waking up the tasks waiting
+    /// on it.
+    ///
+    /// # Errors
+    /// Returns a [`SignalError`] if the notification was destroyed, or if the
+    /// bits are larger than [`MAX_VALUE`].
+    pub fn notify(&self, bits: usize) -> Result<(), SignalError> {
+        self.0.signal(bits)
+    }
+
+    /// Blocks until a bit is set in the notification, then clears all the
+    /// bits and returns them.
+    ///
+    /// # Errors
+    /// Returns [`WaitError::NotFound`] if the notification was destroyed,
+    /// including while waiting on it.
+    pub fn wait(&self) -> Result<usize, WaitError> {
+        self.0.wait()
+    }
+
+    /// Clears all the bits of the notification without waiting.
+    ///
+    /// # Errors
+    /// Returns [`ClearError::NotFound`] if the notification was destroyed.
+    pub fn clear(&self) -> Result<(), ClearError> {
+        self.0.clear()
+    }
+}
+
+/// The state of a [`Mutex`] that is not locked.
+const UNLOCKED: u32 = 0;
+
+/// The state of a [`Mutex`] that is locked, with no task sleeping on it.
+const LOCKED: u32 = 1;
+
+/// The state of a [`Mutex`] that is locked, and on which tasks may be
+/// sleeping. Unlocking the mutex in this state wakes up one of them.
+const CONTENDED: u32 = 2;
+
+/// Sleeps on the given futex word while it holds the given value. If the
+/// futex syscall is not available, the task yields the CPU instead, so that
+/// the locks degrade into spin locks rather than failing.
+fn sleep(word: &AtomicU32, value: u32) {
+    match futex::wait(word, value) {
+        Ok(()) | Err(futex::WaitError::ValueMismatch) => {}
+        Err(_) => crate::task::yield_now(),
+    }
+}
+
+/// A mutual exclusion lock protecting a value. The lock is taken without
+/// entering the kernel when it is free, and tasks sleep on a futex when it is
+/// not. It is meant to be shared by the threads of a task, or by tasks sharing
+/// memory: since switching between green threads is cooperative, a green
+/// thread must never switch to another one while holding a lock that the other
+/// may take.
+#[derive(Debug, Default)]
+pub struct Mutex<T: ?Sized> {
+    state: AtomicU32,
+    value: UnsafeCell<T>,
+}
+
+/// SAFETY: The value is only accessed through a guard, and only one guard can
+/// exist at a time.
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+/// SAFETY: The mutex owns the value, so it can be sent if the value can.
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    /// Creates a new unlocked mutex protecting the given value.
+    #[must_use]
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes the mutex, returning the value it protects.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Locks the mutex, sleeping until it is available, and returns a guard
+    /// giving access to the value. The mutex is unlocked when the guard is
+    /// dropped.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        if self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Mark the mutex as contended before sleeping, so that the task
+            // holding it wakes us up when unlocking it.
+            while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+                sleep(&self.state, CONTENDED);
+            }
+        }
+        MutexGuard { mutex: self }
+    }
+
+    /// Locks the mutex if it is available, without sleeping. Returns `None`
+    /// if it is already locked.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        self.state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| MutexGuard { mutex: self })
+    }
+
+    /// Returns a mutable reference to the value. No locking is needed since
+    /// the mutable borrow guarantees that no guard exists.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Unlocks the mutex, waking up one of the tasks sleeping on it, if any.
+    fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            _ = futex::wake(&self.state, 1);
+        }
+    }
+}
+
+/// A guard giving access to the value protected by a locked [`Mutex`]. The
+/// mutex is unlocked when the guard is dropped.
+#[derive(Debug)]
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The guard proves that the mutex is locked by us.
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The guard proves that the mutex is locked by us.
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+/// A condition variable, allowing tasks to sleep until another task notifies
+/// them that a condition protected by a [`Mutex`] may have changed. Like with
+/// any condition variable, wake-ups may be spurious: the condition must be
+/// checked again after waiting.
+#[derive(Debug, Default)]
+pub struct Condvar {
+    /// A counter incremented by each notification. A waiting task sleeps
+    /// while it holds the value read before unlocking the mutex, so that a
+    /// notification sent in between is never missed.
+    sequence: AtomicU32,
+}
+
+impl Condvar {
+    /// Creates a new condition variable.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            sequence: AtomicU32::new(0),
+        }
+    }
+
+    /// Unlocks the mutex of the given guard and sleeps until the condition
+    /// variable is notified, then locks the mutex again and returns a new
+    /// guard.
+    pub fn wait<'a, T: ?Sized>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let mutex = guard.mutex;
+        let sequence = self.sequence.load(Ordering::Relaxed);
+        drop(guard);
+        sleep(&self.sequence, sequence);
+
+        // Other tasks may be sleeping on the mutex, so it must be taken in
+        // the contended state to make sure that they are woken up later.
+        while mutex.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+            sleep(&mutex.state, CONTENDED);
+        }
+        MutexGuard { mutex }
+    }
+
+    /// Wakes up one of the tasks waiting on the condition variable, if any.
+    pub fn notify_one(&self) {
+        self.sequence.fetch_add(1, Ordering::Relaxed);
+        _ = futex::wake(&self.sequence, 1);
+    }
+
+    /// Wakes up all the tasks waiting on the condition variable.
+    pub fn notify_all(&self) {
+        self.sequence.fetch_add(1, Ordering::Relaxed);
+        _ = futex::wake(&self.sequence, usize::MAX);
+    }
+}
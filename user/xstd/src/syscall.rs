@@ -1,16 +1,28 @@
-/// A trait that help to convert syscall return codes into specific error
-/// types for better error handling.
-pub trait SyscallCode {
-    /// Converts a syscall error code into a specific error type. If the
-    /// code does not match any known error, it should default to a generic
-    /// unknown error. This can happen if the syscall interface is extended
-    /// in the future and this library is not updated accordingly, or if
-    /// this function is used when the syscall did not actually fail.
-    fn from_syscall_code(code: isize) -> Self;
+use ::syscall::args::{SyscallArgs, SyscallResult};
+
+/// Invokes the syscall described by the given arguments, and returns the raw
+/// result of the syscall.
+///
+/// # Safety
+/// The caller must ensure that all pointers in the arguments are valid for
+/// the syscall, as the kernel may read from or write to them.
+pub unsafe fn invoke<A: SyscallArgs>(args: &A) -> SyscallResult {
+    // SAFETY: The caller guarantees that the arguments are valid.
+    unsafe { ::syscall::raw::invoke(args) }
+}
+
+/// Converts the raw result of a syscall into the value returned by the
+/// syscall, or into the error type of the syscall if it failed.
+///
+/// # Errors
+/// Returns the error of the syscall if it failed.
+pub fn decode<E: From<isize>>(result: SyscallResult) -> Result<usize, E> {
+    result.decode().map_err(E::from)
 }
 
-/// Checks if the given syscall return code indicates a failure. Code between
-/// -1 and -255 (inclusive) are considered error codes.
-pub fn failed(code: usize) -> bool {
-    (code as isize) < 0 && (code as isize) >= -255
+/// Invokes the syscall that does nothing. This is only useful to measure the
+/// cost of entering and leaving the kernel.
+pub fn nop() {
+    // SAFETY: This syscall does not take any pointer.
+    _ = unsafe { invoke(&::syscall::args::Nop {}) };
 }
@@ -0,0 +1,22 @@
+use crate::syscall;
+
+/// Retrieves the machine-readable description of the syscalls supported by the
+/// kernel and writes it into the given buffer, sorted by syscall number.
+/// Returns the number of entries written, which is at most the length of the
+/// buffer.
+///
+/// # Errors
+/// This function returns a [`TableError`] if the table could not be
+/// retrieved.
+pub fn syscalls(
+    buffer: &mut [::syscall::table::SyscallDescriptor],
+) -> Result<usize, ::syscall::table::TableError> {
+    let args = ::syscall::args::SyscallTable {
+        buffer: buffer.as_mut_ptr(),
+        capacity: buffer.len(),
+    };
+
+    // SAFETY: The buffer is valid for writes of `capacity` entries during
+    // the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
@@ -1,4 +1,23 @@
-/// Terminates the current process with the given exit code.
+use core::{mem::MaybeUninit, time::Duration};
+
+use crate::{syscall, vdso};
+
+pub use ::syscall::{
+    capability::Capabilities,
+    task::{
+        CURRENT_TASK, DebugError, KillError, Limit, Priority, Registers, SetEssentialError,
+        SetLimitError, SetPriorityError, Termination, TraceError, UNLIMITED,
+    },
+};
+
+/// The remaining quantum below which [`yield_if_needed`] will voluntarily
+/// yield the CPU. Yielding slightly before the end of the quantum allow the
+/// task to choose the point where it will be interrupted instead of being
+/// preempted at an inconvenient point.
+pub const YIELD_THRESHOLD: Duration = Duration::from_micros(500);
+
+/// Terminates the current process with the given exit code. The standard
+/// output is flushed before terminating, unless it is in use.
 ///
 /// # Important
 /// This function will invoke a system call to terminate the process and
@@ -7,13 +26,9 @@
 /// properly released. In general, it is advisable to avoid using this function
 /// unless absolutely necessary.
 pub fn exit(code: i32) -> ! {
-    unsafe {
-        core::arch::asm!("ecall",
-          in("a7") 1,
-          in("a0") code,
-          options(noreturn)
-        );
-    }
+    crate::io::flush_on_exit();
+    // SAFETY: This syscall does not take any pointer and never returns.
+    unsafe { ::syscall::raw::invoke_noreturn(&::syscall::args::TaskExit { code }) }
 }
 
 /// Yields the CPU to the scheduler, allowing other tasks to run. Yielding can
@@ -21,10 +36,322 @@ pub fn exit(code: i32) -> ! {
 /// since your task is voluntarily yielding, it may gain priority in the
 /// scheduler or be rescheduled more quickly when it becomes runnable again.
 pub fn yield_now() {
-    unsafe {
-        core::arch::asm!("ecall",
-          in("a7") 2,
-          options(nomem, nostack)
-        );
+    // SAFETY: This syscall does not take any pointer.
+    _ = unsafe { syscall::invoke(&::syscall::args::TaskYield {}) };
+}
+
+/// Spawns a new task from the given ELF image, with the given arguments and
+/// environment variables (in the `KEY=VALUE` form), and returns the
+/// identifier of the new task. The new task inherits all the capabilities of
+/// the current task.
+///
+/// # Errors
+/// Returns a [`SpawnError`] describing the error if the syscall fails, most
+/// notably if the image is not a valid executable or if the current task is
+/// not allowed to spawn tasks.
+pub fn spawn(
+    image: &[u8],
+    args: &[&str],
+    vars: &[&str],
+) -> Result<usize, ::syscall::task::SpawnError> {
+    spawn_with_capabilities(image, args, vars, Capabilities::ALL)
+}
+
+/// Same as [`spawn`], but the new task only inherits the capabilities of the
+/// current task that are in the given set. This allows to run an untrusted
+/// program with only the rights it needs.
+///
+/// # Errors
+/// Returns a [`SpawnError`] describing the error if the syscall fails, most
+/// notably if the image is not a valid executable or if the current task is
+/// not allowed to spawn tasks.
+pub fn spawn_with_capabilities(
+    image: &[u8],
+    args: &[&str],
+    vars: &[&str],
+    capabilities: Capabilities,
+) -> Result<usize, ::syscall::task::SpawnError> {
+    let env = crate::env::encode(args, vars);
+    let args = ::syscall::args::TaskSpawn {
+        image: image.as_ptr(),
+        image_len: image.len(),
+        env: env.as_ptr(),
+        env_len: env.len(),
+        capabilities,
+    };
+
+    // SAFETY: Both buffers are valid for reads during the whole syscall.
+    syscall::decode::<::syscall::task::SpawnError>(unsafe { syscall::invoke(&args) })
+}
+
+/// Gives up the given capabilities, and returns the capabilities that the
+/// current task still holds. All the threads of the task lose them, and they
+/// can never be regained. This is always allowed, whatever the capabilities
+/// of the task.
+pub fn drop_capabilities(capabilities: Capabilities) -> Capabilities {
+    let args = ::syscall::args::TaskDropCaps { capabilities };
+
+    // SAFETY: This syscall does not take any pointer.
+    let remaining = syscall::decode::<isize>(unsafe { syscall::invoke(&args) }).unwrap_or(0);
+    Capabilities::from_bits(u32::try_from(remaining).unwrap_or(0))
+}
+
+/// Waits until the task with the given identifier terminates, and returns
+/// how it terminated along with whether it terminated uncleanly (see
+/// [`::syscall::task::TerminationInfo::unclean`]). The termination information
+/// of a task can only be collected once, and is discarded by the kernel if
+/// nobody collects it after some time.
+///
+/// # Errors
+/// Returns a [`WaitError`] describing the error if the syscall fails, most
+/// notably if the task does not exist or if its termination was already
+/// collected by another task.
+pub fn wait(id: usize) -> Result<(::syscall::task::Termination, bool), ::syscall::task::WaitError> {
+    let mut info = MaybeUninit::<::syscall::task::TerminationInfo>::uninit();
+    let args = ::syscall::args::TaskWait {
+        task: id,
+        info: info.as_mut_ptr(),
+    };
+
+    // SAFETY: The termination information buffer is valid for writes during
+    // the whole syscall.
+    syscall::decode::<::syscall::task::WaitError>(unsafe { syscall::invoke(&args) })?;
+
+    // SAFETY: The syscall succeeded, so the termination information should
+    // be properly initialized by the kernel.
+    let info = unsafe { info.assume_init() };
+    info.decode()
+        .map(|termination| (termination, info.unclean()))
+        .ok_or(::syscall::task::WaitError::Unknown)
+}
+
+/// Returns the resource usage of the task with the given identifier.
+///
+/// # Errors
+/// Returns a [`UsageError`] describing the error if the syscall fails, most
+/// notably if the task does not exist.
+pub fn usage(id: usize) -> Result<::syscall::task::Usage, ::syscall::task::UsageError> {
+    let mut usage = MaybeUninit::<::syscall::task::Usage>::uninit();
+    let args = ::syscall::args::TaskUsage {
+        task: id,
+        usage: usage.as_mut_ptr(),
+    };
+
+    // SAFETY: The usage buffer is valid for writes during the whole syscall.
+    syscall::decode::<::syscall::task::UsageError>(unsafe { syscall::invoke(&args) })?;
+
+    // SAFETY: The syscall succeeded, so the usage should be properly
+    // initialized by the kernel.
+    Ok(unsafe { usage.assume_init() })
+}
+
+/// Kills the task with the given identifier. The task stops as soon as
+/// possible, even if it is waiting in a syscall, and tasks waiting for it
+/// learn that it was killed by the current task.
+///
+/// # Errors
+/// Returns a [`KillError`] describing the error if the syscall fails, most
+/// notably if the task does not exist or holds capabilities that the current
+/// task does not hold.
+pub fn kill(id: usize) -> Result<(), KillError> {
+    let args = ::syscall::args::TaskKill { task: id };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode::<KillError>(unsafe { syscall::invoke(&args) })?;
+    Ok(())
+}
+
+/// Changes a resource limit of the task with the given identifier, or of the
+/// current task if the identifier is [`CURRENT_TASK`], and returns the
+/// previous value of the limit. The value can be [`UNLIMITED`] to remove the
+/// limit. Tasks spawned afterwards by the task inherit its limits.
+///
+/// # Errors
+/// Returns a [`SetLimitError`] describing the error if the syscall fails,
+/// most notably if the task does not exist or if the value is not allowed for
+/// this limit.
+pub fn set_limit(id: usize, limit: Limit, value: usize) -> Result<usize, SetLimitError> {
+    let args = ::syscall::args::TaskSetLimit {
+        task: id,
+        limit: limit as usize,
+        value,
+    };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode::<SetLimitError>(unsafe { syscall::invoke(&args) })
+}
+
+/// Marks the task with the given identifier, or the current task if the
+/// identifier is [`CURRENT_TASK`], as essential or not, and returns whether it
+/// was essential before. The kernel never kills an essential task when it runs
+/// out of memory, so only the tasks without which the system cannot work
+/// should be marked. Tasks are not essential when spawned.
+///
+/// # Errors
+/// Returns a [`SetEssentialError`] describing the error if the syscall fails,
+/// most notably if the task does not exist or holds capabilities that the
+/// current task does not hold.
+pub fn set_essential(id: usize, essential: bool) -> Result<bool, SetEssentialError> {
+    let args = ::syscall::args::TaskSetEssential {
+        task: id,
+        essential: usize::from(essential),
+    };
+
+    // SAFETY: This syscall does not take any pointer.
+    let previous = syscall::decode::<SetEssentialError>(unsafe { syscall::invoke(&args) })?;
+    Ok(previous != 0)
+}
+
+/// Enables or disables the tracing of the syscalls invoked by the task with
+/// the given identifier, or by the current task if the identifier is
+/// [`CURRENT_TASK`], and returns whether they were traced before. The entry
+/// and exit of each syscall of a traced task are recorded in the kernel trace
+/// buffers along with all its arguments and its duration, and can be read
+/// with [`crate::trace::read`]. Tasks spawned by a traced task are traced as
+/// well.
+///
+/// # Errors
+/// Returns a [`TraceError`] describing the error if the syscall fails, most
+/// notably if the task does not exist or holds capabilities that the current
+/// task does not hold.
+pub fn set_traced(id: usize, traced: bool) -> Result<bool, TraceError> {
+    let args = ::syscall::args::TaskTrace {
+        task: id,
+        enabled: usize::from(traced),
+    };
+
+    // SAFETY: This syscall does not take any pointer.
+    let previous = syscall::decode::<TraceError>(unsafe { syscall::invoke(&args) })?;
+    Ok(previous != 0)
+}
+
+/// Suspends the task with the given identifier, or the task of the thread with
+/// the given identifier. Each thread of the task stops the next time it would
+/// return to user mode, which only happens once its current syscall completes
+/// if it is waiting inside one, and its registers can then be accessed with
+/// [`registers`] and [`set_registers`].
+///
+/// # Errors
+/// Returns a [`DebugError`] describing the error if the syscall fails, most
+/// notably if the task does not exist, is the current task or holds
+/// capabilities that the current task does not hold.
+pub fn suspend(id: usize) -> Result<(), DebugError> {
+    let args = ::syscall::args::TaskSuspend { task: id };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode::<DebugError>(unsafe { syscall::invoke(&args) })?;
+    Ok(())
+}
+
+/// Resumes all the threads of the task with the given identifier, or of the
+/// task of the thread with the given identifier, which continue with the
+/// registers modified with [`set_registers`].
+///
+/// # Errors
+/// Returns a [`DebugError`] describing the error if the syscall fails, most
+/// notably if the task does not exist or cannot be debugged by the current
+/// task.
+pub fn resume(id: usize) -> Result<(), DebugError> {
+    let args = ::syscall::args::TaskResume { task: id, step: 0 };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode::<DebugError>(unsafe { syscall::invoke(&args) })?;
+    Ok(())
+}
+
+/// Makes the stopped thread with the given identifier execute a single
+/// instruction, after which it stops again. The other threads of its task stay
+/// stopped.
+///
+/// # Errors
+/// Returns a [`DebugError`] describing the error if the syscall fails, most
+/// notably if the thread is not stopped or if single-stepping is not supported
+/// on this architecture.
+pub fn step(thread: usize) -> Result<(), DebugError> {
+    let args = ::syscall::args::TaskResume {
+        task: thread,
+        step: 1,
+    };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode::<DebugError>(unsafe { syscall::invoke(&args) })?;
+    Ok(())
+}
+
+/// Returns the registers of the stopped thread with the given identifier.
+///
+/// # Errors
+/// Returns a [`DebugError`] describing the error if the syscall fails, most
+/// notably if the thread is not stopped yet.
+pub fn registers(thread: usize) -> Result<Registers, DebugError> {
+    let mut registers = MaybeUninit::<Registers>::uninit();
+    let args = ::syscall::args::TaskGetRegs {
+        thread,
+        registers: registers.as_mut_ptr(),
+    };
+
+    // SAFETY: The registers buffer is valid for writes during the whole
+    // syscall.
+    syscall::decode::<DebugError>(unsafe { syscall::invoke(&args) })?;
+
+    // SAFETY: The syscall succeeded, so the registers should be properly
+    // initialized by the kernel.
+    Ok(unsafe { registers.assume_init() })
+}
+
+/// Replaces the registers of the stopped thread with the given identifier.
+/// The thread continues with these registers once resumed.
+///
+/// # Errors
+/// Returns a [`DebugError`] describing the error if the syscall fails, most
+/// notably if the thread is not stopped yet.
+pub fn set_registers(thread: usize, registers: &Registers) -> Result<(), DebugError> {
+    let args = ::syscall::args::TaskSetRegs {
+        thread,
+        registers: core::ptr::from_ref(registers),
+    };
+
+    // SAFETY: The registers are valid for reads during the whole syscall.
+    syscall::decode::<DebugError>(unsafe { syscall::invoke(&args) })?;
+    Ok(())
+}
+
+/// Changes the scheduling class of the task with the given identifier, or of
+/// the current task if the identifier is [`CURRENT_TASK`]. The new class is
+/// taken into account the next time the task waits for something.
+///
+/// # Errors
+/// Returns a [`SetPriorityError`] describing the error if the syscall fails,
+/// most notably if the task does not exist.
+pub fn set_priority(id: usize, priority: Priority) -> Result<(), SetPriorityError> {
+    let args = ::syscall::args::TaskSetPriority {
+        task: id,
+        priority: priority as usize,
+    };
+
+    // SAFETY: This syscall does not take any pointer.
+    syscall::decode::<SetPriorityError>(unsafe { syscall::invoke(&args) })?;
+    Ok(())
+}
+
+/// Returns the remaining time before the kernel may preempt the current task
+/// to run another one. This does not invoke any syscall and is cheap enough to
+/// be called in hot loops. If the quantum has already expired, this returns a
+/// zero duration.
+#[must_use]
+pub fn remaining_quantum() -> Duration {
+    Duration::from_nanos(vdso::quantum_deadline().saturating_sub(vdso::now()))
+}
+
+/// Yields the CPU if the remaining quantum of the task is below
+/// [`YIELD_THRESHOLD`]. This is useful for long CPU-bound loops that want to
+/// yield at a convenient point rather than being preempted by the kernel.
+/// Returns `true` if the task has yielded, and `false` otherwise.
+pub fn yield_if_needed() -> bool {
+    if remaining_quantum() < YIELD_THRESHOLD {
+        yield_now();
+        true
+    } else {
+        false
     }
 }
@@ -0,0 +1,150 @@
+use crate::{sync::Mutex, syscall, tls, vdso};
+use alloc::{boxed::Box, sync::Arc, vec};
+use core::mem::ManuallyDrop;
+
+pub use ::syscall::thread::{CreateError, JoinError};
+
+/// The size of the stack of the threads created with [`spawn`], in bytes.
+/// The stack is allocated on the heap and is not protected by a guard page,
+/// so a thread must not overflow it.
+pub const STACK_SIZE: usize = 64 * 1024;
+
+/// What a thread created with [`spawn`] receives in its first argument
+/// register.
+struct Start {
+    /// The closure run by the thread, which stores the result of the closure
+    /// given by the caller for [`JoinHandle::join`].
+    main: Box<dyn FnOnce() + Send>,
+
+    /// The thread pointer of the thread, or null if the executable has no
+    /// thread-local variables.
+    tls: *mut u8,
+}
+
+/// A handle to a thread created with [`spawn`], allowing to wait for its
+/// termination and to retrieve its result. If the handle is dropped without
+/// being joined, the thread keeps running and its stack and its thread-local
+/// storage are never freed.
+#[derive(Debug)]
+pub struct JoinHandle<T> {
+    id: usize,
+    stack: *mut [u8],
+    tls: ManuallyDrop<Option<tls::Block>>,
+    result: Arc<Mutex<Option<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Returns the identifier of the thread.
+    #[must_use]
+    pub const fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Waits until the thread terminates, frees its stack and its thread-local
+    /// storage, and returns the value returned by its closure.
+    ///
+    /// # Errors
+    /// Returns a [`JoinError`] if the syscall fails, which should not happen
+    /// for a thread created with [`spawn`], or [`JoinError::Unknown`] if the
+    /// thread exited with [`exit`] before its closure returned.
+    pub fn join(self) -> Result<T, JoinError> {
+        let mut code = 0;
+        let args = ::syscall::args::ThreadJoin {
+            thread: self.id,
+            code: &raw mut code,
+        };
+
+        // SAFETY: The exit code is valid for writes during the whole syscall.
+        syscall::decode::<JoinError>(unsafe { syscall::invoke(&args) })?;
+
+        // SAFETY: The thread has terminated, so its stack is no longer used
+        // and can be freed. It was leaked from a box by `spawn`.
+        drop(unsafe { Box::from_raw(self.stack) });
+        drop(ManuallyDrop::into_inner(self.tls));
+        self.result.lock().take().ok_or(JoinError::Unknown)
+    }
+}
+
+/// Spawns a new thread in the current task running the given closure, and
+/// returns a handle to join it. The thread shares the address space of the
+/// task and runs on a stack of [`STACK_SIZE`] bytes allocated on the heap,
+/// with its own copy of the thread-local variables of the task. It terminates
+/// when the closure returns, and the task terminates when its last thread
+/// terminates.
+///
+/// # Errors
+/// Returns a [`CreateError`] if the syscall fails, most notably if the task
+/// already has too many threads or is not allowed to create threads, or
+/// [`CreateError::OutOfMemory`] if the thread-local storage of the thread
+/// cannot be allocated.
+pub fn spawn<F, T>(f: F) -> Result<JoinHandle<T>, CreateError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let result = Arc::new(Mutex::new(None));
+    let slot = Arc::clone(&result);
+    let tls = tls::Block::new();
+    if tls.is_none() && vdso::tls_template().mem_size != 0 {
+        return Err(CreateError::OutOfMemory);
+    }
+    let start = Box::into_raw(Box::new(Start {
+        main: Box::new(move || *slot.lock() = Some(f())),
+        tls: tls
+            .as_ref()
+            .map_or(core::ptr::null_mut(), tls::Block::thread_pointer),
+    }));
+
+    let stack = Box::into_raw(vec![0u8; STACK_SIZE].into_boxed_slice());
+    let args = ::syscall::args::ThreadCreate {
+        entry: entry as usize,
+        stack: (stack.cast::<u8>() as usize + STACK_SIZE) & !0xF,
+        argument: start as usize,
+    };
+
+    // SAFETY: This syscall does not take any pointer: the stack, the closure
+    // and the thread-local storage are only used by the new thread.
+    match syscall::decode::<CreateError>(unsafe { syscall::invoke(&args) }) {
+        Ok(id) => Ok(JoinHandle {
+            id,
+            stack,
+            tls: ManuallyDrop::new(tls),
+            result,
+        }),
+        Err(error) => {
+            // SAFETY: The thread was not created, so the closure and the stack
+            // are not used by anyone else.
+            unsafe {
+                drop(Box::from_raw(start));
+                drop(Box::from_raw(stack));
+            }
+            Err(error)
+        }
+    }
+}
+
+/// Terminates the current thread with the given exit code, without running
+/// the destructors of the variables in scope. The other threads of the task
+/// keep running, and the task terminates with this exit code if this was its
+/// last thread. Use [`crate::task::exit`] to terminate the whole task.
+pub fn exit(code: i32) -> ! {
+    // SAFETY: This syscall does not take any pointer and never returns.
+    unsafe { ::syscall::raw::invoke_noreturn(&::syscall::args::ThreadExit { code }) }
+}
+
+/// The entry point of the threads created with [`spawn`], which receives its
+/// [`Start`] structure in its first argument register and the address of its
+/// shared page in its second argument register.
+extern "C" fn entry(start: usize, shared: usize) -> ! {
+    // SAFETY: The argument is the structure leaked by `spawn`, which is only
+    // given to this thread.
+    let start = unsafe { Box::from_raw(start as *mut Start) };
+    if !start.tls.is_null() {
+        // SAFETY: The block is owned by the join handle of the thread, which
+        // only frees it once the thread has terminated.
+        unsafe { tls::set_thread_pointer(start.tls) };
+        vdso::set_thread_page(shared);
+    }
+    (start.main)();
+    exit(0)
+}
@@ -0,0 +1,91 @@
+use crate::syscall;
+use core::time::Duration;
+
+pub use ::syscall::timer::{CreateError, Delivery, DestroyError, Expiration, MIN_INTERVAL};
+
+/// A periodic timer of the kernel, destroyed when dropped. Its expirations
+/// are delivered either as IPC messages to the current task, which can be
+/// recognized with [`expiration`], or by signaling a synchronization object
+/// (see [`crate::sync`]).
+#[derive(Debug)]
+pub struct Timer {
+    id: usize,
+}
+
+impl Timer {
+    /// Creates a timer expiring every `interval`, whose expirations are sent
+    /// to the current task as IPC messages with the given operation.
+    ///
+    /// # Errors
+    /// Returns a [`CreateError`] if the timer cannot be created, most notably
+    /// if the interval is shorter than [`MIN_INTERVAL`] or if the task already
+    /// owns too many timers.
+    pub fn with_messages(interval: Duration, operation: usize) -> Result<Self, CreateError> {
+        Self::create(interval, Delivery::Message, operation, 0)
+    }
+
+    /// Creates a timer expiring every `interval`, whose expirations signal
+    /// the synchronization object with the given identifier with the given
+    /// value.
+    ///
+    /// # Errors
+    /// Returns a [`CreateError`] if the timer cannot be created, most notably
+    /// if the object does not exist, if the interval is shorter than
+    /// [`MIN_INTERVAL`] or if the task already owns too many timers.
+    pub fn with_signal(
+        interval: Duration,
+        object: usize,
+        value: usize,
+    ) -> Result<Self, CreateError> {
+        Self::create(interval, Delivery::Signal, object, value)
+    }
+
+    /// Returns the identifier of the timer, as found in the [`Expiration`] of
+    /// its messages.
+    #[must_use]
+    pub const fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Creates a timer with the given delivery.
+    fn create(
+        interval: Duration,
+        delivery: Delivery,
+        target: usize,
+        value: usize,
+    ) -> Result<Self, CreateError> {
+        let args = ::syscall::args::TimerCreate {
+            interval: usize::try_from(interval.as_nanos()).unwrap_or(usize::MAX),
+            delivery: delivery as usize,
+            target,
+            value,
+        };
+
+        // SAFETY: This syscall does not take any pointer.
+        let id = syscall::decode::<CreateError>(unsafe { syscall::invoke(&args) })?;
+        Ok(Self { id })
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let args = ::syscall::args::TimerDestroy { timer: self.id };
+
+        // SAFETY: This syscall does not take any pointer.
+        _ = syscall::decode::<DestroyError>(unsafe { syscall::invoke(&args) });
+    }
+}
+
+/// Returns the expiration carried by the given message, or `None` if the
+/// message was not sent by the kernel to deliver the expiration of a timer.
+/// Such messages must not be replied to.
+#[must_use]
+pub fn expiration(message: &::syscall::ipc::Message) -> Option<Expiration> {
+    use ::syscall::ipc::Payload;
+
+    if message.sender != ::syscall::ipc::KERNEL_SENDER {
+        return None;
+    }
+    let len = message.payload_len.min(message.payload.len());
+    Expiration::from_payload(&message.payload[..len])
+}
@@ -0,0 +1,107 @@
+use crate::vdso;
+use alloc::alloc::Layout;
+use core::ptr::NonNull;
+
+/// The thread-local storage (TLS) block of a thread, allocated on the heap and
+/// initialized from the template of the executable (see
+/// [`::syscall::vdso::TlsTemplate`]). The block is freed when dropped, so it
+/// must outlive the thread whose thread pointer points to it.
+#[derive(Debug)]
+pub struct Block {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+/// The size of the thread control block that precedes the TLS block, to which
+/// the thread pointer points. There is none on riscv64, where the thread
+/// pointer points to the TLS block itself.
+#[cfg(target_arch = "riscv64")]
+const TCB_SIZE: usize = 0;
+#[cfg(target_arch = "aarch64")]
+const TCB_SIZE: usize = 16;
+
+/// SAFETY: The block is only a piece of memory that is not shared with anyone
+/// else until it is given to a thread.
+unsafe impl Send for Block {}
+
+impl Block {
+    /// Allocates a new block and initializes it from the template of the
+    /// executable. Returns `None` if the executable has no thread-local
+    /// variables, or if the template is invalid or cannot be allocated.
+    #[must_use]
+    pub fn new() -> Option<Self> {
+        let template = vdso::tls_template();
+        let size = usize::try_from(template.mem_size).ok()?;
+        let align = usize::try_from(template.align).ok()?;
+        let image = usize::try_from(template.image).ok()?;
+        let file_size = usize::try_from(template.file_size).ok()?;
+        if size == 0 || file_size > size {
+            return None;
+        }
+
+        // The TLS block starts after the thread control block, at the first
+        // offset aligned on the alignment of the block.
+        let offset = TCB_SIZE.checked_next_multiple_of(align)?;
+        let layout = Layout::from_size_align(offset.checked_add(size)?, align).ok()?;
+        // SAFETY: The layout has a non-zero size.
+        let ptr = NonNull::new(unsafe { alloc::alloc::alloc_zeroed(layout) })?;
+
+        // SAFETY: The kernel verified that the image is part of the loaded
+        // executable, and the block is large enough to hold it.
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                core::ptr::with_exposed_provenance::<u8>(image),
+                ptr.as_ptr().add(offset),
+                file_size,
+            );
+        }
+        Some(Self { ptr, layout })
+    }
+
+    /// Returns the value that the thread pointer of a thread must hold to use
+    /// this block.
+    #[must_use]
+    pub fn thread_pointer(&self) -> *mut u8 {
+        self.ptr.as_ptr()
+    }
+}
+
+impl Drop for Block {
+    fn drop(&mut self) {
+        // SAFETY: The block was allocated with this layout in `Block::new`.
+        unsafe { alloc::alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+/// Makes the given pointer the thread pointer of the current thread, through
+/// which thread-local variables are accessed.
+///
+/// # Safety
+/// The pointer must point to a [`Block`] that outlives the use of thread-local
+/// variables by the current thread, and no reference to a thread-local
+/// variable of the previous block may be used afterwards.
+pub unsafe fn set_thread_pointer(ptr: *mut u8) {
+    // SAFETY: The caller guarantees that the block is valid. The kernel
+    // preserves the thread pointer across traps.
+    unsafe {
+        #[cfg(target_arch = "riscv64")]
+        core::arch::asm!("mv tp, {}", in(reg) ptr, options(nostack));
+        #[cfg(target_arch = "aarch64")]
+        core::arch::asm!("msr tpidr_el0, {}", in(reg) ptr, options(nostack));
+    }
+}
+
+/// Sets up the thread-local storage of the main thread. This is called by the
+/// entry point generated by [`crate::main`] before anything else, and should
+/// not be called anywhere else. The block of the main thread is never freed.
+///
+/// # Safety
+/// This must be called before any thread-local variable is accessed.
+#[doc(hidden)]
+pub unsafe fn init() {
+    if let Some(block) = Block::new() {
+        // SAFETY: The block is leaked, so it lives as long as the task, and
+        // no thread-local variable was accessed yet.
+        unsafe { set_thread_pointer(core::mem::ManuallyDrop::new(block).thread_pointer()) };
+    }
+}
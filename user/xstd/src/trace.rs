@@ -0,0 +1,23 @@
+use crate::syscall;
+
+pub use ::syscall::trace::{Event, EventKind, ReadError};
+
+/// Removes the oldest events from the kernel trace buffers and writes them
+/// into the given buffer. Returns the number of events written, which is at
+/// most the length of the buffer and is zero once the trace buffers are
+/// empty. The events of different cores are not ordered with respect to each
+/// other, and must be sorted by their timestamp to rebuild the global order.
+///
+/// # Errors
+/// Returns a [`ReadError`] if the syscall fails, most notably if tracing is
+/// disabled in the kernel and no task was traced with
+/// [`crate::task::set_traced`].
+pub fn read(buffer: &mut [Event]) -> Result<usize, ReadError> {
+    let args = ::syscall::args::TraceRead {
+        buffer: buffer.as_mut_ptr(),
+        capacity: buffer.len(),
+    };
+
+    // SAFETY: The buffer is valid for writes during the whole syscall.
+    syscall::decode(unsafe { syscall::invoke(&args) })
+}
@@ -0,0 +1,95 @@
+use ::syscall::vdso::{SHARED_PAGE_ADDRESS, SharedPage, TlsTemplate};
+use core::cell::Cell;
+
+/// The address of the page shared between the kernel and the current thread.
+/// The first thread of a task always uses the page at [`SHARED_PAGE_ADDRESS`],
+/// while the other threads record the address given by the kernel when they
+/// start with [`set_thread_page`].
+#[thread_local]
+static THREAD_PAGE: Cell<usize> = Cell::new(SHARED_PAGE_ADDRESS);
+
+/// Returns a pointer to the page shared between the kernel and the current
+/// thread. The page is always mapped by the kernel before the thread starts,
+/// and stays mapped until the thread exits. This must only be used once the
+/// thread-local storage of the thread is set up.
+fn shared_page() -> *mut SharedPage {
+    core::ptr::with_exposed_provenance_mut(THREAD_PAGE.get())
+}
+
+/// Returns a pointer to the shared page of the first thread of the task, which
+/// holds the fields describing the whole task and stays mapped as long as the
+/// task is alive. Contrary to [`shared_page`], this can be used before the
+/// thread-local storage of the thread is set up.
+fn task_page() -> *mut SharedPage {
+    core::ptr::with_exposed_provenance_mut(SHARED_PAGE_ADDRESS)
+}
+
+/// Records the address of the shared page of the current thread, given by the
+/// kernel in the second argument register of the thread when it starts. The
+/// thread-local storage of the thread must be set up.
+pub(crate) fn set_thread_page(address: usize) {
+    THREAD_PAGE.set(address);
+}
+
+/// Returns the time, in nanoseconds since boot, at which the current quantum
+/// of the thread expires.
+#[must_use]
+pub fn quantum_deadline() -> u64 {
+    // SAFETY: The shared page is always mapped and readable. We use a
+    // volatile read because the kernel may update the page at any time.
+    unsafe { (&raw const (*shared_page()).quantum_deadline).read_volatile() }
+}
+
+/// Publishes the bounds of the stack that the thread is about to use. This
+/// is only a hint used by the kernel to report stack overflows of green
+/// threads, and passing `0..0` indicates that the thread uses the stack
+/// provided by the kernel.
+pub fn set_stack_extent(low: usize, high: usize) {
+    // SAFETY: The shared page is always mapped and writable by the thread.
+    // We use volatile writes because the kernel may read the page at any
+    // time.
+    unsafe {
+        (&raw mut (*shared_page()).stack_low).write_volatile(low as u64);
+        (&raw mut (*shared_page()).stack_high).write_volatile(high as u64);
+    }
+}
+
+/// Returns the thread-local storage template of the executable, published by
+/// the kernel when the executable was loaded.
+#[must_use]
+pub fn tls_template() -> TlsTemplate {
+    // SAFETY: The shared page is always mapped and readable. We use a
+    // volatile read because the page is shared with the kernel.
+    unsafe { (&raw const (*task_page()).tls).read_volatile() }
+}
+
+/// Returns the address from which the heap and the other anonymous mappings
+/// of the task should be placed, chosen by the kernel when the task was
+/// created.
+#[must_use]
+pub fn mmap_base() -> usize {
+    // SAFETY: The shared page is always mapped and readable. We use a
+    // volatile read because the page is shared with the kernel.
+    let base = unsafe { (&raw const (*task_page()).mmap_base).read_volatile() };
+    base as usize
+}
+
+/// Returns the current time in nanoseconds since boot. This reads the
+/// `time` counter (or the virtual counter on aarch64) directly and does not
+/// require any syscall.
+#[must_use]
+pub fn now() -> u64 {
+    let ticks: u64;
+    // SAFETY: Reading the counter has no side effects, and is allowed in user
+    // mode by the kernel.
+    unsafe {
+        #[cfg(target_arch = "riscv64")]
+        core::arch::asm!("rdtime {}", out(reg) ticks, options(nomem, nostack));
+        #[cfg(target_arch = "aarch64")]
+        core::arch::asm!("mrs {}, cntvct_el0", out(reg) ticks, options(nomem, nostack));
+    }
+
+    // SAFETY: The shared page is always mapped and readable, and the tick
+    // duration never changes during the lifetime of the thread.
+    ticks * unsafe { (&raw const (*task_page()).tick_duration).read_volatile() }
+}